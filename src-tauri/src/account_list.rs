@@ -0,0 +1,567 @@
+//! 账号列表的分页与增量加载
+//!
+//! 账号上了几百个之后，每次刷新都把整个库序列化过 IPC，webview 明显卡顿：
+//! - [`list_capped`]：`get_accounts` 用，最多 [`GET_ACCOUNTS_CAP`] 个，超出标 `truncated`
+//! - [`page`]：按排序 + 过滤取一页，带过滤后的总数
+//! - [`filtered`]：按标签等条件取全部，不分页
+//! - [`changed_since`]：游标之后改过的账号加删除记录；游标取自上一次响应的 `cursor`
+//! - [`detail`]：单个账号加派生字段，刷新一张卡片用，不用再拉整个列表
+//!
+//! 修改时间由 `AccountStore::stamp_changes` 维护，这里每次取数前先跑一遍，
+//! 再用它留下的时间点当新游标。
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::account::{Account, AccountKind, AccountStore};
+use crate::codex_compat::CompatibilityVerdict;
+
+/// `get_accounts` 一次最多返回的账号数
+pub const GET_ACCOUNTS_CAP: usize = 500;
+
+/// `page` 的单页上限
+pub const MAX_PAGE_SIZE: usize = 200;
+
+/// 排序方式；同值按 id 排，保证翻页时顺序稳定
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountSort {
+    /// 置顶在前，再按手动顺序，最后新建在前（`list_accounts` 的顺序）
+    #[default]
+    Manual,
+    /// 新建的在前
+    CreatedDesc,
+    CreatedAsc,
+    /// 名字，不区分大小写
+    Name,
+    /// 最近使用的在前，没用过的排最后
+    LastUsed,
+    /// 最近修改的在前
+    Updated,
+}
+
+/// 过滤条件，各项都是可选的，同时给出时取交集
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct AccountFilter {
+    /// 名字或备注包含该文本（不区分大小写）
+    pub search: Option<String>,
+    /// 按 `effective_kind` 过滤
+    pub kind: Option<AccountKind>,
+    /// Relay 分类；老账号没填的按 `aggregator` 算
+    pub relay_category: Option<String>,
+    /// 带其中任一标签（不区分大小写）；空表不过滤
+    pub tags: Option<Vec<String>>,
+}
+
+impl AccountFilter {
+    fn matches(&self, account: &Account) -> bool {
+        if let Some(search) = self.search.as_deref().map(str::trim) {
+            let needle = search.to_lowercase();
+            let hit = account.name.to_lowercase().contains(&needle)
+                || account
+                    .notes
+                    .as_deref()
+                    .is_some_and(|n| n.to_lowercase().contains(&needle));
+            if !needle.is_empty() && !hit {
+                return false;
+            }
+        }
+        if let Some(kind) = self.kind {
+            if account.effective_kind() != kind {
+                return false;
+            }
+        }
+        if let Some(tags) = &self.tags {
+            if !account.has_any_tag(tags) {
+                return false;
+            }
+        }
+        if let Some(category) = self.relay_category.as_deref() {
+            if !account.is_relay()
+                || account.relay_category.as_deref().unwrap_or("aggregator") != category
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+/// `get_accounts` 的返回
+#[derive(Serialize, Clone, Debug)]
+pub struct AccountList {
+    pub accounts: Vec<Account>,
+    /// 库里的账号总数
+    pub total: usize,
+    /// 超过上限被截断，剩下的用分页取
+    pub truncated: bool,
+    pub cursor: Option<DateTime<Utc>>,
+    /// 名字已按演示模式打码
+    pub masked: bool,
+    /// 各账号与本机 Codex 版本的兼容性，按 id（由命令层填，见 `codex_compat`）
+    pub compatibility: HashMap<String, CompatibilityVerdict>,
+}
+
+/// `get_accounts_page` 的返回
+#[derive(Serialize, Clone, Debug)]
+pub struct AccountPage {
+    pub items: Vec<Account>,
+    /// 过滤后的总数
+    pub total: usize,
+    pub offset: usize,
+    /// 实际生效的页大小（夹到 1..=[`MAX_PAGE_SIZE`]）
+    pub limit: usize,
+    pub cursor: Option<DateTime<Utc>>,
+    pub masked: bool,
+    pub compatibility: HashMap<String, CompatibilityVerdict>,
+}
+
+/// `get_accounts_changed_since` 的返回
+#[derive(Serialize, Clone, Debug)]
+pub struct AccountDelta {
+    /// 游标之后新增或修改过的账号；`full_reload` 时是全部账号
+    pub changed: Vec<Account>,
+    /// 游标之后删除的账号 id
+    pub deleted: Vec<String>,
+    pub cursor: Option<DateTime<Utc>>,
+    /// 游标太旧（删除记录已被裁掉）或没给游标：`changed` 是完整列表，前端整体替换
+    pub full_reload: bool,
+    pub masked: bool,
+}
+
+/// `get_account` 的返回：账号本身加几个前端常用的派生字段
+#[derive(Serialize, Clone, Debug)]
+pub struct AccountDetail {
+    #[serde(flatten)]
+    pub account: Account,
+    /// auth.json 的 `last_refresh`
+    pub last_refresh: Option<DateTime<Utc>>,
+    /// 额度缓存距今多少秒；没缓存为 None
+    pub quota_age_secs: Option<i64>,
+    pub masked: bool,
+}
+
+/// `get_account` 的错误，按 `code` 区分：账号已经没了（别处删掉）还是暂时取不到
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum GetAccountError {
+    /// `in_trash`：在回收站里，可以恢复
+    NotFound {
+        id: String,
+        in_trash: bool,
+    },
+    Unavailable {
+        message: String,
+    },
+}
+
+impl std::fmt::Display for GetAccountError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GetAccountError::NotFound { id, in_trash: true } => {
+                write!(f, "账号已删除（在回收站里）: {}", id)
+            }
+            GetAccountError::NotFound { id, .. } => write!(f, "账号不存在: {}", id),
+            GetAccountError::Unavailable { message } => write!(f, "读取账号失败: {}", message),
+        }
+    }
+}
+
+/// 单个账号加派生字段
+pub fn detail(
+    store: &AccountStore,
+    id: &str,
+    now: DateTime<Utc>,
+) -> Result<AccountDetail, GetAccountError> {
+    let account = store
+        .accounts
+        .get(id)
+        .ok_or_else(|| GetAccountError::NotFound {
+            id: id.to_string(),
+            in_trash: store.trash.contains_key(id),
+        })?;
+    Ok(AccountDetail {
+        last_refresh: AccountStore::extract_last_refresh(&account.auth_json),
+        quota_age_secs: account
+            .cached_quota
+            .as_ref()
+            .map(|q| (now - q.updated_at).num_seconds().max(0)),
+        account: account.clone(),
+        masked: false,
+    })
+}
+
+fn sorted<'a>(
+    store: &'a AccountStore,
+    sort: AccountSort,
+    filter: &AccountFilter,
+) -> Vec<&'a Account> {
+    let mut accounts: Vec<&Account> = store
+        .accounts
+        .values()
+        .filter(|a| filter.matches(a))
+        .collect();
+    accounts.sort_by(|a, b| {
+        let primary = match sort {
+            AccountSort::Manual => Account::display_order(a, b),
+            AccountSort::CreatedDesc => b.created_at.cmp(&a.created_at),
+            AccountSort::CreatedAsc => a.created_at.cmp(&b.created_at),
+            AccountSort::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            // Some 在 None 前面
+            AccountSort::LastUsed => b.last_used.cmp(&a.last_used),
+            AccountSort::Updated => b.modified_at().cmp(&a.modified_at()),
+        };
+        primary.then_with(|| a.id.cmp(&b.id))
+    });
+    accounts
+}
+
+/// 按 `list_accounts` 的顺序，最多 `cap` 个
+pub fn list_capped(store: &mut AccountStore, cap: usize) -> AccountList {
+    store.stamp_changes();
+    let all = sorted(store, AccountSort::Manual, &AccountFilter::default());
+    AccountList {
+        total: all.len(),
+        truncated: all.len() > cap,
+        accounts: all.into_iter().take(cap).cloned().collect(),
+        cursor: store.changes_cursor(),
+        masked: false,
+        compatibility: HashMap::new(),
+    }
+}
+
+/// 取一页；`offset` 超出总数时返回空页
+pub fn page(
+    store: &mut AccountStore,
+    offset: usize,
+    limit: usize,
+    sort: AccountSort,
+    filter: &AccountFilter,
+) -> AccountPage {
+    store.stamp_changes();
+    let limit = limit.clamp(1, MAX_PAGE_SIZE);
+    let all = sorted(store, sort, filter);
+    AccountPage {
+        total: all.len(),
+        items: all.into_iter().skip(offset).take(limit).cloned().collect(),
+        offset,
+        limit,
+        cursor: store.changes_cursor(),
+        masked: false,
+        compatibility: HashMap::new(),
+    }
+}
+
+/// 按排序 + 过滤取全部（不分页），`list_accounts_filtered` 用
+pub fn filtered(store: &AccountStore, sort: AccountSort, filter: &AccountFilter) -> Vec<Account> {
+    sorted(store, sort, filter).into_iter().cloned().collect()
+}
+
+/// 游标之后的改动；`since` 为 None 或早于删除记录的裁剪点时退化成全量
+pub fn changed_since(store: &mut AccountStore, since: Option<DateTime<Utc>>) -> AccountDelta {
+    store.stamp_changes();
+    let since = since.filter(|at| {
+        store
+            .tombstones_pruned_at
+            .is_none_or(|pruned| pruned <= *at)
+    });
+    let all = sorted(store, AccountSort::Manual, &AccountFilter::default());
+    let (changed, deleted) = match since {
+        Some(since) => (
+            all.into_iter()
+                .filter(|a| a.modified_at() > since)
+                .cloned()
+                .collect(),
+            store
+                .deleted_accounts
+                .iter()
+                .filter(|t| t.deleted_at > since && !store.accounts.contains_key(&t.id))
+                .map(|t| t.id.clone())
+                .collect(),
+        ),
+        None => (all.into_iter().cloned().collect(), Vec::new()),
+    };
+    AccountDelta {
+        changed,
+        deleted,
+        cursor: store.changes_cursor(),
+        full_reload: since.is_none(),
+        masked: false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn store_with(n: usize) -> (AccountStore, Vec<String>) {
+        let mut store = AccountStore::default();
+        let created = Utc::now() - chrono::Duration::days(1);
+        let ids = (0..n)
+            .map(|i| {
+                let id = store
+                    .add_account(
+                        format!("user{:02}@example.com", i),
+                        json!({ "tokens": { "access_token": format!("sk-{}", i) } }),
+                        None,
+                    )
+                    .id;
+                // 前两个同一时刻创建：翻页顺序要靠 id 兜底
+                let offset = chrono::Duration::minutes(i.max(1) as i64);
+                store.accounts.get_mut(&id).unwrap().created_at = created + offset;
+                id
+            })
+            .collect();
+        (store, ids)
+    }
+
+    fn ids(accounts: &[Account]) -> Vec<String> {
+        accounts.iter().map(|a| a.id.clone()).collect()
+    }
+
+    #[test]
+    fn pages_cover_every_account_exactly_once() {
+        let (mut store, _) = store_with(7);
+        let filter = AccountFilter::default();
+        let mut seen = Vec::new();
+        for offset in [0, 3, 6] {
+            let page = page(&mut store, offset, 3, AccountSort::CreatedDesc, &filter);
+            assert_eq!(page.total, 7);
+            assert_eq!(page.items.len(), if offset == 6 { 1 } else { 3 });
+            seen.extend(ids(&page.items));
+        }
+        let full = list_capped(&mut store, GET_ACCOUNTS_CAP);
+        assert_eq!(seen, ids(&full.accounts), "分页拼起来等于完整列表");
+
+        let beyond = page(&mut store, 9, 3, AccountSort::CreatedDesc, &filter);
+        assert!(beyond.items.is_empty());
+        assert_eq!(beyond.total, 7);
+
+        assert_eq!(page(&mut store, 0, 0, AccountSort::Name, &filter).limit, 1);
+        assert_eq!(
+            page(&mut store, 0, 10_000, AccountSort::Name, &filter).limit,
+            MAX_PAGE_SIZE
+        );
+    }
+
+    #[test]
+    fn filter_and_sort_apply_before_paging() {
+        let (mut store, ids) = store_with(5);
+        store
+            .update_account(&ids[3], None, Some("公司 team".into()))
+            .unwrap();
+        store.accounts.get_mut(&ids[1]).unwrap().last_used = Some(Utc::now());
+        let relay = store
+            .add_relay_account(
+                "MiMo".into(),
+                "https://relay.example".into(),
+                "sk-relay".into(),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                Some("coding_plan".into()),
+            )
+            .id;
+
+        let search = AccountFilter {
+            search: Some("TEAM".into()),
+            ..Default::default()
+        };
+        let page1 = page(&mut store, 0, 10, AccountSort::CreatedDesc, &search);
+        assert_eq!(
+            (page1.total, page1.items[0].id.as_str()),
+            (1, ids[3].as_str())
+        );
+
+        let coding = AccountFilter {
+            relay_category: Some("coding_plan".into()),
+            ..Default::default()
+        };
+        let page2 = page(&mut store, 0, 10, AccountSort::CreatedDesc, &coding);
+        assert_eq!(self::ids(&page2.items), vec![relay.clone()]);
+
+        let keys = AccountFilter {
+            kind: Some(AccountKind::OpenaiKey),
+            ..Default::default()
+        };
+        assert_eq!(page(&mut store, 0, 10, AccountSort::Name, &keys).total, 5);
+
+        let by_use = page(
+            &mut store,
+            0,
+            1,
+            AccountSort::LastUsed,
+            &AccountFilter::default(),
+        );
+        assert_eq!(self::ids(&by_use.items), vec![ids[1].clone()]);
+        let by_name = page(
+            &mut store,
+            0,
+            1,
+            AccountSort::Name,
+            &AccountFilter::default(),
+        );
+        assert_eq!(by_name.items[0].name, "MiMo");
+    }
+
+    #[test]
+    fn tag_filter_matches_any_tag_and_sorts_by_last_used() {
+        let (mut store, ids) = store_with(4);
+        store
+            .set_account_tags(&ids[0], vec!["工作".into()])
+            .unwrap();
+        store
+            .set_account_tags(&ids[1], vec!["个人".into(), "Team".into()])
+            .unwrap();
+        store
+            .set_account_tags(&ids[2], vec!["team".into()])
+            .unwrap();
+        store.accounts.get_mut(&ids[2]).unwrap().last_used = Some(Utc::now());
+        store.accounts.get_mut(&ids[1]).unwrap().last_used =
+            Some(Utc::now() - chrono::Duration::hours(1));
+
+        let team = AccountFilter {
+            tags: Some(vec!["TEAM".into()]),
+            ..Default::default()
+        };
+        let created = filtered(&store, AccountSort::CreatedDesc, &team);
+        assert_eq!(self::ids(&created), vec![ids[2].clone(), ids[1].clone()]);
+
+        let either = AccountFilter {
+            tags: Some(vec!["工作".into(), "个人".into()]),
+            ..Default::default()
+        };
+        let by_use = filtered(&store, AccountSort::LastUsed, &either);
+        assert_eq!(self::ids(&by_use), vec![ids[1].clone(), ids[0].clone()]);
+        store.accounts.get_mut(&ids[0]).unwrap().last_used = Some(Utc::now());
+        let by_use = filtered(&store, AccountSort::LastUsed, &either);
+        assert_eq!(self::ids(&by_use), vec![ids[0].clone(), ids[1].clone()]);
+
+        let empty = AccountFilter {
+            tags: Some(Vec::new()),
+            ..Default::default()
+        };
+        assert_eq!(filtered(&store, AccountSort::CreatedDesc, &empty).len(), 4);
+    }
+
+    #[test]
+    fn get_accounts_is_capped_with_marker() {
+        let (mut store, _) = store_with(7);
+        let capped = list_capped(&mut store, 5);
+        assert_eq!((capped.accounts.len(), capped.total), (5, 7));
+        assert!(capped.truncated);
+
+        let full = list_capped(&mut store, 7);
+        assert_eq!(full.accounts.len(), 7);
+        assert!(!full.truncated);
+        assert!(full.cursor.is_some());
+    }
+
+    #[test]
+    fn delta_returns_changes_and_deletions_after_cursor() {
+        let (mut store, ids) = store_with(4);
+        let first = changed_since(&mut store, None);
+        assert!(first.full_reload);
+        assert_eq!(first.changed.len(), 4);
+        let cursor = first.cursor;
+
+        // 方法改、直接改字段、删除、新增
+        store
+            .update_account(&ids[0], Some("renamed".into()), None)
+            .unwrap();
+        store.accounts.get_mut(&ids[1]).unwrap().is_banned = true;
+        store.delete_account(&ids[2]).unwrap();
+        let added = store
+            .add_account("new@example.com".into(), json!({}), None)
+            .id;
+
+        let delta = changed_since(&mut store, cursor);
+        assert!(!delta.full_reload);
+        let mut changed = self::ids(&delta.changed);
+        changed.sort();
+        let mut expected = vec![ids[0].clone(), ids[1].clone(), added];
+        expected.sort();
+        assert_eq!(changed, expected);
+        assert_eq!(delta.deleted, vec![ids[2].clone()]);
+
+        // 没有新改动：空增量，游标前进
+        let idle = changed_since(&mut store, delta.cursor);
+        assert!(idle.changed.is_empty() && idle.deleted.is_empty());
+        assert!(idle.cursor > cursor);
+    }
+
+    #[test]
+    fn stale_cursor_past_pruned_tombstones_forces_full_reload() {
+        let (mut store, _) = store_with(2);
+        let cursor = changed_since(&mut store, None).cursor;
+        for i in 0..crate::account::MAX_TOMBSTONES + 3 {
+            let id = store.add_account(format!("tmp{}", i), json!({}), None).id;
+            store.delete_account(&id).unwrap();
+        }
+        let delta = changed_since(&mut store, cursor);
+        assert!(delta.full_reload, "被裁掉的删除记录没法补发");
+        assert_eq!(delta.changed.len(), 2);
+        assert!(delta.deleted.is_empty());
+
+        // 裁剪点之后的游标照常增量
+        let fresh = changed_since(&mut store, delta.cursor);
+        assert!(!fresh.full_reload);
+    }
+
+    #[test]
+    fn detail_adds_derived_fields_and_distinguishes_missing_from_trashed() {
+        let (mut store, ids) = store_with(2);
+        let now = Utc::now();
+        let account = store.accounts.get_mut(&ids[0]).unwrap();
+        account.auth_json["last_refresh"] = json!("2026-01-02T03:04:05Z");
+        account.cached_quota = Some(
+            serde_json::from_value(json!({
+                "five_hour_left": 50.0,
+                "five_hour_reset": "",
+                "weekly_left": 80.0,
+                "weekly_reset": "",
+                "plan_type": "plus",
+                "updated_at": (now - chrono::Duration::seconds(90)).to_rfc3339(),
+            }))
+            .unwrap(),
+        );
+
+        let found = detail(&store, &ids[0], now).unwrap();
+        assert_eq!(found.account.id, ids[0]);
+        assert_eq!(found.quota_age_secs, Some(90));
+        assert_eq!(
+            found.last_refresh.map(|t| t.to_rfc3339()).as_deref(),
+            Some("2026-01-02T03:04:05+00:00")
+        );
+        // 派生字段和账号字段平铺在同一层
+        let json = serde_json::to_value(&found).unwrap();
+        assert_eq!(json["id"], ids[0].as_str());
+        assert_eq!(json["quota_age_secs"], 90);
+
+        let untouched = detail(&store, &ids[1], now).unwrap();
+        assert_eq!(untouched.quota_age_secs, None);
+        assert_eq!(untouched.account.email, None);
+
+        store.delete_account(&ids[1]).unwrap();
+        assert_eq!(
+            detail(&store, &ids[1], now).unwrap_err(),
+            GetAccountError::NotFound {
+                id: ids[1].clone(),
+                in_trash: true
+            }
+        );
+        let missing = detail(&store, "missing", now).unwrap_err();
+        assert_eq!(
+            serde_json::to_value(&missing).unwrap(),
+            json!({ "code": "not_found", "id": "missing", "in_trash": false })
+        );
+    }
+}
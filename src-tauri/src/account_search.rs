@@ -0,0 +1,172 @@
+//! 账号搜索
+//!
+//! 账号多了之后前端要按名字 / 邮箱 / 备注搜。邮箱得解 id_token 的 JWT 才拿得到，
+//! 每敲一个字都把所有账号解一遍不划算，所以解出来的邮箱按账号缓存在 [`EmailCache`]，
+//! 以 id_token 原文的哈希校验，token 换了才重新解。
+//!
+//! [`search`] 不区分大小写，结果分档排序，同档内按列表顺序（`Account::display_order`）：
+//! 1. 邮箱完全相等
+//! 2. 名字以查询开头
+//! 3. 名字 / 邮箱 / 备注包含查询
+//!
+//! 空查询返回全部账号，和 `get_accounts` 一致。
+
+use std::collections::HashMap;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::Mutex;
+
+use crate::account::{Account, AccountStore};
+
+/// 按账号 id 缓存解出来的邮箱（小写），值里带 id_token 的哈希用来判断是否过期
+#[derive(Default)]
+pub struct EmailCache {
+    entries: Mutex<HashMap<String, (u64, Option<String>)>>,
+}
+
+fn id_token_hash(account: &Account) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    account
+        .auth_json
+        .pointer("/tokens/id_token")
+        .and_then(|v| v.as_str())
+        .hash(&mut hasher);
+    hasher.finish()
+}
+
+impl EmailCache {
+    /// 账号的邮箱（小写）：优先用账号上记的，没记的老账号解 id_token，id_token 没变时直接用缓存
+    pub fn email_of(&self, account: &Account) -> Option<String> {
+        if let Some(email) = &account.email {
+            return Some(email.to_lowercase());
+        }
+        let hash = id_token_hash(account);
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((cached_hash, email)) = entries.get(&account.id) {
+            if *cached_hash == hash {
+                return email.clone();
+            }
+        }
+        let email = AccountStore::extract_email(&account.auth_json).map(|e| e.to_lowercase());
+        entries.insert(account.id.clone(), (hash, email.clone()));
+        email
+    }
+
+    /// 丢掉已经不在库里的账号
+    pub fn retain(&self, store: &AccountStore) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|id, _| store.accounts.contains_key(id));
+    }
+}
+
+/// 按查询搜账号，排序见模块说明
+pub fn search(store: &AccountStore, cache: &EmailCache, query: &str) -> Vec<Account> {
+    let query = query.trim().to_lowercase();
+    let accounts = store.list_accounts();
+    if query.is_empty() {
+        return accounts.into_iter().cloned().collect();
+    }
+    cache.retain(store);
+
+    let mut ranked: Vec<(u8, &Account)> = accounts
+        .into_iter()
+        .filter_map(|account| {
+            let email = cache.email_of(account);
+            let name = account.name.to_lowercase();
+            let rank = if email.as_deref() == Some(query.as_str()) {
+                0
+            } else if name.starts_with(&query) {
+                1
+            } else if name.contains(&query)
+                || email.is_some_and(|e| e.contains(&query))
+                || account
+                    .notes
+                    .as_deref()
+                    .is_some_and(|n| n.to_lowercase().contains(&query))
+            {
+                2
+            } else {
+                return None;
+            };
+            Some((rank, account))
+        })
+        .collect();
+    // 稳定排序：同档保持列表顺序
+    ranked.sort_by_key(|(rank, _)| *rank);
+    ranked.into_iter().map(|(_, a)| a.clone()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::Engine;
+    use serde_json::json;
+
+    fn auth_with_email(email: &str) -> serde_json::Value {
+        let encode = |s: String| base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(s);
+        let id_token = format!(
+            "{}.{}.sig",
+            encode(r#"{"alg":"none"}"#.to_string()),
+            encode(format!(r#"{{"email":"{}"}}"#, email))
+        );
+        json!({ "tokens": { "id_token": id_token } })
+    }
+
+    fn names(accounts: &[Account]) -> Vec<&str> {
+        accounts.iter().map(|a| a.name.as_str()).collect()
+    }
+
+    #[test]
+    fn exact_email_then_name_prefix_then_substring() {
+        let mut store = AccountStore::default();
+        let ids = [
+            ("备用号", "Work@Company.com", None),
+            ("work-team", "team@company.com", None),
+            ("个人", "me@home.com", Some("和 work 共用信用卡")),
+            ("other", "x@y.com", None),
+        ]
+        .map(|(name, email, notes)| {
+            store
+                .add_account(
+                    name.to_string(),
+                    auth_with_email(email),
+                    notes.map(str::to_string),
+                )
+                .id
+        });
+        // 固定列表顺序，同档按它排
+        store.reorder_accounts(&ids).unwrap();
+        let cache = EmailCache::default();
+
+        let hits = search(&store, &cache, " work@company.COM ");
+        assert_eq!(names(&hits), vec!["备用号"]);
+
+        let hits = search(&store, &cache, "WORK");
+        assert_eq!(names(&hits), vec!["work-team", "备用号", "个人"]);
+
+        let hits = search(&store, &cache, "company");
+        assert_eq!(names(&hits), vec!["备用号", "work-team"]);
+
+        assert!(search(&store, &cache, "nobody").is_empty());
+        assert_eq!(search(&store, &cache, "  ").len(), 4);
+    }
+
+    #[test]
+    fn cached_email_follows_id_token_changes() {
+        let mut store = AccountStore::default();
+        let id = store
+            .add_account("a".to_string(), auth_with_email("old@example.com"), None)
+            .id;
+        // 没记邮箱的老账号才走缓存
+        store.accounts.get_mut(&id).unwrap().email = None;
+        let cache = EmailCache::default();
+        assert_eq!(search(&store, &cache, "old@").len(), 1);
+
+        store.accounts.get_mut(&id).unwrap().auth_json = auth_with_email("new@example.com");
+        assert!(search(&store, &cache, "old@").is_empty());
+        assert_eq!(search(&store, &cache, "new@").len(), 1);
+
+        store.accounts.clear();
+        search(&store, &cache, "x");
+        assert!(cache.entries.lock().unwrap().is_empty());
+    }
+}
@@ -0,0 +1,210 @@
+//! 按 id / 邮箱 / 名字定位账号
+//!
+//! 自动化脚本想直接说"切到 work@company.com"，不想先列账号找 UUID。
+//! 所有接受选择器的入口（`switch_account` 的 `selector`、`resolve_account`）都走 [`resolve`]，
+//! 匹配顺序固定：
+//! 1. 账号 id 完全相等（名字恰好长得像某个 id 时也以 id 为准）
+//! 2. 邮箱（从 token claims 里取），不区分大小写
+//! 3. 名字完全相等
+//!
+//! 同一级命中多个账号时报 [`ResolveError::Ambiguous`] 并列出候选，不会猜。
+
+use serde::Serialize;
+
+use crate::account::{Account, AccountStore};
+
+/// 有歧义时错误文本的前缀（前端据此提示用户改用 id）
+pub const AMBIGUOUS_PREFIX: &str = "ACCOUNT_AMBIGUOUS:";
+
+/// 命中方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchedBy {
+    Id,
+    Email,
+    Name,
+}
+
+/// 解析结果 / 歧义候选
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ResolvedAccount {
+    pub id: String,
+    pub name: String,
+    pub email: Option<String>,
+    pub matched_by: MatchedBy,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResolveError {
+    Empty,
+    NotFound(String),
+    Ambiguous {
+        selector: String,
+        candidates: Vec<ResolvedAccount>,
+    },
+}
+
+impl std::fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ResolveError::Empty => write!(f, "账号选择器为空"),
+            ResolveError::NotFound(selector) => write!(f, "找不到账号: {}", selector),
+            ResolveError::Ambiguous {
+                selector,
+                candidates,
+            } => {
+                let list = candidates
+                    .iter()
+                    .map(|c| format!("{} ({})", c.name, c.id))
+                    .collect::<Vec<_>>()
+                    .join("、");
+                write!(
+                    f,
+                    "{}{} 匹配到 {} 个账号，请改用账号 id：{}",
+                    AMBIGUOUS_PREFIX,
+                    selector,
+                    candidates.len(),
+                    list
+                )
+            }
+        }
+    }
+}
+
+fn candidate(account: &Account, matched_by: MatchedBy) -> ResolvedAccount {
+    ResolvedAccount {
+        id: account.id.clone(),
+        name: account.name.clone(),
+        email: AccountStore::extract_email(&account.auth_json),
+        matched_by,
+    }
+}
+
+/// 按选择器找账号（匹配顺序见模块文档）
+pub fn resolve(store: &AccountStore, selector: &str) -> Result<ResolvedAccount, ResolveError> {
+    let selector = selector.trim();
+    if selector.is_empty() {
+        return Err(ResolveError::Empty);
+    }
+    if let Some(account) = store.accounts.get(selector) {
+        return Ok(candidate(account, MatchedBy::Id));
+    }
+
+    let mut accounts: Vec<&Account> = store.accounts.values().collect();
+    accounts.sort_by(|a, b| a.created_at.cmp(&b.created_at).then(a.id.cmp(&b.id)));
+
+    let by_email: Vec<ResolvedAccount> = accounts
+        .iter()
+        .map(|a| candidate(a, MatchedBy::Email))
+        .filter(|c| {
+            c.email
+                .as_deref()
+                .is_some_and(|email| email.eq_ignore_ascii_case(selector))
+        })
+        .collect();
+    let by_name: Vec<ResolvedAccount> = accounts
+        .iter()
+        .filter(|a| a.name.trim() == selector)
+        .map(|a| candidate(a, MatchedBy::Name))
+        .collect();
+
+    for mut matches in [by_email, by_name] {
+        match matches.len() {
+            0 => continue,
+            1 => return Ok(matches.remove(0)),
+            _ => {
+                return Err(ResolveError::Ambiguous {
+                    selector: selector.to_string(),
+                    candidates: matches,
+                })
+            }
+        }
+    }
+    Err(ResolveError::NotFound(selector.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::Engine;
+    use serde_json::json;
+
+    fn auth_with_email(email: &str) -> serde_json::Value {
+        let encode = |s: &str| base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(s);
+        let id_token = format!(
+            "{}.{}.sig",
+            encode(r#"{"alg":"none"}"#),
+            encode(&format!(r#"{{"email":"{}"}}"#, email))
+        );
+        json!({ "tokens": { "id_token": id_token, "access_token": "at" } })
+    }
+
+    fn add(store: &mut AccountStore, name: &str, email: &str) -> String {
+        store
+            .add_account(name.to_string(), auth_with_email(email), None)
+            .id
+    }
+
+    #[test]
+    fn resolves_by_id_email_and_name() {
+        let mut store = AccountStore::default();
+        let work = add(&mut store, "工作号", "Work@Company.com");
+        let home = add(&mut store, "home", "me@example.com");
+
+        let by_id = resolve(&store, &format!(" {} ", work)).unwrap();
+        assert_eq!((by_id.id, by_id.matched_by), (work.clone(), MatchedBy::Id));
+
+        let by_email = resolve(&store, "work@company.COM").unwrap();
+        assert_eq!(by_email.id, work);
+        assert_eq!(by_email.matched_by, MatchedBy::Email);
+        assert_eq!(by_email.email.as_deref(), Some("Work@Company.com"));
+
+        let by_name = resolve(&store, "home").unwrap();
+        assert_eq!((by_name.id, by_name.matched_by), (home, MatchedBy::Name));
+        assert!(
+            matches!(resolve(&store, "HOME"), Err(ResolveError::NotFound(_))),
+            "名字区分大小写"
+        );
+        assert_eq!(resolve(&store, "  "), Err(ResolveError::Empty));
+    }
+
+    #[test]
+    fn shared_names_and_emails_are_ambiguous() {
+        let mut store = AccountStore::default();
+        let a = add(&mut store, "team", "a@example.com");
+        let b = add(&mut store, "team", "b@example.com");
+        // 新建会自动加序号；老配置里仍可能重名
+        store.accounts.get_mut(&b).unwrap().name = "team".into();
+        add(&mut store, "ws-1", "shared@example.com");
+        add(&mut store, "ws-2", "shared@example.com");
+
+        match resolve(&store, "team") {
+            Err(err @ ResolveError::Ambiguous { .. }) => {
+                let text = err.to_string();
+                assert!(text.starts_with(AMBIGUOUS_PREFIX));
+                assert!(text.contains(&a) && text.contains(&b));
+            }
+            other => panic!("expected ambiguity, got {:?}", other),
+        }
+        assert!(matches!(
+            resolve(&store, "shared@example.com"),
+            Err(ResolveError::Ambiguous { candidates, .. }) if candidates.len() == 2
+        ));
+    }
+
+    #[test]
+    fn id_wins_over_a_name_that_looks_like_an_id() {
+        let mut store = AccountStore::default();
+        let real = add(&mut store, "real", "real@example.com");
+        // 另一个号的名字恰好是 real 的 id
+        let impostor = add(&mut store, &real, "impostor@example.com");
+
+        let resolved = resolve(&store, &real).unwrap();
+        assert_eq!(resolved.id, real);
+        assert_eq!(resolved.matched_by, MatchedBy::Id);
+
+        // 邮箱也优先于名字
+        store.accounts.get_mut(&impostor).unwrap().name = "real@example.com".into();
+        assert_eq!(resolve(&store, "real@example.com").unwrap().id, real);
+    }
+}
@@ -0,0 +1,173 @@
+//! 前台活动记录：让后台调度器给用户操作让路
+//!
+//! 用户在界面上手动刷新 / 切号时，scheduler 如果同时跑一轮同步，两边会交错 save、
+//! 重复发事件，偶尔还会把同一个 token 刷两次。这里记两件事：
+//! - 最近一次用户操作的时间：距今不到 `scheduler_quiet_secs` 时 scheduler 推迟本轮，
+//!   过一会儿重试，而不是整轮跳过等下一个间隔
+//! - 用户最近操作过的账号（带 TTL）：scheduler 下一轮跳过这些账号
+//!
+//! 时间从 [`Clock`] 取，测试里换成假时钟。
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 时间源
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+/// 真实单调时钟
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    last_activity: Option<Instant>,
+    touched: HashMap<String, Instant>,
+    deferred: bool,
+}
+
+pub struct ForegroundActivity<C: Clock = SystemClock> {
+    clock: C,
+    inner: Mutex<Inner>,
+}
+
+impl Default for ForegroundActivity {
+    fn default() -> Self {
+        Self::with_clock(SystemClock)
+    }
+}
+
+impl<C: Clock> ForegroundActivity<C> {
+    pub fn with_clock(clock: C) -> Self {
+        Self {
+            clock,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    /// 记一次用户操作
+    pub fn bump(&self) {
+        let now = self.clock.now();
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.last_activity = Some(now);
+        }
+    }
+
+    /// 记一次针对某个账号的用户操作（同时算一次 [`Self::bump`]）
+    pub fn touch_account(&self, account_id: &str) {
+        let now = self.clock.now();
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.last_activity = Some(now);
+            inner.touched.insert(account_id.to_string(), now);
+        }
+    }
+
+    /// scheduler 每轮开始前调用：`quiet` 内有过用户操作就返回还需等待的时长（并标记为推迟中），
+    /// 否则返回 None。`quiet` 为 0 表示不让路
+    pub fn defer_for(&self, quiet: Duration) -> Option<Duration> {
+        let now = self.clock.now();
+        let Ok(mut inner) = self.inner.lock() else {
+            return None;
+        };
+        let remaining = inner
+            .last_activity
+            .map(|at| now.saturating_duration_since(at))
+            .filter(|elapsed| *elapsed < quiet)
+            .map(|elapsed| quiet - elapsed);
+        inner.deferred = remaining.is_some();
+        remaining
+    }
+
+    /// 最近一次 [`Self::defer_for`] 是否决定推迟
+    pub fn is_deferred(&self) -> bool {
+        self.inner.lock().map(|i| i.deferred).unwrap_or(false)
+    }
+
+    /// 该账号在 `ttl` 内被用户操作过；顺手清掉已过期的记录
+    pub fn recently_touched(&self, account_id: &str, ttl: Duration) -> bool {
+        let now = self.clock.now();
+        let Ok(mut inner) = self.inner.lock() else {
+            return false;
+        };
+        inner
+            .touched
+            .retain(|_, at| now.saturating_duration_since(*at) < ttl);
+        inner.touched.contains_key(account_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[derive(Clone)]
+    struct FakeClock(Arc<Mutex<Instant>>);
+
+    impl FakeClock {
+        fn new() -> Self {
+            Self(Arc::new(Mutex::new(Instant::now())))
+        }
+
+        fn advance(&self, secs: u64) {
+            *self.0.lock().unwrap() += Duration::from_secs(secs);
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Instant {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    const QUIET: Duration = Duration::from_secs(30);
+
+    #[test]
+    fn defers_until_quiet_period_passes() {
+        let clock = FakeClock::new();
+        let activity = ForegroundActivity::with_clock(clock.clone());
+        assert_eq!(activity.defer_for(QUIET), None, "没有操作过不推迟");
+        assert!(!activity.is_deferred());
+
+        activity.bump();
+        clock.advance(10);
+        assert_eq!(activity.defer_for(QUIET), Some(Duration::from_secs(20)));
+        assert!(activity.is_deferred());
+
+        clock.advance(20);
+        assert_eq!(activity.defer_for(QUIET), None);
+        assert!(!activity.is_deferred());
+
+        activity.bump();
+        assert_eq!(activity.defer_for(Duration::ZERO), None, "quiet=0 不让路");
+    }
+
+    #[test]
+    fn touched_accounts_expire_after_ttl() {
+        let clock = FakeClock::new();
+        let activity = ForegroundActivity::with_clock(clock.clone());
+        let ttl = Duration::from_secs(600);
+
+        activity.touch_account("a");
+        clock.advance(300);
+        activity.touch_account("b");
+        assert!(activity.recently_touched("a", ttl));
+        assert!(activity.recently_touched("b", ttl));
+        assert!(!activity.recently_touched("c", ttl));
+        assert!(activity.defer_for(QUIET).is_some(), "touch 也算一次操作");
+
+        clock.advance(300);
+        assert!(!activity.recently_touched("a", ttl));
+        assert!(activity.recently_touched("b", ttl));
+
+        clock.advance(300);
+        assert!(!activity.recently_touched("b", ttl));
+    }
+}
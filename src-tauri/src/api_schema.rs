@@ -0,0 +1,190 @@
+//! 对外负载的 schema 版本与 JSON Schema
+//!
+//! 除了自家前端，托盘、状态服务、外部脚本也在消费账号、用量、设置和事件的序列化结果，
+//! 这些形状已经是公开契约。版本规则：
+//! - 只新增字段 / 枚举值：[`SCHEMA_MINOR`] +1
+//! - 删字段、改类型、必有字段变可选等破坏性修改：[`SCHEMA_MAJOR`] +1，
+//!   旧形状保留一个周期，挂在 `_v1` 结尾的命令别名上
+//!
+//! [`schemas`] 由各 DTO 上 derive 的 `JsonSchema` 生成，经 `get_schemas` 下发。
+//! `schemas/api.json` 是提交进仓库的快照：测试里比对当前 schema 和快照，
+//! 破坏性修改且没升 major 直接失败，其余改动提示升 minor 后重新生成
+//! （`UPDATE_SCHEMA_SNAPSHOT=1 cargo test api_schema`）。
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use schemars::schema::RootSchema;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::account::{Account, AppSettings};
+use crate::events::AppEvent;
+use crate::switch_progress::SwitchProgressEvent;
+use crate::usage::UsageDisplay;
+
+/// 破坏性修改时 +1
+pub const SCHEMA_MAJOR: u32 = 1;
+/// 只新增时 +1；升 major 时归零
+pub const SCHEMA_MINOR: u32 = 18;
+
+/// `get_api_version` 的返回
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ApiVersion {
+    /// `major.minor`
+    pub schema_version: String,
+    pub app_version: String,
+}
+
+pub fn schema_version() -> String {
+    format!("{}.{}", SCHEMA_MAJOR, SCHEMA_MINOR)
+}
+
+pub fn api_version() -> ApiVersion {
+    ApiVersion {
+        schema_version: schema_version(),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+    }
+}
+
+/// 公开 DTO 的 schema，键为稳定的名字
+pub fn schemas() -> BTreeMap<&'static str, RootSchema> {
+    BTreeMap::from([
+        ("account", schemars::schema_for!(Account)),
+        ("usage_display", schemars::schema_for!(UsageDisplay)),
+        ("settings", schemars::schema_for!(AppSettings)),
+        ("app_event", schemars::schema_for!(AppEvent)),
+        (
+            "switch_progress",
+            schemars::schema_for!(SwitchProgressEvent),
+        ),
+    ])
+}
+
+/// `get_schemas` 的返回，也是快照文件的内容
+pub fn schema_document() -> Value {
+    serde_json::json!({
+        "schema_version": schema_version(),
+        "schemas": schemas(),
+    })
+}
+
+/// 把 schema 拍平成一组"事实"（某路径的类型、必有字段、枚举值……），描述性字段不算。
+/// 旧 schema 的事实在新 schema 里找不到就是破坏性修改；只多出来的是新增
+fn facts(value: &Value, path: &str, out: &mut BTreeSet<String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, v) in map {
+                match key.as_str() {
+                    "description" | "title" | "default" | "examples" => {}
+                    "required" | "enum" => {
+                        for item in v.as_array().into_iter().flatten() {
+                            out.insert(format!("{}.{}:{}", path, key, item));
+                        }
+                    }
+                    "type" | "$ref" => {
+                        out.insert(format!("{}.{}={}", path, key, v));
+                    }
+                    _ => facts(v, &format!("{}.{}", path, key), out),
+                }
+            }
+        }
+        Value::Array(items) => {
+            for (i, v) in items.iter().enumerate() {
+                facts(v, &format!("{}[{}]", path, i), out);
+            }
+        }
+        other => {
+            out.insert(format!("{}={}", path, other));
+        }
+    }
+}
+
+/// `old` → `new` 的破坏性修改（旧 schema 里有、新 schema 里没了的事实）
+pub fn breaking_changes(old: &Value, new: &Value) -> Vec<String> {
+    let (mut before, mut after) = (BTreeSet::new(), BTreeSet::new());
+    facts(old, "", &mut before);
+    facts(new, "", &mut after);
+    before.difference(&after).cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn snapshot_path() -> PathBuf {
+        std::path::Path::new(file!())
+            .parent()
+            .unwrap()
+            .join("../schemas/api.json")
+    }
+
+    #[test]
+    fn schemas_match_snapshot() {
+        let current = schema_document();
+        let path = snapshot_path();
+        if std::env::var_os("UPDATE_SCHEMA_SNAPSHOT").is_some() {
+            let text = serde_json::to_string_pretty(&current).unwrap();
+            std::fs::write(&path, text + "\n").unwrap();
+            return;
+        }
+        let stored: Value = serde_json::from_str(
+            &std::fs::read_to_string(&path).expect("缺少 schemas/api.json 快照"),
+        )
+        .unwrap();
+        if stored == current {
+            return;
+        }
+
+        let stored_major = stored["schema_version"]
+            .as_str()
+            .and_then(|v| v.split('.').next())
+            .and_then(|m| m.parse::<u32>().ok());
+        let breaking = breaking_changes(&stored["schemas"], &current["schemas"]);
+        assert!(
+            breaking.is_empty() || stored_major != Some(SCHEMA_MAJOR),
+            "公开负载有破坏性修改，需升 SCHEMA_MAJOR 并保留 _v1 命令别名：\n{}",
+            breaking.join("\n")
+        );
+        panic!(
+            "schema 与快照不一致（{} → {}）：确认版本号已按规则调整后，\
+             用 UPDATE_SCHEMA_SNAPSHOT=1 重新生成快照",
+            stored["schema_version"],
+            schema_version()
+        );
+    }
+
+    #[test]
+    fn removing_or_retyping_fields_is_breaking_but_adding_is_not() {
+        let original = schema_document()["schemas"].clone();
+        assert!(breaking_changes(&original, &original).is_empty());
+
+        let mut added = original.clone();
+        added["usage_display"]["properties"]["extra"] = serde_json::json!({"type": "string"});
+        assert!(breaking_changes(&original, &added).is_empty());
+
+        let mut removed = original.clone();
+        removed["usage_display"]["properties"]
+            .as_object_mut()
+            .unwrap()
+            .remove("plan_type");
+        let changes = breaking_changes(&original, &removed);
+        assert!(
+            changes.iter().any(|c| c.contains("plan_type")),
+            "{:?}",
+            changes
+        );
+
+        let mut retyped = original.clone();
+        retyped["account"]["properties"]["name"]["type"] = serde_json::json!("integer");
+        assert!(!breaking_changes(&original, &retyped).is_empty());
+
+        // 必有字段变可选也算破坏
+        let mut optional = original.clone();
+        optional["usage_display"]["required"]
+            .as_array_mut()
+            .unwrap()
+            .retain(|f| f != "five_hour_left");
+        assert!(!breaking_changes(&original, &optional).is_empty());
+    }
+}
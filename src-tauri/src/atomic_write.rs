@@ -0,0 +1,356 @@
+//! 带重试的原子写（先写 `.tmp` 再 rename）
+//!
+//! Windows 上杀软 / 索引服务会短暂占住文件，`rename` 偶发 sharing violation，
+//! 半秒后重试就好。这里对"看起来是暂时性"的错误做有限次指数退避重试，
+//! 其余错误立即返回；放弃时删掉 `.tmp`，不留残骸。
+//!
+//! `auth.json` 和 `accounts.json` 都走这里。文件操作和 sleep 都抽成 trait，方便测试注入失败。
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// 重试策略
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// 总尝试次数（含第一次）
+    pub max_attempts: u32,
+    /// 第一次重试前的等待，之后每次翻倍
+    pub base_delay: Duration,
+    /// 抖动比例：实际等待 = 基准 × (1 + jitter × [0,1) 随机数)
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            base_delay: Duration::from_millis(100),
+            jitter: 0.25,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// 第 `retry` 次重试（从 1 开始）前的等待；`rand01` 为 [0,1) 随机数
+    pub fn delay(&self, retry: u32, rand01: f64) -> Duration {
+        let base = self.base_delay * 2u32.saturating_pow(retry.saturating_sub(1));
+        base.mul_f64(1.0 + self.jitter * rand01.clamp(0.0, 1.0))
+    }
+}
+
+/// 写盘用到的文件操作
+pub trait WriteOps {
+    fn write(&self, path: &Path, content: &[u8]) -> io::Result<()>;
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+    fn remove(&self, path: &Path) -> io::Result<()>;
+}
+
+pub trait Sleeper {
+    fn sleep(&self, dur: Duration);
+}
+
+/// 真实文件系统；unix 下临时文件以 0600 创建，写内容之前再收紧一次
+/// （残留的旧临时文件被截断复用时 `mode` 不生效）
+pub struct RealFs;
+
+impl WriteOps for RealFs {
+    fn write(&self, path: &Path, content: &[u8]) -> io::Result<()> {
+        use std::io::Write;
+        let mut opts = std::fs::OpenOptions::new();
+        opts.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            opts.mode(0o600);
+        }
+        let mut file = opts.open(path)?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+        }
+        file.write_all(content)?;
+        file.sync_all()
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        std::fs::rename(from, to)
+    }
+
+    fn remove(&self, path: &Path) -> io::Result<()> {
+        std::fs::remove_file(path)
+    }
+}
+
+pub struct ThreadSleeper;
+
+impl Sleeper for ThreadSleeper {
+    fn sleep(&self, dur: Duration) {
+        std::thread::sleep(dur);
+    }
+}
+
+/// 可能过一会儿就好的错误：被打断 / 会阻塞 / 资源忙，Windows 上再加
+/// PermissionDenied 和 sharing / lock violation（杀软占用文件的典型表现）
+pub fn is_transient(err: &io::Error) -> bool {
+    match err.kind() {
+        io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock | io::ErrorKind::ResourceBusy => {
+            true
+        }
+        io::ErrorKind::PermissionDenied => cfg!(windows),
+        // ERROR_SHARING_VIOLATION = 32, ERROR_LOCK_VIOLATION = 33
+        _ => cfg!(windows) && matches!(err.raw_os_error(), Some(32) | Some(33)),
+    }
+}
+
+/// `path` 对应的临时文件
+pub fn tmp_path(path: &Path) -> PathBuf {
+    path.with_extension("tmp")
+}
+
+/// 启动时清理上次崩溃 / 放弃后残留的临时文件
+pub fn remove_stale_tmp(path: &Path) {
+    let tmp = tmp_path(path);
+    if tmp.exists() {
+        match std::fs::remove_file(&tmp) {
+            Ok(()) => println!("[AtomicWrite] 已清理残留临时文件 {:?}", tmp),
+            Err(e) => eprintln!("[AtomicWrite] 清理残留临时文件 {:?} 失败: {}", tmp, e),
+        }
+    }
+}
+
+/// 写临时文件 + rename，暂时性错误按策略重试。成功返回实际尝试次数。
+pub fn write_atomic_with(
+    ops: &dyn WriteOps,
+    sleeper: &dyn Sleeper,
+    policy: RetryPolicy,
+    path: &Path,
+    content: &[u8],
+) -> Result<u32, String> {
+    let tmp = tmp_path(path);
+    let max_attempts = policy.max_attempts.max(1);
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let result = ops
+            .write(&tmp, content)
+            .map_err(|e| ("写入临时文件", e))
+            .and_then(|()| ops.rename(&tmp, path).map_err(|e| ("重命名", e)));
+        let (step, err) = match result {
+            Ok(()) => return Ok(attempt),
+            Err(failure) => failure,
+        };
+        if attempt >= max_attempts || !is_transient(&err) {
+            let _ = ops.remove(&tmp);
+            return Err(format!(
+                "写入 {} 失败（{}，已尝试 {} 次）: {}",
+                path.display(),
+                step,
+                attempt,
+                err
+            ));
+        }
+        let delay = policy.delay(attempt, rand::random::<f64>());
+        println!(
+            "[AtomicWrite] {} {} 失败（第 {}/{} 次）: {}，{}ms 后重试",
+            path.display(),
+            step,
+            attempt,
+            max_attempts,
+            err,
+            delay.as_millis()
+        );
+        sleeper.sleep(delay);
+    }
+}
+
+/// 默认策略 + 真实文件系统
+pub fn write_atomic(path: &Path, content: &[u8]) -> Result<(), String> {
+    write_atomic_with(
+        &RealFs,
+        &ThreadSleeper,
+        RetryPolicy::default(),
+        path,
+        content,
+    )
+    .map(|_| ())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+
+    /// 按脚本让 rename 失败，记录所有调用
+    #[derive(Default)]
+    struct FakeFs {
+        rename_failures: RefCell<VecDeque<io::ErrorKind>>,
+        calls: RefCell<Vec<String>>,
+    }
+
+    impl FakeFs {
+        fn failing(kinds: &[io::ErrorKind]) -> Self {
+            Self {
+                rename_failures: RefCell::new(kinds.iter().copied().collect()),
+                ..Default::default()
+            }
+        }
+
+        fn count(&self, op: &str) -> usize {
+            self.calls
+                .borrow()
+                .iter()
+                .filter(|c| c.starts_with(op))
+                .count()
+        }
+    }
+
+    impl WriteOps for FakeFs {
+        fn write(&self, path: &Path, _content: &[u8]) -> io::Result<()> {
+            self.calls
+                .borrow_mut()
+                .push(format!("write {}", path.display()));
+            Ok(())
+        }
+
+        fn rename(&self, from: &Path, _to: &Path) -> io::Result<()> {
+            self.calls
+                .borrow_mut()
+                .push(format!("rename {}", from.display()));
+            match self.rename_failures.borrow_mut().pop_front() {
+                Some(kind) => Err(io::Error::new(kind, "injected")),
+                None => Ok(()),
+            }
+        }
+
+        fn remove(&self, path: &Path) -> io::Result<()> {
+            self.calls
+                .borrow_mut()
+                .push(format!("remove {}", path.display()));
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct RecordingSleeper(RefCell<Vec<Duration>>);
+
+    impl Sleeper for RecordingSleeper {
+        fn sleep(&self, dur: Duration) {
+            self.0.borrow_mut().push(dur);
+        }
+    }
+
+    fn no_jitter() -> RetryPolicy {
+        RetryPolicy {
+            jitter: 0.0,
+            ..RetryPolicy::default()
+        }
+    }
+
+    #[test]
+    fn transient_failures_are_retried_with_doubling_backoff() {
+        let fs = FakeFs::failing(&[io::ErrorKind::Interrupted, io::ErrorKind::WouldBlock]);
+        let sleeper = RecordingSleeper::default();
+        let attempts =
+            write_atomic_with(&fs, &sleeper, no_jitter(), Path::new("/x/auth.json"), b"{}")
+                .unwrap();
+        assert_eq!(attempts, 3);
+        assert_eq!(fs.count("rename"), 3);
+        assert_eq!(fs.count("remove"), 0);
+        assert_eq!(
+            *sleeper.0.borrow(),
+            vec![Duration::from_millis(100), Duration::from_millis(200)]
+        );
+    }
+
+    #[test]
+    fn gives_up_after_max_attempts_and_removes_tmp() {
+        let fs = FakeFs::failing(&[io::ErrorKind::Interrupted; 10]);
+        let sleeper = RecordingSleeper::default();
+        let err = write_atomic_with(&fs, &sleeper, no_jitter(), Path::new("/x/auth.json"), b"{}")
+            .unwrap_err();
+        assert!(err.contains("已尝试 5 次"), "{}", err);
+        assert!(err.contains("injected"), "{}", err);
+        assert_eq!(fs.count("rename"), 5);
+        assert_eq!(sleeper.0.borrow().len(), 4);
+        assert_eq!(sleeper.0.borrow()[3], Duration::from_millis(800));
+        assert_eq!(fs.calls.borrow().last().unwrap(), "remove /x/auth.tmp");
+    }
+
+    #[test]
+    fn permanent_errors_fail_immediately() {
+        let fs = FakeFs::failing(&[io::ErrorKind::NotFound]);
+        let sleeper = RecordingSleeper::default();
+        let err = write_atomic_with(&fs, &sleeper, no_jitter(), Path::new("/x/auth.json"), b"{}")
+            .unwrap_err();
+        assert!(err.contains("已尝试 1 次"), "{}", err);
+        assert!(sleeper.0.borrow().is_empty());
+        assert_eq!(fs.count("remove"), 1);
+    }
+
+    #[test]
+    fn jitter_stays_within_ratio() {
+        let policy = RetryPolicy::default();
+        assert_eq!(policy.delay(1, 0.0), Duration::from_millis(100));
+        assert_eq!(policy.delay(3, 0.0), Duration::from_millis(400));
+        assert_eq!(policy.delay(3, 1.0), Duration::from_millis(500));
+    }
+
+    /// 真写临时文件，rename 失败；失败前检查临时文件已是 0600
+    struct RenameFails;
+
+    impl WriteOps for RenameFails {
+        fn write(&self, path: &Path, content: &[u8]) -> io::Result<()> {
+            RealFs.write(path, content)
+        }
+
+        fn rename(&self, from: &Path, _to: &Path) -> io::Result<()> {
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let mode = std::fs::metadata(from)?.permissions().mode();
+                assert_eq!(mode & 0o777, 0o600);
+            }
+            Err(io::Error::new(io::ErrorKind::StorageFull, "injected"))
+        }
+
+        fn remove(&self, path: &Path) -> io::Result<()> {
+            RealFs.remove(path)
+        }
+    }
+
+    #[test]
+    fn failed_rename_leaves_the_original_intact() {
+        let dir = std::env::temp_dir().join(format!("cs-atomic-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("accounts.json");
+        write_atomic(&path, b"old").unwrap();
+        // 残留的临时文件权限偏宽，也不能让新内容带着它落盘
+        std::fs::write(tmp_path(&path), "stale").unwrap();
+
+        let sleeper = RecordingSleeper::default();
+        let err =
+            write_atomic_with(&RenameFails, &sleeper, no_jitter(), &path, b"new").unwrap_err();
+        assert!(err.contains("重命名"), "{}", err);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "old");
+        assert!(!tmp_path(&path).exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn real_fs_write_and_stale_tmp_cleanup() {
+        let dir = std::env::temp_dir().join(format!("cs-atomic-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("auth.json");
+        std::fs::write(tmp_path(&path), "stale").unwrap();
+        remove_stale_tmp(&path);
+        assert!(!tmp_path(&path).exists());
+
+        write_atomic(&path, b"{\"ok\":true}").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{\"ok\":true}");
+        assert!(!tmp_path(&path).exists());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,379 @@
+//! 从一个文件夹批量导入 auth.json 备份（`auth-work.json`、`auth-personal.json` …）
+//!
+//! 只扫目录本身（不递归）里的 `*.json`，每个文件独立处理，单个文件失败不影响其它：
+//! - 走 `auth_identify::parse_identity` 解析，历史格式一并归一
+//! - 账号名取 token 里的邮箱，拿不到时用文件名（去掉扩展名）
+//! - 用 `auth_identity_matches` 找已存的同一身份账号：有就用更新的 token 覆盖，没有才新增；
+//!   同一个目录里出现两份同身份文件时，第二份按"已存在"处理，不会重复入库
+//! - 顶层带 `accounts` 的是账号库导出（accounts.json），单独列出，提示走普通导入
+//!
+//! 只改内存里的 store，落盘由调用方负责。
+
+use std::fs;
+use std::path::Path;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::account::AccountStore;
+use crate::auth_identify;
+
+/// 单个文件的大小上限；auth.json 一般只有几 KB
+const MAX_FILE_BYTES: u64 = 1024 * 1024;
+
+/// 单个文件的处理结果
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum FileOutcome {
+    Imported { account_id: String, name: String },
+    Updated { account_id: String, name: String },
+    Skipped { reason: String },
+    Failed { error: String },
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct FileReport {
+    /// 文件名（不含目录）
+    pub file: String,
+    #[serde(flatten)]
+    pub outcome: FileOutcome,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct DirImportReport {
+    /// 规范化后的目录路径
+    pub directory: String,
+    /// 按文件名排序
+    pub files: Vec<FileReport>,
+    /// 账号库导出文件（accounts.json 格式），请用"导入"功能
+    pub exports: Vec<String>,
+    pub imported: usize,
+    pub updated: usize,
+}
+
+impl DirImportReport {
+    /// 是否改动了 store（需要落盘）
+    pub fn changed(&self) -> bool {
+        self.imported + self.updated > 0
+    }
+}
+
+/// 扫描 `dir` 并把其中的 auth.json 并入 `store`。
+/// 目录本身不存在 / 不是目录 / 读不了时返回 Err；单个文件的问题只进报告
+pub fn import_directory(store: &mut AccountStore, dir: &Path) -> Result<DirImportReport, String> {
+    let dir = dir
+        .canonicalize()
+        .map_err(|e| format!("目录不可用 {:?}: {}", dir, e))?;
+    if !dir.is_dir() {
+        return Err(format!("不是目录: {:?}", dir));
+    }
+    let mut files: Vec<_> = fs::read_dir(&dir)
+        .map_err(|e| format!("读取目录失败 {:?}: {}", dir, e))?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+        })
+        .collect();
+    files.sort();
+
+    let mut report = DirImportReport {
+        directory: dir.display().to_string(),
+        ..Default::default()
+    };
+    for path in files {
+        let file = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let outcome = match read_auth_file(&path) {
+            Ok(FileContent::Export) => {
+                report.exports.push(file);
+                continue;
+            }
+            Ok(FileContent::Auth(text)) => merge_auth(store, &path, &text),
+            Err(error) => FileOutcome::Failed { error },
+        };
+        match outcome {
+            FileOutcome::Imported { .. } => report.imported += 1,
+            FileOutcome::Updated { .. } => report.updated += 1,
+            _ => {}
+        }
+        report.files.push(FileReport { file, outcome });
+    }
+    println!(
+        "[DirImport] {}：新增 {}，更新 {}，其它 {}，导出文件 {}",
+        report.directory,
+        report.imported,
+        report.updated,
+        report.files.len() - report.imported - report.updated,
+        report.exports.len()
+    );
+    Ok(report)
+}
+
+enum FileContent {
+    Auth(String),
+    Export,
+}
+
+fn read_auth_file(path: &Path) -> Result<FileContent, String> {
+    let meta = fs::metadata(path).map_err(|e| format!("读取失败: {}", e))?;
+    if !meta.is_file() {
+        return Err("不是普通文件".to_string());
+    }
+    if meta.len() > MAX_FILE_BYTES {
+        return Err(format!("文件过大（{} 字节）", meta.len()));
+    }
+    let text = fs::read_to_string(path).map_err(|e| format!("读取失败: {}", e))?;
+    // 解析失败交给 parse_identity 出逐字段错误
+    let is_export = serde_json::from_str::<Value>(&text).ok().is_some_and(|v| {
+        v.get("accounts")
+            .is_some_and(|a| a.is_object() || a.is_array())
+    });
+    Ok(if is_export {
+        FileContent::Export
+    } else {
+        FileContent::Auth(text)
+    })
+}
+
+/// 按身份把一份 auth.json 并入 store；`path` 只在拿不到邮箱时用来起名
+pub(crate) fn merge_auth(store: &mut AccountStore, path: &Path, text: &str) -> FileOutcome {
+    let (auth, identity) = match auth_identify::parse_identity(text) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            return FileOutcome::Failed {
+                error: e.to_string(),
+            }
+        }
+    };
+    if !identity.has_refresh_token {
+        return FileOutcome::Skipped {
+            reason: "缺少 refresh_token，无法自动续期".to_string(),
+        };
+    }
+
+    let existing = store
+        .accounts
+        .values()
+        .find(|a| AccountStore::auth_identity_matches(&a.auth_json, &auth))
+        .map(|a| {
+            (
+                a.id.clone(),
+                a.name.clone(),
+                auth_identify::is_fresher(&auth, &a.auth_json),
+                a.refresh_token.clone(),
+            )
+        });
+    if let Some((id, name, fresher, stored_rt)) = existing {
+        if fresher == Some(false) {
+            return FileOutcome::Skipped {
+                reason: format!("已存账号 {} 的 token 更新，保留现有", name),
+            };
+        }
+        if fresher.is_none() && stored_rt == AccountStore::extract_refresh_token(&auth) {
+            return FileOutcome::Skipped {
+                reason: format!("与已存账号 {} 相同", name),
+            };
+        }
+        if let Err(reason) = store.ensure_unprotected(&id, "被导入覆盖") {
+            return FileOutcome::Skipped { reason };
+        }
+        if !store.sync_account_from_auth_json(&id, auth) {
+            return FileOutcome::Skipped {
+                reason: format!("与已存账号 {} 的邮箱不一致，未覆盖", name),
+            };
+        }
+        return FileOutcome::Updated {
+            account_id: id,
+            name,
+        };
+    }
+
+    let name = identity.email.unwrap_or_else(|| {
+        path.file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default()
+    });
+    let account = store.add_account(name, auth, None);
+    FileOutcome::Imported {
+        account_id: account.id,
+        name: account.name,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::Engine;
+    use serde_json::json;
+
+    fn jwt(payload: Value) -> String {
+        let engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        format!(
+            "{}.{}.sig",
+            engine.encode(r#"{"alg":"none"}"#),
+            engine.encode(serde_json::to_vec(&payload).unwrap())
+        )
+    }
+
+    fn oauth_auth(email: Option<&str>, account_id: &str, refresh_token: &str, exp: i64) -> Value {
+        let auth_claim = json!({ "chatgpt_account_id": account_id });
+        let mut id_claims = json!({ "https://api.openai.com/auth": auth_claim });
+        if let Some(email) = email {
+            id_claims["email"] = json!(email);
+        }
+        json!({
+            "tokens": {
+                "account_id": account_id,
+                "refresh_token": refresh_token,
+                "id_token": jwt(id_claims),
+                "access_token": jwt(json!({ "exp": exp, "https://api.openai.com/auth": auth_claim })),
+            },
+            "last_refresh": "2026-01-01T00:00:00Z",
+        })
+    }
+
+    fn temp_dir() -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("cs-dir-import-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn outcome<'a>(report: &'a DirImportReport, file: &str) -> &'a FileOutcome {
+        &report
+            .files
+            .iter()
+            .find(|f| f.file == file)
+            .unwrap_or_else(|| panic!("报告里没有 {}", file))
+            .outcome
+    }
+
+    #[test]
+    fn imports_folder_of_mixed_files() {
+        let mut store = AccountStore::default();
+        let work_id = store
+            .add_account(
+                "work@example.com".to_string(),
+                oauth_auth(
+                    Some("work@example.com"),
+                    "acct-work",
+                    "rt-old",
+                    1_800_000_000,
+                ),
+                None,
+            )
+            .id;
+
+        let dir = temp_dir();
+        let write = |name: &str, content: String| fs::write(dir.join(name), content).unwrap();
+        // 已存账号的更新 token
+        write(
+            "auth-work.json",
+            oauth_auth(
+                Some("work@example.com"),
+                "acct-work",
+                "rt-new",
+                1_900_000_000,
+            )
+            .to_string(),
+        );
+        // 新账号
+        write(
+            "auth-personal.json",
+            oauth_auth(Some("me@example.com"), "acct-me", "rt-me", 1_800_000_000).to_string(),
+        );
+        // 同一身份的第二份，token 更旧：不重复入库
+        write(
+            "backup-personal.json",
+            oauth_auth(Some("me@example.com"), "acct-me", "rt-me-0", 1_700_000_000).to_string(),
+        );
+        // 历史格式：token 在根上、id_token 里没有邮箱 → 用文件名
+        let legacy = oauth_auth(None, "acct-legacy", "rt-legacy", 1_800_000_000);
+        let mut root = legacy["tokens"].as_object().unwrap().clone();
+        root.insert("last_refresh".to_string(), legacy["last_refresh"].clone());
+        write("legacy-team.json", Value::Object(root).to_string());
+        // 账号库导出
+        write(
+            "accounts.json",
+            json!({ "accounts": {}, "current": null }).to_string(),
+        );
+        write("broken.json", "{ not json".to_string());
+        write(
+            "no-rt.json",
+            json!({ "tokens": { "account_id": "acct-nort", "access_token": jwt(json!({"exp": 1})) } })
+                .to_string(),
+        );
+        write("notes.txt", "不是 json，不扫".to_string());
+
+        let report = import_directory(&mut store, &dir).unwrap();
+        assert_eq!(report.exports, vec!["accounts.json"]);
+        assert_eq!(report.files.len(), 6, "{:?}", report.files);
+        assert!(report.files.windows(2).all(|w| w[0].file < w[1].file));
+        assert_eq!((report.imported, report.updated), (2, 1));
+
+        assert_eq!(
+            outcome(&report, "auth-work.json"),
+            &FileOutcome::Updated {
+                account_id: work_id.clone(),
+                name: "work@example.com".to_string(),
+            }
+        );
+        assert_eq!(
+            store.accounts[&work_id].refresh_token.as_deref(),
+            Some("rt-new")
+        );
+        assert!(matches!(
+            outcome(&report, "auth-personal.json"),
+            FileOutcome::Imported { name, .. } if name == "me@example.com"
+        ));
+        assert!(matches!(
+            outcome(&report, "backup-personal.json"),
+            FileOutcome::Skipped { .. }
+        ));
+        assert!(matches!(
+            outcome(&report, "legacy-team.json"),
+            FileOutcome::Imported { name, .. } if name == "legacy-team"
+        ));
+        assert!(matches!(
+            outcome(&report, "broken.json"),
+            FileOutcome::Failed { error } if error.contains("json")
+        ));
+        assert!(matches!(
+            outcome(&report, "no-rt.json"),
+            FileOutcome::Skipped { reason } if reason.contains("refresh_token")
+        ));
+        assert_eq!(store.accounts.len(), 3);
+
+        // 再扫一次：全是已存在的，不产生改动
+        let again = import_directory(&mut store, &dir).unwrap();
+        assert!(!again.changed());
+        assert_eq!(store.accounts.len(), 3);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn rejects_missing_or_non_directory_paths() {
+        let mut store = AccountStore::default();
+        let dir = temp_dir();
+        assert!(import_directory(&mut store, &dir.join("missing")).is_err());
+        let file = dir.join("auth.json");
+        fs::write(&file, "{}").unwrap();
+        assert!(import_directory(&mut store, &file)
+            .unwrap_err()
+            .contains("不是目录"));
+
+        // `..` 会被规范化
+        fs::create_dir(dir.join("sub")).unwrap();
+        let report = import_directory(&mut store, &dir.join("sub").join("..")).unwrap();
+        assert_eq!(
+            Path::new(&report.directory),
+            dir.canonicalize().unwrap().as_path()
+        );
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
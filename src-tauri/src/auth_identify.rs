@@ -0,0 +1,507 @@
+//! 识别一段粘贴进来的 auth.json 属于哪个已存账号
+//!
+//! 队友发来一段 auth.json 问"这是我们哪个号"：解析（兼容历史格式）→ 用现有提取函数
+//! 拿 email / account_id / user id / 套餐 / token 过期时间 → 用 `auth_identity_matches`
+//! 逐个比对已存账号。全程只读，不落盘、不写 auth.json。
+//!
+//! 兼容的历史格式：
+//! - 根级 `account_id`（部分 Codex 版本）
+//! - 没有 `tokens` 块，token 直接写在根上
+//! - `tokens` 被存成 Python repr 字符串（`"{'access_token': '...'}"`）
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fmt;
+
+use crate::account::{
+    extract_access_token_jwt_exp_iso, extract_token_from_str, AccountKind, AccountStore,
+};
+
+const TOKEN_KEYS: [&str; 4] = ["access_token", "refresh_token", "id_token", "account_id"];
+
+/// 粘贴内容的原始形态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthShape {
+    /// 当前 Codex 的标准格式
+    Standard,
+    /// `account_id` 写在根上
+    RootAccountId,
+    /// 没有 `tokens` 块，token 在根上
+    RootTokens,
+    /// `tokens` 是 Python repr 字符串
+    PythonReprTokens,
+}
+
+/// 某个字段解析失败
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldError {
+    /// 出错的字段路径，例 `tokens.id_token`；整体问题用 `input` / `json`
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, message: impl Into<String>) -> Self {
+        Self {
+            field: field.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+/// 认不出身份时返回的逐字段错误
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct IdentifyError {
+    pub errors: Vec<FieldError>,
+}
+
+impl fmt::Display for IdentifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = self
+            .errors
+            .iter()
+            .map(|e| format!("{}: {}", e.field, e.message))
+            .collect();
+        write!(f, "{}", parts.join("；"))
+    }
+}
+
+/// 从粘贴内容里解析出的身份
+#[derive(Debug, Clone, Serialize)]
+pub struct ParsedIdentity {
+    pub shape: AuthShape,
+    pub email: Option<String>,
+    pub account_id: Option<String>,
+    pub user_id: Option<String>,
+    /// id_token / access_token 里的 `chatgpt_plan_type`
+    pub plan: Option<String>,
+    /// access_token JWT 的 `exp`
+    pub access_token_expires_at: Option<DateTime<Utc>>,
+    pub last_refresh: Option<DateTime<Utc>>,
+    pub has_refresh_token: bool,
+    /// 不影响识别的字段问题（如 last_refresh 格式不对）
+    pub warnings: Vec<FieldError>,
+}
+
+/// 匹配到的已存账号
+#[derive(Debug, Clone, Serialize)]
+pub struct AccountSummary {
+    pub id: String,
+    pub name: String,
+    pub kind: AccountKind,
+    pub email: Option<String>,
+    pub is_current: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IdentifyResult {
+    pub parsed_identity: ParsedIdentity,
+    pub matched_account: Option<AccountSummary>,
+    /// 两边都有 refresh_token 时才有值
+    pub refresh_token_matches_stored: Option<bool>,
+    /// 粘贴的 token 是否比已存的新（先比 access_token 过期时间，再比 last_refresh）
+    pub token_fresher_than_stored: Option<bool>,
+}
+
+/// 把历史格式归一成标准格式，返回归一后的 JSON 和原始形态
+pub fn normalize_pasted(mut auth: Value) -> (Value, AuthShape) {
+    let Some(obj) = auth.as_object_mut() else {
+        return (auth, AuthShape::Standard);
+    };
+    let mut shape = AuthShape::Standard;
+    match obj.get("tokens") {
+        Some(Value::String(repr)) => {
+            let tokens: Map<String, Value> = TOKEN_KEYS
+                .iter()
+                .filter_map(|k| {
+                    extract_token_from_str(repr, k).map(|v| (k.to_string(), Value::from(v)))
+                })
+                .collect();
+            obj.insert("tokens".to_string(), Value::Object(tokens));
+            shape = AuthShape::PythonReprTokens;
+        }
+        None if TOKEN_KEYS[..3].iter().any(|k| obj.contains_key(*k)) => {
+            let tokens: Map<String, Value> = TOKEN_KEYS
+                .iter()
+                .filter_map(|k| obj.remove(*k).map(|v| (k.to_string(), v)))
+                .collect();
+            obj.insert("tokens".to_string(), Value::Object(tokens));
+            shape = AuthShape::RootTokens;
+        }
+        _ => {}
+    }
+    if AccountStore::canonicalize_account_id(&mut auth) && shape == AuthShape::Standard {
+        shape = AuthShape::RootAccountId;
+    }
+    (auth, shape)
+}
+
+fn is_jwt(token: &str) -> bool {
+    token.split('.').count() == 3
+}
+
+/// id_token / access_token claims 里的 `chatgpt_plan_type`
+pub(crate) fn plan_from_claims(auth: &Value) -> Option<String> {
+    ["id_token", "access_token"].iter().find_map(|key| {
+        AccountStore::extract_jwt_claims_from_auth(auth, key)?
+            .get("https://api.openai.com/auth")?
+            .get("chatgpt_plan_type")?
+            .as_str()
+            .map(str::to_string)
+    })
+}
+
+fn access_token_exp(auth: &Value) -> Option<DateTime<Utc>> {
+    let iso = extract_access_token_jwt_exp_iso(auth)?;
+    DateTime::parse_from_rfc3339(&iso)
+        .ok()
+        .map(|dt| dt.with_timezone(&Utc))
+}
+
+/// 解析粘贴内容。返回归一后的 JSON + 身份；认不出身份时返回所有发现的字段错误
+pub fn parse_identity(text: &str) -> Result<(Value, ParsedIdentity), IdentifyError> {
+    let fail = |errors: Vec<FieldError>| Err(IdentifyError { errors });
+    let text = text.trim();
+    if text.is_empty() {
+        return fail(vec![FieldError::new("input", "内容为空")]);
+    }
+    let raw: Value = match serde_json::from_str(text) {
+        Ok(v) => v,
+        Err(e) => {
+            return fail(vec![FieldError::new(
+                "json",
+                format!("第 {} 行第 {} 列: {}", e.line(), e.column(), e),
+            )])
+        }
+    };
+    if !raw.is_object() {
+        return fail(vec![FieldError::new("json", "顶层不是 JSON 对象")]);
+    }
+    if raw.get("OPENAI_API_KEY").is_some_and(|v| !v.is_null()) {
+        return fail(vec![FieldError::new(
+            "OPENAI_API_KEY",
+            "API Key 登录的 auth.json 不带账号身份，无法比对",
+        )]);
+    }
+
+    let (auth, shape) = normalize_pasted(raw);
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+    match auth.get("tokens") {
+        Some(Value::Object(tokens)) => {
+            for key in ["access_token", "id_token"] {
+                match tokens.get(key) {
+                    None | Some(Value::Null) => {}
+                    Some(Value::String(s)) if is_jwt(s) => {
+                        if let Err(e) = crate::jwt_claims::decode(s) {
+                            errors.push(FieldError::new(
+                                &format!("tokens.{}", key),
+                                format!("JWT payload 无法解码: {}", e),
+                            ));
+                        }
+                    }
+                    Some(Value::String(_)) => errors.push(FieldError::new(
+                        &format!("tokens.{}", key),
+                        "不是 JWT（应为 xxx.yyy.zzz 三段）",
+                    )),
+                    Some(_) => {
+                        errors.push(FieldError::new(&format!("tokens.{}", key), "应为字符串"))
+                    }
+                }
+            }
+            if tokens
+                .get("account_id")
+                .is_some_and(|v| !v.is_string() && !v.is_null())
+            {
+                errors.push(FieldError::new("tokens.account_id", "应为字符串"));
+            }
+        }
+        Some(_) => errors.push(FieldError::new("tokens", "应为对象")),
+        None => errors.push(FieldError::new("tokens", "缺少 tokens")),
+    }
+    if auth.get("last_refresh").is_some_and(|v| !v.is_null())
+        && AccountStore::extract_last_refresh(&auth).is_none()
+    {
+        warnings.push(FieldError::new(
+            "last_refresh",
+            "无法解析（应为 RFC3339 或时间戳）",
+        ));
+    }
+
+    let account_id = AccountStore::extract_account_id(&auth);
+    let user_id = AccountStore::extract_openai_user_id(&auth);
+    if account_id.is_none() && user_id.is_none() {
+        errors.push(FieldError::new(
+            "identity",
+            "找不到 account_id 或 user id，无法比对",
+        ));
+        return fail(errors);
+    }
+    // 身份能认出来时，其余字段问题降级为提示
+    errors.append(&mut warnings);
+    let identity = ParsedIdentity {
+        shape,
+        email: AccountStore::extract_email(&auth),
+        account_id,
+        user_id,
+        plan: plan_from_claims(&auth),
+        access_token_expires_at: access_token_exp(&auth),
+        last_refresh: AccountStore::extract_last_refresh(&auth),
+        has_refresh_token: AccountStore::extract_refresh_token(&auth).is_some(),
+        warnings: errors,
+    };
+    Ok((auth, identity))
+}
+
+/// 粘贴的 token 是否比已存的新；两边都拿不到可比时间时返回 None
+pub fn is_fresher(pasted: &Value, stored: &Value) -> Option<bool> {
+    if let (Some(p), Some(s)) = (access_token_exp(pasted), access_token_exp(stored)) {
+        return Some(p > s);
+    }
+    match (
+        AccountStore::extract_last_refresh(pasted),
+        AccountStore::extract_last_refresh(stored),
+    ) {
+        (Some(p), Some(s)) => Some(p > s),
+        _ => None,
+    }
+}
+
+/// 在 store 里找同一身份的账号；有多个时优先当前账号，其次按名字排序取第一个
+pub fn identify(store: &AccountStore, text: &str) -> Result<IdentifyResult, IdentifyError> {
+    let (auth, parsed_identity) = parse_identity(text)?;
+    let mut candidates: Vec<_> = store
+        .accounts
+        .values()
+        .filter(|a| AccountStore::auth_identity_matches(&a.auth_json, &auth))
+        .collect();
+    candidates.sort_by_key(|a| {
+        (
+            store.current.as_deref() != Some(a.id.as_str()),
+            a.name.clone(),
+        )
+    });
+    let Some(account) = candidates.first() else {
+        return Ok(IdentifyResult {
+            parsed_identity,
+            matched_account: None,
+            refresh_token_matches_stored: None,
+            token_fresher_than_stored: None,
+        });
+    };
+
+    let pasted_rt = AccountStore::extract_refresh_token(&auth);
+    let stored_rt = account
+        .refresh_token
+        .clone()
+        .or_else(|| AccountStore::extract_refresh_token(&account.auth_json));
+    let refresh_token_matches_stored = match (pasted_rt, stored_rt) {
+        (Some(p), Some(s)) => Some(p == s),
+        _ => None,
+    };
+    Ok(IdentifyResult {
+        token_fresher_than_stored: is_fresher(&auth, &account.auth_json),
+        refresh_token_matches_stored,
+        matched_account: Some(AccountSummary {
+            id: account.id.clone(),
+            name: account.name.clone(),
+            kind: account.effective_kind(),
+            email: AccountStore::extract_email(&account.auth_json),
+            is_current: store.current.as_deref() == Some(account.id.as_str()),
+        }),
+        parsed_identity,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::Engine;
+    use serde_json::json;
+
+    fn jwt(payload: Value) -> String {
+        let engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        format!(
+            "{}.{}.sig",
+            engine.encode(r#"{"alg":"none"}"#),
+            engine.encode(serde_json::to_vec(&payload).unwrap())
+        )
+    }
+
+    fn oauth_auth(email: &str, account_id: &str, refresh_token: &str, exp: i64) -> Value {
+        let auth_claim = json!({ "chatgpt_account_id": account_id, "chatgpt_plan_type": "plus" });
+        json!({
+            "tokens": {
+                "account_id": account_id,
+                "refresh_token": refresh_token,
+                "id_token": jwt(json!({ "email": email, "https://api.openai.com/auth": auth_claim })),
+                "access_token": jwt(json!({ "exp": exp, "https://api.openai.com/auth": auth_claim })),
+            },
+            "last_refresh": "2026-01-01T00:00:00Z",
+        })
+    }
+
+    fn store_with_work() -> (AccountStore, String) {
+        let mut store = AccountStore::default();
+        let id = store
+            .add_account(
+                "work".to_string(),
+                oauth_auth("work@example.com", "acct-work", "rt-1", 1_800_000_000),
+                None,
+            )
+            .id;
+        store.add_account(
+            "other".to_string(),
+            oauth_auth("other@example.com", "acct-other", "rt-x", 1_800_000_000),
+            None,
+        );
+        (store, id)
+    }
+
+    #[test]
+    fn matches_stored_account_and_compares_tokens() {
+        let (store, id) = store_with_work();
+        let pasted = oauth_auth("work@example.com", "acct-work", "rt-2", 1_900_000_000);
+        let result = identify(&store, &pasted.to_string()).unwrap();
+
+        let identity = &result.parsed_identity;
+        assert_eq!(identity.shape, AuthShape::Standard);
+        assert_eq!(identity.email.as_deref(), Some("work@example.com"));
+        assert_eq!(identity.account_id.as_deref(), Some("acct-work"));
+        assert_eq!(identity.plan.as_deref(), Some("plus"));
+        assert_eq!(
+            identity.access_token_expires_at.map(|t| t.timestamp()),
+            Some(1_900_000_000)
+        );
+        assert!(identity.warnings.is_empty());
+
+        let matched = result.matched_account.unwrap();
+        assert_eq!(matched.id, id);
+        assert_eq!(matched.email.as_deref(), Some("work@example.com"));
+        assert_eq!(result.refresh_token_matches_stored, Some(false));
+        assert_eq!(result.token_fresher_than_stored, Some(true));
+    }
+
+    #[test]
+    fn unknown_identity_has_no_match() {
+        let (store, _) = store_with_work();
+        let pasted = oauth_auth("new@example.com", "acct-new", "rt-9", 1_900_000_000);
+        let result = identify(&store, &pasted.to_string()).unwrap();
+        assert_eq!(
+            result.parsed_identity.account_id.as_deref(),
+            Some("acct-new")
+        );
+        assert!(result.matched_account.is_none());
+        assert_eq!(result.refresh_token_matches_stored, None);
+        assert_eq!(result.token_fresher_than_stored, None);
+    }
+
+    #[test]
+    fn legacy_shapes_are_normalized_before_matching() {
+        let (store, id) = store_with_work();
+        let standard = oauth_auth("work@example.com", "acct-work", "rt-1", 1_800_000_000);
+        let tokens = &standard["tokens"];
+
+        // token 直接写在根上
+        let root = json!({
+            "access_token": tokens["access_token"],
+            "id_token": tokens["id_token"],
+            "refresh_token": "rt-1",
+            "account_id": "acct-work",
+        });
+        let result = identify(&store, &root.to_string()).unwrap();
+        assert_eq!(result.parsed_identity.shape, AuthShape::RootTokens);
+        assert_eq!(result.matched_account.unwrap().id, id);
+        assert_eq!(result.refresh_token_matches_stored, Some(true));
+        assert_eq!(result.token_fresher_than_stored, Some(false));
+
+        // tokens 是 Python repr 字符串
+        let repr = json!({
+            "tokens": format!(
+                "{{'access_token': '{}', 'refresh_token': 'rt-1', 'account_id': 'acct-work'}}",
+                tokens["access_token"].as_str().unwrap()
+            ),
+        });
+        let result = identify(&store, &repr.to_string()).unwrap();
+        assert_eq!(result.parsed_identity.shape, AuthShape::PythonReprTokens);
+        assert_eq!(result.matched_account.unwrap().id, id);
+
+        // 根级 account_id
+        let mut root_id = standard.clone();
+        root_id["tokens"]
+            .as_object_mut()
+            .unwrap()
+            .remove("account_id");
+        root_id["account_id"] = json!("acct-work");
+        let result = identify(&store, &root_id.to_string()).unwrap();
+        assert_eq!(result.parsed_identity.shape, AuthShape::RootAccountId);
+        assert_eq!(result.matched_account.unwrap().id, id);
+    }
+
+    #[test]
+    fn garbage_input_reports_field_errors() {
+        let (store, _) = store_with_work();
+        let fields = |text: &str| -> Vec<String> {
+            identify(&store, text)
+                .unwrap_err()
+                .errors
+                .into_iter()
+                .map(|e| e.field)
+                .collect()
+        };
+        assert_eq!(fields("   "), vec!["input"]);
+        assert_eq!(fields("{ not json"), vec!["json"]);
+        assert_eq!(fields("[1, 2]"), vec!["json"]);
+        assert_eq!(
+            fields(r#"{"OPENAI_API_KEY": "sk-x"}"#),
+            vec!["OPENAI_API_KEY"]
+        );
+        assert_eq!(
+            fields(r#"{"tokens": {"access_token": "nope", "id_token": 42}}"#),
+            vec!["tokens.access_token", "tokens.id_token", "identity"]
+        );
+        assert_eq!(fields(r#"{"tokens": 7}"#), vec!["tokens", "identity"]);
+
+        // 身份认得出来时，其它字段问题只作为提示
+        let mut auth = oauth_auth("work@example.com", "acct-work", "rt-1", 1_800_000_000);
+        auth["last_refresh"] = json!("yesterday");
+        let result = identify(&store, &auth.to_string()).unwrap();
+        assert_eq!(result.parsed_identity.warnings[0].field, "last_refresh");
+        assert!(result.matched_account.is_some());
+    }
+
+    #[test]
+    fn undecodable_jwt_payloads_are_reported_per_field() {
+        let (store, _) = store_with_work();
+        let mut auth = oauth_auth("work@example.com", "acct-work", "rt-1", 1_800_000_000);
+        auth["tokens"]["id_token"] = json!("h.eyJh+IjoxfQ.s");
+        let big = "A".repeat(crate::jwt_claims::MAX_PAYLOAD_BYTES + 1);
+        auth["tokens"]["access_token"] = json!(format!("h.{}.s", big));
+
+        // account_id 还在：能比对，解码问题作为提示
+        let warnings = identify(&store, &auth.to_string())
+            .unwrap()
+            .parsed_identity
+            .warnings;
+        let message = |field: &str| {
+            warnings
+                .iter()
+                .find(|w| w.field == field)
+                .map(|w| w.message.clone())
+                .unwrap()
+        };
+        assert!(message("tokens.id_token").contains("Base64"));
+        assert!(message("tokens.access_token").contains("Token 过大"));
+
+        // 身份只能从 JWT 里拿时，整体报错
+        auth["tokens"].as_object_mut().unwrap().remove("account_id");
+        let err = identify(&store, &auth.to_string()).unwrap_err();
+        let fields: Vec<&str> = err.errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            vec!["tokens.access_token", "tokens.id_token", "identity"]
+        );
+    }
+}
@@ -0,0 +1,381 @@
+//! `~/.codex/auth.json` 写入日志
+//!
+//! Codex、本工具、偶尔还有别人的脚本都会写 auth.json，出了问题很难说清"坏掉的那版是谁写的"。
+//! 这里往 `~/.codex-switcher/auth-journal.jsonl` 追加一行一条：
+//! - 本工具每次写盘（切号、冲突处理、刷新回写、Server 同步、手机锚、退出登录）记操作、
+//!   目标账号，以及写前 / 写后的指纹
+//! - 读盘时发现内容和日志里最后一次的指纹对不上，说明中间有别人写过，记一条 `external`
+//!
+//! 指纹是 access_token / refresh_token 各自 sha256 的前 16 位十六进制，不落任何 token 原文。
+//! 账号标识按 `log_identifier_style` 记（见 `log_identity`）。
+//! 超过 [`JOURNAL_CAP`] 条时整份轮转成 `auth-journal.jsonl.1`（只留一代），读的时候两份合并；
+//! 坏行直接跳过。日志写失败只打印，不影响 auth.json 本身的读写。
+
+use std::fs;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::log_identity::LoggedId;
+
+/// 单个文件最多这么多条，满了就轮转
+pub const JOURNAL_CAP: usize = 2000;
+/// 诊断包里附带最近这么多条
+pub const DIAGNOSTICS_ENTRIES: usize = 50;
+/// 找最后一条时从文件末尾读这么多字节（单条远小于此）
+const TAIL_BYTES: u64 = 8 * 1024;
+
+/// 追加 / 轮转串行化（进程内）
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+/// 这次写盘是谁、为什么
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthWriteOp {
+    /// 切号
+    Switch,
+    /// 冲突处理：用当前账号强制覆盖磁盘
+    Resolve,
+    /// 本机刷新 token 后回写
+    Refresh,
+    /// 从 Server 同步（client / solo 模式）
+    RemoteSync,
+    /// 手机锚设置 / 取消 / 退出时恢复真实 expires_at
+    Anchor,
+    /// 退出 Codex 登录（删除 auth.json）
+    Logout,
+    /// 修复向导补写缺失字段（见 `repair`）
+    Repair,
+    /// 不是本工具写的：读盘时发现的外部变更
+    External,
+    Other,
+}
+
+/// auth.json 内容指纹
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct Fingerprint {
+    pub access: Option<String>,
+    pub refresh: Option<String>,
+}
+
+impl Fingerprint {
+    /// ApiKey schema（Relay）没有 tokens 块，access 取 `OPENAI_API_KEY`
+    pub fn of(auth: &Value) -> Self {
+        let token = |key: &str| {
+            auth.pointer(&format!("/tokens/{}", key))
+                .and_then(|v| v.as_str())
+                .filter(|s| !s.is_empty())
+        };
+        let access = token("access_token").or_else(|| {
+            auth.get("OPENAI_API_KEY")
+                .and_then(|v| v.as_str())
+                .filter(|s| !s.is_empty())
+        });
+        Self {
+            access: access.map(short_sha256),
+            refresh: token("refresh_token").map(short_sha256),
+        }
+    }
+}
+
+fn short_sha256(text: &str) -> String {
+    Sha256::digest(text.as_bytes())
+        .iter()
+        .take(8)
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct JournalEntry {
+    pub at: DateTime<Utc>,
+    pub op: AuthWriteOp,
+    pub account_id: Option<LoggedId>,
+    /// 写前磁盘内容；文件原本不存在时为 None
+    pub before: Option<Fingerprint>,
+    /// 写后磁盘内容；删除时为 None
+    pub after: Option<Fingerprint>,
+}
+
+pub struct AuthJournal {
+    path: PathBuf,
+    cap: usize,
+}
+
+impl AuthJournal {
+    pub fn new(path: PathBuf, cap: usize) -> Self {
+        Self { path, cap }
+    }
+
+    /// `~/.codex-switcher/auth-journal.jsonl`（每次按当前数据目录现算，测试会改 HOME）
+    pub fn system() -> Self {
+        Self::new(
+            crate::paths::data_dir().join("auth-journal.jsonl"),
+            JOURNAL_CAP,
+        )
+    }
+
+    fn rotated_path(&self) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(".1");
+        PathBuf::from(name)
+    }
+
+    /// 记一次本工具的写盘
+    pub fn record_write(
+        &self,
+        op: AuthWriteOp,
+        account_id: Option<LoggedId>,
+        before: Option<&Value>,
+        after: Option<&Value>,
+    ) {
+        self.append(&JournalEntry {
+            at: Utc::now(),
+            op,
+            account_id,
+            before: before.map(Fingerprint::of),
+            after: after.map(Fingerprint::of),
+        });
+    }
+
+    /// 读盘后调用：内容和日志里最后一次记录的指纹不同就记一条 external，返回是否记了
+    pub fn observe(&self, disk: &Value) -> bool {
+        let current = Fingerprint::of(disk);
+        let last = self.last_entry();
+        if last.as_ref().and_then(|e| e.after.as_ref()) == Some(&current) {
+            return false;
+        }
+        self.append(&JournalEntry {
+            at: Utc::now(),
+            op: AuthWriteOp::External,
+            account_id: None,
+            before: last.and_then(|e| e.after),
+            after: Some(current),
+        });
+        true
+    }
+
+    /// 最近 `limit` 条，新的在前；坏行跳过
+    pub fn read(&self, limit: usize) -> Vec<JournalEntry> {
+        let mut entries: Vec<JournalEntry> = [self.rotated_path(), self.path.clone()]
+            .iter()
+            .filter_map(|p| fs::read_to_string(p).ok())
+            .flat_map(|content| parse_lines(&content))
+            .collect();
+        entries.reverse();
+        entries.truncate(limit);
+        entries
+    }
+
+    fn last_entry(&self) -> Option<JournalEntry> {
+        read_tail(&self.path)
+            .and_then(|tail| parse_lines(&tail).pop())
+            .or_else(|| read_tail(&self.rotated_path()).and_then(|tail| parse_lines(&tail).pop()))
+    }
+
+    fn append(&self, entry: &JournalEntry) {
+        if let Err(e) = self.try_append(entry) {
+            eprintln!("[AuthJournal] 写入日志失败: {}", e);
+        }
+    }
+
+    fn try_append(&self, entry: &JournalEntry) -> Result<(), String> {
+        let _guard = WRITE_LOCK.lock().map_err(|e| e.to_string())?;
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("创建目录失败: {}", e))?;
+        }
+        let line = serde_json::to_string(entry).map_err(|e| format!("序列化失败: {}", e))?;
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|e| format!("打开日志失败: {}", e))?;
+        // 上次写到一半（崩溃 / 磁盘满）留下的残行先补个换行，别把这条也拖坏
+        if ends_mid_line(&mut file) {
+            writeln!(file).map_err(|e| format!("写入日志失败: {}", e))?;
+        }
+        writeln!(file, "{}", line).map_err(|e| format!("写入日志失败: {}", e))?;
+        drop(file);
+
+        let lines = fs::read_to_string(&self.path)
+            .map(|c| c.lines().count())
+            .unwrap_or(0);
+        if lines >= self.cap {
+            fs::rename(&self.path, self.rotated_path())
+                .map_err(|e| format!("轮转日志失败: {}", e))?;
+        }
+        Ok(())
+    }
+}
+
+fn ends_mid_line(file: &mut fs::File) -> bool {
+    let mut last = [0u8; 1];
+    file.seek(SeekFrom::End(-1)).is_ok() && file.read_exact(&mut last).is_ok() && last[0] != b'\n'
+}
+
+fn parse_lines(content: &str) -> Vec<JournalEntry> {
+    content
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+/// 文件末尾最多 [`TAIL_BYTES`] 字节（首行可能被截断，解析时会被当坏行跳过）
+fn read_tail(path: &Path) -> Option<String> {
+    let mut file = fs::File::open(path).ok()?;
+    let len = file.metadata().ok()?.len();
+    file.seek(SeekFrom::Start(len.saturating_sub(TAIL_BYTES)))
+        .ok()?;
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf).ok()?;
+    Some(String::from_utf8_lossy(&buf).into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn full(raw: &str) -> LoggedId {
+        crate::log_identity::format(crate::log_identity::LogIdentifierStyle::Full, raw)
+    }
+
+    fn journal(cap: usize) -> (AuthJournal, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("cs-journal-{}", uuid::Uuid::new_v4()));
+        (AuthJournal::new(dir.join("auth-journal.jsonl"), cap), dir)
+    }
+
+    fn auth(access: &str, refresh: &str) -> Value {
+        serde_json::json!({
+            "tokens": { "access_token": access, "refresh_token": refresh },
+            "last_refresh": Utc::now().to_rfc3339(),
+        })
+    }
+
+    #[test]
+    fn fingerprint_is_stable_and_hides_tokens() {
+        let a = Fingerprint::of(&auth("at-1", "rt-1"));
+        // 只看 token，last_refresh / expires_at 之类的变化不算
+        let mut patched = auth("at-1", "rt-1");
+        patched["tokens"]["expires_at"] = serde_json::json!("2099-01-01T00:00:00Z");
+        assert_eq!(Fingerprint::of(&patched), a);
+        assert_eq!(a.access.as_deref().map(str::len), Some(16));
+        assert_ne!(a.access, a.refresh);
+        assert_ne!(Fingerprint::of(&auth("at-1", "rt-2")), a);
+
+        let api_key = serde_json::json!({ "OPENAI_API_KEY": "at-1" });
+        assert_eq!(Fingerprint::of(&api_key).access, a.access);
+        assert_eq!(Fingerprint::of(&api_key).refresh, None);
+
+        let json = serde_json::to_string(&a).unwrap();
+        assert!(!json.contains("at-1") && !json.contains("rt-1"));
+    }
+
+    #[test]
+    fn records_writes_and_external_changes() {
+        let (journal, dir) = journal(JOURNAL_CAP);
+        let first = auth("at-1", "rt-1");
+        // 第一次读盘：日志里还没有记录，算外部写入
+        assert!(journal.observe(&first));
+        assert!(!journal.observe(&first), "内容没变不重复记");
+
+        let second = auth("at-2", "rt-2");
+        journal.record_write(
+            AuthWriteOp::Switch,
+            Some(full("acc-2")),
+            Some(&first),
+            Some(&second),
+        );
+        assert!(!journal.observe(&second), "自己写的不算外部变更");
+
+        // Codex 轮换了 rt
+        let rotated = auth("at-3", "rt-3");
+        assert!(journal.observe(&rotated));
+
+        let entries = journal.read(10);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].op, AuthWriteOp::External);
+        assert_eq!(entries[0].before, Some(Fingerprint::of(&second)));
+        assert_eq!(entries[0].after, Some(Fingerprint::of(&rotated)));
+        assert_eq!(entries[1].op, AuthWriteOp::Switch);
+        assert_eq!(entries[1].account_id.as_deref(), Some("acc-2"));
+        assert_eq!(entries[1].before, Some(Fingerprint::of(&first)));
+        assert_eq!(journal.read(1).len(), 1);
+
+        journal.record_write(AuthWriteOp::Logout, None, Some(&rotated), None);
+        assert_eq!(journal.read(1)[0].after, None);
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn rotates_at_cap() {
+        let (journal, dir) = journal(3);
+        for i in 0..4 {
+            journal.record_write(
+                AuthWriteOp::Refresh,
+                Some(full(&format!("acc-{}", i))),
+                None,
+                Some(&auth(&format!("at-{}", i), "rt")),
+            );
+        }
+        // 第 3 条写完轮转，第 4 条进新文件
+        let rotated = fs::read_to_string(journal.rotated_path()).unwrap();
+        assert_eq!(rotated.lines().count(), 3);
+        assert_eq!(
+            fs::read_to_string(&journal.path).unwrap().lines().count(),
+            1
+        );
+
+        let entries = journal.read(10);
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[0].account_id.as_deref(), Some("acc-3"));
+        assert_eq!(entries[3].account_id.as_deref(), Some("acc-0"));
+
+        // 再满一次，最老的一代被覆盖
+        for i in 4..7 {
+            journal.record_write(
+                AuthWriteOp::Refresh,
+                Some(full(&format!("acc-{}", i))),
+                None,
+                Some(&auth(&format!("at-{}", i), "rt")),
+            );
+        }
+        let entries = journal.read(10);
+        assert_eq!(entries.len(), 4);
+        assert_eq!(entries[0].account_id.as_deref(), Some("acc-6"));
+        assert_eq!(entries[3].account_id.as_deref(), Some("acc-3"));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn skips_corrupt_lines() {
+        let (journal, dir) = journal(JOURNAL_CAP);
+        let first = auth("at-1", "rt-1");
+        journal.record_write(AuthWriteOp::Switch, Some(full("acc-1")), None, Some(&first));
+        let mut file = fs::OpenOptions::new()
+            .append(true)
+            .open(&journal.path)
+            .unwrap();
+        writeln!(file, "not json").unwrap();
+        writeln!(file, r#"{{"at":"2026-01-01T00:00:00Z","op":"unknown_op"}}"#).unwrap();
+        write!(file, r#"{{"at":"2026-01-01T00:00:00Z","op":"sw"#).unwrap();
+        drop(file);
+
+        let entries = journal.read(10);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].account_id.as_deref(), Some("acc-1"));
+        // 坏行之后仍按最后一条好记录判断外部变更
+        assert!(!journal.observe(&first));
+        // 残行后面接着写不受影响
+        journal.record_write(AuthWriteOp::Switch, Some(full("acc-2")), None, Some(&first));
+        let entries = journal.read(10);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].account_id.as_deref(), Some("acc-2"));
+        let _ = fs::remove_dir_all(dir);
+    }
+}
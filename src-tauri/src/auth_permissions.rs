@@ -0,0 +1,263 @@
+//! `~/.codex/auth.json` 权限漂移的检查与自愈
+//!
+//! 有些系统上 Codex 会用 0644 重写 auth.json，本程序下次写盘之前 token 一直是组 / 其他用户可读。
+//! scheduler 每轮同步当前账号时本来就要读这个文件，读成功后顺手看一眼（[`check_and_heal`]）：
+//! - auth.json 比 0600 宽 → 收紧到 0600
+//! - `~/.codex` 比 0700 宽 → 收紧到 0700
+//!
+//! 收紧前的权限写日志；设置项 `leave_auth_permissions` 打开时完全不碰。
+//! 每次收紧都记进 [`DriftTracker`]（进诊断包的健康信息）：同一天反复出现说明有别的程序一直在改回去，
+//! 超过 [`DAILY_NOTIFY_THRESHOLD`] 次当天提醒一次。非 Unix 平台直接跳过。
+
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+
+use chrono::{DateTime, Local, NaiveDate};
+use serde::Serialize;
+
+/// auth.json 目标权限
+pub const FILE_MODE: u32 = 0o600;
+
+/// `~/.codex` 目标权限
+pub const DIR_MODE: u32 = 0o700;
+
+/// 一天内收紧超过这么多次就提醒（当天只提醒一次）
+pub const DAILY_NOTIFY_THRESHOLD: u32 = 3;
+
+/// 一次收紧
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Drift {
+    pub path: PathBuf,
+    /// 收紧前的权限位（如 0o644）
+    pub previous_mode: u32,
+    pub mode: u32,
+}
+
+/// 检查 auth.json 与所在目录的权限，比目标宽就收紧；`leave` 为 true 时什么都不做。
+/// 返回实际收紧了哪些（收紧失败的只写日志，不算）
+#[cfg(unix)]
+pub fn check_and_heal(auth_path: &Path, dir: &Path, leave: bool) -> Vec<Drift> {
+    use std::os::unix::fs::PermissionsExt;
+
+    if leave {
+        return Vec::new();
+    }
+    let mut healed = Vec::new();
+    for (path, target) in [(auth_path, FILE_MODE), (dir, DIR_MODE)] {
+        let Ok(meta) = std::fs::metadata(path) else {
+            continue;
+        };
+        let previous_mode = meta.permissions().mode() & 0o777;
+        if previous_mode & !target == 0 {
+            continue;
+        }
+        // 只去掉多出来的位，原本更严的保持不变
+        let mode = previous_mode & target;
+        match std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)) {
+            Ok(()) => {
+                println!(
+                    "[AuthPermissions] {} 权限被放宽为 {:o}，已收紧到 {:o}",
+                    path.display(),
+                    previous_mode,
+                    mode
+                );
+                healed.push(Drift {
+                    path: path.to_path_buf(),
+                    previous_mode,
+                    mode,
+                });
+            }
+            Err(e) => eprintln!(
+                "[AuthPermissions] 收紧 {} 权限失败（当前 {:o}）: {}",
+                path.display(),
+                previous_mode,
+                e
+            ),
+        }
+    }
+    healed
+}
+
+#[cfg(not(unix))]
+pub fn check_and_heal(_auth_path: &Path, _dir: &Path, _leave: bool) -> Vec<Drift> {
+    Vec::new()
+}
+
+#[derive(Default)]
+struct Inner {
+    total: u64,
+    day: Option<NaiveDate>,
+    today: u32,
+    notified_day: Option<NaiveDate>,
+    last_at: Option<DateTime<Local>>,
+    last: Vec<Drift>,
+}
+
+/// 健康信息里的权限漂移部分
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct DriftHealth {
+    /// 启动以来收紧的总次数（文件和目录分别计）
+    pub total: u64,
+    /// 今天收紧的次数
+    pub today: u32,
+    pub last_at: Option<DateTime<Local>>,
+    pub last: Vec<Drift>,
+}
+
+/// 收紧次数统计
+#[derive(Default)]
+pub struct DriftTracker {
+    inner: Mutex<Inner>,
+}
+
+impl DriftTracker {
+    /// 记下一次检查的结果；今天的次数刚超过阈值（且今天还没提醒过）时返回 true
+    pub fn record(&self, drifts: &[Drift], now: DateTime<Local>) -> bool {
+        if drifts.is_empty() {
+            return false;
+        }
+        let Ok(mut inner) = self.inner.lock() else {
+            return false;
+        };
+        let today = now.date_naive();
+        if inner.day != Some(today) {
+            inner.day = Some(today);
+            inner.today = 0;
+        }
+        inner.total += drifts.len() as u64;
+        inner.today += drifts.len() as u32;
+        inner.last_at = Some(now);
+        inner.last = drifts.to_vec();
+        if inner.today > DAILY_NOTIFY_THRESHOLD && inner.notified_day != Some(today) {
+            inner.notified_day = Some(today);
+            return true;
+        }
+        false
+    }
+
+    pub fn health(&self) -> DriftHealth {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let today = Local::now().date_naive();
+        DriftHealth {
+            total: inner.total,
+            today: if inner.day == Some(today) {
+                inner.today
+            } else {
+                0
+            },
+            last_at: inner.last_at,
+            last: inner.last.clone(),
+        }
+    }
+}
+
+pub fn global() -> &'static DriftTracker {
+    static TRACKER: OnceLock<DriftTracker> = OnceLock::new();
+    TRACKER.get_or_init(DriftTracker::default)
+}
+
+/// 反复漂移时的通知正文
+pub fn notification_body(today: u32) -> String {
+    format!(
+        "今天已 {} 次发现 ~/.codex/auth.json 的权限被放宽并自动收紧，可能有其它程序在反复改写它。",
+        today
+    )
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn loose_codex_dir() -> (PathBuf, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("cs-auth-perm-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let auth = dir.join("auth.json");
+        std::fs::write(&auth, "{}").unwrap();
+        std::fs::set_permissions(&auth, std::fs::Permissions::from_mode(0o644)).unwrap();
+        (dir, auth)
+    }
+
+    fn mode(p: &Path) -> u32 {
+        std::fs::metadata(p).unwrap().permissions().mode() & 0o777
+    }
+
+    #[test]
+    fn loose_file_and_dir_are_tightened() {
+        let (dir, auth) = loose_codex_dir();
+
+        let healed = check_and_heal(&auth, &dir, false);
+        assert_eq!(
+            healed,
+            vec![
+                Drift {
+                    path: auth.clone(),
+                    previous_mode: 0o644,
+                    mode: 0o600
+                },
+                Drift {
+                    path: dir.clone(),
+                    previous_mode: 0o755,
+                    mode: 0o700
+                },
+            ]
+        );
+        assert_eq!(mode(&auth), 0o600);
+        assert_eq!(mode(&dir), 0o700);
+        assert!(
+            check_and_heal(&auth, &dir, false).is_empty(),
+            "已收紧的不再动"
+        );
+
+        // 比目标更严的保持原样
+        std::fs::set_permissions(&auth, std::fs::Permissions::from_mode(0o400)).unwrap();
+        assert!(check_and_heal(&auth, &dir, false).is_empty());
+        assert_eq!(mode(&auth), 0o400);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn opt_out_leaves_permissions_alone() {
+        let (dir, auth) = loose_codex_dir();
+        assert!(check_and_heal(&auth, &dir, true).is_empty());
+        assert_eq!(mode(&auth), 0o644);
+        assert_eq!(mode(&dir), 0o755);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn repeated_drift_notifies_once_per_day() {
+        let tracker = DriftTracker::default();
+        let drift = |p: &str| Drift {
+            path: PathBuf::from(p),
+            previous_mode: 0o644,
+            mode: 0o600,
+        };
+        let day1 = Local.with_ymd_and_hms(2026, 3, 1, 9, 0, 0).unwrap();
+
+        assert!(!tracker.record(&[], day1));
+        let notified: Vec<bool> = (0..DAILY_NOTIFY_THRESHOLD + 2)
+            .map(|_| tracker.record(&[drift("auth.json")], day1))
+            .collect();
+        assert_eq!(notified.iter().filter(|n| **n).count(), 1);
+        assert!(
+            notified[DAILY_NOTIFY_THRESHOLD as usize],
+            "刚超过阈值那次提醒"
+        );
+        assert_eq!(
+            tracker.health().total,
+            u64::from(DAILY_NOTIFY_THRESHOLD) + 2
+        );
+
+        // 第二天重新计数
+        let day2 = day1 + chrono::Duration::days(1);
+        let both = [drift("auth.json"), drift(".codex")];
+        assert!(!tracker.record(&both, day2));
+        assert!(tracker.record(&both, day2), "文件和目录分别计数");
+        let inner = tracker.inner.lock().unwrap();
+        assert_eq!(inner.today, 4);
+        assert_eq!(inner.total, u64::from(DAILY_NOTIFY_THRESHOLD) + 6);
+    }
+}
@@ -0,0 +1,21 @@
+//! Shell 提示符状态行：`codex-switcher-status [--format "<模板>"]`
+//!
+//! 只读本地 accounts.json，不联网；没有账号库时什么都不输出，退出码始终为 0。
+//! 模板语法见 `codex_switcher_lib::status_line`。
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let mut format = None;
+    while let Some(arg) = args.next() {
+        if arg == "--format" || arg == "-f" {
+            format = args.next();
+        } else if let Some(v) = arg.strip_prefix("--format=") {
+            format = Some(v.to_string());
+        }
+    }
+
+    let line = codex_switcher_lib::status_line::from_disk(format.as_deref());
+    if !line.is_empty() {
+        println!("{}", line);
+    }
+}
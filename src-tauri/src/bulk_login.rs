@@ -0,0 +1,554 @@
+//! 批量 OAuth 登录队列
+//!
+//! 新机器上要连着登录五六个账号，每个都要"发起登录 → 浏览器 → 回到应用完成"守着点。
+//! `start_bulk_login` 给一串条目（每条可带 login_hint 邮箱），这里串行跑：
+//! 一条登录落库（走和单次登录一样的 `save_token_as_account`，同身份更新而不是重复建）后
+//! 自动发起下一条，沿途发 [`PROGRESS_EVENT`]，最后返回 [`BulkLoginSummary`]。
+//!
+//! - 每条有超时（默认 [`DEFAULT_STEP_TIMEOUT`]），超时或失败发 [`STEP_FAILED_EVENT`] 后跳到下一条
+//! - 暂停在当前这条结束后生效；取消立即放弃当前这条，剩下的记为未尝试
+//! - 同一时间只允许一个批量会话；会话进行中单次登录命令（`start_oauth_login`）直接拒绝，
+//!   不并入队列——两边抢同一个回调端口和 PKCE 状态只会互相打断。
+//!   手动粘贴回调链接（`submit_oauth_callback`）照常可用，授权码交给当前这条
+//!
+//! 队列本身不关心 OAuth 怎么跑，真实登录经 [`LoginDriver`] 注入（实现在 `lib.rs`），测试换成假实现。
+
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+use std::time::Duration;
+
+use futures_util::future::BoxFuture;
+use serde::Serialize;
+use tokio::sync::Notify;
+
+use crate::service::EventSink;
+
+/// 进度事件名
+pub const PROGRESS_EVENT: &str = "bulk-login-progress";
+/// 单条失败 / 超时事件名
+pub const STEP_FAILED_EVENT: &str = "bulk-login-step-failed";
+/// 每条登录默认最多等多久（和回调监听的时限一致）
+pub const DEFAULT_STEP_TIMEOUT: Duration = Duration::from_secs(180);
+/// 一次最多排多少条
+pub const MAX_ENTRIES: usize = 50;
+
+/// 跑一次完整登录：发起授权 → 等回调 → 换 token → 落库，返回账号名
+pub trait LoginDriver: Send + Sync {
+    fn login<'a>(&'a self, hint: Option<&'a str>) -> BoxFuture<'a, Result<String, String>>;
+}
+
+/// 单条的结果
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum StepOutcome {
+    Succeeded { account: String },
+    Failed { error: String },
+    TimedOut,
+    Cancelled,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct StepReport {
+    pub index: usize,
+    pub hint: Option<String>,
+    #[serde(flatten)]
+    pub outcome: StepOutcome,
+}
+
+/// [`PROGRESS_EVENT`] 负载，也是 `get_bulk_login_status` 的返回
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct BulkLoginProgress {
+    /// 已成功的条数
+    pub completed: usize,
+    pub failed: usize,
+    pub total: usize,
+    pub last_account: Option<String>,
+    /// 下一条（或正在跑的那条）的序号和 hint；全部跑完为 None
+    pub next_index: Option<usize>,
+    pub next_hint: Option<String>,
+    pub paused: bool,
+}
+
+/// 整个会话的结果
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct BulkLoginSummary {
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    /// 取消时还没轮到的条数
+    pub not_attempted: usize,
+    pub cancelled: bool,
+    pub steps: Vec<StepReport>,
+}
+
+#[derive(Default)]
+struct ControlState {
+    paused: bool,
+    cancelled: bool,
+    progress: BulkLoginProgress,
+}
+
+/// 正在跑的会话：命令线程改暂停 / 取消，队列线程在 `changed` 上等
+#[derive(Default)]
+struct Control {
+    state: Mutex<ControlState>,
+    changed: Notify,
+}
+
+impl Control {
+    fn lock(&self) -> MutexGuard<'_, ControlState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// 等到取消
+    async fn cancelled(&self) {
+        loop {
+            let changed = self.changed.notified();
+            if self.lock().cancelled {
+                return;
+            }
+            changed.await;
+        }
+    }
+
+    /// 暂停中就等到继续（或取消）；返回是否已取消
+    async fn wait_unpaused(&self, events: &dyn EventSink) -> bool {
+        let mut announced = false;
+        loop {
+            let changed = self.changed.notified();
+            {
+                let mut state = self.lock();
+                if state.cancelled {
+                    return true;
+                }
+                state.progress.paused = state.paused;
+                if !state.paused {
+                    if announced {
+                        emit(events, PROGRESS_EVENT, &state.progress);
+                    }
+                    return false;
+                }
+                if !announced {
+                    emit(events, PROGRESS_EVENT, &state.progress);
+                    announced = true;
+                }
+            }
+            changed.await;
+        }
+    }
+}
+
+/// 批量登录会话槽（同一时间最多一个）
+#[derive(Default)]
+pub struct BulkLogin {
+    active: Mutex<Option<Arc<Control>>>,
+}
+
+/// 会话结束（含 future 被丢弃）时腾出槽位
+struct ActiveGuard<'a>(&'a BulkLogin);
+
+impl Drop for ActiveGuard<'_> {
+    fn drop(&mut self) {
+        *self.0.slot() = None;
+    }
+}
+
+impl BulkLogin {
+    fn slot(&self) -> MutexGuard<'_, Option<Arc<Control>>> {
+        self.active.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.slot().is_some()
+    }
+
+    /// 单次登录入口调用：批量会话进行中时拒绝
+    pub fn ensure_idle(&self) -> Result<(), String> {
+        if self.is_active() {
+            return Err("批量登录进行中，请等它结束或取消后再单独登录".to_string());
+        }
+        Ok(())
+    }
+
+    fn with_active(&self, f: impl FnOnce(&mut ControlState)) -> Result<(), String> {
+        let control = self.slot().clone().ok_or("没有进行中的批量登录")?;
+        f(&mut control.lock());
+        control.changed.notify_waiters();
+        Ok(())
+    }
+
+    /// 暂停：当前这条跑完后停下
+    pub fn pause(&self) -> Result<(), String> {
+        self.with_active(|s| s.paused = true)
+    }
+
+    pub fn resume(&self) -> Result<(), String> {
+        self.with_active(|s| s.paused = false)
+    }
+
+    /// 取消：放弃当前这条，剩下的不再跑
+    pub fn cancel(&self) -> Result<(), String> {
+        self.with_active(|s| s.cancelled = true)
+    }
+
+    pub fn status(&self) -> Option<BulkLoginProgress> {
+        let control = self.slot().clone()?;
+        let state = control.lock();
+        Some(BulkLoginProgress {
+            paused: state.paused,
+            ..state.progress.clone()
+        })
+    }
+
+    /// 跑完整个队列。`hints` 每项一条登录，`None` 表示不带 login_hint
+    pub async fn run(
+        &self,
+        driver: &dyn LoginDriver,
+        events: &dyn EventSink,
+        hints: Vec<Option<String>>,
+        step_timeout: Duration,
+    ) -> Result<BulkLoginSummary, String> {
+        if hints.is_empty() {
+            return Err("批量登录至少要一条".to_string());
+        }
+        if hints.len() > MAX_ENTRIES {
+            return Err(format!("批量登录一次最多 {} 条", MAX_ENTRIES));
+        }
+        let control = {
+            let mut slot = self.slot();
+            if slot.is_some() {
+                return Err("已有批量登录在进行".to_string());
+            }
+            let control = Arc::new(Control::default());
+            *slot = Some(control.clone());
+            control
+        };
+        let _guard = ActiveGuard(self);
+
+        let total = hints.len();
+        let mut steps = Vec::new();
+        let mut cancelled = false;
+        for (index, hint) in hints.into_iter().enumerate() {
+            {
+                let mut state = control.lock();
+                state.progress.total = total;
+                state.progress.next_index = Some(index);
+                state.progress.next_hint = hint.clone();
+            }
+            if control.wait_unpaused(events).await {
+                cancelled = true;
+                break;
+            }
+            emit(events, PROGRESS_EVENT, &control.lock().progress);
+
+            let step = tokio::time::timeout(step_timeout, driver.login(hint.as_deref()));
+            let outcome = tokio::select! {
+                result = step => match result {
+                    Ok(Ok(account)) => StepOutcome::Succeeded { account },
+                    Ok(Err(error)) => StepOutcome::Failed { error },
+                    Err(_) => StepOutcome::TimedOut,
+                },
+                _ = control.cancelled() => StepOutcome::Cancelled,
+            };
+            let report = StepReport {
+                index,
+                hint,
+                outcome,
+            };
+            {
+                let mut state = control.lock();
+                match &report.outcome {
+                    StepOutcome::Succeeded { account } => {
+                        println!(
+                            "[BulkLogin] 第 {} 条登录成功: {}",
+                            index + 1,
+                            crate::log_identity::id(account)
+                        );
+                        state.progress.completed += 1;
+                        state.progress.last_account = Some(account.clone());
+                    }
+                    StepOutcome::Cancelled => cancelled = true,
+                    _ => {
+                        eprintln!(
+                            "[BulkLogin] 第 {} 条登录失败: {:?}",
+                            index + 1,
+                            report.outcome
+                        );
+                        state.progress.failed += 1;
+                        emit(events, STEP_FAILED_EVENT, &report);
+                    }
+                }
+            }
+            steps.push(report);
+            if cancelled {
+                break;
+            }
+        }
+
+        let progress = {
+            let mut state = control.lock();
+            state.progress.next_index = None;
+            state.progress.next_hint = None;
+            state.progress.paused = false;
+            state.progress.clone()
+        };
+        emit(events, PROGRESS_EVENT, &progress);
+        let attempted = steps
+            .iter()
+            .filter(|s| s.outcome != StepOutcome::Cancelled)
+            .count();
+        Ok(BulkLoginSummary {
+            total,
+            succeeded: progress.completed,
+            failed: progress.failed,
+            not_attempted: total - attempted,
+            cancelled,
+            steps,
+        })
+    }
+}
+
+fn emit(events: &dyn EventSink, event: &str, payload: &impl Serialize) {
+    match serde_json::to_value(payload) {
+        Ok(payload) => events.emit(event, payload),
+        Err(e) => eprintln!("[BulkLogin] 序列化 {} 失败: {}", event, e),
+    }
+}
+
+pub fn global() -> &'static BulkLogin {
+    static BULK: OnceLock<BulkLogin> = OnceLock::new();
+    BULK.get_or_init(BulkLogin::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::AppEvent;
+    use std::collections::VecDeque;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<(String, serde_json::Value)>>);
+
+    impl EventSink for Recorder {
+        fn emit(&self, event: &str, payload: serde_json::Value) {
+            self.0.lock().unwrap().push((event.to_string(), payload));
+        }
+        fn publish(&self, _event: AppEvent) {}
+    }
+
+    impl Recorder {
+        fn named(&self, name: &str) -> Vec<serde_json::Value> {
+            self.0
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|(n, _)| n == name)
+                .map(|(_, p)| p.clone())
+                .collect()
+        }
+    }
+
+    /// 按顺序回放预设结果；`None` 表示一直卡住（等超时 / 取消）
+    #[derive(Default)]
+    struct FakeDriver {
+        replies: Mutex<VecDeque<Option<Result<String, String>>>>,
+        calls: Mutex<Vec<Option<String>>>,
+        /// 每次被调用时通知一下，测试据此在"正在跑某条"时动手
+        started: Notify,
+    }
+
+    impl FakeDriver {
+        fn new(replies: Vec<Option<Result<String, String>>>) -> Self {
+            Self {
+                replies: Mutex::new(replies.into()),
+                ..Default::default()
+            }
+        }
+
+        fn calls(&self) -> Vec<Option<String>> {
+            self.calls.lock().unwrap().clone()
+        }
+    }
+
+    impl LoginDriver for FakeDriver {
+        fn login<'a>(&'a self, hint: Option<&'a str>) -> BoxFuture<'a, Result<String, String>> {
+            self.calls.lock().unwrap().push(hint.map(str::to_string));
+            let reply = self.replies.lock().unwrap().pop_front().flatten();
+            self.started.notify_one();
+            Box::pin(async move {
+                match reply {
+                    Some(result) => result,
+                    None => std::future::pending().await,
+                }
+            })
+        }
+    }
+
+    fn hints(list: &[Option<&str>]) -> Vec<Option<String>> {
+        list.iter().map(|h| h.map(str::to_string)).collect()
+    }
+
+    #[tokio::test]
+    async fn runs_every_entry_in_order_and_reports_progress() {
+        let bulk = BulkLogin::default();
+        let driver = FakeDriver::new(vec![
+            Some(Ok("a@example.com".into())),
+            Some(Err("exchange_code 失败".into())),
+            Some(Ok("c@example.com".into())),
+        ]);
+        let events = Recorder::default();
+        let summary = bulk
+            .run(
+                &driver,
+                &events,
+                hints(&[Some("a@example.com"), None, Some("c@example.com")]),
+                Duration::from_secs(5),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            driver.calls(),
+            hints(&[Some("a@example.com"), None, Some("c@example.com")])
+        );
+        assert_eq!((summary.succeeded, summary.failed), (2, 1));
+        assert_eq!(summary.not_attempted, 0);
+        assert!(!summary.cancelled);
+        assert_eq!(
+            summary.steps[1].outcome,
+            StepOutcome::Failed {
+                error: "exchange_code 失败".into()
+            }
+        );
+
+        let progress = events.named(PROGRESS_EVENT);
+        assert_eq!(progress[1]["last_account"], "a@example.com");
+        assert_eq!(progress[1]["next_index"], 1);
+        assert_eq!(progress[2]["next_hint"], "c@example.com");
+        let last = progress.last().unwrap();
+        assert_eq!(
+            (last["completed"].as_u64(), last["total"].as_u64()),
+            (Some(2), Some(3))
+        );
+        assert!(last["next_index"].is_null());
+        assert_eq!(events.named(STEP_FAILED_EVENT).len(), 1);
+        assert!(!bulk.is_active(), "跑完腾出槽位");
+    }
+
+    #[tokio::test]
+    async fn step_timeout_skips_to_next_entry() {
+        let bulk = BulkLogin::default();
+        let driver = FakeDriver::new(vec![None, Some(Ok("b@example.com".into()))]);
+        let events = Recorder::default();
+        let summary = bulk
+            .run(
+                &driver,
+                &events,
+                hints(&[Some("stuck@example.com"), Some("b@example.com")]),
+                Duration::from_millis(50),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(summary.steps[0].outcome, StepOutcome::TimedOut);
+        assert_eq!(
+            summary.steps[1].outcome,
+            StepOutcome::Succeeded {
+                account: "b@example.com".into()
+            }
+        );
+        let failed = events.named(STEP_FAILED_EVENT);
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0]["status"], "timed_out");
+        assert_eq!(failed[0]["hint"], "stuck@example.com");
+    }
+
+    #[tokio::test]
+    async fn cancel_mid_queue_abandons_current_and_remaining() {
+        let bulk = Arc::new(BulkLogin::default());
+        let driver = Arc::new(FakeDriver::new(vec![
+            Some(Ok("a@example.com".into())),
+            None,
+        ]));
+        let events = Arc::new(Recorder::default());
+        let run = {
+            let (bulk, driver, events) = (bulk.clone(), driver.clone(), events.clone());
+            tokio::spawn(async move {
+                bulk.run(
+                    &*driver,
+                    &*events,
+                    hints(&[None, None, None, None]),
+                    Duration::from_secs(60),
+                )
+                .await
+            })
+        };
+
+        // 等第二条开始跑（第一条立即成功）
+        while driver.calls().len() < 2 {
+            driver.started.notified().await;
+        }
+        bulk.cancel().unwrap();
+        let summary = run.await.unwrap().unwrap();
+
+        assert!(summary.cancelled);
+        assert_eq!(summary.succeeded, 1);
+        assert_eq!(summary.steps[1].outcome, StepOutcome::Cancelled);
+        assert_eq!(summary.not_attempted, 3, "被取消的那条和后面两条");
+        assert_eq!(driver.calls().len(), 2);
+        assert!(events.named(STEP_FAILED_EVENT).is_empty(), "取消不算失败");
+        assert!(bulk.cancel().is_err(), "结束后没有可取消的会话");
+    }
+
+    #[tokio::test]
+    async fn only_one_session_and_pause_holds_the_next_entry() {
+        let bulk = Arc::new(BulkLogin::default());
+        assert!(bulk.ensure_idle().is_ok());
+        assert!(bulk.pause().is_err());
+
+        let driver = Arc::new(FakeDriver::new(vec![
+            None,
+            Some(Ok("b@example.com".into())),
+        ]));
+        let events = Arc::new(Recorder::default());
+        let run = {
+            let (bulk, driver, events) = (bulk.clone(), driver.clone(), events.clone());
+            tokio::spawn(async move {
+                bulk.run(
+                    &*driver,
+                    &*events,
+                    hints(&[None, None]),
+                    Duration::from_millis(200),
+                )
+                .await
+            })
+        };
+        while driver.calls().is_empty() {
+            driver.started.notified().await;
+        }
+
+        // 会话进行中：单次登录和第二个批量会话都被拒
+        assert!(bulk.ensure_idle().is_err());
+        let other = FakeDriver::default();
+        let err = bulk
+            .run(&other, &*events, hints(&[None]), Duration::from_secs(1))
+            .await
+            .unwrap_err();
+        assert!(err.contains("已有批量登录"), "{}", err);
+        assert!(other.calls().is_empty());
+
+        // 暂停在第一条超时后生效，第二条等继续后才开始
+        bulk.pause().unwrap();
+        tokio::time::sleep(Duration::from_millis(400)).await;
+        assert_eq!(driver.calls().len(), 1);
+        assert_eq!(bulk.status().map(|s| s.paused), Some(true));
+        assert!(events
+            .named(PROGRESS_EVENT)
+            .iter()
+            .any(|p| p["paused"] == true));
+
+        bulk.resume().unwrap();
+        let summary = run.await.unwrap().unwrap();
+        assert_eq!((summary.succeeded, summary.failed), (1, 1));
+        assert!(bulk.ensure_idle().is_ok());
+    }
+}
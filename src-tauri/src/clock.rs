@@ -0,0 +1,367 @@
+//! 系统时间可信度：统一的"现在" + 墙钟倒退检测
+//!
+//! 开机时 RTC 偏慢、NTP 还没校准的那段时间里，墙钟可能比真实时间早好几个小时：
+//! 刚刷出来的 token 算出的剩余寿命全错，"缓存已过期"的判断也跟着乱，严重时会反复刷新。
+//! 所有过期 / 陈旧判断都经 [`now`] 取时间，并在 [`is_skewed`] 期间暂停：
+//! - 按 access_token 过期时间触发的刷新（切号前刷新、取额度前的预检）
+//! - 按陈旧程度触发的刷新 / 缓存失效（非活跃保活、refresh_token 老化自动保活、
+//!   窗口 reset 后优先刷额度、日报前刷旧额度）
+//!
+//! 判定（[`evaluate`]，纯函数）：墙钟比"可信下限"早 [`SKEW_TOLERANCE_SECS`] 以上即为异常。
+//! 可信下限取两者较大值：
+//! - store 里落过盘的最新时间戳（last_refresh / updated_at / last_used）
+//! - 上次时间正常时的墙钟 + 之后单调时钟走过的时长
+//!
+//! 落盘时间戳比墙钟超前 [`MAX_TRUSTED_LEAD_DAYS`] 天以上的不算数：那多半是以前时钟超前时写进去的，
+//! 拿它当下限会一直报异常。
+//!
+//! 启动时检查一次；之后 `power` 的采样循环看到墙钟相对单调时钟跳变、或当前仍处于异常时再查。
+//! 时间从 [`WallClock`] 取，测试里换成假时钟。
+
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::account::AccountStore;
+
+/// 墙钟比可信下限早多少秒以内不算异常（容忍 NTP 微调、多端时间戳小误差）
+pub const SKEW_TOLERANCE_SECS: i64 = 5 * 60;
+
+/// 落盘时间戳最多比墙钟超前这么多天还当作可信下限
+pub const MAX_TRUSTED_LEAD_DAYS: i64 = 7;
+
+/// 墙钟与单调时钟的走时差超过该值才算跳变
+pub const JUMP_THRESHOLD_SECS: i64 = 90;
+
+/// 时间源：墙钟 + 单调时钟
+pub trait WallClock: Send + Sync {
+    fn now(&self) -> DateTime<Utc>;
+    fn instant(&self) -> Instant;
+}
+
+/// 真实系统时钟
+pub struct SystemWallClock;
+
+impl WallClock for SystemWallClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+
+    fn instant(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// 系统时间状态（进诊断包、托盘）
+#[derive(Serialize, Clone, Debug, PartialEq, Default)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum ClockHealth {
+    #[default]
+    Ok,
+    /// 墙钟比可信下限 `reference` 早了 `behind_secs` 秒
+    Skewed {
+        behind_secs: i64,
+        reference: DateTime<Utc>,
+    },
+}
+
+impl ClockHealth {
+    pub fn is_skewed(&self) -> bool {
+        matches!(self, Self::Skewed { .. })
+    }
+
+    /// 托盘 / 日志用的一句话说明；正常时为 None
+    pub fn label(&self) -> Option<String> {
+        match self {
+            Self::Ok => None,
+            Self::Skewed { behind_secs, .. } => Some(format!(
+                "系统时间异常（比已知时间早约 {} 分钟）",
+                (behind_secs + 59) / 60
+            )),
+        }
+    }
+}
+
+/// 可信时间下限；`last_good` 是上次时间正常时的墙钟及其后单调时钟走过的时长
+pub fn trusted_floor(
+    wall: DateTime<Utc>,
+    last_good: Option<(DateTime<Utc>, Duration)>,
+    max_persisted: Option<DateTime<Utc>>,
+) -> Option<DateTime<Utc>> {
+    let persisted =
+        max_persisted.filter(|ts| *ts <= wall + chrono::Duration::days(MAX_TRUSTED_LEAD_DAYS));
+    let running = last_good.and_then(|(at, mono)| {
+        chrono::Duration::from_std(mono)
+            .ok()
+            .map(|elapsed| at + elapsed)
+    });
+    persisted.max(running)
+}
+
+/// 判定当前墙钟是否可信
+pub fn evaluate(
+    wall: DateTime<Utc>,
+    last_good: Option<(DateTime<Utc>, Duration)>,
+    max_persisted: Option<DateTime<Utc>>,
+) -> ClockHealth {
+    match trusted_floor(wall, last_good, max_persisted) {
+        Some(floor) if (floor - wall).num_seconds() > SKEW_TOLERANCE_SECS => ClockHealth::Skewed {
+            behind_secs: (floor - wall).num_seconds(),
+            reference: floor,
+        },
+        _ => ClockHealth::Ok,
+    }
+}
+
+/// 一次采样间隔内墙钟相对单调时钟的跳变（秒，正为向前）；小于阈值返回 None。
+///
+/// 向前跳也可能只是睡眠唤醒（单调时钟不计睡眠），所以跳变只触发重新检查，不直接判异常。
+pub fn wall_jump_secs(wall_elapsed_secs: i64, mono_elapsed: Duration) -> Option<i64> {
+    let mono = i64::try_from(mono_elapsed.as_secs()).unwrap_or(i64::MAX);
+    let drift = wall_elapsed_secs.saturating_sub(mono);
+    (drift.abs() > JUMP_THRESHOLD_SECS).then_some(drift)
+}
+
+/// store 里落过盘的最新时间戳
+pub fn newest_persisted(store: &AccountStore) -> Option<DateTime<Utc>> {
+    store
+        .accounts
+        .values()
+        .flat_map(|a| {
+            [
+                AccountStore::extract_last_refresh(&a.auth_json),
+                a.updated_at,
+                a.last_used,
+            ]
+        })
+        .flatten()
+        .max()
+}
+
+struct Inner {
+    /// 上次判定正常时的墙钟和单调时钟
+    last_good: Option<(DateTime<Utc>, Instant)>,
+    health: ClockHealth,
+}
+
+pub struct ClockMonitor<C: WallClock = SystemWallClock> {
+    clock: C,
+    inner: Mutex<Inner>,
+}
+
+impl Default for ClockMonitor {
+    fn default() -> Self {
+        Self::with_clock(SystemWallClock)
+    }
+}
+
+impl<C: WallClock> ClockMonitor<C> {
+    pub fn with_clock(clock: C) -> Self {
+        Self {
+            clock,
+            inner: Mutex::new(Inner {
+                last_good: None,
+                health: ClockHealth::Ok,
+            }),
+        }
+    }
+
+    pub fn now(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
+    pub fn health(&self) -> ClockHealth {
+        self.inner
+            .lock()
+            .map(|inner| inner.health.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn is_skewed(&self) -> bool {
+        self.inner
+            .lock()
+            .map(|inner| inner.health.is_skewed())
+            .unwrap_or(false)
+    }
+
+    /// 重新判定；状态变化（正常 ↔ 异常）时返回新状态
+    pub fn check(&self, max_persisted: Option<DateTime<Utc>>) -> Option<ClockHealth> {
+        let wall = self.clock.now();
+        let mono = self.clock.instant();
+        let Ok(mut inner) = self.inner.lock() else {
+            return None;
+        };
+        let last_good = inner
+            .last_good
+            .map(|(at, seen)| (at, mono.saturating_duration_since(seen)));
+        let health = evaluate(wall, last_good, max_persisted);
+        if !health.is_skewed() {
+            inner.last_good = Some((wall, mono));
+        }
+        let changed = health.is_skewed() != inner.health.is_skewed();
+        inner.health = health.clone();
+        changed.then_some(health)
+    }
+}
+
+pub fn global() -> &'static ClockMonitor {
+    static MONITOR: OnceLock<ClockMonitor> = OnceLock::new();
+    MONITOR.get_or_init(ClockMonitor::default)
+}
+
+/// 过期 / 陈旧判断用的"现在"
+pub fn now() -> DateTime<Utc> {
+    global().now()
+}
+
+/// 系统时间异常期间，按过期时间 / 陈旧程度触发的刷新都应暂停
+pub fn is_skewed() -> bool {
+    global().is_skewed()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn at(ts: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(ts, 0).unwrap()
+    }
+
+    const T: i64 = 1_780_000_000;
+
+    #[test]
+    fn wall_behind_persisted_is_skew() {
+        let health = evaluate(at(T - 3 * 3600), None, Some(at(T)));
+        assert_eq!(
+            health,
+            ClockHealth::Skewed {
+                behind_secs: 3 * 3600,
+                reference: at(T)
+            }
+        );
+        assert_eq!(
+            health.label().unwrap(),
+            "系统时间异常（比已知时间早约 180 分钟）"
+        );
+    }
+
+    #[test]
+    fn small_lag_and_missing_reference_are_ok() {
+        assert_eq!(
+            evaluate(at(T - SKEW_TOLERANCE_SECS), None, Some(at(T))),
+            ClockHealth::Ok
+        );
+        assert_eq!(evaluate(at(T), None, None), ClockHealth::Ok);
+        assert_eq!(evaluate(at(T + 3600), None, Some(at(T))), ClockHealth::Ok);
+    }
+
+    #[test]
+    fn persisted_far_in_the_future_is_ignored() {
+        let poisoned = at(T + (MAX_TRUSTED_LEAD_DAYS + 1) * 86_400);
+        assert_eq!(evaluate(at(T), None, Some(poisoned)), ClockHealth::Ok);
+        // 落在可信窗口里的仍然算数
+        let recent = at(T + 86_400);
+        assert!(evaluate(at(T), None, Some(recent)).is_skewed());
+    }
+
+    #[test]
+    fn backward_jump_while_running_is_skew_even_without_persisted() {
+        // 上次正常在 T，之后单调时钟走了 10 分钟，墙钟却回到 T - 2h
+        let last_good = Some((at(T), Duration::from_secs(600)));
+        let health = evaluate(at(T - 7200), last_good, None);
+        assert_eq!(
+            health,
+            ClockHealth::Skewed {
+                behind_secs: 7200 + 600,
+                reference: at(T + 600)
+            }
+        );
+        // 正常走时：墙钟与单调时钟一致
+        assert_eq!(evaluate(at(T + 600), last_good, None), ClockHealth::Ok);
+    }
+
+    #[test]
+    fn floor_takes_the_later_reference() {
+        let last_good = Some((at(T), Duration::from_secs(60)));
+        assert_eq!(
+            trusted_floor(at(T), last_good, Some(at(T + 3600))),
+            Some(at(T + 3600))
+        );
+        assert_eq!(
+            trusted_floor(at(T), last_good, Some(at(T - 3600))),
+            Some(at(T + 60))
+        );
+        assert_eq!(trusted_floor(at(T), None, None), None);
+    }
+
+    #[test]
+    fn jumps_are_measured_against_monotonic_time() {
+        let thirty = Duration::from_secs(30);
+        assert_eq!(wall_jump_secs(30, thirty), None);
+        assert_eq!(wall_jump_secs(30 + JUMP_THRESHOLD_SECS, thirty), None);
+        assert_eq!(wall_jump_secs(3600 + 30, thirty), Some(3600));
+        assert_eq!(wall_jump_secs(30 - 7200, thirty), Some(-7200));
+    }
+
+    #[derive(Clone)]
+    struct FakeClock(Arc<Mutex<(DateTime<Utc>, Instant)>>);
+
+    impl FakeClock {
+        fn new(wall: DateTime<Utc>) -> Self {
+            Self(Arc::new(Mutex::new((wall, Instant::now()))))
+        }
+
+        /// 墙钟设为 `wall`，单调时钟前进 `mono`
+        fn set(&self, wall: DateTime<Utc>, mono: Duration) {
+            let mut t = self.0.lock().unwrap();
+            *t = (wall, t.1 + mono);
+        }
+    }
+
+    impl WallClock for FakeClock {
+        fn now(&self) -> DateTime<Utc> {
+            self.0.lock().unwrap().0
+        }
+
+        fn instant(&self) -> Instant {
+            self.0.lock().unwrap().1
+        }
+    }
+
+    #[test]
+    fn monitor_flags_skew_at_startup_and_recovers() {
+        // 开机时 RTC 慢了 3 小时，store 里有正常时间写下的时间戳
+        let clock = FakeClock::new(at(T - 3 * 3600));
+        let monitor = ClockMonitor::with_clock(clock.clone());
+        let changed = monitor.check(Some(at(T)));
+        assert!(changed.is_some_and(|h| h.is_skewed()));
+        assert!(monitor.is_skewed());
+        // 仍异常时再查不算状态变化
+        clock.set(at(T - 3 * 3600 + 30), Duration::from_secs(30));
+        assert_eq!(monitor.check(Some(at(T))), None);
+
+        // NTP 校准
+        clock.set(at(T + 60), Duration::from_secs(30));
+        assert_eq!(monitor.check(Some(at(T))), Some(ClockHealth::Ok));
+        assert!(!monitor.is_skewed());
+    }
+
+    #[test]
+    fn monitor_detects_backward_jump_after_good_sample() {
+        let clock = FakeClock::new(at(T));
+        let monitor = ClockMonitor::with_clock(clock.clone());
+        assert_eq!(monitor.check(None), None);
+
+        clock.set(at(T - 4 * 3600), Duration::from_secs(30));
+        assert!(monitor.check(None).is_some_and(|h| h.is_skewed()));
+
+        // 睡眠唤醒：墙钟大步向前、单调时钟几乎没动，属于恢复而不是异常
+        clock.set(at(T + 8 * 3600), Duration::from_secs(30));
+        assert_eq!(monitor.check(None), Some(ClockHealth::Ok));
+        clock.set(at(T + 8 * 3600 + 30), Duration::from_secs(30));
+        assert_eq!(monitor.check(None), None);
+    }
+}
@@ -0,0 +1,571 @@
+//! 账号 auth.json 形状与本机 Codex CLI 版本的兼容性
+//!
+//! 老版本 Codex 见到不认识的字段会解析失败，新版本又要求某些字段必须有；切号后版本对不上，
+//! 用户只看到一个莫名其妙的登录提示。这里：
+//! - 跑一次 `codex --version`（[`PROBE_TIMEOUT`] 超时）拿本机版本并缓存；设置里可手填覆盖
+//! - [`RULES`] 是兼容矩阵：哪个版本区间要求 / 拒绝 auth.json 里的哪个字段，新情况往里加一行即可
+//! - 对每个账号即将写盘的 auth.json 算一个 [`CompatibilityVerdict`]
+//!
+//! 版本解析、矩阵查找、判定都是纯函数；跑进程的部分在 [`VersionProbe`] 后面，测试里换成假的。
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::account::Account;
+
+/// `codex --version` 最长等多久
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Codex CLI 版本；预发布标签只用于展示，比较时按对应正式版算
+/// （预发布版的行为通常已经和即将发布的正式版一致）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CodexVersion {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+    pub pre: Option<String>,
+}
+
+impl CodexVersion {
+    pub const fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+            pre: None,
+        }
+    }
+
+    fn triple(&self) -> (u64, u64, u64) {
+        (self.major, self.minor, self.patch)
+    }
+
+    fn cmp_release(&self, other: &Self) -> Ordering {
+        self.triple().cmp(&other.triple())
+    }
+}
+
+impl std::fmt::Display for CodexVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+        if let Some(pre) = &self.pre {
+            write!(f, "-{}", pre)?;
+        }
+        Ok(())
+    }
+}
+
+/// 从 `codex --version` 的输出（如 `codex-cli 0.130.0`、`codex 0.46.0-alpha.3`、`v0.2.0`）
+/// 里取版本号；缺的 minor / patch 按 0 算
+pub fn parse_version(output: &str) -> Option<CodexVersion> {
+    output.split_whitespace().find_map(|word| {
+        let word = word.strip_prefix('v').unwrap_or(word);
+        if !word.starts_with(|c: char| c.is_ascii_digit()) {
+            return None;
+        }
+        let word = word.split('+').next()?;
+        let (core, pre) = match word.split_once('-') {
+            Some((core, pre)) if !pre.is_empty() => (core, Some(pre.to_string())),
+            Some((core, _)) => (core, None),
+            None => (word, None),
+        };
+        let mut parts = core.split('.');
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next().map_or(Some(0), |p| p.parse().ok())?;
+        let patch = parts.next().map_or(Some(0), |p| p.parse().ok())?;
+        Some(CodexVersion {
+            major,
+            minor,
+            patch,
+            pre,
+        })
+    })
+}
+
+/// auth.json 的登录模式，决定哪些规则适用
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AuthShape {
+    /// `tokens` 块（ChatGPT OAuth）
+    ChatGpt,
+    /// `OPENAI_API_KEY`（中转 / API key）
+    ApiKey,
+}
+
+pub fn auth_shape(auth: &Value) -> AuthShape {
+    if auth.get("OPENAI_API_KEY").is_some_and(|v| !v.is_null()) && auth.get("tokens").is_none() {
+        AuthShape::ApiKey
+    } else {
+        AuthShape::ChatGpt
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RuleKind {
+    /// 该版本区间要求字段存在
+    Requires,
+    /// 该版本区间不认识这个字段，有了反而解析失败
+    Rejects,
+}
+
+/// 兼容矩阵的一行
+#[derive(Debug, Clone)]
+pub struct CompatRule {
+    /// 含
+    pub since: CodexVersion,
+    /// 不含；None 表示直到最新
+    pub until: Option<CodexVersion>,
+    /// None 表示不分登录模式
+    pub shape: Option<AuthShape>,
+    /// auth.json 里的 JSON pointer
+    pub field: &'static str,
+    pub kind: RuleKind,
+    /// 给用户看的原因
+    pub note: &'static str,
+}
+
+impl CompatRule {
+    fn covers(&self, version: &CodexVersion) -> bool {
+        version.cmp_release(&self.since) != Ordering::Less
+            && self
+                .until
+                .as_ref()
+                .is_none_or(|until| version.cmp_release(until) == Ordering::Less)
+    }
+}
+
+/// 兼容矩阵；按实际遇到的问题补充
+pub const RULES: &[CompatRule] = &[
+    CompatRule {
+        since: CodexVersion::new(0, 130, 0),
+        until: None,
+        shape: Some(AuthShape::ChatGpt),
+        field: "/tokens/id_token",
+        kind: RuleKind::Requires,
+        note: "0.130 起 tokens 块缺 id_token 会报 missing field id_token",
+    },
+    CompatRule {
+        since: CodexVersion::new(0, 130, 0),
+        until: None,
+        shape: Some(AuthShape::ChatGpt),
+        field: "/last_refresh",
+        kind: RuleKind::Requires,
+        note: "新版按 last_refresh 判断是否需要刷新，缺了会要求重新登录",
+    },
+    CompatRule {
+        since: CodexVersion::new(0, 0, 0),
+        until: Some(CodexVersion::new(0, 20, 0)),
+        shape: None,
+        field: "/auth_mode",
+        kind: RuleKind::Rejects,
+        note: "旧版不认识 auth_mode 字段",
+    },
+];
+
+/// 某版本适用的规则
+pub fn rules_for<'a>(
+    rules: &'a [CompatRule],
+    version: &'a CodexVersion,
+    shape: AuthShape,
+) -> impl Iterator<Item = &'a CompatRule> + 'a {
+    rules
+        .iter()
+        .filter(move |r| r.covers(version) && r.shape.is_none_or(|s| s == shape))
+}
+
+/// 一个账号在本机 Codex 版本下的兼容性
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum CompatibilityVerdict {
+    Compatible,
+    /// 该版本要求的字段缺失
+    NeedsField {
+        field: String,
+        note: String,
+    },
+    /// 该版本不认识、会解析失败的字段
+    UnsupportedField {
+        field: String,
+        note: String,
+    },
+    /// 没拿到本机 Codex 版本（没装 / 不在 PATH / 输出解析不了）
+    UnknownVersion,
+}
+
+impl CompatibilityVerdict {
+    /// 切号结果里的提示；兼容或版本未知时为 None
+    pub fn warning(&self, version: Option<&CodexVersion>) -> Option<String> {
+        let version = version.map(|v| v.to_string()).unwrap_or_default();
+        match self {
+            Self::NeedsField { field, note } => Some(format!(
+                "本机 Codex {} 需要 auth.json 含 {}（{}），可能会要求重新登录",
+                version, field, note
+            )),
+            Self::UnsupportedField { field, note } => Some(format!(
+                "本机 Codex {} 不支持 auth.json 里的 {}（{}）",
+                version, field, note
+            )),
+            Self::Compatible | Self::UnknownVersion => None,
+        }
+    }
+}
+
+fn has_field(auth: &Value, pointer: &str) -> bool {
+    match auth.pointer(pointer) {
+        None | Some(Value::Null) => false,
+        Some(Value::String(s)) => !s.trim().is_empty(),
+        Some(_) => true,
+    }
+}
+
+/// 按矩阵判定一份即将写盘的 auth.json；先报缺的字段，再报多的字段
+pub fn verdict(
+    rules: &[CompatRule],
+    version: Option<&CodexVersion>,
+    auth: &Value,
+) -> CompatibilityVerdict {
+    let Some(version) = version else {
+        return CompatibilityVerdict::UnknownVersion;
+    };
+    let shape = auth_shape(auth);
+    let applicable: Vec<&CompatRule> = rules_for(rules, version, shape).collect();
+    if let Some(rule) = applicable
+        .iter()
+        .find(|r| r.kind == RuleKind::Requires && !has_field(auth, r.field))
+    {
+        return CompatibilityVerdict::NeedsField {
+            field: rule.field.to_string(),
+            note: rule.note.to_string(),
+        };
+    }
+    if let Some(rule) = applicable
+        .iter()
+        .find(|r| r.kind == RuleKind::Rejects && auth.pointer(r.field).is_some())
+    {
+        return CompatibilityVerdict::UnsupportedField {
+            field: rule.field.to_string(),
+            note: rule.note.to_string(),
+        };
+    }
+    CompatibilityVerdict::Compatible
+}
+
+/// 按账号即将写进 `~/.codex/auth.json` 的内容判定
+pub fn account_verdict(version: Option<&CodexVersion>, account: &Account) -> CompatibilityVerdict {
+    verdict(RULES, version, &account.to_codex_auth_value())
+}
+
+/// 一批账号的判定，按账号 id
+pub fn verdicts<'a>(
+    version: Option<&CodexVersion>,
+    accounts: impl IntoIterator<Item = &'a Account>,
+) -> HashMap<String, CompatibilityVerdict> {
+    accounts
+        .into_iter()
+        .map(|a| (a.id.clone(), account_verdict(version, a)))
+        .collect()
+}
+
+/// 版本从哪来
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum VersionSource {
+    /// 设置里手填
+    Override,
+    Detected,
+    /// 没装 / 探测失败 / 手填的解析不了
+    Unknown,
+}
+
+/// 生效的版本：手填的优先（非空就用，解析不了也不回退到探测，免得用户以为覆盖生效了）
+pub fn resolve_version(
+    override_text: &str,
+    detected: Option<&CodexVersion>,
+) -> (Option<CodexVersion>, VersionSource) {
+    let override_text = override_text.trim();
+    if !override_text.is_empty() {
+        return match parse_version(override_text) {
+            Some(v) => (Some(v), VersionSource::Override),
+            None => (None, VersionSource::Unknown),
+        };
+    }
+    match detected {
+        Some(v) => (Some(v.clone()), VersionSource::Detected),
+        None => (None, VersionSource::Unknown),
+    }
+}
+
+/// 取 Codex CLI 的版本输出
+pub trait VersionProbe: Send + Sync {
+    /// 没装 / 超时 / 退出码非 0 时返回 Err
+    fn version_output(&self) -> Result<String, String>;
+}
+
+/// 跑 PATH 里的 `codex --version`
+pub struct CodexCliProbe {
+    pub timeout: Duration,
+}
+
+impl Default for CodexCliProbe {
+    fn default() -> Self {
+        Self {
+            timeout: PROBE_TIMEOUT,
+        }
+    }
+}
+
+impl VersionProbe for CodexCliProbe {
+    fn version_output(&self) -> Result<String, String> {
+        use std::io::Read;
+        use std::process::{Command, Stdio};
+
+        let mut child = Command::new("codex")
+            .arg("--version")
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| format!("未找到 codex 命令: {}", e))?;
+        let deadline = Instant::now() + self.timeout;
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break status,
+                Ok(None) if Instant::now() >= deadline => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return Err(format!(
+                        "codex --version {} 秒内没有返回",
+                        self.timeout.as_secs()
+                    ));
+                }
+                Ok(None) => std::thread::sleep(Duration::from_millis(50)),
+                Err(e) => return Err(format!("等待 codex 进程失败: {}", e)),
+            }
+        };
+        if !status.success() {
+            return Err(format!("codex --version 退出码 {:?}", status.code()));
+        }
+        let mut out = String::new();
+        if let Some(mut stdout) = child.stdout.take() {
+            stdout
+                .read_to_string(&mut out)
+                .map_err(|e| format!("读取 codex 输出失败: {}", e))?;
+        }
+        Ok(out)
+    }
+}
+
+/// 探测结果缓存：进程内只跑一次，`refresh` 时重跑（用户升级了 Codex）
+pub struct VersionCache<P: VersionProbe = CodexCliProbe> {
+    probe: P,
+    cached: Mutex<Option<Option<CodexVersion>>>,
+}
+
+impl Default for VersionCache {
+    fn default() -> Self {
+        Self::with_probe(CodexCliProbe::default())
+    }
+}
+
+impl<P: VersionProbe> VersionCache<P> {
+    pub fn with_probe(probe: P) -> Self {
+        Self {
+            probe,
+            cached: Mutex::new(None),
+        }
+    }
+
+    /// 本机探测到的版本；会阻塞最多 [`PROBE_TIMEOUT`]，异步上下文里放进 spawn_blocking
+    pub fn detected(&self, refresh: bool) -> Option<CodexVersion> {
+        let Ok(mut cached) = self.cached.lock() else {
+            return None;
+        };
+        if refresh || cached.is_none() {
+            let version = match self.probe.version_output() {
+                Ok(out) => {
+                    let parsed = parse_version(&out);
+                    if parsed.is_none() {
+                        eprintln!("[CodexCompat] 解析不了版本输出: {}", out.trim());
+                    }
+                    parsed
+                }
+                Err(e) => {
+                    println!("[CodexCompat] 获取 Codex 版本失败: {}", e);
+                    None
+                }
+            };
+            *cached = Some(version);
+        }
+        cached.clone().flatten()
+    }
+
+    /// 只看缓存，不跑进程；还没探测过时为 None（列表这类同步命令用）
+    pub fn peek(&self) -> Option<CodexVersion> {
+        self.cached.lock().ok()?.clone().flatten()
+    }
+}
+
+pub fn global() -> &'static VersionCache {
+    static CACHE: std::sync::OnceLock<VersionCache> = std::sync::OnceLock::new();
+    CACHE.get_or_init(VersionCache::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+
+    fn v(major: u64, minor: u64, patch: u64) -> CodexVersion {
+        CodexVersion::new(major, minor, patch)
+    }
+
+    #[test]
+    fn parses_common_version_outputs() {
+        assert_eq!(parse_version("codex-cli 0.130.0\n"), Some(v(0, 130, 0)));
+        assert_eq!(parse_version("v0.2"), Some(v(0, 2, 0)));
+        assert_eq!(parse_version("codex 1.4.7+build.5"), Some(v(1, 4, 7)));
+        let pre = parse_version("codex-cli 0.46.0-alpha.3").unwrap();
+        assert_eq!(pre.triple(), (0, 46, 0));
+        assert_eq!(pre.pre.as_deref(), Some("alpha.3"));
+        assert_eq!(pre.to_string(), "0.46.0-alpha.3");
+        assert_eq!(parse_version("0.1.2504301751"), Some(v(0, 1, 2504301751)));
+    }
+
+    #[test]
+    fn garbage_is_not_a_version() {
+        assert_eq!(parse_version(""), None);
+        assert_eq!(parse_version("command not found: codex"), None);
+        assert_eq!(parse_version("codex x.y.z"), None);
+        assert_eq!(parse_version("codex 1.two.3"), None);
+    }
+
+    #[test]
+    fn matrix_lookup_respects_ranges_and_shape() {
+        let fields = |ver: &CodexVersion, shape| {
+            rules_for(RULES, ver, shape)
+                .map(|r| r.field)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            fields(&v(0, 130, 0), AuthShape::ChatGpt),
+            vec!["/tokens/id_token", "/last_refresh"]
+        );
+        assert!(fields(&v(0, 130, 0), AuthShape::ApiKey).is_empty());
+        assert!(fields(&v(0, 129, 9), AuthShape::ChatGpt).is_empty());
+        assert_eq!(fields(&v(0, 19, 9), AuthShape::ApiKey), vec!["/auth_mode"]);
+        // 预发布版按正式版算
+        let pre = parse_version("0.130.0-alpha.1").unwrap();
+        assert_eq!(fields(&pre, AuthShape::ChatGpt).len(), 2);
+    }
+
+    #[test]
+    fn verdicts_follow_the_matrix() {
+        let full = json!({
+            "tokens": { "id_token": "id", "access_token": "at", "refresh_token": "rt" },
+            "last_refresh": "2026-01-01T00:00:00Z",
+        });
+        let no_refresh = json!({ "tokens": { "id_token": "id", "access_token": "at" } });
+        let no_id =
+            json!({ "tokens": { "id_token": "", "access_token": "at" }, "last_refresh": "x" });
+        let api_key = json!({ "OPENAI_API_KEY": "sk-x" });
+        let new = v(0, 130, 2);
+
+        assert_eq!(
+            verdict(RULES, Some(&new), &full),
+            CompatibilityVerdict::Compatible
+        );
+        assert_eq!(
+            verdict(RULES, Some(&new), &api_key),
+            CompatibilityVerdict::Compatible
+        );
+        assert!(matches!(
+            verdict(RULES, Some(&new), &no_refresh),
+            CompatibilityVerdict::NeedsField { field, .. } if field == "/last_refresh"
+        ));
+        assert!(matches!(
+            verdict(RULES, Some(&new), &no_id),
+            CompatibilityVerdict::NeedsField { field, .. } if field == "/tokens/id_token"
+        ));
+        assert_eq!(
+            verdict(RULES, Some(&v(0, 100, 0)), &no_refresh),
+            CompatibilityVerdict::Compatible
+        );
+
+        let with_mode = json!({ "auth_mode": "chatgpt", "tokens": {} });
+        let old = verdict(RULES, Some(&v(0, 10, 0)), &with_mode);
+        assert!(
+            matches!(old, CompatibilityVerdict::UnsupportedField { ref field, .. } if field == "/auth_mode")
+        );
+        assert!(old.warning(Some(&v(0, 10, 0))).unwrap().contains("0.10.0"));
+
+        assert_eq!(
+            verdict(RULES, None, &full),
+            CompatibilityVerdict::UnknownVersion
+        );
+        assert_eq!(CompatibilityVerdict::UnknownVersion.warning(None), None);
+    }
+
+    #[test]
+    fn override_wins_over_detection() {
+        let detected = v(0, 120, 0);
+        assert_eq!(
+            resolve_version("", Some(&detected)),
+            (Some(detected.clone()), VersionSource::Detected)
+        );
+        assert_eq!(
+            resolve_version(" 0.131.0 ", Some(&detected)),
+            (Some(v(0, 131, 0)), VersionSource::Override)
+        );
+        assert_eq!(
+            resolve_version("latest", Some(&detected)),
+            (None, VersionSource::Unknown)
+        );
+        assert_eq!(resolve_version("", None), (None, VersionSource::Unknown));
+    }
+
+    struct FakeProbe {
+        output: Result<String, String>,
+        calls: AtomicUsize,
+    }
+
+    impl VersionProbe for FakeProbe {
+        fn version_output(&self) -> Result<String, String> {
+            self.calls.fetch_add(1, AtomicOrdering::SeqCst);
+            self.output.clone()
+        }
+    }
+
+    #[test]
+    fn cache_probes_once_until_refreshed() {
+        let cache = VersionCache::with_probe(FakeProbe {
+            output: Ok("codex-cli 0.130.0".into()),
+            calls: AtomicUsize::new(0),
+        });
+        assert_eq!(cache.detected(false), Some(v(0, 130, 0)));
+        assert_eq!(cache.detected(false), Some(v(0, 130, 0)));
+        assert_eq!(cache.probe.calls.load(AtomicOrdering::SeqCst), 1);
+        cache.detected(true);
+        assert_eq!(cache.probe.calls.load(AtomicOrdering::SeqCst), 2);
+    }
+
+    #[test]
+    fn missing_binary_is_unknown_version() {
+        let cache = VersionCache::with_probe(FakeProbe {
+            output: Err("未找到 codex 命令".into()),
+            calls: AtomicUsize::new(0),
+        });
+        assert_eq!(cache.detected(false), None);
+        // 失败结果也缓存，不会每次都去跑进程
+        assert_eq!(cache.detected(false), None);
+        assert_eq!(cache.probe.calls.load(AtomicOrdering::SeqCst), 1);
+        assert_eq!(
+            verdict(RULES, cache.detected(false).as_ref(), &json!({})),
+            CompatibilityVerdict::UnknownVersion
+        );
+    }
+}
@@ -0,0 +1,227 @@
+//! 切号时按账号改 `~/.codex/config.toml` 的顶层 `profile`
+//!
+//! 账号可以绑定一个 config.toml 里定义的 profile（`Account::codex_profile`），
+//! 开了 `apply_profile_on_switch` 后，切号写完 auth.json 紧接着把 `profile = "<名字>"`
+//! 写进 config.toml。用 toml_edit 只改这一个键，注释、空行和其它键原样保留。
+//!
+//! 改之前的原文保存在 [`ProfileChange`] 里：切号后续步骤（保存 accounts.json）失败时
+//! 调 [`ProfileChange::rollback`] 还原。config.toml 不存在时只有开了
+//! `create_codex_config_if_missing` 才新建一个只含 `profile` 的最小文件。
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use toml_edit::{DocumentMut, Item, Value};
+
+use crate::account::{Account, AppSettings};
+
+/// `~/.codex/config.toml`
+pub fn config_path() -> PathBuf {
+    crate::paths::codex_dir().join("config.toml")
+}
+
+/// 把顶层 `profile` 设成 `profile`；原来有值时保留该行的行尾注释
+pub fn set_profile(content: &str, profile: &str) -> Result<String, String> {
+    let mut doc = content
+        .parse::<DocumentMut>()
+        .map_err(|e| format!("解析 config.toml 失败: {}", e))?;
+    match doc.get_mut("profile").and_then(Item::as_value_mut) {
+        Some(existing) => {
+            let decor = existing.decor().clone();
+            *existing = Value::from(profile);
+            *existing.decor_mut() = decor;
+        }
+        None => {
+            doc.insert("profile", toml_edit::value(profile));
+        }
+    }
+    Ok(doc.to_string())
+}
+
+/// config.toml 里是否有 `[profiles.<name>]`
+pub fn has_profile(content: &str, name: &str) -> bool {
+    content
+        .parse::<DocumentMut>()
+        .ok()
+        .and_then(|doc| {
+            doc.get("profiles")
+                .and_then(Item::as_table_like)
+                .map(|t| t.contains_key(name))
+        })
+        .unwrap_or(false)
+}
+
+/// 一次已落盘的 profile 修改，保留改之前的状态以便回滚
+#[derive(Debug)]
+pub struct ProfileChange {
+    path: PathBuf,
+    /// 改之前的原文；None 表示文件是这次新建的
+    previous: Option<String>,
+}
+
+impl ProfileChange {
+    /// 还原成修改前：原文写回，新建的文件删掉
+    pub fn rollback(self) -> Result<(), String> {
+        match self.previous {
+            Some(content) => crate::atomic_write::write_atomic(&self.path, content.as_bytes())
+                .map_err(|e| format!("还原 config.toml 失败: {}", e)),
+            None => fs::remove_file(&self.path)
+                .map_err(|e| format!("删除新建的 config.toml 失败: {}", e)),
+        }
+    }
+}
+
+/// 把 `path` 的 `profile` 改成 `profile`。
+/// 文件不存在且不允许新建、或者已经是这个值时什么都不写，返回 `Ok(None)`
+pub fn apply_profile(
+    path: &Path,
+    profile: &str,
+    create_if_missing: bool,
+) -> Result<Option<ProfileChange>, String> {
+    let previous = match fs::read_to_string(path) {
+        Ok(content) => Some(content),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(format!("读取 config.toml 失败: {}", e)),
+    };
+    let updated = match &previous {
+        Some(content) => {
+            let updated = set_profile(content, profile)?;
+            if updated == *content {
+                return Ok(None);
+            }
+            if !has_profile(content, profile) {
+                println!(
+                    "[CodexConfig] config.toml 里没有 [profiles.{}]，仍按账号设置写入",
+                    profile
+                );
+            }
+            updated
+        }
+        None if create_if_missing => set_profile("", profile)?,
+        None => {
+            println!("[CodexConfig] config.toml 不存在且未允许新建，跳过写入 profile");
+            return Ok(None);
+        }
+    };
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("创建目录失败: {}", e))?;
+    }
+    crate::atomic_write::write_atomic(path, updated.as_bytes())?;
+    println!("[CodexConfig] 已写入 profile = \"{}\"", profile);
+    Ok(Some(ProfileChange {
+        path: path.to_path_buf(),
+        previous,
+    }))
+}
+
+/// 切号落盘后调用：开关打开且目标账号绑定了 profile 才写 `~/.codex/config.toml`
+pub fn apply_for_switch(
+    settings: &AppSettings,
+    account: &Account,
+) -> Result<Option<ProfileChange>, String> {
+    if !settings.apply_profile_on_switch {
+        return Ok(None);
+    }
+    let Some(profile) = account.codex_profile.as_deref() else {
+        return Ok(None);
+    };
+    apply_profile(
+        &config_path(),
+        profile,
+        settings.create_codex_config_if_missing,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FIXTURE: &str = r#"# 全局配置
+model = "gpt-5"
+profile = "personal" # 切号时会改这里
+
+# 公司要求的模型
+[profiles.work]
+model = "o3"
+approval_policy = "on-request"
+
+[profiles.personal]
+model = "gpt-5"
+"#;
+
+    fn temp_config(content: Option<&str>) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("cs-codex-config-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("config.toml");
+        if let Some(content) = content {
+            fs::write(&path, content).unwrap();
+        }
+        path
+    }
+
+    #[test]
+    fn only_profile_key_changes_and_comments_survive() {
+        let updated = set_profile(FIXTURE, "work").unwrap();
+        assert_eq!(
+            updated,
+            FIXTURE.replace(
+                "profile = \"personal\" # 切号时会改这里",
+                "profile = \"work\" # 切号时会改这里"
+            )
+        );
+        assert!(has_profile(&updated, "work"));
+        assert!(!has_profile(&updated, "missing"));
+    }
+
+    #[test]
+    fn missing_profile_key_is_inserted_at_top_level() {
+        let content = "model = \"gpt-5\"\n\n[profiles.work]\nmodel = \"o3\"\n";
+        let updated = set_profile(content, "work").unwrap();
+        let doc = updated.parse::<DocumentMut>().unwrap();
+        assert_eq!(doc["profile"].as_str(), Some("work"));
+        assert_eq!(doc["profiles"]["work"]["model"].as_str(), Some("o3"));
+        assert!(
+            updated.find("profile = ").unwrap() < updated.find("[profiles.work]").unwrap(),
+            "profile 必须在第一个表之前，否则会落进 [profiles.work]"
+        );
+    }
+
+    #[test]
+    fn rollback_restores_original_file() {
+        let path = temp_config(Some(FIXTURE));
+        let change = apply_profile(&path, "work", false).unwrap().unwrap();
+        assert!(fs::read_to_string(&path)
+            .unwrap()
+            .contains("profile = \"work\""));
+
+        change.rollback().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), FIXTURE);
+
+        // 已经是目标值：不写也不产生回滚记录
+        assert!(apply_profile(&path, "personal", false).unwrap().is_none());
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn missing_file_is_created_only_when_allowed() {
+        let path = temp_config(None);
+        assert!(apply_profile(&path, "work", false).unwrap().is_none());
+        assert!(!path.exists());
+
+        let change = apply_profile(&path, "work", true).unwrap().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "profile = \"work\"\n");
+
+        // 回滚新建的文件 = 删掉
+        change.rollback().unwrap();
+        assert!(!path.exists());
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn invalid_toml_is_left_untouched() {
+        let path = temp_config(Some("profile = \n"));
+        assert!(apply_profile(&path, "work", true).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "profile = \n");
+        let _ = fs::remove_dir_all(path.parent().unwrap());
+    }
+}
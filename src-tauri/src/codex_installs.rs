@@ -0,0 +1,289 @@
+//! 多个 Codex 安装
+//!
+//! 有人同时装着正式版和 fork 的 Codex，各用各的配置目录。默认安装就是原来那份 auth.json
+//! （`~/.codex`，受 `codex_home` / `CODEX_HOME` 影响），当前账号仍记在 `AccountStore::current`，
+//! 没登记其它安装时一切照旧。
+//!
+//! 设置 `codex_installs` 登记其它安装（名字 + auth.json 路径）：
+//! - `switch_account` 带 `install` 时只写那个安装的 auth.json（[`switch`]），
+//!   它的当前账号记在 `AccountStore::install_current`；覆盖前先把上一个账号从盘上回流，
+//!   fork 自己轮换过的 refresh_token 不会被冲掉
+//! - 热切、手机锚、config.toml profile、切号钩子、写入日志都只管默认安装
+//! - 后台回流（[`sync_from_disk`]）对每个登记的安装各跑一遍；冲突检测按切号目标的安装查（[`conflict`]）
+
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use crate::account::AccountStore;
+use crate::log_identity;
+use crate::warnings::Warning;
+
+/// 默认安装的名字；不用登记，也不能给别的安装用
+pub const DEFAULT_INSTALL: &str = "default";
+
+/// 登记的一个 Codex 安装
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct CodexInstall {
+    pub name: String,
+    /// 这个安装读的 auth.json（绝对路径）
+    pub auth_path: String,
+}
+
+/// None、空串和 `default` 都指默认安装
+pub fn is_default(install: Option<&str>) -> bool {
+    install
+        .map(str::trim)
+        .is_none_or(|name| name.is_empty() || name == DEFAULT_INSTALL)
+}
+
+/// 检查登记列表：名字非空、不重复、不叫 `default`；路径是绝对路径，且不和默认安装或彼此重复
+pub fn validate(installs: &[CodexInstall], default_auth_path: &Path) -> Result<(), String> {
+    let mut names: Vec<&str> = Vec::new();
+    let mut paths: Vec<&Path> = vec![default_auth_path];
+    for install in installs {
+        let name = install.name.trim();
+        if name.is_empty() {
+            return Err("Codex 安装需要填名字".to_string());
+        }
+        if name == DEFAULT_INSTALL {
+            return Err(format!("{} 是默认安装的名字，请换一个", DEFAULT_INSTALL));
+        }
+        if names.contains(&name) {
+            return Err(format!("Codex 安装重名: {}", name));
+        }
+        names.push(name);
+
+        let path = Path::new(install.auth_path.trim());
+        if !path.is_absolute() {
+            return Err(format!(
+                "Codex 安装 {} 的 auth.json 需填绝对路径: {}",
+                name, install.auth_path
+            ));
+        }
+        if paths.contains(&path) {
+            return Err(format!(
+                "Codex 安装 {} 的 auth.json 和其它安装是同一个文件: {}",
+                name,
+                path.display()
+            ));
+        }
+        paths.push(path);
+    }
+    Ok(())
+}
+
+fn auth_path_of(store: &AccountStore, install: &str) -> Result<PathBuf, String> {
+    store
+        .settings
+        .codex_installs
+        .iter()
+        .find(|i| i.name == install)
+        .map(|i| PathBuf::from(i.auth_path.trim()))
+        .ok_or_else(|| format!("没有登记这个 Codex 安装: {}", install))
+}
+
+/// 某个安装的当前账号
+pub fn current_of<'a>(store: &'a AccountStore, install: Option<&str>) -> Option<&'a str> {
+    match install.filter(|name| !is_default(Some(name))) {
+        None => store.current.as_deref(),
+        Some(name) => store.install_current.get(name).map(String::as_str),
+    }
+}
+
+/// 读某个登记安装的 auth.json
+pub fn read_auth(store: &AccountStore, install: &str) -> Result<serde_json::Value, String> {
+    let path = auth_path_of(store, install)?;
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| format!("读取 {} 失败: {}", path.display(), e))?;
+    serde_json::from_str(&content).map_err(|e| format!("解析 {} 失败: {}", path.display(), e))
+}
+
+/// 把账号写进某个登记安装的 auth.json，记为它的当前账号；默认安装不受影响。
+/// 写之前和默认安装切号一样，先把这个安装的上一个账号按盘上的 auth.json 回流；
+/// 拒绝回流（身份对不上等）不挡切号，作为警告返回。调用方应持有目标账号的刷新锁
+pub fn switch(store: &mut AccountStore, install: &str, id: &str) -> Result<Vec<Warning>, String> {
+    let path = auth_path_of(store, install)?;
+    if store.trash.contains_key(id) {
+        return Err(format!("账号在回收站里，先恢复再切换: {}", id));
+    }
+    let mut warnings = Vec::new();
+    if let Some(previous) = store.install_current.get(install).cloned() {
+        if let Ok(disk) = read_auth(store, install) {
+            if let Err(warning) = store.try_sync_account_from_auth_json(&previous, disk) {
+                warnings.push(warning);
+            }
+        }
+    }
+    let account = store
+        .accounts
+        .get_mut(id)
+        .ok_or_else(|| format!("账号不存在: {}", id))?;
+    if account.archived {
+        return Err(format!("账号已归档，取消归档后再切换: {}", id));
+    }
+    AccountStore::write_auth_file(&path, &account.to_codex_auth_value())?;
+    account.record_switch(Utc::now());
+    account.touch();
+    println!(
+        "[Install] {} 已切换到 {}",
+        install,
+        log_identity::id(&account.name)
+    );
+    store
+        .install_current
+        .insert(install.to_string(), id.to_string());
+    Ok(warnings)
+}
+
+/// 去掉已经取消登记的安装、已经不在库里的账号
+pub fn prune(store: &mut AccountStore) {
+    let AccountStore {
+        install_current,
+        accounts,
+        settings,
+        ..
+    } = store;
+    install_current.retain(|install, id| {
+        accounts.contains_key(id) && settings.codex_installs.iter().any(|i| &i.name == install)
+    });
+}
+
+/// 各登记安装的当前账号按各自的 auth.json 回流；返回账号库是否有变动。
+/// 身份对不上等拒绝同步的情况只记日志，不动账号
+pub fn sync_from_disk(store: &mut AccountStore) -> bool {
+    let mut changed = false;
+    let targets: Vec<(String, String)> = store
+        .install_current
+        .iter()
+        .map(|(install, id)| (install.clone(), id.clone()))
+        .collect();
+    for (install, id) in targets {
+        let Ok(disk) = read_auth(store, &install) else {
+            continue;
+        };
+        let Some(account) = store.accounts.get(&id) else {
+            continue;
+        };
+        if account.archived || account.auth_json == disk {
+            continue;
+        }
+        match store.try_sync_account_from_auth_json(&id, disk) {
+            Ok(synced) => changed |= synced,
+            Err(warning) => println!("[Install] {} 回流跳过: {}", install, warning),
+        }
+    }
+    changed
+}
+
+/// 登记安装的当前账号在磁盘上有没有没同步进来的 token 更新；有的话返回 `账号名（安装名）`
+pub fn conflict(store: &AccountStore, install: &str) -> Option<String> {
+    let id = store.install_current.get(install)?;
+    let account = store.accounts.get(id).filter(|a| !a.archived)?;
+    let disk = read_auth(store, install).ok()?;
+    crate::service::detect_sync_conflict_for_current(account, &disk)
+        .map(|name| format!("{}（{}）", name, install))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn auth(account_id: &str, refresh_token: &str) -> serde_json::Value {
+        json!({
+            "tokens": {
+                "access_token": "at",
+                "account_id": account_id,
+                "refresh_token": refresh_token,
+            }
+        })
+    }
+
+    fn install(name: &str, auth_path: &Path) -> CodexInstall {
+        CodexInstall {
+            name: name.to_string(),
+            auth_path: auth_path.to_string_lossy().into_owned(),
+        }
+    }
+
+    #[test]
+    fn install_list_is_validated() {
+        let default = Path::new("/h/.codex/auth.json");
+        let fork = install("fork", Path::new("/h/.codex-fork/auth.json"));
+        assert!(validate(std::slice::from_ref(&fork), default).is_ok());
+        assert!(validate(&[fork.clone(), fork.clone()], default).is_err());
+        assert!(validate(&[install("default", Path::new("/x/auth.json"))], default).is_err());
+        assert!(validate(&[install(" ", Path::new("/x/auth.json"))], default).is_err());
+        assert!(validate(&[install("rel", Path::new("auth.json"))], default).is_err());
+        assert!(validate(&[install("same", default)], default).is_err());
+
+        assert!(is_default(None));
+        assert!(is_default(Some(" default ")));
+        assert!(!is_default(Some("fork")));
+    }
+
+    #[test]
+    fn switching_an_install_leaves_the_default_current_alone() {
+        let dir = std::env::temp_dir().join(format!("codex-installs-{}", std::process::id()));
+        let fork_auth = dir.join(".codex-fork").join("auth.json");
+        let mut store = AccountStore::default();
+        store.settings.codex_installs = vec![install("fork", &fork_auth)];
+        let main = store
+            .add_account("main".to_string(), auth("acct-main", "rt-main"), None)
+            .id;
+        let side = store
+            .add_account("side".to_string(), auth("acct-side", "rt-side"), None)
+            .id;
+        store.current = Some(main.clone());
+
+        switch(&mut store, "fork", &side).unwrap();
+        assert_eq!(current_of(&store, None), Some(main.as_str()));
+        assert_eq!(current_of(&store, Some("fork")), Some(side.as_str()));
+        assert_eq!(store.accounts[&side].switch_count, 1);
+        assert_eq!(
+            read_auth(&store, "fork").unwrap()["tokens"]["account_id"],
+            "acct-side"
+        );
+        assert!(switch(&mut store, "missing", &side).is_err());
+        assert!(conflict(&store, "fork").is_none());
+
+        // fork 里的 Codex 自己轮换了 refresh_token：冲突检测报出来，后台回流吸收
+        AccountStore::write_auth_file(&fork_auth, &auth("acct-side", "rt-side-2")).unwrap();
+        let name = conflict(&store, "fork").unwrap();
+        assert!(name.starts_with("side") && name.ends_with("（fork）"), "{}", name);
+        assert!(sync_from_disk(&mut store));
+        assert_eq!(
+            store.accounts[&side].refresh_token.as_deref(),
+            Some("rt-side-2")
+        );
+        assert!(!sync_from_disk(&mut store));
+
+        // 换成别的身份登录：拒绝回流
+        AccountStore::write_auth_file(&fork_auth, &auth("acct-other", "rt-x")).unwrap();
+        assert!(!sync_from_disk(&mut store));
+
+        // 切走前 fork 又轮换了一次：先收回 side 的新 token 再覆盖
+        AccountStore::write_auth_file(&fork_auth, &auth("acct-side", "rt-side-3")).unwrap();
+        assert!(switch(&mut store, "fork", &main).unwrap().is_empty());
+        assert_eq!(
+            store.accounts[&side].refresh_token.as_deref(),
+            Some("rt-side-3")
+        );
+        assert_eq!(
+            read_auth(&store, "fork").unwrap()["tokens"]["account_id"],
+            "acct-main"
+        );
+        // 盘上是别的身份：不回流，切号照常，带一条警告
+        AccountStore::write_auth_file(&fork_auth, &auth("acct-other", "rt-y")).unwrap();
+        assert_eq!(switch(&mut store, "fork", &side).unwrap().len(), 1);
+        assert_eq!(current_of(&store, Some("fork")), Some(side.as_str()));
+
+        store.delete_account(&side).unwrap();
+        prune(&mut store);
+        assert_eq!(current_of(&store, Some("fork")), None);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -86,9 +86,7 @@ pub fn list_codex_sessions(
 }
 
 fn sessions_root() -> PathBuf {
-    dirs::home_dir()
-        .map(|h| h.join(".codex").join("sessions"))
-        .unwrap_or_else(|| PathBuf::from(".codex/sessions"))
+    crate::paths::codex_dir().join("sessions")
 }
 
 /// 检测"当前活跃的 codex 会话"：
@@ -0,0 +1,375 @@
+//! 每日额度报告
+//!
+//! 每天固定时间（本地时间 `daily_report_time`）发一条系统通知，汇总所有账号的剩余额度：
+//! `work 5H 100%/周 80%, personal 5H 40%/周 12%`。
+//!
+//! 这里只放纯函数（下次触发时间 / 补发判断 / 文案），循环本身在 `scheduler::start_daily_report`。
+//! 时区做成泛型参数，测试里用自定义的夏令时时区覆盖跨零点和 DST 切换。
+
+use chrono::{DateTime, Duration, LocalResult, NaiveDate, NaiveTime, TimeZone, Utc};
+
+/// 错过发送时间（比如当时没开 App）后，启动时还补发的宽限期
+pub const CATCH_UP_GRACE_MINUTES: i64 = 120;
+
+/// 缓存额度超过这个时间才在发报告前重新拉取
+pub const STALE_QUOTA_MINUTES: i64 = 60;
+
+/// 通知正文的最大字符数（系统通知超长会被截断得很难看，自己先截）
+pub const MAX_REPORT_CHARS: usize = 200;
+
+/// 解析 "HH:MM"（24 小时制）
+pub fn parse_report_time(raw: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(raw.trim(), "%H:%M")
+        .map_err(|_| format!("每日报告时间格式应为 HH:MM，收到: {:?}", raw))
+}
+
+/// 某天的触发时刻。夏令时跳过的时间（比如 02:30 不存在）顺延到跳过之后；
+/// 回拨导致出现两次的时间取第一次。
+fn fire_time_on<Tz: TimeZone>(tz: &Tz, date: NaiveDate, at: NaiveTime) -> DateTime<Tz> {
+    let mut naive = date.and_time(at);
+    // DST 跳变一般是 1 小时，极端时区也不超过几小时
+    for _ in 0..4 {
+        match tz.from_local_datetime(&naive) {
+            LocalResult::Single(t) => return t,
+            LocalResult::Ambiguous(first, _) => return first,
+            LocalResult::None => naive += Duration::hours(1),
+        }
+    }
+    tz.from_utc_datetime(&date.and_time(at))
+}
+
+/// `now` 之后（不含）的下一次触发时刻
+pub fn next_fire_time<Tz: TimeZone>(now: &DateTime<Tz>, at: NaiveTime) -> DateTime<Tz> {
+    let tz = now.timezone();
+    let today = now.date_naive();
+    let candidate = fire_time_on(&tz, today, at);
+    if candidate > *now {
+        return candidate;
+    }
+    let tomorrow = today.succ_opt().unwrap_or(today);
+    fire_time_on(&tz, tomorrow, at)
+}
+
+/// `now` 之前（含）最近一次应触发的时刻
+pub fn last_fire_time<Tz: TimeZone>(now: &DateTime<Tz>, at: NaiveTime) -> DateTime<Tz> {
+    let tz = now.timezone();
+    let today = now.date_naive();
+    let candidate = fire_time_on(&tz, today, at);
+    if candidate <= *now {
+        return candidate;
+    }
+    let yesterday = today.pred_opt().unwrap_or(today);
+    fire_time_on(&tz, yesterday, at)
+}
+
+/// 现在是否该发报告：最近一次触发时刻之后还没发过，且没超过补发宽限期。
+///
+/// - 刚发完就重启：`last_sent` 不早于触发时刻 → 不重复发
+/// - 触发时 App 没开：启动时距触发时刻在宽限期内才补发，否则等下一天
+pub fn should_send<Tz: TimeZone>(
+    now: &DateTime<Tz>,
+    at: NaiveTime,
+    last_sent: Option<DateTime<Utc>>,
+    grace: Duration,
+) -> bool {
+    let due = last_fire_time(now, at).with_timezone(&Utc);
+    if last_sent.is_some_and(|sent| sent >= due) {
+        return false;
+    }
+    now.with_timezone(&Utc) - due <= grace
+}
+
+/// 报告里的一行账号
+#[derive(Debug, Clone)]
+pub struct ReportEntry {
+    pub name: String,
+    pub five_hour_left: Option<f64>,
+    pub weekly_left: Option<f64>,
+}
+
+/// 邮箱只留 @ 前面，省地方
+fn short_name(name: &str) -> &str {
+    let name = name.trim();
+    match name.split_once('@') {
+        Some((local, _)) if !local.is_empty() => local,
+        _ => name,
+    }
+}
+
+fn format_entry(entry: &ReportEntry) -> String {
+    let name = short_name(&entry.name);
+    match (entry.five_hour_left, entry.weekly_left) {
+        (Some(five), Some(week)) => format!("{} 5H {:.0}%/周 {:.0}%", name, five, week),
+        (Some(five), None) => format!("{} 5H {:.0}%", name, five),
+        (None, Some(week)) => format!("{} 周 {:.0}%", name, week),
+        (None, None) => format!("{} 无数据", name),
+    }
+}
+
+/// 拼成一行摘要；超过 `max_chars` 时丢掉后面的账号，末尾注明还剩几个
+pub fn format_report(entries: &[ReportEntry], max_chars: usize) -> String {
+    if entries.is_empty() {
+        return "没有可汇报的账号".to_string();
+    }
+    let parts: Vec<String> = entries.iter().map(format_entry).collect();
+    let mut out = String::new();
+    for (i, part) in parts.iter().enumerate() {
+        let sep = if i == 0 { "" } else { ", " };
+        let remaining = parts.len() - i - 1;
+        let suffix_len = if remaining > 0 {
+            format!(" 等另 {} 个", remaining).chars().count()
+        } else {
+            0
+        };
+        let len = out.chars().count() + sep.chars().count() + part.chars().count();
+        // 放下这一项后，剩余账号的后缀也得放得下
+        if len + suffix_len > max_chars && i > 0 {
+            out.push_str(&format!(" 等另 {} 个", parts.len() - i));
+            return out;
+        }
+        out.push_str(sep);
+        out.push_str(part);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{FixedOffset, NaiveDateTime};
+
+    /// 简化版美东时区：2026-03-08 02:00 跳到 03:00，2026-11-01 02:00 回拨到 01:00
+    #[derive(Debug, Clone, Copy)]
+    struct Eastern;
+
+    const EST: i32 = -5 * 3600;
+    const EDT: i32 = -4 * 3600;
+
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, m, d)
+            .unwrap()
+            .and_hms_opt(h, min, 0)
+            .unwrap()
+    }
+
+    impl TimeZone for Eastern {
+        type Offset = FixedOffset;
+
+        fn from_offset(_: &FixedOffset) -> Self {
+            Eastern
+        }
+
+        fn offset_from_local_date(&self, local: &NaiveDate) -> LocalResult<FixedOffset> {
+            self.offset_from_local_datetime(&local.and_time(NaiveTime::MIN))
+        }
+
+        fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> LocalResult<FixedOffset> {
+            let as_std = self.offset_from_utc_datetime(&(*local - Duration::seconds(EST as i64)));
+            let as_dst = self.offset_from_utc_datetime(&(*local - Duration::seconds(EDT as i64)));
+            let std_ok = as_std.local_minus_utc() == EST;
+            let dst_ok = as_dst.local_minus_utc() == EDT;
+            match (std_ok, dst_ok) {
+                (true, true) => LocalResult::Ambiguous(as_dst, as_std),
+                (true, false) => LocalResult::Single(as_std),
+                (false, true) => LocalResult::Single(as_dst),
+                (false, false) => LocalResult::None,
+            }
+        }
+
+        fn offset_from_utc_date(&self, utc: &NaiveDate) -> FixedOffset {
+            self.offset_from_utc_datetime(&utc.and_time(NaiveTime::MIN))
+        }
+
+        fn offset_from_utc_datetime(&self, at: &NaiveDateTime) -> FixedOffset {
+            let dst = *at >= utc(2026, 3, 8, 7, 0) && *at < utc(2026, 11, 1, 6, 0);
+            FixedOffset::east_opt(if dst { EDT } else { EST }).unwrap()
+        }
+    }
+
+    fn local(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Eastern> {
+        Eastern
+            .with_ymd_and_hms(y, m, d, h, min, 0)
+            .earliest()
+            .unwrap()
+    }
+
+    fn hm(raw: &str) -> NaiveTime {
+        parse_report_time(raw).unwrap()
+    }
+
+    #[test]
+    fn parses_hh_mm_only() {
+        assert_eq!(hm("09:05"), NaiveTime::from_hms_opt(9, 5, 0).unwrap());
+        assert_eq!(hm(" 23:59 "), NaiveTime::from_hms_opt(23, 59, 0).unwrap());
+        assert!(parse_report_time("24:00").is_err());
+        assert!(parse_report_time("9点").is_err());
+        assert!(parse_report_time("").is_err());
+    }
+
+    #[test]
+    fn next_fire_is_today_or_tomorrow() {
+        let now = local(2026, 5, 10, 7, 0);
+        assert_eq!(next_fire_time(&now, hm("09:00")), local(2026, 5, 10, 9, 0));
+        assert_eq!(next_fire_time(&now, hm("07:00")), local(2026, 5, 11, 7, 0));
+        assert_eq!(next_fire_time(&now, hm("06:59")), local(2026, 5, 11, 6, 59));
+    }
+
+    #[test]
+    fn next_fire_crosses_midnight_and_month_end() {
+        let now = local(2026, 5, 31, 23, 50);
+        let next = next_fire_time(&now, hm("00:10"));
+        assert_eq!(next, local(2026, 6, 1, 0, 10));
+        assert_eq!(next - now, Duration::minutes(20));
+
+        let now = local(2026, 6, 1, 0, 5);
+        assert_eq!(next_fire_time(&now, hm("23:55")), local(2026, 6, 1, 23, 55));
+        assert_eq!(
+            last_fire_time(&now, hm("23:55")),
+            local(2026, 5, 31, 23, 55)
+        );
+    }
+
+    #[test]
+    fn spring_forward_gap_fires_after_the_jump() {
+        // 2026-03-08 02:30 不存在，顺延到 03:30 EDT
+        let now = local(2026, 3, 8, 1, 0);
+        let next = next_fire_time(&now, hm("02:30"));
+        assert_eq!(next.naive_local(), utc(2026, 3, 8, 3, 30));
+        assert_eq!(next.offset().local_minus_utc(), EDT);
+        assert_eq!(
+            next.with_timezone(&Utc) - now.with_timezone(&Utc),
+            Duration::minutes(90)
+        );
+
+        // 跨 DST 的一天只有 23 小时
+        let now = local(2026, 3, 7, 8, 0);
+        let next = next_fire_time(&now, hm("08:00"));
+        assert_eq!(
+            next.with_timezone(&Utc) - now.with_timezone(&Utc),
+            Duration::hours(23)
+        );
+    }
+
+    #[test]
+    fn fall_back_ambiguous_time_fires_once() {
+        // 2026-11-01 01:30 出现两次，只在第一次（EDT）触发
+        let now = local(2026, 11, 1, 0, 0);
+        let first = next_fire_time(&now, hm("01:30"));
+        assert_eq!(first.offset().local_minus_utc(), EDT);
+
+        let after = first + Duration::minutes(1);
+        let next = next_fire_time(&after, hm("01:30"));
+        assert_eq!(
+            next.date_naive(),
+            NaiveDate::from_ymd_opt(2026, 11, 2).unwrap()
+        );
+
+        // 第二个 01:30（EST）时刻：今天的已发过，不应再判定为到期
+        let second = first + Duration::hours(1);
+        assert!(!should_send(
+            &second,
+            hm("01:30"),
+            Some(first.with_timezone(&Utc)),
+            Duration::minutes(CATCH_UP_GRACE_MINUTES)
+        ));
+    }
+
+    #[test]
+    fn restart_right_after_report_does_not_resend() {
+        let grace = Duration::minutes(CATCH_UP_GRACE_MINUTES);
+        let at = hm("09:00");
+        let sent = local(2026, 5, 10, 9, 0).with_timezone(&Utc);
+        let now = local(2026, 5, 10, 9, 2);
+        assert!(!should_send(&now, at, Some(sent), grace));
+        // 第二天到点照常发
+        let tomorrow = local(2026, 5, 11, 9, 0);
+        assert!(should_send(&tomorrow, at, Some(sent), grace));
+    }
+
+    #[test]
+    fn missed_window_catches_up_only_within_grace() {
+        let grace = Duration::minutes(CATCH_UP_GRACE_MINUTES);
+        let at = hm("09:00");
+        let yesterday = local(2026, 5, 9, 9, 0).with_timezone(&Utc);
+
+        assert!(should_send(
+            &local(2026, 5, 10, 10, 30),
+            at,
+            Some(yesterday),
+            grace
+        ));
+        assert!(should_send(
+            &local(2026, 5, 10, 11, 0),
+            at,
+            Some(yesterday),
+            grace
+        ));
+        assert!(!should_send(
+            &local(2026, 5, 10, 11, 1),
+            at,
+            Some(yesterday),
+            grace
+        ));
+        // 从没发过也一样
+        assert!(should_send(&local(2026, 5, 10, 9, 0), at, None, grace));
+        assert!(!should_send(&local(2026, 5, 10, 8, 59), at, None, grace));
+    }
+
+    #[test]
+    fn catch_up_grace_spans_midnight() {
+        let grace = Duration::minutes(CATCH_UP_GRACE_MINUTES);
+        let at = hm("23:30");
+        assert!(should_send(&local(2026, 5, 11, 0, 45), at, None, grace));
+        assert!(!should_send(&local(2026, 5, 11, 1, 31), at, None, grace));
+    }
+
+    fn entry(name: &str, five: Option<f64>, week: Option<f64>) -> ReportEntry {
+        ReportEntry {
+            name: name.to_string(),
+            five_hour_left: five,
+            weekly_left: week,
+        }
+    }
+
+    #[test]
+    fn formats_compact_summary() {
+        let entries = vec![
+            entry("work@corp.com", Some(100.0), Some(80.0)),
+            entry("personal", Some(40.4), Some(12.0)),
+            entry("new@x.io", None, None),
+        ];
+        assert_eq!(
+            format_report(&entries, MAX_REPORT_CHARS),
+            "work 5H 100%/周 80%, personal 5H 40%/周 12%, new 无数据"
+        );
+        assert_eq!(format_report(&[], MAX_REPORT_CHARS), "没有可汇报的账号");
+    }
+
+    #[test]
+    fn truncates_many_accounts_with_remaining_count() {
+        let entries: Vec<ReportEntry> = (0..40)
+            .map(|i| entry(&format!("acct{:02}@x.com", i), Some(50.0), Some(50.0)))
+            .collect();
+        let text = format_report(&entries, MAX_REPORT_CHARS);
+        assert!(text.chars().count() <= MAX_REPORT_CHARS, "{}", text);
+        assert!(text.starts_with("acct00 5H 50%/周 50%, acct01"), "{}", text);
+        let shown = text.matches("5H").count();
+        assert!(
+            text.ends_with(&format!(" 等另 {} 个", 40 - shown)),
+            "{}",
+            text
+        );
+    }
+
+    #[test]
+    fn truncation_always_keeps_first_entry() {
+        let entries = vec![
+            entry("a-very-long-account-name", Some(1.0), Some(2.0)),
+            entry("b", Some(3.0), Some(4.0)),
+        ];
+        assert_eq!(
+            format_report(&entries, 10),
+            "a-very-long-account-name 5H 1%/周 2% 等另 1 个"
+        );
+    }
+}
@@ -0,0 +1,484 @@
+//! 诊断包导出（提 issue 用）
+//!
+//! 打一个 zip，里面全部是脱敏后的 JSON / 文本：
+//! - `environment.json`：OS / 架构 / 版本 / 解析出的数据目录
+//! - `accounts.json`：账号摘要（名称取哈希、id 按 `log_identifier_style`、身份取指纹、额度缓存、
+//!   状态标记、保活状态），不含任何 token
+//! - `settings.json`：全局设置，凭据类字段掩码
+//! - `health.json`：调用方传入的运行状态（代理统计等）
+//! - `app-log.txt` / `recent-errors.txt`：`proxy.log` 末尾若干行及其中的错误行
+//! - `auth-journal.json`：最近的 auth.json 写入记录（只有 token 指纹）
+//! - `manifest.json`：以上文件清单
+//!
+//! 所有脱敏都走 `redact` 模块，不在这里另写规则。
+
+use crate::account::{Account, AccountStore, CachedQuota, KeepaliveState};
+use crate::auth_journal::{AuthJournal, JournalEntry};
+use crate::log_identity::{self, LogIdentifierStyle, LoggedId};
+use crate::redact;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// 日志只取末尾这么多行
+pub const LOG_TAIL_LINES: usize = 500;
+/// 读日志时最多从末尾读这么多字节，避免大日志整份进内存
+const LOG_TAIL_MAX_BYTES: u64 = 1024 * 1024;
+
+/// 诊断包内单个文件
+#[derive(Debug, Clone, Serialize)]
+pub struct BundleFile {
+    pub name: String,
+    pub bytes: usize,
+    pub description: String,
+}
+
+/// `create_diagnostics_bundle` 的返回值
+#[derive(Debug, Clone, Serialize)]
+pub struct BundleManifest {
+    pub path: String,
+    pub created_at: DateTime<Utc>,
+    pub files: Vec<BundleFile>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct AccountSummary {
+    /// 按 `log_identifier_style` 处理，和 auth-journal 里的 account_id 对得上
+    id: LoggedId,
+    /// 账号名 sha256 前 8 位（同名账号可对照，看不出原名）
+    name_hash: String,
+    /// ChatGPT workspace + user id 的指纹（排查"两个号其实是同一身份"）
+    identity_fingerprint: Option<String>,
+    kind: crate::account::AccountKind,
+    is_current: bool,
+    is_banned: bool,
+    is_token_invalid: bool,
+    is_logged_out: bool,
+    is_session_anchor: bool,
+    has_refresh_token: bool,
+    created_at: DateTime<Utc>,
+    last_used: Option<DateTime<Utc>>,
+    cached_quota: Option<CachedQuota>,
+    keepalive: KeepaliveState,
+}
+
+fn short_hash(text: &str, len: usize) -> String {
+    let digest = Sha256::digest(text.as_bytes());
+    digest
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect::<String>()
+        .chars()
+        .take(len)
+        .collect()
+}
+
+fn summarize_account(
+    account: &Account,
+    is_current: bool,
+    style: LogIdentifierStyle,
+) -> AccountSummary {
+    let workspace = AccountStore::extract_account_id(&account.auth_json);
+    let user = AccountStore::extract_openai_user_id(&account.auth_json);
+    let identity_fingerprint = (workspace.is_some() || user.is_some()).then(|| {
+        short_hash(
+            &crate::usage_debug::identity_key(workspace.as_deref(), user.as_deref()),
+            12,
+        )
+    });
+    let mut keepalive = account.keepalive.clone();
+    keepalive.last_error = keepalive.last_error.as_deref().map(redact::redact_text);
+    AccountSummary {
+        id: log_identity::format(style, &account.id),
+        name_hash: short_hash(&account.name, 8),
+        identity_fingerprint,
+        kind: account.effective_kind(),
+        is_current,
+        is_banned: account.is_banned,
+        is_token_invalid: account.is_token_invalid,
+        is_logged_out: account.is_logged_out,
+        is_session_anchor: account.is_session_anchor,
+        has_refresh_token: account.refresh_token.is_some(),
+        created_at: account.created_at,
+        last_used: account.last_used,
+        cached_quota: account.cached_quota.clone(),
+        keepalive,
+    }
+}
+
+/// store 里所有凭据原文：auth_json 中凭据键的值、refresh_token、relay cookie、共享密钥
+fn collect_secrets(store: &AccountStore) -> Vec<String> {
+    fn walk(value: &Value, out: &mut Vec<String>) {
+        match value {
+            Value::Object(map) => {
+                for (key, v) in map {
+                    match v {
+                        Value::String(s) if redact::is_secret_key(key) => out.push(s.clone()),
+                        _ => walk(v, out),
+                    }
+                }
+            }
+            Value::Array(items) => items.iter().for_each(|v| walk(v, out)),
+            _ => {}
+        }
+    }
+    let mut secrets = vec![store.settings.remote_shared_secret.clone()];
+    for account in store.accounts.values() {
+        walk(&account.auth_json, &mut secrets);
+        secrets.extend(account.refresh_token.clone());
+        secrets.extend(account.relay_usage_cookie.clone());
+    }
+    // 长的先替换，避免短串先命中把长串拆成两半
+    secrets.sort_by_key(|s| std::cmp::Reverse(s.len()));
+    secrets.dedup();
+    secrets
+}
+
+fn environment_info() -> Value {
+    serde_json::json!({
+        "os": std::env::consts::OS,
+        "arch": std::env::consts::ARCH,
+        "family": std::env::consts::FAMILY,
+        "app_version": env!("CARGO_PKG_VERSION"),
+        "paths": crate::paths::current(),
+    })
+}
+
+/// 读日志末尾 `max_lines` 行（文件不存在返回空串）
+pub fn read_log_tail(path: &Path, max_lines: usize) -> String {
+    use std::io::{Read, Seek, SeekFrom};
+    let Ok(mut file) = std::fs::File::open(path) else {
+        return String::new();
+    };
+    let len = file.metadata().map(|m| m.len()).unwrap_or(0);
+    let start = len.saturating_sub(LOG_TAIL_MAX_BYTES);
+    if file.seek(SeekFrom::Start(start)).is_err() {
+        return String::new();
+    }
+    let mut buf = Vec::new();
+    if file.read_to_end(&mut buf).is_err() {
+        return String::new();
+    }
+    let text = String::from_utf8_lossy(&buf);
+    // 从中间截断时第一行多半不完整，丢掉
+    let mut lines: Vec<&str> = text.lines().skip(usize::from(start > 0)).collect();
+    let keep_from = lines.len().saturating_sub(max_lines);
+    lines.drain(..keep_from);
+    lines.join("\n")
+}
+
+fn is_error_line(line: &str) -> bool {
+    let lower = line.to_lowercase();
+    ["失败", "错误", "error", "panic", "❌"]
+        .iter()
+        .any(|k| lower.contains(k))
+}
+
+fn to_pretty_json<T: Serialize>(value: &T) -> String {
+    serde_json::to_string_pretty(value).unwrap_or_else(|e| format!("{{\"error\":\"{}\"}}", e))
+}
+
+/// 组装诊断包内容（纯函数，不碰磁盘）。返回 (文件名, 说明, 内容)。
+pub fn build_entries(
+    store: &AccountStore,
+    health: Value,
+    log_tail: &str,
+    auth_journal: &[JournalEntry],
+) -> Vec<(String, String, String)> {
+    let current = store.current.as_deref();
+    let style = store.settings.log_identifier_style;
+    let mut accounts: Vec<AccountSummary> = store
+        .accounts
+        .values()
+        .map(|a| summarize_account(a, current == Some(a.id.as_str()), style))
+        .collect();
+    accounts.sort_by_key(|a| a.created_at);
+    let mut accounts = serde_json::to_value(&accounts).unwrap_or(Value::Null);
+    redact::redact_json(&mut accounts);
+
+    let mut settings = serde_json::to_value(&store.settings).unwrap_or(Value::Null);
+    redact::redact_json(&mut settings);
+
+    let mut health = health;
+    redact::redact_json(&mut health);
+
+    let log = redact::redact_text(log_tail);
+    let errors: Vec<&str> = log.lines().filter(|l| is_error_line(l)).collect();
+
+    let entries = vec![
+        (
+            "environment.json".to_string(),
+            "系统 / 版本 / 数据目录".to_string(),
+            to_pretty_json(&environment_info()),
+        ),
+        (
+            "accounts.json".to_string(),
+            format!("{} 个账号摘要（无 token）", store.accounts.len()),
+            to_pretty_json(&accounts),
+        ),
+        (
+            "settings.json".to_string(),
+            "全局设置（凭据已掩码）".to_string(),
+            to_pretty_json(&settings),
+        ),
+        (
+            "health.json".to_string(),
+            "运行状态".to_string(),
+            to_pretty_json(&health),
+        ),
+        (
+            "app-log.txt".to_string(),
+            format!("日志末尾 {} 行（已脱敏）", log.lines().count()),
+            log.clone(),
+        ),
+        (
+            "recent-errors.txt".to_string(),
+            format!("日志中的 {} 条错误行", errors.len()),
+            errors.join("\n"),
+        ),
+        (
+            "auth-journal.json".to_string(),
+            format!("最近 {} 条 auth.json 写入记录", auth_journal.len()),
+            to_pretty_json(&auth_journal),
+        ),
+    ];
+
+    // 规则之外再按 store 里的凭据原文兜底一遍
+    let secrets = collect_secrets(store);
+    entries
+        .into_iter()
+        .map(|(name, description, content)| {
+            (name, description, redact::redact_known(&content, &secrets))
+        })
+        .collect()
+}
+
+/// 默认输出位置：`~/.codex-switcher/diagnostics/codex-switcher-diagnostics-<时间>.zip`
+pub fn default_bundle_path() -> PathBuf {
+    crate::paths::data_dir().join("diagnostics").join(format!(
+        "codex-switcher-diagnostics-{}.zip",
+        Utc::now().format("%Y%m%d-%H%M%S")
+    ))
+}
+
+/// 写 zip；`dest` 是已存在的目录时在其中按默认文件名生成
+pub fn write_bundle(
+    dest: &Path,
+    entries: &[(String, String, String)],
+) -> Result<BundleManifest, String> {
+    use std::io::Write;
+    use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+    let dest = if dest.is_dir() {
+        dest.join(
+            default_bundle_path()
+                .file_name()
+                .unwrap_or_else(|| std::ffi::OsStr::new("codex-switcher-diagnostics.zip")),
+        )
+    } else {
+        dest.to_path_buf()
+    };
+    if let Some(parent) = dest.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| format!("创建目录失败: {}", e))?;
+    }
+
+    let created_at = Utc::now();
+    let mut files: Vec<BundleFile> = entries
+        .iter()
+        .map(|(name, description, content)| BundleFile {
+            name: name.clone(),
+            bytes: content.len(),
+            description: description.clone(),
+        })
+        .collect();
+    let manifest_json = to_pretty_json(&serde_json::json!({
+        "created_at": created_at,
+        "files": &files,
+    }));
+
+    let file = std::fs::File::create(&dest).map_err(|e| format!("创建诊断包失败: {}", e))?;
+    let mut zip = ZipWriter::new(file);
+    let opts = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .unix_permissions(0o600);
+    let manifest_entry = ("manifest.json", manifest_json.as_str());
+    for (name, content) in std::iter::once(manifest_entry)
+        .chain(entries.iter().map(|(n, _, c)| (n.as_str(), c.as_str())))
+    {
+        zip.start_file(name, opts)
+            .map_err(|e| format!("写入 {} 失败: {}", name, e))?;
+        zip.write_all(content.as_bytes())
+            .map_err(|e| format!("写入 {} 失败: {}", name, e))?;
+    }
+    zip.finish().map_err(|e| format!("写入诊断包失败: {}", e))?;
+
+    files.insert(
+        0,
+        BundleFile {
+            name: "manifest.json".to_string(),
+            bytes: manifest_json.len(),
+            description: "文件清单".to_string(),
+        },
+    );
+    Ok(BundleManifest {
+        path: dest.display().to_string(),
+        created_at,
+        files,
+    })
+}
+
+/// 组装并写出诊断包
+pub fn create(
+    store: &AccountStore,
+    health: Value,
+    dest: Option<PathBuf>,
+) -> Result<BundleManifest, String> {
+    let log_tail = read_log_tail(&crate::paths::data_dir().join("proxy.log"), LOG_TAIL_LINES);
+    let journal = AuthJournal::system().read(crate::auth_journal::DIAGNOSTICS_ENTRIES);
+    let entries = build_entries(store, health, &log_tail, &journal);
+    write_bundle(&dest.unwrap_or_else(default_bundle_path), &entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::Engine;
+
+    fn fake_jwt(claims: Value) -> String {
+        let enc = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        format!(
+            "{}.{}.{}",
+            enc.encode(r#"{"alg":"RS256","typ":"JWT"}"#),
+            enc.encode(claims.to_string()),
+            enc.encode("synthetic-signature-bytes-0123456789")
+        )
+    }
+
+    fn store_with_tokens() -> (AccountStore, Vec<String>) {
+        let mut store = AccountStore::default();
+        let mut secrets = Vec::new();
+        for i in 0..3 {
+            let claims = serde_json::json!({
+                "email": format!("user{}@example.com", i),
+                "https://api.openai.com/auth": {
+                    "chatgpt_account_id": format!("ws-{}", i),
+                    "chatgpt_user_id": format!("user-{}", i),
+                    "chatgpt_plan_type": "plus",
+                },
+                "exp": 1_900_000_000,
+            });
+            let access = fake_jwt(claims.clone());
+            let id_token = fake_jwt(claims);
+            let refresh = format!("rt_synthetic-refresh-token-{:04}-abcdefgh", i);
+            let auth = serde_json::json!({
+                "OPENAI_API_KEY": null,
+                "tokens": {
+                    "access_token": access,
+                    "id_token": id_token,
+                    "refresh_token": refresh,
+                    "account_id": format!("ws-{}", i),
+                },
+                "last_refresh": "2026-01-01T00:00:00Z",
+            });
+            let id = store.add_account(format!("账号{}", i), auth, None).id;
+            store.accounts.get_mut(&id).unwrap().keepalive.last_error =
+                Some(format!("refresh failed for {} Bearer {}", refresh, access));
+            store.current = Some(id);
+            secrets.push(access);
+            secrets.push(id_token);
+            secrets.push(refresh);
+        }
+        store.settings.remote_shared_secret = "shared-secret-0123456789".to_string();
+        secrets.push(store.settings.remote_shared_secret.clone());
+        (store, secrets)
+    }
+
+    #[test]
+    fn bundle_contains_no_token_material() {
+        let (store, secrets) = store_with_tokens();
+        let log = format!(
+            "[Switch] 切到 user0@example.com\n[OAuth] token 响应 {{\"access_token\":\"{}\",\"refresh_token\":\"{}\"}}\n[Proxy] ❌ 上游失败 Authorization: Bearer {}",
+            secrets[0], secrets[2], secrets[3]
+        );
+        let health = serde_json::json!({ "proxy": { "total_requests": 3, "remote_shared_secret": secrets.last() } });
+        let entries = build_entries(&store, health, &log, &[]);
+
+        let all: String = entries.iter().map(|(_, _, c)| c.as_str()).collect();
+        for secret in &secrets {
+            // 整串和 JWT 的每一段都不能出现
+            for part in std::iter::once(secret.as_str()).chain(secret.split('.')) {
+                assert!(!all.contains(part), "leaked: {}", part);
+            }
+        }
+        assert!(!all.contains("账号0"), "account names must be hashed");
+        assert!(!all.contains("user0@example.com"));
+
+        let accounts = &entries
+            .iter()
+            .find(|(n, _, _)| n == "accounts.json")
+            .unwrap()
+            .2;
+        let parsed: Vec<Value> = serde_json::from_str(accounts).unwrap();
+        assert_eq!(parsed.len(), 3);
+        assert!(parsed.iter().all(|a| a["identity_fingerprint"].is_string()));
+        let errors = &entries
+            .iter()
+            .find(|(n, _, _)| n == "recent-errors.txt")
+            .unwrap()
+            .2;
+        assert_eq!(errors.lines().count(), 1);
+    }
+
+    #[test]
+    fn hashed_bundle_contains_no_raw_identifiers() {
+        let (mut store, _) = store_with_tokens();
+        store.settings.log_identifier_style = LogIdentifierStyle::Hashed;
+        let ids: Vec<String> = store.accounts.keys().cloned().collect();
+        for (i, account) in store.accounts.values_mut().enumerate() {
+            account.name = format!("user{}@example.com", i);
+        }
+        let journal: Vec<JournalEntry> = ids
+            .iter()
+            .map(|id| JournalEntry {
+                at: Utc::now(),
+                op: crate::auth_journal::AuthWriteOp::Switch,
+                account_id: Some(log_identity::format(LogIdentifierStyle::Hashed, id)),
+                before: None,
+                after: None,
+            })
+            .collect();
+        let log = "[Switch] 切到 user1@example.com\n[Proxy] 自动切号 → user2@example.com";
+        let entries = build_entries(&store, serde_json::json!({}), log, &journal);
+
+        let all: String = entries.iter().map(|(_, _, c)| c.as_str()).collect();
+        for i in 0..3 {
+            let email = format!("user{}@example.com", i);
+            assert!(!all.contains(&email), "leaked: {}", email);
+        }
+        for id in &ids {
+            assert!(!all.contains(id.as_str()), "leaked account id: {}", id);
+            // 摘要和写入记录里是同一个哈希，能对照
+            let hashed = log_identity::format(LogIdentifierStyle::Hashed, id);
+            assert!(all.matches(hashed.as_str()).count() >= 2);
+        }
+    }
+
+    #[test]
+    fn log_tail_keeps_last_lines() {
+        let dir = std::env::temp_dir().join(format!("cs-diag-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("proxy.log");
+        let content: Vec<String> = (0..20).map(|i| format!("line {}", i)).collect();
+        std::fs::write(&path, content.join("\n")).unwrap();
+        assert_eq!(read_log_tail(&path, 3), "line 17\nline 18\nline 19");
+        assert_eq!(read_log_tail(&dir.join("missing.log"), 3), "");
+
+        let manifest = write_bundle(&dir, &[("a.txt".into(), "a".into(), "hello".into())]).unwrap();
+        assert!(manifest.path.ends_with(".zip"));
+        assert_eq!(manifest.files[0].name, "manifest.json");
+        assert_eq!(manifest.files[1].bytes, 5);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
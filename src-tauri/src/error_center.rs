@@ -0,0 +1,398 @@
+//! 错误中心
+//!
+//! 命令失败在前端 toast 一闪就没了，后台任务（保活、自动切号）的失败更是没留任何记录。
+//! 这里把推到用户面前的错误都留一份：时间、来源（命令名 / 后台任务）、账号、
+//! 结构化错误码和给人看的文案，最多 [`CAPACITY`] 条，每次变动落盘（`error-center.json`）。
+//!
+//! 记录只走映射层，不在业务代码里零散调用：
+//! - 命令：前端 `ipc.ts` 包了一层 `invoke`，任何命令失败都回报 `record_command_error`，
+//!   新加的命令天然覆盖
+//! - 后台：[`background_error`] 把已经 emit 给前端的失败事件映射成记录
+//!
+//! 两条路都经 [`classify`] 拆出错误码（`TOKEN_INVALID:` 之类的前缀）并脱敏。
+//! 同一来源 / 账号 / 错误的重复记录合并成一条并累加 `count`，后台每轮都失败也不会刷屏。
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// 最多保留的记录数
+pub const CAPACITY: usize = 200;
+/// 没有可识别前缀时的错误码
+pub const UNKNOWN_CODE: &str = "UNKNOWN";
+/// 识别的错误码（错误文本里以 `CODE:` 形式出现）
+pub const KNOWN_CODES: &[&str] = &[
+    "TOKEN_INVALID",
+    "TOKEN_EXPIRED",
+    "ACCOUNT_BANNED",
+    "ACCOUNT_LOGGED_OUT",
+    "ACCOUNT_AMBIGUOUS",
+    "REFRESH_REUSED",
+    "REFRESH_BANNED",
+    "RELAY_ACCOUNT",
+    "API_KEY_ACCOUNT",
+    "PERMISSION_DENIED",
+    "KEYCHAIN_LOCKED",
+    "QUOTA_EXHAUSTED",
+];
+const MAX_MESSAGE_CHARS: usize = 500;
+
+/// 错误从哪来
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "name", rename_all = "snake_case")]
+pub enum ErrorOrigin {
+    /// 前端调用的命令（命令名）
+    Command(String),
+    /// 后台任务（任务名）
+    Background(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ErrorRecord {
+    pub id: String,
+    /// 最近一次发生的时间
+    pub timestamp: DateTime<Utc>,
+    pub origin: ErrorOrigin,
+    #[serde(default)]
+    pub account_id: Option<String>,
+    /// 错误码（见 [`KNOWN_CODES`]，识别不了为 [`UNKNOWN_CODE`]）
+    pub code: String,
+    /// 去掉错误码、脱敏后的文案
+    pub message: String,
+    /// 合并进来的次数
+    #[serde(default = "one")]
+    pub count: u32,
+    #[serde(default)]
+    pub read: bool,
+}
+
+fn one() -> u32 {
+    1
+}
+
+/// `get_badge_state` 的返回：前端 / 托盘轮询用，只有计数
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct BadgeState {
+    pub unread_errors: usize,
+}
+
+/// 拆出错误码和给人看的文案：取最早出现的已知 `CODE:`，文案去掉所有错误码标记后脱敏
+pub fn classify(raw: &str) -> (String, String) {
+    let code = KNOWN_CODES
+        .iter()
+        .filter_map(|code| raw.find(&format!("{}:", code)).map(|pos| (pos, *code)))
+        .min()
+        .map_or(UNKNOWN_CODE, |(_, code)| code);
+    let mut message = raw.to_string();
+    for code in KNOWN_CODES {
+        message = message.replace(&format!("{}:", code), "");
+    }
+    let message = crate::redact::redact_text(message.trim())
+        .chars()
+        .take(MAX_MESSAGE_CHARS)
+        .collect();
+    (code.to_string(), message)
+}
+
+/// 一条后台失败：任务名、账号、原始错误文本
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackgroundError {
+    pub task: &'static str,
+    pub account_id: Option<String>,
+    pub error: String,
+}
+
+/// 会被记成后台错误的前端事件
+pub const BACKGROUND_ERROR_EVENTS: &[&str] = &[
+    "token-refresh-failed",
+    crate::refresh_ban::EVENT,
+    "proxy-account-banned",
+    "proxy-all-exhausted",
+];
+
+/// 把已经 emit 给前端的失败事件映射成后台错误（`event` 为事件名，`payload` 为其 JSON 负载）
+pub fn background_error(event: &str, payload: &Value) -> Option<BackgroundError> {
+    let text = |key: &str| payload.get(key).and_then(Value::as_str).map(str::to_string);
+    let (task, account_id, error) = match event {
+        "token-refresh-failed" => (
+            "keepalive",
+            None,
+            format!("{}：{}", text("account_name")?, text("reason")?),
+        ),
+        crate::refresh_ban::EVENT => (
+            "refresh",
+            text("account_id"),
+            format!("{}{}", crate::refresh_ban::BANNED_PREFIX, text("message")?),
+        ),
+        "proxy-account-banned" => (
+            "proxy",
+            None,
+            format!("ACCOUNT_BANNED:{} 被判定封号", payload.as_str()?),
+        ),
+        "proxy-all-exhausted" => (
+            "proxy",
+            None,
+            format!("QUOTA_EXHAUSTED:{}", payload.as_str()?),
+        ),
+        _ => return None,
+    };
+    Some(BackgroundError {
+        task,
+        account_id,
+        error,
+    })
+}
+
+/// 错误中心：有界列表 + 每次变动落盘
+pub struct ErrorCenter {
+    path: PathBuf,
+    records: Mutex<VecDeque<ErrorRecord>>,
+}
+
+impl ErrorCenter {
+    /// 从默认位置（数据目录下的 `error-center.json`）加载
+    pub fn load() -> Self {
+        Self::load_from(crate::paths::data_dir().join("error-center.json"))
+    }
+
+    /// 从指定文件加载；文件不存在或损坏时从空开始
+    pub fn load_from(path: PathBuf) -> Self {
+        let mut records: Vec<ErrorRecord> = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok())
+            .unwrap_or_default();
+        records.sort_by_key(|r| r.timestamp);
+        let skip = records.len().saturating_sub(CAPACITY);
+        Self {
+            path,
+            records: Mutex::new(records.into_iter().skip(skip).collect()),
+        }
+    }
+
+    /// 记一条错误，返回记录 id（合并到已有记录时返回那条的 id）
+    pub fn record(&self, origin: ErrorOrigin, account_id: Option<&str>, error: &str) -> String {
+        let (code, message) = classify(error);
+        let Ok(mut records) = self.records.lock() else {
+            return String::new();
+        };
+        let existing = records.iter().position(|r| {
+            r.origin == origin
+                && r.account_id.as_deref() == account_id
+                && r.code == code
+                && r.message == message
+        });
+        let record = match existing.and_then(|i| records.remove(i)) {
+            Some(mut record) => {
+                record.count += 1;
+                record.timestamp = Utc::now();
+                record.read = false;
+                record
+            }
+            None => ErrorRecord {
+                id: uuid::Uuid::new_v4().to_string(),
+                timestamp: Utc::now(),
+                origin,
+                account_id: account_id.map(str::to_string),
+                code,
+                message,
+                count: 1,
+                read: false,
+            },
+        };
+        let id = record.id.clone();
+        if records.len() >= CAPACITY {
+            records.pop_front();
+        }
+        records.push_back(record);
+        self.save(&records);
+        id
+    }
+
+    /// 最近的 `limit` 条（新的在前），返回的同时标为已读
+    pub fn recent(&self, limit: usize) -> Vec<ErrorRecord> {
+        let Ok(mut records) = self.records.lock() else {
+            return Vec::new();
+        };
+        let mut changed = false;
+        let out = records
+            .iter_mut()
+            .rev()
+            .take(limit.clamp(1, CAPACITY))
+            .map(|r| {
+                changed |= !r.read;
+                let shown = r.clone();
+                r.read = true;
+                shown
+            })
+            .collect();
+        if changed {
+            self.save(&records);
+        }
+        out
+    }
+
+    /// 删掉一条；不存在时返回 false
+    pub fn dismiss(&self, id: &str) -> bool {
+        let Ok(mut records) = self.records.lock() else {
+            return false;
+        };
+        let before = records.len();
+        records.retain(|r| r.id != id);
+        let removed = records.len() != before;
+        if removed {
+            self.save(&records);
+        }
+        removed
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut records) = self.records.lock() {
+            records.clear();
+            self.save(&records);
+        }
+    }
+
+    pub fn badge(&self) -> BadgeState {
+        BadgeState {
+            unread_errors: self
+                .records
+                .lock()
+                .map(|records| records.iter().filter(|r| !r.read).count())
+                .unwrap_or(0),
+        }
+    }
+
+    fn save(&self, records: &VecDeque<ErrorRecord>) {
+        let result = serde_json::to_vec(records)
+            .map_err(|e| e.to_string())
+            .and_then(|json| {
+                if let Some(parent) = self.path.parent() {
+                    std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                }
+                crate::atomic_write::write_atomic(&self.path, &json)
+            });
+        if let Err(e) = result {
+            eprintln!("[ErrorCenter] 落盘失败: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn temp_center() -> (ErrorCenter, PathBuf) {
+        let path = std::env::temp_dir()
+            .join(format!("cs-errors-{}", uuid::Uuid::new_v4()))
+            .join("error-center.json");
+        (ErrorCenter::load_from(path.clone()), path)
+    }
+
+    fn command(name: &str) -> ErrorOrigin {
+        ErrorOrigin::Command(name.to_string())
+    }
+
+    #[test]
+    fn classify_extracts_codes_and_redacts() {
+        assert_eq!(
+            classify("TOKEN_INVALID:刷新 token 失败: REFRESH_REUSED:被拒绝"),
+            (
+                "TOKEN_INVALID".to_string(),
+                "刷新 token 失败: 被拒绝".to_string()
+            )
+        );
+        assert_eq!(classify("网络超时").0, UNKNOWN_CODE);
+        let (_, message) = classify("401 refresh_token=rt_abcdefgh");
+        assert!(!message.contains("rt_abcdefgh"));
+    }
+
+    #[test]
+    fn command_and_background_errors_are_captured_through_the_mapping() {
+        let (center, path) = temp_center();
+        center.record(
+            command("switch_account"),
+            Some("id-1"),
+            "ACCOUNT_BANNED:账号已封禁",
+        );
+
+        let payload =
+            json!({ "account_id": "id-2", "account_name": "b", "message": "需要重新登录" });
+        let bg = background_error(crate::refresh_ban::EVENT, &payload).unwrap();
+        center.record(
+            ErrorOrigin::Background(bg.task.to_string()),
+            bg.account_id.as_deref(),
+            &bg.error,
+        );
+        let exhausted =
+            background_error("proxy-all-exhausted", &json!("所有账号额度已耗尽")).unwrap();
+        assert_eq!(classify(&exhausted.error).0, "QUOTA_EXHAUSTED");
+        assert!(background_error("accounts-updated", &Value::Null).is_none());
+
+        let records = center.recent(10);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].origin, ErrorOrigin::Background("refresh".into()));
+        assert_eq!(records[0].code, "REFRESH_BANNED");
+        assert_eq!(records[0].account_id.as_deref(), Some("id-2"));
+        assert_eq!(records[1].origin, command("switch_account"));
+        assert_eq!(records[1].code, "ACCOUNT_BANNED");
+        assert_eq!(records[1].message, "账号已封禁");
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn unread_counter_transitions() {
+        let (center, path) = temp_center();
+        assert_eq!(center.badge().unread_errors, 0);
+        let a = center.record(command("a"), None, "boom");
+        center.record(command("b"), None, "boom");
+        assert_eq!(center.badge().unread_errors, 2);
+
+        // 只看最新一条，只有它变成已读
+        assert!(!center.recent(1)[0].read);
+        assert_eq!(center.badge().unread_errors, 1);
+        assert!(center.dismiss(&a));
+        assert!(!center.dismiss(&a));
+        assert_eq!(center.badge().unread_errors, 0);
+
+        // 已读的记录再次发生：合并、重新变成未读
+        let again = center.record(command("b"), None, "boom");
+        assert_eq!(center.badge().unread_errors, 1);
+        let records = center.recent(10);
+        assert_eq!(records.len(), 1);
+        assert_eq!(
+            (records[0].id.as_str(), records[0].count),
+            (again.as_str(), 2)
+        );
+
+        center.record(command("c"), None, "boom");
+        center.clear();
+        assert_eq!(center.badge().unread_errors, 0);
+        assert!(center.recent(10).is_empty());
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+
+    #[test]
+    fn list_is_bounded_and_survives_reload() {
+        let (center, path) = temp_center();
+        for i in 0..CAPACITY + 5 {
+            center.record(command(&format!("cmd{i}")), None, "boom");
+        }
+        let reloaded = ErrorCenter::load_from(path.clone());
+        assert_eq!(reloaded.badge().unread_errors, CAPACITY);
+        let records = reloaded.recent(CAPACITY);
+        assert_eq!(records.len(), CAPACITY);
+        assert_eq!(records[0].origin, command(&format!("cmd{}", CAPACITY + 4)));
+        assert_eq!(records[CAPACITY - 1].origin, command("cmd5"));
+
+        // 已读状态也落盘了
+        assert_eq!(
+            ErrorCenter::load_from(path.clone()).badge().unread_errors,
+            0
+        );
+        let _ = std::fs::remove_dir_all(path.parent().unwrap());
+    }
+}
@@ -0,0 +1,534 @@
+//! 应用事件总线 + Remote Server `/events` SSE 推送
+//!
+//! 事件统一走 `EventBus`（tokio broadcast）：
+//! - Tauri 侧由转发任务订阅后 `emit(event.name(), &event)` 给前端
+//! - Remote Server 的 `GET /events` 每个连接各订阅一份，按 SSE 推给外部看板
+//!
+//! 两边拿到的是同一个 `AppEvent` 序列化结果，负载不会分叉。
+//!
+//! SSE 连接：
+//! - 鉴权同 Remote Server（`X-Auth-Token` 或 `Authorization: Bearer <secret>`）
+//! - 每条事件一帧 `event: <name>\ndata: <单行 JSON>\n\n`
+//! - 每 30s 一帧注释心跳 `: heartbeat\n\n`
+//! - 每个客户端只有一个小的有界缓冲，写不进去就直接断开该客户端，不无限堆积
+//! - Server 停止（JoinHandle 被 abort）时关闭所有连接
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::time::Duration;
+
+use bytes::Bytes;
+use chrono::{DateTime, Utc};
+use http_body_util::combinators::UnsyncBoxBody;
+use http_body_util::{BodyExt, Full, StreamBody};
+use hyper::body::Frame;
+use hyper::{Response, StatusCode};
+use schemars::JsonSchema;
+use serde::Serialize;
+use tokio::sync::{broadcast, mpsc, watch};
+
+use crate::account::{AccountStore, QuotaSource};
+
+/// broadcast 通道容量；订阅者落后超过这么多条会收到 Lagged
+const BUS_CAPACITY: usize = 256;
+
+pub type SseBody = UnsyncBoxBody<Bytes, Infallible>;
+
+/// 前端和 SSE 共用的类型化事件
+#[derive(Debug, Clone, PartialEq, Serialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum AppEvent {
+    /// 当前账号变化（None = 没有当前账号）
+    CurrentChanged {
+        account_id: Option<String>,
+        account_name: Option<String>,
+    },
+    /// 某个账号的额度缓存刷新了
+    QuotaUpdated {
+        account_id: String,
+        account_name: String,
+        plan_type: String,
+        five_hour_left: f64,
+        weekly_left: f64,
+        updated_at: DateTime<Utc>,
+        source: QuotaSource,
+    },
+    /// 当前账号在 IDE 里有未同步的 token 更新
+    SyncConflict { account_name: String },
+    /// 刷新额度时发现套餐变了（plan 字段为原始值，display 为展示名）
+    PlanChanged {
+        account_id: String,
+        account_name: String,
+        old_plan: String,
+        new_plan: String,
+        old_display: String,
+        new_display: String,
+        downgrade: bool,
+        changed_at: DateTime<Utc>,
+    },
+}
+
+impl AppEvent {
+    /// Tauri 事件名 / SSE `event:` 字段
+    pub fn name(&self) -> &'static str {
+        match self {
+            AppEvent::CurrentChanged { .. } => "current-changed",
+            AppEvent::QuotaUpdated { .. } => "quota-updated",
+            AppEvent::SyncConflict { .. } => "sync-conflict",
+            AppEvent::PlanChanged { .. } => "plan-changed",
+        }
+    }
+
+    /// 编码成一帧 SSE
+    pub fn to_sse_frame(&self) -> Bytes {
+        let data = serde_json::to_string(self).unwrap_or_else(|_| "{}".to_string());
+        Bytes::from(format!("event: {}\ndata: {}\n\n", self.name(), data))
+    }
+}
+
+/// 事件总线；clone 共享同一个 broadcast 通道
+#[derive(Clone)]
+pub struct EventBus {
+    tx: broadcast::Sender<AppEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        let (tx, _) = broadcast::channel(BUS_CAPACITY);
+        Self { tx }
+    }
+}
+
+impl EventBus {
+    /// 发布事件；没有订阅者时静默丢弃
+    pub fn publish(&self, event: AppEvent) {
+        let _ = self.tx.send(event);
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<AppEvent> {
+        self.tx.subscribe()
+    }
+}
+
+/// 上一次看到的 store 状态，用来从 "accounts-updated" 里推导出具体事件
+#[derive(Debug, Default)]
+pub struct StoreDigest {
+    current: Option<String>,
+    quota_stamps: HashMap<String, DateTime<Utc>>,
+    plan_change_stamps: HashMap<String, DateTime<Utc>>,
+}
+
+impl StoreDigest {
+    pub fn from_store(store: &AccountStore) -> Self {
+        let mut digest = Self::default();
+        digest.diff(store);
+        digest
+    }
+
+    /// 和上次对比，返回变化对应的事件并更新基准
+    pub fn diff(&mut self, store: &AccountStore) -> Vec<AppEvent> {
+        let mut events = Vec::new();
+        if store.current != self.current {
+            let name = store
+                .current
+                .as_ref()
+                .and_then(|id| store.accounts.get(id))
+                .map(|a| a.name.clone());
+            events.push(AppEvent::CurrentChanged {
+                account_id: store.current.clone(),
+                account_name: name,
+            });
+            self.current = store.current.clone();
+        }
+
+        let mut stamps = HashMap::with_capacity(store.accounts.len());
+        let mut plan_stamps = HashMap::new();
+        let mut ids: Vec<&String> = store.accounts.keys().collect();
+        ids.sort();
+        for id in ids {
+            let account = &store.accounts[id];
+            if let Some(change) = account.last_plan_change.as_ref() {
+                if self.plan_change_stamps.get(id) != Some(&change.changed_at) {
+                    events.push(AppEvent::PlanChanged {
+                        account_id: id.clone(),
+                        account_name: account.name.clone(),
+                        old_plan: change.old_plan.clone(),
+                        new_plan: change.new_plan.clone(),
+                        old_display: crate::plan::display_plan(&change.old_plan),
+                        new_display: crate::plan::display_plan(&change.new_plan),
+                        downgrade: change.is_downgrade(),
+                        changed_at: change.changed_at,
+                    });
+                }
+                plan_stamps.insert(id.clone(), change.changed_at);
+            }
+            let Some(quota) = account.cached_quota.as_ref() else {
+                continue;
+            };
+            if self.quota_stamps.get(id) != Some(&quota.updated_at) {
+                events.push(AppEvent::QuotaUpdated {
+                    account_id: id.clone(),
+                    account_name: account.name.clone(),
+                    plan_type: quota.plan_type.clone(),
+                    five_hour_left: quota.five_hour_left,
+                    weekly_left: quota.weekly_left,
+                    updated_at: quota.updated_at,
+                    source: quota.source,
+                });
+            }
+            stamps.insert(id.clone(), quota.updated_at);
+        }
+        self.quota_stamps = stamps;
+        self.plan_change_stamps = plan_stamps;
+        events
+    }
+}
+
+/// SSE 连接参数
+#[derive(Debug, Clone, Copy)]
+pub struct SseConfig {
+    pub heartbeat: Duration,
+    /// 单个客户端待发送帧的上限，写满即断开
+    pub client_buffer: usize,
+}
+
+impl Default for SseConfig {
+    fn default() -> Self {
+        Self {
+            heartbeat: Duration::from_secs(30),
+            client_buffer: 32,
+        }
+    }
+}
+
+pub const HEARTBEAT_FRAME: &str = ": heartbeat\n\n";
+
+/// 校验 `X-Auth-Token` 或 `Authorization: Bearer`；未配置密钥时一律拒绝
+pub fn check_auth(secret: &str, headers: &hyper::HeaderMap) -> bool {
+    if secret.is_empty() {
+        return false;
+    }
+    let header = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+    header("X-Auth-Token") == Some(secret)
+        || header("Authorization").and_then(|v| v.strip_prefix("Bearer ")) == Some(secret)
+}
+
+/// 把 broadcast 里的事件搬到单个客户端的有界队列。
+/// 客户端断开、跟不上（队列满 / broadcast 落后）或 server 关闭时返回。
+pub async fn pump(
+    mut events: broadcast::Receiver<AppEvent>,
+    out: mpsc::Sender<Bytes>,
+    config: SseConfig,
+    mut shutdown: watch::Receiver<()>,
+) {
+    let mut heartbeat = tokio::time::interval(config.heartbeat);
+    heartbeat.tick().await; // 第一下立即触发，跳过
+    loop {
+        let frame = tokio::select! {
+            received = events.recv() => match received {
+                Ok(event) => event.to_sse_frame(),
+                Err(broadcast::error::RecvError::Lagged(n)) => {
+                    eprintln!("[Events] SSE 客户端落后 {} 条，断开", n);
+                    return;
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            },
+            _ = heartbeat.tick() => Bytes::from_static(HEARTBEAT_FRAME.as_bytes()),
+            _ = shutdown.changed() => return,
+        };
+        match out.try_send(frame) {
+            Ok(()) => {}
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                eprintln!("[Events] SSE 客户端消费过慢，断开");
+                return;
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => return,
+        }
+    }
+}
+
+fn full_body(status: StatusCode, body: &'static str) -> Response<SseBody> {
+    let mut resp = Response::new(Full::new(Bytes::from_static(body.as_bytes())).boxed_unsync());
+    *resp.status_mut() = status;
+    resp.headers_mut().insert(
+        hyper::header::CONTENT_TYPE,
+        hyper::header::HeaderValue::from_static("application/json"),
+    );
+    resp
+}
+
+/// `GET /events`：鉴权后返回 SSE 流
+pub fn serve_events(
+    bus: &EventBus,
+    secret: &str,
+    headers: &hyper::HeaderMap,
+    config: SseConfig,
+    shutdown: watch::Receiver<()>,
+) -> Response<SseBody> {
+    if !check_auth(secret, headers) {
+        return full_body(StatusCode::UNAUTHORIZED, r#"{"error":"unauthorized"}"#);
+    }
+
+    let (tx, rx) = mpsc::channel::<Bytes>(config.client_buffer.max(1));
+    tokio::spawn(pump(bus.subscribe(), tx, config, shutdown));
+
+    let stream = futures_util::stream::unfold(rx, |mut rx| async move {
+        rx.recv()
+            .await
+            .map(|frame| (Ok::<_, Infallible>(Frame::data(frame)), rx))
+    });
+    let mut resp = Response::new(StreamBody::new(stream).boxed_unsync());
+    let headers = resp.headers_mut();
+    headers.insert(
+        hyper::header::CONTENT_TYPE,
+        hyper::header::HeaderValue::from_static("text/event-stream"),
+    );
+    headers.insert(
+        hyper::header::CACHE_CONTROL,
+        hyper::header::HeaderValue::from_static("no-cache"),
+    );
+    resp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::server::conn::http1;
+    use hyper::service::service_fn;
+    use hyper_util::rt::TokioIo;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::{TcpListener, TcpStream};
+
+    const SECRET: &str = "s3cret";
+
+    fn conflict(name: &str) -> AppEvent {
+        AppEvent::SyncConflict {
+            account_name: name.to_string(),
+        }
+    }
+
+    /// 起一个只挂 `/events` 的 hyper server，返回端口和用来关闭的 watch sender
+    async fn spawn_server(bus: EventBus, config: SseConfig) -> (u16, watch::Sender<()>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (shutdown_tx, shutdown_rx) = watch::channel(());
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let (bus, shutdown) = (bus.clone(), shutdown_rx.clone());
+                tokio::spawn(async move {
+                    let service = service_fn(move |req: hyper::Request<hyper::body::Incoming>| {
+                        let resp =
+                            serve_events(&bus, SECRET, req.headers(), config, shutdown.clone());
+                        async move { Ok::<_, Infallible>(resp) }
+                    });
+                    let _ = http1::Builder::new()
+                        .serve_connection(TokioIo::new(stream), service)
+                        .await;
+                });
+            }
+        });
+        (port, shutdown_tx)
+    }
+
+    async fn connect(port: u16, auth: &str) -> TcpStream {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        let req = format!(
+            "GET /events HTTP/1.1\r\nHost: localhost\r\n{}\r\n",
+            if auth.is_empty() {
+                String::new()
+            } else {
+                format!("{}\r\n", auth)
+            }
+        );
+        stream.write_all(req.as_bytes()).await.unwrap();
+        stream
+    }
+
+    /// 一直读到出现 `needle` 为止（2s 超时），返回累计读到的文本
+    async fn read_until(stream: &mut TcpStream, needle: &str) -> String {
+        let mut buf = Vec::new();
+        let mut chunk = [0u8; 4096];
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(2);
+        while !String::from_utf8_lossy(&buf).contains(needle) {
+            let n = tokio::time::timeout_at(deadline, stream.read(&mut chunk))
+                .await
+                .expect("timed out waiting for SSE data")
+                .unwrap();
+            if n == 0 {
+                break;
+            }
+            buf.extend_from_slice(&chunk[..n]);
+        }
+        String::from_utf8_lossy(&buf).into_owned()
+    }
+
+    #[tokio::test]
+    async fn rejects_missing_or_wrong_token() {
+        let (port, _shutdown) = spawn_server(EventBus::default(), SseConfig::default()).await;
+        for auth in ["", "Authorization: Bearer nope", "X-Auth-Token: nope"] {
+            let mut stream = connect(port, auth).await;
+            let text = read_until(&mut stream, "unauthorized").await;
+            assert!(text.starts_with("HTTP/1.1 401"), "{}", text);
+        }
+    }
+
+    #[tokio::test]
+    async fn delivers_events_as_sse_frames() {
+        let bus = EventBus::default();
+        let (port, _shutdown) = spawn_server(bus.clone(), SseConfig::default()).await;
+        let mut stream = connect(port, &format!("Authorization: Bearer {}", SECRET)).await;
+        let head = read_until(&mut stream, "\r\n\r\n").await;
+        assert!(head.starts_with("HTTP/1.1 200"), "{}", head);
+        assert!(head.contains("text/event-stream"));
+
+        bus.publish(conflict("work"));
+        let text = read_until(&mut stream, "\n\n").await;
+        assert!(text.contains("event: sync-conflict\n"), "{}", text);
+        assert!(
+            text.contains(r#"data: {"type":"sync-conflict","account_name":"work"}"#),
+            "{}",
+            text
+        );
+    }
+
+    #[tokio::test]
+    async fn sends_heartbeat_comments() {
+        let config = SseConfig {
+            heartbeat: Duration::from_millis(50),
+            ..SseConfig::default()
+        };
+        let (port, _shutdown) = spawn_server(EventBus::default(), config).await;
+        let mut stream = connect(port, &format!("X-Auth-Token: {}", SECRET)).await;
+        let text = read_until(&mut stream, HEARTBEAT_FRAME).await;
+        assert!(text.contains(HEARTBEAT_FRAME));
+    }
+
+    #[tokio::test]
+    async fn shutdown_closes_client_connections() {
+        let (port, shutdown) = spawn_server(EventBus::default(), SseConfig::default()).await;
+        let mut stream = connect(port, &format!("X-Auth-Token: {}", SECRET)).await;
+        read_until(&mut stream, "\r\n\r\n").await;
+        drop(shutdown);
+        // chunked 结束块 `0\r\n\r\n` 表示流被服务端正常关闭
+        let text = read_until(&mut stream, "0\r\n\r\n").await;
+        assert!(text.ends_with("0\r\n\r\n"), "{:?}", text);
+    }
+
+    #[tokio::test]
+    async fn slow_client_is_dropped_instead_of_buffered() {
+        let bus = EventBus::default();
+        let (tx, mut rx) = mpsc::channel(2);
+        let (_shutdown_tx, shutdown_rx) = watch::channel(());
+        let config = SseConfig {
+            client_buffer: 2,
+            ..SseConfig::default()
+        };
+        let task = tokio::spawn(pump(bus.subscribe(), tx, config, shutdown_rx));
+        // 客户端一条都不读
+        for i in 0..5 {
+            bus.publish(conflict(&format!("acc{}", i)));
+        }
+        tokio::time::timeout(Duration::from_secs(2), task)
+            .await
+            .expect("pump should give up on a slow client")
+            .unwrap();
+        // 已缓冲的两条之后通道关闭
+        assert!(rx.recv().await.is_some());
+        assert!(rx.recv().await.is_some());
+        assert!(rx.recv().await.is_none());
+    }
+
+    #[test]
+    fn digest_reports_current_and_quota_changes_once() {
+        let mut store = AccountStore::default();
+        let account = store.add_account("work".to_string(), serde_json::json!({}), None);
+        let id = account.id.clone();
+        store.current = None;
+        let mut digest = StoreDigest::from_store(&store);
+        assert!(digest.diff(&store).is_empty());
+
+        store.current = Some(id.clone());
+        store.accounts.get_mut(&id).unwrap().cached_quota = Some(crate::account::CachedQuota {
+            five_hour_left: 40.0,
+            five_hour_reset: String::new(),
+            five_hour_reset_at: None,
+            five_hour_label: String::new(),
+            weekly_left: 70.0,
+            weekly_reset: String::new(),
+            weekly_reset_at: None,
+            weekly_label: String::new(),
+            plan_type: "plus".to_string(),
+            is_valid_for_cli: true,
+            codex_available: true,
+            updated_at: Utc::now(),
+            display_plan: "Plus".to_string(),
+            source: crate::account::QuotaSource::Scheduler,
+            five_hour_counts: Default::default(),
+            weekly_counts: Default::default(),
+            suspect: false,
+        });
+        let events = digest.diff(&store);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].name(), "current-changed");
+        assert_eq!(events[1].name(), "quota-updated");
+        assert_eq!(
+            serde_json::to_value(&events[1]).unwrap()["source"],
+            "scheduler"
+        );
+        assert!(digest.diff(&store).is_empty());
+    }
+
+    #[test]
+    fn plan_change_is_emitted_once() {
+        let quota = |plan: &str| crate::account::CachedQuota {
+            five_hour_left: 50.0,
+            five_hour_reset: String::new(),
+            five_hour_reset_at: None,
+            five_hour_label: String::new(),
+            weekly_left: 50.0,
+            weekly_reset: String::new(),
+            weekly_reset_at: None,
+            weekly_label: String::new(),
+            plan_type: plan.to_string(),
+            is_valid_for_cli: true,
+            codex_available: true,
+            updated_at: Utc::now(),
+            display_plan: crate::plan::display_plan(plan),
+            source: crate::account::QuotaSource::Scheduler,
+            five_hour_counts: Default::default(),
+            weekly_counts: Default::default(),
+            suspect: false,
+        };
+        let mut store = AccountStore::default();
+        let id = store
+            .add_account("work".to_string(), serde_json::json!({}), None)
+            .id;
+        let account = store.accounts.get_mut(&id).unwrap();
+        account.set_cached_quota(quota("pro"));
+        let mut digest = StoreDigest::from_store(&store);
+
+        let account = store.accounts.get_mut(&id).unwrap();
+        assert!(account.set_cached_quota(quota("plus")).is_some());
+        let plan_events: Vec<AppEvent> = digest
+            .diff(&store)
+            .into_iter()
+            .filter(|e| e.name() == "plan-changed")
+            .collect();
+        assert_eq!(plan_events.len(), 1);
+        let payload = serde_json::to_value(&plan_events[0]).unwrap();
+        assert_eq!(payload["old_display"], "Pro");
+        assert_eq!(payload["new_display"], "Plus");
+        assert_eq!(payload["downgrade"], true);
+
+        // 同一套餐再刷几次：quota-updated 照发，plan-changed 不再发
+        for _ in 0..2 {
+            let account = store.accounts.get_mut(&id).unwrap();
+            assert!(account.set_cached_quota(quota("plus")).is_none());
+            assert!(digest
+                .diff(&store)
+                .iter()
+                .all(|e| e.name() != "plan-changed"));
+        }
+    }
+}
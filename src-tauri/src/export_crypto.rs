@@ -0,0 +1,244 @@
+//! 带口令加密的账号导出
+//!
+//! 明文导出里全是 refresh_token，贴进聊天或丢进同步盘就等于交出账号。
+//! 这里把 `AccountStore::export` 的结果整段加密成一个自描述的 JSON 信封 [`Envelope`]：
+//! - `magic` 固定为 [`MAGIC`]，`version` 为 [`FORMAT_VERSION`]；格式以后变了靠它们识别
+//! - 密钥由口令经 PBKDF2-HMAC-SHA256 派生（迭代次数、盐写在信封里）
+//! - 加密用 ChaCha20-Poly1305（AEAD），`magic` 和 `version` 作为附加数据一起认证
+//!
+//! 加密原语用已经在依赖树里的 `ring`，没有为此另引 argon2 / scrypt。
+//! 口令错了或密文被改过，AEAD 校验都会失败，统一报 [`DECRYPT_FAILED`]，不会走到 JSON 解析。
+//! 前端拿不准粘贴的是哪种格式时用 [`detect_format`]。
+
+use std::num::NonZeroU32;
+
+use base64::Engine;
+use rand::{rng, RngCore};
+use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, CHACHA20_POLY1305, NONCE_LEN};
+use ring::pbkdf2;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// 信封的 `magic`
+pub const MAGIC: &str = "codex-switcher-encrypted-export";
+/// 当前写出的信封版本；改了 KDF / 算法 / 字段时 +1
+pub const FORMAT_VERSION: u32 = 1;
+/// 新导出用的 PBKDF2 迭代次数（OWASP 对 HMAC-SHA256 的建议值）
+pub const PBKDF2_ITERATIONS: u32 = 600_000;
+/// 读信封时接受的迭代次数上限，防止手改的信封把解密拖成几分钟
+const MAX_ITERATIONS: u32 = 10_000_000;
+/// 导出时口令的最短长度（字符）
+pub const MIN_PASSPHRASE_CHARS: usize = 8;
+pub const DECRYPT_FAILED: &str = "解密失败：口令错误或数据已损坏";
+
+const KDF: &str = "pbkdf2-hmac-sha256";
+const CIPHER: &str = "chacha20-poly1305";
+const SALT_LEN: usize = 16;
+const KEY_LEN: usize = 32;
+
+/// 加密导出的 JSON 信封；二进制字段都是标准 base64
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Envelope {
+    pub magic: String,
+    pub version: u32,
+    pub kdf: String,
+    pub iterations: u32,
+    pub salt: String,
+    pub cipher: String,
+    pub nonce: String,
+    /// 密文 + 16 字节认证标签
+    pub ciphertext: String,
+}
+
+/// 一段导出内容的格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    /// `export_accounts` 的明文 JSON
+    Plaintext,
+    /// [`Envelope`]
+    Encrypted,
+    /// 都不像
+    Unknown,
+}
+
+/// 判断一段文本是哪种导出：带 [`MAGIC`] 的是加密信封，有 `accounts` 对象的是明文
+pub fn detect_format(text: &str) -> ExportFormat {
+    let Ok(doc) = serde_json::from_str::<Value>(text.trim()) else {
+        return ExportFormat::Unknown;
+    };
+    if doc.get("magic").and_then(Value::as_str) == Some(MAGIC) {
+        ExportFormat::Encrypted
+    } else if doc.get("accounts").is_some_and(Value::is_object) {
+        ExportFormat::Plaintext
+    } else {
+        ExportFormat::Unknown
+    }
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], iterations: NonZeroU32) -> LessSafeKey {
+    let mut key = [0u8; KEY_LEN];
+    pbkdf2::derive(
+        pbkdf2::PBKDF2_HMAC_SHA256,
+        iterations,
+        salt,
+        passphrase.as_bytes(),
+        &mut key,
+    );
+    // 长度固定为 32，和算法要求一致，不会失败
+    LessSafeKey::new(UnboundKey::new(&CHACHA20_POLY1305, &key).expect("chacha20 key"))
+}
+
+fn aad(version: u32) -> String {
+    format!("{}/{}", MAGIC, version)
+}
+
+/// 用口令加密，返回信封的 JSON 文本
+pub fn encrypt(plaintext: &str, passphrase: &str) -> Result<String, String> {
+    encrypt_with(plaintext, passphrase, PBKDF2_ITERATIONS)
+}
+
+fn encrypt_with(plaintext: &str, passphrase: &str, iterations: u32) -> Result<String, String> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_CHARS {
+        return Err(format!("口令至少 {} 个字符", MIN_PASSPHRASE_CHARS));
+    }
+    let iterations = NonZeroU32::new(iterations).ok_or("迭代次数不能为 0")?;
+    let mut salt = [0u8; SALT_LEN];
+    let mut nonce = [0u8; NONCE_LEN];
+    rng().fill_bytes(&mut salt);
+    rng().fill_bytes(&mut nonce);
+
+    let key = derive_key(passphrase, &salt, iterations);
+    let mut sealed = plaintext.as_bytes().to_vec();
+    key.seal_in_place_append_tag(
+        Nonce::assume_unique_for_key(nonce),
+        Aad::from(aad(FORMAT_VERSION).as_bytes()),
+        &mut sealed,
+    )
+    .map_err(|_| "加密失败".to_string())?;
+
+    let b64 = base64::engine::general_purpose::STANDARD;
+    let envelope = Envelope {
+        magic: MAGIC.to_string(),
+        version: FORMAT_VERSION,
+        kdf: KDF.to_string(),
+        iterations: iterations.get(),
+        salt: b64.encode(salt),
+        cipher: CIPHER.to_string(),
+        nonce: b64.encode(nonce),
+        ciphertext: b64.encode(sealed),
+    };
+    serde_json::to_string_pretty(&envelope).map_err(|e| format!("加密导出失败: {}", e))
+}
+
+/// 解开信封，返回原来的明文导出
+pub fn decrypt(payload: &str, passphrase: &str) -> Result<String, String> {
+    let doc: Value = serde_json::from_str(payload.trim())
+        .map_err(|_| "不是加密导出：内容不是 JSON".to_string())?;
+    if doc.get("magic").and_then(Value::as_str) != Some(MAGIC) {
+        return Err("不是加密导出：缺少加密导出标识".to_string());
+    }
+    let version = doc.get("version").and_then(Value::as_u64).unwrap_or(0);
+    if version > u64::from(FORMAT_VERSION) {
+        return Err(format!(
+            "加密导出格式版本 {} 比本程序支持的 {} 新，请升级后再导入",
+            version, FORMAT_VERSION
+        ));
+    }
+    let envelope: Envelope =
+        serde_json::from_value(doc).map_err(|e| format!("加密导出信封不完整: {}", e))?;
+    if envelope.kdf != KDF || envelope.cipher != CIPHER {
+        return Err(format!(
+            "不支持的加密方式: {} / {}",
+            envelope.kdf, envelope.cipher
+        ));
+    }
+    let iterations = NonZeroU32::new(envelope.iterations)
+        .filter(|n| n.get() <= MAX_ITERATIONS)
+        .ok_or_else(|| format!("迭代次数不合法: {}", envelope.iterations))?;
+
+    let b64 = base64::engine::general_purpose::STANDARD;
+    let field = |name: &str, value: &str| {
+        b64.decode(value)
+            .map_err(|_| format!("加密导出信封的 {} 不是合法 base64", name))
+    };
+    let salt = field("salt", &envelope.salt)?;
+    let nonce = Nonce::try_assume_unique_for_key(&field("nonce", &envelope.nonce)?)
+        .map_err(|_| "加密导出信封的 nonce 长度不对".to_string())?;
+    let mut sealed = field("ciphertext", &envelope.ciphertext)?;
+
+    let key = derive_key(passphrase, &salt, iterations);
+    let plaintext = key
+        .open_in_place(
+            nonce,
+            Aad::from(aad(envelope.version).as_bytes()),
+            &mut sealed,
+        )
+        .map_err(|_| DECRYPT_FAILED.to_string())?;
+    String::from_utf8(plaintext.to_vec()).map_err(|_| DECRYPT_FAILED.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 测试里用小迭代次数，免得 debug 构建下每次派生都要好几秒
+    const FAST: u32 = 1_000;
+    const EXPORT: &str = r#"{"accounts": {"a": {"refresh_token": "rt-secret"}}}"#;
+
+    #[test]
+    fn round_trips_and_hides_the_plaintext() {
+        let sealed = encrypt_with(EXPORT, "correct horse", FAST).unwrap();
+        assert!(!sealed.contains("rt-secret"));
+        assert_eq!(detect_format(&sealed), ExportFormat::Encrypted);
+        assert_eq!(detect_format(EXPORT), ExportFormat::Plaintext);
+        assert_eq!(detect_format("rt-secret"), ExportFormat::Unknown);
+
+        let envelope: Envelope = serde_json::from_str(&sealed).unwrap();
+        assert_eq!(envelope.version, FORMAT_VERSION);
+        assert_eq!(envelope.iterations, FAST);
+        assert_eq!(decrypt(&sealed, "correct horse").unwrap(), EXPORT);
+
+        // 同样的输入每次盐和 nonce 都不同
+        assert_ne!(sealed, encrypt_with(EXPORT, "correct horse", FAST).unwrap());
+        assert!(encrypt(EXPORT, "short").unwrap_err().contains("至少"));
+    }
+
+    #[test]
+    fn wrong_passphrase_or_tampering_is_a_decryption_error() {
+        let sealed = encrypt_with(EXPORT, "correct horse", FAST).unwrap();
+        assert_eq!(decrypt(&sealed, "wrong horse").unwrap_err(), DECRYPT_FAILED);
+
+        let mut envelope: Envelope = serde_json::from_str(&sealed).unwrap();
+        let b64 = base64::engine::general_purpose::STANDARD;
+        let mut bytes = b64.decode(&envelope.ciphertext).unwrap();
+        bytes[0] ^= 1;
+        envelope.ciphertext = b64.encode(bytes);
+        let tampered = serde_json::to_string(&envelope).unwrap();
+        assert_eq!(
+            decrypt(&tampered, "correct horse").unwrap_err(),
+            DECRYPT_FAILED
+        );
+    }
+
+    #[test]
+    fn foreign_or_newer_payloads_are_rejected_up_front() {
+        assert!(decrypt(EXPORT, "correct horse")
+            .unwrap_err()
+            .contains("不是加密导出"));
+        assert!(decrypt("{", "correct horse")
+            .unwrap_err()
+            .contains("不是加密导出"));
+
+        let sealed = encrypt_with(EXPORT, "correct horse", FAST).unwrap();
+        let mut doc: Value = serde_json::from_str(&sealed).unwrap();
+        doc["version"] = Value::from(FORMAT_VERSION + 1);
+        let err = decrypt(&doc.to_string(), "correct horse").unwrap_err();
+        assert!(err.contains("请升级"), "{}", err);
+
+        doc["version"] = Value::from(FORMAT_VERSION);
+        doc["iterations"] = Value::from(MAX_ITERATIONS + 1);
+        let err = decrypt(&doc.to_string(), "correct horse").unwrap_err();
+        assert!(err.contains("迭代次数"), "{}", err);
+    }
+}
@@ -0,0 +1,423 @@
+//! 从其它工具的配置目录里发现登录凭据
+//!
+//! 同一个身份的 token 经常不止在 `~/.codex/auth.json` 里：有些社区 CLI 会在自己的点目录下
+//! 存一份兼容的 token JSON。这里按 [`KNOWN_SOURCES`] 登记的位置扫一遍（只读），
+//! 解析走 `auth_identify`（历史格式一并归一），和账号库比对后列出来，不导入任何东西；
+//! 用户挑中某一条再 [`adopt`]，经 `auth_dir_import::merge_auth` 按身份新增或覆盖。
+//!
+//! 扫描有边界：
+//! - 只看登记过的路径，通配符只允许出现在最后一段，每个模式最多展开 [`MAX_MATCHES_PER_PATTERN`] 个文件
+//! - 单个文件不超过 [`MAX_FILE_BYTES`]
+//! - 符号链接解析后必须还在主目录内，指到外面的直接跳过
+//! - 某个位置读不了 / 解析不了只记进 [`DiscoveryReport::errors`]，不影响其它位置
+//!
+//! 永远不写来源工具的文件。新增位置只需在 [`KNOWN_SOURCES`] 里加一行。
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::account::AccountStore;
+use crate::auth_dir_import::{self, FileOutcome};
+use crate::auth_identify::{self, AccountSummary, ParsedIdentity};
+
+/// 单个文件的大小上限；兼容 auth.json 的文件一般只有几 KB
+pub const MAX_FILE_BYTES: u64 = 256 * 1024;
+/// 每个通配模式最多展开多少个文件
+pub const MAX_MATCHES_PER_PATTERN: usize = 32;
+
+/// 登记位置适用的平台
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    Any,
+    MacOs,
+    Linux,
+    Windows,
+}
+
+impl Platform {
+    fn is_current(self) -> bool {
+        match self {
+            Platform::Any => true,
+            Platform::MacOs => cfg!(target_os = "macos"),
+            Platform::Linux => cfg!(target_os = "linux"),
+            Platform::Windows => cfg!(windows),
+        }
+    }
+}
+
+/// 一个已知的凭据位置
+#[derive(Debug, Clone, Copy)]
+pub struct KnownSource {
+    /// 来源工具名（展示用）
+    pub tool: &'static str,
+    pub platform: Platform,
+    /// 相对主目录的路径，`/` 分隔；最后一段可以带一个 `*`
+    pub pattern: &'static str,
+}
+
+/// 已知位置登记表
+pub const KNOWN_SOURCES: &[KnownSource] = &[
+    // just-every/code：Codex 的社区分支，auth.json 格式相同
+    KnownSource {
+        tool: "every-code",
+        platform: Platform::Any,
+        pattern: ".code/auth.json",
+    },
+    // CLIProxyAPI：每个 Codex 登录一个 codex-<邮箱>.json，token 写在根上
+    KnownSource {
+        tool: "cli-proxy-api",
+        platform: Platform::Any,
+        pattern: ".cli-proxy-api/codex-*.json",
+    },
+];
+
+/// 发现的一份凭据
+#[derive(Serialize, Clone, Debug)]
+pub struct DiscoveredLogin {
+    pub source_tool: String,
+    pub path: String,
+    pub identity: ParsedIdentity,
+    pub matched_account: Option<AccountSummary>,
+    /// 和匹配到的账号比，这份 token 是否更新；没匹配到或比不出来为 None
+    pub token_fresher_than_stored: Option<bool>,
+}
+
+/// 某个位置扫描失败
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct LocationError {
+    pub source_tool: String,
+    pub path: String,
+    pub error: String,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct DiscoveryReport {
+    pub logins: Vec<DiscoveredLogin>,
+    pub errors: Vec<LocationError>,
+}
+
+/// 最后一段的 `*` 通配（只支持一个）
+fn matches_glob(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('*') {
+        Some((prefix, suffix)) => {
+            name.len() >= prefix.len() + suffix.len()
+                && name.starts_with(prefix)
+                && name.ends_with(suffix)
+        }
+        None => pattern == name,
+    }
+}
+
+/// 把登记的模式展开成候选路径（不存在的目录 / 文件直接忽略）
+fn expand(home: &Path, pattern: &str) -> Vec<PathBuf> {
+    let (dir, last) = match pattern.rsplit_once('/') {
+        Some((dir, last)) => (home.join(dir), last),
+        None => (home.to_path_buf(), pattern),
+    };
+    if !last.contains('*') {
+        return vec![dir.join(last)];
+    }
+    let Ok(entries) = fs::read_dir(&dir) else {
+        return Vec::new();
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .filter(|e| matches_glob(last, &e.file_name().to_string_lossy()))
+        .map(|e| e.path())
+        .collect();
+    paths.sort();
+    paths.truncate(MAX_MATCHES_PER_PATTERN);
+    paths
+}
+
+/// 当前平台下所有候选路径（带来源工具名）
+fn candidates(home: &Path, sources: &[KnownSource]) -> Vec<(&'static str, PathBuf)> {
+    sources
+        .iter()
+        .filter(|s| s.platform.is_current())
+        .flat_map(|s| {
+            expand(home, s.pattern)
+                .into_iter()
+                .map(move |path| (s.tool, path))
+        })
+        .collect()
+}
+
+/// 读一个候选文件；不存在返回 Ok(None)
+fn read_candidate(home: &Path, path: &Path) -> Result<Option<String>, String> {
+    if fs::symlink_metadata(path).is_err() {
+        return Ok(None);
+    }
+    let resolved = path
+        .canonicalize()
+        .map_err(|e| format!("无法解析路径: {}", e))?;
+    if !resolved.starts_with(home) {
+        return Err(format!("指向主目录以外（{}），已跳过", resolved.display()));
+    }
+    let meta = fs::metadata(&resolved).map_err(|e| format!("读取失败: {}", e))?;
+    if !meta.is_file() {
+        return Err("不是普通文件".to_string());
+    }
+    if meta.len() > MAX_FILE_BYTES {
+        return Err(format!("文件过大（{} 字节）", meta.len()));
+    }
+    fs::read_to_string(&resolved)
+        .map(Some)
+        .map_err(|e| format!("读取失败: {}", e))
+}
+
+fn canonical_home(home: &Path) -> Result<PathBuf, String> {
+    home.canonicalize()
+        .map_err(|e| format!("主目录不可用 {:?}: {}", home, e))
+}
+
+/// 扫描 `home` 下登记的位置，和 `store` 比对。只读
+pub fn discover(
+    store: &AccountStore,
+    home: &Path,
+    sources: &[KnownSource],
+) -> Result<DiscoveryReport, String> {
+    let home = canonical_home(home)?;
+    let mut report = DiscoveryReport::default();
+    for (tool, path) in candidates(&home, sources) {
+        let error = |error: String| LocationError {
+            source_tool: tool.to_string(),
+            path: path.display().to_string(),
+            error,
+        };
+        let text = match read_candidate(&home, &path) {
+            Ok(Some(text)) => text,
+            Ok(None) => continue,
+            Err(e) => {
+                report.errors.push(error(e));
+                continue;
+            }
+        };
+        match auth_identify::identify(store, &text) {
+            Ok(result) => report.logins.push(DiscoveredLogin {
+                source_tool: tool.to_string(),
+                path: path.display().to_string(),
+                identity: result.parsed_identity,
+                matched_account: result.matched_account,
+                token_fresher_than_stored: result.token_fresher_than_stored,
+            }),
+            Err(e) => report.errors.push(error(e.to_string())),
+        }
+    }
+    println!(
+        "[Discover] 发现 {} 份外部凭据，{} 个位置出错",
+        report.logins.len(),
+        report.errors.len()
+    );
+    Ok(report)
+}
+
+/// 采用 [`discover`] 列出的某一份：同身份的已存账号按更新的 token 覆盖，否则新增。
+/// `path` 必须是登记位置展开出来的路径，不接受任意文件。只改内存里的 store
+pub fn adopt(
+    store: &mut AccountStore,
+    home: &Path,
+    sources: &[KnownSource],
+    path: &str,
+) -> Result<FileOutcome, String> {
+    let home = canonical_home(home)?;
+    let (_, path) = candidates(&home, sources)
+        .into_iter()
+        .find(|(_, p)| p.display().to_string() == path)
+        .ok_or_else(|| format!("{} 不是已知的凭据位置，请重新扫描", path))?;
+    let text = read_candidate(&home, &path)?.ok_or("文件已不存在，请重新扫描")?;
+    Ok(auth_dir_import::merge_auth(store, &path, &text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::Engine;
+    use serde_json::{json, Value};
+
+    fn jwt(payload: Value) -> String {
+        let engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        format!(
+            "{}.{}.sig",
+            engine.encode(r#"{"alg":"none"}"#),
+            engine.encode(serde_json::to_vec(&payload).unwrap())
+        )
+    }
+
+    fn tokens(email: &str, account_id: &str, refresh_token: &str, exp: i64) -> Value {
+        let auth_claim = json!({ "chatgpt_account_id": account_id });
+        json!({
+            "account_id": account_id,
+            "refresh_token": refresh_token,
+            "id_token": jwt(json!({ "email": email, "https://api.openai.com/auth": auth_claim })),
+            "access_token": jwt(json!({ "exp": exp, "https://api.openai.com/auth": auth_claim })),
+        })
+    }
+
+    /// 临时主目录：每个登记位置放一份，外加诱饵
+    fn seeded_home() -> PathBuf {
+        let home = std::env::temp_dir().join(format!("cs-discover-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(home.join(".code")).unwrap();
+        fs::create_dir_all(home.join(".cli-proxy-api")).unwrap();
+        fs::write(
+            home.join(".code/auth.json"),
+            json!({ "tokens": tokens("work@example.com", "acct-work", "rt-new", 2_000_000_000) })
+                .to_string(),
+        )
+        .unwrap();
+        // CLIProxyAPI 的格式：token 在根上，带自己的附加字段
+        let mut root = tokens("spare@example.com", "acct-spare", "rt-spare", 1_900_000_000);
+        root["type"] = json!("codex");
+        root["email"] = json!("spare@example.com");
+        fs::write(
+            home.join(".cli-proxy-api/codex-spare@example.com.json"),
+            root.to_string(),
+        )
+        .unwrap();
+        // 诱饵：命中模式但不是合法 JSON；不命中模式的文件不该被读
+        fs::write(home.join(".cli-proxy-api/codex-broken.json"), "{not json").unwrap();
+        fs::write(home.join(".cli-proxy-api/config.json"), "{}").unwrap();
+        home
+    }
+
+    fn store_with_work() -> AccountStore {
+        let mut store = AccountStore::default();
+        store.add_account(
+            "work@example.com".to_string(),
+            json!({
+                "tokens": tokens("work@example.com", "acct-work", "rt-old", 1_800_000_000),
+            }),
+            None,
+        );
+        store
+    }
+
+    #[test]
+    fn discovers_known_locations_without_importing() {
+        let home = seeded_home();
+        let store = store_with_work();
+        let report = discover(&store, &home, KNOWN_SOURCES).unwrap();
+
+        let mut found: Vec<_> = report
+            .logins
+            .iter()
+            .map(|l| (l.source_tool.as_str(), l.identity.email.clone().unwrap()))
+            .collect();
+        found.sort();
+        assert_eq!(
+            found,
+            vec![
+                ("cli-proxy-api", "spare@example.com".to_string()),
+                ("every-code", "work@example.com".to_string()),
+            ]
+        );
+        let work = report
+            .logins
+            .iter()
+            .find(|l| l.source_tool == "every-code")
+            .unwrap();
+        assert_eq!(
+            work.matched_account.as_ref().map(|a| a.name.as_str()),
+            Some("work@example.com")
+        );
+        assert_eq!(work.token_fresher_than_stored, Some(true));
+        let spare = report
+            .logins
+            .iter()
+            .find(|l| l.source_tool == "cli-proxy-api")
+            .unwrap();
+        assert!(spare.matched_account.is_none());
+
+        assert_eq!(report.errors.len(), 1, "{:?}", report.errors);
+        assert!(report.errors[0].path.ends_with("codex-broken.json"));
+        assert_eq!(store.accounts.len(), 1, "扫描不导入");
+        let _ = fs::remove_dir_all(&home);
+    }
+
+    #[test]
+    fn oversized_files_and_links_out_of_home_are_reported() {
+        let home = seeded_home();
+        fs::write(
+            home.join(".cli-proxy-api/codex-huge.json"),
+            "x".repeat(MAX_FILE_BYTES as usize + 1),
+        )
+        .unwrap();
+        #[cfg(unix)]
+        {
+            let outside = std::env::temp_dir().join(format!("cs-outside-{}", uuid::Uuid::new_v4()));
+            fs::write(&outside, "{}").unwrap();
+            std::os::unix::fs::symlink(&outside, home.join(".cli-proxy-api/codex-link.json"))
+                .unwrap();
+        }
+        let report = discover(&AccountStore::default(), &home, KNOWN_SOURCES).unwrap();
+        let error = |file: &str| {
+            report
+                .errors
+                .iter()
+                .find(|e| e.path.ends_with(file))
+                .map(|e| e.error.clone())
+                .unwrap_or_default()
+        };
+        assert!(error("codex-huge.json").contains("过大"));
+        #[cfg(unix)]
+        assert!(error("codex-link.json").contains("主目录以外"));
+        assert_eq!(report.logins.len(), 2, "出错的位置不影响其它位置");
+
+        // 当前平台不适用的登记位置不扫
+        let other = KnownSource {
+            tool: "elsewhere",
+            platform: if cfg!(windows) {
+                Platform::Linux
+            } else {
+                Platform::Windows
+            },
+            pattern: ".code/auth.json",
+        };
+        let report = discover(&AccountStore::default(), &home, &[other]).unwrap();
+        assert!(report.logins.is_empty() && report.errors.is_empty());
+        let _ = fs::remove_dir_all(&home);
+    }
+
+    #[test]
+    fn adopt_imports_or_updates_through_identity_match() {
+        let home = seeded_home();
+        let mut store = store_with_work();
+        let report = discover(&store, &home, KNOWN_SOURCES).unwrap();
+        let path_of = |tool: &str| {
+            report
+                .logins
+                .iter()
+                .find(|l| l.source_tool == tool)
+                .unwrap()
+                .path
+                .clone()
+        };
+        let source = home.join(".code/auth.json");
+        let before = fs::read(&source).unwrap();
+
+        let updated = adopt(&mut store, &home, KNOWN_SOURCES, &path_of("every-code")).unwrap();
+        assert!(
+            matches!(updated, FileOutcome::Updated { ref name, .. } if name == "work@example.com")
+        );
+        let imported = adopt(&mut store, &home, KNOWN_SOURCES, &path_of("cli-proxy-api")).unwrap();
+        assert!(
+            matches!(imported, FileOutcome::Imported { ref name, .. } if name == "spare@example.com")
+        );
+        assert_eq!(store.accounts.len(), 2);
+        assert_eq!(fs::read(&source).unwrap(), before, "来源文件不被改写");
+
+        // 登记位置以外的路径一律拒绝
+        let stray = home.join("stray.json");
+        fs::write(&stray, fs::read(&source).unwrap()).unwrap();
+        assert!(adopt(
+            &mut store,
+            &home,
+            KNOWN_SOURCES,
+            &stray.display().to_string()
+        )
+        .is_err());
+        let _ = fs::remove_dir_all(&home);
+    }
+}
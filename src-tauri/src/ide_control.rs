@@ -106,6 +106,20 @@ pub fn reload_ide(name: &str, use_window_reload: bool) -> Result<(), String> {
     }
 }
 
+/// Codex App 是否带隔离属性（只读检查，不需要 sudo）；非 macOS 或没装时为 false
+pub fn has_quarantine() -> bool {
+    if !cfg!(target_os = "macos") || !std::path::Path::new(CODEX_APP_PATH).exists() {
+        return false;
+    }
+    Command::new("xattr")
+        .args(["-p", "com.apple.quarantine", CODEX_APP_PATH])
+        .output()
+        .map(|o| o.status.success())
+        .unwrap_or(false)
+}
+
+const CODEX_APP_PATH: &str = "/Applications/Codex.app";
+
 /// 移除 Codex App 的隔离属性 (修复闪退)
 pub fn remove_quarantine() -> Result<(), String> {
     let script = r#"
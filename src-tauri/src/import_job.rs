@@ -0,0 +1,401 @@
+//! 大账号库的分批导入（可取消、带进度）
+//!
+//! 和 `import_accounts`（整库替换）不同，这里是**合并**：按账号 id 逐个并入现有库，
+//! 设置 / 当前账号不动（当前账号为空时才用导出文件里的）。
+//!
+//! 流程由 [`ImportJob`] 这个纯状态机驱动，不依赖 Tauri：
+//! - 每次 `run_batch` 处理最多 `batch_size` 条，返回本批结束时的进度
+//! - 单条解析 / 校验失败只记进 `errors`，不影响同批其它账号
+//! - 取消只在批与批之间生效：已经并入的账号**保留**，剩下的不再处理，
+//!   报告里 `cancelled = true`、`processed < total`
+//!
+//! 同一时间只允许一个导入任务，由 [`ImportJobs`] 把关。
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::account::{Account, AccountStore};
+
+const DEFAULT_BATCH_SIZE: usize = 20;
+
+/// 导入选项
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportOptions {
+    /// 已存在同 id 账号时是否覆盖（默认跳过）
+    #[serde(default)]
+    pub overwrite_existing: bool,
+    /// 每批处理条数，每批结束后落盘 + 推一次进度
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+}
+
+fn default_batch_size() -> usize {
+    DEFAULT_BATCH_SIZE
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        Self {
+            overwrite_existing: false,
+            batch_size: DEFAULT_BATCH_SIZE,
+        }
+    }
+}
+
+/// `import-progress` 事件负载
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ImportProgress {
+    pub job_id: String,
+    pub done: usize,
+    pub total: usize,
+    pub current_name: Option<String>,
+    pub errors_so_far: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ImportError {
+    /// 账号名；条目连名字都解析不出来时用 `#序号`
+    pub name: String,
+    pub reason: String,
+}
+
+/// `import-complete` 事件负载
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportReport {
+    pub job_id: String,
+    pub total: usize,
+    pub processed: usize,
+    pub added: usize,
+    pub updated: usize,
+    pub skipped: usize,
+    pub errors: Vec<ImportError>,
+    pub cancelled: bool,
+}
+
+/// 一次导入任务
+pub struct ImportJob {
+    id: String,
+    entries: Vec<Value>,
+    current: Option<String>,
+    options: ImportOptions,
+    cancel: Arc<AtomicBool>,
+    next: usize,
+    added: usize,
+    updated: usize,
+    skipped: usize,
+    errors: Vec<ImportError>,
+    cancelled: bool,
+}
+
+impl ImportJob {
+    /// 解析导出文件的外层结构；`accounts` 既可以是 id → 账号的对象，也可以是数组。
+    /// 单个账号的内容留到 `run_batch` 时再解析，坏条目不会让整个任务起不来。
+    pub fn new(id: String, json: &str, options: ImportOptions) -> Result<Self, String> {
+        let root: Value = serde_json::from_str(json).map_err(|e| format!("导入失败: {}", e))?;
+        if root.get(crate::account::REDACTED_EXPORT_MARKER).is_some() {
+            return Err(crate::account::REDACTED_IMPORT_REFUSED.to_string());
+        }
+        let entries = match root.get("accounts") {
+            Some(Value::Object(map)) => map.values().cloned().collect(),
+            Some(Value::Array(list)) => list.clone(),
+            _ => return Err("导入失败: 缺少 accounts 字段".to_string()),
+        };
+        let current = root
+            .get("current")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
+        Ok(Self {
+            id,
+            entries,
+            current,
+            options,
+            cancel: Arc::new(AtomicBool::new(false)),
+            next: 0,
+            added: 0,
+            updated: 0,
+            skipped: 0,
+            errors: Vec::new(),
+            cancelled: false,
+        })
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// 取消标记，交给 [`ImportJobs`] 登记
+    pub fn cancel_token(&self) -> Arc<AtomicBool> {
+        self.cancel.clone()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.cancelled || self.next >= self.entries.len()
+    }
+
+    /// 处理下一批并入 `store`；任务已结束（处理完或被取消）返回 None
+    pub fn run_batch(&mut self, store: &mut AccountStore) -> Option<ImportProgress> {
+        if self.is_finished() {
+            return None;
+        }
+        if self.cancel.load(Ordering::Relaxed) {
+            self.cancelled = true;
+            return None;
+        }
+        let end = (self.next + self.options.batch_size.max(1)).min(self.entries.len());
+        let mut current_name = None;
+        for index in self.next..end {
+            let entry = self.entries[index].clone();
+            let name = entry
+                .get("name")
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| format!("#{}", index + 1));
+            if let Err(reason) = self.merge_entry(store, entry) {
+                self.errors.push(ImportError {
+                    name: name.clone(),
+                    reason,
+                });
+            }
+            current_name = Some(name);
+        }
+        self.next = end;
+        if store.current.is_none() {
+            store.current = self
+                .current
+                .clone()
+                .filter(|id| store.accounts.contains_key(id));
+        }
+        Some(self.progress(current_name))
+    }
+
+    fn merge_entry(&mut self, store: &mut AccountStore, entry: Value) -> Result<(), String> {
+        let mut account: Account =
+            serde_json::from_value(entry).map_err(|e| format!("解析失败: {}", e))?;
+        if account.id.trim().is_empty() {
+            return Err("缺少账号 id".to_string());
+        }
+        if account.refresh_token.as_deref().map(str::trim) == Some("") {
+            account.refresh_token = None;
+        }
+        if account.refresh_token.is_none() {
+            account.refresh_token = AccountStore::extract_refresh_token(&account.auth_json);
+        }
+        if account.is_chatgpt_oauth() && account.refresh_token.is_none() {
+            return Err("缺少 refresh_token，无法自动续期，请重新登录后再导入".to_string());
+        }
+        if store.accounts.contains_key(&account.id) {
+            if !self.options.overwrite_existing {
+                self.skipped += 1;
+                return Ok(());
+            }
+            self.updated += 1;
+        } else {
+            self.added += 1;
+        }
+        store.accounts.insert(account.id.clone(), account);
+        Ok(())
+    }
+
+    fn progress(&self, current_name: Option<String>) -> ImportProgress {
+        ImportProgress {
+            job_id: self.id.clone(),
+            done: self.next,
+            total: self.entries.len(),
+            current_name,
+            errors_so_far: self.errors.len(),
+        }
+    }
+
+    pub fn report(&self) -> ImportReport {
+        ImportReport {
+            job_id: self.id.clone(),
+            total: self.entries.len(),
+            processed: self.next,
+            added: self.added,
+            updated: self.updated,
+            skipped: self.skipped,
+            errors: self.errors.clone(),
+            cancelled: self.cancelled,
+        }
+    }
+}
+
+/// 正在运行的导入任务登记处：同一时间最多一个
+#[derive(Default)]
+pub struct ImportJobs {
+    running: Mutex<Option<(String, Arc<AtomicBool>)>>,
+}
+
+impl ImportJobs {
+    pub fn try_start(&self, job: &ImportJob) -> Result<(), String> {
+        let mut running = self.running.lock().map_err(|e| e.to_string())?;
+        if let Some((id, _)) = running.as_ref() {
+            return Err(format!("已有导入任务在进行中 ({})，请等待完成或先取消", id));
+        }
+        *running = Some((job.id().to_string(), job.cancel_token()));
+        Ok(())
+    }
+
+    /// 请求取消；任务会在当前批处理完后停下
+    pub fn cancel(&self, job_id: &str) -> Result<(), String> {
+        let running = self.running.lock().map_err(|e| e.to_string())?;
+        match running.as_ref() {
+            Some((id, token)) if id == job_id => {
+                token.store(true, Ordering::Relaxed);
+                Ok(())
+            }
+            _ => Err(format!("导入任务不存在或已结束: {}", job_id)),
+        }
+    }
+
+    pub fn finish(&self, job_id: &str) {
+        if let Ok(mut running) = self.running.lock() {
+            if running.as_ref().map(|(id, _)| id == job_id) == Some(true) {
+                *running = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn oauth_auth(rt: &str) -> Value {
+        json!({ "tokens": { "access_token": "eyJ.at", "refresh_token": rt } })
+    }
+
+    /// 导出 n 个账号，名字 acct-0..acct-n
+    fn export_json(n: usize) -> (String, Vec<String>) {
+        let mut source = AccountStore::default();
+        let ids = (0..n)
+            .map(|i| {
+                source
+                    .add_account(format!("acct-{}", i), oauth_auth("rt"), None)
+                    .id
+            })
+            .collect();
+        (serde_json::to_string(&source).unwrap(), ids)
+    }
+
+    fn options(batch_size: usize) -> ImportOptions {
+        ImportOptions {
+            batch_size,
+            ..ImportOptions::default()
+        }
+    }
+
+    #[test]
+    fn runs_in_batches_and_reports_progress() {
+        let (json, ids) = export_json(5);
+        let mut job = ImportJob::new("job".into(), &json, options(2)).unwrap();
+        let mut store = AccountStore::default();
+        let mut done = Vec::new();
+        while let Some(progress) = job.run_batch(&mut store) {
+            assert_eq!(progress.total, 5);
+            done.push(progress.done);
+        }
+        assert_eq!(done, vec![2, 4, 5]);
+        let report = job.report();
+        assert_eq!((report.added, report.processed), (5, 5));
+        assert!(!report.cancelled);
+        assert_eq!(store.accounts.len(), 5);
+        assert!(ids.contains(store.current.as_ref().unwrap()));
+    }
+
+    #[test]
+    fn cancel_mid_stream_keeps_merged_accounts() {
+        let (json, _) = export_json(6);
+        let mut job = ImportJob::new("job".into(), &json, options(2)).unwrap();
+        let jobs = ImportJobs::default();
+        jobs.try_start(&job).unwrap();
+        let mut store = AccountStore::default();
+
+        assert_eq!(job.run_batch(&mut store).unwrap().done, 2);
+        jobs.cancel("job").unwrap();
+        assert!(job.run_batch(&mut store).is_none());
+        assert!(job.is_finished());
+
+        let report = job.report();
+        assert!(report.cancelled);
+        assert_eq!((report.processed, report.total, report.added), (2, 6, 2));
+        assert_eq!(store.accounts.len(), 2);
+    }
+
+    #[test]
+    fn failing_entry_does_not_abort_batch() {
+        let (json, _) = export_json(3);
+        let mut root: Value = serde_json::from_str(&json).unwrap();
+        let accounts = root["accounts"].as_object_mut().unwrap();
+        let first = accounts.keys().next().unwrap().clone();
+        accounts[&first]["auth_json"] = json!({ "tokens": { "access_token": "eyJ.at" } });
+        accounts[&first]["refresh_token"] = Value::Null;
+        accounts.insert("broken".into(), json!({ "name": "broken" }));
+
+        let mut job =
+            ImportJob::new("job".into(), &root.to_string(), ImportOptions::default()).unwrap();
+        let mut store = AccountStore::default();
+        let progress = job.run_batch(&mut store).unwrap();
+        assert_eq!((progress.done, progress.errors_so_far), (4, 2));
+        assert!(job.run_batch(&mut store).is_none());
+
+        let report = job.report();
+        assert_eq!(report.added, 2);
+        let failed: Vec<&str> = report.errors.iter().map(|e| e.name.as_str()).collect();
+        assert!(failed.contains(&"broken"));
+        assert!(report
+            .errors
+            .iter()
+            .any(|e| e.reason.contains("refresh_token")));
+        assert_eq!(store.accounts.len(), 2);
+    }
+
+    #[test]
+    fn existing_accounts_are_skipped_unless_overwrite() {
+        let (json, ids) = export_json(2);
+        let mut store = AccountStore::import(&json).unwrap();
+        store.accounts.get_mut(&ids[0]).unwrap().name = "renamed".into();
+
+        let mut job = ImportJob::new("a".into(), &json, ImportOptions::default()).unwrap();
+        while job.run_batch(&mut store).is_some() {}
+        assert_eq!(job.report().skipped, 2);
+        assert_eq!(store.accounts[&ids[0]].name, "renamed");
+
+        let overwrite = ImportOptions {
+            overwrite_existing: true,
+            ..ImportOptions::default()
+        };
+        let mut job = ImportJob::new("b".into(), &json, overwrite).unwrap();
+        while job.run_batch(&mut store).is_some() {}
+        assert_eq!(job.report().updated, 2);
+        assert_eq!(store.accounts[&ids[0]].name, "acct-0");
+    }
+
+    #[test]
+    fn only_one_job_at_a_time() {
+        let (json, _) = export_json(1);
+        let jobs = ImportJobs::default();
+        let first = ImportJob::new("first".into(), &json, ImportOptions::default()).unwrap();
+        let second = ImportJob::new("second".into(), &json, ImportOptions::default()).unwrap();
+
+        jobs.try_start(&first).unwrap();
+        assert!(jobs.try_start(&second).unwrap_err().contains("first"));
+        assert!(jobs.cancel("second").is_err());
+
+        jobs.finish("second");
+        assert!(jobs.try_start(&second).is_err());
+        jobs.finish("first");
+        jobs.try_start(&second).unwrap();
+    }
+
+    #[test]
+    fn rejects_files_without_accounts() {
+        assert!(ImportJob::new("x".into(), "{}", ImportOptions::default()).is_err());
+        assert!(ImportJob::new("x".into(), "not json", ImportOptions::default()).is_err());
+    }
+}
@@ -0,0 +1,371 @@
+//! 合并式导入
+//!
+//! 替换式导入会把整个账号库换成导入文件的内容；合并式只往现有库里并：
+//! - 库里没有的身份直接新增（id 撞了就换个新 id，名字重了自动加后缀）
+//! - 同一身份（`find_same_identity`：`auth_identity_matches`，老数据退回比邮箱）的已存账号，
+//!   只有导入的 `last_refresh` 比现有的新时才用它的 token 覆盖，账号的名字、备注等本地信息不动
+//! - 中转站账号没有 token 身份，按 id 判断是否已存在
+//! - 缺 refresh_token 的账号单独跳过，不影响其它账号
+//!
+//! `settings` 和 `current` 都保留本机的。只改内存里的 store，落盘由调用方负责。
+
+use serde::{Deserialize, Serialize};
+
+use crate::account::{Account, AccountStore};
+use crate::undo::UndoReceipt;
+
+/// `import_accounts` 的导入方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportMode {
+    /// 整个账号库换成导入的内容（可撤销）
+    #[default]
+    Replace,
+    /// 并入现有账号库，见模块说明
+    Merge,
+}
+
+/// 单个导入账号的处理结果
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum MergeOutcome {
+    Added { account_id: String },
+    Updated { account_id: String },
+    Skipped { reason: String },
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct MergeEntry {
+    /// 导入文件里的账号名
+    pub name: String,
+    #[serde(flatten)]
+    pub outcome: MergeOutcome,
+}
+
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct MergeReport {
+    pub added: usize,
+    pub updated: usize,
+    pub skipped: usize,
+    /// 按导入文件里的列表顺序
+    pub accounts: Vec<MergeEntry>,
+}
+
+impl MergeReport {
+    /// 有没有账号被新增或更新（需要落盘）
+    pub fn changed(&self) -> bool {
+        self.added + self.updated > 0
+    }
+}
+
+/// 导入前后账号库的差异（按账号 id 计）
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct ImportSummary {
+    pub added: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    /// 只有替换式导入会拿掉账号
+    pub removed: usize,
+    /// 合并式导入跳过的账号
+    pub skipped: usize,
+}
+
+impl ImportSummary {
+    /// 替换式导入：`before` 换成 `after` 的差异
+    pub fn between(before: &AccountStore, after: &AccountStore) -> Self {
+        let mut summary = Self::default();
+        for (id, account) in &after.accounts {
+            match before.accounts.get(id) {
+                None => summary.added += 1,
+                Some(old)
+                    if serde_json::to_value(old).ok() == serde_json::to_value(account).ok() =>
+                {
+                    summary.unchanged += 1
+                }
+                Some(_) => summary.updated += 1,
+            }
+        }
+        summary.removed = before
+            .accounts
+            .keys()
+            .filter(|id| !after.accounts.contains_key(*id))
+            .count();
+        summary
+    }
+
+    /// 合并式导入：`before` 是合并前的账号数
+    pub fn from_merge(report: &MergeReport, before: usize) -> Self {
+        Self {
+            added: report.added,
+            updated: report.updated,
+            unchanged: before - report.updated,
+            removed: 0,
+            skipped: report.skipped,
+        }
+    }
+}
+
+/// `import_accounts` 的返回
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct ImportResult {
+    pub mode: ImportMode,
+    pub summary: ImportSummary,
+    /// 替换式导入的撤销凭据
+    pub undo: Option<UndoReceipt>,
+    /// 替换式导入前留的账号库备份（见 `store_backups`）
+    pub backup_path: Option<String>,
+    /// 合并式导入的逐账号报告
+    pub merge: Option<MergeReport>,
+}
+
+/// 把 `incoming` 并入 `store`，规则见模块说明
+pub fn merge(store: &mut AccountStore, incoming: &AccountStore) -> MergeReport {
+    let mut report = MergeReport::default();
+    for account in incoming.list_accounts() {
+        let outcome = merge_account(store, account.clone());
+        match outcome {
+            MergeOutcome::Added { .. } => report.added += 1,
+            MergeOutcome::Updated { .. } => report.updated += 1,
+            MergeOutcome::Skipped { .. } => report.skipped += 1,
+        }
+        report.accounts.push(MergeEntry {
+            name: account.name.clone(),
+            outcome,
+        });
+    }
+    println!(
+        "[Import] 合并导入：新增 {}，更新 {}，跳过 {}",
+        report.added, report.updated, report.skipped
+    );
+    report
+}
+
+fn merge_account(store: &mut AccountStore, account: Account) -> MergeOutcome {
+    if account.is_relay() {
+        if let Some(existing) = store.accounts.get(&account.id) {
+            return MergeOutcome::Skipped {
+                reason: format!("中转站账号 {} 已存在", existing.name),
+            };
+        }
+        return add(store, account);
+    }
+    if !account.is_api_key() && account.refresh_token.is_none() {
+        return MergeOutcome::Skipped {
+            reason: "缺少 refresh_token，无法自动续期，请重新登录后再导入".to_string(),
+        };
+    }
+    let Some(id) = store.find_same_identity(&account.auth_json) else {
+        return add(store, account);
+    };
+    let existing = &store.accounts[&id];
+    let name = existing.name.clone();
+    if account.is_api_key() {
+        return MergeOutcome::Skipped {
+            reason: format!("与已存账号 {} 相同", name),
+        };
+    }
+
+    let incoming_refresh = AccountStore::extract_last_refresh(&account.auth_json);
+    let stored_refresh = AccountStore::extract_last_refresh(&existing.auth_json);
+    match (incoming_refresh, stored_refresh) {
+        (None, _) => {
+            return MergeOutcome::Skipped {
+                reason: format!("没有 last_refresh，无法判断是否比已存账号 {} 新", name),
+            }
+        }
+        (Some(incoming), Some(stored)) if incoming <= stored => {
+            return MergeOutcome::Skipped {
+                reason: format!("已存账号 {} 的 token 不比导入的旧，保留现有", name),
+            }
+        }
+        _ => {}
+    }
+    if let Err(reason) = store.ensure_unprotected(&id, "被导入覆盖") {
+        return MergeOutcome::Skipped { reason };
+    }
+    if !store.sync_account_from_auth_json(&id, account.auth_json) {
+        return MergeOutcome::Skipped {
+            reason: format!("与已存账号 {} 的身份或邮箱不一致，未覆盖", name),
+        };
+    }
+    MergeOutcome::Updated { account_id: id }
+}
+
+fn add(store: &mut AccountStore, mut account: Account) -> MergeOutcome {
+    if store.accounts.contains_key(&account.id) {
+        account.id = uuid::Uuid::new_v4().to_string();
+    }
+    account.name = store.unique_account_name(&account.name, None);
+    // 会话锚点全库只能有一个，导入的不抢本机的
+    account.is_session_anchor = false;
+    // 手动排序位置是导出方的，排到本机列表里没有意义
+    account.sort_index = None;
+    let account_id = account.id.clone();
+    store.accounts.insert(account_id.clone(), account);
+    MergeOutcome::Added { account_id }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn auth(account_id: &str, rt: &str, last_refresh: &str) -> Value {
+        json!({
+            "tokens": {
+                "access_token": format!("at-{}", rt),
+                "refresh_token": rt,
+                "account_id": account_id,
+            },
+            "last_refresh": last_refresh,
+        })
+    }
+
+    #[test]
+    fn merge_adds_unknown_updates_newer_and_skips_the_rest() {
+        let mut store = AccountStore::default();
+        let stale = store
+            .add_account(
+                "stale".to_string(),
+                auth("ws-1", "rt-old", "2026-01-01T00:00:00Z"),
+                Some("本机备注".to_string()),
+            )
+            .id;
+        let fresh = store
+            .add_account(
+                "fresh".to_string(),
+                auth("ws-2", "rt-keep", "2026-03-01T00:00:00Z"),
+                None,
+            )
+            .id;
+        store.current = Some(fresh.clone());
+        store.settings.refresh_interval_minutes = 42;
+
+        let mut incoming = AccountStore::default();
+        let newer = incoming
+            .add_account(
+                "renamed".to_string(),
+                auth("ws-1", "rt-new", "2026-02-01T00:00:00Z"),
+                None,
+            )
+            .id;
+        let older = incoming
+            .add_account(
+                "fresh".to_string(),
+                auth("ws-2", "rt-older", "2026-02-01T00:00:00Z"),
+                None,
+            )
+            .id;
+        let unknown = incoming
+            .add_account(
+                "stale".to_string(),
+                auth("ws-3", "rt-3", "2026-02-01T00:00:00Z"),
+                None,
+            )
+            .id;
+        let no_rt = incoming
+            .add_account(
+                "no-rt".to_string(),
+                json!({ "tokens": { "access_token": "at", "account_id": "ws-4" } }),
+                None,
+            )
+            .id;
+        incoming
+            .reorder_accounts(&[newer, older, unknown, no_rt])
+            .unwrap();
+        incoming.current = incoming.accounts.keys().next().cloned();
+        incoming.settings.refresh_interval_minutes = 5;
+
+        let report = merge(&mut store, &incoming);
+        assert_eq!((report.added, report.updated, report.skipped), (1, 1, 2));
+        assert!(report.changed());
+        let statuses: Vec<_> = report
+            .accounts
+            .iter()
+            .map(|e| serde_json::to_value(e).unwrap()["status"].clone())
+            .collect();
+        assert_eq!(statuses, vec!["updated", "skipped", "added", "skipped"]);
+        let MergeOutcome::Skipped { reason } = &report.accounts[3].outcome else {
+            panic!("{:?}", report.accounts[3]);
+        };
+        assert!(reason.contains("refresh_token"), "{}", reason);
+
+        // 覆盖 token，本地信息不动
+        let updated = &store.accounts[&stale];
+        assert_eq!(updated.refresh_token.as_deref(), Some("rt-new"));
+        assert_eq!(updated.name, "stale");
+        assert_eq!(updated.notes.as_deref(), Some("本机备注"));
+        assert_eq!(
+            store.accounts[&fresh].refresh_token.as_deref(),
+            Some("rt-keep")
+        );
+
+        // 新增的重名账号自动改名
+        let MergeOutcome::Added { account_id } = &report.accounts[2].outcome else {
+            panic!("{:?}", report.accounts[2]);
+        };
+        assert_ne!(store.accounts[account_id].name, "stale");
+        assert_eq!(store.accounts.len(), 3);
+
+        assert_eq!(store.current.as_deref(), Some(fresh.as_str()));
+        assert_eq!(store.settings.refresh_interval_minutes, 42);
+
+        // 再并一次：都已存在且不比现有的新
+        let again = merge(&mut store, &incoming);
+        assert_eq!((again.added, again.updated), (0, 0));
+        assert!(!again.changed());
+        let summary = ImportSummary::from_merge(&report, 2);
+        assert_eq!(
+            (summary.added, summary.updated, summary.unchanged),
+            (1, 1, 1)
+        );
+    }
+
+    #[test]
+    fn replace_summary_counts_added_updated_unchanged_and_removed() {
+        let mut before = AccountStore::default();
+        let kept = before
+            .add_account(
+                "kept".to_string(),
+                auth("ws-1", "rt-1", "2026-01-01T00:00:00Z"),
+                None,
+            )
+            .id;
+        let changed = before
+            .add_account(
+                "changed".to_string(),
+                auth("ws-2", "rt-2", "2026-01-01T00:00:00Z"),
+                None,
+            )
+            .id;
+        let gone = before
+            .add_account(
+                "gone".to_string(),
+                auth("ws-3", "rt-3", "2026-01-01T00:00:00Z"),
+                None,
+            )
+            .id;
+
+        let mut after = before.clone();
+        after.accounts.remove(&gone);
+        after.accounts.get_mut(&changed).unwrap().notes = Some("新备注".to_string());
+        after.add_account(
+            "new".to_string(),
+            auth("ws-4", "rt-4", "2026-01-01T00:00:00Z"),
+            None,
+        );
+
+        let summary = ImportSummary::between(&before, &after);
+        assert_eq!(
+            summary,
+            ImportSummary {
+                added: 1,
+                updated: 1,
+                unchanged: 1,
+                removed: 1,
+                skipped: 0,
+            }
+        );
+        assert!(after.accounts.contains_key(&kept));
+    }
+}
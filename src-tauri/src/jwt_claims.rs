@@ -0,0 +1,232 @@
+//! JWT payload 解码（带上限）
+//!
+//! auth.json 可能来自不可信的导入：payload 段可以有好几 MB，JSON 可以嵌套上千层。
+//! 这里在真正解码之前逐步设卡，每一步失败都给出 [`ClaimsError`] 说明卡在哪：
+//! 1. 整个 token 不超过 [`MAX_TOKEN_BYTES`]，且恰好三段
+//! 2. payload 段不超过 [`MAX_PAYLOAD_BYTES`]，只含 base64url 字母表（末尾 `=` 补位可有可无）
+//! 3. base64url 解码 → UTF-8 → JSON；解析前先数嵌套层数，超过 [`MAX_DEPTH`] 直接拒绝
+//!
+//! `AccountStore` 上对外的提取函数仍然返回 `Option` / `String` 错误，
+//! 需要区分失败原因的（导入校验按字段报错）直接用 [`decode`]。
+
+use std::fmt;
+
+use base64::Engine;
+use serde_json::Value;
+
+/// payload 段的最大长度（字节）
+pub const MAX_PAYLOAD_BYTES: usize = 64 * 1024;
+/// 整个 token 的最大长度（header + payload + signature）
+pub const MAX_TOKEN_BYTES: usize = 2 * MAX_PAYLOAD_BYTES;
+/// claims JSON 最多嵌套的层数（真实 token 不超过 3 层）
+pub const MAX_DEPTH: usize = 32;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClaimsError {
+    /// 不是 `header.payload.signature` 三段（带实际段数）
+    Segments(usize),
+    /// token / payload 超长，或 JSON 嵌套过深
+    TooLarge(String),
+    /// payload 含 base64url 字母表以外的字符，或长度不合法
+    NotBase64(String),
+    NotUtf8,
+    NotJson(String),
+}
+
+impl fmt::Display for ClaimsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClaimsError::Segments(n) => write!(f, "无效的 Token 格式（应为三段，实际 {} 段）", n),
+            ClaimsError::TooLarge(what) => write!(f, "Token 过大: {}", what),
+            ClaimsError::NotBase64(why) => write!(f, "Base64 解码失败: {}", why),
+            ClaimsError::NotUtf8 => write!(f, "payload 不是合法的 UTF-8"),
+            ClaimsError::NotJson(why) => write!(f, "JSON 解析失败: {}", why),
+        }
+    }
+}
+
+fn is_base64url(b: u8) -> bool {
+    b.is_ascii_alphanumeric() || b == b'-' || b == b'_'
+}
+
+/// JSON 文本的最大嵌套层数（只数字符串外的括号；不合法的 JSON 留给解析器报错）
+fn nesting_depth(json: &str) -> usize {
+    let (mut depth, mut max) = (0usize, 0usize);
+    let (mut in_string, mut escaped) = (false, false);
+    for b in json.bytes() {
+        if in_string {
+            match b {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match b {
+            b'"' => in_string = true,
+            b'{' | b'[' => {
+                depth += 1;
+                max = max.max(depth);
+            }
+            b'}' | b']' => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+    }
+    max
+}
+
+/// 解出 JWT payload 里的 claims（各步上限见模块文档）
+pub fn decode(token: &str) -> Result<Value, ClaimsError> {
+    if token.len() > MAX_TOKEN_BYTES {
+        return Err(ClaimsError::TooLarge(format!(
+            "token {} 字节，上限 {}",
+            token.len(),
+            MAX_TOKEN_BYTES
+        )));
+    }
+    let parts: Vec<&str> = token.split('.').collect();
+    if parts.len() != 3 {
+        return Err(ClaimsError::Segments(parts.len()));
+    }
+    let payload = parts[1];
+    if payload.len() > MAX_PAYLOAD_BYTES {
+        return Err(ClaimsError::TooLarge(format!(
+            "payload {} 字节，上限 {}",
+            payload.len(),
+            MAX_PAYLOAD_BYTES
+        )));
+    }
+    let unpadded = payload.trim_end_matches('=');
+    if let Some(pos) = unpadded.bytes().position(|b| !is_base64url(b)) {
+        return Err(ClaimsError::NotBase64(format!(
+            "第 {} 个字符不在 base64url 字母表内",
+            pos + 1
+        )));
+    }
+    let bytes = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(unpadded)
+        .map_err(|e| ClaimsError::NotBase64(e.to_string()))?;
+    let text = String::from_utf8(bytes).map_err(|_| ClaimsError::NotUtf8)?;
+    let depth = nesting_depth(&text);
+    if depth > MAX_DEPTH {
+        return Err(ClaimsError::TooLarge(format!(
+            "JSON 嵌套 {} 层，上限 {}",
+            depth, MAX_DEPTH
+        )));
+    }
+    serde_json::from_str(&text).map_err(|e| ClaimsError::NotJson(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn b64(bytes: &[u8]) -> String {
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes)
+    }
+
+    fn jwt(payload: &[u8]) -> String {
+        format!("{}.{}.sig", b64(br#"{"alg":"none"}"#), b64(payload))
+    }
+
+    #[test]
+    fn decodes_well_formed_tokens_with_or_without_padding() {
+        let token = jwt(br#"{"email":"a@example.com","nested":{"x":[1,"]"]}}"#);
+        assert_eq!(decode(&token).unwrap()["email"], "a@example.com");
+
+        let payload = base64::engine::general_purpose::URL_SAFE.encode(br#"{"a":1}"#);
+        assert!(payload.ends_with('='));
+        assert_eq!(decode(&format!("h.{}.s", payload)).unwrap()["a"], 1);
+    }
+
+    #[test]
+    fn oversized_tokens_are_rejected_before_decoding() {
+        let big = format!(r#"{{"pad":"{}"}}"#, "x".repeat(MAX_PAYLOAD_BYTES));
+        assert!(matches!(
+            decode(&jwt(big.as_bytes())),
+            Err(ClaimsError::TooLarge(_))
+        ));
+
+        // 就算段数不对，超长也先按超长拒
+        let huge = "a.".repeat(MAX_TOKEN_BYTES);
+        assert!(matches!(decode(&huge), Err(ClaimsError::TooLarge(_))));
+
+        // 正好在上限内的照常解
+        let payload = "A".repeat(MAX_PAYLOAD_BYTES);
+        assert!(!matches!(
+            decode(&format!("h.{}.s", payload)),
+            Err(ClaimsError::TooLarge(_))
+        ));
+    }
+
+    #[test]
+    fn deep_nesting_is_rejected_without_recursing() {
+        let deep = format!("{}{}", "[".repeat(10_000), "]".repeat(10_000));
+        assert!(matches!(
+            decode(&jwt(deep.as_bytes())),
+            Err(ClaimsError::TooLarge(_))
+        ));
+
+        let ok = format!(
+            r#"{{"a":{}1{}}}"#,
+            "[".repeat(MAX_DEPTH - 1),
+            "]".repeat(MAX_DEPTH - 1)
+        );
+        assert!(decode(&jwt(ok.as_bytes())).is_ok());
+
+        // 字符串里的括号不算层数
+        let quoted = format!(r#"{{"a":"{}"}}"#, "[".repeat(1000));
+        assert!(decode(&jwt(quoted.as_bytes())).is_ok());
+    }
+
+    #[test]
+    fn malformed_tokens_are_classified() {
+        assert_eq!(decode("only.two"), Err(ClaimsError::Segments(2)));
+        assert_eq!(decode("a.b.c.d"), Err(ClaimsError::Segments(4)));
+        assert_eq!(decode(""), Err(ClaimsError::Segments(1)));
+
+        for bad in [
+            "h.eyJh+IjoxfQ.s",
+            "h.eyJh/IjoxfQ.s",
+            "h.eyJ hIjoxfQ.s",
+            "h.é.s",
+            "h.a=b.s",
+        ] {
+            assert!(
+                matches!(decode(bad), Err(ClaimsError::NotBase64(_))),
+                "{} 应判为非 base64",
+                bad
+            );
+        }
+        // 截断：长度 mod 4 == 1 解不出来
+        assert!(matches!(
+            decode("h.eyJhI.s"),
+            Err(ClaimsError::NotBase64(_))
+        ));
+
+        assert_eq!(decode(&jwt(&[0xff, 0xfe, 0x7b])), Err(ClaimsError::NotUtf8));
+
+        let truncated = jwt(br#"{"email":"a@example"#);
+        assert!(matches!(decode(&truncated), Err(ClaimsError::NotJson(_))));
+        assert!(matches!(decode(&jwt(b"")), Err(ClaimsError::NotJson(_))));
+    }
+
+    #[test]
+    fn arbitrary_bytes_never_panic() {
+        // 伪随机字节，覆盖各种奇怪的组合
+        let mut seed = 0x2545_f491_4f6c_dd1du64;
+        for len in 0..300 {
+            let bytes: Vec<u8> = (0..len)
+                .map(|_| {
+                    seed ^= seed << 13;
+                    seed ^= seed >> 7;
+                    seed ^= seed << 17;
+                    seed as u8
+                })
+                .collect();
+            let _ = decode(&jwt(&bytes));
+            let _ = decode(&format!("h.{}.s", String::from_utf8_lossy(&bytes)));
+        }
+    }
+}
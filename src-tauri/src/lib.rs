@@ -3,41 +3,105 @@
 //! 暴露所有 Tauri 命令供前端调用
 
 pub mod account;
+mod account_compare;
+mod account_integrity;
+mod account_list;
+mod account_search;
+mod account_selector;
+mod activity;
+mod activity_feed;
+mod api_schema;
+mod atomic_write;
+mod auth_permissions;
+mod auth_dir_import;
+pub mod auth_journal;
+mod auth_identify;
 mod bulk_import;
+mod bulk_login;
+mod clock;
+mod codex_compat;
+mod codex_config;
+mod codex_installs;
 mod codex_sessions;
+mod daily_report;
 mod deep_link;
+mod diagnostics;
+mod error_center;
+mod external_logins;
+pub mod events;
+mod export_crypto;
 mod ide_control;
+mod import_job;
+mod import_merge;
+mod jwt_claims;
+mod log_identity;
+mod navigation;
+mod notes_template;
 pub mod mailbox;
+mod notify;
 pub mod oauth;
 mod oauth_server;
 pub mod otp_login;
+mod pacing;
+pub mod paths;
+mod plan;
+mod power;
+mod presentation;
 mod provider_quirks;
 mod proxy;
 mod quota_snapshot;
-mod refresh_lock;
+mod quota_window;
+mod redact;
+mod refresh_ban;
+pub mod refresh_lock;
 pub mod relay_translate;
 mod remote_client;
 mod remote_server;
+mod repair;
+pub mod request_headers;
 mod scheduler;
+mod scheduler_supervisor;
 pub mod sentinel;
+pub mod service;
 mod session_affinity;
 mod session_import;
 mod session_routes;
+mod share_snapshot;
 mod skills;
+mod staging;
+mod store_backups;
+mod store_migration;
+mod store_recovery;
+pub mod status_line;
+mod switch_hooks;
+mod switch_intent;
 mod switch_log;
+mod switch_progress;
+mod temp_switch;
+mod token_aging;
+mod token_intern;
+mod token_status;
+pub mod token_store;
 mod token_tracker;
 mod tray;
-mod usage;
+mod undo;
+pub mod usage;
+mod usage_debug;
+pub mod warnings;
 
-use account::{Account, AccountStore};
+use account::{Account, AccountStore, QuotaSource};
+use auth_journal::AuthWriteOp;
 use chrono::Utc;
 use refresh_lock::RefreshLockManager;
+pub use service::score_candidate_accounts;
 use std::net::{IpAddr, Ipv4Addr, UdpSocket};
 use std::process::Command;
 use tauri::{Emitter, Manager, State};
 use usage::{UsageDisplay, UsageFetcher};
 
 const QUARANTINE_FIX_TICKET_TTL_SECS: i64 = 120;
+const UNPROTECT_TICKET_TTL_SECS: i64 = 120;
+const BULK_DELETE_TICKET_TTL_SECS: i64 = 120;
 
 #[derive(Clone, Debug)]
 struct QuarantineFixTicket {
@@ -45,36 +109,61 @@ struct QuarantineFixTicket {
     expires_at: chrono::DateTime<Utc>,
 }
 
-fn allow_local_refresh_for_quota(is_current: bool) -> bool {
-    let _ = is_current;
-    // 统一禁用配额查询路径下的本地 refresh。防止非当前账号消耗旧 refresh_token。
-    false
+/// 危险操作的一次性确认，只对签发时的操作对象（`scope`）有效
+#[derive(Clone, Debug)]
+struct ScopedTicket {
+    scope: String,
+    value: String,
+    expires_at: chrono::DateTime<Utc>,
 }
 
-fn detect_sync_conflict_for_current(
-    account: &Account,
-    disk_auth: &serde_json::Value,
-) -> Option<String> {
-    // 身份不一致时不应提示“Token 冲突”，避免误判
-    if !AccountStore::auth_identity_matches(&account.auth_json, disk_auth) {
-        return None;
-    }
-
-    let official_rt = AccountStore::extract_refresh_token(disk_auth);
-    let local_rt = AccountStore::extract_refresh_token(&account.auth_json);
+fn issue_scoped_ticket(
+    slot: &std::sync::Mutex<Option<ScopedTicket>>,
+    scope: String,
+    ttl_secs: i64,
+) -> Result<String, String> {
+    let ticket = uuid::Uuid::new_v4().to_string();
+    let mut slot = slot.lock().map_err(|e| e.to_string())?;
+    *slot = Some(ScopedTicket {
+        scope,
+        value: ticket.clone(),
+        expires_at: Utc::now() + chrono::Duration::seconds(ttl_secs),
+    });
+    Ok(ticket)
+}
 
-    // 如果官方 Token 存在且与本地不同（通常是更新了），则视为冲突
-    if official_rt.is_some() && official_rt != local_rt {
-        let disk_email =
-            AccountStore::extract_email(disk_auth).unwrap_or_else(|| "未知账号".to_string());
-        if disk_email == account.name {
-            return Some(account.name.clone());
-        } else {
-            return Some(format!("{} ({})", account.name, disk_email));
+/// 用掉确认（无论成败都作废）；`action` 用在错误信息里
+fn consume_scoped_ticket(
+    slot: &std::sync::Mutex<Option<ScopedTicket>>,
+    scope: &str,
+    provided_ticket: &str,
+    action: &str,
+) -> Result<(), String> {
+    let mut slot = slot.lock().map_err(|e| e.to_string())?;
+    match slot.take() {
+        Some(stored) if stored.expires_at < Utc::now() => {
+            Err(format!("{}的确认已过期，请重新操作", action))
         }
+        Some(stored) if stored.value != provided_ticket || stored.scope != scope => {
+            Err(format!("{}的确认无效，请重新操作", action))
+        }
+        Some(_) => Ok(()),
+        None => Err(format!("{}需要先确认，请重新操作", action)),
     }
+}
 
-    None
+/// 批量删除确认的对象：去重排序后的 id 列表，和传入顺序无关
+fn bulk_delete_scope(ids: &[String]) -> String {
+    let mut ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+    ids.sort_unstable();
+    ids.dedup();
+    ids.join("\n")
+}
+
+fn allow_local_refresh_for_quota(is_current: bool) -> bool {
+    let _ = is_current;
+    // 统一禁用配额查询路径下的本地 refresh。防止非当前账号消耗旧 refresh_token。
+    false
 }
 
 /// 全局 store 句柄，供 panic_hook / 退出兜底使用（panic hook 拿不到 Tauri 的
@@ -86,7 +175,8 @@ static GLOBAL_STORE_FOR_EXIT: std::sync::OnceLock<
 /// 应用状态
 pub struct AppState {
     pub store: std::sync::Arc<std::sync::Mutex<AccountStore>>,
-    pub scheduler: std::sync::Mutex<Option<tauri::async_runtime::JoinHandle<()>>>,
+    /// 后台同步调度器（见 `scheduler_supervisor`），启停都经它
+    pub scheduler: std::sync::Arc<scheduler_supervisor::SchedulerSupervisor>,
     pub proxy_handle: std::sync::Mutex<Option<tauri::async_runtime::JoinHandle<()>>>,
     pub proxy_stats: std::sync::Arc<proxy::ProxyStats>,
     pub token_tracker: std::sync::Arc<token_tracker::TokenTracker>,
@@ -100,19 +190,63 @@ pub struct AppState {
     pub refresh_locks: RefreshLockManager,
     pub remote_server_handle: std::sync::Mutex<Option<tauri::async_runtime::JoinHandle<()>>>,
     pub solo_heartbeat_handle: std::sync::Mutex<Option<tauri::async_runtime::JoinHandle<()>>>,
+    /// 类型化事件总线：Tauri 前端和 Remote Server `/events` 共用
+    pub events: events::EventBus,
+    /// 演示模式开关（不持久化）
+    pub presentation: presentation::PresentationMode,
+    /// 正在进行的分批导入任务（同一时间最多一个）
+    pub import_jobs: std::sync::Arc<import_job::ImportJobs>,
+    /// 用户前台操作记录，scheduler 据此让路（见 `activity`）
+    pub foreground: std::sync::Arc<activity::ForegroundActivity>,
+    /// 后台同步轮次间距（见 `pacing`），跨 scheduler 重启保留
+    pub pacer: std::sync::Arc<pacing::SyncPacer>,
+    /// 托盘交给主窗口前端的导航意图（见 `navigation`）
+    pub navigation: navigation::PendingNavigation,
+    /// 删号 / 替换式导入的撤销缓冲（只在内存，见 `undo`）
+    pub undo: undo::UndoBuffer,
+    /// 搜索用的邮箱缓存（见 `account_search`）
+    pub email_cache: account_search::EmailCache,
+    /// 应用内活动流（见 `activity_feed`），定时落盘
+    pub activity: std::sync::Arc<activity_feed::ActivityFeed>,
+    /// 最近推给用户的错误（见 `error_center`）
+    pub errors: std::sync::Arc<error_center::ErrorCenter>,
+    /// 限时临时切号的待切回（见 `temp_switch`），落盘跨重启
+    pub pending_revert: temp_switch::RevertSlot,
     quarantine_fix_ticket: std::sync::Mutex<Option<QuarantineFixTicket>>,
+    unprotect_ticket: std::sync::Mutex<Option<ScopedTicket>>,
+    bulk_delete_ticket: std::sync::Mutex<Option<ScopedTicket>>,
 }
 
 impl AppState {
     pub fn new() -> Self {
-        let store = std::sync::Arc::new(std::sync::Mutex::new(AccountStore::load()));
+        let loaded = AccountStore::load();
+        usage_debug::global().configure(
+            loaded.settings.debug_capture_responses,
+            loaded.settings.debug_capture_dump,
+        );
+        log_identity::set_style(loaded.settings.log_identifier_style);
+        let errors = std::sync::Arc::new(error_center::ErrorCenter::load());
+        if let Some(message) = loaded.health().message() {
+            errors.record(
+                error_center::ErrorOrigin::Background("load_accounts".to_string()),
+                None,
+                &message,
+            );
+        }
+        let store = std::sync::Arc::new(std::sync::Mutex::new(loaded));
         // 注册到全局侧通道，供 panic hook / RunEvent::Exit 在 Tauri State 不可达
         // 的位置使用。第二次调用会被忽略（OnceLock 语义）—— 多实例非预期场景下
         // 也只会保留第一份。
         let _ = GLOBAL_STORE_FOR_EXIT.set(store.clone());
         Self {
             store,
-            scheduler: std::sync::Mutex::new(None),
+            // 使用 Tauri 的 async runtime 而不是直接 tokio::spawn
+            // 因为在 setup() 中调用时 Tokio runtime 可能尚未完全初始化
+            scheduler: std::sync::Arc::new(scheduler_supervisor::SchedulerSupervisor::new(
+                |task| {
+                    tauri::async_runtime::spawn(task);
+                },
+            )),
             proxy_handle: std::sync::Mutex::new(None),
             proxy_stats: std::sync::Arc::new(proxy::ProxyStats::default()),
             token_tracker: token_tracker::TokenTracker::new(),
@@ -126,7 +260,20 @@ impl AppState {
             refresh_locks: RefreshLockManager::default(),
             remote_server_handle: std::sync::Mutex::new(None),
             solo_heartbeat_handle: std::sync::Mutex::new(None),
+            events: events::EventBus::default(),
+            presentation: presentation::PresentationMode::default(),
+            import_jobs: std::sync::Arc::new(import_job::ImportJobs::default()),
+            foreground: std::sync::Arc::new(activity::ForegroundActivity::default()),
+            pacer: std::sync::Arc::new(pacing::SyncPacer::default()),
+            navigation: navigation::PendingNavigation::default(),
+            undo: undo::UndoBuffer::default(),
+            email_cache: account_search::EmailCache::default(),
+            activity: std::sync::Arc::new(activity_feed::ActivityFeed::load()),
+            errors,
+            pending_revert: temp_switch::RevertSlot::load(),
             quarantine_fix_ticket: std::sync::Mutex::new(None),
+            unprotect_ticket: std::sync::Mutex::new(None),
+            bulk_delete_ticket: std::sync::Mutex::new(None),
         }
     }
 
@@ -161,6 +308,77 @@ impl AppState {
             None => Err("缺少安全确认，请重新点击修复".to_string()),
         }
     }
+
+    fn issue_unprotect_ticket(&self, account_id: &str) -> Result<String, String> {
+        issue_scoped_ticket(
+            &self.unprotect_ticket,
+            account_id.to_string(),
+            UNPROTECT_TICKET_TTL_SECS,
+        )
+    }
+
+    fn consume_unprotect_ticket(
+        &self,
+        account_id: &str,
+        provided_ticket: &str,
+    ) -> Result<(), String> {
+        consume_scoped_ticket(
+            &self.unprotect_ticket,
+            account_id,
+            provided_ticket,
+            "取消保护",
+        )
+    }
+
+    fn issue_bulk_delete_ticket(&self, ids: &[String]) -> Result<String, String> {
+        issue_scoped_ticket(
+            &self.bulk_delete_ticket,
+            bulk_delete_scope(ids),
+            BULK_DELETE_TICKET_TTL_SECS,
+        )
+    }
+
+    fn consume_bulk_delete_ticket(
+        &self,
+        ids: &[String],
+        provided_ticket: &str,
+    ) -> Result<(), String> {
+        consume_scoped_ticket(
+            &self.bulk_delete_ticket,
+            &bulk_delete_scope(ids),
+            provided_ticket,
+            "批量删除",
+        )
+    }
+
+    /// 账号业务层：真实 HTTP + 当前应用的事件出口
+    fn account_service(&self, app: &tauri::AppHandle) -> service::AccountService {
+        service::AccountService::new(
+            self.store.clone(),
+            self.refresh_locks.clone(),
+            std::sync::Arc::new(service::HttpUsageClient),
+            std::sync::Arc::new(TauriEventSink {
+                app: app.clone(),
+                bus: self.events.clone(),
+            }),
+        )
+    }
+}
+
+/// `service::EventSink` 的 Tauri 实现
+struct TauriEventSink {
+    app: tauri::AppHandle,
+    bus: events::EventBus,
+}
+
+impl service::EventSink for TauriEventSink {
+    fn emit(&self, event: &str, payload: serde_json::Value) {
+        let _ = self.app.emit(event, payload);
+    }
+
+    fn publish(&self, event: events::AppEvent) {
+        self.bus.publish(event);
+    }
 }
 
 impl Default for AppState {
@@ -169,11 +387,173 @@ impl Default for AppState {
     }
 }
 
-/// 获取所有账号
+/// 演示模式下下发给前端的账号：名字 / 邮箱打码，且不带 token
+fn present_accounts(state: &AppState, accounts: Vec<Account>) -> (Vec<Account>, bool) {
+    if !state.presentation.is_enabled() {
+        return (accounts, false);
+    }
+    let masked = accounts
+        .into_iter()
+        .map(|mut acc| {
+            // 先固化 kind，清空 auth_json 后前端就推不出 legacy 账号的类型了
+            acc.kind = acc.effective_kind();
+            acc.name = presentation::mask_name(&acc.name);
+            acc.email = acc.email.as_deref().map(presentation::mask_email);
+            acc.notes = None;
+            acc.auth_json = serde_json::json!({});
+            acc.refresh_token = None;
+            acc
+        })
+        .collect();
+    (masked, true)
+}
+
+/// 获取账号列表，最多 `GET_ACCOUNTS_CAP` 个，超出时 `truncated` 为 true，剩下的走分页
 #[tauri::command]
-fn get_accounts(state: State<AppState>) -> Result<Vec<Account>, String> {
-    let store = state.store.lock().map_err(|e| e.to_string())?;
-    Ok(store.list_accounts().into_iter().cloned().collect())
+fn get_accounts(state: State<AppState>) -> Result<account_list::AccountList, String> {
+    let mut list = {
+        let mut store = state.store.lock().map_err(|e| e.to_string())?;
+        let mut list = account_list::list_capped(&mut store, account_list::GET_ACCOUNTS_CAP);
+        list.compatibility =
+            codex_compat::verdicts(cached_codex_version(&store).as_ref(), &list.accounts);
+        list
+    };
+    (list.accounts, list.masked) = present_accounts(&state, list.accounts);
+    Ok(list)
+}
+
+/// 分页取账号；`sort` / `filter` 缺省为新建在前、不过滤
+#[tauri::command]
+fn get_accounts_page(
+    state: State<AppState>,
+    offset: usize,
+    limit: usize,
+    sort: Option<account_list::AccountSort>,
+    filter: Option<account_list::AccountFilter>,
+) -> Result<account_list::AccountPage, String> {
+    let mut page = {
+        let mut store = state.store.lock().map_err(|e| e.to_string())?;
+        let mut page = account_list::page(
+            &mut store,
+            offset,
+            limit,
+            sort.unwrap_or_default(),
+            &filter.unwrap_or_default(),
+        );
+        page.compatibility =
+            codex_compat::verdicts(cached_codex_version(&store).as_ref(), &page.items);
+        page
+    };
+    (page.items, page.masked) = present_accounts(&state, page.items);
+    Ok(page)
+}
+
+/// 按标签过滤的账号列表（带任一标签即可，不区分大小写；不传或传空表不过滤）。
+/// `sort_by_last_used` 时最近用过的在前，否则同账号列表（置顶 → 手动顺序 → 新建在前）
+#[tauri::command]
+fn list_accounts_filtered(
+    state: State<AppState>,
+    tags: Option<Vec<String>>,
+    sort_by_last_used: Option<bool>,
+) -> Result<Vec<Account>, String> {
+    let accounts = {
+        let store = state.store.lock().map_err(|e| e.to_string())?;
+        let sort = if sort_by_last_used.unwrap_or(false) {
+            account_list::AccountSort::LastUsed
+        } else {
+            account_list::AccountSort::Manual
+        };
+        let filter = account_list::AccountFilter {
+            tags,
+            ..Default::default()
+        };
+        account_list::filtered(&store, sort, &filter)
+    };
+    Ok(present_accounts(&state, accounts).0)
+}
+
+/// 单个账号加派生字段（last_refresh、额度缓存年龄），刷新一张卡片时用。
+/// 账号不存在时错误带 `code: "not_found"`，前端据此区分"已在别处删除"和暂时性失败
+#[tauri::command]
+fn get_account(
+    state: State<AppState>,
+    id: String,
+) -> Result<account_list::AccountDetail, account_list::GetAccountError> {
+    let mut detail = {
+        let store = state
+            .store
+            .lock()
+            .map_err(|e| account_list::GetAccountError::Unavailable {
+                message: e.to_string(),
+            })?;
+        account_list::detail(&store, &id, Utc::now())?
+    };
+    let (mut accounts, masked) = present_accounts(&state, vec![detail.account]);
+    detail.account = accounts.remove(0);
+    if masked {
+        detail.masked = true;
+    }
+    Ok(detail)
+}
+
+/// 按名字 / 邮箱 / 备注搜账号（不区分大小写）：邮箱完全相等的在前，再是名字前缀，再是包含；
+/// 空查询返回全部账号
+#[tauri::command]
+fn search_accounts(state: State<AppState>, query: String) -> Result<Vec<Account>, String> {
+    let accounts = {
+        let store = state.store.lock().map_err(|e| e.to_string())?;
+        account_search::search(&store, &state.email_cache, &query)
+    };
+    Ok(present_accounts(&state, accounts).0)
+}
+
+/// 增量加载：`cursor` 之后改过的账号和删掉的账号 id；`cursor` 取自上一次列表 / 增量响应
+#[tauri::command]
+fn get_accounts_changed_since(
+    state: State<AppState>,
+    cursor: Option<chrono::DateTime<Utc>>,
+) -> Result<account_list::AccountDelta, String> {
+    let mut delta = {
+        let mut store = state.store.lock().map_err(|e| e.to_string())?;
+        account_list::changed_since(&mut store, cursor)
+    };
+    (delta.changed, delta.masked) = present_accounts(&state, delta.changed);
+    Ok(delta)
+}
+
+/// 开关演示模式（仅内存，重启后恢复）
+#[tauri::command]
+fn set_presentation_mode(
+    state: State<AppState>,
+    app: tauri::AppHandle,
+    enabled: bool,
+) -> Result<bool, String> {
+    state.presentation.set(enabled);
+    println!(
+        "[Presentation] 演示模式已{}",
+        if enabled { "开启" } else { "关闭" }
+    );
+    let _ = app.emit("accounts-updated", ());
+    tray::update_tray_menu(&app);
+    Ok(enabled)
+}
+
+/// 当前是否处于演示模式
+#[tauri::command]
+fn get_presentation_mode(state: State<AppState>) -> bool {
+    state.presentation.is_enabled()
+}
+
+/// 公开负载的 schema 版本 + 应用版本，版本规则见 `api_schema`
+#[tauri::command]
+fn get_api_version() -> api_schema::ApiVersion {
+    api_schema::api_version()
+}
+
+/// 公开 DTO（账号、用量、设置、事件）的 JSON Schema，供外部消费方校验
+#[tauri::command]
+fn get_schemas() -> serde_json::Value {
+    api_schema::schema_document()
 }
 
 /// 获取当前激活的账号 ID
@@ -190,6 +570,40 @@ fn get_settings(state: State<AppState>) -> Result<account::AppSettings, String>
     Ok(store.settings.clone())
 }
 
+/// 启动时 accounts.json 的加载情况：损坏后是否已改名留存、从备份恢复或拒绝写回
+#[tauri::command]
+fn get_store_health(state: State<AppState>) -> Result<store_recovery::StoreHealth, String> {
+    let store = state.store.lock().map_err(|e| e.to_string())?;
+    Ok(store.health().clone())
+}
+
+/// 预览新账号备注模板。`id_or_preview_identity` 是已有账号 id 时用该账号的身份渲染，
+/// 否则当作示例邮箱；`template` 为空时用已保存的模板。模板为空时返回 None（沿用固定备注）
+#[tauri::command]
+fn render_notes_template(
+    state: State<AppState>,
+    id_or_preview_identity: Option<String>,
+    template: Option<String>,
+) -> Result<Option<String>, String> {
+    let store = state.store.lock().map_err(|e| e.to_string())?;
+    let template = template.unwrap_or_else(|| store.settings.new_account_notes_template.clone());
+    let today = chrono::Local::now().date_naive();
+    let identity = id_or_preview_identity.unwrap_or_default();
+    let values = match store.accounts.get(identity.trim()) {
+        Some(account) => notes_template::TemplateValues::from_auth(&account.auth_json, today),
+        None => notes_template::TemplateValues {
+            email: Some(identity.trim())
+                .filter(|s| !s.is_empty())
+                .unwrap_or("someone@example.com")
+                .to_string(),
+            plan: "plus".to_string(),
+            date: today,
+            account_id: "preview-account-id".to_string(),
+        },
+    };
+    notes_template::render(&template, &values, None)
+}
+
 /// 代理状态信息
 #[derive(Debug, Clone, serde::Serialize)]
 pub struct ProxyStatus {
@@ -270,19 +684,70 @@ fn get_proxy_status(state: State<AppState>) -> Result<ProxyStatus, String> {
     })
 }
 
-/// 更新全局设置
+/// 只导出设置（不含账号和 token），换机器时用
+#[tauri::command]
+fn export_settings(state: State<AppState>) -> Result<String, String> {
+    let store = state.store.lock().map_err(|e| e.to_string())?;
+    store.settings.export()
+}
+
+/// 导入 `export_settings` 的内容：校验取值范围后走 `update_settings`，后台刷新、代理等
+/// 的启停和手动改设置一样生效。账号和 current 不动
+#[tauri::command]
+fn import_settings(
+    state: State<AppState>,
+    app: tauri::AppHandle,
+    json: String,
+) -> Result<account::AppSettings, String> {
+    let settings = {
+        let store = state.store.lock().map_err(|e| e.to_string())?;
+        account::AppSettings::import(&json, &store.settings)?
+    };
+    update_settings(state, app, settings)
+}
+
+/// 更新全局设置；返回实际保存的设置（调度间隔等可能被夹进合法范围），前端以此为准
 #[tauri::command]
 fn update_settings(
     state: State<AppState>,
     app: tauri::AppHandle,
     mut settings: account::AppSettings,
-) -> Result<(), String> {
+) -> Result<account::AppSettings, String> {
     // client 模式硬约束：本机不做保活（保活由 Server 负责）
     // quota_refresh_enabled 在 client 模式下被用作"Server 状态同步循环"的开关；
     // 即使用户把它关掉，我们也始终会启动该循环（见下面启动条件）。
     if settings.remote_mode == "client" {
         settings.background_refresh = false;
     }
+    oauth::validate_callback_ports(&settings.oauth_callback_ports)?;
+    switch_hooks::validate(&mut settings.post_switch_hooks)?;
+    if settings.daily_report_enabled {
+        daily_report::parse_report_time(&settings.daily_report_time)?;
+    }
+    request_headers::validate_user_agent(&settings.usage_user_agent)?;
+    notes_template::validate(&settings.new_account_notes_template)?;
+    paths::validate_codex_home(settings.codex_home.as_deref())?;
+    codex_installs::validate(
+        &settings.codex_installs,
+        &paths::codex_dir_for(settings.codex_home.as_deref()).join("auth.json"),
+    )?;
+    for install in &mut settings.codex_installs {
+        install.name = install.name.trim().to_string();
+        install.auth_path = install.auth_path.trim().to_string();
+    }
+    settings.codex_home = settings
+        .codex_home
+        .map(|dir| dir.trim().to_string())
+        .filter(|dir| !dir.is_empty());
+    settings.usage_user_agent = settings.usage_user_agent.trim().to_string();
+    let interval = account::clamp_refresh_interval(settings.refresh_interval_minutes);
+    if interval != settings.refresh_interval_minutes {
+        println!(
+            "[Settings] 调度间隔 {} 分钟超出范围，已调整为 {} 分钟",
+            settings.refresh_interval_minutes, interval
+        );
+        settings.refresh_interval_minutes = interval;
+    }
     let (
         prev_bg_refresh,
         prev_proxy_enabled,
@@ -300,30 +765,28 @@ fn update_settings(
             store.settings.quota_refresh_enabled,
             store.settings.remote_mode.clone(),
         );
+        // token 存储方式只能经 migrate_token_storage 改，这里直接改会让 token 落到错的地方
+        settings.token_storage = store.settings.token_storage;
+        settings.keychain_include_access_tokens = store.settings.keychain_include_access_tokens;
+        // OAuth 配置走 add_auth_profile / remove_auth_profile，不随整份设置覆盖
+        settings.auth_profiles = store.settings.auth_profiles.clone();
         store.settings = settings.clone();
+        codex_installs::prune(&mut store);
         store.save()?;
         prev
     };
+    usage_debug::global().configure(
+        settings.debug_capture_responses,
+        settings.debug_capture_dump,
+    );
+    log_identity::set_style(settings.log_identifier_style);
+    paths::set_codex_home(settings.codex_home.as_deref());
 
     // 联动刷新托盘菜单文案 (同步更新“下个账号”预览)
     crate::tray::update_tray_menu(&app);
 
     // 后台刷新生命周期
-    let mut scheduler_handle = state.scheduler.lock().map_err(|e| e.to_string())?;
-    match (prev_bg_refresh, settings.background_refresh) {
-        (false, true) => {
-            if scheduler_handle.is_none() {
-                let handle = scheduler::start(state.store.clone(), app.clone());
-                *scheduler_handle = Some(handle);
-            }
-        }
-        (true, false) => {
-            if let Some(handle) = scheduler_handle.take() {
-                handle.abort();
-            }
-        }
-        _ => {}
-    }
+    apply_background_refresh(&state, &app, prev_bg_refresh, settings.background_refresh)?;
 
     // 代理生命周期
     let mut proxy_handle = state.proxy_handle.lock().map_err(|e| e.to_string())?;
@@ -436,63 +899,174 @@ fn update_settings(
     }
 
     app.emit("settings-updated", ()).ok();
+    Ok(settings)
+}
+
+/// 后台刷新开关变化时启停调度器（设置页和托盘共用）
+fn apply_background_refresh(
+    state: &AppState,
+    app: &tauri::AppHandle,
+    prev: bool,
+    enabled: bool,
+) -> Result<(), String> {
+    match (prev, enabled) {
+        (false, true) => {
+            start_scheduler(state, app);
+        }
+        (true, false) => {
+            let supervisor = state.scheduler.clone();
+            tauri::async_runtime::spawn(async move {
+                supervisor.stop(scheduler_supervisor::STOP_GRACE).await;
+            });
+        }
+        _ => {}
+    }
     Ok(())
 }
 
-/// 从当前 Codex 登录状态导入账号
+/// 经监管器拉起后台调度循环；已在跑时什么也不做
+fn start_scheduler(state: &AppState, app: &tauri::AppHandle) -> scheduler_supervisor::StartOutcome {
+    let (store, app, foreground, pacer) = (
+        state.store.clone(),
+        app.clone(),
+        state.foreground.clone(),
+        state.pacer.clone(),
+    );
+    state
+        .scheduler
+        .start(move |shutdown| scheduler::run(store, app, foreground, pacer, shutdown))
+}
+
+/// 从当前 Codex 登录状态导入账号。同一身份的账号已存在时只更新它的 token（返回里
+/// `updated` 为 true）；`force` 时照样新建一份
 #[tauri::command]
 fn import_current_account(
     state: State<AppState>,
     app: tauri::AppHandle,
     name: String,
     notes: Option<String>,
-) -> Result<Account, String> {
-    let auth_json = AccountStore::read_codex_auth()?;
-    if AccountStore::extract_refresh_token(&auth_json).is_none() {
-        return Err("当前 auth.json 缺少 refresh_token，无法自动续期，请重新登录".to_string());
-    }
+    force: Option<bool>,
+) -> Result<account::ImportedAccount, String> {
+    let imported =
+        state
+            .account_service(&app)
+            .import_current(name, notes, force.unwrap_or(false))?;
+    crate::tray::update_tray_menu(&app);
+    Ok(imported)
+}
 
-    let account = {
-        let mut store = state.store.lock().map_err(|e| e.to_string())?;
-        let account = store.add_account(name, auth_json, notes);
-        store.save()?;
-        account
-    };
+/// 从粘贴的 auth.json 文本导入账号，不碰本机的 ~/.codex/auth.json。
+/// 去重和 `force` 同 `import_current_account`；格式不对时报具体缺了哪个字段
+#[tauri::command]
+fn import_auth_json(
+    state: State<AppState>,
+    app: tauri::AppHandle,
+    name: String,
+    json_text: String,
+    notes: Option<String>,
+    force: Option<bool>,
+) -> Result<account::ImportedAccount, String> {
+    let imported = state.account_service(&app).import_pasted(
+        name,
+        &json_text,
+        notes,
+        force.unwrap_or(false),
+    )?;
     crate::tray::update_tray_menu(&app);
-    Ok(account)
+    Ok(imported)
 }
 
 // is_token_expired removed: align with Codex last_refresh-based refresh
 
-/// 检查当前 IDE 中的账号是否有未同步的 Token 更新
+/// 检查当前 IDE 中的账号是否有未同步的 Token 更新；`install` 缺省查默认安装
 #[tauri::command]
-fn check_sync_conflict(state: State<AppState>) -> Result<Option<String>, String> {
-    let auth_json = match AccountStore::read_codex_auth() {
-        Ok(a) => a,
-        Err(_) => return Ok(None), // 如果由于文件不存在等原因读取失败，视为无冲突
-    };
-
-    let store = state.store.lock().map_err(|e| e.to_string())?;
-
-    // 检查这个 auth.json 是否属于我们当前的活跃账号，且内容是否有变
-    if let Some(current_id) = &store.current {
-        if let Some(account) = store.accounts.get(current_id) {
-            if let Some(name) = detect_sync_conflict_for_current(account, &auth_json) {
-                return Ok(Some(name));
-            }
-        }
+fn check_sync_conflict(
+    state: State<AppState>,
+    app: tauri::AppHandle,
+    install: Option<String>,
+) -> Result<Option<String>, String> {
+    let service = state.account_service(&app);
+    match install.filter(|name| !codex_installs::is_default(Some(name))) {
+        Some(install) => service.check_install_conflict(install.trim()),
+        None => service.check_conflict(),
     }
+}
 
-    Ok(None)
+/// 退出 Codex 登录（不删账号）：最后回流一次 → 删 auth.json → 清 current。
+/// 全程持有 store 锁，其它写盘路径（切号 / 回流）都要先拿这把锁。
+/// current-changed(None) 由事件总线在 accounts-updated 后对比 store 推出。
+#[tauri::command]
+fn logout_codex(
+    state: State<AppState>,
+    app: tauri::AppHandle,
+    skip_final_sync: Option<bool>,
+) -> Result<account::CodexLogout, String> {
+    let outcome = {
+        let mut store = state.store.lock().map_err(|e| e.to_string())?;
+        store.logout_codex(skip_final_sync.unwrap_or(false))?
+    };
+    let _ = app.emit("accounts-updated", ());
+    crate::tray::update_tray_menu(&app);
+    Ok(outcome)
 }
 
-/// 删除账号
+/// 删除账号；返回撤销凭据（[`undo::UNDO_TTL`] 内可调 `undo` 恢复）
 #[tauri::command]
 async fn delete_account(
     state: State<'_, AppState>,
     app: tauri::AppHandle,
     id: String,
-) -> Result<(), String> {
+) -> Result<undo::UndoReceipt, String> {
+    let outcome = delete_accounts_internal(&state, &app, vec![id], true).await?;
+    match (outcome.undo, outcome.result.skipped.into_iter().next()) {
+        (Some(receipt), _) => Ok(receipt),
+        (None, Some(skipped)) => Err(skipped.reason),
+        (None, None) => Err("要删除的账号不存在".to_string()),
+    }
+}
+
+/// `delete_accounts` 的返回
+#[derive(serde::Serialize)]
+struct BulkDeleteOutcome {
+    #[serde(flatten)]
+    result: account::BulkDelete,
+    /// 删掉了账号时给撤销凭据（整批共用，撤销全有或全无）
+    undo: Option<undo::UndoReceipt>,
+}
+
+/// 批量删除前先要一张一次性确认（[`BULK_DELETE_TICKET_TTL_SECS`] 内有效），
+/// 只对这一组 id 有效；前端据此必须先弹确认框
+#[tauri::command]
+fn request_delete_accounts_ticket(
+    state: State<AppState>,
+    ids: Vec<String>,
+) -> Result<String, String> {
+    if ids.is_empty() {
+        return Err("没有选要删除的账号".to_string());
+    }
+    state.issue_bulk_delete_ticket(&ids)
+}
+
+/// 批量删除账号：整批校验后一次删完、保存一次。不存在、受保护的跳过，当前账号除非
+/// `allow_current` 也跳过；返回删掉的、跳过的（带原因）和撤销凭据
+#[tauri::command]
+async fn delete_accounts(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    ids: Vec<String>,
+    ticket: String,
+    allow_current: Option<bool>,
+) -> Result<BulkDeleteOutcome, String> {
+    state.consume_bulk_delete_ticket(&ids, &ticket)?;
+    delete_accounts_internal(&state, &app, ids, allow_current.unwrap_or(false)).await
+}
+
+async fn delete_accounts_internal(
+    state: &State<'_, AppState>,
+    app: &tauri::AppHandle,
+    ids: Vec<String>,
+    allow_current: bool,
+) -> Result<BulkDeleteOutcome, String> {
     // 先取一份快照：client 模式下需要把删号指令同步给 Server
     let (remote_mode, primary, fallback, secret) = {
         let store = state.store.lock().map_err(|e| e.to_string())?;
@@ -504,54 +1078,276 @@ async fn delete_account(
         )
     };
 
-    {
+    let (result, receipt) = {
         let mut store = state.store.lock().map_err(|e| e.to_string())?;
-        if store.current.as_deref() == Some(&id) {
-            store.current = None;
+        // 撤销只放回真删掉的
+        let planned = store.plan_delete_accounts(&ids, allow_current);
+        let stash = undo::UndoStash::before_delete(&store, &planned.removed);
+        let result = store.delete_accounts(&ids, allow_current);
+        if result.removed.is_empty() {
+            return Ok(BulkDeleteOutcome { result, undo: None });
         }
-        store.delete_account(&id)?;
+        store.purge_expired_trash(Utc::now());
         store.save()?;
+        (result, Some(state.undo.push(stash.finish(&store))))
+    };
+    for id in &result.removed {
+        if let Err(e) = staging::Staging::system().cleanup(Some(id)) {
+            eprintln!("[Staging] 清理 {} 的暂存副本失败: {}", id, e);
+        }
     }
 
     // client / solo 模式：同步删除 Server 上的对应账号（失败不影响本地删除已完成的事实）
     if account::pushes_to_server(&remote_mode) && !secret.is_empty() {
         match remote_client::resolve_base_url(&primary, &fallback).await {
             Ok(base) => {
-                if let Err(e) = remote_client::delete_account(&base, &secret, &id).await {
-                    eprintln!("[DeleteAccount] Server 端联动删除失败（本地已删除）: {}", e);
+                for id in &result.removed {
+                    if let Err(e) = remote_client::delete_account(&base, &secret, id).await {
+                        eprintln!("[DeleteAccount] Server 端联动删除失败（本地已删除）: {}", e);
+                    }
                 }
             }
             Err(e) => eprintln!("[DeleteAccount] Server 不可达（本地已删除）: {}", e),
         }
     }
 
-    crate::tray::update_tray_menu(&app);
-    Ok(())
+    crate::tray::update_tray_menu(app);
+    Ok(BulkDeleteOutcome {
+        result,
+        undo: receipt,
+    })
 }
 
-/// 更新账号信息
+/// 撤销最近的删号 / 替换式导入：账号原样放回（同 id 同数据），见 `undo`
 #[tauri::command]
-fn update_account(
-    state: State<AppState>,
+async fn undo(
+    state: State<'_, AppState>,
     app: tauri::AppHandle,
-    id: String,
-    name: Option<String>,
-    notes: Option<String>,
-) -> Result<(), String> {
-    {
+    token: String,
+) -> Result<undo::UndoOutcome, String> {
+    let (outcome, to_push, secret) = {
         let mut store = state.store.lock().map_err(|e| e.to_string())?;
-        store.update_account(&id, name, notes)?;
+        let outcome = state.undo.undo(&mut store, &token)?;
         store.save()?;
-    }
-    crate::tray::update_tray_menu(&app);
-    Ok(())
-}
-
-/// 更新 Relay usage 专用 Cookie（MiMo Token Plan 等控制台配额接口使用）。
-#[tauri::command]
-fn update_relay_usage_cookie(
-    state: State<AppState>,
-    id: String,
+        let to_push: Vec<Account> = if account::pushes_to_server(&store.settings.remote_mode) {
+            outcome
+                .restored
+                .iter()
+                .filter_map(|id| store.accounts.get(id).cloned())
+                .collect()
+        } else {
+            Vec::new()
+        };
+        (
+            outcome,
+            to_push,
+            store.settings.remote_shared_secret.clone(),
+        )
+    };
+    println!(
+        "[Undo] 已撤销 {:?}：恢复 {} 个账号，移除 {} 个",
+        outcome.kind,
+        outcome.restored.len(),
+        outcome.removed.len()
+    );
+    let _ = app.emit("accounts-updated", ());
+    crate::tray::update_tray_menu(&app);
+
+    // 删号时已联动删了 Server 上的账号，撤销后重新推上去（尽力而为）
+    if !to_push.is_empty() && !secret.is_empty() {
+        match client_settings_snapshot(&state).await {
+            Ok((url, secret)) => {
+                for acc in &to_push {
+                    if let Err(e) = remote_client::upsert_account(&url, &secret, acc).await {
+                        eprintln!(
+                            "[Undo] 重新推送 {} 到 Server 失败: {}",
+                            log_identity::id(&acc.name),
+                            e
+                        );
+                    }
+                }
+            }
+            Err(e) => eprintln!("[Undo] Server 不可达，恢复的账号未推送: {}", e),
+        }
+    }
+    Ok(outcome)
+}
+
+/// 回收站里的账号，最近删的在前
+#[tauri::command]
+fn get_trash(state: State<AppState>) -> Result<Vec<account::TrashedAccount>, String> {
+    let entries: Vec<account::TrashedAccount> = {
+        let store = state.store.lock().map_err(|e| e.to_string())?;
+        store.list_trash().into_iter().cloned().collect()
+    };
+    let deleted_at: Vec<_> = entries.iter().map(|e| e.deleted_at).collect();
+    let (accounts, _) = present_accounts(&state, entries.into_iter().map(|e| e.account).collect());
+    Ok(accounts
+        .into_iter()
+        .zip(deleted_at)
+        .map(|(account, deleted_at)| account::TrashedAccount {
+            account,
+            deleted_at,
+        })
+        .collect())
+}
+
+/// 从回收站恢复账号；删号时联动删了 Server 上的账号的，恢复后重新推上去（尽力而为）
+#[tauri::command]
+async fn restore_account(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    id: String,
+) -> Result<(), String> {
+    let to_push = {
+        let mut store = state.store.lock().map_err(|e| e.to_string())?;
+        store.restore_from_trash(&id)?;
+        store.save()?;
+        let pushes = account::pushes_to_server(&store.settings.remote_mode)
+            && !store.settings.remote_shared_secret.is_empty();
+        store.accounts.get(&id).filter(|_| pushes).cloned()
+    };
+    println!("[Trash] 已从回收站恢复 {}", log_identity::id(&id));
+    let _ = app.emit("accounts-updated", ());
+    crate::tray::update_tray_menu(&app);
+
+    if let Some(acc) = to_push {
+        match client_settings_snapshot(&state).await {
+            Ok((url, secret)) => {
+                if let Err(e) = remote_client::upsert_account(&url, &secret, &acc).await {
+                    eprintln!(
+                        "[Trash] 重新推送 {} 到 Server 失败: {}",
+                        log_identity::id(&acc.name),
+                        e
+                    );
+                }
+            }
+            Err(e) => eprintln!("[Trash] Server 不可达，恢复的账号未推送: {}", e),
+        }
+    }
+    Ok(())
+}
+
+/// 彻底清掉在回收站里放了 `older_than_days` 天以上的账号（0 = 清空回收站），返回清掉的个数
+#[tauri::command]
+fn purge_trash(state: State<AppState>, older_than_days: u32) -> Result<usize, String> {
+    let mut store = state.store.lock().map_err(|e| e.to_string())?;
+    let purged = store.purge_trash(chrono::Duration::days(older_than_days as i64), Utc::now());
+    if !purged.is_empty() {
+        store.save()?;
+        println!("[Trash] 已彻底清除 {} 个账号", purged.len());
+    }
+    Ok(purged.len())
+}
+
+/// 复制账号当备份（不会成为当前账号，副本关掉非活跃保活），返回新账号
+#[tauri::command]
+fn clone_account(
+    state: State<AppState>,
+    app: tauri::AppHandle,
+    id: String,
+    new_name: String,
+) -> Result<Account, String> {
+    let clone = {
+        let mut store = state.store.lock().map_err(|e| e.to_string())?;
+        let clone = store.clone_account(&id, new_name)?;
+        store.save()?;
+        clone
+    };
+    println!(
+        "[Clone] 已复制 {} → {}",
+        log_identity::id(&id),
+        log_identity::id(&clone.name)
+    );
+    let _ = app.emit("accounts-updated", ());
+    Ok(clone)
+}
+
+/// 最近切到这个号的时间，新的在前（最多 `account::MAX_SWITCH_HISTORY` 条）
+#[tauri::command]
+fn get_account_switch_history(
+    state: State<AppState>,
+    id: String,
+) -> Result<Vec<chrono::DateTime<Utc>>, String> {
+    let store = state.store.lock().map_err(|e| e.to_string())?;
+    let account = store
+        .accounts
+        .get(&id)
+        .ok_or_else(|| format!("账号不存在: {}", id))?;
+    Ok(account.switch_history.iter().rev().copied().collect())
+}
+
+/// 同一身份的重复账号分组（每组两个以上的 id），前端据此提示合并
+#[tauri::command]
+fn find_duplicate_accounts(state: State<AppState>) -> Result<Vec<Vec<String>>, String> {
+    let store = state.store.lock().map_err(|e| e.to_string())?;
+    Ok(store.find_duplicate_accounts())
+}
+
+/// 把重复账号 `remove_id` 合并进 `keep_id`（身份不一致时拒绝），被合并的号进回收站
+#[tauri::command]
+async fn merge_accounts(
+    state: State<'_, AppState>,
+    app: tauri::AppHandle,
+    keep_id: String,
+    remove_id: String,
+) -> Result<Account, String> {
+    let (merged, pushes) = {
+        let mut store = state.store.lock().map_err(|e| e.to_string())?;
+        let merged = store.merge_accounts(&keep_id, &remove_id)?;
+        store.save()?;
+        let pushes = account::pushes_to_server(&store.settings.remote_mode)
+            && !store.settings.remote_shared_secret.is_empty();
+        (merged, pushes)
+    };
+    println!(
+        "[Merge] 已把 {} 合并进 {}",
+        log_identity::id(&remove_id),
+        log_identity::id(&merged.name)
+    );
+    let _ = app.emit("accounts-updated", ());
+    crate::tray::update_tray_menu(&app);
+
+    // client / solo 模式：Server 上同样合并（失败不影响本地已完成的合并）
+    if pushes {
+        match client_settings_snapshot(&state).await {
+            Ok((url, secret)) => {
+                if let Err(e) = remote_client::upsert_account(&url, &secret, &merged).await {
+                    eprintln!("[Merge] 推送合并后的账号到 Server 失败: {}", e);
+                }
+                if let Err(e) = remote_client::delete_account(&url, &secret, &remove_id).await {
+                    eprintln!("[Merge] Server 端联动删除失败（本地已合并）: {}", e);
+                }
+            }
+            Err(e) => eprintln!("[Merge] Server 不可达（本地已合并）: {}", e),
+        }
+    }
+    Ok(merged)
+}
+
+/// 更新账号信息
+#[tauri::command]
+fn update_account(
+    state: State<AppState>,
+    app: tauri::AppHandle,
+    id: String,
+    name: Option<String>,
+    notes: Option<String>,
+) -> Result<(), String> {
+    {
+        let mut store = state.store.lock().map_err(|e| e.to_string())?;
+        store.update_account(&id, name, notes)?;
+        store.save()?;
+    }
+    crate::tray::update_tray_menu(&app);
+    Ok(())
+}
+
+/// 更新 Relay usage 专用 Cookie（MiMo Token Plan 等控制台配额接口使用）。
+#[tauri::command]
+fn update_relay_usage_cookie(
+    state: State<AppState>,
+    id: String,
     usage_cookie: Option<String>,
 ) -> Result<(), String> {
     let normalized = usage_cookie
@@ -576,6 +1372,154 @@ fn set_account_inactive_refresh_enabled(
     Ok(())
 }
 
+/// 设置账号颜色标签（`#RRGGBB`，传空清除）；当前账号变色时立即刷新托盘
+#[tauri::command]
+fn set_account_color(
+    state: State<AppState>,
+    app: tauri::AppHandle,
+    id: String,
+    color: Option<String>,
+) -> Result<(), String> {
+    let is_current = {
+        let mut store = state.store.lock().map_err(|e| e.to_string())?;
+        store.set_account_color(&id, color.as_deref())?;
+        store.save()?;
+        store.current.as_deref() == Some(id.as_str())
+    };
+    if is_current {
+        crate::tray::update_tray_menu(&app);
+    }
+    Ok(())
+}
+
+/// 同时设置账号颜色和头像 emoji（整体替换，传空清除）；当前账号改了时立即刷新托盘
+#[tauri::command]
+fn update_account_appearance(
+    state: State<AppState>,
+    app: tauri::AppHandle,
+    id: String,
+    color: Option<String>,
+    emoji: Option<String>,
+) -> Result<(), String> {
+    let is_current = {
+        let mut store = state.store.lock().map_err(|e| e.to_string())?;
+        store.update_account_appearance(&id, color.as_deref(), emoji.as_deref())?;
+        store.save()?;
+        store.current.as_deref() == Some(id.as_str())
+    };
+    if is_current {
+        crate::tray::update_tray_menu(&app);
+    }
+    Ok(())
+}
+
+/// 设置账号切换时写进 `~/.codex/config.toml` 的 profile（传空清除）
+#[tauri::command]
+fn set_account_codex_profile(
+    state: State<AppState>,
+    id: String,
+    profile: Option<String>,
+) -> Result<(), String> {
+    let mut store = state.store.lock().map_err(|e| e.to_string())?;
+    store.set_codex_profile(&id, profile.as_deref())?;
+    store.save()
+}
+
+/// 置顶 / 取消置顶账号
+#[tauri::command]
+fn pin_account(state: State<AppState>, id: String, pinned: bool) -> Result<(), String> {
+    let mut store = state.store.lock().map_err(|e| e.to_string())?;
+    store.pin_account(&id, pinned)?;
+    store.save()
+}
+
+/// 取消账号保护前先要一张一次性确认（[`UNPROTECT_TICKET_TTL_SECS`] 内有效）
+#[tauri::command]
+fn request_unprotect_ticket(state: State<AppState>, id: String) -> Result<String, String> {
+    {
+        let store = state.store.lock().map_err(|e| e.to_string())?;
+        if !store.accounts.contains_key(&id) {
+            return Err(format!("账号不存在: {}", id));
+        }
+    }
+    state.issue_unprotect_ticket(&id)
+}
+
+/// 保护 / 取消保护账号；取消时要带 `request_unprotect_ticket` 拿到的确认
+#[tauri::command]
+fn set_account_protected(
+    state: State<AppState>,
+    id: String,
+    protected: bool,
+    ticket: Option<String>,
+) -> Result<(), String> {
+    if !protected {
+        state.consume_unprotect_ticket(&id, ticket.as_deref().unwrap_or_default())?;
+    }
+    let mut store = state.store.lock().map_err(|e| e.to_string())?;
+    store.set_account_protected(&id, protected)?;
+    store.save()
+}
+
+/// 归档 / 取消归档账号（归档的号不能切、不参与自动选号和批量刷额度）
+#[tauri::command]
+fn archive_account(state: State<AppState>, id: String, archived: bool) -> Result<(), String> {
+    let mut store = state.store.lock().map_err(|e| e.to_string())?;
+    store.archive_account(&id, archived)?;
+    store.save()
+}
+
+/// 保存手动排序：`ids` 依次排在前面，没列出的按新建时间排在后面
+#[tauri::command]
+fn reorder_accounts(state: State<AppState>, ids: Vec<String>) -> Result<(), String> {
+    let mut store = state.store.lock().map_err(|e| e.to_string())?;
+    store.reorder_accounts(&ids)?;
+    store.save()
+}
+
+/// 设置账号的分组标签（整体替换，传空表清除）
+#[tauri::command]
+fn set_account_tags(state: State<AppState>, id: String, tags: Vec<String>) -> Result<(), String> {
+    let mut store = state.store.lock().map_err(|e| e.to_string())?;
+    store.set_account_tags(&id, tags)?;
+    store.save()
+}
+
+/// 设置账号的一条键值备注（键不区分大小写，已存在时覆盖）
+#[tauri::command]
+fn set_account_metadata(
+    state: State<AppState>,
+    id: String,
+    key: String,
+    value: String,
+) -> Result<(), String> {
+    let mut store = state.store.lock().map_err(|e| e.to_string())?;
+    store.set_account_metadata(&id, &key, &value)?;
+    store.save()
+}
+
+/// 删除账号的一条键值备注；没有这一条时什么也不做
+#[tauri::command]
+fn delete_account_metadata(state: State<AppState>, id: String, key: String) -> Result<(), String> {
+    let mut store = state.store.lock().map_err(|e| e.to_string())?;
+    if store.delete_account_metadata(&id, &key)? {
+        store.save()?;
+    }
+    Ok(())
+}
+
+/// 设置账号查询配额时附加的请求头（传空表清除）
+#[tauri::command]
+fn set_account_request_headers(
+    state: State<AppState>,
+    id: String,
+    headers: std::collections::BTreeMap<String, String>,
+) -> Result<(), String> {
+    let mut store = state.store.lock().map_err(|e| e.to_string())?;
+    store.set_request_headers(&id, headers)?;
+    store.save()
+}
+
 /// 设置 / 取消 手机锚账号（Codex.app 手机远程连接绑定）。
 ///
 /// 副作用：
@@ -602,19 +1546,19 @@ fn set_session_anchor(
                 .accounts
                 .get(&id)
                 .ok_or_else(|| format!("账号不存在: {}", id))?;
-            (Some(acc.to_codex_auth_value()), after, "set")
+            (Some((id.clone(), acc.to_codex_auth_value())), after, "set")
         } else {
             // 取消 anchor → 把当前 current 写盘（无 current 则跳过）
-            let candidate = store
-                .current
-                .clone()
-                .and_then(|cid| store.accounts.get(&cid).map(|a| a.to_codex_auth_value()));
+            let candidate = store.current.clone().and_then(|cid| {
+                let auth = store.accounts.get(&cid)?.to_codex_auth_value();
+                Some((cid, auth))
+            });
             (candidate, after, "clear")
         }
     };
 
-    if let Some(auth) = disk_auth {
-        AccountStore::write_codex_auth(&auth)?;
+    if let Some((written_id, auth)) = disk_auth {
+        AccountStore::write_codex_auth(&auth, AuthWriteOp::Anchor, Some(&written_id))?;
         println!(
             "[Anchor] {} 完成；当前 anchor = {:?}",
             action, anchor_after
@@ -631,10 +1575,194 @@ fn set_session_anchor(
 /// 导出所有账号配置
 #[tauri::command]
 fn export_accounts(state: State<AppState>) -> Result<String, String> {
-    let store = state.store.lock().map_err(|e| e.to_string())?;
+    let mut store = state.store.lock().map_err(|e| e.to_string())?;
+    // 启动时钥匙串锁着没读出来的，这里再试一次，导出必须带完整 token
+    store.hydrate_tokens_with(token_store::keychain());
     store.export()
 }
 
+/// 只导出选中的账号（当前账号清空）；`include_settings` 为 false 时不带设置，
+/// 导入方保留自己的设置。有不存在的 id 报错
+#[tauri::command]
+fn export_accounts_subset(
+    state: State<AppState>,
+    ids: Vec<String>,
+    include_settings: bool,
+) -> Result<String, String> {
+    let mut store = state.store.lock().map_err(|e| e.to_string())?;
+    store.hydrate_tokens_with(token_store::keychain());
+    store.export_subset(&ids, include_settings)
+}
+
+/// 脱敏导出：不带任何 token，贴进求助帖看账号布局和额度用；导入时会被拒绝
+#[tauri::command]
+fn export_accounts_redacted(state: State<AppState>) -> Result<String, String> {
+    let store = state.store.lock().map_err(|e| e.to_string())?;
+    store.export_redacted()
+}
+
+/// 带口令加密导出（信封格式见 `export_crypto`）；明文导出照旧保留
+#[tauri::command]
+fn export_accounts_encrypted(state: State<AppState>, passphrase: String) -> Result<String, String> {
+    let plaintext = export_accounts(state)?;
+    export_crypto::encrypt(&plaintext, &passphrase)
+}
+
+/// 粘贴的导出内容是明文、加密信封还是都不是；只读
+#[tauri::command]
+fn detect_export_format(text: String) -> export_crypto::ExportFormat {
+    export_crypto::detect_format(&text)
+}
+
+/// 识别一段粘贴的 auth.json 属于哪个已存账号。只读：不落盘、不写 auth.json。
+/// 解析失败时返回逐字段错误（`{ errors: [{ field, message }] }`）
+#[tauri::command]
+fn identify_auth_json(
+    state: State<AppState>,
+    json_text: String,
+) -> Result<auth_identify::IdentifyResult, auth_identify::IdentifyError> {
+    let store = state
+        .store
+        .lock()
+        .map_err(|e| auth_identify::IdentifyError {
+            errors: vec![auth_identify::FieldError::new("store", e.to_string())],
+        })?;
+    auth_identify::identify(&store, &json_text)
+}
+
+/// token 存储方式 + 读取失败的账号 + 最近一次凭据库错误。
+/// 顺带重试一次没读出来的账号（用户可能刚解锁了钥匙串）。
+#[tauri::command]
+fn get_token_storage_status(
+    state: State<AppState>,
+) -> Result<token_store::TokenStorageStatus, String> {
+    let mut store = state.store.lock().map_err(|e| e.to_string())?;
+    if !store.unresolved_token_accounts().is_empty()
+        && store
+            .hydrate_tokens_with(token_store::keychain())
+            .is_empty()
+    {
+        token_store::clear_error();
+    }
+    Ok(token_store::TokenStorageStatus {
+        storage: store.settings.token_storage,
+        include_access_tokens: store.settings.keychain_include_access_tokens,
+        unresolved_accounts: store.unresolved_token_accounts(),
+        last_error: token_store::last_error(),
+    })
+}
+
+/// 把 token 迁到另一种存储（accounts.json ⇄ 系统凭据库）
+#[tauri::command]
+fn migrate_token_storage(
+    state: State<AppState>,
+    target: token_store::TokenStorage,
+    include_access_tokens: Option<bool>,
+) -> Result<account::TokenMigration, String> {
+    let mut store = state.store.lock().map_err(|e| e.to_string())?;
+    let include_access =
+        include_access_tokens.unwrap_or(store.settings.keychain_include_access_tokens);
+    store.migrate_token_storage_with(target, include_access, token_store::keychain())
+}
+
+/// refresh_token 闲置超过阈值的账号（见 `token_aging`）
+#[tauri::command]
+fn get_aging_tokens(state: State<AppState>) -> Result<Vec<token_aging::AgingToken>, String> {
+    let store = state.store.lock().map_err(|e| e.to_string())?;
+    Ok(token_aging::collect(&store, clock::now()))
+}
+
+/// 手动保活：用该账号的 refresh_token 刷新一次，重置闲置天数
+#[tauri::command]
+async fn keepalive_refresh_account(
+    state: tauri::State<'_, AppState>,
+    app: tauri::AppHandle,
+    id: String,
+) -> Result<(), String> {
+    token_aging::keepalive(&state.store, &state.refresh_locks, &id).await?;
+    let _ = app.emit("accounts-updated", ());
+    Ok(())
+}
+
+/// 把账号的 auth.json 暂存到单独的 CODEX_HOME，用于不打断当前会话的试运行（见 `staging`）
+#[tauri::command]
+fn stage_account(
+    state: State<AppState>,
+    id: String,
+    staging_dir: Option<std::path::PathBuf>,
+) -> Result<staging::StageResult, String> {
+    let store = state.store.lock().map_err(|e| e.to_string())?;
+    let account = store
+        .accounts
+        .get(&id)
+        .ok_or_else(|| format!("账号 {} 不存在", id))?;
+    staging::Staging::system().stage(account, staging_dir)
+}
+
+/// 清理暂存副本：传 id 只清该账号，不传清全部；返回清掉的份数
+#[tauri::command]
+fn cleanup_staging(id: Option<String>) -> Result<usize, String> {
+    staging::Staging::system().cleanup(id.as_deref())
+}
+
+/// 全部 OAuth 配置：内置 `openai-default` 在最前，其后是自定义的
+#[tauri::command]
+fn list_auth_profiles(state: State<AppState>) -> Result<Vec<oauth::AuthProfile>, String> {
+    let store = state.store.lock().map_err(|e| e.to_string())?;
+    let mut profiles = vec![oauth::AuthProfile::builtin()];
+    profiles.extend(store.settings.auth_profiles.iter().cloned());
+    Ok(profiles)
+}
+
+/// 新增或更新一个自定义 OAuth 配置（同 id 覆盖）
+#[tauri::command]
+fn add_auth_profile(
+    state: State<AppState>,
+    mut profile: oauth::AuthProfile,
+) -> Result<oauth::AuthProfile, String> {
+    profile.id = profile.id.trim().to_string();
+    profile.issuer = profile.issuer.trim().to_string();
+    profile.client_id = profile.client_id.trim().to_string();
+    oauth::validate_profile(&profile)?;
+
+    let mut store = state.store.lock().map_err(|e| e.to_string())?;
+    let profiles = &mut store.settings.auth_profiles;
+    match profiles.iter_mut().find(|p| p.id == profile.id) {
+        Some(existing) => *existing = profile.clone(),
+        None => profiles.push(profile.clone()),
+    }
+    store.save()?;
+    Ok(profile)
+}
+
+/// 删除自定义 OAuth 配置；还有账号在用时拒绝，否则这些账号之后刷新会落到错误的 issuer
+#[tauri::command]
+fn remove_auth_profile(state: State<AppState>, id: String) -> Result<(), String> {
+    if id == oauth::DEFAULT_PROFILE_ID {
+        return Err("内置 OAuth 配置不能删除".to_string());
+    }
+    let mut store = state.store.lock().map_err(|e| e.to_string())?;
+    let users: Vec<String> = store
+        .accounts
+        .values()
+        .filter(|a| a.auth_profile_id.as_deref() == Some(id.as_str()))
+        .map(|a| a.name.clone())
+        .collect();
+    if !users.is_empty() {
+        return Err(format!(
+            "以下账号仍在使用 OAuth 配置 {}: {}",
+            id,
+            users.join(", ")
+        ));
+    }
+    let before = store.settings.auth_profiles.len();
+    store.settings.auth_profiles.retain(|p| p.id != id);
+    if store.settings.auth_profiles.len() == before {
+        return Err(format!("OAuth 配置 {} 不存在", id));
+    }
+    store.save()
+}
+
 /// 批量导入：自动嗅探 cpa / sub2api / cockpit / 四段RT / native 这 5 种格式。
 /// 前端把每个文件读成 base64（binary 安全）传过来，filename 用来辅助嗅探（zip 后缀等）。
 /// 返回每文件的 summary + 总账号详情，UI 用来给用户预览导入结果。
@@ -733,7 +1861,11 @@ fn bulk_import_accounts(
                 };
                 match remote_client::upsert_account(&base, &secret, &account_clone).await {
                     Ok(_) => pushed += 1,
-                    Err(e) => eprintln!("[BulkImport] push {} 失败: {}", account_clone.name, e),
+                    Err(e) => eprintln!(
+                        "[BulkImport] push {} 失败: {}",
+                        log_identity::id(&account_clone.name),
+                        e
+                    ),
                 }
             }
             if pushed > 0 {
@@ -935,23 +2067,90 @@ async fn refresh_relay_usage(
         None => return Err("usage 策略未确定".to_string()),
     };
 
-    {
+    {
+        let mut store = state.store.lock().map_err(|e| e.to_string())?;
+        if let Some(acc) = store.accounts.get_mut(&id) {
+            acc.relay_usage_cache = Some(cache.clone());
+            store.save()?;
+        }
+    }
+    Ok(cache)
+}
+
+/// 导入账号库导出。`mode` 默认 `replace`：整个账号库换成导入文件的内容（先留一份带时间戳的备份），
+/// 返回撤销凭据；`merge`：并入现有账号库，返回逐账号报告（见 `import_merge`）
+#[tauri::command]
+fn import_accounts(
+    state: State<AppState>,
+    app: tauri::AppHandle,
+    json: String,
+    mode: Option<import_merge::ImportMode>,
+) -> Result<import_merge::ImportResult, String> {
+    import_from_export(&state, &app, &json, mode.unwrap_or_default())
+}
+
+/// 解开 `export_accounts_encrypted` 的信封后导入，`mode` 同 `import_accounts`；
+/// 口令错误报解密失败，不动账号库
+#[tauri::command]
+fn import_accounts_encrypted(
+    state: State<AppState>,
+    app: tauri::AppHandle,
+    payload: String,
+    passphrase: String,
+    mode: Option<import_merge::ImportMode>,
+) -> Result<import_merge::ImportResult, String> {
+    let json = export_crypto::decrypt(&payload, &passphrase)?;
+    import_from_export(&state, &app, &json, mode.unwrap_or_default())
+}
+
+fn import_from_export(
+    state: &AppState,
+    app: &tauri::AppHandle,
+    json: &str,
+    mode: import_merge::ImportMode,
+) -> Result<import_merge::ImportResult, String> {
+    if mode == import_merge::ImportMode::Merge {
+        return merge_store_from_export(state, app, json);
+    }
+    replace_store_from_export(state, app, json)
+}
+
+/// 合并式导入：缺 refresh_token 的账号逐个跳过，不拒绝整份文件；设置和 current 用本机的
+fn merge_store_from_export(
+    state: &AppState,
+    app: &tauri::AppHandle,
+    json: &str,
+) -> Result<import_merge::ImportResult, String> {
+    let incoming = AccountStore::import(json)?;
+    let (report, summary) = {
         let mut store = state.store.lock().map_err(|e| e.to_string())?;
-        if let Some(acc) = store.accounts.get_mut(&id) {
-            acc.relay_usage_cache = Some(cache.clone());
+        let before = store.accounts.len();
+        let report = import_merge::merge(&mut store, &incoming);
+        if report.changed() {
             store.save()?;
         }
+        let summary = import_merge::ImportSummary::from_merge(&report, before);
+        (report, summary)
+    };
+    if report.changed() {
+        crate::tray::update_tray_menu(app);
     }
-    Ok(cache)
+    Ok(import_merge::ImportResult {
+        mode: import_merge::ImportMode::Merge,
+        summary,
+        undo: None,
+        backup_path: None,
+        merge: Some(report),
+    })
 }
 
-#[tauri::command]
-fn import_accounts(
-    state: State<AppState>,
-    app: tauri::AppHandle,
-    json: String,
-) -> Result<(), String> {
-    let new_store = AccountStore::import(&json)?;
+fn replace_store_from_export(
+    state: &AppState,
+    app: &tauri::AppHandle,
+    json: &str,
+) -> Result<import_merge::ImportResult, String> {
+    let mut new_store = AccountStore::import(json)?;
+    let keep_settings = !AccountStore::export_has_settings(json);
     let missing = new_store.accounts_missing_refresh_token();
     if !missing.is_empty() {
         return Err(format!(
@@ -959,21 +2158,192 @@ fn import_accounts(
             missing.join(", ")
         ));
     }
-    {
+    let (receipt, backup, summary) = {
         let mut store = state.store.lock().map_err(|e| e.to_string())?;
+        let dir = store_backups::backups_dir();
+        let kind = store_backups::BackupKind::Import;
+        let backup = store_backups::write(&dir, kind, &store, Utc::now())
+            .map_err(|e| format!("导入前备份账号库失败，未导入: {}", e))?;
+        store_backups::prune_keep_latest(&dir, kind, store.settings.store_backup_keep);
+        println!("[Import] 替换前已备份账号库到 {}", backup.path);
+        // 不带设置的部分导出：整份设置用本机的
+        if keep_settings {
+            new_store.settings = store.settings.clone();
+        }
+        // token 存储方式、Codex 目录是本机属性，不随导入文件走
+        new_store.settings.token_storage = store.settings.token_storage;
+        new_store.settings.keychain_include_access_tokens =
+            store.settings.keychain_include_access_tokens;
+        new_store.settings.codex_home = store.settings.codex_home.clone();
+        new_store.settings.codex_installs = store.settings.codex_installs.clone();
+        new_store.install_current = store.install_current.clone();
+        codex_installs::prune(&mut new_store);
+        let stash = undo::UndoStash::before_replace(&store, &new_store);
+        let summary = import_merge::ImportSummary::between(&store, &new_store);
         *store = new_store;
         store.save()?;
+        (state.undo.push(stash.finish(&store)), backup, summary)
+    };
+    crate::tray::update_tray_menu(app);
+    Ok(import_merge::ImportResult {
+        mode: import_merge::ImportMode::Replace,
+        summary,
+        undo: Some(receipt),
+        backup_path: Some(backup.path),
+        merge: None,
+    })
+}
+
+/// 账号库备份（替换式导入前的和每天自动的），从新到旧；只读
+#[tauri::command]
+fn list_store_backups() -> Vec<store_backups::StoreBackup> {
+    store_backups::list(&store_backups::backups_dir())
+}
+
+/// 从 `list_store_backups` 里的一份备份恢复；`mode` 同 `import_accounts`（默认整库替换，
+/// 替换前照样先备份当前库）。`file_name` 只能是备份目录里的备份文件名
+#[tauri::command]
+fn restore_store_backup(
+    state: State<AppState>,
+    app: tauri::AppHandle,
+    file_name: String,
+    mode: Option<import_merge::ImportMode>,
+) -> Result<import_merge::ImportResult, String> {
+    let path = store_backups::resolve(&store_backups::backups_dir(), &file_name)?;
+    let json = store_backups::read(&path).map_err(|e| format!("{}: {}", file_name, e))?;
+    let result = import_from_export(&state, &app, &json, mode.unwrap_or_default())?;
+    println!(
+        "[Restore] 已从备份 {} 恢复：新增 {}，更新 {}，移除 {}",
+        file_name, result.summary.added, result.summary.updated, result.summary.removed
+    );
+    Ok(result)
+}
+
+/// 从文件夹导入 auth.json 备份：同一身份的已存账号用更新的 token 覆盖，其余新增。
+/// 返回逐文件报告，见 `auth_dir_import`
+#[tauri::command]
+fn import_auth_directory(
+    state: State<AppState>,
+    app: tauri::AppHandle,
+    path: String,
+) -> Result<auth_dir_import::DirImportReport, String> {
+    let report = {
+        let mut store = state.store.lock().map_err(|e| e.to_string())?;
+        let report = auth_dir_import::import_directory(&mut store, std::path::Path::new(&path))?;
+        if report.changed() {
+            store.save()?;
+        }
+        report
+    };
+    if report.changed() {
+        crate::tray::update_tray_menu(&app);
     }
+    Ok(report)
+}
+
+/// 扫描其它工具目录里的登录凭据，和账号库比对后列出来（只读，见 `external_logins`）
+#[tauri::command]
+fn discover_external_logins(
+    state: State<AppState>,
+) -> Result<external_logins::DiscoveryReport, String> {
+    let store = state.store.lock().map_err(|e| e.to_string())?;
+    external_logins::discover(&store, &paths::home_dir(), external_logins::KNOWN_SOURCES)
+}
+
+/// 采用扫描出来的某一份凭据：同身份的已存账号覆盖，否则新增。来源文件不动
+#[tauri::command]
+fn adopt_discovered_login(
+    state: State<AppState>,
+    app: tauri::AppHandle,
+    path: String,
+) -> Result<auth_dir_import::FileOutcome, String> {
+    let outcome = {
+        let mut store = state.store.lock().map_err(|e| e.to_string())?;
+        let outcome = external_logins::adopt(
+            &mut store,
+            &paths::home_dir(),
+            external_logins::KNOWN_SOURCES,
+            &path,
+        )?;
+        if matches!(
+            outcome,
+            auth_dir_import::FileOutcome::Imported { .. }
+                | auth_dir_import::FileOutcome::Updated { .. }
+        ) {
+            store.save()?;
+        }
+        outcome
+    };
     crate::tray::update_tray_menu(&app);
-    Ok(())
+    Ok(outcome)
+}
+
+/// 分批导入（合并进现有账号库），立即返回任务 id。
+/// 每批结束后落盘并推 `import-progress`，结束时推 `import-complete`（带完整报告）。
+/// 取消后已并入的账号保留，详见 `import_job` 模块说明。
+#[tauri::command]
+fn import_accounts_async(
+    state: State<AppState>,
+    app: tauri::AppHandle,
+    json: String,
+    options: Option<import_job::ImportOptions>,
+) -> Result<String, String> {
+    let job_id = uuid::Uuid::new_v4().to_string();
+    let mut job = import_job::ImportJob::new(job_id.clone(), &json, options.unwrap_or_default())?;
+    state.import_jobs.try_start(&job)?;
+
+    let store = state.store.clone();
+    let jobs = state.import_jobs.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        loop {
+            let progress = {
+                let Ok(mut store) = store.lock() else { break };
+                let Some(progress) = job.run_batch(&mut store) else {
+                    break;
+                };
+                if let Err(e) = store.save() {
+                    eprintln!("[Import] 保存失败: {}", e);
+                }
+                progress
+            };
+            let _ = app.emit("import-progress", &progress);
+        }
+        jobs.finish(job.id());
+        let report = job.report();
+        println!(
+            "[Import] 任务 {} 结束: 新增 {} 覆盖 {} 跳过 {} 失败 {}{}",
+            report.job_id,
+            report.added,
+            report.updated,
+            report.skipped,
+            report.errors.len(),
+            if report.cancelled {
+                "（已取消）"
+            } else {
+                ""
+            }
+        );
+        let _ = app.emit("import-complete", &report);
+        let _ = app.emit("accounts-updated", ());
+        crate::tray::update_tray_menu(&app);
+    });
+    Ok(job_id)
+}
+
+/// 取消分批导入；当前批处理完后停下
+#[tauri::command]
+fn cancel_import(state: State<AppState>, job_id: String) -> Result<(), String> {
+    state.import_jobs.cancel(&job_id)
 }
 
 /// 把已经拿到的 OAuth Token 落进账号库 + 推 Server + 刷托盘。
-/// 浏览器登录和 OTP 自动登录都走这一条路。
+/// 浏览器登录和 OTP 自动登录都走这一条路。新建账号的名称缺省用 id_token 里的邮箱
 async fn save_token_as_account(
     state: &tauri::State<'_, AppState>,
     app: &tauri::AppHandle,
     token_res: oauth::TokenResponse,
+    profile: &oauth::AuthProfile,
+    name: Option<String>,
     notes: Option<String>,
 ) -> Result<Account, String> {
     if token_res.refresh_token.is_none() {
@@ -986,7 +2356,7 @@ async fn save_token_as_account(
         .and_then(|id_t| oauth::parse_user_info(id_t))
         .ok_or("无法从授权响应中解析用户信息 (Missing ID Token)")?;
 
-    let (account, is_client_mode) = {
+    let account = {
         let mut store = state.store.lock().map_err(|e| e.to_string())?;
 
         let expires_at = token_res
@@ -998,24 +2368,69 @@ async fn save_token_as_account(
                 "access_token": token_res.access_token,
                 "refresh_token": token_res.refresh_token,
                 "id_token": token_res.id_token,
-                "account_id": user_info.account_id,
+                // 必须是工作区 id（chatgpt_account_id），不能用 sub：usage 请求的
+                // ChatGPT-Account-Id 头靠它区分个人 / Team 额度
+                "account_id": user_info.chatgpt_account_id,
                 "expires_at": expires_at
             },
             "last_refresh": chrono::Utc::now().to_rfc3339()
         });
 
-        let mut account = store.add_account(user_info.email, auth_json, notes);
+        // 内置 profile 不落字段，和老账号一致
+        let profile_id = Some(profile.id.clone()).filter(|id| id != oauth::DEFAULT_PROFILE_ID);
 
-        account.refresh_token = token_res.refresh_token.clone();
-        if let Some(acc) = store.accounts.get_mut(&account.id) {
-            acc.refresh_token = token_res.refresh_token;
-        }
+        // 挂着刷新禁令的账号重新登录：换上新 token 并解除禁令，不另建账号
+        let account = if let Some(id) = store.find_refresh_banned_account(&auth_json) {
+            store.complete_relogin(&id, auth_json, token_res.refresh_token)?;
+            let acc = store
+                .accounts
+                .get_mut(&id)
+                .ok_or_else(|| format!("账号不存在: {}", id))?;
+            acc.auth_profile_id = profile_id;
+            println!(
+                "[Login] {} 重新登录成功，已解除刷新禁令",
+                log_identity::id(&acc.name)
+            );
+            acc.clone()
+        } else {
+            let values = notes_template::TemplateValues::from_auth(
+                &auth_json,
+                chrono::Local::now().date_naive(),
+            );
+            let notes = notes_template::render(
+                &store.settings.new_account_notes_template,
+                &values,
+                notes.as_deref(),
+            )?;
+            let name = name.unwrap_or(user_info.email);
+            let mut account = store.add_account(name, auth_json, notes);
+            account.refresh_token = token_res.refresh_token.clone();
+            account.auth_profile_id = profile_id.clone();
+            if let Some(acc) = store.accounts.get_mut(&account.id) {
+                acc.refresh_token = token_res.refresh_token;
+                acc.auth_profile_id = profile_id;
+            }
+            account
+        };
 
         store.save()?;
-        let should_push = account::pushes_to_server(&store.settings.remote_mode);
-        (account, should_push)
+        account
     };
 
+    finish_saved_login(state, app, account).await
+}
+
+/// 登录落库之后的共同收尾（新建、重新登录、就地换 token 都走这里）：Client 模式推给
+/// Server（Server 合并成另一个 id 时本地跟着改 id）、通知前端、刷托盘。返回最终的账号
+async fn finish_saved_login(
+    state: &tauri::State<'_, AppState>,
+    app: &tauri::AppHandle,
+    mut account: Account,
+) -> Result<Account, String> {
+    let is_client_mode = {
+        let store = state.store.lock().map_err(|e| e.to_string())?;
+        account::pushes_to_server(&store.settings.remote_mode)
+    };
     if is_client_mode {
         let (url, secret) = client_settings_snapshot(state).await?;
         let to_push = {
@@ -1030,25 +2445,26 @@ async fn save_token_as_account(
                         if let Ok(mut store) = state.store.lock() {
                             if let Some(mut a) = store.accounts.remove(&account.id) {
                                 a.id = new_id.clone();
-                                store.accounts.insert(new_id.clone(), a);
+                                store.accounts.insert(new_id.clone(), a.clone());
                                 if store.current.as_deref() == Some(account.id.as_str()) {
                                     store.current = Some(new_id.clone());
                                 }
                                 let _ = store.save();
+                                account = a;
                             }
                         }
-                        let _ = app.emit("accounts-updated", ());
                     }
                     println!(
-                        "[Login] 已推送新账号到 Server：id={} action={} quota_refreshed={}",
+                        "[Login] 已推送账号到 Server：id={} action={} quota_refreshed={}",
                         outcome.id, outcome.upserted, outcome.quota_refreshed
                     );
                 }
-                Err(e) => eprintln!("[Login] 推送新账号到 Server 失败: {}", e),
+                Err(e) => eprintln!("[Login] 推送账号到 Server 失败: {}", e),
             }
         }
     }
 
+    let _ = app.emit("accounts-updated", ());
     crate::tray::update_tray_menu(app);
     Ok(account)
 }
@@ -1060,7 +2476,7 @@ async fn save_token_as_account(
 async fn force_overwrite_disk_with_current(
     state: tauri::State<'_, AppState>,
 ) -> Result<String, String> {
-    let auth_json = {
+    let (current_id, auth_json) = {
         let store = state.store.lock().map_err(|e| e.to_string())?;
         let current_id = store
             .current
@@ -1070,9 +2486,10 @@ async fn force_overwrite_disk_with_current(
             .accounts
             .get(&current_id)
             .ok_or_else(|| format!("账号 {} 不存在", current_id))?;
-        account.auth_json.clone()
+        let auth_json = account.auth_json.clone();
+        (current_id, auth_json)
     };
-    AccountStore::write_codex_auth(&auth_json)?;
+    AccountStore::write_codex_auth(&auth_json, AuthWriteOp::Resolve, Some(&current_id))?;
     Ok("已覆盖 ~/.codex/auth.json".to_string())
 }
 
@@ -1083,16 +2500,238 @@ async fn finalize_oauth_login(
     app: tauri::AppHandle,
     code: String,
 ) -> Result<Account, String> {
-    let token_res = oauth_server::complete_oauth_login(code).await?;
+    let (token_res, profile) = oauth_server::complete_oauth_login(code).await?;
     save_token_as_account(
         &state,
         &app,
         token_res,
+        &profile,
+        None,
         Some("OpenAI OAuth 登录".to_string()),
     )
     .await
 }
 
+/// 只凭一个 refresh_token 建账号（老登录只留下了 rt，或导出时被脱敏到只剩 rt）：
+/// 先到 token 端点换一套新 token，成功后和浏览器登录一样落库。换不到（invalid_grant 等）
+/// 直接报错，不会留下缺 token 的半成品账号。`name` 留空用邮箱。
+/// 换 token 会轮换掉 rt：rt 已经存在某个账号上时，持该账号的刷新锁就地刷新它；
+/// 换回来的身份是已存账号时，同样持锁就地换上新 token，都不另建账号
+#[tauri::command]
+async fn add_account_from_refresh_token(
+    state: tauri::State<'_, AppState>,
+    app: tauri::AppHandle,
+    name: String,
+    refresh_token: String,
+    profile_id: Option<String>,
+) -> Result<Account, String> {
+    let refresh_token = refresh_token.trim().to_string();
+    if refresh_token.is_empty() {
+        return Err("refresh_token 为空".to_string());
+    }
+    let existing = {
+        let store = state.store.lock().map_err(|e| e.to_string())?;
+        store.find_by_refresh_token(&refresh_token)
+    };
+    if let Some(id) = existing {
+        token_aging::keepalive(&state.store, &state.refresh_locks, &id).await?;
+        let account = {
+            let store = state.store.lock().map_err(|e| e.to_string())?;
+            store
+                .accounts
+                .get(&id)
+                .cloned()
+                .ok_or_else(|| format!("账号不存在: {}", id))?
+        };
+        println!(
+            "[Login] refresh_token 属于已存账号 {}，已就地刷新",
+            log_identity::id(&account.name)
+        );
+        return finish_saved_login(&state, &app, account).await;
+    }
+
+    let (_, profile) =
+        oauth_server::login_settings(&state, profile_id.as_deref()).map_err(|e| e.to_string())?;
+    let mut token_res = oauth::refresh_access_token(&profile, &refresh_token)
+        .await
+        .map_err(|e| format!("用 refresh_token 换取 token 失败，未创建账号: {}", e))?;
+    // 端点没轮换 rt 时沿用原来的
+    token_res.refresh_token.get_or_insert(refresh_token);
+    if let Some(account) = refresh_same_identity_in_place(&state, &token_res).await? {
+        return finish_saved_login(&state, &app, account).await;
+    }
+    let name = Some(name.trim().to_string()).filter(|n| !n.is_empty());
+    save_token_as_account(
+        &state,
+        &app,
+        token_res,
+        &profile,
+        name,
+        Some("由 refresh_token 导入".to_string()),
+    )
+    .await
+}
+
+/// 换回来的 token 属于已存账号（它存的是另一个 rt）时，持它的刷新锁换上新 token 并返回它。
+/// 挂着刷新禁令的交给 `save_token_as_account` 走重新登录
+async fn refresh_same_identity_in_place(
+    state: &tauri::State<'_, AppState>,
+    token_res: &oauth::TokenResponse,
+) -> Result<Option<Account>, String> {
+    let Some(id_token) = token_res.id_token.as_deref() else {
+        return Ok(None);
+    };
+    let Some(user_info) = oauth::parse_user_info(id_token) else {
+        return Ok(None);
+    };
+    let id = {
+        let store = state.store.lock().map_err(|e| e.to_string())?;
+        match store.find_login_owner(id_token, user_info.chatgpt_account_id.as_deref()) {
+            Some(id) => id,
+            None => return Ok(None),
+        }
+    };
+    if !state
+        .refresh_locks
+        .acquire(
+            &id,
+            tokio::time::Duration::from_secs(token_aging::LOCK_WAIT_SECS),
+        )
+        .await
+    {
+        return Err("该账号正在被其他流程刷新，请稍后重试".to_string());
+    }
+    let result = apply_login_tokens_locked(state, &id, token_res);
+    state.refresh_locks.release(&id).await;
+    result.map(Some)
+}
+
+fn apply_login_tokens_locked(
+    state: &AppState,
+    id: &str,
+    token_res: &oauth::TokenResponse,
+) -> Result<Account, String> {
+    let mut store = state.store.lock().map_err(|e| e.to_string())?;
+    let account = store
+        .accounts
+        .get_mut(id)
+        .ok_or_else(|| format!("账号不存在: {}", id))?;
+    AccountStore::apply_refreshed_tokens(
+        account,
+        token_res.access_token.clone(),
+        token_res.refresh_token.clone(),
+        token_res.id_token.clone(),
+        token_res.expires_in,
+    );
+    let account = account.clone();
+    store.save()?;
+    println!(
+        "[Login] refresh_token 换回的是已存账号 {}，已就地换上新 token",
+        log_identity::id(&account.name)
+    );
+    Ok(account)
+}
+
+// ============================================================================
+// 批量 OAuth 登录
+// ============================================================================
+
+/// `bulk_login::LoginDriver` 的真实实现：浏览器授权 → 换 token → `save_token_as_account`
+struct OAuthLoginDriver {
+    app: tauri::AppHandle,
+    ports: Vec<u16>,
+    profile: oauth::AuthProfile,
+}
+
+impl bulk_login::LoginDriver for OAuthLoginDriver {
+    fn login<'a>(
+        &'a self,
+        hint: Option<&'a str>,
+    ) -> futures_util::future::BoxFuture<'a, Result<String, String>> {
+        Box::pin(async move {
+            let code = oauth_server::authorize_and_wait(
+                &self.app,
+                &self.ports,
+                self.profile.clone(),
+                hint,
+            )
+            .await?;
+            let (token_res, profile) = oauth_server::complete_oauth_login(code).await?;
+            let state = self.app.state::<AppState>();
+            let account = save_token_as_account(
+                &state,
+                &self.app,
+                token_res,
+                &profile,
+                None,
+                Some("批量 OAuth 登录".to_string()),
+            )
+            .await?;
+            let _ = self.app.emit("accounts-updated", ());
+            Ok(account.name)
+        })
+    }
+}
+
+/// 批量 OAuth 登录：`entries` 每项一条，`Some(邮箱)` 作为 login_hint 预填授权页。
+/// 串行跑完整个队列后返回汇总；进度见 `bulk_login` 模块文档
+#[tauri::command]
+async fn start_bulk_login(
+    state: tauri::State<'_, AppState>,
+    app: tauri::AppHandle,
+    entries: Vec<Option<String>>,
+    step_timeout_secs: Option<u64>,
+    profile_id: Option<String>,
+) -> Result<bulk_login::BulkLoginSummary, String> {
+    let (ports, profile) =
+        oauth_server::login_settings(&state, profile_id.as_deref()).map_err(|e| e.to_string())?;
+    let driver = OAuthLoginDriver {
+        app: app.clone(),
+        ports,
+        profile,
+    };
+    let events = TauriEventSink {
+        app,
+        bus: state.events.clone(),
+    };
+    let entries = entries
+        .into_iter()
+        .map(|e| e.map(|s| s.trim().to_string()).filter(|s| !s.is_empty()))
+        .collect();
+    let step_timeout = step_timeout_secs
+        .map(std::time::Duration::from_secs)
+        .unwrap_or(bulk_login::DEFAULT_STEP_TIMEOUT);
+    bulk_login::global()
+        .run(&driver, &events, entries, step_timeout)
+        .await
+}
+
+#[tauri::command]
+fn pause_bulk_login() -> Result<(), String> {
+    bulk_login::global().pause()
+}
+
+#[tauri::command]
+fn resume_bulk_login() -> Result<(), String> {
+    bulk_login::global().resume()
+}
+
+#[tauri::command]
+fn cancel_bulk_login() -> Result<(), String> {
+    bulk_login::global().cancel()
+}
+
+/// 当前批量登录进度；没有进行中的会话返回 None
+#[tauri::command]
+fn get_bulk_login_status(state: State<AppState>) -> Option<bulk_login::BulkLoginProgress> {
+    let mut progress = bulk_login::global().status()?;
+    if state.presentation.is_enabled() {
+        progress.last_account = progress.last_account.map(|n| presentation::mask_name(&n));
+        progress.next_hint = progress.next_hint.map(|h| presentation::mask_email(&h));
+    }
+    Some(progress)
+}
+
 // ============================================================================
 // 邮箱 OTP 批量自动授权
 // ============================================================================
@@ -1269,6 +2908,8 @@ async fn start_otp_login_batch(
                     &state,
                     &app,
                     out.token,
+                    &oauth::AuthProfile::builtin(),
+                    None,
                     Some("邮箱 OTP 自动授权".to_string()),
                 )
                 .await
@@ -1327,217 +2968,384 @@ async fn start_otp_login_batch(
         }
     }
 
-    Ok(OtpBatchResult { success, failed })
+    Ok(OtpBatchResult { success, failed })
+}
+
+// 补充 AppState 的辅助方法以方便在 finalize_oauth_login 中获取 AppHandle 是不行的，
+// 因为 finalize_oauth_login 是 async 且 Command 宏会处理。
+// 我们直接给 finalize_oauth_login 增加 AppHandle 参数。
+
+/// 切换到指定账号（异步版本，不做本地 Token 续期）。
+/// `install` 为登记的其它 Codex 安装名，缺省切默认安装（见 `codex_installs`）
+#[tauri::command]
+async fn switch_account(
+    state: tauri::State<'_, AppState>,
+    app: tauri::AppHandle,
+    id: Option<String>,
+    selector: Option<String>,
+    install: Option<String>,
+) -> Result<SwitchResult, String> {
+    switch_account_v2(state, app, id, selector, install)
+        .await
+        .map(|warned| warned.value)
+}
+
+/// 同 `switch_account`，另带沿途没挡住切号的警告（预检失败、拒绝回流……）
+#[tauri::command]
+async fn switch_account_v2(
+    state: tauri::State<'_, AppState>,
+    app: tauri::AppHandle,
+    id: Option<String>,
+    selector: Option<String>,
+    install: Option<String>,
+) -> Result<warnings::Warned<SwitchResult>, String> {
+    let id = switch_target(&state.store, id, selector)?;
+    if let Some(install) = install.filter(|name| !codex_installs::is_default(Some(name))) {
+        // 其它安装只换 auth.json：不预检、不热切、不跑钩子。
+        // 持目标账号的刷新锁写，后台刷新不会在写盘中途换掉它的 token
+        if !state
+            .refresh_locks
+            .acquire(
+                &id,
+                tokio::time::Duration::from_secs(token_aging::LOCK_WAIT_SECS),
+            )
+            .await
+        {
+            return Err("该账号正在被其他流程刷新，请稍后重试".to_string());
+        }
+        let switched = state
+            .store
+            .lock()
+            .map_err(|e| e.to_string())
+            .and_then(|mut store| {
+                let warnings = codex_installs::switch(&mut store, install.trim(), &id)?;
+                store.save()?;
+                Ok(warnings)
+            });
+        state.refresh_locks.release(&id).await;
+        let install_warnings = switched?;
+        let _ = app.emit("accounts-updated", ());
+        crate::tray::update_tray_menu(&app);
+        return Ok(warnings::Warned::new(
+            SwitchResult {
+                hooks: Vec::new(),
+                compatibility_warning: None,
+            },
+            install_warnings,
+        ));
+    }
+    let presentation = state.presentation.is_enabled();
+    let result = perform_switch(state, &app, &id).await?;
+    cancel_revert(&app, "手动切号");
+    Ok(result.masked_if(presentation))
+}
+
+/// 正常切号流程：切换 → 重载 → 钩子；手动切号和临时切号到点切回共用
+async fn perform_switch(
+    state: tauri::State<'_, AppState>,
+    app: &tauri::AppHandle,
+    id: &str,
+) -> Result<warnings::Warned<SwitchResult>, String> {
+    let store = state.store.clone();
+    let warnings::Warned {
+        value: mut progress,
+        mut warnings,
+    } = state
+        .account_service(app)
+        .switch_tracked(id, proxy_running(&state))
+        .await?;
+    progress.stage(switch_progress::SwitchStage::Reloading);
+    match finish_switch(state, app, id).await {
+        Ok(solo) => {
+            warnings.extend(solo);
+            progress.done_with(&warnings);
+        }
+        Err(e) => {
+            progress.fail(&e);
+            return Err(e);
+        }
+    }
+    let result = SwitchResult {
+        hooks: run_post_switch_hooks(&store, id).await,
+        compatibility_warning: switch_compatibility_warning(&store, id).await,
+    };
+    Ok(warnings::Warned::new(result, warnings))
+}
+
+/// 限时临时切号：照常切过去，`minutes` 分钟后自动切回当前账号（见 `temp_switch`）
+#[tauri::command]
+async fn switch_account_temporarily(
+    state: tauri::State<'_, AppState>,
+    app: tauri::AppHandle,
+    id: String,
+    minutes: u32,
+) -> Result<SwitchResult, String> {
+    let previous = state
+        .store
+        .lock()
+        .map_err(|e| e.to_string())?
+        .current
+        .clone();
+    // 先校验，不合法就别切
+    temp_switch::schedule(previous.as_deref(), &id, minutes, Utc::now())?;
+    let result = perform_switch(state, &app, &id).await?.value;
+    let pending = temp_switch::schedule(previous.as_deref(), &id, minutes, Utc::now())?;
+    println!(
+        "[TempSwitch] 临时切到 {}，{} 自动切回 {}",
+        pending.temporary_id, pending.revert_at, pending.revert_to
+    );
+    app.state::<AppState>().pending_revert.set(pending);
+    pending_revert_changed(&app);
+    let _ = app.emit("accounts-updated", ());
+    Ok(result)
+}
+
+/// 待切回 + 两端账号名（给界面横幅）
+#[derive(serde::Serialize)]
+struct PendingRevertView {
+    #[serde(flatten)]
+    pending: temp_switch::PendingRevert,
+    temporary_name: String,
+    revert_to_name: String,
+    warn_at: chrono::DateTime<Utc>,
+}
+
+#[tauri::command]
+fn get_pending_revert(state: State<AppState>) -> Result<Option<PendingRevertView>, String> {
+    let Some(pending) = state.pending_revert.get() else {
+        return Ok(None);
+    };
+    let store = state.store.lock().map_err(|e| e.to_string())?;
+    let name = |id: &str| {
+        let name = store
+            .accounts
+            .get(id)
+            .map_or_else(|| id.to_string(), |a| a.name.clone());
+        if state.presentation.is_enabled() {
+            presentation::mask_name(&name)
+        } else {
+            name
+        }
+    };
+    Ok(Some(PendingRevertView {
+        temporary_name: name(&pending.temporary_id),
+        revert_to_name: name(&pending.revert_to),
+        warn_at: temp_switch::warn_at(&pending),
+        pending,
+    }))
+}
+
+/// 取消待切回，继续用临时账号；返回是否真的取消了一条
+#[tauri::command]
+fn cancel_pending_revert(app: tauri::AppHandle) -> Result<bool, String> {
+    Ok(cancel_revert(&app, "用户取消").is_some())
 }
 
-// 补充 AppState 的辅助方法以方便在 finalize_oauth_login 中获取 AppHandle 是不行的，
-// 因为 finalize_oauth_login 是 async 且 Command 宏会处理。
-// 我们直接给 finalize_oauth_login 增加 AppHandle 参数。
+/// 清掉待切回（手动切号 / 用户取消 / 托盘取消）
+pub fn cancel_revert(app: &tauri::AppHandle, reason: &str) -> Option<temp_switch::PendingRevert> {
+    let cancelled = app.state::<AppState>().pending_revert.clear()?;
+    println!(
+        "[TempSwitch] {}，取消自动切回 {}",
+        reason, cancelled.revert_to
+    );
+    pending_revert_changed(app);
+    Some(cancelled)
+}
 
-/// 切换到指定账号（异步版本，不做本地 Token 续期）
-#[tauri::command]
-async fn switch_account(
-    state: tauri::State<'_, AppState>,
-    app: tauri::AppHandle,
-    id: String,
-) -> Result<(), String> {
-    // 0. 切换前仅同步“当前激活账号”与官方 auth.json，避免全表匹配导致串号
-    if let Ok(current_auth) = AccountStore::read_codex_auth() {
-        if let Ok(mut store) = state.store.lock() {
-            if let Some(current_id) = store.current.clone() {
-                if store.sync_account_from_auth_json(&current_id, current_auth) {
-                    if let Err(e) = store.save() {
-                        eprintln!("[Sync] 保存当前账号失败: {}", e);
-                    }
-                }
-            }
+fn pending_revert_changed(app: &tauri::AppHandle) {
+    let _ = app.emit("pending-revert-changed", ());
+    crate::tray::update_tray_menu(app);
+}
+
+/// `switch_account` 的目标：`id` 和 `selector`（id / 邮箱 / 名字，见 `account_selector`）二选一
+fn switch_target(
+    store: &std::sync::Mutex<AccountStore>,
+    id: Option<String>,
+    selector: Option<String>,
+) -> Result<String, String> {
+    match (id, selector) {
+        (Some(id), None) => Ok(id),
+        (None, Some(selector)) => {
+            let store = store.lock().map_err(|e| e.to_string())?;
+            account_selector::resolve(&store, &selector)
+                .map(|resolved| resolved.id)
+                .map_err(|e| e.to_string())
         }
+        _ => Err("id 和 selector 必须且只能提供一个".to_string()),
     }
+}
 
-    // 0.5 提前判断 Relay 类型 —— 用于跳过 OpenAI usage 预检
-    let is_target_relay = state
-        .store
-        .lock()
+/// 按 id / 邮箱 / 名字定位账号（匹配顺序见 `account_selector`）
+#[tauri::command]
+fn resolve_account(
+    state: State<AppState>,
+    selector: String,
+) -> Result<account_selector::ResolvedAccount, String> {
+    let store = state.store.lock().map_err(|e| e.to_string())?;
+    let mut resolved = account_selector::resolve(&store, &selector).map_err(|e| e.to_string())?;
+    if state.presentation.is_enabled() {
+        resolved.name = presentation::mask_name(&resolved.name);
+        resolved.email = resolved.email.as_deref().map(presentation::mask_email);
+    }
+    Ok(resolved)
+}
+
+/// 手动切号的返回：切号本身已成功，钩子各自的结果（失败不回滚）
+#[derive(serde::Serialize)]
+struct SwitchResult {
+    hooks: Vec<switch_hooks::HookReport>,
+    /// 目标账号的 auth.json 与本机 Codex 版本可能不兼容时的提示（不阻止切号）
+    compatibility_warning: Option<String>,
+}
+
+/// 只用已缓存的探测结果（不跑进程）加设置里的手填版本
+fn cached_codex_version(store: &AccountStore) -> Option<codex_compat::CodexVersion> {
+    codex_compat::resolve_version(
+        &store.settings.codex_version_override,
+        codex_compat::global().peek().as_ref(),
+    )
+    .0
+}
+
+/// 切号后检查目标账号与本机 Codex 的兼容性；首次会探测版本，放在阻塞线程里跑
+async fn switch_compatibility_warning(
+    store: &std::sync::Mutex<AccountStore>,
+    id: &str,
+) -> Option<String> {
+    let detected = tauri::async_runtime::spawn_blocking(|| codex_compat::global().detected(false))
+        .await
         .ok()
-        .and_then(|s| s.accounts.get(&id).map(|a| a.is_relay()))
-        .unwrap_or(false);
+        .flatten();
+    let store = store.lock().ok()?;
+    let account = store.accounts.get(id)?;
+    let (version, _) =
+        codex_compat::resolve_version(&store.settings.codex_version_override, detected.as_ref());
+    let warning =
+        codex_compat::account_verdict(version.as_ref(), account).warning(version.as_ref());
+    if let Some(w) = &warning {
+        println!("[CodexCompat] {}: {}", log_identity::id(&account.name), w);
+    }
+    warning
+}
 
-    // 1. 获取目标账号的校验凭据
-    let (target_id, access_token, refresh_token, account_id) = {
-        let store = state.store.lock().map_err(|e| e.to_string())?;
-        let account = store
-            .accounts
-            .get(&id)
-            .ok_or_else(|| format!("账号 {} 不存在", id))?;
-
-        let access_token = account
-            .auth_json
-            .get("tokens")
-            .and_then(|t| t.get("access_token"))
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string())
-            .ok_or("账号缺少 access_token")?;
-
-        let refresh_token = account.refresh_token.clone();
-
-        let account_id = account
-            .auth_json
-            .get("account_id")
-            .or_else(|| {
-                account
-                    .auth_json
-                    .get("tokens")
-                    .and_then(|t| t.get("account_id"))
-            })
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string());
+/// 本机 Codex 版本及各账号的兼容性
+#[derive(serde::Serialize)]
+struct CodexCompatibilityReport {
+    version: Option<codex_compat::CodexVersion>,
+    source: codex_compat::VersionSource,
+    /// 探测到的版本（有手填覆盖时也给出，方便对照）
+    detected: Option<codex_compat::CodexVersion>,
+    accounts: std::collections::HashMap<String, codex_compat::CompatibilityVerdict>,
+}
 
-        (account.id.clone(), access_token, refresh_token, account_id)
-    };
+/// 查本机 Codex 版本与各账号的兼容性；`refresh` 为 true 时重新跑 `codex --version`
+#[tauri::command]
+async fn get_codex_compatibility(
+    state: State<'_, AppState>,
+    refresh: Option<bool>,
+) -> Result<CodexCompatibilityReport, String> {
+    let refresh = refresh.unwrap_or(false);
+    let detected =
+        tauri::async_runtime::spawn_blocking(move || codex_compat::global().detected(refresh))
+            .await
+            .map_err(|e| e.to_string())?;
+    let store = state.store.lock().map_err(|e| e.to_string())?;
+    let (version, source) =
+        codex_compat::resolve_version(&store.settings.codex_version_override, detected.as_ref());
+    Ok(CodexCompatibilityReport {
+        accounts: codex_compat::verdicts(version.as_ref(), store.accounts.values()),
+        version,
+        source,
+        detected,
+    })
+}
 
-    // 1.5. 检查 JWT 是否过期，如果过期则尝试刷新
-    let (access_token, refresh_token) = {
-        let mut needs_refresh = false;
-        if let Ok(claims) = AccountStore::extract_jwt_claims_from_token(&access_token) {
-            if let Some(exp) = claims.get("exp").and_then(|v| v.as_i64()) {
-                let now = Utc::now().timestamp();
-                // 如果剩余时间小于 5 分钟，则触发刷新
-                if exp - now < 300 {
-                    println!("[Switch] JWT 已过期或即将过期 ({}), 触发自动刷新", exp);
-                    needs_refresh = true;
-                }
-            }
-        } else {
-            println!("[Switch] 无法解析 JWT Claims，尝试盲刷");
-            needs_refresh = true;
-        }
+/// 各账号 access_token 的过期时间、上次刷新时间与新鲜度；只读，不触发刷新
+#[tauri::command]
+fn get_account_statuses(
+    state: State<'_, AppState>,
+) -> Result<std::collections::HashMap<String, token_status::TokenStatus>, String> {
+    let store = state.store.lock().map_err(|e| e.to_string())?;
+    Ok(token_status::statuses(&store, chrono::Utc::now()))
+}
 
-        if needs_refresh && refresh_token.is_some() {
-            if let Some(ref rt) = refresh_token {
-                match oauth::refresh_access_token(rt).await {
-                    Ok(token_res) => {
-                        println!("[Switch] 自动刷新 Token 成功");
-                        let mut store = state.store.lock().map_err(|e| e.to_string())?;
-                        if let Some(account) = store.accounts.get_mut(&target_id) {
-                            AccountStore::apply_refreshed_tokens(
-                                account,
-                                token_res.access_token.clone(),
-                                token_res.refresh_token.clone(),
-                                token_res.id_token,
-                                token_res.expires_in,
-                            );
-                            if let Err(e) = store.save() {
-                                eprintln!("[Store] 保存失败: {}", e);
-                            }
-                            (token_res.access_token, token_res.refresh_token)
-                        } else {
-                            (access_token, refresh_token)
-                        }
-                    }
-                    Err(e) => {
-                        println!("[Switch] 自动刷新 Token 失败: {}", e);
-                        (access_token, refresh_token)
-                    }
-                }
-            } else {
-                (access_token, refresh_token)
-            }
-        } else {
-            (access_token, refresh_token)
-        }
-    };
+/// 核对账号的 token 是否属于它声称的身份（account_id / sub / 邮箱 / refresh_token）；只读
+#[tauri::command]
+fn verify_account_integrity(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<account_integrity::IntegrityReport, String> {
+    let store = state.store.lock().map_err(|e| e.to_string())?;
+    let account = store
+        .accounts
+        .get(&id)
+        .ok_or_else(|| format!("账号 {} 不存在", id))?;
+    Ok(account_integrity::verify(account))
+}
 
-    // 2. 预检（非阻断）：仅尝试读取配额缓存，不触发本地 refresh_token 刷新。
-    // 失败不阻断切换，交由 Codex 在实际请求中按需维护 token 生命周期。
-    if is_target_relay {
-        println!("[Switch] Relay 类型，跳过 OpenAI usage 预检: {}", target_id);
-    } else {
-        println!(
-            "[Switch] 预检目标账号配额（不触发本地 refresh）: {}",
-            target_id
-        );
-        match usage::UsageFetcher::fetch_usage_direct(
-            access_token,
-            account_id,
-            refresh_token,
-            false,
+/// 所有账号的自洽性检查，只列出有问题的账号（id → 问题）；只读
+#[tauri::command]
+fn verify_all_accounts(
+    state: State<'_, AppState>,
+) -> Result<std::collections::BTreeMap<String, Vec<account_integrity::IntegrityIssue>>, String> {
+    let store = state.store.lock().map_err(|e| e.to_string())?;
+    Ok(account_integrity::verify_all(&store))
+}
+
+/// 切号收尾后跑钩子；锁内只取配置和账号信息，执行期间不持有 store 锁
+async fn run_post_switch_hooks(
+    store: &std::sync::Mutex<AccountStore>,
+    id: &str,
+) -> Vec<switch_hooks::HookReport> {
+    let (hooks, enabled, ctx) = {
+        let Ok(store) = store.lock() else {
+            return Vec::new();
+        };
+        let Some(account) = store.accounts.get(id) else {
+            return Vec::new();
+        };
+        (
+            store.settings.post_switch_hooks.clone(),
+            store.settings.post_switch_hooks_enabled,
+            switch_hooks::HookContext::for_account(account),
         )
-        .await
-        {
-            Ok((usage, _)) => {
-                // 写 quota 快照（先取 email 不锁 store）
-                let email_for_snap = state
-                    .store
-                    .lock()
-                    .ok()
-                    .and_then(|s| {
-                        s.accounts
-                            .get(&target_id)
-                            .and_then(|a| AccountStore::extract_email(&a.auth_json))
-                    })
-                    .unwrap_or_default();
-                quota_snapshot::append_from_usage(
-                    &target_id,
-                    &email_for_snap,
-                    &usage,
-                    "switch_precheck",
-                );
-                let mut store = state.store.lock().map_err(|e| e.to_string())?;
-                if let Some(account) = store.accounts.get_mut(&target_id) {
-                    account.cached_quota = Some(account::CachedQuota {
-                        five_hour_left: usage.five_hour_left as f64,
-                        five_hour_reset: usage.five_hour_reset.clone(),
-                        five_hour_reset_at: usage.five_hour_reset_at,
-                        five_hour_label: usage.five_hour_label.clone(),
-                        weekly_left: usage.weekly_left as f64,
-                        weekly_reset: usage.weekly_reset.clone(),
-                        weekly_reset_at: usage.weekly_reset_at,
-                        weekly_label: usage.weekly_label.clone(),
-                        plan_type: usage.plan_type.clone(),
-                        is_valid_for_cli: usage.is_valid_for_cli,
-                        updated_at: chrono::Utc::now(),
-                    });
-                    if let Err(e) = store.save() {
-                        eprintln!("[Store] 保存失败: {}", e);
-                    }
-                }
-            }
-            Err(e) => {
-                println!("[Switch] 预检配额失败（忽略，不阻断切换）: {}", e);
-            }
-        }
-    } // end if !is_target_relay
+    };
+    switch_hooks::run_hooks(&hooks, enabled, &ctx).await
+}
+
+/// 预览切到某账号时会执行哪些钩子、注入哪些环境变量（不实际执行）
+#[tauri::command]
+fn run_hooks_dry(
+    state: State<AppState>,
+    id: String,
+) -> Result<Vec<switch_hooks::HookPlan>, String> {
+    let store = state.store.lock().map_err(|e| e.to_string())?;
+    let account = store
+        .accounts
+        .get(&id)
+        .ok_or_else(|| format!("账号 {} 不存在", id))?;
+    Ok(switch_hooks::plan(
+        &store.settings.post_switch_hooks,
+        store.settings.post_switch_hooks_enabled,
+        &switch_hooks::HookContext::for_account(account),
+    ))
+}
 
-    // 3. 执行切换：根据 switch_mode + 代理运行状态决定热/冷切
-    let proxy_running = state
+fn proxy_running(state: &AppState) -> bool {
+    state
         .proxy_handle
         .lock()
         .map(|h| h.is_some())
-        .unwrap_or(false);
-    let hot = {
-        let store = state.store.lock().map_err(|e| e.to_string())?;
-        account::should_hot_switch(&store.settings, proxy_running)
-    };
-    println!(
-        "[Switch] 执行切换...（模式={}）",
-        if hot { "热切" } else { "冷切" }
-    );
-    if !state
-        .refresh_locks
-        .acquire(&target_id, tokio::time::Duration::from_secs(5))
-        .await
-    {
-        return Err("该账号正在被其他流程刷新，请稍后重试".to_string());
-    }
-    let switch_result: Result<(), String> = {
-        let mut store = state.store.lock().map_err(|e| e.to_string())?;
-        match store.switch_to(&target_id, hot) {
-            Ok(()) => store.save(),
-            Err(e) => Err(e),
-        }
-    };
-    state.refresh_locks.release(&target_id).await;
-    switch_result?;
+        .unwrap_or(false)
+}
+
+/// 切号落盘后的 Tauri 侧收尾：代理缓存、切号日志、WebSocket 重连、托盘、solo 推送。
+/// solo 推送失败不算切号失败，作为警告返回
+async fn finish_switch(
+    state: tauri::State<'_, AppState>,
+    app: &tauri::AppHandle,
+    id: &str,
+) -> Result<Option<warnings::Warning>, String> {
     // 切号后代理的远端 token 缓存需失效
     proxy::invalidate_remote_token_cache();
     println!("[Switch] 切换完成！");
@@ -1552,12 +3360,12 @@ async fn switch_account(
             .map(|a| a.name.clone());
         let to_name = store
             .accounts
-            .get(&target_id)
+            .get(id)
             .map(|a| a.name.clone())
             .unwrap_or_default();
         let to_quota = store
             .accounts
-            .get(&target_id)
+            .get(id)
             .and_then(|a| a.cached_quota.as_ref())
             .map(|q| q.five_hour_left);
         state.switch_logger.log_switch(
@@ -1574,11 +3382,10 @@ async fn switch_account(
     println!("[Switch] 已通知代理断开 WebSocket 连接");
 
     // 联动刷新托盘菜单
-    crate::tray::update_tray_menu(&app);
+    crate::tray::update_tray_menu(app);
 
     // solo 模式：把新的 current 推给 Server（仅归档，失败不回滚）
-    push_solo_current_if_needed(state, &target_id).await;
-    Ok(())
+    Ok(push_solo_current_if_needed(state, id).await)
 }
 
 /// 手动一键同号：拉 Server 的 current 并在本地热切到它。
@@ -1618,7 +3425,10 @@ async fn solo_sync_current(
 /// 这样 Server.current = 用户选的号，fast_auth_sync 30s 拉到的也是同一个，
 /// 不会再"用户切到 X，30 秒后又被 Server 拉回 Y"。
 /// fire-and-forget，不阻塞调用方；Server 不可达只记日志。
-async fn push_solo_current_if_needed(state: tauri::State<'_, AppState>, new_id: &str) {
+async fn push_solo_current_if_needed(
+    state: tauri::State<'_, AppState>,
+    new_id: &str,
+) -> Option<warnings::Warning> {
     let (mode, primary, fallback, secret) = {
         match state.store.lock() {
             Ok(s) => (
@@ -1627,31 +3437,38 @@ async fn push_solo_current_if_needed(state: tauri::State<'_, AppState>, new_id:
                 s.settings.remote_server_url_fallback.clone(),
                 s.settings.remote_shared_secret.clone(),
             ),
-            Err(_) => return,
+            Err(_) => return None,
         }
     };
     // solo + client 都要 push（off / server 模式没 Server 可推）
     if !matches!(mode.as_str(), "solo" | "client") || secret.is_empty() {
-        return;
+        return None;
     }
     // client 模式 = 两端协作，让 Server 也写 disk（apply_to_disk=true）
     // solo 模式 = 本机自治，Server 仅记录 current 指针归档（apply_to_disk=false）
     let apply_to_disk = mode == "client";
-    match remote_client::resolve_base_url(&primary, &fallback).await {
+    let error = match remote_client::resolve_base_url(&primary, &fallback).await {
         Ok(base) => {
-            if let Err(e) =
-                remote_client::push_solo_switch(&base, &secret, new_id, apply_to_disk).await
-            {
-                eprintln!("[Switch] push /solo/current 失败（已本地生效）: {}", e);
-            } else {
-                println!(
-                    "[Switch] 手工切号已同步到 Server (mode={}, apply_to_disk={})",
-                    mode, apply_to_disk
-                );
+            match remote_client::push_solo_switch(&base, &secret, new_id, apply_to_disk).await {
+                Err(e) => {
+                    eprintln!("[Switch] push /solo/current 失败（已本地生效）: {}", e);
+                    e
+                }
+                Ok(()) => {
+                    println!(
+                        "[Switch] 手工切号已同步到 Server (mode={}, apply_to_disk={})",
+                        mode, apply_to_disk
+                    );
+                    return None;
+                }
             }
         }
-        Err(e) => eprintln!("[Switch] Server 不可达，切号未同步: {}", e),
-    }
+        Err(e) => {
+            eprintln!("[Switch] Server 不可达，切号未同步: {}", e);
+            e
+        }
+    };
+    Some(warnings::Warning::new(warnings::WarningCode::SoloPushFailed).with("error", error))
 }
 
 /// solo 模式心跳循环：固定间隔向 Server 发心跳，让 Server 知道"本机正在接管保活"。
@@ -1830,7 +3647,11 @@ pub async fn do_one_fast_auth_sync(store: &std::sync::Arc<std::sync::Mutex<Accou
             }
             // 扩展 expires_at 到 +24h，codex CLI 看到"很新鲜"就不会自己 refresh，
             // 真过期时 proxy 这边接管处理
-            if let Err(e) = AccountStore::write_codex_auth_extended_expiry(&t.auth_json) {
+            if let Err(e) = AccountStore::write_codex_auth_extended_expiry(
+                &t.auth_json,
+                AuthWriteOp::RemoteSync,
+                Some(&cid),
+            ) {
                 eprintln!("[FastAuthSync] 写 ~/.codex/auth.json 失败: {}", e);
                 return false;
             }
@@ -1841,19 +3662,345 @@ pub async fn do_one_fast_auth_sync(store: &std::sync::Arc<std::sync::Mutex<Accou
     }
 }
 
-/// 快速 auth.json 同步循环（仅 client 模式）：每 30s 拉一次 Server 上 current 的最新 token
-/// 并写盘到 ~/.codex/auth.json。把"Server 已轮换 RT vs 本机 auth.json 滞后"的窗口压到 30s。
-/// 与 start_quota_refresh 解耦：quota 5 分钟级，token 30 秒级，互不阻塞。
-pub fn start_fast_auth_sync(
-    store: std::sync::Arc<std::sync::Mutex<AccountStore>>,
-) -> tauri::async_runtime::JoinHandle<()> {
+/// 快速 auth.json 同步循环（仅 client 模式）：每 30s 拉一次 Server 上 current 的最新 token
+/// 并写盘到 ~/.codex/auth.json。把"Server 已轮换 RT vs 本机 auth.json 滞后"的窗口压到 30s。
+/// 与 start_quota_refresh 解耦：quota 5 分钟级，token 30 秒级，互不阻塞。
+pub fn start_fast_auth_sync(
+    store: std::sync::Arc<std::sync::Mutex<AccountStore>>,
+) -> tauri::async_runtime::JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        println!("[FastAuthSync] 快速同步循环已启动（30s，仅 client 模式生效）");
+        loop {
+            crate::power::sleep_or_resume(std::time::Duration::from_secs(30)).await;
+            do_one_fast_auth_sync(&store).await;
+        }
+    })
+}
+
+/// 事件总线的两端：
+/// - 每次 "accounts-updated" 后对比 store，推导出 current-changed / quota-updated 发布到总线
+/// - 订阅总线，原样 emit 给前端（SSE 那一端在 remote_server 里各自订阅）
+pub fn start_event_fanout(app: &tauri::App) {
+    use tauri::Listener;
+    let state = app.state::<AppState>();
+    let store = state.store.clone();
+    let bus = state.events.clone();
+
+    // emit 可能发生在持有 store 锁的时候，监听回调里只打个信号，diff 放到异步任务里做
+    let changed = std::sync::Arc::new(tokio::sync::Notify::new());
+    let signal = changed.clone();
+    app.listen_any("accounts-updated", move |_| signal.notify_one());
+    let mut digest = store
+        .lock()
+        .map(|s| events::StoreDigest::from_store(&s))
+        .unwrap_or_default();
+    let publisher = bus.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            changed.notified().await;
+            let derived = match store.lock() {
+                Ok(s) => digest.diff(&s),
+                Err(_) => continue,
+            };
+            for event in derived {
+                publisher.publish(event);
+            }
+        }
+    });
+
+    let mut rx = bus.subscribe();
+    let app_handle = app.handle().clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) => {
+                    let _ = app_handle.emit(event.name(), &event);
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+/// 活动流定时落盘间隔
+const ACTIVITY_FLUSH_SECS: u64 = 60;
+
+/// 往活动流记一条（拿不到 AppState 的早期阶段静默跳过）
+fn record_activity(app: &tauri::AppHandle, activity: activity_feed::Activity) {
+    if let Some(state) = app.try_state::<AppState>() {
+        state.activity.record(activity);
+    }
+}
+
+/// 启动时收拾上次崩溃留下的半截切号（见 `switch_intent`）
+fn recover_pending_switch(app: &tauri::AppHandle) {
+    let path = switch_intent::default_path();
+    let Some(intent) = switch_intent::load_from(&path) else {
+        return;
+    };
+    let Some(state) = app.try_state::<AppState>() else {
+        return;
+    };
+    let disk = AccountStore::read_codex_auth().ok();
+    let (resolution, target_name) = {
+        let Ok(mut store) = state.store.lock() else {
+            return;
+        };
+        let resolution = switch_intent::reconcile(&mut store, &intent, disk.as_ref(), Utc::now());
+        if resolution.changes_store() {
+            if let Err(e) = store.save() {
+                // 保留记录，下次启动再试
+                eprintln!("[Switch] 收拾半截切号后保存失败: {}", e);
+                return;
+            }
+        }
+        let name = store
+            .accounts
+            .get(&intent.target_id)
+            .map_or_else(|| intent.target_id.clone(), |a| a.name.clone());
+        (resolution, name)
+    };
+    println!(
+        "[Switch] 发现未完成的切号（目标 {}，阶段 {:?}，开始于 {}）→ {}",
+        log_identity::id(&target_name),
+        intent.stage,
+        intent.started_at,
+        resolution.label()
+    );
+    if let Err(e) = switch_intent::clear_at(&path) {
+        eprintln!("[Switch] 清除切号意图失败: {}", e);
+    }
+    match resolution {
+        switch_intent::Resolution::Finish | switch_intent::Resolution::RollBack => {
+            record_activity(
+                app,
+                activity_feed::Activity::SwitchRecovered {
+                    account_id: intent.target_id.clone(),
+                    account_name: target_name,
+                    rolled_back: resolution == switch_intent::Resolution::RollBack,
+                },
+            );
+            let _ = app.emit("accounts-updated", ());
+        }
+        switch_intent::Resolution::Stale => record_activity(
+            app,
+            activity_feed::Activity::StaleSwitchIntent {
+                account_name: target_name,
+            },
+        ),
+        _ => {}
+    }
+}
+
+/// 活动流的采集端：订阅事件总线，再监听已经 emit 给前端的导入 / 错误事件，
+/// 统一经 `Activity` 映射后入流；另起定时任务把新条目落盘
+pub fn start_activity_feed(app: &tauri::App) {
+    use tauri::Listener;
+    let state = app.state::<AppState>();
+
+    for event in [
+        "import-complete",
+        "proxy-account-banned",
+        "proxy-all-exhausted",
+        refresh_ban::EVENT,
+    ] {
+        let feed = state.activity.clone();
+        app.listen_any(event, move |e| {
+            let payload = serde_json::from_str(e.payload()).unwrap_or_default();
+            if let Some(activity) = activity_feed::Activity::from_emitted(event, &payload) {
+                feed.record(activity);
+            }
+        });
+    }
+
+    let mut rx = state.events.subscribe();
+    let feed = state.activity.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            match rx.recv().await {
+                Ok(event) => {
+                    if let Some(activity) = activity_feed::Activity::from_app_event(&event) {
+                        feed.record(activity);
+                    }
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(tokio::sync::broadcast::error::RecvError::Closed) => break,
+            }
+        }
+    });
+
+    let feed = state.activity.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(std::time::Duration::from_secs(ACTIVITY_FLUSH_SECS)).await;
+            if let Err(e) = feed.flush() {
+                eprintln!("[ActivityFeed] 落盘失败: {}", e);
+            }
+        }
+    });
+}
+
+/// 账号库合并写的间隔：期间 `mark_dirty` 多少次都只写一次
+const STORE_FLUSH_MILLIS: u64 = 500;
+
+/// 后台合并写账号库（见 `AccountStore::mark_dirty`）。拿的是账号库本身的锁，
+/// 不会和导入这类整份替换账号库的操作交错
+pub fn start_store_persister(app: &tauri::App) {
+    let store = app.state::<AppState>().store.clone();
+    tauri::async_runtime::spawn(async move {
+        loop {
+            tokio::time::sleep(std::time::Duration::from_millis(STORE_FLUSH_MILLIS)).await;
+            flush_store(&store, "Persister");
+        }
+    });
+}
+
+/// 立即写掉账号库里待落盘的改动（定时任务、隐藏窗口、退出时用）
+fn flush_store(store: &std::sync::Mutex<AccountStore>, reason: &str) {
+    let Ok(guard) = store.lock() else {
+        return;
+    };
+    if let Err(e) = guard.flush() {
+        eprintln!("[{}] 账号库落盘失败: {}", reason, e);
+    }
+}
+
+/// 记一条后台任务失败（拿不到 AppState 的早期阶段静默跳过）
+fn record_background_error(
+    app: &tauri::AppHandle,
+    task: &str,
+    account_id: Option<&str>,
+    error: &str,
+) {
+    if let Some(state) = app.try_state::<AppState>() {
+        state.errors.record(
+            error_center::ErrorOrigin::Background(task.to_string()),
+            account_id,
+            error,
+        );
+    }
+}
+
+/// 临时切号定时器间隔：提醒 / 切回最多晚这么久
+const REVERT_TICK_SECS: u64 = 30;
+
+/// 临时切号的定时器：到点前提醒、到点切回、被别的切号顶掉时作废（见 `temp_switch`）
+pub fn start_revert_timer(app: &tauri::App) {
+    let handle = app.handle().clone();
     tauri::async_runtime::spawn(async move {
-        println!("[FastAuthSync] 快速同步循环已启动（30s，仅 client 模式生效）");
         loop {
-            tokio::time::sleep(std::time::Duration::from_secs(30)).await;
-            do_one_fast_auth_sync(&store).await;
+            revert_tick(&handle).await;
+            tokio::time::sleep(std::time::Duration::from_secs(REVERT_TICK_SECS)).await;
         }
-    })
+    });
+}
+
+async fn revert_tick(app: &tauri::AppHandle) {
+    let state = app.state::<AppState>();
+    let Some(pending) = state.pending_revert.get() else {
+        return;
+    };
+    let (current, temporary_name, revert_to_name) = {
+        let Ok(store) = state.store.lock() else {
+            return;
+        };
+        let name = |id: &str| {
+            store
+                .accounts
+                .get(id)
+                .map_or_else(|| id.to_string(), |a| a.name.clone())
+        };
+        (
+            store.current.clone(),
+            name(&pending.temporary_id),
+            name(&pending.revert_to),
+        )
+    };
+    let quiet = state.presentation.is_enabled();
+    match temp_switch::step(&pending, current.as_deref(), Utc::now()) {
+        temp_switch::Step::Wait => {}
+        temp_switch::Step::Superseded => {
+            state.pending_revert.clear();
+            println!(
+                "[TempSwitch] 当前账号已不是 {}，取消自动切回",
+                log_identity::id(&temporary_name)
+            );
+            pending_revert_changed(app);
+        }
+        temp_switch::Step::Warn => {
+            state.pending_revert.mark_warned(&pending);
+            let minutes = (pending.revert_at - Utc::now()).num_minutes().max(0) + 1;
+            println!(
+                "[TempSwitch] 约 {} 分钟后切回 {}",
+                minutes,
+                log_identity::id(&revert_to_name)
+            );
+            if !quiet {
+                crate::notify::send(
+                    "即将切回原账号",
+                    &format!(
+                        "{} 约 {} 分钟后自动切回 {}。想继续用可在主窗口或托盘里取消自动切回。",
+                        temporary_name, minutes, revert_to_name
+                    ),
+                );
+            }
+            pending_revert_changed(app);
+        }
+        temp_switch::Step::Revert => {
+            state.pending_revert.clear();
+            match perform_switch(app.state(), app, &pending.revert_to).await {
+                Ok(_) => {
+                    println!(
+                        "[TempSwitch] ✅ 已自动切回 {}",
+                        log_identity::id(&revert_to_name)
+                    );
+                    let _ = app.emit("accounts-updated", ());
+                    if !quiet {
+                        crate::notify::send(
+                            "已切回原账号",
+                            &format!(
+                                "临时使用 {} 到时，已切回 {}",
+                                temporary_name, revert_to_name
+                            ),
+                        );
+                    }
+                }
+                Err(e) => {
+                    eprintln!(
+                        "[TempSwitch] ❌ 自动切回 {} 失败: {}",
+                        log_identity::id(&revert_to_name),
+                        e
+                    );
+                    record_background_error(
+                        app,
+                        "temp_switch_revert",
+                        Some(&pending.revert_to),
+                        &e,
+                    );
+                    if !quiet {
+                        crate::notify::send("自动切回失败", &e);
+                    }
+                }
+            }
+            pending_revert_changed(app);
+        }
+    }
+}
+
+/// 错误中心的后台采集端：已经 emit 给前端的失败事件统一映射成记录
+pub fn start_error_capture(app: &tauri::App) {
+    use tauri::Listener;
+    let handle = app.handle().clone();
+    for &event in error_center::BACKGROUND_ERROR_EVENTS {
+        let handle = handle.clone();
+        app.listen_any(event, move |e| {
+            let payload = serde_json::from_str(e.payload()).unwrap_or_default();
+            if let Some(bg) = error_center::background_error(event, &payload) {
+                record_background_error(&handle, bg.task, bg.account_id.as_deref(), &bg.error);
+            }
+        });
+    }
 }
 
 pub fn start_quota_refresh(
@@ -1862,6 +4009,7 @@ pub fn start_quota_refresh(
 ) -> tauri::async_runtime::JoinHandle<()> {
     tauri::async_runtime::spawn(async move {
         println!("[QuotaRefresh] 定时额度刷新已启动");
+        let mut exhaustion_warned: Option<(String, Option<i64>)> = None;
 
         loop {
             let (enabled, interval_minutes, batch_size, remote_mode, primary, fallback, secret) = {
@@ -1922,7 +4070,9 @@ pub fn start_quota_refresh(
                                         Ok(outcome) => {
                                             println!(
                                                 "[QuotaRefresh] 后台补推成功: id={} name={} action={}",
-                                                cand.id, cand.name, outcome.upserted
+                                                cand.id,
+                                                log_identity::id(&cand.name),
+                                                outcome.upserted
                                             );
                                             remote_ids.insert(cand.id.clone());
                                             if outcome.upserted == "merged"
@@ -1936,7 +4086,9 @@ pub fn start_quota_refresh(
                                             // 不丢，由 10 分钟宽限期保护；下个 5 分钟周期继续重试
                                             eprintln!(
                                                 "[QuotaRefresh] 后台补推失败 id={} name={}: {}",
-                                                cand.id, cand.name, e
+                                                cand.id,
+                                                log_identity::id(&cand.name),
+                                                e
                                             );
                                         }
                                     }
@@ -1959,8 +4111,9 @@ pub fn start_quota_refresh(
                                         //    污染本地。is_banned 也同样跳过（Relay 没有"封号"概念）。
                                         for e in &entries {
                                             if let Some(acc) = s.accounts.get_mut(&e.id) {
-                                                if let Some(q) = e.cached_quota.clone() {
-                                                    acc.cached_quota = Some(q);
+                                                if let Some(mut q) = e.cached_quota.clone() {
+                                                    q.source = QuotaSource::OfflineCache;
+                                                    acc.set_cached_quota(q);
                                                     updated += 1;
                                                 }
                                                 if !acc.is_relay() {
@@ -1995,7 +4148,7 @@ pub fn start_quota_refresh(
                                                         "[QuotaRefresh] 跳过 prune（{}s 宽限期内）: id={} name={}",
                                                         age.num_seconds(),
                                                         id,
-                                                        acc.name
+                                                        log_identity::id(&acc.name)
                                                     );
                                                     continue;
                                                 }
@@ -2046,7 +4199,9 @@ pub fn start_quota_refresh(
                                                         {
                                                             println!(
                                                                 "[QuotaRefresh] client 对齐 current → {}",
-                                                                cur.name.clone().unwrap_or_default()
+                                                                log_identity::id(
+                                                                    cur.name.as_deref().unwrap_or_default()
+                                                                )
                                                             );
                                                         }
                                                         s.should_write_disk_for(&cid)
@@ -2056,11 +4211,15 @@ pub fn start_quota_refresh(
                                                     if !allow_disk {
                                                         println!(
                                                             "[QuotaRefresh] 手机锚生效，跳过写 ~/.codex/auth.json（{} != anchor）",
-                                                            cur.name.clone().unwrap_or_default()
+                                                            log_identity::id(
+                                                                cur.name.as_deref().unwrap_or_default()
+                                                            )
                                                         );
                                                     } else if let Err(e) =
                                                         account::AccountStore::write_codex_auth_extended_expiry(
                                                             &t.auth_json,
+                                                            AuthWriteOp::RemoteSync,
+                                                            Some(&cid),
                                                         )
                                                     {
                                                         // client 模式：用 extended_expiry 防 codex 自刷
@@ -2071,7 +4230,9 @@ pub fn start_quota_refresh(
                                                     } else {
                                                         println!(
                                                             "[QuotaRefresh] client 已写 ~/.codex/auth.json（{}）",
-                                                            cur.name.unwrap_or_default()
+                                                            log_identity::id(
+                                                                cur.name.as_deref().unwrap_or_default()
+                                                            )
                                                         );
                                                     }
                                                     // 切号后清掉 proxy 端的远端 token 缓存
@@ -2099,7 +4260,8 @@ pub fn start_quota_refresh(
                     Err(e) => println!("[QuotaRefresh] client Server 不可达: {}", e),
                 }
                 let sync_minutes = u64::from(interval_minutes.max(5)); // client 模式最少 5 分钟
-                tokio::time::sleep(tokio::time::Duration::from_secs(sync_minutes * 60)).await;
+                crate::power::sleep_or_resume(tokio::time::Duration::from_secs(sync_minutes * 60))
+                    .await;
                 continue;
             }
 
@@ -2127,12 +4289,19 @@ pub fn start_quota_refresh(
             // Relay 账号的 quota 通过专属 fetcher 拉取，这里跳过避免无谓打 OpenAI usage API
             let targets: Vec<(String, String)> = {
                 let s = store.lock().unwrap();
-                let now_ts = chrono::Utc::now().timestamp();
+                let now_ts = clock::now().timestamp();
+                // 系统时间异常时"窗口已 reset"判断不可信，只按最旧优先走普通 batch
+                let skewed = clock::is_skewed();
                 let candidates: Vec<_> = s
                     .accounts
                     .values()
                     .filter(|a| {
-                        !a.is_banned && !a.is_token_invalid && !a.is_logged_out && !a.is_relay()
+                        !a.is_banned
+                            && !a.is_token_invalid
+                            && !a.is_logged_out
+                            && !a.is_relay()
+                            && !a.archived
+                            && !a.lacks_codex_access()
                     })
                     .map(|a| {
                         let cq = a.cached_quota.as_ref();
@@ -2149,7 +4318,7 @@ pub fn start_quota_refresh(
                         let weekly_expired = weekly_reset > 0
                             && weekly_reset <= now_ts
                             && updated_ts < weekly_reset;
-                        let expired = five_expired || weekly_expired;
+                        let expired = !skewed && (five_expired || weekly_expired);
                         (a.id.clone(), a.name.clone(), updated, expired)
                     })
                     .collect();
@@ -2181,10 +4350,11 @@ pub fn start_quota_refresh(
                     .collect()
             };
 
+            let (mut refreshed, mut failed) = (0usize, 0usize);
             for (id, name) in &targets {
-                println!("[QuotaRefresh] 刷新 {} ...", name);
+                println!("[QuotaRefresh] 刷新 {} ...", log_identity::id(&name));
 
-                let (at, aid, rt) = {
+                let (at, expires_at, aid, rt, auth_profile, overrides) = {
                     let s = store.lock().unwrap();
                     let acc = match s.accounts.get(id) {
                         Some(a) => a,
@@ -2192,17 +4362,45 @@ pub fn start_quota_refresh(
                     };
                     (
                         AccountStore::extract_access_token(&acc.auth_json),
+                        AccountStore::extract_access_token_expiry(&acc.auth_json),
                         AccountStore::extract_account_id(&acc.auth_json),
                         acc.refresh_token.clone(),
+                        s.auth_profile_of(acc),
+                        request_headers::RequestOverrides::for_account(&s.settings, acc),
                     )
                 };
 
+                // 这一轮的 usage 请求不带本地 refresh：已过期的 token 发出去注定 401，直接跳过
+                if let Some(exp) = expires_at.filter(|_| at.is_some()) {
+                    let preflight = usage::token_preflight_now(Some(exp), false);
+                    if preflight == usage::TokenPreflight::Fail {
+                        println!(
+                            "[QuotaRefresh] {} 跳过：{}",
+                            log_identity::id(&name),
+                            usage::token_expired_error(exp)
+                        );
+                        continue;
+                    }
+                }
+
                 // 没有 access_token 先用 refresh_token 换
                 let access_token = match at {
                     Some(t) => t,
                     None => {
+                        let banned = store
+                            .lock()
+                            .map(|s| refresh_ban::guard(&s, id).is_err())
+                            .unwrap_or(true);
+                        if banned {
+                            println!(
+                                "[QuotaRefresh] {} 跳过：{}",
+                                log_identity::id(&name),
+                                refresh_ban::REASON
+                            );
+                            continue;
+                        }
                         if let Some(ref rt_val) = rt {
-                            match crate::oauth::refresh_access_token(rt_val).await {
+                            match crate::oauth::refresh_access_token(&auth_profile, rt_val).await {
                                 Ok(res) => {
                                     if let Ok(mut s) = store.lock() {
                                         if let Some(acc) = s.accounts.get_mut(id) {
@@ -2219,7 +4417,20 @@ pub fn start_quota_refresh(
                                     res.access_token
                                 }
                                 Err(e) => {
-                                    println!("[QuotaRefresh] {} token 刷新失败: {}", name, e);
+                                    println!(
+                                        "[QuotaRefresh] {} token 刷新失败: {}",
+                                        log_identity::id(&name),
+                                        e
+                                    );
+                                    failed += 1;
+                                    if let Ok(mut s) = store.lock() {
+                                        if let Some(notice) =
+                                            refresh_ban::record_failure(&mut s, id, &e)
+                                        {
+                                            let _ = s.save();
+                                            let _ = app_handle.emit(refresh_ban::EVENT, &notice);
+                                        }
+                                    }
                                     continue;
                                 }
                             }
@@ -2229,7 +4440,15 @@ pub fn start_quota_refresh(
                     }
                 };
 
-                match usage::UsageFetcher::fetch_usage_direct(access_token, aid, rt, false).await {
+                match usage::UsageFetcher::fetch_usage_direct(
+                    access_token,
+                    aid,
+                    rt,
+                    None,
+                    overrides,
+                )
+                .await
+                {
                     Ok((usage, _)) => {
                         let email_for_snap = if let Ok(s) = store.lock() {
                             s.accounts
@@ -2248,26 +4467,17 @@ pub fn start_quota_refresh(
                         );
                         if let Ok(mut s) = store.lock() {
                             if let Some(acc) = s.accounts.get_mut(id) {
-                                acc.cached_quota = Some(account::CachedQuota {
-                                    five_hour_left: usage.five_hour_left as f64,
-                                    five_hour_reset: usage.five_hour_reset.clone(),
-                                    five_hour_reset_at: usage.five_hour_reset_at,
-                                    five_hour_label: usage.five_hour_label.clone(),
-                                    weekly_left: usage.weekly_left as f64,
-                                    weekly_reset: usage.weekly_reset.clone(),
-                                    weekly_reset_at: usage.weekly_reset_at,
-                                    weekly_label: usage.weekly_label.clone(),
-                                    plan_type: usage.plan_type.clone(),
-                                    is_valid_for_cli: usage.is_valid_for_cli,
-                                    updated_at: chrono::Utc::now(),
-                                });
-                                let _ = s.save();
+                                acc.set_cached_quota((&usage, QuotaSource::Scheduler).into());
+                                s.mark_dirty();
                             }
                         }
                         println!(
                             "[QuotaRefresh] {} → 5h:{}% 周:{}%",
-                            name, usage.five_hour_left, usage.weekly_left
+                            log_identity::id(&name),
+                            usage.five_hour_left,
+                            usage.weekly_left
                         );
+                        refreshed += 1;
 
                         // 记录自动刷新额度日志
                         use tauri::Manager;
@@ -2285,8 +4495,22 @@ pub fn start_quota_refresh(
 
                         let _ = app_handle.emit("accounts-updated", ());
                     }
+                    Err(e) if usage::is_upstream_unavailable(&e) => {
+                        println!(
+                            "[QuotaRefresh] {} 上游暂时不可用，保留旧缓存，下一轮再试: {}",
+                            log_identity::id(&name),
+                            e
+                        );
+                        failed += 1;
+                    }
                     Err(e) => {
-                        println!("[QuotaRefresh] {} 额度查询失败: {}", name, e);
+                        println!(
+                            "[QuotaRefresh] {} 额度查询失败: {}",
+                            log_identity::id(&name),
+                            e
+                        );
+                        failed += 1;
+                        record_background_error(&app_handle, "quota_refresh", Some(id), &e);
                         // 封号/失效标记
                         if e.contains("ACCOUNT_BANNED") {
                             if let Ok(mut s) = store.lock() {
@@ -2313,92 +4537,82 @@ pub fn start_quota_refresh(
                 tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
             }
 
+            if refreshed + failed > 0 {
+                record_activity(
+                    &app_handle,
+                    activity_feed::Activity::QuotaRound { refreshed, failed },
+                );
+            }
+
+            warn_if_exhausting_soon(&store, &app_handle, &mut exhaustion_warned);
+
             // 整轮跑完后再 sleep 到下一周期（没有目标的时候缩短到 60s）
             let next_sleep_secs = if targets.is_empty() {
                 60
             } else {
                 u64::from(interval_minutes) * 60
             };
-            tokio::time::sleep(tokio::time::Duration::from_secs(next_sleep_secs)).await;
+            crate::power::sleep_or_resume(tokio::time::Duration::from_secs(next_sleep_secs)).await;
         }
     })
 }
 
-pub fn score_candidate_accounts(store: &AccountStore) -> Vec<(String, String, f64)> {
-    let current_id = store.current.as_deref().unwrap_or("");
-    let allow_free = store.settings.allow_auto_switch_to_free;
-    let allow_switch_in_relay = store.settings.relay_auto_switch_in;
-    let now = chrono::Utc::now().timestamp();
-
-    let mut scored: Vec<(String, String, f64)> = Vec::new();
-
-    for account in store.accounts.values() {
-        if account.id == current_id
-            || account.is_banned
-            || account.is_token_invalid
-            || account.is_logged_out
-        {
-            continue;
-        }
-        // 默认不"切到 Relay"：自动选号跳过 Relay 候选
-        if !allow_switch_in_relay && account.is_relay() {
-            continue;
-        }
-
-        let score = match &account.cached_quota {
-            None => 50.0,
-            Some(q) => {
-                let plan = q.plan_type.to_lowercase();
-                let is_free = plan == "free" || plan == "unknown";
-
-                if is_free && !allow_free {
-                    continue;
-                }
-
-                // Plan 优先级加分：pro > plus/team > free
-                let plan_bonus = match plan.as_str() {
-                    "pro" => 30.0,
-                    "plus" | "team" | "enterprise" => 20.0,
-                    "edu" | "business" => 15.0,
-                    "free" | "unknown" => 0.0,
-                    _ => 10.0,
-                };
-
-                // 5h 可用度
-                let five_h = if q.five_hour_left <= 0.0 {
-                    match q.five_hour_reset_at {
-                        Some(reset_at) if now >= reset_at => 50.0,
-                        _ => 0.0,
-                    }
-                } else {
-                    q.five_hour_left
-                };
-
-                // 周可用度
-                let weekly = if q.weekly_left <= 0.0 {
-                    match q.weekly_reset_at {
-                        Some(reset_at) if now >= reset_at => 50.0,
-                        _ => 0.0,
-                    }
-                } else {
-                    q.weekly_left
-                };
+/// 当前账号按近期速率预计在这么多分钟内打满 5h 额度时提醒
+const EXHAUSTION_WARN_MINUTES: u32 = 30;
 
-                let effective = if is_free { five_h } else { five_h.min(weekly) };
-                if effective <= 0.0 {
-                    continue;
-                }
-                // 最终评分 = 额度分 + Plan 加分
-                effective + plan_bonus
-            }
-        };
+/// "quota-exhaustion-warning" 事件负载
+#[derive(serde::Serialize, Clone)]
+struct ExhaustionWarningPayload {
+    account_id: String,
+    account_name: String,
+    minutes_remaining: u32,
+    confidence: f64,
+}
 
-        scored.push((account.id.clone(), account.name.clone(), score));
+/// 当前账号预计 30 分钟内耗尽时 emit 提醒；同一个 5h 窗口只提醒一次
+fn warn_if_exhausting_soon(
+    store: &std::sync::Arc<std::sync::Mutex<AccountStore>>,
+    app_handle: &tauri::AppHandle,
+    warned: &mut Option<(String, Option<i64>)>,
+) {
+    let Some((id, name, reset_at)) = store.lock().ok().and_then(|s| {
+        let id = s.current.clone()?;
+        let acc = s.accounts.get(&id).filter(|a| !a.is_relay())?;
+        let reset_at = acc.cached_quota.as_ref().and_then(|q| q.five_hour_reset_at);
+        Some((id, acc.name.clone(), reset_at))
+    }) else {
+        return;
+    };
+    let window = (id.clone(), reset_at);
+    if warned.as_ref() == Some(&window) {
+        return;
     }
-
-    // 按得分从高到低排序
-    scored.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(std::cmp::Ordering::Equal));
-    scored
+    // 演示模式下后台照常刷新，但不弹提醒
+    if app_handle.state::<AppState>().presentation.is_enabled() {
+        return;
+    }
+    let Some(estimate) = quota_snapshot::estimate_for_account(&id, chrono::Utc::now().timestamp())
+    else {
+        return;
+    };
+    if estimate.minutes_remaining > EXHAUSTION_WARN_MINUTES {
+        return;
+    }
+    println!(
+        "[QuotaRefresh] ⚠️ {} 按当前速率预计 30 分钟内耗尽（约 {} 分钟）",
+        log_identity::id(&name),
+        estimate.minutes_remaining
+    );
+    let _ = app_handle.emit(
+        "quota-exhaustion-warning",
+        ExhaustionWarningPayload {
+            account_id: id,
+            account_name: name,
+            minutes_remaining: estimate.minutes_remaining,
+            confidence: estimate.confidence,
+        },
+    );
+    *warned = Some(window);
 }
 
 /// 预测下一个最优账号（tray 菜单预览）
@@ -2410,236 +4624,59 @@ pub fn predict_next_account_internal(state: tauri::State<'_, AppState>) -> Optio
         .map(|(_, name, score)| (name.clone(), *score as i32))
 }
 
-/// 智能切号：选最优账号并切换
+/// 智能切号：选最优账号并切换（托盘 / popup 直接调，不经主窗口前端）。返回切到的账号 id 和警告
 pub async fn switch_to_next_account_internal(
     state: tauri::State<'_, AppState>,
     app: tauri::AppHandle,
-) -> Result<(), String> {
-    // 1. 用评分算法选出最优候选
-    let candidates = {
-        let store = state.store.lock().map_err(|e| e.to_string())?;
-        score_candidate_accounts(&store)
-    };
-
-    if candidates.is_empty() {
-        return Err("没有可用账号".to_string());
-    }
-
-    // 2. 按得分从高到低尝试，查 API 确认额度后切换
-    for (target_id, target_name, score) in &candidates {
-        println!("[SmartSwitch] 候选: {} (评分 {:.0})", target_name, score);
-
-        // Relay 类型不走 OpenAI usage API（中转站不支持），直接接受候选
-        let is_relay = state
-            .store
-            .lock()
-            .ok()
-            .and_then(|s| s.accounts.get(target_id).map(|a| a.is_relay()))
-            .unwrap_or(false);
-        if is_relay {
-            println!(
-                "[SmartSwitch] Relay 类型，跳过 quota 检查直接切换: {}",
-                target_name
-            );
-            return switch_account(state, app.clone(), target_id.clone()).await;
-        }
-
-        // 查 API 确认最新额度
-        let quota = match get_quota_internal(&state, target_id.clone()).await {
-            Ok(u) => u,
-            Err(e) => {
-                // 封号/失效/登出检测
-                if e.contains("ACCOUNT_BANNED")
-                    || e.contains("TOKEN_INVALID")
-                    || e.contains("ACCOUNT_LOGGED_OUT")
-                {
-                    println!("[SmartSwitch] 账号 {} 已封禁/失效/登出，跳过", target_name);
-                    continue;
-                }
-                println!(
-                    "[SmartSwitch] 账号 {} 额度查询失败: {}，跳过",
-                    target_name, e
-                );
-                continue;
-            }
-        };
-
-        let plan = quota.plan_type.to_lowercase();
-        let is_free = plan == "free" || plan == "unknown";
-
-        let has_quota = if is_free {
-            quota.five_hour_left > 0
-        } else {
-            quota.five_hour_left > 0 && quota.weekly_left > 0
-        };
-
-        if has_quota {
-            println!(
-                "[SmartSwitch] 选中最优账号: {} ({}, 5h={}%, 周={}%)",
-                target_name, quota.plan_type, quota.five_hour_left, quota.weekly_left
-            );
-            return switch_account(state, app.clone(), target_id.clone()).await;
-        } else {
-            println!("[SmartSwitch] 账号 {} 额度已耗尽，继续找", target_name);
-        }
-    }
-
-    Err("遍历完所有账号，未发现可用配额的账号".to_string())
+) -> Result<warnings::Warned<String>, String> {
+    let store = state.store.clone();
+    let mut result = state
+        .account_service(&app)
+        .switch_to_next_warned(proxy_running(&state))
+        .await?;
+    let presentation = state.presentation.is_enabled();
+    result
+        .warnings
+        .extend(finish_switch(state, &app, &result.value).await?);
+    cancel_revert(&app, "手动切号");
+    // 托盘切号没有界面展示钩子结果，失败已在 run_hooks 里打日志
+    run_post_switch_hooks(&store, &result.value).await;
+    Ok(result.masked_if(presentation))
 }
 
-/// 内部辅助：获取额度数据
-async fn get_quota_internal(state: &AppState, id: String) -> Result<UsageDisplay, String> {
-    // Relay 账号没有 OpenAI 5h+周窗口模型；上层应改用 refresh_relay_usage
-    {
-        let store = state.store.lock().map_err(|e| e.to_string())?;
-        if let Some(acc) = store.accounts.get(&id) {
-            if acc.is_relay() {
-                return Err(
-                    "RELAY_ACCOUNT:中转站账号不支持 OpenAI usage 查询，请用「中转站余额刷新」"
-                        .to_string(),
-                );
-            }
-        }
-    }
-    let (access_token, account_id, refresh_token) = {
-        let store = state.store.lock().map_err(|e| e.to_string())?;
-        let account = store.accounts.get(&id).ok_or("账号不存在")?;
-        let at = AccountStore::extract_access_token(&account.auth_json);
-        let aid = AccountStore::extract_account_id(&account.auth_json);
-        let rt = account.refresh_token.clone();
-        (at, aid, rt)
-    };
-
-    // 如果没有 access_token，先用 refresh_token 换一个
-    let access_token = if let Some(at) = access_token {
-        at
-    } else if let Some(ref rt) = refresh_token {
-        match crate::oauth::refresh_access_token(rt).await {
-            Ok(token_res) => {
-                // 保存新 token
-                let mut store = state.store.lock().map_err(|e| e.to_string())?;
-                if let Some(account) = store.accounts.get_mut(&id) {
-                    AccountStore::apply_refreshed_tokens(
-                        account,
-                        token_res.access_token.clone(),
-                        token_res.refresh_token.clone(),
-                        token_res.id_token,
-                        token_res.expires_in,
-                    );
-                    if let Err(e) = store.save() {
-                        eprintln!("[Store] 保存失败: {}", e);
-                    }
-                }
-                token_res.access_token
-            }
-            Err(e) => return Err(format!("TOKEN_INVALID:刷新 token 失败: {}", e)),
-        }
-    } else {
-        return Err("TOKEN_INVALID:无 access_token 且无 refresh_token".to_string());
-    };
-
-    let result =
-        UsageFetcher::fetch_usage_direct(access_token, account_id, refresh_token, true).await;
-
-    // 检测封号/失效：分开标记
-    if let Err(ref e) = result {
-        if e.contains("ACCOUNT_BANNED") {
-            let mut store = state.store.lock().map_err(|e| e.to_string())?;
-            if let Some(account) = store.accounts.get_mut(&id) {
-                account.is_banned = true;
-                account.is_token_invalid = false;
-                account.is_logged_out = false;
-                if let Err(e) = store.save() {
-                    eprintln!("[Store] 保存失败: {}", e);
-                }
-            }
-            return Err(e.clone());
-        }
-        if e.contains("TOKEN_INVALID") {
-            let mut store = state.store.lock().map_err(|e| e.to_string())?;
-            if let Some(account) = store.accounts.get_mut(&id) {
-                account.is_token_invalid = true;
-                account.is_banned = false;
-                account.is_logged_out = false;
-                if let Err(e) = store.save() {
-                    eprintln!("[Store] 保存失败: {}", e);
-                }
-            }
-            return Err(e.clone());
-        }
-        if e.contains("ACCOUNT_LOGGED_OUT") {
-            let mut store = state.store.lock().map_err(|e| e.to_string())?;
-            if let Some(account) = store.accounts.get_mut(&id) {
-                account.is_logged_out = true;
-                account.is_banned = false;
-                account.is_token_invalid = false;
-                if let Err(e) = store.save() {
-                    eprintln!("[Store] 保存失败: {}", e);
-                }
-            }
-            return Err(e.clone());
-        }
-    }
-
-    let (display, new_tokens) = result?;
-
-    // 如果产生了新 Token，保存
-    if let Some(res) = new_tokens {
-        let mut store = state.store.lock().map_err(|e| e.to_string())?;
-        if let Some(account) = store.accounts.get_mut(&id) {
-            AccountStore::apply_refreshed_tokens(
-                account,
-                res.access_token,
-                res.refresh_token,
-                res.id_token,
-                res.expires_in,
-            );
-            if let Err(e) = store.save() {
-                eprintln!("[Store] 保存失败: {}", e);
-            }
-        }
-    }
-
-    // 更新缓存
-    {
-        let mut store = state.store.lock().map_err(|e| e.to_string())?;
-        if let Some(account) = store.accounts.get_mut(&id) {
-            account.cached_quota = Some(usage_to_cached(&display));
-            if let Err(e) = store.save() {
-                eprintln!("[Store] 保存失败: {}", e);
-            }
-        }
-    }
-
-    Ok(display)
+/// 智能切号（tray popup 调用）
+#[tauri::command]
+async fn switch_to_next_account(
+    state: tauri::State<'_, AppState>,
+    app: tauri::AppHandle,
+) -> Result<(), String> {
+    switch_to_next_account_internal(state, app)
+        .await
+        .map(|_| ())
 }
 
-fn usage_to_cached(u: &UsageDisplay) -> crate::account::CachedQuota {
-    crate::account::CachedQuota {
-        five_hour_left: u.five_hour_left as f64,
-        five_hour_reset: u.five_hour_reset.clone(),
-        five_hour_reset_at: u.five_hour_reset_at,
-        five_hour_label: u.five_hour_label.clone(),
-        weekly_left: u.weekly_left as f64,
-        weekly_reset: u.weekly_reset.clone(),
-        weekly_reset_at: u.weekly_reset_at,
-        weekly_label: u.weekly_label.clone(),
-        plan_type: u.plan_type.clone(),
-        is_valid_for_cli: u.is_valid_for_cli,
-        updated_at: Utc::now(),
-    }
+/// 同 `switch_to_next_account`，返回切到的账号 id 和跳过的候选等警告
+#[tauri::command]
+async fn switch_to_next_account_v2(
+    state: tauri::State<'_, AppState>,
+    app: tauri::AppHandle,
+) -> Result<warnings::Warned<String>, String> {
+    switch_to_next_account_internal(state, app).await
 }
 
-/// 将当前 Codex auth.json 强制同步到指定账号
+/// 将当前 Codex auth.json 强制同步到指定账号；`install` 缺省用默认安装的 auth.json
 #[tauri::command]
-fn sync_current_auth_to_account(state: State<AppState>, id: String) -> Result<(), String> {
-    let auth_json = AccountStore::read_codex_auth()?;
-    let mut store = state.store.lock().map_err(|e| e.to_string())?;
-    if store.sync_account_from_auth_json(&id, auth_json) {
-        store.save()?;
-        return Ok(());
+fn sync_current_auth_to_account(
+    state: State<AppState>,
+    app: tauri::AppHandle,
+    id: String,
+    install: Option<String>,
+) -> Result<(), String> {
+    let service = state.account_service(&app);
+    match install.filter(|name| !codex_installs::is_default(Some(name))) {
+        Some(install) => service.resolve_install_conflict(install.trim(), &id),
+        None => service.resolve_conflict(&id),
     }
-    Err("同步失败：账号不存在或 User ID 不匹配".to_string())
 }
 
 /// 检查 Codex 是否已登录
@@ -2648,256 +4685,260 @@ fn check_codex_login() -> Result<bool, String> {
     Ok(AccountStore::codex_auth_path().exists())
 }
 
-/// 获取指定账号的用量信息（不切换账号）
+/// 获取指定账号的用量信息（不切换账号）；`source` 缺省按手动刷新记
 #[tauri::command]
 async fn get_quota_by_id(
     state: tauri::State<'_, AppState>,
     app: tauri::AppHandle,
     id: String,
+    source: Option<QuotaSource>,
 ) -> Result<UsageDisplay, String> {
-    // Relay 账号：不走 OpenAI usage 路径
-    {
-        let store = state.store.lock().map_err(|e| e.to_string())?;
-        if let Some(acc) = store.accounts.get(&id) {
-            if acc.is_relay() {
-                return Err(
-                    "RELAY_ACCOUNT:中转站账号请用「中转站余额刷新」，不是 OpenAI usage".to_string(),
-                );
-            }
-        }
-    }
-
-    // 当前激活账号：先按 ~/.codex/auth.json 做身份校验与同步，再继续走 API 查询配额
-    let is_current = {
-        let store = state.store.lock().map_err(|e| e.to_string())?;
-        store.current.as_deref() == Some(id.as_str())
-    };
-
-    if is_current {
-        // 手机锚模式（v0.7+）：disk 故意锁在 anchor 上，跟 current 身份不匹配是 BY DESIGN，
-        // 不该把这种状态当冲突。anchor != current 时整段 disk 校验/反向同步直接跳过 ——
-        // proxy 会按 store.current 的 token 路由 quota 请求，store 内本来就是权威值。
-        let anchor_owns_disk = {
-            let store = state.store.lock().map_err(|e| e.to_string())?;
-            store
-                .session_anchor_id()
-                .map(|aid| aid != id)
-                .unwrap_or(false)
-        };
-
-        if anchor_owns_disk {
-            println!(
-                "[Quota] 手机锚生效，disk 归 anchor，跳过 current({}) 的 disk 一致性校验",
-                id
-            );
-        } else {
-            let official_auth = AccountStore::read_codex_auth()?;
-            let mut store = state.store.lock().map_err(|e| e.to_string())?;
-            let local_auth = store
-                .accounts
-                .get(&id)
-                .ok_or_else(|| format!("账号 {} 不存在", id))?
-                .auth_json
-                .clone();
-
-            if !AccountStore::auth_identity_matches(&local_auth, &official_auth) {
-                return Err(
-                    "当前激活账号与 ~/.codex/auth.json 身份不匹配，已拒绝覆盖，请先在 Codex 中切回同一账号".to_string(),
-                );
-            }
-
-            if local_auth != official_auth {
-                println!(
-                    "[Quota] 当前激活账号 {}：检测到官方 auth.json 变更，按权威源同步。",
-                    id
-                );
-                if store.sync_account_from_auth_json(&id, official_auth) {
-                    store.save()?;
-                }
-            } else {
-                println!("[Quota] 当前激活账号 {}：已与官方 auth.json 保持一致。", id);
-            }
-        }
-    }
-
-    // 1. 从 Store 获取该账号的 Token
-    let (access_token_opt, account_id, refresh_token) = {
-        let store = state.store.lock().map_err(|e| e.to_string())?;
-        let account = store
-            .accounts
-            .get(&id)
-            .ok_or_else(|| format!("账号 {} 不存在", id))?;
-
-        let at = AccountStore::extract_access_token(&account.auth_json);
-        let aid = AccountStore::extract_account_id(&account.auth_json);
-        let rt = account
-            .refresh_token
-            .clone()
-            .or_else(|| AccountStore::extract_refresh_token(&account.auth_json));
+    state
+        .account_service(&app)
+        .refresh_quota(&id, source.unwrap_or_default())
+        .await
+}
 
-        (at, aid, rt)
+/// 额度快照分享文本（见 `share_snapshot`）；`include_accounts` 缺省为所有订阅账号
+#[tauri::command]
+fn generate_share_snapshot(
+    state: State<AppState>,
+    format: share_snapshot::ShareFormat,
+    include_accounts: Option<Vec<String>>,
+    emails: Option<share_snapshot::ShareEmails>,
+    include_notes: Option<bool>,
+) -> Result<String, String> {
+    let store = state.store.lock().map_err(|e| e.to_string())?;
+    let accounts: Vec<&Account> = match &include_accounts {
+        Some(ids) => ids
+            .iter()
+            .map(|id| {
+                store
+                    .accounts
+                    .get(id)
+                    .ok_or_else(|| format!("账号不存在: {}", id))
+            })
+            .collect::<Result<_, _>>()?,
+        None => store
+            .accounts
+            .values()
+            .filter(|a| share_snapshot::included_by_default(a))
+            .collect(),
     };
-
-    // 如果没有 access_token，先用 refresh_token 换一个
-    let access_token = if let Some(at) = access_token_opt {
-        at
-    } else if let Some(ref rt) = refresh_token {
-        match crate::oauth::refresh_access_token(rt).await {
-            Ok(token_res) => {
-                let mut store = state.store.lock().map_err(|e| e.to_string())?;
-                if let Some(account) = store.accounts.get_mut(&id) {
-                    AccountStore::apply_refreshed_tokens(
-                        account,
-                        token_res.access_token.clone(),
-                        token_res.refresh_token.clone(),
-                        token_res.id_token,
-                        token_res.expires_in,
-                    );
-                    if let Err(e) = store.save() {
-                        eprintln!("[Store] 保存失败: {}", e);
-                    }
-                }
-                token_res.access_token
-            }
-            Err(e) => return Err(format!("TOKEN_INVALID:刷新 token 失败: {}", e)),
-        }
-    } else {
-        return Err("TOKEN_INVALID:无 access_token 且无 refresh_token".to_string());
+    let options = share_snapshot::ShareOptions {
+        emails: emails.unwrap_or_default(),
+        include_notes: include_notes.unwrap_or(false),
+        presentation: state.presentation.is_enabled(),
     };
+    let snapshot = share_snapshot::build(accounts, &options, chrono::Local::now().fixed_offset());
+    Ok(share_snapshot::render(&snapshot, format))
+}
 
-    // 2. 使用 Token 获取用量（允许自动刷新）
-    let result = UsageFetcher::fetch_usage_direct(
-        access_token,
-        account_id,
-        refresh_token,
-        true, // 允许 refresh，解决 token 过期问题
-    )
-    .await;
-
-    // 检测封号/失效：分开标记
-    if let Err(ref e) = result {
-        if e.contains("ACCOUNT_BANNED") {
-            let mut store = state.store.lock().map_err(|e| e.to_string())?;
-            if let Some(account) = store.accounts.get_mut(&id) {
-                account.is_banned = true;
-                account.is_token_invalid = false;
-                account.is_logged_out = false;
-                if let Err(e) = store.save() {
-                    eprintln!("[Store] 保存失败: {}", e);
-                }
-            }
-            return Err(e.clone());
-        }
-        if e.contains("TOKEN_INVALID") {
-            let mut store = state.store.lock().map_err(|e| e.to_string())?;
-            if let Some(account) = store.accounts.get_mut(&id) {
-                account.is_token_invalid = true;
-                account.is_banned = false;
-                account.is_logged_out = false;
-                if let Err(e) = store.save() {
-                    eprintln!("[Store] 保存失败: {}", e);
-                }
-            }
-            return Err(e.clone());
-        }
-        if e.contains("ACCOUNT_LOGGED_OUT") {
-            let mut store = state.store.lock().map_err(|e| e.to_string())?;
-            if let Some(account) = store.accounts.get_mut(&id) {
-                account.is_logged_out = true;
-                account.is_banned = false;
-                account.is_token_invalid = false;
-                if let Err(e) = store.save() {
-                    eprintln!("[Store] 保存失败: {}", e);
-                }
+/// `compare_accounts` 先刷新额度时每个账号的时限
+const COMPARE_REFRESH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// 两个账号逐项对比（见 `account_compare`）。默认只用缓存；`refresh_first` 时先按正常流程
+/// 并发刷新两边额度（走刷新锁、各自限时），刷新失败不影响对比，原因放在 `refresh_errors`
+#[tauri::command]
+async fn compare_accounts(
+    state: tauri::State<'_, AppState>,
+    app: tauri::AppHandle,
+    id_a: String,
+    id_b: String,
+    refresh_first: Option<bool>,
+) -> Result<account_compare::AccountComparison, String> {
+    if id_a == id_b {
+        return Err("请选择两个不同的账号".to_string());
+    }
+    let mut refresh_errors = Vec::new();
+    if refresh_first.unwrap_or(false) {
+        let service = state.account_service(&app);
+        let refresh = |id: &str| {
+            tokio::time::timeout(
+                COMPARE_REFRESH_TIMEOUT,
+                service.refresh_quota(id, QuotaSource::ManualRefresh),
+            )
+        };
+        let (ra, rb) = tokio::join!(refresh(&id_a), refresh(&id_b));
+        for (id, result) in [(&id_a, ra), (&id_b, rb)] {
+            match result {
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => refresh_errors.push(format!("{}: {}", id, e)),
+                Err(_) => refresh_errors.push(format!("{}: 刷新超时", id)),
             }
-            return Err(e.clone());
         }
+        let _ = app.emit("accounts-updated", ());
     }
+    let store = state.store.lock().map_err(|e| e.to_string())?;
+    let get = |id: &str| {
+        store
+            .accounts
+            .get(id)
+            .ok_or_else(|| format!("账号 {} 不存在", id))
+    };
+    let mut comparison = account_compare::compare(get(&id_a)?, get(&id_b)?, Utc::now());
+    comparison.refresh_errors = refresh_errors;
+    Ok(comparison)
+}
 
-    let (usage, new_tokens) = result?;
-
-    // 3. 如果有新 Token，更新该账号的数据
-    if let Some(tokens) = new_tokens {
-        let mut store = state.store.lock().map_err(|e| e.to_string())?;
-        if let Some(account) = store.accounts.get_mut(&id) {
-            // 更新 auth_json 中的 Token 信息
-            if let Some(obj) = account.auth_json.as_object_mut() {
-                if let Some(tokens_obj) = obj.get_mut("tokens").and_then(|v| v.as_object_mut()) {
-                    tokens_obj.insert(
-                        "access_token".to_string(),
-                        serde_json::json!(tokens.access_token),
-                    );
-
-                    if let Some(rt) = &tokens.refresh_token {
-                        tokens_obj.insert("refresh_token".to_string(), serde_json::json!(rt));
-                    } else if let Some(rt) = account.refresh_token.as_deref() {
-                        if tokens_obj.get("refresh_token").is_none() {
-                            tokens_obj.insert("refresh_token".to_string(), serde_json::json!(rt));
-                        }
-                    }
+/// 开发者调试：取某账号最近几次 usage 接口原始响应（已脱敏）及对应的解析结果，
+/// 方便把两者一起贴进 bug report。需先在设置里打开 `debug_capture_responses`。
+#[tauri::command]
+fn get_last_usage_response(
+    state: State<AppState>,
+    id: String,
+) -> Result<usage_debug::UsageDebugReport, String> {
+    let store = state.store.lock().map_err(|e| e.to_string())?;
+    let account = store
+        .accounts
+        .get(&id)
+        .ok_or_else(|| format!("账号 {} 不存在", id))?;
+    let key = usage_debug::identity_key(
+        AccountStore::extract_account_id(&account.auth_json).as_deref(),
+        AccountStore::extract_openai_user_id(&account.auth_json).as_deref(),
+    );
+    let capture = usage_debug::global();
+    Ok(usage_debug::UsageDebugReport {
+        account_id: id,
+        capture_enabled: capture.is_enabled(),
+        captures: capture.recent(&key),
+    })
+}
 
-                    if let Some(it) = &tokens.id_token {
-                        tokens_obj.insert("id_token".to_string(), serde_json::json!(it));
-                    }
+/// Shell 提示符状态行（只读内存里的 store + cached_quota，不联网）
+#[tauri::command]
+fn get_status_line(state: State<AppState>, format: Option<String>) -> Result<String, String> {
+    let store = state.store.lock().map_err(|e| e.to_string())?;
+    Ok(status_line::from_store(&store, format.as_deref()))
+}
 
-                    if let Some(expires_in) = tokens.expires_in {
-                        let expires_at = (chrono::Utc::now()
-                            + chrono::Duration::seconds(expires_in as i64))
-                        .to_rfc3339();
-                        tokens_obj.insert("expires_at".to_string(), serde_json::json!(expires_at));
-                    }
-                }
-            }
+/// 导出脱敏诊断包（提 issue 用）；`path` 为空写到 `~/.codex-switcher/diagnostics/`
+#[tauri::command]
+fn create_diagnostics_bundle(
+    state: State<AppState>,
+    path: Option<String>,
+) -> Result<diagnostics::BundleManifest, String> {
+    let running = |h: &std::sync::Mutex<Option<tauri::async_runtime::JoinHandle<()>>>| {
+        h.lock().map(|h| h.is_some()).unwrap_or(false)
+    };
+    let health = serde_json::json!({
+        "scheduler_running": state.scheduler.is_running(),
+        "scheduler_status": state.scheduler.status(),
+        "quota_refresh_running": running(&state.quota_refresh_handle),
+        "remote_server_running": running(&state.remote_server_handle),
+        "solo_heartbeat_running": running(&state.solo_heartbeat_handle),
+        "proxy_running": running(&state.proxy_handle),
+        "proxy_total_requests": state
+            .proxy_stats
+            .total_requests
+            .load(std::sync::atomic::Ordering::Relaxed),
+        "proxy_auto_switches": state
+            .proxy_stats
+            .auto_switches
+            .load(std::sync::atomic::Ordering::Relaxed),
+        "usage_capture_enabled": usage_debug::global().is_enabled(),
+        "scheduler_deferred": state.foreground.is_deferred(),
+        "scheduler_pacing": state.pacer.health(),
+        "clock": clock::global().health(),
+        "auth_permissions": auth_permissions::global().health(),
+    });
+    let store = state.store.lock().map_err(|e| e.to_string())?;
+    let manifest = diagnostics::create(&store, health, path.map(std::path::PathBuf::from))?;
+    println!("[Diagnostics] 诊断包已导出: {}", manifest.path);
+    Ok(manifest)
+}
 
-            // 更新 refresh_token 字段
-            if let Some(rt) = tokens.refresh_token {
-                account.refresh_token = Some(rt);
-            }
-            if let Some(obj) = account.auth_json.as_object_mut() {
-                obj.insert(
-                    "last_refresh".to_string(),
-                    serde_json::json!(Utc::now().to_rfc3339()),
-                );
-            }
+/// auth.json 写入记录，新的在前（只有 token 指纹，见 auth_journal）
+#[tauri::command]
+fn get_auth_journal(limit: Option<usize>) -> Vec<auth_journal::JournalEntry> {
+    auth_journal::AuthJournal::system().read(limit.unwrap_or(100))
+}
 
-            // 更新配额缓存
-            account.cached_quota = Some(account::CachedQuota {
-                five_hour_left: usage.five_hour_left as f64,
-                five_hour_reset: usage.five_hour_reset.clone(),
-                five_hour_reset_at: usage.five_hour_reset_at,
-                five_hour_label: usage.five_hour_label.clone(),
-                weekly_left: usage.weekly_left as f64,
-                weekly_reset: usage.weekly_reset.clone(),
-                weekly_reset_at: usage.weekly_reset_at,
-                weekly_label: usage.weekly_label.clone(),
-                plan_type: usage.plan_type.clone(),
-                is_valid_for_cli: usage.is_valid_for_cli,
-                updated_at: Utc::now(),
-            });
-        }
-        store.save()?;
-    } else {
-        // 即使没有新 Token，也更新配额缓存
+/// 维护：按存档 id_token 重新推导每个账号的工作区 id（tokens.account_id）。
+/// `apply` 为 false 时只返回需要修正的账号，不落盘。
+#[tauri::command]
+fn reconcile_account_ids(
+    state: State<AppState>,
+    app: tauri::AppHandle,
+    apply: bool,
+) -> Result<Vec<account::AccountIdChange>, String> {
+    let changes = {
         let mut store = state.store.lock().map_err(|e| e.to_string())?;
-        if let Some(account) = store.accounts.get_mut(&id) {
-            account.cached_quota = Some(account::CachedQuota {
-                five_hour_left: usage.five_hour_left as f64,
-                five_hour_reset: usage.five_hour_reset.clone(),
-                five_hour_reset_at: usage.five_hour_reset_at,
-                five_hour_label: usage.five_hour_label.clone(),
-                weekly_left: usage.weekly_left as f64,
-                weekly_reset: usage.weekly_reset.clone(),
-                weekly_reset_at: usage.weekly_reset_at,
-                weekly_label: usage.weekly_label.clone(),
-                plan_type: usage.plan_type.clone(),
-                is_valid_for_cli: usage.is_valid_for_cli,
-                updated_at: Utc::now(),
-            });
+        let changes = store.reconcile_account_ids(apply);
+        if apply && !changes.is_empty() {
+            store.save()?;
         }
-        store.save()?;
+        changes
+    };
+    for c in &changes {
+        println!(
+            "[Reconcile] {} account_id {:?} → {}{}",
+            log_identity::id(&c.name),
+            c.old_account_id.as_deref().map(log_identity::id),
+            log_identity::id(&c.new_account_id),
+            if apply { "（已修正）" } else { "" }
+        );
+    }
+    if apply && !changes.is_empty() {
+        proxy::invalidate_remote_token_cache();
+        let _ = app.emit("accounts-updated", ());
+    }
+    Ok(changes)
+}
+
+/// 诊断：当前生效的数据目录，以及每个候选为什么被选中 / 跳过
+#[tauri::command]
+fn get_paths() -> paths::Paths {
+    paths::Paths {
+        codex_dir: paths::codex_dir(),
+        ..paths::current()
     }
+}
 
-    Ok(usage)
+/// 一键修复：按顺序跑权限、当前账号指针、auth.json refresh_token、调度器、残留文件、
+/// Codex App 隔离属性几项检查；`dry_run` 时只报告将会做什么。需要 sudo 或涉及手机锚的只给出操作说明
+#[tauri::command]
+fn repair_setup(
+    state: State<AppState>,
+    app: tauri::AppHandle,
+    dry_run: bool,
+) -> Result<Vec<repair::RepairReport>, String> {
+    let (background_refresh, scheduler_alive) = {
+        let enabled = state
+            .store
+            .lock()
+            .map_err(|e| e.to_string())?
+            .settings
+            .background_refresh;
+        (enabled, state.scheduler.is_running())
+    };
+    let mut steps: Vec<Box<dyn repair::RepairStep + '_>> = vec![
+        Box::new(repair::PermissionStep::system()),
+        Box::new(repair::CurrentPointerStep::new(state.store.clone())),
+        Box::new(repair::DiskRefreshTokenStep::new(state.store.clone())),
+        Box::new(repair::SchedulerStep::new(
+            background_refresh,
+            scheduler_alive,
+            || {
+                // 只在循环已停或崩溃时才会走到这里，start 会换上新的
+                start_scheduler(&state, &app);
+                Ok(())
+            },
+        )),
+        Box::new(repair::StaleFilesStep::system(state.store.clone())),
+        Box::new(repair::QuarantineStep),
+    ];
+    let reports = repair::run(&mut steps, dry_run);
+    drop(steps);
+    if reports
+        .iter()
+        .any(|r| r.status == repair::RepairStatus::Fixed)
+    {
+        proxy::invalidate_remote_token_cache();
+        let _ = app.emit("accounts-updated", ());
+        tray::update_tray_menu(&app);
+    }
+    Ok(reports)
 }
 
 /// 修复 Codex App 的隔离属性 (需要 sudo 权限)
@@ -3011,6 +5052,8 @@ struct AccountTokenHistory {
     cycles_5h: Vec<CycleDetail>,
     /// 周周期全量历史，倒序
     cycles_week: Vec<CycleDetail>,
+    /// 仅当前账号：按近期 5h 用量速率预计多久耗尽
+    exhaustion_estimate: Option<quota_snapshot::ExhaustionEstimate>,
 }
 
 #[tauri::command]
@@ -3100,11 +5143,21 @@ fn get_account_token_history(
                 .cloned()
         };
 
+        let is_current = current_id.as_ref() == Some(id);
+        let exhaustion_estimate = if is_current {
+            quota_snapshot::estimate_exhaustion(
+                &quota_snapshot::five_hour_samples(&snapshots, id),
+                chrono::Utc::now().timestamp(),
+            )
+        } else {
+            None
+        };
+
         out.push(AccountTokenHistory {
             account_id: id.clone(),
             email,
             plan_type: cq.plan_type.clone(),
-            is_current: current_id.as_ref() == Some(id),
+            is_current,
             is_banned: acc.is_banned,
             is_token_invalid: acc.is_token_invalid,
             current_5h,
@@ -3113,6 +5166,7 @@ fn get_account_token_history(
             last_week,
             cycles_5h,
             cycles_week,
+            exhaustion_estimate,
         });
     }
 
@@ -3306,7 +5360,7 @@ fn build_cycles(
     let has_current = result.iter().any(|c| c.is_current);
     println!(
         "[AcctHist] {} {} entries={} reset_at={:?} cycles={} has_current={}",
-        account_name,
+        log_identity::id(&account_name),
         label,
         entries.len(),
         reset_at_opt,
@@ -3840,11 +5894,7 @@ fn get_skill_app_status() -> Result<std::collections::HashMap<String, bool>, Str
 
 #[tauri::command]
 fn get_skill_content(directory: String) -> Result<String, String> {
-    let ssot = dirs::home_dir()
-        .unwrap()
-        .join(".codex-switcher")
-        .join("skills")
-        .join(&directory);
+    let ssot = paths::data_dir().join("skills").join(&directory);
     let md_path = ssot.join("SKILL.md");
     std::fs::read_to_string(&md_path).map_err(|e| format!("读取失败: {}", e))
 }
@@ -3865,6 +5915,75 @@ fn sync_all_skills() -> Result<(), String> {
     Ok(())
 }
 
+/// 前端 `ipc.ts` 在任何命令失败时回报到这里（错误中心的命令侧采集端）
+#[tauri::command]
+fn record_command_error(
+    state: State<AppState>,
+    command: String,
+    error: String,
+    account_id: Option<String>,
+) -> Result<String, String> {
+    Ok(state.errors.record(
+        error_center::ErrorOrigin::Command(command),
+        account_id.as_deref(),
+        &error,
+    ))
+}
+
+/// 最近的错误（新的在前），返回的同时标为已读
+#[tauri::command]
+fn get_recent_errors(
+    state: State<AppState>,
+    limit: Option<usize>,
+) -> Result<Vec<error_center::ErrorRecord>, String> {
+    Ok(state.errors.recent(limit.unwrap_or(error_center::CAPACITY)))
+}
+
+#[tauri::command]
+fn dismiss_error(state: State<AppState>, id: String) -> Result<(), String> {
+    if state.errors.dismiss(&id) {
+        Ok(())
+    } else {
+        Err("错误记录不存在".to_string())
+    }
+}
+
+#[tauri::command]
+fn clear_errors(state: State<AppState>) -> Result<(), String> {
+    state.errors.clear();
+    Ok(())
+}
+
+/// 角标状态：只有计数，供前端 / 托盘频繁轮询
+#[tauri::command]
+fn get_badge_state(state: State<AppState>) -> Result<error_center::BadgeState, String> {
+    Ok(state.errors.badge())
+}
+
+/// 活动流：新的在前，可按分类筛选；翻页时 `before` 传上一页最后一条的时间。
+/// 演示模式下账号名打码
+#[tauri::command]
+fn get_activity_feed(
+    state: State<AppState>,
+    category: Option<activity_feed::ActivityCategory>,
+    limit: Option<usize>,
+    before: Option<chrono::DateTime<Utc>>,
+) -> Result<Vec<activity_feed::ActivityEntry>, String> {
+    let mut entries = state.activity.query(
+        category,
+        limit.unwrap_or(activity_feed::DEFAULT_LIMIT),
+        before,
+    );
+    if state.presentation.is_enabled() {
+        for entry in &mut entries {
+            if let Some(name) = entry.params.get_mut("account_name") {
+                *name = presentation::mask_name(name);
+            }
+        }
+    }
+    Ok(entries)
+}
+
 #[tauri::command]
 fn get_switch_history(
     state: State<AppState>,
@@ -3885,6 +6004,12 @@ fn show_main_window_cmd(app: tauri::AppHandle) {
     crate::tray::show_main_window_from_cmd(&app);
 }
 
+/// 取走托盘留给主窗口的导航意图（前端加载时、收到 `navigation-pending` 时调用）
+#[tauri::command]
+fn get_pending_navigation(state: State<AppState>) -> Vec<navigation::NavIntent> {
+    state.navigation.drain()
+}
+
 /// 杀死所有 codex 相关进程（排除 Codex Switcher 自身）
 #[tauri::command]
 fn kill_codex_processes() -> Result<String, String> {
@@ -3919,7 +6044,7 @@ fn kill_codex_processes() -> Result<String, String> {
 /// 设置 OPENAI_BASE_URL 环境变量（终端 + GUI 应用全覆盖）
 #[tauri::command]
 fn set_proxy_env(port: u16, enable: bool) -> Result<String, String> {
-    let home = dirs::home_dir().ok_or("无法获取用户目录")?;
+    let home = paths::home_dir();
     let env_value = format!("http://localhost:{}/v1", port);
     let env_line = format!("export OPENAI_BASE_URL={}", env_value);
     let marker = "# codex-switcher-proxy";
@@ -3984,10 +6109,7 @@ fn set_proxy_env(port: u16, enable: bool) -> Result<String, String> {
 
 /// 读写 ~/.codex/config.toml 的 openai_base_url 字段
 fn set_codex_config_base_url(url: Option<&str>) -> Result<(), String> {
-    let config_path = dirs::home_dir()
-        .ok_or("无法获取用户目录")?
-        .join(".codex")
-        .join("config.toml");
+    let config_path = paths::codex_dir().join("config.toml");
 
     if !config_path.exists() {
         if url.is_some() {
@@ -4047,10 +6169,7 @@ fn set_codex_config_base_url(url: Option<&str>) -> Result<(), String> {
 /// 切换 Codex fast 模式（修改 config.toml 的 profile 字段）
 #[tauri::command]
 fn set_codex_fast_mode(enable: bool) -> Result<String, String> {
-    let config_path = dirs::home_dir()
-        .ok_or("无法获取用户目录")?
-        .join(".codex")
-        .join("config.toml");
+    let config_path = paths::codex_dir().join("config.toml");
 
     if !config_path.exists() {
         return Err("~/.codex/config.toml 不存在".to_string());
@@ -4094,10 +6213,7 @@ fn set_codex_fast_mode(enable: bool) -> Result<String, String> {
 /// 切换 ~/.codex/config.toml 里的 [features] goals 开关
 #[tauri::command]
 fn set_codex_features_goals(enable: bool) -> Result<String, String> {
-    let config_path = dirs::home_dir()
-        .ok_or("无法获取用户目录")?
-        .join(".codex")
-        .join("config.toml");
+    let config_path = paths::codex_dir().join("config.toml");
 
     let content = if config_path.exists() {
         std::fs::read_to_string(&config_path)
@@ -4163,10 +6279,7 @@ fn set_codex_features_goals(enable: bool) -> Result<String, String> {
 /// 读 ~/.codex/config.toml 里的 [features] goals 开关
 #[tauri::command]
 fn get_codex_features_goals() -> Result<bool, String> {
-    let config_path = dirs::home_dir()
-        .ok_or("无法获取用户目录")?
-        .join(".codex")
-        .join("config.toml");
+    let config_path = paths::codex_dir().join("config.toml");
 
     if !config_path.exists() {
         return Ok(false);
@@ -4192,10 +6305,7 @@ fn get_codex_features_goals() -> Result<bool, String> {
 /// 获取当前 fast 模式状态
 #[tauri::command]
 fn get_codex_fast_mode() -> Result<bool, String> {
-    let config_path = dirs::home_dir()
-        .ok_or("无法获取用户目录")?
-        .join(".codex")
-        .join("config.toml");
+    let config_path = paths::codex_dir().join("config.toml");
 
     if !config_path.exists() {
         return Ok(false);
@@ -4219,11 +6329,22 @@ pub struct SyncStatus {
     pub disk_email: Option<String>,
     pub matching_id: Option<String>,
     pub current_id: Option<String>,
+    /// 演示模式指示，前端据此显示横幅
+    pub presentation_mode: bool,
 }
 
 /// 检查 IDE 磁盘状态与内存状态的同步情况
 #[tauri::command]
 fn get_sync_status(state: State<AppState>) -> Result<SyncStatus, String> {
+    let mut status = read_sync_status(&state)?;
+    status.presentation_mode = state.presentation.is_enabled();
+    if status.presentation_mode {
+        status.disk_email = status.disk_email.map(|e| presentation::mask_email(&e));
+    }
+    Ok(status)
+}
+
+fn read_sync_status(state: &AppState) -> Result<SyncStatus, String> {
     let disk_auth = match AccountStore::read_codex_auth() {
         Ok(a) => a,
         Err(_) => {
@@ -4233,6 +6354,7 @@ fn get_sync_status(state: State<AppState>) -> Result<SyncStatus, String> {
                 disk_email: None,
                 matching_id: None,
                 current_id: store.current.clone(),
+                presentation_mode: false,
             });
         }
     };
@@ -4262,6 +6384,7 @@ fn get_sync_status(state: State<AppState>) -> Result<SyncStatus, String> {
                         disk_email: None,
                         matching_id: store.current.clone(),
                         current_id: store.current.clone(),
+                        presentation_mode: false,
                     });
                 }
             }
@@ -4290,6 +6413,7 @@ fn get_sync_status(state: State<AppState>) -> Result<SyncStatus, String> {
             disk_email,
             matching_id: store.current.clone(),
             current_id: store.current.clone(),
+            presentation_mode: false,
         });
     }
 
@@ -4328,6 +6452,7 @@ fn get_sync_status(state: State<AppState>) -> Result<SyncStatus, String> {
         disk_email,
         matching_id,
         current_id: store.current.clone(),
+        presentation_mode: false,
     })
 }
 
@@ -4336,7 +6461,6 @@ fn get_sync_status(state: State<AppState>) -> Result<SyncStatus, String> {
 #[tauri::command]
 fn sync_active_with_disk(state: State<AppState>, app: tauri::AppHandle) -> Result<(), String> {
     let disk_auth = AccountStore::read_codex_auth()?;
-    let disk_email = AccountStore::extract_email(&disk_auth);
     let mut store = state.store.lock().map_err(|e| e.to_string())?;
 
     // 手机锚模式（v0.7+）：disk 故意锁在 anchor 上，"按 disk 对齐 current" 等于
@@ -4352,30 +6476,8 @@ fn sync_active_with_disk(state: State<AppState>, app: tauri::AppHandle) -> Resul
         }
     }
 
-    // 优先用 JWT Email 匹配（最可靠），其次才用 account_id
-    let matching_id = disk_email
-        .as_deref()
-        .and_then(|email| {
-            let email_lower = email.to_lowercase();
-            store
-                .accounts
-                .values()
-                .find(|a| {
-                    AccountStore::extract_email(&a.auth_json)
-                        .map(|e| e.to_lowercase() == email_lower)
-                        .unwrap_or(false)
-                        || a.name.to_lowercase() == email_lower
-                })
-                .map(|a| a.id.clone())
-        })
-        .or_else(|| {
-            // fallback: account_id 匹配
-            store
-                .accounts
-                .values()
-                .find(|a| AccountStore::auth_identity_matches(&a.auth_json, &disk_auth))
-                .map(|a| a.id.clone())
-        })
+    let matching_id = store
+        .find_account_for_disk_auth(&disk_auth)
         .ok_or_else(|| "磁盘账号不在管理列表中，请先导入".to_string())?;
 
     // 安全：只改指针，不覆盖 Token。避免封号 Token 污染好号。
@@ -4583,7 +6685,11 @@ async fn remote_pull_all_tokens(
                     let _ = store.save();
                 }
                 println!("[RemotePull] 手机锚生效，跳过写 ~/.codex/auth.json（current={} != anchor）", cid);
-            } else if let Err(e) = account::AccountStore::write_codex_auth(&auth) {
+            } else if let Err(e) = account::AccountStore::write_codex_auth(
+                &auth,
+                AuthWriteOp::RemoteSync,
+                Some(cid.as_str()),
+            ) {
                 errors.push((cid.clone(), format!("写 auth.json 失败: {}", e)));
             } else {
                 wrote_auth_json = true;
@@ -4627,17 +6733,26 @@ async fn remote_refresh_account_quota(
     state: State<'_, AppState>,
     app: tauri::AppHandle,
     id: String,
+    source: Option<QuotaSource>,
 ) -> Result<UsageDisplay, String> {
+    let source = source.unwrap_or_default();
     let (url, secret) = client_settings_snapshot(&state).await?;
     match remote_client::refresh_account_quota(&url, &secret, &id).await {
         Ok(usage) => {
             if let Ok(mut store) = state.store.lock() {
                 if let Some(acc) = store.accounts.get_mut(&id) {
-                    acc.cached_quota = Some(usage_to_cached(&usage));
+                    acc.set_cached_quota((&usage, source).into());
+                    // 封禁 / 失效 / 登出标记的解除是状态变化，立即落盘；只更新额度的走合并写
+                    let cleared = acc.is_banned || acc.is_token_invalid || acc.is_logged_out;
                     acc.is_banned = false;
                     acc.is_token_invalid = false;
                     acc.is_logged_out = false;
-                    let _ = store.save();
+                    if !cleared {
+                        store.mark_dirty();
+                    } else if let Err(e) = store.save() {
+                        eprintln!("[Store] 保存失败: {}", e);
+                        store.mark_dirty();
+                    }
                 }
             }
             let _ = app.emit("accounts-updated", ());
@@ -4646,6 +6761,10 @@ async fn remote_refresh_account_quota(
         Err(e) => {
             // Server 那边可能根本没有这个账号（典型场景：刚批量导入到本机的账号还没推到 Server）
             // → fallback 到本地直查，用本机 store 里的 token / refresh_token 跑一次 fetch_usage_direct
+            // Server 报上游故障时本机直连也是同一个上游，不 fallback，保留旧缓存
+            if usage::is_upstream_unavailable(&e) {
+                return Err(e);
+            }
             let lower = e.to_lowercase();
             let is_missing = lower.contains("not_found")
                 || lower.contains("not found")
@@ -4658,7 +6777,7 @@ async fn remote_refresh_account_quota(
                 "[Quota] Server 没有账号 {}，fallback 到本地直查（可能是刚导入未推 Server）",
                 id
             );
-            get_quota_by_id(state, app, id).await
+            get_quota_by_id(state, app, id, Some(source)).await
         }
     }
 }
@@ -4783,25 +6902,66 @@ fn restore_anchor_disk_on_exit(reason: &str) {
     }
 }
 
+/// 数据目录全部不可用：终端启动直接打到 stderr；GUI 启动则起一个只有错误弹窗的空应用，
+/// 用户关掉弹窗即退出。
+fn report_startup_error(err: &paths::PathsError, context: tauri::Context<tauri::Wry>) {
+    use std::io::IsTerminal;
+
+    let message = err.to_string();
+    eprintln!("[Startup] {}", message);
+    if std::io::stderr().is_terminal() {
+        std::process::exit(1);
+    }
+
+    let _ = tauri::Builder::default()
+        .plugin(tauri_plugin_dialog::init())
+        .setup(move |app| {
+            use tauri_plugin_dialog::{DialogExt, MessageDialogKind};
+            let handle = app.handle().clone();
+            app.dialog()
+                .message(message.clone())
+                .title("Codex Switcher 无法启动")
+                .kind(MessageDialogKind::Error)
+                .show(move |_| handle.exit(1));
+            Ok(())
+        })
+        .run(context);
+    std::process::exit(1);
+}
+
 pub fn run() {
+    let context = tauri::generate_context!();
+
+    // 先解析数据目录，后面所有路径（账号库、日志、备份）都从这里取
+    let resolved = match paths::init() {
+        Ok(p) => p,
+        Err(e) => {
+            report_startup_error(&e, context);
+            return;
+        }
+    };
+
     // 把 stdout/stderr 重定向到 ~/.codex-switcher/proxy.log
     // 兼容 GUI 启动（Mac App double-click / Tauri build），让所有 println! / eprintln! 落盘
-    if let Some(home) = dirs::home_dir() {
-        let dir = home.join(".codex-switcher");
-        let _ = std::fs::create_dir_all(&dir);
-        let log_path = dir.join("proxy.log");
-        if let Ok(file) = std::fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&log_path)
-        {
-            redirect_stdout_stderr_to_file(file);
-            eprintln!(
-                "\n=== codex-switcher started {} pid={} ===",
-                chrono::Utc::now().to_rfc3339(),
-                std::process::id()
-            );
-        }
+    let log_path = resolved.data_dir.join("proxy.log");
+    if let Ok(file) = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&log_path)
+    {
+        redirect_stdout_stderr_to_file(file);
+        eprintln!(
+            "\n=== codex-switcher started {} pid={} ===",
+            chrono::Utc::now().to_rfc3339(),
+            std::process::id()
+        );
+    }
+    if resolved.source != paths::PathSource::HomeEnv {
+        eprintln!(
+            "[Startup] 数据目录来自 {:?}: {}",
+            resolved.source,
+            resolved.home.display()
+        );
     }
 
     // panic 兜底：跟 RunEvent::Exit 走同一条恢复路径
@@ -4848,6 +7008,8 @@ pub fn run() {
                 eprintln!("初始化托盘失败: {:?}", e);
             }
 
+            recover_pending_switch(app.handle());
+
             // 启动后台调度器（仅在设置开启时）
             let state = app.state::<AppState>();
             let should_start = state
@@ -4856,9 +7018,7 @@ pub fn run() {
                 .map(|store| store.settings.background_refresh)
                 .unwrap_or(false);
             if should_start {
-                let handle = scheduler::start(state.store.clone(), app.handle().clone());
-                let mut scheduler_handle = state.scheduler.lock().unwrap();
-                *scheduler_handle = Some(handle);
+                start_scheduler(&state, app.handle());
             } else {
                 println!("[Scheduler] 后台刷新未开启，跳过启动");
             }
@@ -4945,6 +7105,24 @@ pub fn run() {
                 println!("[QuotaRefresh] 启动中（setup 阶段）");
             }
 
+            // 事件总线：accounts-updated → 类型化事件 → 前端 / SSE
+            start_event_fanout(app);
+            start_activity_feed(app);
+            start_store_persister(app);
+            start_error_capture(app);
+            start_revert_timer(app);
+
+            // 启动时先确认系统时间可信（RTC 偏慢、NTP 未校准时暂停按过期时间刷新）
+            power::check_clock(&state.store, app.handle());
+
+            // 后台探测一次本机 Codex 版本，账号列表的兼容性判定直接读缓存
+            std::thread::spawn(|| {
+                codex_compat::global().detected(false);
+            });
+
+            // 睡眠/唤醒检测：唤醒后立即对齐 auth.json 与额度，不等下一个 tick
+            power::start(state.store.clone(), app.handle().clone());
+
             // 启动时立刻跑一次同步，把 store/disk 不一致 + 落后的 RT 立即对齐
             let store_for_init = state.store.clone();
             tauri::async_runtime::spawn(async move {
@@ -5001,10 +7179,16 @@ pub fn run() {
                     match remote_client::upsert_account(&url, &secret, &acc).await {
                         Ok(o) => println!(
                             "[RelayPushOnStart] {} → {} ({})",
-                            acc.name, o.id, o.upserted
+                            log_identity::id(&acc.name),
+                            o.id,
+                            o.upserted
                         ),
                         Err(e) => {
-                            eprintln!("[RelayPushOnStart] {} 失败: {}", acc.name, e)
+                            eprintln!(
+                                "[RelayPushOnStart] {} 失败: {}",
+                                log_identity::id(&acc.name),
+                                e
+                            )
                         }
                     }
                 }
@@ -5012,6 +7196,18 @@ pub fn run() {
             // 快速 auth.json 同步循环（client 模式专用，但循环内自检模式，可以无脑启动）
             let _fast_auth_handle = start_fast_auth_sync(state.store.clone());
 
+            // 每日额度报告（未开启时空转）
+            let _daily_report_handle =
+                scheduler::start_daily_report(state.store.clone(), app.handle().clone());
+
+            // refresh_token 老化检查（阈值为 0 时空转）
+            let _token_aging_handle =
+                scheduler::start_token_aging(state.store.clone(), app.handle().clone());
+
+            // 每日自动备份账号库（未开启时空转）
+            let _auto_backup_handle =
+                scheduler::start_auto_backup(state.store.clone(), app.handle().clone());
+
             // 手机锚保活循环（无 anchor 时空转，不影响无该功能的用户）
             let _anchor_handle = scheduler::start_anchor_refresh(
                 state.store.clone(),
@@ -5059,6 +7255,9 @@ pub fn run() {
             // 拦截关闭事件，改为隐藏窗口并从 Dock 隐藏
             if let tauri::WindowEvent::CloseRequested { api, .. } = event {
                 let _ = window.hide();
+                if let Some(state) = window.try_state::<AppState>() {
+                    flush_store(&state.store, "Hide");
+                }
                 // macOS: 隐藏 Dock 图标，变成纯后台托盘应用
                 #[cfg(target_os = "macos")]
                 {
@@ -5069,36 +7268,98 @@ pub fn run() {
                 api.prevent_close();
             }
         })
-        .invoke_handler(tauri::generate_handler![
+        .invoke_handler(guard_presentation_mode(tauri::generate_handler![
             get_accounts,
+            get_accounts_page,
+            list_accounts_filtered,
+            get_account,
+            search_accounts,
+            get_accounts_changed_since,
             get_current_account_id,
             import_current_account,
             switch_account,
+            switch_account_v2,
+            switch_account_temporarily,
+            get_pending_revert,
+            cancel_pending_revert,
+            resolve_account,
+            run_hooks_dry,
+            get_codex_compatibility,
+            get_account_statuses,
+            verify_account_integrity,
+            verify_all_accounts,
             sync_current_auth_to_account,
             delete_account,
+            request_delete_accounts_ticket,
+            delete_accounts,
+            undo,
+            get_trash,
+            restore_account,
+            purge_trash,
             update_account,
             update_relay_usage_cookie,
             set_account_inactive_refresh_enabled,
+            set_account_color,
+            update_account_appearance,
+            set_account_codex_profile,
+            set_account_tags,
+            set_account_metadata,
+            delete_account_metadata,
+            pin_account,
+            request_unprotect_ticket,
+            set_account_protected,
+            archive_account,
+            find_duplicate_accounts,
+            get_account_switch_history,
+            clone_account,
+            merge_accounts,
+            reorder_accounts,
+            set_account_request_headers,
             set_session_anchor,
             export_accounts,
             import_accounts,
+            import_accounts_async,
+            import_auth_directory,
+            discover_external_logins,
+            adopt_discovered_login,
+            cancel_import,
             add_relay_account,
             update_relay_model_map,
             refresh_relay_usage,
             bulk_import_accounts,
             check_codex_login,
             get_quota_by_id,
+            compare_accounts,
+            generate_share_snapshot,
+            get_last_usage_response,
+            get_paths,
+            get_store_health,
+            create_diagnostics_bundle,
+            get_pending_navigation,
+            switch_to_next_account,
+            switch_to_next_account_v2,
+            get_auth_journal,
+            reconcile_account_ids,
+            get_status_line,
             oauth_server::start_oauth_login,
             oauth_server::submit_oauth_callback,
             oauth_server::copy_to_clipboard,
             session_import::import_chatgpt_session,
             solo_sync_current,
             finalize_oauth_login,
+            start_bulk_login,
+            pause_bulk_login,
+            resume_bulk_login,
+            cancel_bulk_login,
+            get_bulk_login_status,
             force_overwrite_disk_with_current,
             start_otp_login_batch,
             reload_ide_windows,
             get_settings,
+            render_notes_template,
             update_settings,
+            export_settings,
+            import_settings,
             get_proxy_status,
             kill_codex_processes,
             set_proxy_env,
@@ -5122,6 +7383,12 @@ pub fn run() {
             list_codex_sessions,
             detect_active_codex_session,
             force_auth_resync,
+            get_activity_feed,
+            record_command_error,
+            get_recent_errors,
+            dismiss_error,
+            clear_errors,
+            get_badge_state,
             get_switch_history,
             get_switch_stats,
             get_installed_skills,
@@ -5137,7 +7404,28 @@ pub fn run() {
             scan_and_import_skills,
             sync_all_skills,
             check_sync_conflict,
+            get_token_storage_status,
+            identify_auth_json,
+            export_accounts_subset,
+            export_accounts_encrypted,
+            export_accounts_redacted,
+            list_store_backups,
+            restore_store_backup,
+            import_accounts_encrypted,
+            detect_export_format,
+            import_auth_json,
+            add_account_from_refresh_token,
+            migrate_token_storage,
+            get_aging_tokens,
+            keepalive_refresh_account,
+            stage_account,
+            cleanup_staging,
+            list_auth_profiles,
+            add_auth_profile,
+            remove_auth_profile,
+            logout_codex,
             request_quarantine_fix_ticket,
+            repair_setup,
             fix_codex_quarantine,
             get_sync_status,
             sync_active_with_disk,
@@ -5154,19 +7442,69 @@ pub fn run() {
             remote_refresh_account_quota,
             remote_sync_skills,
             remote_restart_server,
-        ])
-        .build(tauri::generate_context!())
+            set_presentation_mode,
+            get_presentation_mode,
+            get_api_version,
+            get_schemas,
+        ]))
+        .build(context)
         .expect("error while building tauri application")
-        .run(|_app_handle, event| {
+        .run(|app_handle, event| {
             // 退出兜底：把 anchor 的 expires_at 在磁盘上恢复成真实值，
             // 让 Codex.app 在 codex-switcher 死掉之后能自己 refresh（而不是
             // 拿着撒谎的 +24h expires_at 继续用导致手机 bridge 静默 401）。
             if matches!(event, tauri::RunEvent::Exit) {
                 restore_anchor_disk_on_exit("Exit");
+                if let Some(state) = app_handle.try_state::<AppState>() {
+                    flush_store(&state.store, "Exit");
+                    let _ = state.activity.flush();
+                }
             }
         });
 }
 
+/// 演示模式下拦截所有非白名单命令（白名单见 `presentation::READ_ONLY_COMMANDS`）。
+/// 放行的非只读命令顺便记一次前台操作，带账号 id 的同时记到该账号上（scheduler 据此让路）
+fn guard_presentation_mode<R: tauri::Runtime>(
+    handler: impl Fn(tauri::ipc::Invoke<R>) -> bool + Send + Sync + 'static,
+) -> impl Fn(tauri::ipc::Invoke<R>) -> bool + Send + Sync + 'static {
+    move |invoke| {
+        let command = invoke.message.command();
+        let state = invoke.message.webview_ref().try_state::<AppState>();
+        let mutating = presentation::is_mutating(command);
+        let blocked = mutating && state.as_ref().is_some_and(|s| s.presentation.is_enabled());
+        if mutating && !blocked {
+            if let Some(state) = state.as_ref() {
+                record_foreground_activity(&state.foreground, invoke.message.payload());
+            }
+        }
+        if blocked {
+            let err = presentation::rejection(command);
+            println!("[Presentation] 已拦截命令 {}", command);
+            invoke.resolver.reject(err);
+            return true;
+        }
+        handler(invoke)
+    }
+}
+
+/// 命令参数里的账号 id（`id` / `accountId`）
+fn record_foreground_activity(
+    activity: &activity::ForegroundActivity,
+    payload: &tauri::ipc::InvokeBody,
+) {
+    let account_id = match payload {
+        tauri::ipc::InvokeBody::Json(args) => ["id", "accountId"]
+            .iter()
+            .find_map(|k| args.get(*k).and_then(|v| v.as_str())),
+        _ => None,
+    };
+    match account_id {
+        Some(id) => activity.touch_account(id),
+        None => activity.bump(),
+    }
+}
+
 #[cfg(unix)]
 fn redirect_stdout_stderr_to_file(file: std::fs::File) {
     use std::os::unix::io::IntoRawFd;
@@ -5187,69 +7525,12 @@ mod tests {
     use super::*;
     use chrono::Utc;
 
-    fn test_auth(account_id: &str, refresh_token: &str) -> serde_json::Value {
-        serde_json::json!({
-            "tokens": {
-                "account_id": account_id,
-                "refresh_token": refresh_token
-            }
-        })
-    }
-
-    fn test_account(name: &str, account_id: &str, refresh_token: &str) -> Account {
-        let auth_json = test_auth(account_id, refresh_token);
-        Account {
-            id: "acc-1".to_string(),
-            name: name.to_string(),
-            auth_json: auth_json.clone(),
-            refresh_token: AccountStore::extract_refresh_token(&auth_json),
-            created_at: Utc::now(),
-            last_used: None,
-            notes: None,
-            cached_quota: None,
-            keepalive: account::KeepaliveState::default(),
-            is_banned: false,
-            is_token_invalid: false,
-            is_logged_out: false,
-            kind: account::AccountKind::Legacy,
-            relay_base_url: None,
-            relay_homepage: None,
-            relay_usage_preset: None,
-            relay_usage_cookie: None,
-            relay_usage_cache: None,
-            relay_model_map: None,
-            relay_model_fallback: None,
-            relay_protocol: None,
-            relay_category: None,
-            is_session_anchor: false,
-        }
-    }
-
     #[test]
     fn quota_refresh_never_allows_local_token_refresh() {
         assert!(!allow_local_refresh_for_quota(true));
         assert!(!allow_local_refresh_for_quota(false));
     }
 
-    #[test]
-    fn sync_conflict_is_ignored_when_identity_mismatch() {
-        let current = test_account("current", "acct-local", "rt-local");
-        let disk_auth = test_auth("acct-disk", "rt-new");
-
-        assert_eq!(detect_sync_conflict_for_current(&current, &disk_auth), None);
-    }
-
-    #[test]
-    fn sync_conflict_is_reported_when_identity_matches_and_refresh_token_changed() {
-        let current = test_account("current", "acct-1", "rt-local");
-        let disk_auth = test_auth("acct-1", "rt-new");
-
-        assert_eq!(
-            detect_sync_conflict_for_current(&current, &disk_auth),
-            Some("current".to_string())
-        );
-    }
-
     #[test]
     fn quarantine_fix_ticket_can_only_be_used_once() {
         let state = AppState::new();
@@ -5283,4 +7564,34 @@ mod tests {
             .expect_err("expired ticket should be rejected");
         assert!(err.contains("过期"));
     }
+
+    #[test]
+    fn bulk_delete_ticket_covers_exactly_the_confirmed_ids() {
+        let state = AppState::new();
+        let ids = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let ticket = state.issue_bulk_delete_ticket(&ids(&["a", "b"])).unwrap();
+        // 顺序无关
+        assert!(state
+            .consume_bulk_delete_ticket(&ids(&["b", "a", "a"]), &ticket)
+            .is_ok());
+
+        let ticket = state.issue_bulk_delete_ticket(&ids(&["a", "b"])).unwrap();
+        let err = state
+            .consume_bulk_delete_ticket(&ids(&["a", "b", "c"]), &ticket)
+            .unwrap_err();
+        assert!(err.contains("批量删除"), "{}", err);
+    }
+
+    #[test]
+    fn unprotect_ticket_is_bound_to_one_account_and_used_once() {
+        let state = AppState::new();
+        let ticket = state.issue_unprotect_ticket("a").unwrap();
+        assert!(state.consume_unprotect_ticket("b", &ticket).is_err());
+        // 用错一次就作废
+        assert!(state.consume_unprotect_ticket("a", &ticket).is_err());
+
+        let ticket = state.issue_unprotect_ticket("a").unwrap();
+        assert!(state.consume_unprotect_ticket("a", &ticket).is_ok());
+        assert!(state.consume_unprotect_ticket("a", &ticket).is_err());
+    }
 }
@@ -0,0 +1,139 @@
+//! 日志里的账号标识
+//!
+//! 多人共用一台机器（各自的系统用户各跑一份）时，日志和诊断包里不该出现完整邮箱。
+//! 日志、活动流、auth.json 写入记录、诊断包要写账号名 / 邮箱 / account_id 时一律经
+//! [`format`]（纯函数）转成 [`LoggedId`]，按设置 `log_identifier_style` 决定写成什么：
+//! - `full`：原样（默认，和以前一样）
+//! - `masked`：`wo…@co….com`，规则同演示模式
+//! - `hashed`：`id:` + sha256 前 8 位十六进制；同一标识在各行里一致，能对照但看不出原文
+//!
+//! 收标识的日志接口（如 `AuthJournal::record_write`）参数是 `LoggedId` 而不是 `&str`，
+//! 绕不过这里。日志调用点拿不到设置时用 [`id`]，按启动 / 改设置时同步进来的全局风格格式化。
+
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// 日志里账号标识的写法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LogIdentifierStyle {
+    #[default]
+    Full,
+    Masked,
+    Hashed,
+}
+
+/// 已按风格处理过、可以写进日志的标识
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct LoggedId(String);
+
+impl LoggedId {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::ops::Deref for LoggedId {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for LoggedId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Display for LoggedId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// 按风格格式化一个标识（纯函数）
+pub fn format(style: LogIdentifierStyle, raw: &str) -> LoggedId {
+    LoggedId(match style {
+        LogIdentifierStyle::Full => raw.to_string(),
+        LogIdentifierStyle::Masked => crate::presentation::mask_name(raw),
+        LogIdentifierStyle::Hashed => {
+            // 大小写不同的同一邮箱算一个人
+            let digest = Sha256::digest(raw.trim().to_lowercase().as_bytes());
+            let hex: String = digest
+                .iter()
+                .take(4)
+                .map(|b| format!("{:02x}", b))
+                .collect();
+            format!("id:{}", hex)
+        }
+    })
+}
+
+static STYLE: AtomicU8 = AtomicU8::new(0);
+
+/// 同步全局风格（启动加载设置、保存设置时调）
+pub fn set_style(style: LogIdentifierStyle) {
+    STYLE.store(style as u8, Ordering::Relaxed);
+}
+
+pub fn style() -> LogIdentifierStyle {
+    match STYLE.load(Ordering::Relaxed) {
+        1 => LogIdentifierStyle::Masked,
+        2 => LogIdentifierStyle::Hashed,
+        _ => LogIdentifierStyle::Full,
+    }
+}
+
+/// 按当前全局风格格式化（日志调用点用）
+pub fn id(raw: &str) -> LoggedId {
+    format(style(), raw)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_style_renders_expected_form() {
+        let email = "work@company.com";
+        assert_eq!(format(LogIdentifierStyle::Full, email).as_str(), email);
+        assert_eq!(
+            format(LogIdentifierStyle::Masked, email).as_str(),
+            "wo…@co….com"
+        );
+        assert_eq!(
+            format(LogIdentifierStyle::Masked, "团队主号").as_str(),
+            "团队…"
+        );
+
+        let hashed = format(LogIdentifierStyle::Hashed, email);
+        assert!(hashed.starts_with("id:"));
+        assert_eq!(hashed.len(), "id:".len() + 8);
+        assert!(!hashed.contains("work") && !hashed.contains("company"));
+    }
+
+    #[test]
+    fn hashed_form_is_stable_and_distinguishes_identities() {
+        let a = format(LogIdentifierStyle::Hashed, "work@company.com");
+        assert_eq!(a, format(LogIdentifierStyle::Hashed, "work@company.com"));
+        assert_eq!(a, format(LogIdentifierStyle::Hashed, " Work@Company.com"));
+        assert_ne!(a, format(LogIdentifierStyle::Hashed, "home@company.com"));
+    }
+
+    #[test]
+    fn style_round_trips_through_settings_json() {
+        let style: LogIdentifierStyle = serde_json::from_str("\"hashed\"").unwrap();
+        assert_eq!(style, LogIdentifierStyle::Hashed);
+        assert_eq!(
+            serde_json::to_string(&LogIdentifierStyle::Masked).unwrap(),
+            "\"masked\""
+        );
+    }
+}
@@ -0,0 +1,182 @@
+//! 托盘 → 主窗口的导航意图
+//!
+//! 开机自启后主窗口可能从没显示过，webview 还没加载，直接 emit 给前端的事件会丢。
+//! 需要界面的托盘动作（设置、添加账号）统一走 [`open_with_intent`]：
+//! 先确保主窗口存在并前置（没有就新建），再把意图放进 [`PendingNavigation`]，
+//! 最后 emit `navigation-pending` 提醒已加载的前端；前端加载时 / 收到事件时调
+//! `get_pending_navigation` 把队列取空。先入队后通知，前端无论何时加载都不会漏。
+//!
+//! 窗口操作走 [`WindowHost`]，测试里换成假实现即可脱离 Tauri。
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+/// 提醒前端来取导航意图的事件名
+pub const PENDING_EVENT: &str = "navigation-pending";
+/// 队列上限：前端一直没加载时只留最近几条
+const QUEUE_CAP: usize = 8;
+
+/// 前端要跳转到的位置
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum NavIntent {
+    Dashboard,
+    Settings,
+    /// 打开"添加账号"弹窗（OAuth 登录等）
+    AddAccount,
+}
+
+/// 等前端来取的导航意图
+#[derive(Default)]
+pub struct PendingNavigation {
+    queue: Mutex<VecDeque<NavIntent>>,
+}
+
+impl PendingNavigation {
+    /// 入队；和队尾相同的不重复放（连点托盘菜单）
+    pub fn push(&self, intent: NavIntent) {
+        let Ok(mut queue) = self.queue.lock() else {
+            return;
+        };
+        if queue.back() == Some(&intent) {
+            return;
+        }
+        if queue.len() >= QUEUE_CAP {
+            queue.pop_front();
+        }
+        queue.push_back(intent);
+    }
+
+    /// 取空队列，按入队顺序返回
+    pub fn drain(&self) -> Vec<NavIntent> {
+        self.queue
+            .lock()
+            .map(|mut q| q.drain(..).collect())
+            .unwrap_or_default()
+    }
+}
+
+/// 主窗口操作
+pub trait WindowHost {
+    /// 主窗口是否已创建
+    fn has_main_window(&self) -> bool;
+    /// 按配置新建主窗口
+    fn create_main_window(&self) -> Result<(), String>;
+    /// 显示、取消最小化并聚焦（macOS 恢复 Dock 图标）
+    fn show_main_window(&self);
+    /// 通知已加载的前端来取导航意图
+    fn notify_pending(&self);
+}
+
+/// 确保主窗口存在并前置，再把意图交给前端
+pub fn open_with_intent(
+    host: &dyn WindowHost,
+    pending: &PendingNavigation,
+    intent: NavIntent,
+) -> Result<(), String> {
+    if !host.has_main_window() {
+        println!("[Navigation] 主窗口不存在，新建后再跳转: {:?}", intent);
+        host.create_main_window()?;
+    }
+    pending.push(intent);
+    host.show_main_window();
+    host.notify_pending();
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+
+    #[derive(Default)]
+    struct FakeHost {
+        exists: RefCell<bool>,
+        fail_create: bool,
+        calls: RefCell<Vec<&'static str>>,
+    }
+
+    impl WindowHost for FakeHost {
+        fn has_main_window(&self) -> bool {
+            *self.exists.borrow()
+        }
+        fn create_main_window(&self) -> Result<(), String> {
+            self.calls.borrow_mut().push("create");
+            if self.fail_create {
+                return Err("创建窗口失败".to_string());
+            }
+            *self.exists.borrow_mut() = true;
+            Ok(())
+        }
+        fn show_main_window(&self) {
+            self.calls.borrow_mut().push("show");
+        }
+        fn notify_pending(&self) {
+            self.calls.borrow_mut().push("notify");
+        }
+    }
+
+    #[test]
+    fn queue_drains_in_order_and_dedupes_tail() {
+        let pending = PendingNavigation::default();
+        pending.push(NavIntent::Settings);
+        pending.push(NavIntent::Settings);
+        pending.push(NavIntent::AddAccount);
+        pending.push(NavIntent::Settings);
+        assert_eq!(
+            pending.drain(),
+            vec![
+                NavIntent::Settings,
+                NavIntent::AddAccount,
+                NavIntent::Settings
+            ]
+        );
+        assert!(pending.drain().is_empty(), "取一次就清空");
+
+        for _ in 0..QUEUE_CAP {
+            pending.push(NavIntent::Dashboard);
+            pending.push(NavIntent::Settings);
+        }
+        pending.push(NavIntent::AddAccount);
+        let drained = pending.drain();
+        assert_eq!(drained.len(), QUEUE_CAP);
+        assert_eq!(drained.last(), Some(&NavIntent::AddAccount), "满了丢最旧的");
+    }
+
+    #[test]
+    fn creates_missing_window_before_queueing() {
+        let host = FakeHost::default();
+        let pending = PendingNavigation::default();
+        open_with_intent(&host, &pending, NavIntent::Settings).unwrap();
+        assert_eq!(*host.calls.borrow(), vec!["create", "show", "notify"]);
+        assert_eq!(pending.drain(), vec![NavIntent::Settings]);
+
+        // 窗口已在（只是隐藏）：不重复创建
+        host.calls.borrow_mut().clear();
+        open_with_intent(&host, &pending, NavIntent::AddAccount).unwrap();
+        assert_eq!(*host.calls.borrow(), vec!["show", "notify"]);
+        assert_eq!(pending.drain(), vec![NavIntent::AddAccount]);
+    }
+
+    #[test]
+    fn failed_window_creation_queues_nothing() {
+        let host = FakeHost {
+            fail_create: true,
+            ..Default::default()
+        };
+        let pending = PendingNavigation::default();
+        assert!(open_with_intent(&host, &pending, NavIntent::Settings).is_err());
+        assert_eq!(*host.calls.borrow(), vec!["create"]);
+        assert!(pending.drain().is_empty());
+    }
+
+    #[test]
+    fn intent_serializes_as_snake_case() {
+        assert_eq!(
+            serde_json::to_string(&NavIntent::AddAccount).unwrap(),
+            "\"add_account\""
+        );
+    }
+}
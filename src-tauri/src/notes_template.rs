@@ -0,0 +1,206 @@
+//! 新账号的备注模板
+//!
+//! 团队约定每个账号的备注写上负责人、购买日期、续费日期，但新建账号时备注只有
+//! "OpenAI OAuth 登录" 这类固定文字，后面常常忘了补。设置 `new_account_notes_template`
+//! 非空时，OAuth 登录、批量登录、导入当前登录新建的账号按模板预填备注。
+//!
+//! 占位符见 [`PLACEHOLDERS`]，写成 `{email}` 这样；模板里出现别的 `{名字}` 保存设置时直接拒绝。
+//! 备注是纯文本，渲染不做任何转义；结果超过 [`MAX_NOTES_CHARS`] 个字符时截断并以 `…` 结尾。
+
+use chrono::NaiveDate;
+use serde_json::Value;
+
+use crate::account::AccountStore;
+
+/// 模板支持的占位符
+pub const PLACEHOLDERS: [&str; 4] = ["email", "plan", "date", "account_id"];
+
+/// 模板渲染出的备注最多这么多字符
+pub const MAX_NOTES_CHARS: usize = 500;
+
+/// 渲染用到的值；拿不到的留空
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateValues {
+    pub email: String,
+    pub plan: String,
+    /// 建号当天（本地日期）
+    pub date: NaiveDate,
+    pub account_id: String,
+}
+
+impl TemplateValues {
+    /// 从账号的 auth.json 里取邮箱、套餐、工作区 id
+    pub fn from_auth(auth_json: &Value, date: NaiveDate) -> Self {
+        Self {
+            email: AccountStore::extract_email(auth_json).unwrap_or_default(),
+            plan: crate::auth_identify::plan_from_claims(auth_json).unwrap_or_default(),
+            date,
+            account_id: AccountStore::extract_account_id(auth_json).unwrap_or_default(),
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<String> {
+        match name {
+            "email" => Some(self.email.clone()),
+            "plan" => Some(self.plan.clone()),
+            "date" => Some(self.date.format("%Y-%m-%d").to_string()),
+            "account_id" => Some(self.account_id.clone()),
+            _ => None,
+        }
+    }
+}
+
+enum Segment<'a> {
+    Text(&'a str),
+    Placeholder(&'a str),
+}
+
+/// 切成文字和 `{名字}` 两种片段；没配对的 `{` 当普通文字
+fn segments(template: &str) -> Vec<Segment<'_>> {
+    let mut out = Vec::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        let Some(len) = rest[open + 1..].find(['{', '}']) else {
+            break;
+        };
+        let close = open + 1 + len;
+        if &rest[close..=close] == "{" {
+            // `{a{b}` 前一个 `{` 没配对
+            out.push(Segment::Text(&rest[..close]));
+            rest = &rest[close..];
+            continue;
+        }
+        out.push(Segment::Text(&rest[..open]));
+        out.push(Segment::Placeholder(&rest[open + 1..close]));
+        rest = &rest[close + 1..];
+    }
+    out.push(Segment::Text(rest));
+    out
+}
+
+/// 检查模板里的占位符；有未知的时错误里列出全部名字
+pub fn validate(template: &str) -> Result<(), String> {
+    let mut unknown: Vec<String> = Vec::new();
+    for segment in segments(template) {
+        match segment {
+            Segment::Placeholder(name) if !PLACEHOLDERS.contains(&name) => {
+                let tagged = format!("{{{}}}", name);
+                if !unknown.contains(&tagged) {
+                    unknown.push(tagged);
+                }
+            }
+            _ => {}
+        }
+    }
+    if unknown.is_empty() {
+        return Ok(());
+    }
+    Err(format!(
+        "备注模板里有不支持的占位符：{}（可用：{}）",
+        unknown.join("、"),
+        PLACEHOLDERS
+            .iter()
+            .map(|p| format!("{{{}}}", p))
+            .collect::<Vec<_>>()
+            .join("、")
+    ))
+}
+
+/// 渲染新账号的备注。模板为空时用 `default`（各建号入口原来的固定备注）
+pub fn render(
+    template: &str,
+    values: &TemplateValues,
+    default: Option<&str>,
+) -> Result<Option<String>, String> {
+    if template.trim().is_empty() {
+        return Ok(default.map(str::to_string));
+    }
+    validate(template)?;
+    let rendered: String = segments(template)
+        .into_iter()
+        .map(|segment| match segment {
+            Segment::Text(text) => text.to_string(),
+            Segment::Placeholder(name) => values.get(name).unwrap_or_default(),
+        })
+        .collect();
+    Ok(Some(truncate(rendered.trim())))
+}
+
+fn truncate(text: &str) -> String {
+    if text.chars().count() <= MAX_NOTES_CHARS {
+        return text.to_string();
+    }
+    let kept: String = text.chars().take(MAX_NOTES_CHARS - 1).collect();
+    format!("{}…", kept)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values() -> TemplateValues {
+        TemplateValues {
+            email: "work@company.com".to_string(),
+            plan: "team".to_string(),
+            date: NaiveDate::from_ymd_opt(2026, 3, 1).unwrap(),
+            account_id: "ws-1".to_string(),
+        }
+    }
+
+    fn render_ok(template: &str) -> String {
+        render(template, &values(), None).unwrap().unwrap()
+    }
+
+    #[test]
+    fn every_placeholder_is_filled() {
+        assert_eq!(render_ok("{email}"), "work@company.com");
+        assert_eq!(render_ok("{plan}"), "team");
+        assert_eq!(render_ok("{date}"), "2026-03-01");
+        assert_eq!(render_ok("{account_id}"), "ws-1");
+        assert_eq!(
+            render_ok("负责人：\n购买：{date} / {plan}\n续费：\n{email} ({account_id})"),
+            "负责人：\n购买：2026-03-01 / team\n续费：\nwork@company.com (ws-1)"
+        );
+        // 没配对的花括号、拿不到的值都原样处理，不做转义
+        assert_eq!(render_ok("{ {email} } <b>"), "{ work@company.com } <b>");
+        let missing = TemplateValues {
+            plan: String::new(),
+            ..values()
+        };
+        assert_eq!(
+            render("套餐[{plan}]", &missing, None).unwrap().unwrap(),
+            "套餐[]"
+        );
+    }
+
+    #[test]
+    fn unknown_placeholders_are_rejected_by_name() {
+        let err = validate("{owner} 续费 {renew_date} {email} {owner}").unwrap_err();
+        assert!(err.contains("{owner}、{renew_date}"), "{}", err);
+        assert_eq!(err.matches("{owner}").count(), 1);
+        assert!(render("{Email}", &values(), None).is_err());
+        assert!(validate("{}").is_err());
+        assert!(validate("{email} {plan} {date} {account_id} {").is_ok());
+    }
+
+    #[test]
+    fn empty_template_falls_back_to_the_default_notes() {
+        assert_eq!(
+            render("", &values(), Some("OpenAI OAuth 登录")).unwrap(),
+            Some("OpenAI OAuth 登录".to_string())
+        );
+        assert_eq!(render("  \n", &values(), None).unwrap(), None);
+    }
+
+    #[test]
+    fn long_results_are_truncated_with_an_ellipsis() {
+        let template = format!("{}{{email}}", "备".repeat(MAX_NOTES_CHARS));
+        let notes = render_ok(&template);
+        assert_eq!(notes.chars().count(), MAX_NOTES_CHARS);
+        assert!(notes.ends_with('…'));
+        assert!(!notes.contains("work@"));
+
+        let exact = "备".repeat(MAX_NOTES_CHARS);
+        assert_eq!(render_ok(&exact), exact);
+    }
+}
@@ -0,0 +1,46 @@
+//! 系统通知
+//!
+//! 目前只有 macOS（osascript `display notification`），其它平台只打日志。
+//! 调用方自己判断开关（`notify_on_switch` / 演示模式等），这里只管发。
+
+/// AppleScript 字符串字面量转义
+fn escape_applescript(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn display_notification_script(subtitle: &str, body: &str) -> String {
+    format!(
+        "display notification \"{}\" with title \"Codex Switcher\" subtitle \"{}\"",
+        escape_applescript(body),
+        escape_applescript(subtitle)
+    )
+}
+
+/// 发一条系统通知（后台线程执行，不阻塞调用方）
+pub fn send(subtitle: &str, body: &str) {
+    let script = display_notification_script(subtitle, body);
+    if cfg!(target_os = "macos") {
+        std::thread::spawn(move || {
+            let _ = std::process::Command::new("osascript")
+                .arg("-e")
+                .arg(script)
+                .output();
+        });
+    } else {
+        println!("[Notify] {}: {}", subtitle, body);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn script_escapes_quotes_and_backslashes() {
+        let script = display_notification_script("每日额度", r#"a "b" \c"#);
+        assert_eq!(
+            script,
+            r#"display notification "a \"b\" \\c" with title "Codex Switcher" subtitle "每日额度""#
+        );
+    }
+}
@@ -1,14 +1,196 @@
 use base64::{engine::general_purpose, Engine as _};
 use rand::{rng, RngCore};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 use std::sync::OnceLock;
 use std::time::Duration;
 
 /// OpenAI 官方授权常量 (参考 codex-main)
 pub const CLIENT_ID: &str = "app_EMoamEEZ73f0CkXaXp7hrann";
-pub const AUTH_URL: &str = "https://auth.openai.com/oauth/authorize";
-pub const TOKEN_URL: &str = "https://auth.openai.com/oauth/token";
+pub const ISSUER: &str = "https://auth.openai.com";
+
+/// 内置 profile 的 id；`auth_profile_id` 缺省的老账号都按它处理
+pub const DEFAULT_PROFILE_ID: &str = "openai-default";
+
+const SCOPE: &str = "openid profile email offline_access";
+const DEFAULT_AUTHORIZE_PATH: &str = "/oauth/authorize";
+const DEFAULT_TOKEN_PATH: &str = "/oauth/token";
+
+/// 授权 URL 固定参数，`extra_params` 不允许覆盖
+const RESERVED_PARAMS: [&str; 7] = [
+    "response_type",
+    "client_id",
+    "redirect_uri",
+    "scope",
+    "code_challenge",
+    "code_challenge_method",
+    "state",
+];
+
+/// 一套 OAuth 配置（issuer / client / 端点）。
+///
+/// 企业部署会在 OpenAI 前面套一层自己的 issuer 和 client_id，个人账号仍走官方默认；
+/// 账号登录时记下所用 profile 的 id，之后刷新一直走该 profile 的 token 端点。
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct AuthProfile {
+    pub id: String,
+    #[serde(default)]
+    pub name: String,
+    /// issuer 根地址，如 `https://auth.openai.com`
+    pub issuer: String,
+    pub client_id: String,
+    /// 缺省为 `{issuer}/oauth/authorize`；可填绝对地址或以 `/` 开头的路径
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authorize_endpoint: Option<String>,
+    /// 缺省为 `{issuer}/oauth/token`；写法同上
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_endpoint: Option<String>,
+    /// 追加到授权 URL 上的额外参数
+    #[serde(default)]
+    pub extra_params: BTreeMap<String, String>,
+}
+
+impl AuthProfile {
+    /// 内置的 OpenAI 官方配置
+    pub fn builtin() -> Self {
+        Self {
+            id: DEFAULT_PROFILE_ID.to_string(),
+            name: "OpenAI".to_string(),
+            issuer: ISSUER.to_string(),
+            client_id: CLIENT_ID.to_string(),
+            authorize_endpoint: None,
+            token_endpoint: None,
+            extra_params: BTreeMap::new(),
+        }
+    }
+
+    pub fn authorize_url(&self) -> String {
+        self.endpoint(self.authorize_endpoint.as_deref(), DEFAULT_AUTHORIZE_PATH)
+    }
+
+    pub fn token_url(&self) -> String {
+        self.endpoint(self.token_endpoint.as_deref(), DEFAULT_TOKEN_PATH)
+    }
+
+    /// 端点拼接统一在这里：绝对地址原样用，路径挂到 issuer 下，缺省用默认路径
+    fn endpoint(&self, custom: Option<&str>, default_path: &str) -> String {
+        let issuer = self.issuer.trim().trim_end_matches('/');
+        match custom.map(str::trim).filter(|s| !s.is_empty()) {
+            Some(url) if url.contains("://") => url.to_string(),
+            Some(path) => format!("{}/{}", issuer, path.trim_start_matches('/')),
+            None => format!("{}{}", issuer, default_path),
+        }
+    }
+}
+
+/// 按 id 在自定义 profile 里找；id 为空或是内置 id 时用内置 profile。
+/// 找不到（配置被手改删掉）也退回内置，并打日志
+pub fn resolve_profile(custom: &[AuthProfile], id: Option<&str>) -> AuthProfile {
+    match id.filter(|id| *id != DEFAULT_PROFILE_ID) {
+        None => AuthProfile::builtin(),
+        Some(id) => custom
+            .iter()
+            .find(|p| p.id == id)
+            .cloned()
+            .unwrap_or_else(|| {
+                eprintln!("[OAuth] 找不到 auth profile {}，退回内置配置", id);
+                AuthProfile::builtin()
+            }),
+    }
+}
+
+/// 校验一个待保存的 profile：id 合法且不占用内置 id、client_id 非空、
+/// issuer 和显式端点都必须是 https，额外参数不能覆盖固定参数
+pub fn validate_profile(profile: &AuthProfile) -> Result<(), String> {
+    let id = profile.id.trim();
+    if id.is_empty()
+        || !id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err("profile id 只能包含字母、数字、- 和 _".to_string());
+    }
+    if id == DEFAULT_PROFILE_ID {
+        return Err(format!("{} 是内置 profile，不能覆盖", DEFAULT_PROFILE_ID));
+    }
+    if profile.client_id.trim().is_empty() {
+        return Err("client_id 不能为空".to_string());
+    }
+    require_https("issuer", &profile.issuer)?;
+    require_https("authorize_endpoint", &profile.authorize_url())?;
+    require_https("token_endpoint", &profile.token_url())?;
+    if let Some(key) = profile
+        .extra_params
+        .keys()
+        .find(|k| RESERVED_PARAMS.contains(&k.as_str()))
+    {
+        return Err(format!("额外参数 {} 与固定参数冲突", key));
+    }
+    Ok(())
+}
+
+fn require_https(field: &str, raw: &str) -> Result<(), String> {
+    let url = url::Url::parse(raw.trim()).map_err(|e| format!("{} 不是合法 URL: {}", field, e))?;
+    if url.scheme() != "https" {
+        return Err(format!("{} 必须是 https 地址: {}", field, raw));
+    }
+    if url.host_str().unwrap_or_default().is_empty() {
+        return Err(format!("{} 缺少主机名: {}", field, raw));
+    }
+    Ok(())
+}
+
+/// 构造授权 URL。固定参数与官方一致：手动拼接，不对特殊字符编码；
+/// profile 的额外参数按 key 排序追加在末尾，会做 URL 编码
+pub fn build_authorize_url(
+    profile: &AuthProfile,
+    redirect_uri: &str,
+    code_challenge: &str,
+    state: &str,
+) -> String {
+    let mut qs = format!(
+        "response_type=code&client_id={}&redirect_uri={}&scope={}&code_challenge={}&code_challenge_method=S256&id_token_add_organizations=true&codex_cli_simplified_flow=true&state={}&originator=codex_vscode",
+        profile.client_id, redirect_uri, SCOPE, code_challenge, state
+    );
+    for (key, value) in &profile.extra_params {
+        qs.push_str(&format!(
+            "&{}={}",
+            urlencoding::encode(key),
+            urlencoding::encode(value)
+        ));
+    }
+    format!("{}?{}", profile.authorize_url(), qs)
+}
+
+/// 官方 client 在授权服务器登记过的本地回调端口（redirect_uri 白名单）。
+/// 只有这些端口拼出来的 `http://localhost:<port>/auth/callback` 才会被接受，
+/// 设置项 `oauth_callback_ports` 必须是它的子集。
+pub const ALLOWED_CALLBACK_PORTS: [u16; 3] = [1455, 1456, 1457];
+
+/// 校验回调端口配置：非空、无重复、全部在白名单内
+pub fn validate_callback_ports(ports: &[u16]) -> Result<(), String> {
+    if ports.is_empty() {
+        return Err("OAuth 回调端口列表不能为空".to_string());
+    }
+    if let Some(bad) = ports.iter().find(|p| !ALLOWED_CALLBACK_PORTS.contains(p)) {
+        return Err(format!(
+            "OAuth 回调端口 {} 不在允许列表 {:?} 内",
+            bad, ALLOWED_CALLBACK_PORTS
+        ));
+    }
+    let mut seen = std::collections::HashSet::new();
+    if let Some(dup) = ports.iter().find(|p| !seen.insert(**p)) {
+        return Err(format!("OAuth 回调端口 {} 重复", dup));
+    }
+    Ok(())
+}
+
+/// 本地回调地址
+pub fn callback_redirect_uri(port: u16) -> String {
+    format!("http://localhost:{}/auth/callback", port)
+}
 
 /// 进程级共享 reqwest::Client：连接池可复用，避免每次 quota 刷新都跑 TLS 握手。
 /// 给整个请求加了一个 connect+request 双限，避免少数账号让 /oauth/token 无限期挂起
@@ -61,14 +243,65 @@ pub struct TokenResponse {
 }
 
 /// 用户信息预提取 (通过解析 id_token)
+///
+/// 两个 id 含义不同，不能混用：
+/// - `chatgpt_account_id`：ChatGPT 工作区（个人 / Team）id，写进 `tokens.account_id`，
+///   usage 请求的 `ChatGPT-Account-Id` 头用它；取错了会拿到别的工作区的额度
+/// - `user_id`：登录用户本身的 id，同一个人在不同工作区下相同，只用于身份比对
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserInfo {
     pub email: String,
-    pub account_id: Option<String>,
+    /// 优先级：`https://api.openai.com/auth`.chatgpt_account_id → 顶层 chatgpt_account_id。
+    /// 绝不回落到 `sub`（那是用户 id，不是工作区 id）
+    pub chatgpt_account_id: Option<String>,
+    /// 优先级：`https://api.openai.com/auth`.chatgpt_user_id → .user_id → 顶层 `sub`
+    pub user_id: Option<String>,
+}
+
+/// OpenAI 自定义 claim 的命名空间
+const AUTH_CLAIM: &str = "https://api.openai.com/auth";
+
+/// 取 `https://api.openai.com/auth` 下的字段。这个 claim 见过三种形态：
+/// 正常的对象、被序列化成字符串的对象、以及包了一层数组；缺失时返回 None。
+fn auth_claim_field(claims: &serde_json::Value, field: &str) -> Option<String> {
+    fn from_value(v: &serde_json::Value, field: &str) -> Option<String> {
+        match v {
+            serde_json::Value::Object(obj) => obj.get(field).and_then(first_non_empty_str),
+            serde_json::Value::String(raw) => serde_json::from_str::<serde_json::Value>(raw)
+                .ok()
+                .filter(|parsed| parsed.is_object())
+                .and_then(|parsed| from_value(&parsed, field)),
+            serde_json::Value::Array(items) => items.iter().find_map(|i| from_value(i, field)),
+            _ => None,
+        }
+    }
+    from_value(claims.get(AUTH_CLAIM)?, field)
+}
+
+/// 字符串直接取；数组取第一个非空字符串
+fn first_non_empty_str(v: &serde_json::Value) -> Option<String> {
+    match v {
+        serde_json::Value::String(s) => Some(s.trim().to_string()).filter(|s| !s.is_empty()),
+        serde_json::Value::Array(items) => items.iter().find_map(first_non_empty_str),
+        _ => None,
+    }
+}
+
+/// 解码 JWT payload（不验签）
+pub fn decode_jwt_claims(token: &str) -> Option<serde_json::Value> {
+    let parts: Vec<&str> = token.split('.').collect();
+    if parts.len() < 2 {
+        return None;
+    }
+    let payload = general_purpose::URL_SAFE_NO_PAD
+        .decode(parts[1].trim_end_matches('='))
+        .ok()?;
+    serde_json::from_slice(&payload).ok()
 }
 
 /// 使用授权码交换访问令牌 (与官方一致: 手动拼接请求体)
 pub async fn exchange_code(
+    profile: &AuthProfile,
     code: &str,
     redirect_uri: &str,
     code_verifier: &str,
@@ -78,12 +311,12 @@ pub async fn exchange_code(
         "grant_type=authorization_code&code={}&redirect_uri={}&client_id={}&code_verifier={}",
         urlencoding::encode(code),
         urlencoding::encode(redirect_uri),
-        urlencoding::encode(CLIENT_ID),
+        urlencoding::encode(&profile.client_id),
         urlencoding::encode(code_verifier)
     );
 
     let response = token_client()
-        .post(TOKEN_URL)
+        .post(profile.token_url())
         .header("Content-Type", "application/x-www-form-urlencoded")
         .timeout(Duration::from_secs(20))
         .body(body)
@@ -102,19 +335,45 @@ pub async fn exchange_code(
         .map_err(|e| format!("解析令牌响应失败: {}", e))
 }
 
-/// 使用刷新令牌获取新访问令牌
-pub async fn refresh_access_token(refresh_token: &str) -> Result<TokenResponse, String> {
+/// 刷新被拒、且属于"refresh_token 重复使用 / invalid_grant"一类时错误串的前缀。
+/// 这类拒绝说明 IdP 已经作废了整条授权，再刷只会更糟，见 `refresh_ban`
+pub const REFRESH_REUSED_PREFIX: &str = "REFRESH_REUSED:";
+
+/// 重复使用 / 授权作废一类拒绝在响应体里的特征（小写）
+const REUSE_MARKERS: [&str; 4] = [
+    "refresh_token_reused",
+    "refresh_token_invalidated",
+    "invalid_grant",
+    "already been used",
+];
+
+/// token 端点的拒绝响应体是否属于重复使用 / invalid_grant 一类
+pub fn is_reuse_rejection(body: &str) -> bool {
+    let lower = body.to_lowercase();
+    REUSE_MARKERS.iter().any(|m| lower.contains(m))
+}
+
+/// 错误串（可能被调用方再包一层）是否来自重复使用一类的刷新拒绝
+pub fn is_refresh_reused(error: &str) -> bool {
+    error.contains(REFRESH_REUSED_PREFIX)
+}
+
+/// 使用刷新令牌获取新访问令牌，走账号所属 profile 的 token 端点
+pub async fn refresh_access_token(
+    profile: &AuthProfile,
+    refresh_token: &str,
+) -> Result<TokenResponse, String> {
     let params = [
         ("grant_type", "refresh_token"),
-        ("client_id", CLIENT_ID),
+        ("client_id", profile.client_id.as_str()),
         ("refresh_token", refresh_token),
-        ("scope", "openid profile email offline_access"),
+        ("scope", SCOPE),
     ];
 
     // 关键：之前没 timeout，OpenAI 边缘把这个账号 hang 住时整条 quota 刷新永久卡死。
     // 15s 是经验值：正常 < 1s 完成，10s+ 基本可以判定为边缘节流/限流。
     let response = token_client()
-        .post(TOKEN_URL)
+        .post(profile.token_url())
         .header("Content-Type", "application/x-www-form-urlencoded")
         .timeout(Duration::from_secs(15))
         .form(&params)
@@ -124,6 +383,12 @@ pub async fn refresh_access_token(refresh_token: &str) -> Result<TokenResponse,
 
     if !response.status().is_success() {
         let error_body = response.text().await.unwrap_or_default();
+        if is_reuse_rejection(&error_body) {
+            return Err(format!(
+                "{}刷新令牌被拒绝: {}",
+                REFRESH_REUSED_PREFIX, error_body
+            ));
+        }
         return Err(format!("刷新令牌被拒绝: {}", error_body));
     }
 
@@ -135,22 +400,329 @@ pub async fn refresh_access_token(refresh_token: &str) -> Result<TokenResponse,
 
 /// 从 ID Token 中提取用户信息 (JWT 解析)
 pub fn parse_user_info(id_token: &str) -> Option<UserInfo> {
-    let parts: Vec<&str> = id_token.split('.').collect();
-    if parts.len() < 2 {
-        return None;
+    let claims = decode_jwt_claims(id_token)?;
+    let email = claims.get("email")?.as_str()?.to_string();
+    Some(UserInfo {
+        email,
+        chatgpt_account_id: chatgpt_account_id_from_claims(&claims),
+        user_id: user_id_from_claims(&claims),
+    })
+}
+
+/// 工作区 id，见 [`UserInfo::chatgpt_account_id`]
+pub fn chatgpt_account_id_from_claims(claims: &serde_json::Value) -> Option<String> {
+    auth_claim_field(claims, "chatgpt_account_id").or_else(|| {
+        claims
+            .get("chatgpt_account_id")
+            .and_then(first_non_empty_str)
+    })
+}
+
+/// 用户 id，见 [`UserInfo::user_id`]
+pub fn user_id_from_claims(claims: &serde_json::Value) -> Option<String> {
+    auth_claim_field(claims, "chatgpt_user_id")
+        .or_else(|| auth_claim_field(claims, "user_id"))
+        .or_else(|| claims.get("sub").and_then(first_non_empty_str))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jwt(payload: serde_json::Value) -> String {
+        let enc = |v: &str| general_purpose::URL_SAFE_NO_PAD.encode(v);
+        format!(
+            "{}.{}.sig",
+            enc(r#"{"alg":"none"}"#),
+            enc(&payload.to_string())
+        )
+    }
+
+    #[test]
+    fn prefers_workspace_claim_over_sub() {
+        let token = jwt(serde_json::json!({
+            "email": "a@example.com",
+            "sub": "auth0|user-1",
+            AUTH_CLAIM: {
+                "chatgpt_account_id": "ws-team",
+                "chatgpt_user_id": "user-1"
+            }
+        }));
+        let info = parse_user_info(&token).unwrap();
+        assert_eq!(info.email, "a@example.com");
+        assert_eq!(info.chatgpt_account_id.as_deref(), Some("ws-team"));
+        assert_eq!(info.user_id.as_deref(), Some("user-1"));
+    }
+
+    #[test]
+    fn absent_auth_claim_never_uses_sub_as_workspace() {
+        let token = jwt(serde_json::json!({ "email": "a@example.com", "sub": "user-1" }));
+        let info = parse_user_info(&token).unwrap();
+        assert_eq!(info.chatgpt_account_id, None);
+        assert_eq!(info.user_id.as_deref(), Some("user-1"));
+
+        let top_level = jwt(serde_json::json!({
+            "email": "a@example.com",
+            "sub": "user-1",
+            "chatgpt_account_id": "ws-top"
+        }));
+        assert_eq!(
+            parse_user_info(&top_level)
+                .unwrap()
+                .chatgpt_account_id
+                .as_deref(),
+            Some("ws-top")
+        );
+    }
+
+    #[test]
+    fn auth_claim_as_json_string() {
+        let nested = serde_json::json!({ "chatgpt_account_id": "ws-str", "user_id": "user-s" });
+        let token = jwt(serde_json::json!({
+            "email": "a@example.com",
+            "sub": "sub-1",
+            AUTH_CLAIM: nested.to_string()
+        }));
+        let info = parse_user_info(&token).unwrap();
+        assert_eq!(info.chatgpt_account_id.as_deref(), Some("ws-str"));
+        assert_eq!(info.user_id.as_deref(), Some("user-s"));
+
+        // 不是 JSON 的字符串当作缺失
+        let garbage = jwt(serde_json::json!({
+            "email": "a@example.com",
+            "sub": "sub-1",
+            AUTH_CLAIM: "not-json"
+        }));
+        let info = parse_user_info(&garbage).unwrap();
+        assert_eq!(info.chatgpt_account_id, None);
+        assert_eq!(info.user_id.as_deref(), Some("sub-1"));
+    }
+
+    #[test]
+    fn auth_claim_as_array() {
+        let token = jwt(serde_json::json!({
+            "email": "a@example.com",
+            "sub": "sub-1",
+            AUTH_CLAIM: [
+                { "plan": "team" },
+                { "chatgpt_account_id": ["", "ws-arr"], "chatgpt_user_id": "user-a" }
+            ]
+        }));
+        let info = parse_user_info(&token).unwrap();
+        assert_eq!(info.chatgpt_account_id.as_deref(), Some("ws-arr"));
+        assert_eq!(info.user_id.as_deref(), Some("user-a"));
     }
 
-    let payload = general_purpose::URL_SAFE_NO_PAD.decode(parts[1]).ok()?;
-    let json: serde_json::Value = serde_json::from_slice(&payload).ok()?;
+    #[test]
+    fn malformed_tokens_are_rejected() {
+        assert!(parse_user_info("not-a-jwt").is_none());
+        assert!(parse_user_info(&jwt(serde_json::json!({ "sub": "no-email" }))).is_none());
+    }
+
+    fn enterprise_profile() -> AuthProfile {
+        AuthProfile {
+            id: "corp".to_string(),
+            name: "Corp SSO".to_string(),
+            issuer: "https://sso.corp.example/".to_string(),
+            client_id: "corp-client".to_string(),
+            authorize_endpoint: Some("/openai/authorize".to_string()),
+            token_endpoint: Some("https://token.corp.example/oauth2/token".to_string()),
+            extra_params: BTreeMap::from([("audience".to_string(), "codex api".to_string())]),
+        }
+    }
 
-    let email = json.get("email")?.as_str()?.to_string();
+    #[test]
+    fn endpoint_urls_are_built_from_issuer() {
+        let builtin = AuthProfile::builtin();
+        assert_eq!(
+            builtin.authorize_url(),
+            "https://auth.openai.com/oauth/authorize"
+        );
+        assert_eq!(builtin.token_url(), "https://auth.openai.com/oauth/token");
 
-    // 从 OpenAI 特有的 claims 中获取 account_id
-    let account_id = json
-        .get("https://api.openai.com/auth")
-        .and_then(|v| v.get("chatgpt_account_id"))
-        .and_then(|v| v.as_str())
-        .map(|s| s.to_string());
+        let corp = enterprise_profile();
+        assert_eq!(
+            corp.authorize_url(),
+            "https://sso.corp.example/openai/authorize"
+        );
+        assert_eq!(corp.token_url(), "https://token.corp.example/oauth2/token");
 
-    Some(UserInfo { email, account_id })
+        let bare = AuthProfile {
+            authorize_endpoint: None,
+            token_endpoint: Some("  ".to_string()),
+            ..corp
+        };
+        assert_eq!(bare.token_url(), "https://sso.corp.example/oauth/token");
+    }
+
+    #[test]
+    fn builtin_authorize_url_matches_official_format() {
+        let url = build_authorize_url(
+            &AuthProfile::builtin(),
+            "http://localhost:1455/auth/callback",
+            "chal",
+            "st",
+        );
+        assert_eq!(
+            url,
+            "https://auth.openai.com/oauth/authorize?response_type=code&client_id=app_EMoamEEZ73f0CkXaXp7hrann&redirect_uri=http://localhost:1455/auth/callback&scope=openid profile email offline_access&code_challenge=chal&code_challenge_method=S256&id_token_add_organizations=true&codex_cli_simplified_flow=true&state=st&originator=codex_vscode"
+        );
+
+        let corp = build_authorize_url(
+            &enterprise_profile(),
+            "http://localhost:1455/auth/callback",
+            "c",
+            "s",
+        );
+        assert!(corp.starts_with("https://sso.corp.example/openai/authorize?"));
+        assert!(corp.contains("client_id=corp-client&"));
+        assert!(corp.ends_with("&audience=codex%20api"));
+    }
+
+    #[test]
+    fn resolve_falls_back_to_builtin() {
+        let custom = vec![enterprise_profile()];
+        assert_eq!(resolve_profile(&custom, None), AuthProfile::builtin());
+        assert_eq!(
+            resolve_profile(&custom, Some(DEFAULT_PROFILE_ID)),
+            AuthProfile::builtin()
+        );
+        assert_eq!(
+            resolve_profile(&custom, Some("corp")).client_id,
+            "corp-client"
+        );
+        assert_eq!(
+            resolve_profile(&custom, Some("gone")),
+            AuthProfile::builtin()
+        );
+    }
+
+    #[test]
+    fn validation_requires_https_and_free_id() {
+        assert!(validate_profile(&enterprise_profile()).is_ok());
+
+        let mut p = enterprise_profile();
+        p.issuer = "http://sso.corp.example".to_string();
+        assert!(validate_profile(&p).unwrap_err().contains("issuer"));
+
+        let mut p = enterprise_profile();
+        p.token_endpoint = Some("http://token.corp.example/token".to_string());
+        assert!(validate_profile(&p).unwrap_err().contains("token_endpoint"));
+
+        let mut p = enterprise_profile();
+        p.id = DEFAULT_PROFILE_ID.to_string();
+        assert!(validate_profile(&p).is_err());
+
+        let mut p = enterprise_profile();
+        p.id = "has space".to_string();
+        assert!(validate_profile(&p).is_err());
+
+        let mut p = enterprise_profile();
+        p.client_id = " ".to_string();
+        assert!(validate_profile(&p).is_err());
+
+        let mut p = enterprise_profile();
+        p.extra_params
+            .insert("client_id".to_string(), "x".to_string());
+        assert!(validate_profile(&p).unwrap_err().contains("client_id"));
+    }
+
+    /// 起一个只应答一次的 token 端点，返回 (profile, 收到的请求)
+    async fn mock_token_server(
+        client_id: &str,
+        access_token: &str,
+    ) -> (AuthProfile, tokio::task::JoinHandle<String>) {
+        let body = serde_json::json!({
+            "access_token": access_token,
+            "refresh_token": "rt-next",
+            "expires_in": 3600
+        });
+        mock_token_server_with(client_id, "200 OK", body).await
+    }
+
+    async fn mock_token_server_with(
+        client_id: &str,
+        status: &str,
+        body: serde_json::Value,
+    ) -> (AuthProfile, tokio::task::JoinHandle<String>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let body = body.to_string();
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            body.len(),
+            body
+        );
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            // 头和表单体可能分两次到，读到表单体里的 scope 为止
+            let mut request = Vec::new();
+            let mut buf = [0u8; 4096];
+            while !String::from_utf8_lossy(&request).contains("scope=") {
+                let n = socket.read(&mut buf).await.unwrap();
+                if n == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buf[..n]);
+            }
+            let _ = socket.write_all(response.as_bytes()).await;
+            let _ = socket.shutdown().await;
+            String::from_utf8_lossy(&request).into_owned()
+        });
+        let profile = AuthProfile {
+            id: client_id.to_string(),
+            name: String::new(),
+            issuer: format!("http://{}", addr),
+            client_id: client_id.to_string(),
+            authorize_endpoint: None,
+            token_endpoint: Some(format!("/{}/token", client_id)),
+            extra_params: BTreeMap::new(),
+        };
+        (profile, handle)
+    }
+
+    #[tokio::test]
+    async fn refresh_uses_each_profiles_token_endpoint() {
+        let (alpha, alpha_req) = mock_token_server("alpha", "at-alpha").await;
+        let (beta, beta_req) = mock_token_server("beta", "at-beta").await;
+
+        let a = refresh_access_token(&alpha, "rt-a").await.unwrap();
+        let b = refresh_access_token(&beta, "rt-b").await.unwrap();
+        assert_eq!(a.access_token, "at-alpha");
+        assert_eq!(b.access_token, "at-beta");
+        assert_eq!(a.refresh_token.as_deref(), Some("rt-next"));
+
+        let alpha_req = alpha_req.await.unwrap();
+        assert!(alpha_req.starts_with("POST /alpha/token "), "{}", alpha_req);
+        assert!(alpha_req.contains("client_id=alpha"), "{}", alpha_req);
+        assert!(alpha_req.contains("refresh_token=rt-a"), "{}", alpha_req);
+
+        let beta_req = beta_req.await.unwrap();
+        assert!(beta_req.starts_with("POST /beta/token "), "{}", beta_req);
+        assert!(beta_req.contains("client_id=beta"), "{}", beta_req);
+    }
+
+    #[tokio::test]
+    async fn reuse_rejections_are_tagged() {
+        let reused = serde_json::json!({ "error": {
+            "message": "Your refresh token has already been used to generate a new access token.",
+            "code": "refresh_token_reused"
+        }});
+        let (profile, _) = mock_token_server_with("reuse", "401 Unauthorized", reused).await;
+        let err = refresh_access_token(&profile, "rt").await.unwrap_err();
+        assert!(err.starts_with(REFRESH_REUSED_PREFIX), "{}", err);
+        assert!(is_refresh_reused(&format!("TOKEN_INVALID:{}", err)));
+
+        let grant = serde_json::json!({ "error": "invalid_grant" });
+        let (profile, _) = mock_token_server_with("grant", "400 Bad Request", grant).await;
+        let err = refresh_access_token(&profile, "rt").await.unwrap_err();
+        assert!(is_refresh_reused(&err), "{}", err);
+
+        let other = serde_json::json!({ "error": "temporarily_unavailable" });
+        let (profile, _) = mock_token_server_with("other", "503 Service Unavailable", other).await;
+        let err = refresh_access_token(&profile, "rt").await.unwrap_err();
+        assert!(!is_refresh_reused(&err), "{}", err);
+    }
 }
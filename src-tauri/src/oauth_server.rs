@@ -1,18 +1,22 @@
 use crate::oauth;
 use base64::{engine::general_purpose, Engine as _};
 use rand::{rng, RngCore};
+use serde::Serialize;
 use std::sync::Mutex;
 use std::sync::OnceLock;
 use tauri::{AppHandle, Emitter};
 use tauri_plugin_opener::OpenerExt;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpListener;
+use tokio::sync::oneshot;
 use tokio::time::{Duration, Instant};
 use url::Url;
 
 /// 使用 OnceLock 代替 lazy_static 存储 OAuth 流程中的临时数据
 static PENDING_LOGIN: OnceLock<Mutex<Option<PendingLogin>>> = OnceLock::new();
 static CALLBACK_TASK: OnceLock<Mutex<Option<tokio::task::JoinHandle<()>>>> = OnceLock::new();
+/// 批量登录在等授权码时挂在这里；挂着时回调不再派发给前端，直接交给它
+static CODE_WAITER: OnceLock<Mutex<Option<oneshot::Sender<String>>>> = OnceLock::new();
 
 fn get_pending_login() -> &'static Mutex<Option<PendingLogin>> {
     PENDING_LOGIN.get_or_init(|| Mutex::new(None))
@@ -22,10 +26,39 @@ fn get_callback_task() -> &'static Mutex<Option<tokio::task::JoinHandle<()>>> {
     CALLBACK_TASK.get_or_init(|| Mutex::new(None))
 }
 
+fn get_code_waiter() -> &'static Mutex<Option<oneshot::Sender<String>>> {
+    CODE_WAITER.get_or_init(|| Mutex::new(None))
+}
+
+fn abort_callback_task() {
+    if let Ok(mut slot) = get_callback_task().lock() {
+        if let Some(task) = slot.take() {
+            task.abort();
+        }
+    }
+}
+
+/// 拿到授权码后的去向：有批量登录在等就交给它，否则派发 `oauth-callback-received` 给前端
+fn deliver_code(app_handle: &AppHandle, code: String) -> Result<(), String> {
+    let waiter = get_code_waiter().lock().ok().and_then(|mut w| w.take());
+    let code = match waiter {
+        Some(tx) => match tx.send(code) {
+            Ok(()) => return Ok(()),
+            // 等的一方已经放弃（超时 / 取消），退回老路径
+            Err(code) => code,
+        },
+        None => code,
+    };
+    app_handle
+        .emit("oauth-callback-received", code)
+        .map_err(|e| format!("派发 oauth-callback-received 失败: {}", e))
+}
+
 struct PendingLogin {
     pkce: oauth::PkceCodes,
     port: u16,
     state: String,
+    profile: oauth::AuthProfile,
 }
 
 /// 生成与官方一致的 state (Base64 编码的32字节随机数)
@@ -35,64 +68,224 @@ fn generate_state() -> String {
     general_purpose::URL_SAFE_NO_PAD.encode(bytes)
 }
 
-/// 官方固定端口
-const DEFAULT_PORT: u16 = 1455;
+/// `start_oauth_login` 的返回值
+#[derive(Debug, Clone, Serialize)]
+pub struct OAuthLoginStart {
+    pub auth_url: String,
+    /// 实际绑定的回调端口
+    pub port: u16,
+}
+
+/// 单个端口的绑定结果
+#[derive(Debug, Clone, Serialize)]
+pub struct PortAttempt {
+    pub port: u16,
+    pub error: String,
+    /// 占用该端口的进程（尽力而为，查不到为 None）
+    pub holder: Option<String>,
+}
+
+/// `start_oauth_login` 的结构化错误，前端按 `kind` 区分
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum OAuthStartError {
+    /// 候选端口全部绑定失败
+    PortUnavailable {
+        tried: Vec<PortAttempt>,
+    },
+    Other {
+        message: String,
+    },
+}
+
+impl std::fmt::Display for OAuthStartError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OAuthStartError::PortUnavailable { tried } => {
+                write!(f, "OAuth 回调端口全部被占用：")?;
+                for (i, a) in tried.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "；")?;
+                    }
+                    write!(f, "{} ({})", a.port, a.error)?;
+                    if let Some(holder) = &a.holder {
+                        write!(f, " 占用者: {}", holder)?;
+                    }
+                }
+                write!(f, "。请关闭占用进程后重试")
+            }
+            OAuthStartError::Other { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<String> for OAuthStartError {
+    fn from(message: String) -> Self {
+        OAuthStartError::Other { message }
+    }
+}
+
+impl From<&str> for OAuthStartError {
+    fn from(message: &str) -> Self {
+        OAuthStartError::Other {
+            message: message.to_string(),
+        }
+    }
+}
+
+/// 按顺序尝试绑定 loopback 端口，返回第一个成功的
+async fn bind_first_available(ports: &[u16]) -> Result<(TcpListener, u16), OAuthStartError> {
+    let mut tried = Vec::new();
+    for &port in ports {
+        match TcpListener::bind(("127.0.0.1", port)).await {
+            Ok(listener) => {
+                if !tried.is_empty() {
+                    println!("[OAuth] 端口 {:?} 不可用，改用 {}", tried, port);
+                }
+                return Ok((listener, port));
+            }
+            Err(e) => tried.push(PortAttempt {
+                port,
+                error: e.to_string(),
+                holder: port_holder(port),
+            }),
+        }
+    }
+    Err(OAuthStartError::PortUnavailable { tried })
+}
+
+/// 查询监听某端口的进程（`lsof`），仅用于报错提示
+#[cfg(unix)]
+fn port_holder(port: u16) -> Option<String> {
+    let output = std::process::Command::new("lsof")
+        .args(["-nP", &format!("-iTCP:{}", port), "-sTCP:LISTEN", "-Fpc"])
+        .output()
+        .ok()?;
+    parse_lsof_holder(&String::from_utf8_lossy(&output.stdout))
+}
+
+#[cfg(not(unix))]
+fn port_holder(_port: u16) -> Option<String> {
+    None
+}
+
+/// 解析 `lsof -F pc` 输出（`p<pid>` / `c<command>` 各占一行）
+#[cfg_attr(not(unix), allow(dead_code))]
+fn parse_lsof_holder(output: &str) -> Option<String> {
+    let mut pid = None;
+    let mut command = None;
+    for line in output.lines() {
+        if let Some(v) = line.strip_prefix('p') {
+            pid.get_or_insert_with(|| v.to_string());
+        } else if let Some(v) = line.strip_prefix('c') {
+            command.get_or_insert_with(|| v.to_string());
+        }
+    }
+    match (command, pid) {
+        (Some(c), Some(p)) => Some(format!("{} (pid {})", c, p)),
+        (None, Some(p)) => Some(format!("pid {}", p)),
+        _ => None,
+    }
+}
 
 /// 准备 OAuth 流程并返回授权 URL
 ///
 /// `open_browser=Some(false)` 时不调用系统默认浏览器，前端可以把返回的 URL 拷贝到剪贴板，
 /// 用户自行粘贴到目标浏览器里完成授权。回调监听仍然启动，所以授权完成后流程与"直接点击登录"一致。
+///
+/// 回调端口按设置 `oauth_callback_ports` 依次尝试（必须在 `oauth::ALLOWED_CALLBACK_PORTS` 内），
+/// 选中的端口随返回值带回。
+///
+/// `profile_id` 选择 OAuth 配置（见 `oauth::AuthProfile`），缺省走内置 `openai-default`；
+/// 登录完成后该 id 记在新账号上。
+///
+/// 批量登录（`bulk_login`）进行中时直接拒绝：两边共用回调端口和待完成的 PKCE 状态。
 #[tauri::command]
 pub async fn start_oauth_login(
     app_handle: AppHandle,
+    state: tauri::State<'_, crate::AppState>,
     open_browser: Option<bool>,
-) -> Result<String, String> {
-    // 1. 如果有旧回调任务，先中止，避免同一进程重复占用固定端口
-    if let Ok(mut task_slot) = get_callback_task().lock() {
-        if let Some(task) = task_slot.take() {
-            task.abort();
-        }
-    }
+    profile_id: Option<String>,
+) -> Result<OAuthLoginStart, OAuthStartError> {
+    crate::bulk_login::global().ensure_idle()?;
+    let (ports, profile) = login_settings(&state, profile_id.as_deref())?;
+    begin_authorization(
+        &app_handle,
+        &ports,
+        profile,
+        open_browser.unwrap_or(true),
+        None,
+    )
+    .await
+}
+
+/// 解析本次登录用的回调端口和 OAuth 配置
+pub(crate) fn login_settings(
+    state: &crate::AppState,
+    profile_id: Option<&str>,
+) -> Result<(Vec<u16>, oauth::AuthProfile), OAuthStartError> {
+    let (ports, profile) = {
+        let store = state.store.lock().map_err(|e| e.to_string())?;
+        let profile = match profile_id.as_deref() {
+            None | Some(oauth::DEFAULT_PROFILE_ID) => oauth::AuthProfile::builtin(),
+            Some(id) => store
+                .settings
+                .auth_profiles
+                .iter()
+                .find(|p| p.id == id)
+                .cloned()
+                .ok_or_else(|| OAuthStartError::from(format!("OAuth 配置 {} 不存在", id)))?,
+        };
+        (store.settings.oauth_callback_ports.clone(), profile)
+    };
+    // 手改配置导致不合法时退回默认白名单，不让登录卡死
+    let ports = if oauth::validate_callback_ports(&ports).is_ok() {
+        ports
+    } else {
+        oauth::ALLOWED_CALLBACK_PORTS.to_vec()
+    };
+    Ok((ports, profile))
+}
+
+/// 绑端口、生成 PKCE、开回调监听并（按需）打开浏览器。
+/// `login_hint` 非空时附在授权 URL 上，授权页会预填该邮箱
+async fn begin_authorization(
+    app_handle: &AppHandle,
+    ports: &[u16],
+    profile: oauth::AuthProfile,
+    open_browser: bool,
+    login_hint: Option<&str>,
+) -> Result<OAuthLoginStart, OAuthStartError> {
+    // 1. 如果有旧回调任务，先中止，避免同一进程重复占用端口
+    abort_callback_task();
 
     // 等待端口从旧任务释放
     tokio::time::sleep(Duration::from_millis(100)).await;
 
-    let listener = TcpListener::bind(format!("127.0.0.1:{}", DEFAULT_PORT))
-        .await
-        .map_err(|e| {
-            format!(
-                "无法绑定本地端口 {}: {}。请关闭占用该端口的进程后重试。",
-                DEFAULT_PORT, e
-            )
-        })?;
-    let port = DEFAULT_PORT;
+    let (listener, port) = bind_first_available(ports).await?;
 
     // 2. 生成 PKCE 和 State (与官方一致)
     let pkce = oauth::generate_pkce();
     let state = generate_state();
-    let redirect_uri = format!("http://localhost:{}/auth/callback", port);
+    let redirect_uri = oauth::callback_redirect_uri(port);
 
     // 3. 构造授权 URL (与官方完全一致: 手动拼接, 不对特殊字符编码)
-    let qs = format!(
-        "response_type=code&client_id={}&redirect_uri={}&scope={}&code_challenge={}&code_challenge_method=S256&id_token_add_organizations=true&codex_cli_simplified_flow=true&state={}&originator=codex_vscode",
-        oauth::CLIENT_ID,
-        redirect_uri,
-        "openid profile email offline_access",
-        pkce.code_challenge,
-        state
-    );
-
-    let auth_url = format!("{}?{}", oauth::AUTH_URL, qs);
+    let mut auth_url =
+        oauth::build_authorize_url(&profile, &redirect_uri, &pkce.code_challenge, &state);
+    if let Some(hint) = login_hint.filter(|h| !h.is_empty()) {
+        auth_url.push_str(&format!("&login_hint={}", urlencoding::encode(hint)));
+    }
 
     // 4. 保存状态，开启监听任务
     {
         let mut pending = get_pending_login()
             .lock()
-            .map_err(|_| "登录流程状态锁异常")?;
+            .map_err(|_| OAuthStartError::from("登录流程状态锁异常"))?;
         *pending = Some(PendingLogin {
             pkce: pkce.clone(),
             port,
             state: state.clone(),
+            profile,
         });
     }
 
@@ -106,11 +299,38 @@ pub async fn start_oauth_login(
     }
 
     // 6. 打开浏览器（除非前端显式要求"只拿 URL 不开浏览器"）
-    if open_browser.unwrap_or(true) {
+    if open_browser {
         let _ = app_handle.opener().open_url(&auth_url, None::<String>);
     }
 
-    Ok(auth_url)
+    Ok(OAuthLoginStart { auth_url, port })
+}
+
+/// 批量登录的一条：发起授权并等到授权码。
+/// 超时 / 取消时调用方丢弃这个 future，守卫负责摘掉等待者并停掉回调监听
+pub(crate) async fn authorize_and_wait(
+    app_handle: &AppHandle,
+    ports: &[u16],
+    profile: oauth::AuthProfile,
+    login_hint: Option<&str>,
+) -> Result<String, String> {
+    struct WaiterGuard;
+    impl Drop for WaiterGuard {
+        fn drop(&mut self) {
+            if let Ok(mut waiter) = get_code_waiter().lock() {
+                *waiter = None;
+            }
+            abort_callback_task();
+        }
+    }
+
+    let (tx, rx) = oneshot::channel();
+    *get_code_waiter().lock().map_err(|_| "登录流程状态锁异常")? = Some(tx);
+    let _guard = WaiterGuard;
+    begin_authorization(app_handle, ports, profile, true, login_hint)
+        .await
+        .map_err(|e| e.to_string())?;
+    rx.await.map_err(|_| "授权流程被中断".to_string())
 }
 
 /// 监听回调
@@ -159,8 +379,8 @@ async fn handle_callback(listener: TcpListener, app_handle: AppHandle, expected_
                 <script>setTimeout(() => window.close(), 3000)</script></body></html>";
             let _ = socket.write_all(response.as_bytes()).await;
 
-            if let Err(e) = app_handle.emit("oauth-callback-received", code) {
-                eprintln!("发送 oauth-callback-received 事件失败: {}", e);
+            if let Err(e) = deliver_code(&app_handle, code) {
+                eprintln!("{}", e);
             }
             return;
         }
@@ -229,17 +449,10 @@ pub async fn submit_oauth_callback(app_handle: AppHandle, input: String) -> Resu
     }
 
     // 停掉后端 HTTP 监听，避免它再接收一个回调
-    if let Ok(mut slot) = get_callback_task().lock() {
-        if let Some(task) = slot.take() {
-            task.abort();
-        }
-    }
+    abort_callback_task();
 
-    // 走跟 HTTP 监听完全相同的路径：把 code 丢到前端
-    app_handle
-        .emit("oauth-callback-received", code)
-        .map_err(|e| format!("派发 oauth-callback-received 失败: {}", e))?;
-    Ok(())
+    // 走跟 HTTP 监听完全相同的路径：交给等着的批量登录，或丢到前端
+    deliver_code(&app_handle, code)
 }
 
 fn parse_callback_input(input: &str) -> (Option<String>, Option<String>) {
@@ -300,24 +513,83 @@ pub async fn copy_to_clipboard(text: String) -> Result<(), String> {
     Ok(())
 }
 
-/// 最后一步：使用捕获到的 Code 交换 Token (由前端触发)
+/// 最后一步：使用捕获到的 Code 交换 Token (由前端触发)。
+/// 一并返回本次登录所用 profile，调用方记到账号上
 #[tauri::command]
-pub async fn complete_oauth_login(code: String) -> Result<oauth::TokenResponse, String> {
+pub async fn complete_oauth_login(
+    code: String,
+) -> Result<(oauth::TokenResponse, oauth::AuthProfile), String> {
     // 提取所需数据并立即释放锁，避免跨 await 持有 MutexGuard
-    let (code_verifier, port) = {
+    let (code_verifier, port, profile) = {
         let mut pending_lock = get_pending_login().lock().map_err(|_| "锁被污染")?;
         let pending = pending_lock.take().ok_or("登录流程已过期或未启动")?;
-        (pending.pkce.code_verifier, pending.port)
+        (pending.pkce.code_verifier, pending.port, pending.profile)
     };
 
-    let redirect_uri = format!("http://localhost:{}/auth/callback", port);
+    let redirect_uri = oauth::callback_redirect_uri(port);
 
-    oauth::exchange_code(&code, &redirect_uri, &code_verifier).await
+    let tokens = oauth::exchange_code(&profile, &code, &redirect_uri, &code_verifier).await?;
+    Ok((tokens, profile))
 }
 
 #[cfg(test)]
 mod tests {
-    use super::extract_oauth_code_from_request;
+    use super::{
+        bind_first_available, extract_oauth_code_from_request, parse_lsof_holder, OAuthStartError,
+    };
+    use crate::oauth::{validate_callback_ports, ALLOWED_CALLBACK_PORTS};
+
+    /// 拿一个当前空闲的端口（绑 0 再放掉）
+    fn free_port() -> u16 {
+        let l = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        l.local_addr().unwrap().port()
+    }
+
+    #[tokio::test]
+    async fn bind_falls_back_to_next_port_when_first_is_taken() {
+        let squatter = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let taken = squatter.local_addr().unwrap().port();
+        let free = free_port();
+
+        let (_listener, port) = bind_first_available(&[taken, free]).await.unwrap();
+        assert_eq!(port, free);
+    }
+
+    #[tokio::test]
+    async fn bind_reports_every_port_when_all_taken() {
+        let a = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let b = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let ports = [
+            a.local_addr().unwrap().port(),
+            b.local_addr().unwrap().port(),
+        ];
+
+        match bind_first_available(&ports).await {
+            Err(OAuthStartError::PortUnavailable { tried }) => {
+                assert_eq!(tried.iter().map(|t| t.port).collect::<Vec<_>>(), ports);
+                assert!(tried.iter().all(|t| !t.error.is_empty()));
+            }
+            other => panic!("expected PortUnavailable, got {:?}", other.map(|(_, p)| p)),
+        }
+    }
+
+    #[test]
+    fn callback_ports_must_be_registered_subset() {
+        assert!(validate_callback_ports(&ALLOWED_CALLBACK_PORTS).is_ok());
+        assert!(validate_callback_ports(&[1455]).is_ok());
+        assert!(validate_callback_ports(&[]).is_err());
+        assert!(validate_callback_ports(&[1455, 8080]).is_err());
+        assert!(validate_callback_ports(&[1455, 1455]).is_err());
+    }
+
+    #[test]
+    fn parses_lsof_field_output() {
+        assert_eq!(
+            parse_lsof_holder("p4242\ncnode\nf12\n"),
+            Some("node (pid 4242)".to_string())
+        );
+        assert_eq!(parse_lsof_holder(""), None);
+    }
 
     #[test]
     fn extract_code_success_when_state_matches() {
@@ -24,7 +24,7 @@ use std::time::{Duration, Instant};
 
 const UA: &str = "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/142.0.0.0 Safari/537.36";
 const REDIRECT_HOST: &str = "localhost";
-const REDIRECT_PORT: u16 = 1455;
+const REDIRECT_PORT: u16 = crate::oauth::ALLOWED_CALLBACK_PORTS[0];
 const FLOW: &str = "authorize_continue";
 
 #[derive(Debug, Clone)]
@@ -330,7 +330,13 @@ pub async fn run_login(
     println!("  → 拿到 OAuth code: {}…", &code[..code.len().min(16)]);
 
     log_step("9. POST /oauth/token 兑换 access/refresh/id_token");
-    let token = oauth::exchange_code(&code, &redirect_uri, &pkce.code_verifier).await?;
+    let token = oauth::exchange_code(
+        &oauth::AuthProfile::builtin(),
+        &code,
+        &redirect_uri,
+        &pkce.code_verifier,
+    )
+    .await?;
     let email = token
         .id_token
         .as_deref()
@@ -341,16 +347,13 @@ pub async fn run_login(
 }
 
 fn build_auth_url(code_challenge: &str, state: &str, redirect_uri: &str) -> String {
-    // 与 oauth_server.rs 保持一致：raw 拼接，不 urlencode
-    let qs = format!(
-        "response_type=code&client_id={}&redirect_uri={}&scope={}&code_challenge={}&code_challenge_method=S256&id_token_add_organizations=true&codex_cli_simplified_flow=true&state={}&originator=codex_vscode",
-        oauth::CLIENT_ID,
+    // OTP 自动登录只对接官方登录页，固定用内置 profile
+    oauth::build_authorize_url(
+        &oauth::AuthProfile::builtin(),
         redirect_uri,
-        "openid profile email offline_access",
         code_challenge,
-        state
-    );
-    format!("{}?{}", oauth::AUTH_URL, qs)
+        state,
+    )
 }
 
 fn generate_state() -> String {
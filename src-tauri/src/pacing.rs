@@ -0,0 +1,157 @@
+//! 后台同步节流：两轮同步之间至少隔 [`MIN_CYCLE_SPACING`]
+//!
+//! 调度间隔到点、系统唤醒后的立即重同步可能前后脚触发，各自都想跑一整轮。
+//! scheduler 每轮真正开始前问一次 [`SyncPacer::try_begin`]：离上一轮太近就记一次跳过、
+//! 等到满足间距再说。生效的调度间隔和最近一次跳过一起进诊断包的 scheduler 健康信息。
+//!
+//! 时间从 [`Clock`] 取，测试里换成假时钟。
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::activity::{Clock, SystemClock};
+
+/// 两轮实际同步之间的最小间距
+pub const MIN_CYCLE_SPACING: Duration = Duration::from_secs(60);
+
+#[derive(Default)]
+struct Inner {
+    interval_minutes: Option<u32>,
+    last_cycle: Option<Instant>,
+    last_skip: Option<Instant>,
+    skips: u64,
+}
+
+/// scheduler 健康信息里的节流部分
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct PacingHealth {
+    /// 最近一轮实际使用的调度间隔（分钟）；还没跑过为 None
+    pub interval_minutes: Option<u32>,
+    pub min_spacing_secs: u64,
+    pub last_cycle_secs_ago: Option<u64>,
+    /// 最近一次因间距不够被跳过距今多久
+    pub last_spacing_skip_secs_ago: Option<u64>,
+    pub spacing_skips: u64,
+}
+
+pub struct SyncPacer<C: Clock = SystemClock> {
+    clock: C,
+    min_spacing: Duration,
+    inner: Mutex<Inner>,
+}
+
+impl Default for SyncPacer {
+    fn default() -> Self {
+        Self::with_clock(SystemClock, MIN_CYCLE_SPACING)
+    }
+}
+
+impl<C: Clock> SyncPacer<C> {
+    pub fn with_clock(clock: C, min_spacing: Duration) -> Self {
+        Self {
+            clock,
+            min_spacing,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    /// 每轮同步开始前调用：间距够就记下本轮（连同生效的间隔）并返回 None；
+    /// 否则记一次跳过，返回还需等待的时长
+    pub fn try_begin(&self, interval_minutes: u32) -> Option<Duration> {
+        let now = self.clock.now();
+        let Ok(mut inner) = self.inner.lock() else {
+            return None;
+        };
+        let wait = inner
+            .last_cycle
+            .map(|at| now.saturating_duration_since(at))
+            .filter(|elapsed| *elapsed < self.min_spacing)
+            .map(|elapsed| self.min_spacing - elapsed);
+        if wait.is_some() {
+            inner.last_skip = Some(now);
+            inner.skips += 1;
+        } else {
+            inner.last_cycle = Some(now);
+            inner.interval_minutes = Some(interval_minutes);
+        }
+        wait
+    }
+
+    pub fn health(&self) -> PacingHealth {
+        let now = self.clock.now();
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let ago = |at: Option<Instant>| at.map(|at| now.saturating_duration_since(at).as_secs());
+        PacingHealth {
+            interval_minutes: inner.interval_minutes,
+            min_spacing_secs: self.min_spacing.as_secs(),
+            last_cycle_secs_ago: ago(inner.last_cycle),
+            last_spacing_skip_secs_ago: ago(inner.last_skip),
+            spacing_skips: inner.skips,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[derive(Clone)]
+    struct FakeClock(Arc<Mutex<Instant>>);
+
+    impl FakeClock {
+        fn advance(&self, d: Duration) {
+            *self.0.lock().unwrap() += d;
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Instant {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    fn pacer() -> (SyncPacer<FakeClock>, FakeClock) {
+        let clock = FakeClock(Arc::new(Mutex::new(Instant::now())));
+        (
+            SyncPacer::with_clock(clock.clone(), MIN_CYCLE_SPACING),
+            clock,
+        )
+    }
+
+    #[test]
+    fn first_cycle_runs_immediately() {
+        let (pacer, _) = pacer();
+        assert_eq!(pacer.try_begin(30), None);
+        let health = pacer.health();
+        assert_eq!(health.interval_minutes, Some(30));
+        assert_eq!(health.last_cycle_secs_ago, Some(0));
+        assert_eq!(health.spacing_skips, 0);
+        assert_eq!(health.last_spacing_skip_secs_ago, None);
+    }
+
+    #[test]
+    fn coinciding_triggers_are_spaced_out() {
+        let (pacer, clock) = pacer();
+        assert_eq!(pacer.try_begin(30), None);
+
+        // 唤醒重同步 20 秒后又触发一轮：等满 60 秒
+        clock.advance(Duration::from_secs(20));
+        assert_eq!(pacer.try_begin(30), Some(Duration::from_secs(40)));
+        clock.advance(Duration::from_secs(39));
+        assert_eq!(pacer.try_begin(30), Some(Duration::from_secs(1)));
+        let health = pacer.health();
+        assert_eq!(health.spacing_skips, 2);
+        assert_eq!(health.last_spacing_skip_secs_ago, Some(0));
+        assert_eq!(health.last_cycle_secs_ago, Some(59), "跳过不算一轮");
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(pacer.try_begin(45), None);
+        let health = pacer.health();
+        assert_eq!(health.interval_minutes, Some(45));
+        assert_eq!(health.last_cycle_secs_ago, Some(0));
+        assert_eq!(health.last_spacing_skip_secs_ago, Some(1));
+    }
+}
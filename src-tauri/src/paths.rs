@@ -0,0 +1,431 @@
+//! 数据目录解析
+//!
+//! 以前各处直接 `dirs::home_dir().expect(..)`，在拿不到用户目录的环境
+//! （部分 Windows 服务上下文、没设 HOME 的精简容器 / systemd user service）会整个 panic。
+//! 现在启动时统一解析一次"基准目录"（相当于原来的 `~`），按顺序尝试：
+//!
+//! 1. `$HOME`
+//! 2. `dirs::home_dir()`（平台 API，Windows Known Folder / getpwuid）
+//! 3. `--data-dir <dir>` 启动参数，其次 `CODEX_SWITCHER_HOME` 环境变量
+//! 4. 便携模式：可执行文件旁的 `codex-switcher-data/`
+//!
+//! 第一个可写的胜出；`~/.codex-switcher` 与 `~/.codex` 都挂在它下面。
+//! 全部不可写时 `init` 返回 `PathsError`，由 `run()` 负责报给用户。
+//!
+//! Codex 自己的目录（auth.json 等）另有覆盖：设置 `codex_home` > `CODEX_HOME` 环境变量 >
+//! `<home>/.codex`，见 [`codex_dir`]。设置改了经 [`set_codex_home`] 立即生效，不用重启。
+
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::sync::{OnceLock, RwLock};
+
+/// 启动参数：显式指定基准目录
+pub const DATA_DIR_FLAG: &str = "--data-dir";
+/// 环境变量：显式指定基准目录
+pub const DATA_DIR_ENV: &str = "CODEX_SWITCHER_HOME";
+/// 便携模式目录名（位于可执行文件同级）
+pub const PORTABLE_DIR_NAME: &str = "codex-switcher-data";
+/// Codex CLI 自己认的配置目录环境变量
+pub const CODEX_HOME_ENV: &str = "CODEX_HOME";
+
+static RESOLVED: OnceLock<Paths> = OnceLock::new();
+/// 设置里的 `codex_home`（已展开 `~`）
+static CODEX_HOME_SETTING: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PathSource {
+    HomeEnv,
+    PlatformHome,
+    CliFlag,
+    EnvOverride,
+    Portable,
+}
+
+impl PathSource {
+    fn label(self) -> &'static str {
+        match self {
+            PathSource::HomeEnv => "$HOME",
+            PathSource::PlatformHome => "dirs::home_dir()",
+            PathSource::CliFlag => DATA_DIR_FLAG,
+            PathSource::EnvOverride => DATA_DIR_ENV,
+            PathSource::Portable => "便携模式",
+        }
+    }
+}
+
+/// 单个候选的尝试结果（`get_paths` 诊断用）
+#[derive(Debug, Clone, Serialize)]
+pub struct PathAttempt {
+    pub source: PathSource,
+    /// 候选不存在时为 None（如 HOME 未设置）
+    pub base: Option<PathBuf>,
+    /// None 表示选用；否则是跳过原因
+    pub rejected: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Paths {
+    /// 基准目录（相当于 `~`）
+    pub home: PathBuf,
+    /// `<home>/.codex-switcher`
+    pub data_dir: PathBuf,
+    /// `<home>/.codex`；`get_paths` 返回的是生效的 Codex 目录（见 [`codex_dir`]）
+    pub codex_dir: PathBuf,
+    pub source: PathSource,
+    pub attempts: Vec<PathAttempt>,
+}
+
+/// 所有候选都不可用
+#[derive(Debug, Clone, Serialize)]
+pub struct PathsError {
+    pub attempts: Vec<PathAttempt>,
+}
+
+impl std::fmt::Display for PathsError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "找不到可写的数据目录，已尝试：")?;
+        for a in &self.attempts {
+            let base = a
+                .base
+                .as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_else(|| "(未提供)".to_string());
+            writeln!(
+                f,
+                "  - {}: {} → {}",
+                a.source.label(),
+                base,
+                a.rejected.as_deref().unwrap_or("可用")
+            )?;
+        }
+        write!(
+            f,
+            "可通过 {} <目录> 或环境变量 {} 指定数据目录",
+            DATA_DIR_FLAG, DATA_DIR_ENV
+        )
+    }
+}
+
+/// 解析所需的外部输入，测试里直接构造，不碰真实环境
+#[derive(Debug, Clone, Default)]
+pub struct PathInputs {
+    pub home_env: Option<PathBuf>,
+    pub platform_home: Option<PathBuf>,
+    pub cli_data_dir: Option<PathBuf>,
+    pub env_data_dir: Option<PathBuf>,
+    pub exe_dir: Option<PathBuf>,
+}
+
+impl PathInputs {
+    /// 从当前进程收集
+    pub fn from_process() -> Self {
+        let non_empty = |v: String| (!v.trim().is_empty()).then(|| PathBuf::from(v));
+        Self {
+            home_env: std::env::var("HOME").ok().and_then(non_empty),
+            platform_home: dirs::home_dir(),
+            cli_data_dir: parse_data_dir_flag(std::env::args().skip(1)),
+            env_data_dir: std::env::var(DATA_DIR_ENV).ok().and_then(non_empty),
+            exe_dir: std::env::current_exe()
+                .ok()
+                .and_then(|p| p.parent().map(|d| d.to_path_buf())),
+        }
+    }
+
+    fn candidates(&self) -> Vec<(PathSource, Option<PathBuf>)> {
+        vec![
+            (PathSource::HomeEnv, self.home_env.clone()),
+            (PathSource::PlatformHome, self.platform_home.clone()),
+            (PathSource::CliFlag, self.cli_data_dir.clone()),
+            (PathSource::EnvOverride, self.env_data_dir.clone()),
+            (
+                PathSource::Portable,
+                self.exe_dir.as_ref().map(|d| d.join(PORTABLE_DIR_NAME)),
+            ),
+        ]
+    }
+}
+
+/// 支持 `--data-dir <dir>` 与 `--data-dir=<dir>`
+fn parse_data_dir_flag(args: impl IntoIterator<Item = String>) -> Option<PathBuf> {
+    let mut iter = args.into_iter();
+    while let Some(arg) = iter.next() {
+        if arg == DATA_DIR_FLAG {
+            return iter.next().map(PathBuf::from);
+        }
+        if let Some(v) = arg.strip_prefix(&format!("{}=", DATA_DIR_FLAG)) {
+            return Some(PathBuf::from(v));
+        }
+    }
+    None
+}
+
+impl Paths {
+    fn from_home(home: PathBuf, source: PathSource, attempts: Vec<PathAttempt>) -> Self {
+        Self {
+            data_dir: home.join(".codex-switcher"),
+            codex_dir: home.join(".codex"),
+            home,
+            source,
+            attempts,
+        }
+    }
+
+    /// 按顺序挑第一个 `check` 通过的候选；`check` 返回 Err 说明跳过原因
+    pub fn resolve_with(
+        inputs: &PathInputs,
+        check: impl Fn(&Path) -> Result<(), String>,
+    ) -> Result<Self, PathsError> {
+        let mut attempts = Vec::new();
+        for (source, base) in inputs.candidates() {
+            let Some(base) = base else {
+                attempts.push(PathAttempt {
+                    source,
+                    base: None,
+                    rejected: Some("未提供".to_string()),
+                });
+                continue;
+            };
+            match check(&base) {
+                Ok(()) => {
+                    attempts.push(PathAttempt {
+                        source,
+                        base: Some(base.clone()),
+                        rejected: None,
+                    });
+                    return Ok(Self::from_home(base, source, attempts));
+                }
+                Err(reason) => attempts.push(PathAttempt {
+                    source,
+                    base: Some(base),
+                    rejected: Some(reason),
+                }),
+            }
+        }
+        Err(PathsError { attempts })
+    }
+}
+
+/// 可写探测：能建出 `<base>/.codex-switcher` 并在里面写文件
+fn probe_writable(base: &Path) -> Result<(), String> {
+    let dir = base.join(".codex-switcher");
+    std::fs::create_dir_all(&dir).map_err(|e| format!("无法创建目录: {}", e))?;
+    let probe = dir.join(format!(".write-probe-{}", std::process::id()));
+    std::fs::write(&probe, b"ok").map_err(|e| format!("不可写: {}", e))?;
+    let _ = std::fs::remove_file(&probe);
+    Ok(())
+}
+
+/// 启动时调用一次：解析并固定全局路径。重复调用返回已固定的结果。
+pub fn init() -> Result<&'static Paths, PathsError> {
+    if let Some(p) = RESOLVED.get() {
+        return Ok(p);
+    }
+    let resolved = Paths::resolve_with(&PathInputs::from_process(), probe_writable)?;
+    Ok(RESOLVED.get_or_init(|| resolved))
+}
+
+/// 当前生效的路径。
+///
+/// 应用内 `run()` 已经 `init()` 过，直接返回固定值；集成测试等未 init 的场景
+/// 每次按当前环境现算（测试会临时改 HOME），不做可写探测。
+/// 连一个候选都没有时退到系统临时目录，保证不 panic。
+pub fn current() -> Paths {
+    if let Some(p) = RESOLVED.get() {
+        return p.clone();
+    }
+    Paths::resolve_with(&PathInputs::from_process(), |_| Ok(())).unwrap_or_else(|e| {
+        Paths::from_home(std::env::temp_dir(), PathSource::Portable, e.attempts)
+    })
+}
+
+/// `~/.codex-switcher`
+pub fn data_dir() -> PathBuf {
+    current().data_dir
+}
+
+/// Codex 配置目录：设置 `codex_home` > `CODEX_HOME` > `~/.codex`
+pub fn codex_dir() -> PathBuf {
+    let setting = CODEX_HOME_SETTING
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    resolve_codex_dir(setting)
+}
+
+/// 设置 `codex_home` 改成 `raw` 后的 Codex 配置目录（保存设置前校验用）
+pub fn codex_dir_for(raw: Option<&str>) -> PathBuf {
+    resolve_codex_dir(expand_codex_home(raw, &home_dir()))
+}
+
+fn resolve_codex_dir(setting: Option<PathBuf>) -> PathBuf {
+    let env = std::env::var(CODEX_HOME_ENV)
+        .ok()
+        .filter(|v| !v.trim().is_empty())
+        .map(PathBuf::from);
+    setting.or(env).unwrap_or_else(|| current().codex_dir)
+}
+
+/// 设置里填的目录：去首尾空白，`~` 开头的换成基准目录；空的当没填
+fn expand_codex_home(raw: Option<&str>, home: &Path) -> Option<PathBuf> {
+    let raw = raw.map(str::trim).filter(|v| !v.is_empty())?;
+    match raw.strip_prefix('~') {
+        Some("") => Some(home.to_path_buf()),
+        Some(rest) if rest.starts_with(['/', '\\']) => Some(home.join(&rest[1..])),
+        _ => Some(PathBuf::from(raw)),
+    }
+}
+
+/// 检查设置里的 `codex_home`：须是绝对路径，不存在就建出来，建不出来或不是目录报错。
+/// 返回展开后的目录；没填时为 None
+pub fn validate_codex_home(raw: Option<&str>) -> Result<Option<PathBuf>, String> {
+    let Some(dir) = expand_codex_home(raw, &home_dir()) else {
+        return Ok(None);
+    };
+    if !dir.is_absolute() {
+        return Err(format!(
+            "Codex 目录需填绝对路径（可用 ~ 开头）: {}",
+            dir.display()
+        ));
+    }
+    if dir.exists() && !dir.is_dir() {
+        return Err(format!("Codex 目录不是文件夹: {}", dir.display()));
+    }
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("无法创建 Codex 目录 {}: {}", dir.display(), e))?;
+    Ok(Some(dir))
+}
+
+/// 同步设置里的 `codex_home`（启动加载设置、保存设置时调）；之后的 [`codex_dir`] 立即用新值
+pub fn set_codex_home(raw: Option<&str>) {
+    let dir = expand_codex_home(raw, &home_dir());
+    *CODEX_HOME_SETTING
+        .write()
+        .unwrap_or_else(|e| e.into_inner()) = dir;
+}
+
+/// 基准目录（`~`），给需要访问其它 CLI 目录（~/.claude 等）的地方用
+pub fn home_dir() -> PathBuf {
+    current().home
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all_inputs() -> PathInputs {
+        PathInputs {
+            home_env: Some(PathBuf::from("/h/env")),
+            platform_home: Some(PathBuf::from("/h/platform")),
+            cli_data_dir: Some(PathBuf::from("/h/cli")),
+            env_data_dir: Some(PathBuf::from("/h/override")),
+            exe_dir: Some(PathBuf::from("/opt/app")),
+        }
+    }
+
+    fn only_writable(ok: &'static [&'static str]) -> impl Fn(&Path) -> Result<(), String> {
+        move |p: &Path| {
+            if ok.iter().any(|o| Path::new(o) == p) {
+                Ok(())
+            } else {
+                Err("不可写".to_string())
+            }
+        }
+    }
+
+    #[test]
+    fn home_env_wins_when_writable() {
+        let p = Paths::resolve_with(&all_inputs(), |_| Ok(())).unwrap();
+        assert_eq!(p.source, PathSource::HomeEnv);
+        assert_eq!(p.data_dir, PathBuf::from("/h/env/.codex-switcher"));
+        assert_eq!(p.codex_dir, PathBuf::from("/h/env/.codex"));
+        assert_eq!(p.attempts.len(), 1);
+    }
+
+    #[test]
+    fn falls_back_in_order() {
+        let mut inputs = all_inputs();
+        inputs.home_env = None;
+        let p = Paths::resolve_with(&inputs, only_writable(&["/h/cli", "/h/override"])).unwrap();
+        assert_eq!(p.source, PathSource::CliFlag);
+        assert_eq!(p.attempts[0].rejected.as_deref(), Some("未提供"));
+        assert_eq!(p.attempts[1].rejected.as_deref(), Some("不可写"));
+
+        inputs.cli_data_dir = None;
+        let p = Paths::resolve_with(&inputs, only_writable(&["/h/override"])).unwrap();
+        assert_eq!(p.source, PathSource::EnvOverride);
+
+        let p =
+            Paths::resolve_with(&inputs, only_writable(&["/opt/app/codex-switcher-data"])).unwrap();
+        assert_eq!(p.source, PathSource::Portable);
+        assert_eq!(
+            p.data_dir,
+            PathBuf::from("/opt/app/codex-switcher-data/.codex-switcher")
+        );
+    }
+
+    #[test]
+    fn errors_when_nothing_writable() {
+        let err = Paths::resolve_with(&all_inputs(), only_writable(&[])).unwrap_err();
+        assert_eq!(err.attempts.len(), 5);
+        assert!(err.to_string().contains(DATA_DIR_ENV));
+    }
+
+    #[test]
+    fn parses_data_dir_flag_forms() {
+        let args = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            parse_data_dir_flag(args(&["--data-dir", "/x"])),
+            Some(PathBuf::from("/x"))
+        );
+        assert_eq!(
+            parse_data_dir_flag(args(&["--foo", "--data-dir=/y"])),
+            Some(PathBuf::from("/y"))
+        );
+        assert_eq!(parse_data_dir_flag(args(&["--data-dir"])), None);
+    }
+
+    #[test]
+    fn probe_rejects_unwritable_and_accepts_tempdir() {
+        let tmp = std::env::temp_dir().join(format!("codex-switcher-paths-{}", std::process::id()));
+        assert!(probe_writable(&tmp).is_ok());
+        assert!(tmp.join(".codex-switcher").is_dir());
+        let _ = std::fs::remove_dir_all(&tmp);
+
+        let file =
+            std::env::temp_dir().join(format!("codex-switcher-paths-file-{}", std::process::id()));
+        std::fs::write(&file, b"x").unwrap();
+        assert!(probe_writable(&file).is_err());
+        let _ = std::fs::remove_file(&file);
+    }
+
+    #[test]
+    fn codex_home_setting_expands_tilde_and_is_created() {
+        let home = Path::new("/h/env");
+        assert_eq!(expand_codex_home(None, home), None);
+        assert_eq!(expand_codex_home(Some("  "), home), None);
+        assert_eq!(expand_codex_home(Some("~"), home), Some(home.to_path_buf()));
+        assert_eq!(
+            expand_codex_home(Some(" ~/proj/.codex "), home),
+            Some(home.join("proj/.codex"))
+        );
+        assert_eq!(
+            expand_codex_home(Some("~other/x"), home),
+            Some(PathBuf::from("~other/x"))
+        );
+
+        let tmp = std::env::temp_dir().join(format!("codex-switcher-home-{}", std::process::id()));
+        let dir = tmp.join("a").join(".codex");
+        let resolved = validate_codex_home(dir.to_str()).unwrap();
+        assert_eq!(resolved, Some(dir.clone()));
+        assert!(dir.is_dir());
+
+        let file = tmp.join("file");
+        std::fs::write(&file, b"x").unwrap();
+        let err = validate_codex_home(file.to_str()).unwrap_err();
+        assert!(err.contains("不是文件夹"), "{}", err);
+        assert!(validate_codex_home(Some("relative/.codex")).is_err());
+        assert_eq!(validate_codex_home(Some("")).unwrap(), None);
+        let _ = std::fs::remove_dir_all(&tmp);
+    }
+}
@@ -0,0 +1,142 @@
+//! 套餐名展示 + 套餐变更检测
+//!
+//! usage 接口返回的 `plan_type` 是原始值（`plus` / `pro` / `team` ...），OpenAI 时不时改名。
+//! 存储和比较一律用原始值，只有渲染时经 [`display_plan`] 转成展示名：
+//! 已知值查表，未知值按 `_` / `-` / 空格分词后首字母大写原样透出。
+
+use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// 已知原始值 → 展示名（key 为小写）
+const PLAN_LABELS: &[(&str, &str)] = &[
+    ("free", "Free"),
+    ("free_workspace", "Free 工作区"),
+    ("guest", "访客"),
+    ("go", "Go"),
+    ("plus", "Plus"),
+    ("pro", "Pro"),
+    ("prolite", "Pro Lite"),
+    ("team", "Team"),
+    ("business", "Business"),
+    ("enterprise", "Enterprise"),
+    ("edu", "Edu"),
+    ("education", "Edu"),
+    ("unknown", "未知"),
+];
+
+/// 原始 plan_type → 展示名
+pub fn display_plan(raw: &str) -> String {
+    let key = raw.trim().to_lowercase();
+    if key.is_empty() {
+        return "未知".to_string();
+    }
+    if let Some((_, label)) = PLAN_LABELS.iter().find(|(k, _)| *k == key) {
+        return label.to_string();
+    }
+    key.split(['_', '-', ' '])
+        .filter(|w| !w.is_empty())
+        .map(|w| {
+            let mut chars = w.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect::<String>(),
+                None => String::new(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// 一次套餐变更（只保留最近一次）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct PlanChange {
+    pub old_plan: String,
+    pub new_plan: String,
+    pub changed_at: DateTime<Utc>,
+}
+
+impl PlanChange {
+    /// 新套餐在已知档位里比旧的低（例如 pro → plus）；有一边不在表里时返回 false
+    pub fn is_downgrade(&self) -> bool {
+        match (plan_rank(&self.old_plan), plan_rank(&self.new_plan)) {
+            (Some(old), Some(new)) => new < old,
+            _ => false,
+        }
+    }
+}
+
+/// 粗略档位，只用来判断升 / 降级
+pub(crate) fn plan_rank(raw: &str) -> Option<u8> {
+    let rank = match raw.trim().to_lowercase().as_str() {
+        "guest" | "free" | "free_workspace" => 0,
+        "go" => 1,
+        "plus" => 2,
+        "prolite" => 3,
+        "pro" => 4,
+        _ => return None,
+    };
+    Some(rank)
+}
+
+/// 空值 / `unknown` 多半是接口没返回，不算真正的套餐
+fn is_meaningful(raw: &str) -> bool {
+    let raw = raw.trim();
+    !raw.is_empty() && !raw.eq_ignore_ascii_case("unknown")
+}
+
+/// 对比旧 / 新原始值；没有旧值（首次拉取）、任一边无意义、或只是大小写不同都不算变更
+pub fn detect_change(old: Option<&str>, new: &str, at: DateTime<Utc>) -> Option<PlanChange> {
+    let old = old.filter(|o| is_meaningful(o))?;
+    if !is_meaningful(new) || old.trim().eq_ignore_ascii_case(new.trim()) {
+        return None;
+    }
+    Some(PlanChange {
+        old_plan: old.trim().to_string(),
+        new_plan: new.trim().to_string(),
+        changed_at: at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_plans_use_table() {
+        assert_eq!(display_plan("plus"), "Plus");
+        assert_eq!(display_plan("PRO"), "Pro");
+        assert_eq!(display_plan("prolite"), "Pro Lite");
+        assert_eq!(display_plan(" business "), "Business");
+        assert_eq!(display_plan("unknown"), "未知");
+        assert_eq!(display_plan(""), "未知");
+    }
+
+    #[test]
+    fn unknown_plans_are_title_cased() {
+        assert_eq!(display_plan("self_serve_business"), "Self Serve Business");
+        assert_eq!(display_plan("team-plus"), "Team Plus");
+        assert_eq!(display_plan("ultra"), "Ultra");
+    }
+
+    #[test]
+    fn detects_real_changes_only() {
+        let now = Utc::now();
+        let change = detect_change(Some("pro"), "plus", now).unwrap();
+        assert_eq!(change.old_plan, "pro");
+        assert_eq!(change.new_plan, "plus");
+        assert_eq!(change.changed_at, now);
+        assert!(change.is_downgrade());
+        assert!(!detect_change(Some("plus"), "pro", now)
+            .unwrap()
+            .is_downgrade());
+
+        assert!(detect_change(None, "plus", now).is_none());
+        assert!(detect_change(Some("plus"), "plus", now).is_none());
+        assert!(detect_change(Some("Plus"), "plus", now).is_none());
+        assert!(detect_change(Some("unknown"), "plus", now).is_none());
+        assert!(detect_change(Some("plus"), "unknown", now).is_none());
+        assert!(!detect_change(Some("team"), "business", now)
+            .unwrap()
+            .is_downgrade());
+    }
+}
@@ -0,0 +1,225 @@
+//! 系统睡眠/唤醒检测
+//!
+//! tokio 的 sleep 基于单调时钟，笔记本合盖睡一晚后倒计时只是接着走，
+//! 唤醒后最长要等一个完整周期才会同步。这里用“墙钟 vs 单调时钟”漂移做基线检测：
+//! 墙钟前进远超单调时钟的经过时间，就视为刚从睡眠中恢复。
+//!
+//! 检测到唤醒后：
+//! - 唤醒所有挂在 `sleep_or_resume` 上的后台循环（调度器 / 额度刷新 / 快速同步），
+//!   让它们立刻跑一轮并重新计算基于重置时间的下一次唤醒
+//! - 当前账号立即按 auth.json 回流同步 + 刷新额度
+//! - emit "accounts-updated" 并刷新托盘
+//!
+//! 不依赖平台电源事件（IOKit / WM_POWERBROADCAST / logind），漂移检测在所有平台都可用。
+//!
+//! 同一个采样循环也负责系统时间检查（见 `clock`）：墙钟向前或向后跳变、或当前仍处于
+//! "系统时间异常"时重新判定一次。
+
+use crate::account::{AccountStore, QuotaSource};
+use crate::AppState;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime};
+use tauri::{Emitter, Manager};
+use tokio::sync::Notify;
+
+/// 漂移检测的采样间隔
+const CHECK_INTERVAL_SECS: u64 = 30;
+
+/// 墙钟比单调时钟多走超过该值才算唤醒（避开 NTP 微调等小跳变）
+const RESUME_DRIFT_THRESHOLD_SECS: u64 = 90;
+
+/// 判断一次采样间隔内是否发生了睡眠；返回估算的睡眠时长。
+///
+/// `wall_elapsed` 为 `None` 表示墙钟倒退（手动改时间 / NTP 回拨），不当作唤醒。
+pub fn detect_resume(
+    wall_elapsed: Option<Duration>,
+    mono_elapsed: Duration,
+    threshold: Duration,
+) -> Option<Duration> {
+    let drift = wall_elapsed?.checked_sub(mono_elapsed)?;
+    (drift > threshold).then_some(drift)
+}
+
+/// 一次采样的结论
+struct Tick {
+    /// 检测到唤醒时的睡眠时长
+    resumed: Option<Duration>,
+    /// 墙钟相对单调时钟明显跳变（向前或向后）
+    wall_jumped: bool,
+}
+
+/// 记录上一次采样点，逐次比较两种时钟
+struct ResumeDetector {
+    wall: SystemTime,
+    mono: Instant,
+}
+
+impl ResumeDetector {
+    fn new() -> Self {
+        Self {
+            wall: SystemTime::now(),
+            mono: Instant::now(),
+        }
+    }
+
+    /// 采样一次并更新基准点
+    fn check(&mut self) -> Tick {
+        let wall = SystemTime::now();
+        let mono = Instant::now();
+        let mono_elapsed = mono.duration_since(self.mono);
+        let wall_elapsed_secs = match wall.duration_since(self.wall) {
+            Ok(d) => d.as_secs() as i64,
+            Err(e) => -(e.duration().as_secs() as i64),
+        };
+        let resumed = detect_resume(
+            wall.duration_since(self.wall).ok(),
+            mono_elapsed,
+            Duration::from_secs(RESUME_DRIFT_THRESHOLD_SECS),
+        );
+        self.wall = wall;
+        self.mono = mono;
+        Tick {
+            resumed,
+            wall_jumped: crate::clock::wall_jump_secs(wall_elapsed_secs, mono_elapsed).is_some(),
+        }
+    }
+}
+
+fn resume_notify() -> &'static Notify {
+    static NOTIFY: OnceLock<Notify> = OnceLock::new();
+    NOTIFY.get_or_init(Notify::new)
+}
+
+/// 后台循环用的 sleep：到点或系统唤醒，先到者返回
+pub async fn sleep_or_resume(dur: Duration) {
+    tokio::select! {
+        _ = tokio::time::sleep(dur) => {}
+        _ = resume_notify().notified() => {}
+    }
+}
+
+/// 启动唤醒检测循环
+pub fn start(
+    store: Arc<Mutex<AccountStore>>,
+    app_handle: tauri::AppHandle,
+) -> tauri::async_runtime::JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        let mut detector = ResumeDetector::new();
+        loop {
+            tokio::time::sleep(Duration::from_secs(CHECK_INTERVAL_SECS)).await;
+            let tick = detector.check();
+            if tick.wall_jumped || crate::clock::is_skewed() {
+                check_clock(&store, &app_handle);
+            }
+            let Some(slept) = tick.resumed else {
+                continue;
+            };
+            println!(
+                "[Power] 检测到系统唤醒（约睡眠 {} 分钟），立即重新同步",
+                slept.as_secs() / 60
+            );
+            resume_notify().notify_waiters();
+            resync_after_resume(&store, &app_handle).await;
+        }
+    })
+}
+
+/// 重新判定系统时间；进入异常时提示，恢复后唤醒后台循环重新计算下一次调度
+pub fn check_clock(store: &Arc<Mutex<AccountStore>>, app_handle: &tauri::AppHandle) {
+    let newest = match store.lock() {
+        Ok(s) => crate::clock::newest_persisted(&s),
+        Err(_) => return,
+    };
+    let Some(health) = crate::clock::global().check(newest) else {
+        return;
+    };
+    match health.label() {
+        Some(label) => eprintln!("[Clock] ⚠️ {}，暂停按过期时间 / 陈旧程度触发的刷新", label),
+        None => {
+            println!("[Clock] 系统时间已恢复，重新计算调度");
+            resume_notify().notify_waiters();
+            let _ = app_handle.emit("accounts-updated", ());
+        }
+    }
+    let _ = app_handle.emit("clock-health-changed", &health);
+    crate::tray::update_tray_menu(app_handle);
+}
+
+async fn resync_after_resume(store: &Arc<Mutex<AccountStore>>, app_handle: &tauri::AppHandle) {
+    let (is_client, current_id) = {
+        let s = store.lock().unwrap();
+        let current_id = s
+            .current
+            .clone()
+            .filter(|id| s.accounts.get(id).is_some_and(|a| !a.is_relay()));
+        (s.settings.remote_mode == "client", current_id)
+    };
+
+    if is_client {
+        // client 模式以 Server 为准，走快速同步拉取最新 token
+        crate::do_one_fast_auth_sync(store).await;
+    } else {
+        crate::scheduler::sync_current_from_disk(store, app_handle);
+        if let Some(id) = current_id {
+            let state = app_handle.state::<AppState>();
+            let service = state.account_service(app_handle);
+            if let Err(e) = service.refresh_quota(&id, QuotaSource::Scheduler).await {
+                println!("[Power] 唤醒后刷新当前账号额度失败: {}", e);
+            }
+        }
+    }
+
+    let _ = app_handle.emit("accounts-updated", ());
+    crate::tray::update_tray_menu(app_handle);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const THRESHOLD: Duration = Duration::from_secs(RESUME_DRIFT_THRESHOLD_SECS);
+
+    #[test]
+    fn normal_tick_is_not_a_resume() {
+        let elapsed = Duration::from_secs(30);
+        assert_eq!(detect_resume(Some(elapsed), elapsed, THRESHOLD), None);
+        assert_eq!(
+            detect_resume(Some(Duration::from_secs(31)), elapsed, THRESHOLD),
+            None
+        );
+    }
+
+    #[test]
+    fn large_wall_jump_is_a_resume() {
+        let slept = detect_resume(
+            Some(Duration::from_secs(8 * 3600 + 30)),
+            Duration::from_secs(30),
+            THRESHOLD,
+        );
+        assert_eq!(slept, Some(Duration::from_secs(8 * 3600)));
+    }
+
+    #[test]
+    fn drift_must_exceed_threshold() {
+        let mono = Duration::from_secs(30);
+        assert_eq!(detect_resume(Some(mono + THRESHOLD), mono, THRESHOLD), None);
+        assert_eq!(
+            detect_resume(
+                Some(mono + THRESHOLD + Duration::from_secs(1)),
+                mono,
+                THRESHOLD
+            ),
+            Some(THRESHOLD + Duration::from_secs(1))
+        );
+    }
+
+    #[test]
+    fn backwards_clock_is_not_a_resume() {
+        let mono = Duration::from_secs(30);
+        assert_eq!(detect_resume(None, mono, THRESHOLD), None);
+        assert_eq!(
+            detect_resume(Some(Duration::from_secs(5)), mono, THRESHOLD),
+            None
+        );
+    }
+}
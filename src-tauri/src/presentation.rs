@@ -0,0 +1,207 @@
+//! 演示模式（录屏 / 共享屏幕时用）
+//!
+//! 开启后：
+//! - 除白名单外的所有命令一律拒绝，返回以 `PRESENTATION_MODE:` 开头的错误
+//! - `get_accounts` 等返回的账号名 / 邮箱打码成 `wo…@co….com`
+//! - 托盘 tooltip 打码，托盘快速切号走 `switch_account` 同样被拒
+//! - 后台同步照常进行，但不弹系统通知
+//!
+//! 状态只在内存里，重启即恢复正常模式。
+//!
+//! 命令分类是显式白名单：新加的命令默认按"会修改状态"处理，
+//! 确认只读后再加进 [`READ_ONLY_COMMANDS`]。
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// 演示模式下被拒命令的错误前缀，前端据此识别
+pub const ERROR_PREFIX: &str = "PRESENTATION_MODE";
+
+/// 演示模式下仍允许调用的命令（只读 / 纯 UI）
+pub const READ_ONLY_COMMANDS: &[&str] = &[
+    "set_presentation_mode",
+    "get_presentation_mode",
+    "get_api_version",
+    "get_schemas",
+    "get_accounts",
+    "get_accounts_page",
+    "list_accounts_filtered",
+    "get_account",
+    "search_accounts",
+    "find_duplicate_accounts",
+    "get_account_switch_history",
+    "get_trash",
+    "get_accounts_changed_since",
+    "get_current_account_id",
+    "resolve_account",
+    "get_pending_revert",
+    "generate_share_snapshot",
+    "get_settings",
+    "get_store_health",
+    "render_notes_template",
+    "get_proxy_status",
+    "get_sync_status",
+    "get_status_line",
+    "get_token_stats",
+    "get_token_history",
+    "get_quota_cycles",
+    "get_plan_capacity_estimates",
+    "get_account_token_history",
+    "get_session_bindings",
+    "list_session_routes",
+    "list_codex_sessions",
+    "detect_active_codex_session",
+    "get_activity_feed",
+    "get_recent_errors",
+    "get_badge_state",
+    // 错误中心的记账，不算用户操作，演示模式下也要放行
+    "record_command_error",
+    "get_switch_history",
+    "get_switch_stats",
+    "get_codex_fast_mode",
+    "get_codex_features_goals",
+    "get_installed_skills",
+    "get_skill_repos",
+    "get_skill_app_status",
+    "get_skill_content",
+    "check_sync_conflict",
+    "get_token_storage_status",
+    "list_auth_profiles",
+    "get_aging_tokens",
+    "get_auth_journal",
+    "get_codex_compatibility",
+    "get_account_statuses",
+    "verify_account_integrity",
+    "verify_all_accounts",
+    "detect_export_format",
+    "list_store_backups",
+    "show_main_window_cmd",
+    "get_pending_navigation",
+    "get_bulk_login_status",
+];
+
+/// 该命令在演示模式下是否需要拦截；不在白名单里的一律视为会修改状态
+pub fn is_mutating(command: &str) -> bool {
+    !READ_ONLY_COMMANDS.contains(&command)
+}
+
+/// 被拦截时返回给前端的错误
+pub fn rejection(command: &str) -> String {
+    format!("{}: 演示模式下不允许执行 {}", ERROR_PREFIX, command)
+}
+
+/// 演示模式开关（不持久化）
+#[derive(Debug, Default)]
+pub struct PresentationMode {
+    enabled: AtomicBool,
+}
+
+impl PresentationMode {
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+}
+
+/// 保留前两个字符（本身不超过两个字符时只留一个），其余换成省略号
+fn mask_part(part: &str) -> String {
+    let keep = if part.chars().count() <= 2 { 1 } else { 2 };
+    format!("{}…", part.chars().take(keep).collect::<String>())
+}
+
+/// `work@company.com` → `wo…@co….com`；不是邮箱的按普通名字处理
+pub fn mask_email(email: &str) -> String {
+    let Some((local, domain)) = email.split_once('@') else {
+        return mask_name(email);
+    };
+    let masked_domain = match domain.rsplit_once('.') {
+        Some((host, tld)) if !host.is_empty() && !tld.is_empty() => {
+            format!("{}.{}", mask_part(host), tld)
+        }
+        _ => mask_part(domain),
+    };
+    format!("{}@{}", mask_part(local), masked_domain)
+}
+
+/// 账号显示名：邮箱按邮箱规则打码，其它只保留前两个字符
+pub fn mask_name(name: &str) -> String {
+    if name.contains('@') {
+        return mask_email(name);
+    }
+    if name.is_empty() {
+        return String::new();
+    }
+    mask_part(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn masks_email_to_expected_form() {
+        assert_eq!(mask_email("work@company.com"), "wo…@co….com");
+        assert_eq!(mask_email("alice.b@mail.example.org"), "al…@ma….org");
+    }
+
+    #[test]
+    fn short_parts_keep_at_most_one_char() {
+        assert_eq!(mask_email("ab@cd.io"), "a…@c….io");
+        assert_eq!(mask_email("x@localhost"), "x…@lo…");
+        assert_eq!(mask_name("z"), "z…");
+    }
+
+    #[test]
+    fn masks_plain_names_and_unicode() {
+        assert_eq!(mask_name("MiMo 中转"), "Mi…");
+        assert_eq!(mask_name("张三的账号"), "张三…");
+        assert_eq!(mask_name("bob@team.dev"), "bo…@te….dev");
+        assert_eq!(mask_name(""), "");
+    }
+
+    #[test]
+    fn readers_are_whitelisted() {
+        for cmd in [
+            "get_accounts",
+            "get_current_account_id",
+            "get_sync_status",
+            "set_presentation_mode",
+            "get_presentation_mode",
+        ] {
+            assert!(!is_mutating(cmd), "{} 应为只读", cmd);
+        }
+    }
+
+    #[test]
+    fn unknown_and_writer_commands_default_to_mutating() {
+        for cmd in [
+            "switch_account",
+            "delete_account",
+            "update_settings",
+            "export_accounts",
+            "copy_to_clipboard",
+            "some_command_added_later",
+        ] {
+            assert!(is_mutating(cmd), "{} 应被拦截", cmd);
+        }
+    }
+
+    #[test]
+    fn rejection_carries_prefix() {
+        let err = rejection("switch_account");
+        assert!(err.starts_with(ERROR_PREFIX));
+        assert!(err.contains("switch_account"));
+    }
+
+    #[test]
+    fn toggle_is_in_memory() {
+        let mode = PresentationMode::default();
+        assert!(!mode.is_enabled());
+        mode.set(true);
+        assert!(mode.is_enabled());
+        mode.set(false);
+        assert!(!mode.is_enabled());
+    }
+}
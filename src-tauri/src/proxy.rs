@@ -23,12 +23,14 @@ use hyper::service::service_fn;
 use hyper::{Method, Request, Response, StatusCode};
 use hyper_util::rt::TokioIo;
 use reqwest::Client;
-use tauri::Emitter;
+use tauri::{Emitter, Manager};
 use tokio::net::TcpListener;
 use tokio_tungstenite::tungstenite;
 use tungstenite::client::IntoClientRequest;
 
-use crate::account::AccountStore;
+use crate::account::{AccountStore, QuotaSource};
+use crate::auth_journal::AuthWriteOp;
+use crate::log_identity;
 use crate::session_affinity::SessionAffinity;
 use crate::session_routes::SessionRoutesStore;
 use crate::switch_log::{SwitchLogger, SwitchReason};
@@ -122,8 +124,11 @@ async fn silent_refresh_current(state: &ProxyState) -> SilentRefreshOutcome {
                             store.sync_account_from_auth_json(&current_id, t.auth_json.clone());
                             let _ = store.save();
                         }
-                        if let Err(e) = AccountStore::write_codex_auth_extended_expiry(&t.auth_json)
-                        {
+                        if let Err(e) = AccountStore::write_codex_auth_extended_expiry(
+                            &t.auth_json,
+                            AuthWriteOp::RemoteSync,
+                            Some(&current_id),
+                        ) {
                             eprintln!("[Proxy] Server 拉到 token 后写 auth.json 失败: {}", e);
                         }
                         invalidate_remote_token_cache();
@@ -150,22 +155,25 @@ async fn silent_refresh_current(state: &ProxyState) -> SilentRefreshOutcome {
     }
 
     // 2) 本地 oauth refresh（off/server 模式 或 上面 Server 路径失败的降级）
-    let rt = {
+    let (rt, auth_profile) = {
         let store = match state.store.lock() {
             Ok(s) => s,
             Err(_) => return SilentRefreshOutcome::OtherError("store lock 失败".into()),
         };
-        match store
-            .accounts
-            .get(&current_id)
-            .and_then(|a| a.refresh_token.clone())
-        {
-            Some(rt) => rt,
+        if let Err(e) = crate::refresh_ban::guard(&store, &current_id) {
+            return SilentRefreshOutcome::OtherError(e);
+        }
+        match store.accounts.get(&current_id).and_then(|a| {
+            a.refresh_token
+                .clone()
+                .map(|rt| (rt, store.auth_profile_of(a)))
+        }) {
+            Some(found) => found,
             None => return SilentRefreshOutcome::NoRefreshToken,
         }
     };
 
-    match crate::oauth::refresh_access_token(&rt).await {
+    match crate::oauth::refresh_access_token(&auth_profile, &rt).await {
         Ok(new_tokens) => {
             // apply 到 store
             let updated_auth = if let Ok(mut store) = state.store.lock() {
@@ -187,7 +195,9 @@ async fn silent_refresh_current(state: &ProxyState) -> SilentRefreshOutcome {
                 None
             };
             if let Some(auth) = updated_auth {
-                if let Err(e) = AccountStore::write_codex_auth(&auth) {
+                if let Err(e) =
+                    AccountStore::write_codex_auth(&auth, AuthWriteOp::Refresh, Some(&current_id))
+                {
                     eprintln!("[Proxy] 本地刷新后写 auth.json 失败: {}", e);
                 } else {
                     println!("[Proxy] 本地 refresh 成功，已同步 auth.json");
@@ -195,6 +205,17 @@ async fn silent_refresh_current(state: &ProxyState) -> SilentRefreshOutcome {
             }
             SilentRefreshOutcome::Refreshed(new_tokens.access_token)
         }
+        Err(e) if crate::oauth::is_refresh_reused(&e) => {
+            if let Ok(mut store) = state.store.lock() {
+                if let Some(notice) =
+                    crate::refresh_ban::record_failure(&mut store, &current_id, &e)
+                {
+                    let _ = store.save();
+                    let _ = state.app_handle.emit(crate::refresh_ban::EVENT, &notice);
+                }
+            }
+            SilentRefreshOutcome::OtherError(e)
+        }
         Err(e) => {
             let lower = e.to_lowercase();
             if lower.contains("logged out")
@@ -261,6 +282,17 @@ struct ProxyState {
     session_routes: Arc<Mutex<SessionRoutesStore>>,
 }
 
+impl ProxyState {
+    /// 系统通知开关：设置里关掉或处于演示模式时都不弹
+    fn system_notify_enabled(&self, store: &AccountStore) -> bool {
+        store.settings.notify_on_switch
+            && !self
+                .app_handle
+                .try_state::<crate::AppState>()
+                .is_some_and(|s| s.presentation.is_enabled())
+    }
+}
+
 /// 启动代理服务器
 pub fn start(
     store: Arc<Mutex<AccountStore>>,
@@ -381,8 +413,11 @@ async fn get_current_token(state: &ProxyState) -> Result<(String, bool), String>
                         // 目的：让本机 Codex CLI 永远读到新鲜 access_token，避免它自己触发 oauth refresh
                         // 使 refresh_token 在两端分叉。
                         // 用 extended_expiry 版本：把 expires_at 顶到 +24h，codex 永远不会主动 refresh。
-                        if let Err(e) = AccountStore::write_codex_auth_extended_expiry(&t.auth_json)
-                        {
+                        if let Err(e) = AccountStore::write_codex_auth_extended_expiry(
+                            &t.auth_json,
+                            AuthWriteOp::RemoteSync,
+                            Some(&current_id),
+                        ) {
                             eprintln!("[Proxy] 写 ~/.codex/auth.json 失败: {}", e);
                         }
                         if let Some(tok) = AccountStore::extract_access_token(&t.auth_json) {
@@ -479,7 +514,11 @@ async fn resolve_token_with_affinity(
                 let _ = routes.save();
                 drop(routes);
                 let is_chatgpt = token.starts_with("eyJ");
-                println!("[Proxy] Hard route hit: {} → {}", sk, name);
+                println!(
+                    "[Proxy] Hard route hit: {} → {}",
+                    sk,
+                    log_identity::id(&name)
+                );
                 Some((token, account_id, name))
             } else {
                 None
@@ -511,7 +550,11 @@ async fn resolve_token_with_affinity(
                 .get(id)
                 .map(|a| {
                     // 基础健康：没被打三个 flag
-                    let basic_ok = !a.is_banned && !a.is_logged_out && !a.is_token_invalid;
+                    let basic_ok = !a.is_banned
+                        && !a.is_logged_out
+                        && !a.is_token_invalid
+                        && !a.archived
+                        && !a.lacks_codex_access();
                     if !basic_ok {
                         return false;
                     }
@@ -727,7 +770,9 @@ fn resolve_hard_route(
         .unwrap_or_else(|| account_id.clone());
     println!(
         "[Proxy] Hard route hit: session={} → {} ({})",
-        raw_session, account_id, acc_name
+        raw_session,
+        account_id,
+        log_identity::id(&acc_name)
     );
     Some((sk, account_id))
 }
@@ -804,16 +849,14 @@ fn pick_next_account(state: &ProxyState) -> PickResult {
     if candidates.is_empty() {
         let now = Utc::now().timestamp();
         let mut earliest: Option<i64> = None;
+        // 每个号要等耗尽的窗口全部重置才能用：周额度耗尽的号不能按 5H 重置时间算
         for account in store.accounts.values() {
-            if let Some(q) = &account.cached_quota {
-                for r in [q.five_hour_reset_at, q.weekly_reset_at]
-                    .into_iter()
-                    .flatten()
-                {
-                    if now < r {
-                        earliest = Some(earliest.map_or(r, |e: i64| e.min(r)));
-                    }
-                }
+            let usable_at = account
+                .cached_quota
+                .as_ref()
+                .and_then(|q| crate::quota_window::classify(q, now).usable_at());
+            if let Some(r) = usable_at {
+                earliest = Some(earliest.map_or(r, |e: i64| e.min(r)));
             }
         }
         return PickResult::Exhausted {
@@ -846,13 +889,14 @@ fn should_preemptive_switch(state: &ProxyState) -> bool {
         Err(_) => return false,
     };
 
-    let (t5h, tw, fg) = (
-        store.settings.proxy_threshold_5h as f64,
-        store.settings.proxy_threshold_weekly as f64,
+    let (t5h, tw, unit, fg) = (
+        store.settings.proxy_threshold_5h,
+        store.settings.proxy_threshold_weekly,
+        store.settings.proxy_threshold_unit,
         store.settings.proxy_free_guard as f64,
     );
 
-    if t5h == 0.0 && tw == 0.0 && fg == 0.0 {
+    if t5h == 0 && tw == 0 && fg == 0.0 {
         return false;
     }
 
@@ -871,8 +915,12 @@ fn should_preemptive_switch(state: &ProxyState) -> bool {
         return false;
     }
 
-    if account.is_banned || account.is_token_invalid || account.is_logged_out {
-        println!("[Proxy] 发现当前账号被封禁/失效/登出，触发预防性切号");
+    if account.is_banned
+        || account.is_token_invalid
+        || account.is_logged_out
+        || account.lacks_codex_access()
+    {
+        println!("[Proxy] 发现当前账号被封禁/失效/登出/无 Codex 权限，触发预防性切号");
         return true;
     }
 
@@ -901,21 +949,26 @@ fn should_preemptive_switch(state: &ProxyState) -> bool {
         );
         return true;
     }
-    if t5h > 0.0 && quota.five_hour_left < t5h {
+    if crate::account::below_threshold(quota.five_hour_left, &quota.five_hour_counts, t5h, unit) {
         if has_active_session {
             return false;
         }
         println!(
-            "[Proxy] 5h 阈值触发: {:.0}% < {:.0}%",
-            quota.five_hour_left, t5h
+            "[Proxy] 5h 阈值触发: {} < {}",
+            crate::usage::format_left(quota.five_hour_left, &quota.five_hour_counts),
+            unit.format(t5h)
         );
         return true;
     }
-    if tw > 0.0 && quota.weekly_left < tw {
+    if crate::account::below_threshold(quota.weekly_left, &quota.weekly_counts, tw, unit) {
         if has_active_session {
             return false;
         }
-        println!("[Proxy] 周阈值触发: {:.0}% < {:.0}%", quota.weekly_left, tw);
+        println!(
+            "[Proxy] 周阈值触发: {} < {}",
+            crate::usage::format_left(quota.weekly_left, &quota.weekly_counts),
+            unit.format(tw)
+        );
         return true;
     }
     false
@@ -930,20 +983,11 @@ fn mark_current_banned(state: &ProxyState) {
                 account.is_banned = true;
                 let name = account.name.clone();
                 let _ = store.save();
-                println!("[Proxy] 账号 {} 已标记为封号", name);
+                println!("[Proxy] 账号 {} 已标记为封号", log_identity::id(&name));
                 let _ = state.app_handle.emit("proxy-account-banned", &name);
                 // macOS 系统通知（可配置）
-                if store.settings.notify_on_switch {
-                    let notify_name = name.clone();
-                    std::thread::spawn(move || {
-                        let _ = std::process::Command::new("osascript")
-                            .arg("-e")
-                            .arg(format!(
-                                "display notification \"{}\" with title \"Codex Switcher\" subtitle \"检测到封号\"",
-                                notify_name
-                            ))
-                            .output();
-                    });
+                if state.system_notify_enabled(&store) {
+                    crate::notify::send("检测到封号", &name);
                 }
             }
         }
@@ -978,13 +1022,20 @@ fn mark_current_quota_depleted(state: &ProxyState) {
     }
 }
 
+/// (store id, access_token, chatgpt_account_id, refresh_token, email, 请求头覆盖)
+type QuotaFetchInfo = (
+    String,
+    String,
+    Option<String>,
+    Option<String>,
+    String,
+    crate::request_headers::RequestOverrides,
+);
+
 /// 后台拉一次 /wham/usage 把 used_percent 写进 quota-snapshots.jsonl。
 /// info 为 None（Relay / 没拿到 access_token / 等）则跳过。
-fn spawn_quota_snapshot(
-    info: Option<(String, String, Option<String>, Option<String>, String)>,
-    trigger: &'static str,
-) {
-    let (store_id, access_token, chatgpt_account_id, refresh_token, email) = match info {
+fn spawn_quota_snapshot(info: Option<QuotaFetchInfo>, trigger: &'static str) {
+    let (store_id, access_token, chatgpt_account_id, refresh_token, email, overrides) = match info {
         Some(x) => x,
         None => return,
     };
@@ -993,7 +1044,8 @@ fn spawn_quota_snapshot(
             access_token,
             chatgpt_account_id,
             refresh_token,
-            false,
+            None,
+            overrides,
         )
         .await
         {
@@ -1049,7 +1101,8 @@ fn do_switch(state: &ProxyState, new_id: &str, reason: SwitchReason) -> Result<(
         let rt = acc.refresh_token.clone();
         let email = crate::account::AccountStore::extract_email(&acc.auth_json)
             .unwrap_or_else(|| acc.name.clone());
-        Some((id.clone(), at, aid, rt, email))
+        let overrides = crate::request_headers::RequestOverrides::for_account(&store.settings, acc);
+        Some((id.clone(), at, aid, rt, email, overrides))
     });
     let to_fetch_info = {
         let acc = store.accounts.get(new_id);
@@ -1060,7 +1113,9 @@ fn do_switch(state: &ProxyState, new_id: &str, reason: SwitchReason) -> Result<(
                 let rt = a.refresh_token.clone();
                 let email = crate::account::AccountStore::extract_email(&a.auth_json)
                     .unwrap_or_else(|| a.name.clone());
-                at.map(|at| (new_id.to_string(), at, aid, rt, email))
+                let overrides =
+                    crate::request_headers::RequestOverrides::for_account(&store.settings, a);
+                at.map(|at| (new_id.to_string(), at, aid, rt, email, overrides))
             }
             _ => None,
         }
@@ -1087,7 +1142,11 @@ fn do_switch(state: &ProxyState, new_id: &str, reason: SwitchReason) -> Result<(
         .and_then(|a| a.cached_quota.as_ref())
         .map(|q| q.five_hour_left);
 
-    println!("[Proxy] 自动切号 → {} ({})", to_name, reason);
+    println!(
+        "[Proxy] 自动切号 → {} ({})",
+        log_identity::id(&to_name),
+        reason
+    );
 
     // 记录切号日志
     state.switch_logger.log_switch(
@@ -1109,7 +1168,7 @@ fn do_switch(state: &ProxyState, new_id: &str, reason: SwitchReason) -> Result<(
     let _ = state.app_handle.emit("accounts-updated", ());
 
     // 读取通知设置
-    let notify_enabled = store.settings.notify_on_switch;
+    let notify_enabled = state.system_notify_enabled(&store);
     let inject_enabled = store.settings.inject_switch_message;
     // solo 模式下把 current 同步给 Server（非阻塞）
     let solo_push = if store.settings.remote_mode == "solo"
@@ -1151,16 +1210,7 @@ fn do_switch(state: &ProxyState, new_id: &str, reason: SwitchReason) -> Result<(
     // macOS 系统通知（可配置）
     if notify_enabled {
         let from = from_name.unwrap_or_else(|| "无".to_string());
-        let notify_msg = format!("{} → {}", from, to_name);
-        std::thread::spawn(move || {
-            let _ = std::process::Command::new("osascript")
-                .arg("-e")
-                .arg(format!(
-                    "display notification \"{}\" with title \"Codex Switcher\" subtitle \"自动切号\"",
-                    notify_msg
-                ))
-                .output();
-        });
+        crate::notify::send("自动切号", &format!("{} → {}", from, to_name));
     }
 
     // 注入 WebSocket 消息标记（可配置，实验性）
@@ -2135,8 +2185,12 @@ async fn adopt_remote_current(
             // Relay 走 ApiKey schema、订阅号走 OAuth schema —— 见 to_codex_auth_value 注释。
             let auth = acc.to_codex_auth_value();
             // adopt_remote_current 是 client 模式的换号路径，扩展 expires_at 防 codex 自刷
-            crate::account::AccountStore::write_codex_auth_extended_expiry(&auth)
-                .map_err(|e| format!("写 auth.json 失败: {}", e))?;
+            crate::account::AccountStore::write_codex_auth_extended_expiry(
+                &auth,
+                AuthWriteOp::Switch,
+                Some(new_id),
+            )
+            .map_err(|e| format!("写 auth.json 失败: {}", e))?;
         }
         store.current = Some(new_id.to_string());
         let _ = store.save();
@@ -2297,7 +2351,13 @@ fn restore_current_if_flagged(state: &ProxyState, entry_id: Option<&str>) {
     let entry_ok = entry_id
         .filter(|eid| *eid != current_id.as_str())
         .and_then(|eid| store.accounts.get(eid))
-        .map(|a| !a.is_banned && !a.is_token_invalid && !a.is_logged_out)
+        .map(|a| {
+            !a.is_banned
+                && !a.is_token_invalid
+                && !a.is_logged_out
+                && !a.archived
+                && !a.lacks_codex_access()
+        })
         .unwrap_or(false);
     // 兜底候选：遵守 `relay_auto_switch_in` 约束 —— 默认 false 时不能挑 Relay
     // 类账号（否则用户手切到订阅号、自动切链失败后会被收尾切到 GLM/MiMo 这种
@@ -2314,6 +2374,8 @@ fn restore_current_if_flagged(state: &ProxyState, entry_id: Option<&str>) {
                     && !a.is_banned
                     && !a.is_token_invalid
                     && !a.is_logged_out
+                    && !a.archived
+                    && !a.lacks_codex_access()
                     && (allow_relay || !a.is_relay())
             })
             .map(|(id, _)| id.clone())
@@ -3543,8 +3605,7 @@ fn dump_moderation_sample(buf: Arc<Mutex<Vec<u8>>>, hit_kw: &'static str) {
             Ok(b) => b.clone(),
             Err(_) => return,
         };
-        let Some(home) = dirs::home_dir() else { return };
-        let dir = home.join(".codex-switcher").join("moderation-samples");
+        let dir = crate::paths::data_dir().join("moderation-samples");
         if let Err(e) = std::fs::create_dir_all(&dir) {
             eprintln!("[Proxy] moderation 样本目录创建失败: {}", e);
             return;
@@ -3764,7 +3825,11 @@ async fn handle_websocket(
                         if a.is_relay() && !store.settings.relay_auto_switch_out {
                             return Some(false);
                         }
-                        if a.is_banned || a.is_token_invalid || a.is_logged_out {
+                        if a.is_banned
+                            || a.is_token_invalid
+                            || a.is_logged_out
+                            || a.lacks_codex_access()
+                        {
                             return Some(true);
                         }
                         a.cached_quota.as_ref().map(|q| {
@@ -3793,7 +3858,7 @@ async fn handle_websocket(
                 {
                     // 查 API 确认候选号是否真的有额度
                     let has_quota = {
-                        let (at, aid, rt) = {
+                        let (at, aid, rt, overrides) = {
                             let store = state.store.lock().map_err(|e| e.to_string()).ok();
                             if let Some(s) = store {
                                 let acc = s.accounts.get(&id);
@@ -3802,11 +3867,15 @@ async fn handle_websocket(
                                         AccountStore::extract_access_token(&a.auth_json),
                                         AccountStore::extract_account_id(&a.auth_json),
                                         a.refresh_token.clone(),
+                                        crate::request_headers::RequestOverrides::for_account(
+                                            &s.settings,
+                                            a,
+                                        ),
                                     )
                                 })
-                                .unwrap_or((None, None, None))
+                                .unwrap_or_default()
                             } else {
-                                (None, None, None)
+                                Default::default()
                             }
                         };
                         if let Some(access_token) = at {
@@ -3814,7 +3883,8 @@ async fn handle_websocket(
                                 access_token,
                                 aid,
                                 rt,
-                                false,
+                                None,
+                                overrides,
                             )
                             .await
                             {
@@ -3841,20 +3911,10 @@ async fn handle_websocket(
                                     // 更新缓存
                                     if let Ok(mut store) = state.store.lock() {
                                         if let Some(acc) = store.accounts.get_mut(&id) {
-                                            acc.cached_quota = Some(crate::account::CachedQuota {
-                                                five_hour_left: usage.five_hour_left as f64,
-                                                five_hour_reset: usage.five_hour_reset.clone(),
-                                                five_hour_reset_at: usage.five_hour_reset_at,
-                                                five_hour_label: usage.five_hour_label.clone(),
-                                                weekly_left: usage.weekly_left as f64,
-                                                weekly_reset: usage.weekly_reset.clone(),
-                                                weekly_reset_at: usage.weekly_reset_at,
-                                                weekly_label: usage.weekly_label.clone(),
-                                                plan_type: usage.plan_type.clone(),
-                                                is_valid_for_cli: usage.is_valid_for_cli,
-                                                updated_at: chrono::Utc::now(),
-                                            });
-                                            let _ = store.save();
+                                            acc.set_cached_quota(
+                                                (&usage, QuotaSource::SwitchPrecheck).into(),
+                                            );
+                                            store.mark_dirty();
                                         }
                                     }
                                     usage.five_hour_left > 0 && usage.weekly_left > 0
@@ -29,10 +29,7 @@ pub struct QuotaSnapshot {
 }
 
 pub fn path() -> PathBuf {
-    dirs::home_dir()
-        .expect("home dir")
-        .join(".codex-switcher")
-        .join("quota-snapshots.jsonl")
+    crate::paths::data_dir().join("quota-snapshots.jsonl")
 }
 
 pub fn append(snap: &QuotaSnapshot) {
@@ -81,3 +78,217 @@ pub fn append_from_usage(
     };
     append(&snap);
 }
+
+/// 速率估算只看最近这么长的样本
+const VELOCITY_WINDOW_SECS: i64 = 60 * 60;
+/// 样本跨度不足时不估算（两个相邻整数百分比之间的噪声会被放大）
+const VELOCITY_MIN_SPAN_SECS: i64 = 10 * 60;
+const VELOCITY_MIN_SAMPLES: usize = 3;
+/// 低于该速率（%/分钟）视为空闲，不给预测
+const VELOCITY_MIN_BURN_PER_MIN: f64 = 0.05;
+/// reset_at 前后浮动在此范围内视为同一窗口（上游按"当前时间 + 剩余秒数"算，会抖动）
+const RESET_AT_TOLERANCE_SECS: i64 = 120;
+/// used_pct 回落超过该值视为窗口已重置（小幅回落当作量化噪声）
+const RESET_DROP_PCT: f64 = 10.0;
+
+/// 速率估算用的单个样本（5h 窗口）
+#[derive(Debug, Clone, PartialEq)]
+pub struct UsageSample {
+    pub ts: i64,
+    pub used_pct: f64,
+    pub reset_at: Option<i64>,
+}
+
+/// "按当前速率多久耗尽" 的估算结果
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExhaustionEstimate {
+    pub minutes_remaining: u32,
+    /// 0-1：回归拟合度 × 样本数量系数
+    pub confidence: f64,
+}
+
+impl UsageSample {
+    fn is_reset_from(&self, prev: &UsageSample) -> bool {
+        let reset_moved = match (prev.reset_at, self.reset_at) {
+            (Some(a), Some(b)) => (b - a).abs() > RESET_AT_TOLERANCE_SECS,
+            _ => false,
+        };
+        reset_moved || prev.used_pct - self.used_pct > RESET_DROP_PCT
+    }
+}
+
+/// 取某账号的 5h 窗口样本（按时间升序）
+pub fn five_hour_samples(snaps: &[QuotaSnapshot], account_id: &str) -> Vec<UsageSample> {
+    let mut samples: Vec<UsageSample> = snaps
+        .iter()
+        .filter(|s| s.account_id == account_id)
+        .map(|s| UsageSample {
+            ts: s.ts.timestamp(),
+            used_pct: f64::from(s.five_hour_used_pct),
+            reset_at: s.five_hour_reset_at,
+        })
+        .collect();
+    samples.sort_by_key(|s| s.ts);
+    samples
+}
+
+/// 按最近 60 分钟的 5h 用量样本做线性回归，估算多久打满。
+///
+/// 返回 `None`：样本太少 / 跨度太短、速率持平或下降、或按速率会先等到窗口重置。
+/// 窗口中途发生重置（reset_at 跳变或 used_pct 大幅回落）时只用重置之后的样本。
+pub fn estimate_exhaustion(samples: &[UsageSample], now: i64) -> Option<ExhaustionEstimate> {
+    let mut recent: Vec<&UsageSample> = samples
+        .iter()
+        .filter(|s| s.ts <= now && now - s.ts <= VELOCITY_WINDOW_SECS)
+        .collect();
+    recent.sort_by_key(|s| s.ts);
+    let start = recent
+        .windows(2)
+        .rposition(|w| w[1].is_reset_from(w[0]))
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    let window = &recent[start..];
+
+    let (first, last) = (window.first()?, window.last()?);
+    if window.len() < VELOCITY_MIN_SAMPLES || last.ts - first.ts < VELOCITY_MIN_SPAN_SECS {
+        return None;
+    }
+
+    // 最小二乘：x = 距首个样本的分钟数，y = used_pct
+    let n = window.len() as f64;
+    let xs: Vec<f64> = window
+        .iter()
+        .map(|s| (s.ts - first.ts) as f64 / 60.0)
+        .collect();
+    let mean_x = xs.iter().sum::<f64>() / n;
+    let mean_y = window.iter().map(|s| s.used_pct).sum::<f64>() / n;
+    let (mut sxx, mut sxy, mut syy) = (0.0, 0.0, 0.0);
+    for (x, s) in xs.iter().zip(window) {
+        let (dx, dy) = (x - mean_x, s.used_pct - mean_y);
+        sxx += dx * dx;
+        sxy += dx * dy;
+        syy += dy * dy;
+    }
+    let slope = sxy / sxx;
+    if !slope.is_finite() || slope < VELOCITY_MIN_BURN_PER_MIN {
+        return None;
+    }
+
+    let remaining_pct = (100.0 - last.used_pct).max(0.0);
+    let minutes_from_last = remaining_pct / slope;
+    let exhaust_at = last.ts + (minutes_from_last * 60.0) as i64;
+    if last.reset_at.is_some_and(|reset_at| exhaust_at >= reset_at) {
+        return None;
+    }
+
+    let r_squared = if syy > 0.0 {
+        (sxy * sxy / (sxx * syy)).clamp(0.0, 1.0)
+    } else {
+        1.0
+    };
+    let sample_factor = (n / 6.0).min(1.0);
+    Some(ExhaustionEstimate {
+        minutes_remaining: ((exhaust_at - now).max(0) as f64 / 60.0).ceil() as u32,
+        confidence: r_squared * sample_factor,
+    })
+}
+
+/// 读快照文件，估算某账号的 5h 耗尽时间
+pub fn estimate_for_account(account_id: &str, now: i64) -> Option<ExhaustionEstimate> {
+    estimate_exhaustion(&five_hour_samples(&read_all(), account_id), now)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_700_000_000;
+    const RESET_AT: i64 = NOW + 3 * 3600;
+
+    fn sample(mins_ago: i64, used_pct: f64, reset_at: i64) -> UsageSample {
+        UsageSample {
+            ts: NOW - mins_ago * 60,
+            used_pct,
+            reset_at: Some(reset_at),
+        }
+    }
+
+    #[test]
+    fn steady_burn_predicts_exhaustion() {
+        // 1%/分钟，当前 60% → 40 分钟后打满
+        let samples: Vec<_> = (0..=6)
+            .map(|i| sample(30 - i * 5, 30.0 + (i * 5) as f64, RESET_AT))
+            .collect();
+        let est = estimate_exhaustion(&samples, NOW).unwrap();
+        assert_eq!(est.minutes_remaining, 40);
+        assert!(est.confidence > 0.99);
+    }
+
+    #[test]
+    fn idle_account_has_no_estimate() {
+        let samples: Vec<_> = (0..=6)
+            .map(|i| sample(30 - i * 5, 42.0, RESET_AT))
+            .collect();
+        assert_eq!(estimate_exhaustion(&samples, NOW), None);
+    }
+
+    #[test]
+    fn too_few_or_stale_samples_have_no_estimate() {
+        let two = vec![sample(20, 10.0, RESET_AT), sample(0, 30.0, RESET_AT)];
+        assert_eq!(estimate_exhaustion(&two, NOW), None);
+        let stale: Vec<_> = (0..=6)
+            .map(|i| sample(200 - i * 5, 30.0 + (i * 5) as f64, RESET_AT))
+            .collect();
+        assert_eq!(estimate_exhaustion(&stale, NOW), None);
+    }
+
+    #[test]
+    fn reset_mid_window_restarts_from_new_window() {
+        // 重置前高速消耗，重置后（reset_at 跳变、用量回落）只剩缓慢消耗
+        let old_reset = NOW - 30 * 60;
+        let mut samples = vec![
+            sample(55, 80.0, old_reset),
+            sample(45, 90.0, old_reset),
+            sample(35, 98.0, old_reset),
+        ];
+        samples.extend((0..=5).map(|i| sample(25 - i * 5, 2.0 + (i * 2) as f64, RESET_AT)));
+        // 0.4%/分钟，当前 12% → 220 分钟，晚于 3h 后的重置
+        assert_eq!(estimate_exhaustion(&samples, NOW), None);
+
+        let far_reset = NOW + 5 * 3600;
+        for s in samples.iter_mut().skip(3) {
+            s.reset_at = Some(far_reset);
+        }
+        let est = estimate_exhaustion(&samples, NOW).unwrap();
+        assert_eq!(est.minutes_remaining, 220);
+    }
+
+    #[test]
+    fn noisy_burn_still_estimates_with_lower_confidence() {
+        // 约 1%/分钟，叠加 ±3 的量化噪声
+        let noise = [0.0, 3.0, -3.0, 2.0, -2.0, 3.0, 0.0];
+        let samples: Vec<_> = (0..=6)
+            .map(|i| {
+                sample(
+                    30 - i * 5,
+                    30.0 + (i * 5) as f64 + noise[i as usize],
+                    RESET_AT,
+                )
+            })
+            .collect();
+        let est = estimate_exhaustion(&samples, NOW).unwrap();
+        assert!((30..=50).contains(&est.minutes_remaining), "{:?}", est);
+        assert!(est.confidence < 0.99 && est.confidence > 0.5, "{:?}", est);
+    }
+
+    #[test]
+    fn jittering_reset_at_is_same_window() {
+        let samples: Vec<_> = (0..=6)
+            .map(|i| sample(30 - i * 5, 30.0 + (i * 5) as f64, RESET_AT + i % 2 * 30))
+            .collect();
+        assert_eq!(
+            estimate_exhaustion(&samples, NOW).map(|e| e.minutes_remaining),
+            Some(40)
+        );
+    }
+}
@@ -0,0 +1,255 @@
+//! 5H 窗口和周窗口分开判断耗尽
+//!
+//! 5H 额度用完几个小时就回来，周额度用完要等好几天。选号、状态展示如果只看"有没有额度"，
+//! 就会把快恢复的号和要躺几天的号混为一谈。这里把缓存的额度按窗口分类（[`classify`]，纯函数）：
+//! - 两个窗口各自是 [`WindowState::Available`] 或 [`WindowState::Exhausted`]，可以同时耗尽；
+//!   缓存里是 0 但重置时间已过的窗口按可用处理（缓存还没来得及刷新）
+//! - 选号（[`eligibility`]）：周额度耗尽直接排除；5H 耗尽但重置时间在前瞻窗口内
+//!   （设置 `five_hour_reset_lookahead_minutes`，默认 15 分钟）的仍可入选，排在有额度的号后面
+//! - free 套餐只看 5H（和以前一致）
+
+use crate::account::CachedQuota;
+
+/// 单个窗口的状态
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WindowState {
+    Available,
+    /// `reset_at` 为 Unix 秒，接口没给时为 None
+    Exhausted {
+        reset_at: Option<i64>,
+    },
+}
+
+/// 哪个窗口耗尽了
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Exhaustion {
+    FiveHourExhausted { reset_at: Option<i64> },
+    WeeklyExhausted { reset_at: Option<i64> },
+}
+
+impl Exhaustion {
+    pub fn reset_at(&self) -> Option<i64> {
+        match self {
+            Exhaustion::FiveHourExhausted { reset_at }
+            | Exhaustion::WeeklyExhausted { reset_at } => *reset_at,
+        }
+    }
+
+    /// 托盘 / 日志里的短标签
+    pub fn label(&self) -> &'static str {
+        match self {
+            Exhaustion::FiveHourExhausted { .. } => "5H 已用完",
+            Exhaustion::WeeklyExhausted { .. } => "周额度已用完",
+        }
+    }
+}
+
+/// 两个窗口的分类结果
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct QuotaWindows {
+    pub five_hour: WindowState,
+    pub weekly: WindowState,
+}
+
+fn classify_window(left: f64, reset_at: Option<i64>, now: i64) -> WindowState {
+    if left > 0.0 || reset_at.is_some_and(|r| now >= r) {
+        WindowState::Available
+    } else {
+        WindowState::Exhausted { reset_at }
+    }
+}
+
+pub fn classify(quota: &CachedQuota, now: i64) -> QuotaWindows {
+    QuotaWindows {
+        five_hour: classify_window(quota.five_hour_left, quota.five_hour_reset_at, now),
+        weekly: classify_window(quota.weekly_left, quota.weekly_reset_at, now),
+    }
+}
+
+impl QuotaWindows {
+    /// 所有耗尽的窗口，5H 在前
+    pub fn exhaustions(&self) -> Vec<Exhaustion> {
+        let mut out = Vec::new();
+        if let WindowState::Exhausted { reset_at } = self.five_hour {
+            out.push(Exhaustion::FiveHourExhausted { reset_at });
+        }
+        if let WindowState::Exhausted { reset_at } = self.weekly {
+            out.push(Exhaustion::WeeklyExhausted { reset_at });
+        }
+        out
+    }
+
+    /// 卡住账号的窗口：两个都耗尽时是周（恢复得晚）
+    pub fn blocker(&self) -> Option<Exhaustion> {
+        self.exhaustions().pop()
+    }
+
+    /// 最早什么时候两个窗口都恢复；没耗尽为 None，耗尽但不知道何时重置也为 None
+    pub fn usable_at(&self) -> Option<i64> {
+        let exhausted = self.exhaustions();
+        if exhausted.is_empty() {
+            return None;
+        }
+        exhausted
+            .iter()
+            .map(Exhaustion::reset_at)
+            .collect::<Option<Vec<_>>>()?
+            .into_iter()
+            .max()
+    }
+}
+
+/// 自动选号的资格
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Eligibility {
+    Eligible,
+    /// 5H 耗尽但马上重置，可以作为后备
+    AfterFiveHourReset {
+        reset_at: i64,
+    },
+    Excluded(Exhaustion),
+}
+
+/// `lookahead_secs`：5H 重置时间离现在不超过这么久时仍可入选
+pub fn eligibility(
+    windows: &QuotaWindows,
+    free_plan: bool,
+    now: i64,
+    lookahead_secs: i64,
+) -> Eligibility {
+    if !free_plan {
+        if let WindowState::Exhausted { reset_at } = windows.weekly {
+            return Eligibility::Excluded(Exhaustion::WeeklyExhausted { reset_at });
+        }
+    }
+    match windows.five_hour {
+        WindowState::Available => Eligibility::Eligible,
+        WindowState::Exhausted {
+            reset_at: Some(reset_at),
+        } if reset_at - now <= lookahead_secs => Eligibility::AfterFiveHourReset { reset_at },
+        WindowState::Exhausted { reset_at } => {
+            Eligibility::Excluded(Exhaustion::FiveHourExhausted { reset_at })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_800_000_000;
+    const LOOKAHEAD: i64 = 15 * 60;
+
+    fn quota(
+        five: f64,
+        five_reset: Option<i64>,
+        weekly: f64,
+        weekly_reset: Option<i64>,
+    ) -> CachedQuota {
+        serde_json::from_value(serde_json::json!({
+            "five_hour_left": five,
+            "five_hour_reset": "",
+            "five_hour_reset_at": five_reset,
+            "weekly_left": weekly,
+            "weekly_reset": "",
+            "weekly_reset_at": weekly_reset,
+            "plan_type": "plus",
+            "updated_at": "2027-01-15T08:00:00Z",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn windows_are_classified_independently() {
+        let both_ok = classify(&quota(40.0, Some(NOW + 60), 70.0, Some(NOW + 86_400)), NOW);
+        assert_eq!(both_ok.five_hour, WindowState::Available);
+        assert_eq!(both_ok.weekly, WindowState::Available);
+        assert!(both_ok.exhaustions().is_empty());
+        assert_eq!(both_ok.blocker(), None);
+
+        let five = classify(&quota(0.0, Some(NOW + 600), 70.0, None), NOW);
+        assert_eq!(
+            five.exhaustions(),
+            vec![Exhaustion::FiveHourExhausted {
+                reset_at: Some(NOW + 600)
+            }]
+        );
+
+        let weekly = classify(&quota(40.0, None, 0.0, Some(NOW + 3 * 86_400)), NOW);
+        assert_eq!(
+            weekly.blocker(),
+            Some(Exhaustion::WeeklyExhausted {
+                reset_at: Some(NOW + 3 * 86_400)
+            })
+        );
+
+        let both = classify(&quota(0.0, Some(NOW + 600), 0.0, Some(NOW + 86_400)), NOW);
+        assert_eq!(both.exhaustions().len(), 2);
+        assert_eq!(both.blocker().unwrap().label(), "周额度已用完");
+        assert_eq!(both.usable_at(), Some(NOW + 86_400));
+    }
+
+    #[test]
+    fn elapsed_reset_counts_as_available_and_unknown_reset_stays_exhausted() {
+        let elapsed = classify(&quota(0.0, Some(NOW - 1), 0.0, Some(NOW)), NOW);
+        assert_eq!(elapsed.five_hour, WindowState::Available);
+        assert_eq!(elapsed.weekly, WindowState::Available);
+
+        let unknown = classify(&quota(0.0, None, 50.0, None), NOW);
+        assert_eq!(unknown.five_hour, WindowState::Exhausted { reset_at: None });
+        assert_eq!(unknown.usable_at(), None);
+    }
+
+    #[test]
+    fn imminent_five_hour_reset_is_a_fallback_but_weekly_exhaustion_is_excluded() {
+        let elig = |q: CachedQuota| eligibility(&classify(&q, NOW), false, NOW, LOOKAHEAD);
+
+        assert_eq!(elig(quota(30.0, None, 30.0, None)), Eligibility::Eligible);
+        // 5H 10 分钟后重置：可以作为后备
+        assert_eq!(
+            elig(quota(0.0, Some(NOW + 600), 30.0, None)),
+            Eligibility::AfterFiveHourReset {
+                reset_at: NOW + 600
+            }
+        );
+        // 正好卡在前瞻边界也算
+        assert!(matches!(
+            elig(quota(0.0, Some(NOW + LOOKAHEAD), 30.0, None)),
+            Eligibility::AfterFiveHourReset { .. }
+        ));
+        // 还要 2 小时 / 不知道什么时候重置：排除
+        assert!(matches!(
+            elig(quota(0.0, Some(NOW + 7_200), 30.0, None)),
+            Eligibility::Excluded(Exhaustion::FiveHourExhausted { .. })
+        ));
+        assert!(matches!(
+            elig(quota(0.0, None, 30.0, None)),
+            Eligibility::Excluded(Exhaustion::FiveHourExhausted { .. })
+        ));
+
+        // 周额度耗尽：哪怕周重置也只差 1 分钟、5H 满格，照样排除
+        assert_eq!(
+            elig(quota(100.0, None, 0.0, Some(NOW + 60))),
+            Eligibility::Excluded(Exhaustion::WeeklyExhausted {
+                reset_at: Some(NOW + 60)
+            })
+        );
+        assert!(matches!(
+            elig(quota(0.0, Some(NOW + 600), 0.0, Some(NOW + 86_400))),
+            Eligibility::Excluded(Exhaustion::WeeklyExhausted { .. })
+        ));
+    }
+
+    #[test]
+    fn free_plans_only_look_at_the_five_hour_window() {
+        let windows = classify(&quota(20.0, None, 0.0, Some(NOW + 86_400)), NOW);
+        assert_eq!(
+            eligibility(&windows, true, NOW, LOOKAHEAD),
+            Eligibility::Eligible
+        );
+        assert!(matches!(
+            eligibility(&windows, false, NOW, LOOKAHEAD),
+            Eligibility::Excluded(_)
+        ));
+    }
+}
@@ -0,0 +1,98 @@
+//! refresh_token 重复使用后的刷新禁令
+//!
+//! 并发刷新偶尔会让 IdP 判定 refresh_token 被重复使用，整条授权随之作废；这时继续重试
+//! 只会越刷越糟。第一次识别到这类拒绝（`oauth::is_refresh_reused`）就给账号挂上禁令：
+//! 标记 TokenInvalid、发通知让用户重新登录。之后每个刷新入口先过 [`guard`]，直接短路、
+//! 不再发请求。禁令只在该账号重新登录成功后解除（`AccountStore::complete_relogin`）。
+
+use serde::Serialize;
+
+use crate::account::AccountStore;
+
+/// 有禁令时刷新入口返回的错误前缀
+pub const BANNED_PREFIX: &str = "REFRESH_BANNED:";
+/// 禁令原因（同时写进保活错误，UI 直接展示）
+pub const REASON: &str = "refresh token 被判定重复使用";
+/// 首次挂禁令时发给前端的事件
+pub const EVENT: &str = "refresh-banned";
+
+/// [`EVENT`] 的负载
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct RefreshBanNotice {
+    pub account_id: String,
+    pub account_name: String,
+    pub message: String,
+}
+
+/// 刷新前检查：账号挂着禁令时返回错误，调用方不要再发刷新请求
+pub fn guard(store: &AccountStore, id: &str) -> Result<(), String> {
+    match store
+        .accounts
+        .get(id)
+        .and_then(|a| a.keepalive.refresh_ban.as_ref())
+    {
+        Some(ban) => Err(format!(
+            "{}{}，已停止自动刷新，请重新登录",
+            BANNED_PREFIX, ban.reason
+        )),
+        None => Ok(()),
+    }
+}
+
+/// 刷新失败后调用：属于重复使用一类且是首次识别时挂上禁令、发系统通知，
+/// 返回要转发给前端的通知（调用方 emit [`EVENT`] 并负责落盘）
+pub fn record_failure(store: &mut AccountStore, id: &str, error: &str) -> Option<RefreshBanNotice> {
+    if !crate::oauth::is_refresh_reused(error) || !store.ban_refresh(id, REASON) {
+        return None;
+    }
+    let account_name = store.accounts.get(id)?.name.clone();
+    let message = format!(
+        "{} 的 {}，已停止该账号的所有自动刷新，请重新登录",
+        account_name, REASON
+    );
+    eprintln!(
+        "[RefreshBan] {}: {}",
+        crate::log_identity::id(&account_name),
+        error
+    );
+    crate::notify::send("需要重新登录", &message);
+    Some(RefreshBanNotice {
+        account_id: id.to_string(),
+        account_name,
+        message,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn store_with_account() -> (AccountStore, String) {
+        let mut store = AccountStore::default();
+        let auth = json!({ "tokens": { "account_id": "ws-1", "refresh_token": "rt-1" } });
+        let id = store.add_account("a@example.com".into(), auth, None).id;
+        (store, id)
+    }
+
+    #[test]
+    fn only_reuse_errors_ban_and_only_once() {
+        let (mut store, id) = store_with_account();
+        assert!(record_failure(&mut store, &id, "刷新令牌失败: timeout").is_none());
+        assert!(guard(&store, &id).is_ok());
+
+        let err = format!(
+            "TOKEN_INVALID:刷新 token 失败: {}刷新令牌被拒绝: {{}}",
+            crate::oauth::REFRESH_REUSED_PREFIX
+        );
+        let notice = record_failure(&mut store, &id, &err).unwrap();
+        assert_eq!(notice.account_id, id);
+        let account = &store.accounts[&id];
+        assert!(account.is_token_invalid);
+        assert_eq!(account.keepalive.last_error.as_deref(), Some(REASON));
+        assert!(guard(&store, &id).unwrap_err().starts_with(BANNED_PREFIX));
+
+        // 第二次不再通知
+        assert!(record_failure(&mut store, &id, &err).is_none());
+    }
+}
@@ -1,7 +1,8 @@
 //! Remote Mode — Server 侧 HTTP API 服务器
 //!
 //! 提供账号 CRUD 和 token 拉取接口，供本机 client 模式调用。
-//! 认证：X-Auth-Token 头必须匹配 settings.remote_shared_secret。
+//! 认证：X-Auth-Token 头（或 `Authorization: Bearer`）必须匹配 settings.remote_shared_secret。
+//! `GET /events` 以 SSE 推送账号事件（见 `events`）。
 use std::convert::Infallible;
 use std::net::SocketAddr;
 use std::sync::atomic::{AtomicI64, Ordering};
@@ -16,10 +17,13 @@ use hyper::{Method, Request, Response, StatusCode};
 use hyper_util::rt::TokioIo;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use tauri::Emitter;
+use tauri::{Emitter, Manager};
 use tokio::net::TcpListener;
+use tokio::sync::watch;
 
-use crate::account::{Account, AccountStore};
+use crate::account::{Account, AccountStore, QuotaSource};
+use crate::events::{EventBus, SseBody, SseConfig};
+use crate::request_headers::RequestOverrides;
 
 type ResponseBody = Full<Bytes>;
 
@@ -63,6 +67,9 @@ struct ApiState {
     secret: String,
     version: String,
     app_handle: tauri::AppHandle,
+    events: EventBus,
+    /// server 任务被 abort 时 sender 随之 drop，SSE 连接据此关闭
+    shutdown: watch::Receiver<()>,
 }
 
 pub fn spawn_remote_server(
@@ -91,11 +98,15 @@ pub fn spawn_remote_server(
         };
         println!("[RemoteServer] Server HTTP API 已启动: http://{}", addr);
 
+        let (_shutdown_tx, shutdown) = watch::channel(());
+        let events = app_handle.state::<crate::AppState>().events.clone();
         let state = Arc::new(ApiState {
             store,
             secret,
             version,
             app_handle,
+            events,
+            shutdown,
         });
 
         loop {
@@ -111,7 +122,7 @@ pub fn spawn_remote_server(
                 let io = TokioIo::new(stream);
                 let service = service_fn(move |req| {
                     let state = state.clone();
-                    async move { Ok::<_, Infallible>(route(state, req, peer).await) }
+                    async move { Ok::<_, Infallible>(dispatch(state, req, peer).await) }
                 });
                 if let Err(e) = http1::Builder::new()
                     .keep_alive(true)
@@ -125,6 +136,26 @@ pub fn spawn_remote_server(
     })
 }
 
+/// `/events` 是流式响应，单独处理；其余走 `route` 的一次性 body
+async fn dispatch(
+    state: Arc<ApiState>,
+    req: Request<Incoming>,
+    peer: SocketAddr,
+) -> Response<SseBody> {
+    if req.uri().path() == "/events" && req.method() == Method::GET {
+        return crate::events::serve_events(
+            &state.events,
+            &state.secret,
+            req.headers(),
+            SseConfig::default(),
+            state.shutdown.clone(),
+        );
+    }
+    route(state, req, peer)
+        .await
+        .map(|body| body.boxed_unsync())
+}
+
 async fn route(
     state: Arc<ApiState>,
     req: Request<Incoming>,
@@ -204,13 +235,8 @@ async fn route(
 }
 
 fn check_auth(state: &ApiState, headers: &hyper::HeaderMap) -> bool {
-    if state.secret.is_empty() {
-        return false; // 未配置密钥时拒绝所有请求（避免误暴露）
-    }
-    match headers.get("X-Auth-Token").and_then(|v| v.to_str().ok()) {
-        Some(v) if v == state.secret => true,
-        _ => false,
-    }
+    // 未配置密钥时拒绝所有请求（避免误暴露）
+    crate::events::check_auth(&state.secret, headers)
 }
 
 fn handle_health(state: &ApiState) -> Response<ResponseBody> {
@@ -369,7 +395,7 @@ async fn handle_upsert(state: &ApiState, req: Request<Incoming>) -> Response<Res
     let id = final_id;
 
     // upsert 完成后：服务端主动刷新一次该账号的额度
-    let (access_token_opt, account_id, refresh_token) = {
+    let (access_token_opt, account_id, refresh_token, auth_profile, overrides) = {
         let store = match state.store.lock() {
             Ok(s) => s,
             Err(_) => {
@@ -393,8 +419,16 @@ async fn handle_upsert(state: &ApiState, req: Request<Incoming>) -> Response<Res
                 a.refresh_token
                     .clone()
                     .or_else(|| AccountStore::extract_refresh_token(&a.auth_json)),
+                store.auth_profile_of(a),
+                RequestOverrides::for_account(&store.settings, a),
+            ),
+            None => (
+                None,
+                None,
+                None,
+                crate::oauth::AuthProfile::builtin(),
+                RequestOverrides::default(),
             ),
-            None => (None, None, None),
         }
     };
 
@@ -409,6 +443,12 @@ async fn handle_upsert(state: &ApiState, req: Request<Incoming>) -> Response<Res
         .ok()
         .and_then(|s| s.accounts.get(&id).map(|a| a.is_relay()))
         .unwrap_or(false);
+    // 挂着刷新禁令时只用现成的 access_token 查额度，不刷新
+    let refresh_ban = state
+        .store
+        .lock()
+        .ok()
+        .and_then(|s| crate::refresh_ban::guard(&s, &id).err());
 
     let access_token = if is_relay {
         None // 跳过下面的 fetch_usage_direct 分支
@@ -416,8 +456,11 @@ async fn handle_upsert(state: &ApiState, req: Request<Incoming>) -> Response<Res
         match access_token_opt {
             Some(t) => Some(t),
             None => {
-                if let Some(ref rt) = refresh_token {
-                    match crate::oauth::refresh_access_token(rt).await {
+                if let Some(e) = refresh_ban.clone() {
+                    quota_error = Some(e);
+                    None
+                } else if let Some(ref rt) = refresh_token {
+                    match crate::oauth::refresh_access_token(&auth_profile, rt).await {
                         Ok(tok) => {
                             let mutated = if let Ok(mut s) = state.store.lock() {
                                 if let Some(acc) = s.accounts.get_mut(&id) {
@@ -441,6 +484,7 @@ async fn handle_upsert(state: &ApiState, req: Request<Incoming>) -> Response<Res
                             Some(tok.access_token)
                         }
                         Err(e) => {
+                            record_refresh_failure(state, &id, &e);
                             quota_error = Some(format!("刷新 token 失败: {}", e));
                             None
                         }
@@ -454,25 +498,19 @@ async fn handle_upsert(state: &ApiState, req: Request<Incoming>) -> Response<Res
     };
 
     if let Some(at) = access_token {
-        match crate::usage::UsageFetcher::fetch_usage_direct(at, account_id, refresh_token, true)
-            .await
+        match crate::usage::UsageFetcher::fetch_usage_direct(
+            at,
+            account_id,
+            refresh_token,
+            refresh_ban.is_none().then_some(&auth_profile),
+            overrides,
+        )
+        .await
         {
             Ok((usage, _)) => {
                 let mutated = if let Ok(mut s) = state.store.lock() {
                     if let Some(acc) = s.accounts.get_mut(&id) {
-                        acc.cached_quota = Some(crate::account::CachedQuota {
-                            five_hour_left: usage.five_hour_left as f64,
-                            five_hour_reset: usage.five_hour_reset.clone(),
-                            five_hour_reset_at: usage.five_hour_reset_at,
-                            five_hour_label: usage.five_hour_label.clone(),
-                            weekly_left: usage.weekly_left as f64,
-                            weekly_reset: usage.weekly_reset.clone(),
-                            weekly_reset_at: usage.weekly_reset_at,
-                            weekly_label: usage.weekly_label.clone(),
-                            plan_type: usage.plan_type.clone(),
-                            is_valid_for_cli: usage.is_valid_for_cli,
-                            updated_at: chrono::Utc::now(),
-                        });
+                        acc.set_cached_quota((&usage, QuotaSource::ManualRefresh).into());
                         acc.is_banned = false;
                         acc.is_token_invalid = false;
                         acc.is_logged_out = false;
@@ -490,8 +528,11 @@ async fn handle_upsert(state: &ApiState, req: Request<Incoming>) -> Response<Res
                 let _ = state.app_handle.emit("accounts-updated", ());
             }
             Err(e) => {
+                record_refresh_failure(state, &id, &e);
                 let mut mutated = false;
-                if e.contains("ACCOUNT_BANNED") {
+                if crate::usage::is_upstream_unavailable(&e) {
+                    // 上游故障（5xx / HTML 错误页）不是账号问题，标记不动、旧缓存保留
+                } else if e.contains("ACCOUNT_BANNED") {
                     if let Ok(mut s) = state.store.lock() {
                         if let Some(a) = s.accounts.get_mut(&id) {
                             a.is_banned = true;
@@ -532,6 +573,19 @@ async fn handle_upsert(state: &ApiState, req: Request<Incoming>) -> Response<Res
 }
 
 /// 直接 upsert 到 accounts HashMap
+/// 刷新失败过一遍刷新禁令（见 `refresh_ban`），首次挂上时通知本机前端
+fn record_refresh_failure(state: &ApiState, id: &str, error: &str) {
+    let notice = state
+        .store
+        .lock()
+        .ok()
+        .and_then(|mut s| crate::refresh_ban::record_failure(&mut s, id, error));
+    if let Some(notice) = notice {
+        schedule_save(state.store.clone());
+        let _ = state.app_handle.emit(crate::refresh_ban::EVENT, &notice);
+    }
+}
+
 fn upsert_account(store: &mut AccountStore, incoming: Account) -> Result<(), String> {
     store.accounts.insert(incoming.id.clone(), incoming);
     Ok(())
@@ -561,7 +615,15 @@ fn handle_delete(state: &ApiState, id: &str) -> Response<ResponseBody> {
 async fn handle_refresh_account(state: &ApiState, id: &str) -> Response<ResponseBody> {
     let id = id.to_string();
 
-    let (access_token_opt, account_id, refresh_token, is_relay) = {
+    let (
+        access_token_opt,
+        account_id,
+        refresh_token,
+        is_relay,
+        auth_profile,
+        overrides,
+        refresh_ban,
+    ) = {
         let store = match state.store.lock() {
             Ok(s) => s,
             Err(e) => return err_resp(format!("锁获取失败: {}", e)),
@@ -574,6 +636,9 @@ async fn handle_refresh_account(state: &ApiState, id: &str) -> Response<Response
                     .clone()
                     .or_else(|| AccountStore::extract_refresh_token(&a.auth_json)),
                 a.is_relay(),
+                store.auth_profile_of(a),
+                RequestOverrides::for_account(&store.settings, a),
+                crate::refresh_ban::guard(&store, &id).err(),
             ),
             None => {
                 return json_resp(StatusCode::NOT_FOUND, json!({"error": "account not found"}));
@@ -593,13 +658,16 @@ async fn handle_refresh_account(state: &ApiState, id: &str) -> Response<Response
     let access_token = match access_token_opt {
         Some(t) => t,
         None => {
+            if let Some(e) = &refresh_ban {
+                return json_resp(StatusCode::BAD_REQUEST, json!({ "error": e }));
+            }
             let Some(rt) = refresh_token.clone() else {
                 return json_resp(
                     StatusCode::BAD_REQUEST,
                     json!({"error": "TOKEN_INVALID:无 access_token 且无 refresh_token"}),
                 );
             };
-            match crate::oauth::refresh_access_token(&rt).await {
+            match crate::oauth::refresh_access_token(&auth_profile, &rt).await {
                 Ok(tok) => {
                     let mutated = if let Ok(mut s) = state.store.lock() {
                         if let Some(acc) = s.accounts.get_mut(&id) {
@@ -623,6 +691,7 @@ async fn handle_refresh_account(state: &ApiState, id: &str) -> Response<Response
                     tok.access_token
                 }
                 Err(e) => {
+                    record_refresh_failure(state, &id, &e);
                     return json_resp(
                         StatusCode::BAD_REQUEST,
                         json!({"error": format!("TOKEN_INVALID:刷新 token 失败: {}", e)}),
@@ -636,26 +705,15 @@ async fn handle_refresh_account(state: &ApiState, id: &str) -> Response<Response
         access_token,
         account_id,
         refresh_token,
-        true,
+        refresh_ban.is_none().then_some(&auth_profile),
+        overrides,
     )
     .await
     {
         Ok((usage, _)) => {
             let mutated = if let Ok(mut s) = state.store.lock() {
                 if let Some(acc) = s.accounts.get_mut(&id) {
-                    acc.cached_quota = Some(crate::account::CachedQuota {
-                        five_hour_left: usage.five_hour_left as f64,
-                        five_hour_reset: usage.five_hour_reset.clone(),
-                        five_hour_reset_at: usage.five_hour_reset_at,
-                        five_hour_label: usage.five_hour_label.clone(),
-                        weekly_left: usage.weekly_left as f64,
-                        weekly_reset: usage.weekly_reset.clone(),
-                        weekly_reset_at: usage.weekly_reset_at,
-                        weekly_label: usage.weekly_label.clone(),
-                        plan_type: usage.plan_type.clone(),
-                        is_valid_for_cli: usage.is_valid_for_cli,
-                        updated_at: chrono::Utc::now(),
-                    });
+                    acc.set_cached_quota((&usage, QuotaSource::ManualRefresh).into());
                     acc.is_banned = false;
                     acc.is_token_invalid = false;
                     acc.is_logged_out = false;
@@ -673,8 +731,11 @@ async fn handle_refresh_account(state: &ApiState, id: &str) -> Response<Response
             json_resp(StatusCode::OK, json!({"ok": true, "usage": usage}))
         }
         Err(e) => {
+            record_refresh_failure(state, &id, &e);
             let mut mutated = false;
-            if e.contains("ACCOUNT_BANNED") {
+            if crate::usage::is_upstream_unavailable(&e) {
+                // 上游故障（5xx / HTML 错误页）不是账号问题，标记不动、旧缓存保留
+            } else if e.contains("ACCOUNT_BANNED") {
                 if let Ok(mut s) = state.store.lock() {
                     if let Some(a) = s.accounts.get_mut(&id) {
                         a.is_banned = true;
@@ -815,21 +876,14 @@ async fn handle_switch(state: &ApiState, req: Request<Incoming>) -> Response<Res
     });
 
     let Some(new_id) = next_id else {
-        // 算最早 reset
+        // 算最早恢复时间（耗尽的窗口全部重置才算，周额度耗尽的号不看 5H）
         let now = chrono::Utc::now().timestamp();
-        let mut earliest: Option<i64> = None;
-        for a in store.accounts.values() {
-            if let Some(q) = &a.cached_quota {
-                for r in [q.five_hour_reset_at, q.weekly_reset_at]
-                    .into_iter()
-                    .flatten()
-                {
-                    if now < r {
-                        earliest = Some(earliest.map_or(r, |e: i64| e.min(r)));
-                    }
-                }
-            }
-        }
+        let earliest: Option<i64> = store
+            .accounts
+            .values()
+            .filter_map(|a| a.cached_quota.as_ref())
+            .filter_map(|q| crate::quota_window::classify(q, now).usable_at())
+            .min();
         return json_resp(
             StatusCode::OK,
             json!({
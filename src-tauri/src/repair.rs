@@ -0,0 +1,708 @@
+//! 一键修复向导
+//!
+//! 出问题时（权限不对、auth.json 缺 refresh_token、激活指针和磁盘对不上、调度器挂了……）
+//! 不懂行的用户很难知道该点哪个修复按钮。`repair_setup` 按固定顺序跑一遍 [`RepairStep`]：
+//! - 每一步先 [`RepairStep::plan`]：没问题 → `Skipped`；需要人工（sudo、手机锚等受保护账号）→
+//!   `NeedsManualAction`，永远不自动执行；能修 → 试运行只报告，否则 [`RepairStep::apply`]
+//! - 某一步出错（含 panic）只记 `Failed`，后面的步骤照跑
+//!
+//! 新的修复实现 [`RepairStep`] 后加进 `lib.rs` 里的步骤列表即可。
+
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::account::AccountStore;
+use crate::auth_journal::AuthWriteOp;
+use crate::staging::Staging;
+
+/// 一步的检查结果
+#[derive(Debug, Clone, PartialEq)]
+pub enum StepPlan {
+    /// 没问题，附说明
+    Nothing(String),
+    /// 可以自动修，附将要做什么
+    Fix(String),
+    /// 需要用户自己动手，附操作说明
+    Manual(String),
+}
+
+/// 一个可插拔的检查 / 修复步骤
+pub trait RepairStep {
+    fn name(&self) -> &'static str;
+    /// 只检查不改动；试运行和正式运行都会调用
+    fn plan(&mut self) -> Result<StepPlan, String>;
+    /// 执行修复，返回做了什么；只在 `plan` 返回 `Fix` 且不是试运行时调用
+    fn apply(&mut self) -> Result<String, String>;
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RepairStatus {
+    Skipped,
+    Fixed,
+    NeedsManualAction { instruction: String },
+    Failed { error: String },
+}
+
+/// 一步的结果
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct RepairReport {
+    pub step: String,
+    pub status: RepairStatus,
+    /// 检查 / 修复的说明；试运行时是"将会做什么"
+    pub detail: Option<String>,
+    /// 试运行且这一步本会自动修复
+    pub would_fix: bool,
+}
+
+impl RepairReport {
+    fn new(step: &str, status: RepairStatus, detail: Option<String>) -> Self {
+        Self {
+            step: step.to_string(),
+            status,
+            detail,
+            would_fix: false,
+        }
+    }
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "未知错误".to_string())
+}
+
+/// 按顺序跑完所有步骤；单步失败不影响后续
+pub fn run(steps: &mut [Box<dyn RepairStep + '_>], dry_run: bool) -> Vec<RepairReport> {
+    steps
+        .iter_mut()
+        .map(|step| {
+            let name = step.name();
+            let plan = catch_unwind(AssertUnwindSafe(|| step.plan()))
+                .unwrap_or_else(|p| Err(format!("检查时崩溃: {}", panic_message(p))));
+            let report = match plan {
+                Err(error) => RepairReport::new(name, RepairStatus::Failed { error }, None),
+                Ok(StepPlan::Nothing(detail)) => {
+                    RepairReport::new(name, RepairStatus::Skipped, Some(detail))
+                }
+                Ok(StepPlan::Manual(instruction)) => {
+                    RepairReport::new(name, RepairStatus::NeedsManualAction { instruction }, None)
+                }
+                Ok(StepPlan::Fix(detail)) if dry_run => RepairReport {
+                    would_fix: true,
+                    ..RepairReport::new(name, RepairStatus::Skipped, Some(detail))
+                },
+                Ok(StepPlan::Fix(planned)) => {
+                    match catch_unwind(AssertUnwindSafe(|| step.apply()))
+                        .unwrap_or_else(|p| Err(format!("修复时崩溃: {}", panic_message(p))))
+                    {
+                        Ok(done) => RepairReport::new(name, RepairStatus::Fixed, Some(done)),
+                        Err(error) => {
+                            RepairReport::new(name, RepairStatus::Failed { error }, Some(planned))
+                        }
+                    }
+                }
+            };
+            println!(
+                "[Repair] {}{}: {:?}",
+                name,
+                if dry_run { "（试运行）" } else { "" },
+                report.status
+            );
+            report
+        })
+        .collect()
+}
+
+/// 数据目录、accounts.json、Codex 目录、auth.json 的属主和权限（目录 0700、文件 0600）
+pub struct PermissionStep {
+    targets: Vec<(PathBuf, u32)>,
+    loose: Vec<(PathBuf, u32)>,
+}
+
+impl PermissionStep {
+    pub fn new(targets: Vec<(PathBuf, u32)>) -> Self {
+        Self {
+            targets,
+            loose: Vec::new(),
+        }
+    }
+
+    pub fn system() -> Self {
+        Self::new(vec![
+            (crate::paths::data_dir(), 0o700),
+            (AccountStore::config_path(), 0o600),
+            (crate::paths::codex_dir(), 0o700),
+            (AccountStore::codex_auth_path(), 0o600),
+        ])
+    }
+}
+
+impl RepairStep for PermissionStep {
+    fn name(&self) -> &'static str {
+        "文件权限"
+    }
+
+    #[cfg(unix)]
+    fn plan(&mut self) -> Result<StepPlan, String> {
+        use std::os::unix::fs::MetadataExt;
+
+        let uid = unsafe { libc::geteuid() };
+        let mut foreign = Vec::new();
+        self.loose.clear();
+        for (path, mode) in &self.targets {
+            let Ok(meta) = std::fs::metadata(path) else {
+                continue;
+            };
+            if meta.uid() != uid {
+                foreign.push(path.display().to_string());
+            } else if meta.mode() & 0o077 != 0 {
+                self.loose.push((path.clone(), *mode));
+            }
+        }
+        if !foreign.is_empty() {
+            return Ok(StepPlan::Manual(format!(
+                "以下文件属于其他用户（多半是用 sudo 跑过 codex），请在终端执行 sudo chown -R $(whoami) 后重试: {}",
+                foreign.join(", ")
+            )));
+        }
+        if self.loose.is_empty() {
+            return Ok(StepPlan::Nothing("权限正常".to_string()));
+        }
+        Ok(StepPlan::Fix(format!(
+            "收紧权限: {}",
+            self.loose
+                .iter()
+                .map(|(p, m)| format!("{} → {:o}", p.display(), m))
+                .collect::<Vec<_>>()
+                .join(", ")
+        )))
+    }
+
+    #[cfg(not(unix))]
+    fn plan(&mut self) -> Result<StepPlan, String> {
+        Ok(StepPlan::Nothing("仅在 macOS / Linux 上检查".to_string()))
+    }
+
+    #[cfg(unix)]
+    fn apply(&mut self) -> Result<String, String> {
+        use std::os::unix::fs::PermissionsExt;
+
+        for (path, mode) in &self.loose {
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(*mode))
+                .map_err(|e| format!("设置 {} 权限失败: {}", path.display(), e))?;
+        }
+        Ok(format!("已收紧 {} 个文件 / 目录的权限", self.loose.len()))
+    }
+
+    #[cfg(not(unix))]
+    fn apply(&mut self) -> Result<String, String> {
+        Ok(String::new())
+    }
+}
+
+fn account_label(store: &AccountStore, id: Option<&str>) -> String {
+    id.map(|id| {
+        store
+            .accounts
+            .get(id)
+            .map_or_else(|| id.to_string(), |a| a.name.clone())
+    })
+    .unwrap_or_else(|| "（无）".to_string())
+}
+
+/// 激活指针对齐到磁盘 auth.json 所属账号（只改指针，不动 token，同 `sync_active_with_disk`）
+pub struct CurrentPointerStep {
+    store: Arc<Mutex<AccountStore>>,
+    target: Option<String>,
+}
+
+impl CurrentPointerStep {
+    pub fn new(store: Arc<Mutex<AccountStore>>) -> Self {
+        Self {
+            store,
+            target: None,
+        }
+    }
+}
+
+impl RepairStep for CurrentPointerStep {
+    fn name(&self) -> &'static str {
+        "当前账号指针"
+    }
+
+    fn plan(&mut self) -> Result<StepPlan, String> {
+        let Ok(disk_auth) = AccountStore::read_codex_auth() else {
+            return Ok(StepPlan::Manual(
+                "~/.codex/auth.json 不存在或读不出来：在账号列表里切换一次账号即可重新写入"
+                    .to_string(),
+            ));
+        };
+        let store = self.store.lock().map_err(|e| e.to_string())?;
+        if let Some(anchor_id) = store.session_anchor_id() {
+            if store.current.as_deref() != Some(anchor_id.as_str()) {
+                return Ok(StepPlan::Manual(
+                    "手机锚生效中，磁盘是锚账号的镜像，不能按它对齐：如需调整请先在锚账号上取消手机锚"
+                        .to_string(),
+                ));
+            }
+        }
+        let Some(matching) = store.find_account_for_disk_auth(&disk_auth) else {
+            return Ok(StepPlan::Manual(
+                "磁盘上登录的账号不在管理列表中：请先导入当前账号".to_string(),
+            ));
+        };
+        if store.current.as_deref() == Some(matching.as_str()) {
+            return Ok(StepPlan::Nothing("与磁盘一致".to_string()));
+        }
+        let detail = format!(
+            "当前账号从 {} 改为磁盘上登录的 {}",
+            account_label(&store, store.current.as_deref()),
+            account_label(&store, Some(&matching))
+        );
+        self.target = Some(matching);
+        Ok(StepPlan::Fix(detail))
+    }
+
+    fn apply(&mut self) -> Result<String, String> {
+        let target = self.target.clone().ok_or("没有要对齐的账号")?;
+        let mut store = self.store.lock().map_err(|e| e.to_string())?;
+        if !store.accounts.contains_key(&target) {
+            return Err(format!("账号 {} 已不存在", target));
+        }
+        store.current = Some(target.clone());
+        store.save()?;
+        Ok(format!(
+            "已将当前账号设为 {}",
+            account_label(&store, Some(&target))
+        ))
+    }
+}
+
+/// 磁盘 auth.json 缺 refresh_token 时，用当前账号已存的补回去；
+/// 只在磁盘身份和当前账号一致、且当前账号有 refresh_token 时才动手
+pub struct DiskRefreshTokenStep {
+    store: Arc<Mutex<AccountStore>>,
+    patch: Option<(String, Value)>,
+}
+
+impl DiskRefreshTokenStep {
+    pub fn new(store: Arc<Mutex<AccountStore>>) -> Self {
+        Self { store, patch: None }
+    }
+}
+
+impl RepairStep for DiskRefreshTokenStep {
+    fn name(&self) -> &'static str {
+        "auth.json 的 refresh_token"
+    }
+
+    fn plan(&mut self) -> Result<StepPlan, String> {
+        let Ok(disk_auth) = AccountStore::read_codex_auth() else {
+            return Ok(StepPlan::Nothing("磁盘没有 auth.json".to_string()));
+        };
+        match disk_auth.get("tokens") {
+            None => {
+                return Ok(StepPlan::Nothing(
+                    "API key 登录，不需要 refresh_token".to_string(),
+                ))
+            }
+            Some(tokens) if !tokens.is_object() => {
+                return Ok(StepPlan::Manual(
+                    "auth.json 的 tokens 格式异常：请重新登录 Codex 后导入".to_string(),
+                ))
+            }
+            Some(_) => {}
+        }
+        if AccountStore::extract_refresh_token(&disk_auth).is_some() {
+            return Ok(StepPlan::Nothing("refresh_token 完好".to_string()));
+        }
+        let store = self.store.lock().map_err(|e| e.to_string())?;
+        if store.session_anchor_id().is_some() {
+            return Ok(StepPlan::Manual(
+                "auth.json 缺少 refresh_token，但手机锚生效中，不自动改写：请确认后在锚账号上重新设置一次手机锚"
+                    .to_string(),
+            ));
+        }
+        let repairable = store
+            .current
+            .as_deref()
+            .and_then(|id| store.accounts.get(id))
+            .filter(|a| AccountStore::auth_identity_matches(&a.auth_json, &disk_auth))
+            .and_then(|a| {
+                a.refresh_token
+                    .clone()
+                    .or_else(|| AccountStore::extract_refresh_token(&a.auth_json))
+                    .map(|rt| (a.id.clone(), a.name.clone(), rt))
+            });
+        let Some((id, name, refresh_token)) = repairable else {
+            return Ok(StepPlan::Manual(
+                "auth.json 缺少 refresh_token，且无法确认属于哪个已存账号：请重新登录 Codex 后导入"
+                    .to_string(),
+            ));
+        };
+        let mut patched = disk_auth;
+        patched["tokens"]["refresh_token"] = Value::String(refresh_token);
+        self.patch = Some((id, patched));
+        Ok(StepPlan::Fix(format!(
+            "用 {} 已存的 refresh_token 补回 auth.json",
+            name
+        )))
+    }
+
+    fn apply(&mut self) -> Result<String, String> {
+        let (id, auth) = self.patch.take().ok_or("没有要补写的内容")?;
+        // 持锁写盘，和切号 / 刷新回写串行
+        let _store = self.store.lock().map_err(|e| e.to_string())?;
+        AccountStore::write_codex_auth(&auth, AuthWriteOp::Repair, Some(&id))?;
+        Ok("已补回 refresh_token".to_string())
+    }
+}
+
+/// 开了后台刷新但调度器没在跑时重新拉起
+pub struct SchedulerStep<'a> {
+    enabled: bool,
+    alive: bool,
+    respawn: Box<dyn FnMut() -> Result<(), String> + 'a>,
+}
+
+impl<'a> SchedulerStep<'a> {
+    pub fn new(
+        enabled: bool,
+        alive: bool,
+        respawn: impl FnMut() -> Result<(), String> + 'a,
+    ) -> Self {
+        Self {
+            enabled,
+            alive,
+            respawn: Box::new(respawn),
+        }
+    }
+}
+
+impl RepairStep for SchedulerStep<'_> {
+    fn name(&self) -> &'static str {
+        "后台调度器"
+    }
+
+    fn plan(&mut self) -> Result<StepPlan, String> {
+        Ok(match (self.enabled, self.alive) {
+            (false, _) => StepPlan::Nothing("后台刷新未开启".to_string()),
+            (true, true) => StepPlan::Nothing("运行中".to_string()),
+            (true, false) => StepPlan::Fix("后台刷新已开启但调度器没在运行，重新启动".to_string()),
+        })
+    }
+
+    fn apply(&mut self) -> Result<String, String> {
+        (self.respawn)()?;
+        Ok("已重新启动调度器".to_string())
+    }
+}
+
+/// 崩溃残留的 `.tmp` 文件，以及已删除账号的暂存副本
+pub struct StaleFilesStep {
+    store: Arc<Mutex<AccountStore>>,
+    staging: Staging,
+    tmp_targets: Vec<PathBuf>,
+    stale_tmp: Vec<PathBuf>,
+    orphans: Vec<String>,
+}
+
+impl StaleFilesStep {
+    pub fn new(
+        store: Arc<Mutex<AccountStore>>,
+        staging: Staging,
+        tmp_targets: Vec<PathBuf>,
+    ) -> Self {
+        Self {
+            store,
+            staging,
+            tmp_targets,
+            stale_tmp: Vec::new(),
+            orphans: Vec::new(),
+        }
+    }
+
+    pub fn system(store: Arc<Mutex<AccountStore>>) -> Self {
+        Self::new(
+            store,
+            Staging::system(),
+            vec![AccountStore::config_path(), AccountStore::codex_auth_path()],
+        )
+    }
+}
+
+impl RepairStep for StaleFilesStep {
+    fn name(&self) -> &'static str {
+        "残留文件"
+    }
+
+    fn plan(&mut self) -> Result<StepPlan, String> {
+        self.stale_tmp = self
+            .tmp_targets
+            .iter()
+            .map(|p| crate::atomic_write::tmp_path(p))
+            .filter(|p| p.exists())
+            .collect();
+        let store = self.store.lock().map_err(|e| e.to_string())?;
+        self.orphans = self
+            .staging
+            .list()
+            .into_iter()
+            .map(|e| e.account_id)
+            .filter(|id| !store.accounts.contains_key(id))
+            .collect();
+        self.orphans.sort();
+        self.orphans.dedup();
+        if self.stale_tmp.is_empty() && self.orphans.is_empty() {
+            return Ok(StepPlan::Nothing("没有残留".to_string()));
+        }
+        Ok(StepPlan::Fix(format!(
+            "删除 {} 个临时文件、{} 个已删除账号的暂存副本",
+            self.stale_tmp.len(),
+            self.orphans.len()
+        )))
+    }
+
+    fn apply(&mut self) -> Result<String, String> {
+        // 持锁：accounts.json / auth.json 的原子写都在 store 锁内，不会删到正在写的临时文件
+        let _store = self.store.lock().map_err(|e| e.to_string())?;
+        let mut errors = Vec::new();
+        for tmp in &self.stale_tmp {
+            match std::fs::remove_file(tmp) {
+                Ok(()) => {}
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => errors.push(format!("{}: {}", tmp.display(), e)),
+            }
+        }
+        let mut staged = 0;
+        for id in &self.orphans {
+            match self.staging.cleanup(Some(id)) {
+                Ok(n) => staged += n,
+                Err(e) => errors.push(e),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors.join("; "));
+        }
+        Ok(format!(
+            "已删除 {} 个临时文件、{} 份暂存副本",
+            self.stale_tmp.len(),
+            staged
+        ))
+    }
+}
+
+/// Codex.app 的隔离属性：移除需要管理员密码，只提示不执行
+pub struct QuarantineStep;
+
+impl RepairStep for QuarantineStep {
+    fn name(&self) -> &'static str {
+        "Codex App 隔离属性"
+    }
+
+    fn plan(&mut self) -> Result<StepPlan, String> {
+        if crate::ide_control::has_quarantine() {
+            Ok(StepPlan::Manual(
+                "Codex.app 带有隔离属性（可能导致闪退）：请在设置 → 故障修复里点“修复 Codex App 闪退”，需要输入管理员密码"
+                    .to_string(),
+            ))
+        } else {
+            Ok(StepPlan::Nothing("未发现".to_string()))
+        }
+    }
+
+    fn apply(&mut self) -> Result<String, String> {
+        Err("需要管理员权限，不自动执行".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    struct FakeStep {
+        name: &'static str,
+        plan: Result<StepPlan, String>,
+        apply: Result<String, String>,
+        applied: Rc<Cell<usize>>,
+    }
+
+    impl FakeStep {
+        fn boxed(
+            name: &'static str,
+            plan: Result<StepPlan, String>,
+            apply: Result<String, String>,
+            applied: &Rc<Cell<usize>>,
+        ) -> Box<dyn RepairStep> {
+            Box::new(Self {
+                name,
+                plan,
+                apply,
+                applied: applied.clone(),
+            })
+        }
+    }
+
+    impl RepairStep for FakeStep {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+        fn plan(&mut self) -> Result<StepPlan, String> {
+            self.plan.clone()
+        }
+        fn apply(&mut self) -> Result<String, String> {
+            self.applied.set(self.applied.get() + 1);
+            self.apply.clone()
+        }
+    }
+
+    struct PanicStep;
+
+    impl RepairStep for PanicStep {
+        fn name(&self) -> &'static str {
+            "panic"
+        }
+        fn plan(&mut self) -> Result<StepPlan, String> {
+            panic!("boom")
+        }
+        fn apply(&mut self) -> Result<String, String> {
+            unreachable!()
+        }
+    }
+
+    fn steps(applied: &Rc<Cell<usize>>) -> Vec<Box<dyn RepairStep>> {
+        vec![
+            FakeStep::boxed(
+                "ok",
+                Ok(StepPlan::Nothing("fine".into())),
+                Ok(String::new()),
+                applied,
+            ),
+            FakeStep::boxed(
+                "fix",
+                Ok(StepPlan::Fix("will fix".into())),
+                Ok("fixed".into()),
+                applied,
+            ),
+            FakeStep::boxed(
+                "sudo",
+                Ok(StepPlan::Manual("run sudo".into())),
+                Ok("never".into()),
+                applied,
+            ),
+            FakeStep::boxed(
+                "check-fails",
+                Err("cannot read".into()),
+                Ok(String::new()),
+                applied,
+            ),
+            Box::new(PanicStep),
+            FakeStep::boxed(
+                "apply-fails",
+                Ok(StepPlan::Fix("try".into())),
+                Err("denied".into()),
+                applied,
+            ),
+        ]
+    }
+
+    #[test]
+    fn every_status_is_reported_and_failures_do_not_stop_the_run() {
+        let applied = Rc::new(Cell::new(0));
+        let reports = run(&mut steps(&applied), false);
+        let statuses: Vec<_> = reports
+            .iter()
+            .map(|r| (r.step.as_str(), &r.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            vec![
+                ("ok", &RepairStatus::Skipped),
+                ("fix", &RepairStatus::Fixed),
+                (
+                    "sudo",
+                    &RepairStatus::NeedsManualAction {
+                        instruction: "run sudo".into()
+                    }
+                ),
+                (
+                    "check-fails",
+                    &RepairStatus::Failed {
+                        error: "cannot read".into()
+                    }
+                ),
+                (
+                    "panic",
+                    &RepairStatus::Failed {
+                        error: "检查时崩溃: boom".into()
+                    }
+                ),
+                (
+                    "apply-fails",
+                    &RepairStatus::Failed {
+                        error: "denied".into()
+                    }
+                ),
+            ]
+        );
+        assert_eq!(reports[1].detail.as_deref(), Some("fixed"));
+        assert!(reports.iter().all(|r| !r.would_fix));
+        // 只有 fix 和 apply-fails 执行了修复；manual 一律不执行
+        assert_eq!(applied.get(), 2);
+    }
+
+    #[test]
+    fn dry_run_only_reports() {
+        let applied = Rc::new(Cell::new(0));
+        let reports = run(&mut steps(&applied), true);
+        assert_eq!(applied.get(), 0);
+        let would: Vec<_> = reports
+            .iter()
+            .filter(|r| r.would_fix)
+            .map(|r| (r.step.as_str(), r.detail.as_deref()))
+            .collect();
+        assert_eq!(
+            would,
+            vec![("fix", Some("will fix")), ("apply-fails", Some("try"))]
+        );
+        assert!(reports
+            .iter()
+            .filter(|r| r.would_fix)
+            .all(|r| r.status == RepairStatus::Skipped));
+        assert!(matches!(
+            reports[2].status,
+            RepairStatus::NeedsManualAction { .. }
+        ));
+    }
+
+    #[test]
+    fn scheduler_step_respawns_only_when_enabled_and_dead() {
+        let respawned = Cell::new(0);
+        let mut steps: Vec<Box<dyn RepairStep + '_>> = vec![
+            Box::new(SchedulerStep::new(false, false, || {
+                respawned.set(respawned.get() + 1);
+                Ok(())
+            })),
+            Box::new(SchedulerStep::new(true, true, || {
+                respawned.set(respawned.get() + 1);
+                Ok(())
+            })),
+            Box::new(SchedulerStep::new(true, false, || {
+                respawned.set(respawned.get() + 1);
+                Ok(())
+            })),
+        ];
+        let reports = run(&mut steps, false);
+        assert_eq!(reports[2].status, RepairStatus::Fixed);
+        assert_eq!(respawned.get(), 1);
+    }
+}
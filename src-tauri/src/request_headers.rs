@@ -0,0 +1,194 @@
+//! 配额请求的自定义请求头
+//!
+//! 有的托管工作区只放行 User-Agent 命中白名单的 API 流量，默认 UA 会被 403。
+//! 优先级：内置默认 < 设置里的全局 User-Agent < 账号自己的 `request_headers`。
+//! 鉴权相关的头（`Authorization`、`ChatGPT-Account-Id`）由请求代码按账号填，不允许覆盖。
+
+use std::collections::BTreeMap;
+
+use crate::account::{Account, AppSettings};
+
+/// 账号级请求头最多这么多条
+pub const MAX_HEADERS: usize = 16;
+const MAX_NAME_LEN: usize = 64;
+const MAX_VALUE_LEN: usize = 1024;
+
+/// 不允许自定义的头（小写）
+pub const FORBIDDEN_HEADERS: &[&str] = &[
+    "authorization",
+    "chatgpt-account-id",
+    "host",
+    "content-length",
+];
+
+fn is_token_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c)
+}
+
+/// 校验头名：RFC 7230 token，且不在 [`FORBIDDEN_HEADERS`] 里
+pub fn validate_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.len() > MAX_NAME_LEN || !name.chars().all(is_token_char) {
+        return Err(format!("请求头名称不合法: {:?}", name));
+    }
+    if FORBIDDEN_HEADERS.contains(&name.to_ascii_lowercase().as_str()) {
+        return Err(format!("不允许自定义请求头 {}", name));
+    }
+    Ok(())
+}
+
+/// 校验头值：可见 ASCII、空格和 tab，不能换行
+pub fn validate_value(name: &str, value: &str) -> Result<(), String> {
+    if value.len() > MAX_VALUE_LEN {
+        return Err(format!("请求头 {} 的值太长（上限 {} 字节）", name, MAX_VALUE_LEN));
+    }
+    if !value.chars().all(|c| c == '\t' || (' '..='~').contains(&c)) {
+        return Err(format!("请求头 {} 的值含有不允许的字符", name));
+    }
+    Ok(())
+}
+
+/// 校验并规范化账号级请求头：名称去空白，值去首尾空白；同名（不分大小写）只能出现一次
+pub fn normalize(headers: BTreeMap<String, String>) -> Result<BTreeMap<String, String>, String> {
+    if headers.len() > MAX_HEADERS {
+        return Err(format!("自定义请求头最多 {} 条", MAX_HEADERS));
+    }
+    let mut out = BTreeMap::new();
+    for (name, value) in headers {
+        let name = name.trim().to_string();
+        let value = value.trim().to_string();
+        validate_name(&name)?;
+        validate_value(&name, &value)?;
+        if out.keys().any(|k: &String| k.eq_ignore_ascii_case(&name)) {
+            return Err(format!("请求头 {} 重复", name));
+        }
+        out.insert(name, value);
+    }
+    Ok(out)
+}
+
+/// 全局 User-Agent 覆盖：空串表示用默认
+pub fn validate_user_agent(user_agent: &str) -> Result<(), String> {
+    validate_value("User-Agent", user_agent.trim())
+}
+
+/// 某个账号请求时叠加在默认头之上的部分
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RequestOverrides {
+    /// 设置里的全局 User-Agent（None = 用默认）
+    pub user_agent: Option<String>,
+    /// 账号自己的请求头
+    pub headers: BTreeMap<String, String>,
+}
+
+impl RequestOverrides {
+    pub fn for_account(settings: &AppSettings, account: &Account) -> Self {
+        Self {
+            user_agent: Some(settings.usage_user_agent.trim().to_string())
+                .filter(|ua| !ua.is_empty()),
+            headers: account.request_headers.clone(),
+        }
+    }
+}
+
+/// 合并请求头：默认 < 全局 User-Agent < 账号请求头，名称不分大小写；
+/// 覆盖时保留默认头的位置，新增的按名称排序接在后面。
+/// 禁止的头即使混进了账号配置（手改 accounts.json）也会被丢掉
+pub fn merge(defaults: &[(&str, String)], overrides: &RequestOverrides) -> Vec<(String, String)> {
+    let mut merged: Vec<(String, String)> = defaults
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.clone()))
+        .collect();
+    let mut set = |name: &str, value: &str| {
+        match merged
+            .iter_mut()
+            .find(|(existing, _)| existing.eq_ignore_ascii_case(name))
+        {
+            Some(slot) => slot.1 = value.to_string(),
+            None => merged.push((name.to_string(), value.to_string())),
+        }
+    };
+    if let Some(ua) = &overrides.user_agent {
+        set("User-Agent", ua);
+    }
+    for (name, value) in &overrides.headers {
+        if validate_name(name).is_ok() && validate_value(name, value).is_ok() {
+            set(name, value);
+        }
+    }
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&str, &str)]) -> BTreeMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn validation_rejects_bad_and_forbidden_headers() {
+        assert!(normalize(headers(&[(" X-Team ", " blue ")])).is_ok());
+        assert_eq!(
+            normalize(headers(&[(" X-Team ", " blue ")])).unwrap(),
+            headers(&[("X-Team", "blue")])
+        );
+        assert!(normalize(headers(&[("Authorization", "Bearer x")]))
+            .unwrap_err()
+            .contains("Authorization"));
+        assert!(normalize(headers(&[("chatgpt-account-id", "ws")])).is_err());
+        assert!(normalize(headers(&[("Bad Name", "v")])).is_err());
+        assert!(normalize(headers(&[("", "v")])).is_err());
+        assert!(normalize(headers(&[("X-A", "line\r\nInjected: 1")])).is_err());
+        assert!(normalize(headers(&[("X-A", "中文")])).is_err());
+        assert!(normalize(headers(&[("X-A", "1"), ("x-a", "2")])).is_err());
+        let many = (0..=MAX_HEADERS)
+            .map(|i| (format!("X-{i}"), "v".to_string()))
+            .collect();
+        assert!(normalize(many).is_err());
+        assert!(validate_user_agent("Mozilla/5.0 (X11)").is_ok());
+        assert!(validate_user_agent("ua\n").is_ok(), "首尾空白先去掉");
+        assert!(validate_user_agent("a\nb").is_err());
+    }
+
+    #[test]
+    fn merge_precedence_is_defaults_then_global_then_account() {
+        let defaults = [
+            ("User-Agent", "default-ua".to_string()),
+            ("Accept", "application/json".to_string()),
+        ];
+        assert_eq!(
+            merge(&defaults, &RequestOverrides::default()),
+            vec![
+                ("User-Agent".to_string(), "default-ua".to_string()),
+                ("Accept".to_string(), "application/json".to_string()),
+            ]
+        );
+
+        let global = RequestOverrides {
+            user_agent: Some("global-ua".into()),
+            headers: BTreeMap::new(),
+        };
+        assert_eq!(merge(&defaults, &global)[0].1, "global-ua");
+
+        let account = RequestOverrides {
+            user_agent: Some("global-ua".into()),
+            headers: headers(&[
+                ("user-agent", "team-ua"),
+                ("X-Team", "blue"),
+                ("Authorization", "Bearer evil"),
+            ]),
+        };
+        assert_eq!(
+            merge(&defaults, &account),
+            vec![
+                ("User-Agent".to_string(), "team-ua".to_string()),
+                ("Accept".to_string(), "application/json".to_string()),
+                ("X-Team".to_string(), "blue".to_string()),
+            ]
+        );
+    }
+}
@@ -6,12 +6,30 @@
 //! - 手机锚账号 (v0.7+)：独立 4 min tick，无论 current 是谁都强保活，且把
 //!   刷新出来的 token 落盘到 `~/.codex/auth.json`（用 +24h 撒谎 expires_at 让
 //!   Codex.app 永远不会自己 refresh，rt 单写者就是本程序）
+//! - 每日额度报告：到点刷新过期额度后发一条汇总通知（见 `daily_report`）
+//! - refresh_token 老化：闲置过久的账号提醒一次，用户开启时自动保活（见 `token_aging`）
+//! - 自动备份：开启时每天把账号库备份一份到 `backups/`（见 `store_backups`）
+//! - 给用户操作让路：最近有界面操作时推迟本轮、稍后重试；用户刚操作过的账号本轮跳过
+//!   （见 `activity`）
+//! - 轮次节流：不管什么触发，两轮实际同步之间至少隔 1 分钟（见 `pacing`）
 
-use crate::account::AccountStore;
+use crate::account::{AccountStore, QuotaSource};
+use crate::auth_journal::AuthWriteOp;
+use crate::auth_permissions;
+use crate::activity::ForegroundActivity;
+use crate::daily_report;
+use crate::log_identity;
 use crate::oauth;
+use crate::pacing::SyncPacer;
+use crate::scheduler_supervisor::Shutdown;
+use crate::store_backups::{self, BackupKind};
+use crate::token_aging::{self, KeepaliveOffer};
+use crate::warnings::Warning;
+use futures_util::StreamExt;
 use serde::Serialize;
-use std::sync::{Arc, Mutex};
-use tauri::Emitter;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, OnceLock};
+use tauri::{Emitter, Manager};
 use tokio::time::Duration;
 
 /// anchor 刷新间隔：4 分钟。
@@ -20,11 +38,27 @@ use tokio::time::Duration;
 /// 比这个再短意义不大（rt 旋转有限），更长则不安全。
 const ANCHOR_REFRESH_INTERVAL_SECS: u64 = 4 * 60;
 
+/// 因用户操作推迟时，最少隔多久再看一眼
+const DEFER_MIN_RETRY_SECS: u64 = 5;
+
+/// 每日报告循环最长睡多久就重新看一眼设置（开关 / 时间改了一分钟内生效）
+const DAILY_REPORT_TICK_SECS: i64 = 60;
+
+/// 发报告前刷新过期额度的并发数
+const DAILY_REPORT_REFRESH_CONCURRENCY: usize = 3;
+
+/// refresh_token 老化检查间隔：指标是天级的，一小时看一次足够
+const TOKEN_AGING_TICK_SECS: u64 = 60 * 60;
+
+/// 自动备份检查间隔：每天最多一份，一小时看一次足够
+const AUTO_BACKUP_TICK_SECS: u64 = 60 * 60;
+
 #[derive(Debug, Clone)]
 struct RefreshTarget {
     id: String,
     name: String,
     refresh_token: String,
+    auth_profile: oauth::AuthProfile,
 }
 
 #[derive(Serialize, Clone)]
@@ -35,7 +69,8 @@ struct RefreshFailedPayload {
 
 fn is_reused_or_revoked_error(reason: &str) -> bool {
     let lower = reason.to_lowercase();
-    lower.contains("refresh_token_reused")
+    oauth::is_refresh_reused(reason)
+        || lower.contains("refresh_token_reused")
         || lower.contains("refresh_token_invalidated")
         || lower.contains("refresh_token_expired")
         || lower.contains("deactivated")
@@ -48,194 +83,356 @@ fn is_logged_out_error(reason: &str) -> bool {
     lower.contains("logged out") || lower.contains("signed in to another account")
 }
 
-/// 启动后台状态同步调度器
-pub fn start(
+/// 当前账号按 `~/.codex/auth.json` 回流同步，登记的其它 Codex 安装各按自己的 auth.json 回流；
+/// 返回账号库是否有变动。定时 tick 和唤醒后的立即重同步（`power`）共用。
+pub(crate) fn sync_current_from_disk(
+    store: &Arc<Mutex<AccountStore>>,
+    app_handle: &tauri::AppHandle,
+) -> bool {
+    let installs_changed = sync_installs_from_disk(store);
+    sync_default_from_disk(store, app_handle) || installs_changed
+}
+
+/// 登记的其它 Codex 安装（见 `codex_installs`）
+fn sync_installs_from_disk(store: &Arc<Mutex<AccountStore>>) -> bool {
+    let mut store = store.lock().unwrap();
+    if !crate::codex_installs::sync_from_disk(&mut store) {
+        return false;
+    }
+    let _ = store.save();
+    println!("[Scheduler] ✅ 其它 Codex 安装的当前账号已回流");
+    true
+}
+
+fn sync_default_from_disk(store: &Arc<Mutex<AccountStore>>, app_handle: &tauri::AppHandle) -> bool {
+    let Ok(official_auth) = AccountStore::read_codex_auth() else {
+        return false;
+    };
+    let mut store = store.lock().unwrap();
+    heal_auth_permissions(store.settings.leave_auth_permissions, app_handle);
+    if let Some(rotated) = crate::service::auto_adopt_current_rotation(&mut store, &official_auth) {
+        let _ = app_handle.emit("token-rotated", &rotated);
+        return true;
+    }
+    let Some(current_id) = store.current.clone() else {
+        return false;
+    };
+    let Some(local_auth) = store.accounts.get(&current_id).map(|a| a.auth_json.clone()) else {
+        return false;
+    };
+
+    if local_auth == official_auth {
+        println!(
+            "[Scheduler] 当前账号 {} 与官方 auth.json 一致。",
+            current_id
+        );
+        report_sync_warning(app_handle, &current_id, None);
+        return false;
+    }
+
+    println!(
+        "[Scheduler] 当前账号 {} 检测到官方 auth.json 变动，按权威源同步。",
+        current_id
+    );
+    match store.try_sync_account_from_auth_json(&current_id, official_auth) {
+        Ok(true) => {
+            let _ = store.save();
+            println!("[Scheduler] ✅ 当前账号反向同步成功");
+            report_sync_warning(app_handle, &current_id, None);
+            true
+        }
+        Ok(false) => false,
+        Err(warning) => {
+            println!("[Scheduler] {}，跳过同步。", warning);
+            report_sync_warning(app_handle, &current_id, Some(warning));
+            false
+        }
+    }
+}
+
+/// `sync-warning` 事件负载；`warning` 为 None 表示之前的拒绝已经消除
+#[derive(Serialize, Clone)]
+struct SyncWarningEvent {
+    account_id: String,
+    warning: Option<Warning>,
+}
+
+/// 后台回流被拒时通知前端。每轮都会再判一次，只在结论变化时发，免得每轮刷一条
+fn report_sync_warning(app_handle: &tauri::AppHandle, account_id: &str, warning: Option<Warning>) {
+    static LAST: OnceLock<Mutex<Option<(String, Warning)>>> = OnceLock::new();
+    let next = warning.clone().map(|w| (account_id.to_string(), w));
+    {
+        let mut last = LAST
+            .get_or_init(Mutex::default)
+            .lock()
+            .unwrap_or_else(|e| e.into_inner());
+        if *last == next {
+            return;
+        }
+        *last = next;
+    }
+    let presentation = app_handle
+        .state::<crate::AppState>()
+        .presentation
+        .is_enabled();
+    let _ = app_handle.emit(
+        "sync-warning",
+        SyncWarningEvent {
+            account_id: account_id.to_string(),
+            warning: warning.map(|w| if presentation { w.masked() } else { w }),
+        },
+    );
+}
+
+/// 读到的 auth.json 权限被放宽了就收紧；同一天反复出现时提醒一次
+fn heal_auth_permissions(leave: bool, app_handle: &tauri::AppHandle) {
+    let drifts = auth_permissions::check_and_heal(
+        &AccountStore::codex_auth_path(),
+        &crate::paths::codex_dir(),
+        leave,
+    );
+    let tracker = auth_permissions::global();
+    if !tracker.record(&drifts, chrono::Local::now()) {
+        return;
+    }
+    let today = tracker.health().today;
+    eprintln!("[Scheduler] ⚠️ auth.json 权限今天已被放宽 {} 次", today);
+    let state = app_handle.state::<crate::AppState>();
+    if !state.presentation.is_enabled() {
+        crate::notify::send(
+            "auth.json 权限反复被放宽",
+            &auth_permissions::notification_body(today),
+        );
+    }
+}
+
+/// 后台状态同步调度循环，由 `SchedulerSupervisor` 拉起；收到停止信号后在睡眠或两个账号之间退出
+pub async fn run(
     store: Arc<Mutex<AccountStore>>,
     app_handle: tauri::AppHandle,
-) -> tauri::async_runtime::JoinHandle<()> {
-    // 使用 Tauri 的 async runtime 而不是直接 tokio::spawn
-    // 因为在 setup() 中调用时 Tokio runtime 可能尚未完全初始化
-    tauri::async_runtime::spawn(async move {
-        println!("✅ 后台调度器已启动");
+    activity: Arc<ForegroundActivity>,
+    pacer: Arc<SyncPacer>,
+    mut shutdown: Shutdown,
+) {
+    println!("✅ 后台调度器已启动");
 
-        loop {
-            let (enabled, interval_minutes, inactive_refresh_days, remote_mode) = {
-                let store = store.lock().unwrap();
-                (
-                    store.settings.background_refresh,
-                    store.settings.refresh_interval_minutes,
-                    store.settings.inactive_refresh_days,
-                    store.settings.remote_mode.clone(),
-                )
-            };
+    loop {
+        let (enabled, interval_minutes, inactive_refresh_days, remote_mode, quiet_secs) = {
+            let store = store.lock().unwrap();
+            (
+                store.settings.background_refresh,
+                store.settings.refresh_interval_minutes,
+                store.settings.inactive_refresh_days,
+                store.settings.remote_mode.clone(),
+                store.settings.scheduler_quiet_secs,
+            )
+        };
 
-            if !enabled {
-                tokio::time::sleep(Duration::from_secs(60)).await;
-                continue;
+        if !enabled {
+            if !shutdown.sleep(Duration::from_secs(60)).await {
+                break;
             }
+            continue;
+        }
 
-            // client 模式下：保活交给 Server，本机只做 auth.json 反向同步，不独占刷新
-            if remote_mode == "client" {
-                println!("[Scheduler] client 模式：跳过本机保活，Server 负责刷新");
-                tokio::time::sleep(Duration::from_secs(60)).await;
-                continue;
+        // client 模式下：保活交给 Server，本机只做 auth.json 反向同步，不独占刷新
+        if remote_mode == "client" {
+            println!("[Scheduler] client 模式：跳过本机保活，Server 负责刷新");
+            if !shutdown.sleep(Duration::from_secs(60)).await {
+                break;
             }
+            continue;
+        }
 
-            println!("[Scheduler] 开始后台同步检查...");
-
-            let interval_minutes = if interval_minutes == 0 {
-                30
-            } else {
-                interval_minutes
-            };
+        // 主窗口开着且用户刚操作过：推迟本轮，等安静下来再跑
+        let window_visible = app_handle
+            .get_webview_window("main")
+            .and_then(|w| w.is_visible().ok())
+            .unwrap_or(false);
+        let wait = if window_visible {
+            activity.defer_for(Duration::from_secs(quiet_secs))
+        } else {
+            activity.defer_for(Duration::ZERO)
+        };
+        if let Some(wait) = wait {
+            let wait = wait.max(Duration::from_secs(DEFER_MIN_RETRY_SECS));
+            println!(
+                "[Scheduler] 用户正在操作界面，推迟本轮同步 {} 秒",
+                wait.as_secs()
+            );
+            if !shutdown.sleep(wait).await {
+                break;
+            }
+            continue;
+        }
 
-            let mut store_changed = false;
-            let mut has_failure_event = false;
-
-            // 1) 同步当前账号（权威源：~/.codex/auth.json）
-            if let Ok(official_auth) = AccountStore::read_codex_auth() {
-                let mut store = store.lock().unwrap();
-                if let Some(current_id) = store.current.clone() {
-                    let local_auth = store.accounts.get(&current_id).map(|a| a.auth_json.clone());
-
-                    if let Some(local_auth) = local_auth {
-                        if AccountStore::auth_identity_matches(&local_auth, &official_auth) {
-                            if local_auth != official_auth {
-                                println!(
-                                    "[Scheduler] 当前账号 {} 检测到官方 auth.json 变动，按权威源同步。",
-                                    current_id
-                                );
-                                if store.sync_account_from_auth_json(&current_id, official_auth) {
-                                    let _ = store.save();
-                                    store_changed = true;
-                                    println!("[Scheduler] ✅ 当前账号反向同步成功");
-                                }
-                            } else {
-                                println!(
-                                    "[Scheduler] 当前账号 {} 与官方 auth.json 一致。",
-                                    current_id
-                                );
-                            }
-                        } else {
-                            println!(
-                                "[Scheduler] 当前账号 {} 与官方 auth.json 身份不匹配，跳过同步。",
-                                current_id
-                            );
-                        }
-                    }
-                }
+        // 调度间隔到点和唤醒重同步可能前后脚触发：离上一轮太近就等满最小间距
+        if let Some(wait) = pacer.try_begin(interval_minutes) {
+            println!(
+                "[Scheduler] 距上一轮同步不足 {} 秒，{} 秒后再跑",
+                crate::pacing::MIN_CYCLE_SPACING.as_secs(),
+                wait.as_secs()
+            );
+            if !shutdown.sleep(wait).await {
+                break;
             }
+            continue;
+        }
 
-            // 2) 收集应由 Switcher 独占保活的非活跃账号
-            let targets: Vec<RefreshTarget> = {
-                let store = store.lock().unwrap();
-                let current = store.current.as_deref();
-                store
-                    .accounts
-                    .values()
-                    .filter(|account| current != Some(account.id.as_str()))
-                    .filter(|account| {
-                        AccountStore::should_refresh_inactive_account(
-                            account,
-                            inactive_refresh_days,
-                        )
-                    })
-                    .filter_map(|account| {
-                        let rt = account
-                            .refresh_token
-                            .clone()
-                            .or_else(|| AccountStore::extract_refresh_token(&account.auth_json))?;
-                        Some(RefreshTarget {
-                            id: account.id.clone(),
-                            name: account.name.clone(),
-                            refresh_token: rt,
-                        })
+        println!("[Scheduler] 开始后台同步检查...");
+
+        // 上一轮之后用户动过的账号，这一轮不碰（interval 已在保存 / 加载设置时夹进合法范围）
+        let touched_ttl = Duration::from_secs(u64::from(interval_minutes) * 60);
+
+        let mut store_changed = false;
+        let mut has_failure_event = false;
+
+        // 1) 同步当前账号（权威源：~/.codex/auth.json）
+        if sync_current_from_disk(&store, &app_handle) {
+            store_changed = true;
+        }
+
+        // 2) 收集应由 Switcher 独占保活的非活跃账号
+        let targets: Vec<RefreshTarget> = {
+            let store = store.lock().unwrap();
+            let current = store.current.as_deref();
+            store
+                .accounts
+                .values()
+                .filter(|account| current != Some(account.id.as_str()))
+                .filter(|account| {
+                    let touched = activity.recently_touched(&account.id, touched_ttl);
+                    if touched {
+                        println!(
+                            "[Scheduler] 账号 {} 刚被用户操作过，本轮跳过",
+                            log_identity::id(&account.name)
+                        );
+                    }
+                    !touched
+                })
+                .filter(|account| {
+                    AccountStore::should_refresh_inactive_account(account, inactive_refresh_days)
+                })
+                .filter_map(|account| {
+                    let rt = account
+                        .refresh_token
+                        .clone()
+                        .or_else(|| AccountStore::extract_refresh_token(&account.auth_json))?;
+                    Some(RefreshTarget {
+                        id: account.id.clone(),
+                        name: account.name.clone(),
+                        refresh_token: rt,
+                        auth_profile: store.auth_profile_of(account),
                     })
-                    .collect()
-            };
+                })
+                .collect()
+        };
 
-            // 3) 对非活跃账号执行独占保活刷新
-            for target in targets {
-                println!("[Scheduler] 非活跃账号 {} 尝试保活刷新", target.name);
+        // 3) 对非活跃账号执行独占保活刷新
+        for target in targets {
+            if shutdown.is_requested() {
+                break;
+            }
+            println!(
+                "[Scheduler] 非活跃账号 {} 尝试保活刷新",
+                log_identity::id(&target.name)
+            );
 
-                match oauth::refresh_access_token(&target.refresh_token).await {
-                    Ok(tokens) => {
-                        let mut store = store.lock().unwrap();
-                        if store.current.as_deref() == Some(target.id.as_str()) {
-                            // 账号已变为当前，交给官方路径维护
+            match oauth::refresh_access_token(&target.auth_profile, &target.refresh_token).await {
+                Ok(tokens) => {
+                    let mut store = store.lock().unwrap();
+                    if store.current.as_deref() == Some(target.id.as_str()) {
+                        // 账号已变为当前，交给官方路径维护
+                        continue;
+                    }
+                    if let Some(account) = store.accounts.get_mut(&target.id) {
+                        if !account.keepalive.inactive_refresh_enabled {
                             continue;
                         }
+                        AccountStore::apply_refreshed_tokens(
+                            account,
+                            tokens.access_token,
+                            tokens.refresh_token,
+                            tokens.id_token,
+                            tokens.expires_in,
+                        );
+                    }
+                    store.mark_keepalive_attempt_success(&target.id);
+                    let _ = store.save();
+                    store_changed = true;
+                    println!(
+                        "[Scheduler] ✅ 非活跃账号 {} 保活刷新成功",
+                        log_identity::id(&target.name)
+                    );
+
+                    // 记录后台保活系统日志
+                    use tauri::Manager;
+                    if let Some(logger) =
+                        app_handle.try_state::<Arc<crate::switch_log::SwitchLogger>>()
+                    {
+                        logger.inner().log_switch(
+                            None,
+                            target.name.clone(),
+                            crate::switch_log::SwitchReason::BackgroundKeepalive,
+                            None,
+                            None,
+                        );
+                    }
+                }
+                Err(err) => {
+                    let reason = err;
+                    let mut store = store.lock().unwrap();
+                    store.mark_keepalive_attempt_failed(&target.id, reason.clone());
+                    if let Some(notice) =
+                        crate::refresh_ban::record_failure(&mut store, &target.id, &reason)
+                    {
+                        let _ = app_handle.emit(crate::refresh_ban::EVENT, &notice);
+                    }
+                    if is_reused_or_revoked_error(&reason) || is_logged_out_error(&reason) {
+                        // 风险保护：检测到 reused/revoked 后，自动停用该账号的非活跃保活，避免重复消耗。
+                        let _ = store.set_inactive_refresh_enabled(&target.id, false);
                         if let Some(account) = store.accounts.get_mut(&target.id) {
-                            if !account.keepalive.inactive_refresh_enabled {
-                                continue;
+                            if is_logged_out_error(&reason) {
+                                account.is_logged_out = true;
+                            } else {
+                                account.is_token_invalid = true;
                             }
-                            AccountStore::apply_refreshed_tokens(
-                                account,
-                                tokens.access_token,
-                                tokens.refresh_token,
-                                tokens.id_token,
-                                tokens.expires_in,
-                            );
-                        }
-                        store.mark_keepalive_attempt_success(&target.id);
-                        let _ = store.save();
-                        store_changed = true;
-                        println!("[Scheduler] ✅ 非活跃账号 {} 保活刷新成功", target.name);
-
-                        // 记录后台保活系统日志
-                        use tauri::Manager;
-                        if let Some(logger) =
-                            app_handle.try_state::<Arc<crate::switch_log::SwitchLogger>>()
-                        {
-                            logger.inner().log_switch(
-                                None,
-                                target.name.clone(),
-                                crate::switch_log::SwitchReason::BackgroundKeepalive,
-                                None,
-                                None,
-                            );
                         }
                     }
-                    Err(err) => {
-                        let reason = err;
-                        let mut store = store.lock().unwrap();
-                        store.mark_keepalive_attempt_failed(&target.id, reason.clone());
-                        if is_reused_or_revoked_error(&reason) || is_logged_out_error(&reason) {
-                            // 风险保护：检测到 reused/revoked 后，自动停用该账号的非活跃保活，避免重复消耗。
-                            let _ = store.set_inactive_refresh_enabled(&target.id, false);
-                            if let Some(account) = store.accounts.get_mut(&target.id) {
-                                if is_logged_out_error(&reason) {
-                                    account.is_logged_out = true;
-                                } else {
-                                    account.is_token_invalid = true;
-                                }
-                            }
-                        }
-                        let _ = store.save();
-                        has_failure_event = true;
-                        println!(
-                            "[Scheduler] ❌ 非活跃账号 {} 保活刷新失败: {}",
-                            target.name, reason
-                        );
+                    let _ = store.save();
+                    has_failure_event = true;
+                    println!(
+                        "[Scheduler] ❌ 非活跃账号 {} 保活刷新失败: {}",
+                        log_identity::id(&target.name),
+                        reason
+                    );
 
-                        let _ = app_handle.emit(
-                            "token-refresh-failed",
-                            RefreshFailedPayload {
-                                account_name: target.name,
-                                reason,
-                            },
-                        );
-                    }
+                    let _ = app_handle.emit(
+                        "token-refresh-failed",
+                        RefreshFailedPayload {
+                            account_name: target.name,
+                            reason,
+                        },
+                    );
                 }
             }
+        }
 
-            if store_changed || has_failure_event {
-                let _ = app_handle.emit("accounts-updated", ());
-            }
+        if store_changed || has_failure_event {
+            let _ = app_handle.emit("accounts-updated", ());
+        }
 
-            tokio::time::sleep(Duration::from_secs(u64::from(interval_minutes) * 60)).await;
+        let interval = Duration::from_secs(u64::from(interval_minutes) * 60);
+        // 睡满一个间隔（系统唤醒会提前叫醒）
+        if shutdown
+            .until(crate::power::sleep_or_resume(interval))
+            .await
+            .is_none()
+        {
+            break;
         }
-    })
+    }
+    println!("[Scheduler] 后台调度器已停止");
 }
 
 /// 启动手机锚专用刷新循环（v0.7+）。
@@ -261,21 +458,32 @@ pub fn start_anchor_refresh(
             tokio::time::sleep(Duration::from_secs(ANCHOR_REFRESH_INTERVAL_SECS)).await;
 
             // 1) 取 anchor 信息 + 模式
-            let (anchor_id, anchor_name, anchor_rt, remote_mode) = {
+            let (anchor_id, anchor_name, anchor_rt, anchor_profile, remote_mode) = {
                 let store = match store.lock() {
                     Ok(g) => g,
                     Err(_) => continue,
                 };
                 let mode = store.settings.remote_mode.clone();
                 match store.session_anchor() {
+                    Some(acc) if acc.keepalive.refresh_ban.is_some() => {
+                        // 刷新禁令：重新登录前不再保活，免得越刷越糟
+                        continue;
+                    }
                     Some(acc) => {
                         let rt = acc
                             .refresh_token
                             .clone()
                             .or_else(|| AccountStore::extract_refresh_token(&acc.auth_json));
-                        (Some(acc.id.clone()), acc.name.clone(), rt, mode)
+                        let profile = store.auth_profile_of(acc);
+                        (Some(acc.id.clone()), acc.name.clone(), rt, profile, mode)
                     }
-                    None => (None, String::new(), None, mode),
+                    None => (
+                        None,
+                        String::new(),
+                        None,
+                        oauth::AuthProfile::builtin(),
+                        mode,
+                    ),
                 }
             };
 
@@ -292,13 +500,13 @@ pub fn start_anchor_refresh(
             let Some(rt) = anchor_rt else {
                 eprintln!(
                     "[AnchorRefresh] anchor 账号 {} 缺 refresh_token，无法保活（需要重新登录）",
-                    anchor_name
+                    log_identity::id(&anchor_name)
                 );
                 continue;
             };
 
             // 2) 刷新 token
-            match oauth::refresh_access_token(&rt).await {
+            match oauth::refresh_access_token(&anchor_profile, &rt).await {
                 Ok(tokens) => {
                     // 3a) 写回 store
                     let auth_value = {
@@ -313,7 +521,7 @@ pub fn start_anchor_refresh(
                         if !account.is_session_anchor {
                             println!(
                                 "[AnchorRefresh] {} 已不是 anchor（用户中途取消），跳过写盘",
-                                anchor_name
+                                log_identity::id(&anchor_name)
                             );
                             continue;
                         }
@@ -330,14 +538,16 @@ pub fn start_anchor_refresh(
                     };
 
                     // 3b) 写盘（extended_expiry 防 Codex.app 自刷）
-                    if let Err(e) =
-                        AccountStore::write_codex_auth_extended_expiry(&auth_value)
-                    {
+                    if let Err(e) = AccountStore::write_codex_auth_extended_expiry(
+                        &auth_value,
+                        AuthWriteOp::Refresh,
+                        Some(&anchor_id),
+                    ) {
                         eprintln!("[AnchorRefresh] 写 ~/.codex/auth.json 失败: {}", e);
                     } else {
                         println!(
                             "[AnchorRefresh] ✅ anchor {} 保活成功 + 已落盘",
-                            anchor_name
+                            log_identity::id(&anchor_name)
                         );
                     }
                     crate::proxy::invalidate_remote_token_cache();
@@ -345,13 +555,19 @@ pub fn start_anchor_refresh(
                 Err(reason) => {
                     eprintln!(
                         "[AnchorRefresh] ❌ anchor {} 保活失败: {}",
-                        anchor_name, reason
+                        log_identity::id(&anchor_name),
+                        reason
                     );
                     // rt 失效是致命情况：手机 bridge 会跟着断。标记账号 token_invalid，
                     // 让 UI 弹出"重新登录 anchor"提示
                     if is_reused_or_revoked_error(&reason) || is_logged_out_error(&reason)
                     {
                         if let Ok(mut store) = store.lock() {
+                            if let Some(notice) =
+                                crate::refresh_ban::record_failure(&mut store, &anchor_id, &reason)
+                            {
+                                let _ = app_handle.emit(crate::refresh_ban::EVENT, &notice);
+                            }
                             if let Some(account) = store.accounts.get_mut(&anchor_id) {
                                 if is_logged_out_error(&reason) {
                                     account.is_logged_out = true;
@@ -374,3 +590,236 @@ pub fn start_anchor_refresh(
         }
     })
 }
+
+/// 启动每日额度报告循环（未开启时空转）
+pub fn start_daily_report(
+    store: Arc<Mutex<AccountStore>>,
+    app_handle: tauri::AppHandle,
+) -> tauri::async_runtime::JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        println!("✅ 每日额度报告循环已启动");
+        loop {
+            let schedule = store.lock().ok().and_then(|s| {
+                if !s.settings.daily_report_enabled {
+                    return None;
+                }
+                let at = daily_report::parse_report_time(&s.settings.daily_report_time).ok()?;
+                Some((at, s.last_report_sent_at))
+            });
+
+            let mut wait_secs = DAILY_REPORT_TICK_SECS;
+            if let Some((at, last_sent)) = schedule {
+                let now = chrono::Local::now();
+                let grace = chrono::Duration::minutes(daily_report::CATCH_UP_GRACE_MINUTES);
+                if daily_report::should_send(&now, at, last_sent, grace) {
+                    send_daily_report(&store, &app_handle).await;
+                }
+                let now = chrono::Local::now();
+                let until_next = (daily_report::next_fire_time(&now, at) - now).num_seconds();
+                wait_secs = until_next.clamp(1, DAILY_REPORT_TICK_SECS);
+            }
+            crate::power::sleep_or_resume(Duration::from_secs(wait_secs as u64)).await;
+        }
+    })
+}
+
+/// 启动 refresh_token 老化检查循环（阈值为 0 时空转）。
+///
+/// 不依赖 `background_refresh`：关着后台刷新的用户恰恰最容易让备用号闲置过期。
+pub fn start_token_aging(
+    store: Arc<Mutex<AccountStore>>,
+    app_handle: tauri::AppHandle,
+) -> tauri::async_runtime::JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        println!("✅ refresh_token 老化检查循环已启动");
+        let mut notified = HashSet::new();
+        loop {
+            check_token_aging(&store, &app_handle, &mut notified).await;
+            crate::power::sleep_or_resume(Duration::from_secs(TOKEN_AGING_TICK_SECS)).await;
+        }
+    })
+}
+
+/// 启动每日自动备份循环（`auto_backup` 关着时空转）
+pub fn start_auto_backup(
+    store: Arc<Mutex<AccountStore>>,
+    app_handle: tauri::AppHandle,
+) -> tauri::async_runtime::JoinHandle<()> {
+    tauri::async_runtime::spawn(async move {
+        println!("✅ 账号库自动备份循环已启动");
+        loop {
+            run_auto_backup(&store, &app_handle);
+            crate::power::sleep_or_resume(Duration::from_secs(AUTO_BACKUP_TICK_SECS)).await;
+        }
+    })
+}
+
+/// 到点就写一份定时备份、清掉过期的，写成了发 `backup-created`
+fn run_auto_backup(store: &Arc<Mutex<AccountStore>>, app_handle: &tauri::AppHandle) {
+    let dir = store_backups::backups_dir();
+    let now = chrono::Utc::now();
+    let backup = {
+        let Ok(store) = store.lock() else { return };
+        // 没加载成功的库不备份：那只是个空壳
+        if !store.settings.auto_backup
+            || store.load_error().is_some()
+            || !store_backups::scheduled_due(&dir, now)
+        {
+            return;
+        }
+        // 持锁写：和 save 互斥，拿到的是完整的库；Keychain 模式下 token 不进备份文件
+        let backup = store_backups::write(&dir, BackupKind::Scheduled, &store, now);
+        let pruned = store_backups::prune_older_than(
+            &dir,
+            BackupKind::Scheduled,
+            store.settings.backup_retention_days,
+            now,
+        );
+        if pruned > 0 {
+            println!("[AutoBackup] 清掉 {} 份过期的自动备份", pruned);
+        }
+        backup
+    };
+    match backup {
+        Ok(backup) => {
+            println!("[AutoBackup] ✅ 已备份账号库到 {}", backup.path);
+            let _ = app_handle.emit("backup-created", &backup);
+        }
+        Err(e) => eprintln!("[AutoBackup] ❌ 备份账号库失败: {}", e),
+    }
+}
+
+/// 先对允许自动保活的账号刷一次，剩下仍老化且新跨过阈值的发事件 + 通知
+async fn check_token_aging(
+    store: &Arc<Mutex<AccountStore>>,
+    app_handle: &tauri::AppHandle,
+    notified: &mut HashSet<String>,
+) {
+    if crate::clock::is_skewed() {
+        println!("[TokenAging] 系统时间异常，跳过本轮闲置检查");
+        return;
+    }
+    let collect = || {
+        store
+            .lock()
+            .map(|s| token_aging::collect(&s, crate::clock::now()))
+            .unwrap_or_default()
+    };
+    let state = app_handle.state::<crate::AppState>();
+
+    let mut aging = collect();
+    let mut refreshed = false;
+    for token in aging.iter().filter(|t| t.keepalive == KeepaliveOffer::Auto) {
+        match token_aging::keepalive(store, &state.refresh_locks, &token.id).await {
+            Ok(()) => {
+                refreshed = true;
+                println!(
+                    "[TokenAging] ✅ {} 闲置 {} 天，已自动保活",
+                    log_identity::id(&token.name),
+                    token.idle_days
+                );
+            }
+            Err(e) => eprintln!(
+                "[TokenAging] ❌ {} 自动保活失败: {}",
+                log_identity::id(&token.name),
+                e
+            ),
+        }
+    }
+    if refreshed {
+        aging = collect();
+        let _ = app_handle.emit("accounts-updated", ());
+    }
+
+    let fresh = token_aging::newly_aging(notified, &aging);
+    if fresh.is_empty() {
+        return;
+    }
+    println!("[TokenAging] {} 个账号 refresh_token 闲置过久", fresh.len());
+    let _ = app_handle.emit("refresh-token-aging", &fresh);
+    if !state.presentation.is_enabled() {
+        crate::notify::send(
+            "refresh_token 闲置预警",
+            &token_aging::notification_body(&fresh),
+        );
+    }
+}
+
+/// 刷新过期额度 → 拼摘要 → 发通知 → 记录发送时间
+async fn send_daily_report(store: &Arc<Mutex<AccountStore>>, app_handle: &tauri::AppHandle) {
+    let state = app_handle.state::<crate::AppState>();
+    // 演示模式不弹通知；不记发送时间，宽限期内退出演示还能补发
+    if state.presentation.is_enabled() {
+        return;
+    }
+
+    let (is_client, stale_ids) = {
+        let Ok(s) = store.lock() else {
+            return;
+        };
+        let stale_before =
+            crate::clock::now() - chrono::Duration::minutes(daily_report::STALE_QUOTA_MINUTES);
+        let stale_ids: Vec<String> = s
+            .accounts
+            .values()
+            .filter(|a| {
+                !a.is_relay()
+                    && !a.is_api_key()
+                    && !a.archived
+                    && !a.is_banned
+                    && !a.is_token_invalid
+                    && !a.is_logged_out
+            })
+            .filter(|a| {
+                a.cached_quota
+                    .as_ref()
+                    .is_none_or(|q| q.updated_at < stale_before)
+            })
+            .map(|a| a.id.clone())
+            .collect();
+        (s.settings.remote_mode == "client", stale_ids)
+    };
+
+    // client 模式额度由 Server 同步过来，本机不直连上游；系统时间异常时判断不了新旧，不刷
+    if crate::clock::is_skewed() {
+        println!("[DailyReport] 系统时间异常，跳过刷新过期额度");
+    } else if !is_client && !stale_ids.is_empty() {
+        println!("[DailyReport] 刷新 {} 个过期额度", stale_ids.len());
+        let service = &state.account_service(app_handle);
+        futures_util::stream::iter(stale_ids)
+            .map(|id| async move {
+                if let Err(e) = service.refresh_quota(&id, QuotaSource::Scheduler).await {
+                    println!("[DailyReport] 刷新 {} 额度失败: {}", id, e);
+                }
+            })
+            .buffer_unordered(DAILY_REPORT_REFRESH_CONCURRENCY)
+            .collect::<Vec<()>>()
+            .await;
+    }
+
+    let text = {
+        let Ok(mut s) = store.lock() else {
+            return;
+        };
+        let entries: Vec<daily_report::ReportEntry> = s
+            .list_accounts()
+            .into_iter()
+            .filter(|a| !a.is_relay() && !a.archived)
+            .map(|a| daily_report::ReportEntry {
+                name: a.name.clone(),
+                five_hour_left: a.cached_quota.as_ref().map(|q| q.five_hour_left),
+                weekly_left: a.cached_quota.as_ref().map(|q| q.weekly_left),
+            })
+            .collect();
+        s.last_report_sent_at = Some(chrono::Utc::now());
+        if let Err(e) = s.save() {
+            eprintln!("[DailyReport] 记录发送时间失败: {}", e);
+        }
+        daily_report::format_report(&entries, daily_report::MAX_REPORT_CHARS)
+    };
+
+    println!("[DailyReport] 发送每日额度报告: {}", text);
+    crate::notify::send("每日额度报告", &text);
+    let _ = app_handle.emit("accounts-updated", ());
+    crate::tray::update_tray_menu(app_handle);
+}
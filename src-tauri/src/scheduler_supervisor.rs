@@ -0,0 +1,379 @@
+//! 后台调度器的生命周期监管
+//!
+//! 以前 `AppState` 里只放一个 `Option<JoinHandle>`，setup 和 `update_settings` 各自判断
+//! `is_none()` 再 spawn；唤醒重同步、看门狗重拉这类触发点一多，就容易出现两个循环同时写账号库。
+//! 现在统一经 [`SchedulerSupervisor`]：
+//! - [`SchedulerSupervisor::start`] 幂等：已有存活的循环（看任务是否真的还在跑，而不是槽位非空）
+//!   返回 [`StartOutcome::AlreadyRunning`]
+//! - [`SchedulerSupervisor::stop`] 先发协作式停止信号（循环在睡眠 / 轮次间隙检查 [`Shutdown`]），
+//!   超时还没退出再强制中止
+//! - [`SchedulerSupervisor::restart`] = stop + start
+//! - [`SchedulerSupervisor::status`] 报告运行中 / 已停止 / 崩溃（panic 或没收到停止信号就退出了），
+//!   供诊断包的运行状态使用
+//!
+//! 监管器不依赖 Tauri：任务经构造时传入的 spawn 函数派发，测试里直接用 tokio。
+
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use futures_util::future::{AbortHandle, Abortable, BoxFuture};
+use futures_util::FutureExt;
+use serde::Serialize;
+use tokio::sync::watch;
+
+/// 停止时等循环自己退出的最长时间，超时后强制中止
+pub const STOP_GRACE: Duration = Duration::from_secs(10);
+
+/// 调度器当前状态
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+pub enum SupervisorStatus {
+    Running { since: DateTime<Utc> },
+    Stopped,
+    Crashed { at: DateTime<Utc>, error: String },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StartOutcome {
+    Started,
+    AlreadyRunning,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StopOutcome {
+    /// 循环收到信号后自己退出了
+    Graceful,
+    /// 宽限期内没退出，已强制中止
+    Aborted,
+    NotRunning,
+}
+
+/// 循环体拿到的停止信号
+pub struct Shutdown(watch::Receiver<bool>);
+
+impl Shutdown {
+    pub fn is_requested(&self) -> bool {
+        *self.0.borrow()
+    }
+
+    /// 等到收到停止信号（监管器已丢弃时也算）
+    pub async fn requested(&mut self) {
+        let _ = self.0.wait_for(|stop| *stop).await;
+    }
+
+    /// 睡 `dur`；中途收到停止信号返回 false
+    pub async fn sleep(&mut self, dur: Duration) -> bool {
+        self.until(tokio::time::sleep(dur)).await.is_some()
+    }
+
+    /// 跑 `fut`，期间收到停止信号就放弃并返回 None
+    pub async fn until<F: Future>(&mut self, fut: F) -> Option<F::Output> {
+        if self.is_requested() {
+            return None;
+        }
+        tokio::select! {
+            output = fut => Some(output),
+            _ = self.requested() => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum ExitKind {
+    Returned,
+    Panicked(String),
+    Aborted,
+}
+
+#[derive(Debug, Clone)]
+struct Exit {
+    at: DateTime<Utc>,
+    kind: ExitKind,
+}
+
+struct Running {
+    since: DateTime<Utc>,
+    stop_tx: watch::Sender<bool>,
+    abort: AbortHandle,
+    exit_rx: watch::Receiver<Option<Exit>>,
+}
+
+impl Running {
+    fn exit(&self) -> Option<Exit> {
+        self.exit_rx.borrow().clone()
+    }
+
+    fn status(&self) -> SupervisorStatus {
+        let Some(exit) = self.exit() else {
+            return SupervisorStatus::Running { since: self.since };
+        };
+        let stop_requested = *self.stop_tx.borrow();
+        match exit.kind {
+            ExitKind::Returned if !stop_requested => SupervisorStatus::Crashed {
+                at: exit.at,
+                error: "调度循环意外退出".to_string(),
+            },
+            ExitKind::Panicked(error) => SupervisorStatus::Crashed { at: exit.at, error },
+            ExitKind::Returned | ExitKind::Aborted => SupervisorStatus::Stopped,
+        }
+    }
+}
+
+type Spawner = Box<dyn Fn(BoxFuture<'static, ()>) + Send + Sync>;
+
+pub struct SchedulerSupervisor {
+    spawn: Spawner,
+    running: Mutex<Option<Running>>,
+}
+
+impl SchedulerSupervisor {
+    /// `spawn` 负责把任务派发到运行时（正式环境用 `tauri::async_runtime::spawn`）
+    pub fn new(spawn: impl Fn(BoxFuture<'static, ()>) + Send + Sync + 'static) -> Self {
+        Self {
+            spawn: Box::new(spawn),
+            running: Mutex::new(None),
+        }
+    }
+
+    /// 没有存活的循环时用 `body` 起一个
+    pub fn start<F, Fut>(&self, body: F) -> StartOutcome
+    where
+        F: FnOnce(Shutdown) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let Ok(mut slot) = self.running.lock() else {
+            return StartOutcome::AlreadyRunning;
+        };
+        if slot.as_ref().is_some_and(|r| r.exit().is_none()) {
+            return StartOutcome::AlreadyRunning;
+        }
+
+        let (stop_tx, stop_rx) = watch::channel(false);
+        let (exit_tx, exit_rx) = watch::channel(None);
+        let (abort, registration) = AbortHandle::new_pair();
+        let body = AssertUnwindSafe(body(Shutdown(stop_rx))).catch_unwind();
+        let task = async move {
+            let kind = match Abortable::new(body, registration).await {
+                Ok(Ok(())) => ExitKind::Returned,
+                Ok(Err(panic)) => ExitKind::Panicked(panic_message(panic.as_ref())),
+                Err(_) => ExitKind::Aborted,
+            };
+            if let ExitKind::Panicked(message) = &kind {
+                eprintln!("[Scheduler] 调度循环崩溃: {}", message);
+            }
+            let _ = exit_tx.send(Some(Exit {
+                at: Utc::now(),
+                kind,
+            }));
+        };
+        *slot = Some(Running {
+            since: Utc::now(),
+            stop_tx,
+            abort,
+            exit_rx,
+        });
+        (self.spawn)(Box::pin(task));
+        StartOutcome::Started
+    }
+
+    /// 发停止信号并等循环退出；超过 `grace` 还没退出就强制中止
+    pub async fn stop(&self, grace: Duration) -> StopOutcome {
+        let (abort, mut exit_rx) = {
+            let Ok(slot) = self.running.lock() else {
+                return StopOutcome::NotRunning;
+            };
+            match slot.as_ref() {
+                Some(running) if running.exit().is_none() => {
+                    let _ = running.stop_tx.send(true);
+                    (running.abort.clone(), running.exit_rx.clone())
+                }
+                _ => {
+                    drop(slot);
+                    self.clear_finished();
+                    return StopOutcome::NotRunning;
+                }
+            }
+        };
+
+        let graceful = tokio::time::timeout(grace, exit_rx.wait_for(Option::is_some))
+            .await
+            .is_ok();
+        let outcome = if graceful {
+            StopOutcome::Graceful
+        } else {
+            eprintln!("[Scheduler] {} 秒内没有退出，强制中止", grace.as_secs());
+            abort.abort();
+            let _ = exit_rx.wait_for(Option::is_some).await;
+            StopOutcome::Aborted
+        };
+        self.clear_finished();
+        outcome
+    }
+
+    pub async fn restart<F, Fut>(&self, grace: Duration, body: F) -> StartOutcome
+    where
+        F: FnOnce(Shutdown) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.stop(grace).await;
+        self.start(body)
+    }
+
+    pub fn status(&self) -> SupervisorStatus {
+        self.running
+            .lock()
+            .ok()
+            .and_then(|slot| slot.as_ref().map(Running::status))
+            .unwrap_or(SupervisorStatus::Stopped)
+    }
+
+    pub fn is_running(&self) -> bool {
+        matches!(self.status(), SupervisorStatus::Running { .. })
+    }
+
+    /// 已退出且是正常停止的任务从槽位里清掉；崩溃的留着，状态里继续可见
+    fn clear_finished(&self) {
+        if let Ok(mut slot) = self.running.lock() {
+            if slot
+                .as_ref()
+                .is_some_and(|r| r.status() == SupervisorStatus::Stopped)
+            {
+                *slot = None;
+            }
+        }
+    }
+}
+
+fn panic_message(panic: &(dyn std::any::Any + Send)) -> String {
+    panic
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "未知错误".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn supervisor() -> SchedulerSupervisor {
+        SchedulerSupervisor::new(|task| {
+            tokio::spawn(task);
+        })
+    }
+
+    /// 每 10ms 数一次，收到停止信号就退出
+    fn counting_loop(ticks: Arc<AtomicUsize>) -> impl FnOnce(Shutdown) -> BoxFuture<'static, ()> {
+        move |mut shutdown| {
+            Box::pin(async move {
+                while shutdown.sleep(Duration::from_millis(10)).await {
+                    ticks.fetch_add(1, Ordering::SeqCst);
+                }
+            })
+        }
+    }
+
+    async fn wait_for_status(sup: &SchedulerSupervisor, pred: impl Fn(&SupervisorStatus) -> bool) {
+        for _ in 0..100 {
+            if pred(&sup.status()) {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("状态没有变化: {:?}", sup.status());
+    }
+
+    #[tokio::test]
+    async fn start_is_idempotent_while_the_loop_is_alive() {
+        let sup = supervisor();
+        let ticks = Arc::new(AtomicUsize::new(0));
+        assert_eq!(
+            sup.start(counting_loop(ticks.clone())),
+            StartOutcome::Started
+        );
+        let second = Arc::new(AtomicUsize::new(0));
+        assert_eq!(
+            sup.start(counting_loop(second.clone())),
+            StartOutcome::AlreadyRunning
+        );
+        assert!(sup.is_running());
+
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert!(ticks.load(Ordering::SeqCst) > 0);
+        assert_eq!(second.load(Ordering::SeqCst), 0, "第二个循环不该被拉起");
+        assert_eq!(sup.stop(STOP_GRACE).await, StopOutcome::Graceful);
+    }
+
+    #[tokio::test]
+    async fn stop_is_graceful_and_allows_a_fresh_start() {
+        let sup = supervisor();
+        let ticks = Arc::new(AtomicUsize::new(0));
+        sup.start(counting_loop(ticks.clone()));
+        assert_eq!(
+            sup.stop(Duration::from_secs(1)).await,
+            StopOutcome::Graceful
+        );
+        assert_eq!(sup.status(), SupervisorStatus::Stopped);
+        assert_eq!(
+            sup.stop(Duration::from_secs(1)).await,
+            StopOutcome::NotRunning
+        );
+
+        let stopped_at = ticks.load(Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert_eq!(ticks.load(Ordering::SeqCst), stopped_at, "停止后不再跑");
+
+        assert_eq!(
+            sup.restart(Duration::from_secs(1), counting_loop(ticks.clone()))
+                .await,
+            StartOutcome::Started
+        );
+        assert!(sup.is_running());
+    }
+
+    #[tokio::test]
+    async fn stop_aborts_a_loop_that_ignores_the_signal_after_the_grace_period() {
+        let sup = supervisor();
+        sup.start(|_shutdown| async {
+            std::future::pending::<()>().await;
+        });
+        let grace = Duration::from_millis(50);
+        let began = std::time::Instant::now();
+        assert_eq!(sup.stop(grace).await, StopOutcome::Aborted);
+        let took = began.elapsed();
+        assert!(took >= grace, "宽限期内不该中止: {:?}", took);
+        assert!(
+            took < Duration::from_secs(2),
+            "中止后应立即返回: {:?}",
+            took
+        );
+        assert_eq!(sup.status(), SupervisorStatus::Stopped);
+    }
+
+    #[tokio::test]
+    async fn panics_and_unexpected_returns_are_reported_as_crashes() {
+        let sup = supervisor();
+        sup.start(|_shutdown| async {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+            panic!("store 锁中毒");
+        });
+        wait_for_status(&sup, |s| matches!(s, SupervisorStatus::Crashed { .. })).await;
+        match sup.status() {
+            SupervisorStatus::Crashed { error, .. } => assert!(error.contains("store 锁中毒")),
+            other => panic!("{:?}", other),
+        }
+
+        // 崩溃的循环不算存活，可以重新拉起
+        let ticks = Arc::new(AtomicUsize::new(0));
+        assert_eq!(sup.start(counting_loop(ticks)), StartOutcome::Started);
+        sup.stop(STOP_GRACE).await;
+
+        sup.start(|_shutdown| async {});
+        wait_for_status(&sup, |s| matches!(s, SupervisorStatus::Crashed { .. })).await;
+    }
+}
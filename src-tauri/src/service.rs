@@ -0,0 +1,1336 @@
+//! 账号命令的业务层
+//!
+//! `lib.rs` 里的 Tauri 命令只做参数反序列化、调用 [`AccountService`]、错误透传；
+//! 取 token、刷新后写回、配额缓存、封号/失效标记、派发事件都收在这里，
+//! 切号和单账号刷新额度共用同一份 token 写回逻辑（`AccountStore::apply_refreshed_tokens`）。
+//!
+//! 对外依赖走两个 trait，测试里换成假实现即可脱离 Tauri 和真实网络：
+//! - [`UsageClient`]：usage 接口 + OAuth 刷新，真实实现是 [`HttpUsageClient`]
+//! - [`EventSink`]：前端事件 + 类型化事件总线，真实实现在 `lib.rs`（包 `AppHandle`）
+//!
+//! 托盘、代理 WebSocket、切号日志、solo 推送这些只在 Tauri 里存在的副作用仍留在命令里。
+//! 切号沿途经 [`EventSink`] 发 `switch-progress`（见 `switch_progress`）。
+
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use chrono::Utc;
+use futures_util::future::BoxFuture;
+use serde::Serialize;
+
+use crate::account::{self, Account, AccountStore, CachedQuota, ImportedAccount, QuotaSource};
+use crate::auth_identify;
+use crate::codex_config::{self, ProfileChange};
+use crate::codex_installs;
+use crate::events::AppEvent;
+use crate::log_identity;
+use crate::notes_template;
+use crate::oauth::{self, AuthProfile, TokenResponse};
+use crate::quota_snapshot;
+use crate::quota_window::{self, Eligibility};
+use crate::refresh_ban;
+use crate::refresh_lock::RefreshLockManager;
+use crate::request_headers::RequestOverrides;
+use crate::switch_intent::{self, IntentStage, SwitchIntent};
+use crate::switch_progress::{SwitchProgress, SwitchStage};
+use crate::usage::{self, TokenPreflight, UsageDisplay, UsageFetcher};
+use crate::warnings::{Warned, Warning, WarningCode};
+
+/// 切号时等别的流程释放目标账号刷新锁的最长时间
+const SWITCH_LOCK_WAIT_SECS: u64 = 5;
+/// access_token 剩余有效期低于这个秒数时，切号前先刷新
+const SWITCH_REFRESH_MARGIN_SECS: i64 = 300;
+/// 切号预检配额最多等这么久，超时按预检失败处理（不阻断切号）
+const SWITCH_PRECHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// usage 接口与 OAuth 刷新
+pub trait UsageClient: Send + Sync {
+    /// 同 `UsageFetcher::fetch_usage_direct`：`local_refresh` 为 None 时不在本地消耗 rt
+    fn fetch_usage<'a>(
+        &'a self,
+        access_token: String,
+        account_id: Option<String>,
+        refresh_token: Option<String>,
+        local_refresh: Option<&'a AuthProfile>,
+        overrides: RequestOverrides,
+    ) -> BoxFuture<'a, Result<(UsageDisplay, Option<TokenResponse>), String>>;
+
+    /// 同 `oauth::refresh_access_token`
+    fn refresh_token<'a>(
+        &'a self,
+        profile: &'a AuthProfile,
+        refresh_token: &'a str,
+    ) -> BoxFuture<'a, Result<TokenResponse, String>>;
+}
+
+/// 走真实 HTTP（reqwest）的 [`UsageClient`]
+pub struct HttpUsageClient;
+
+impl UsageClient for HttpUsageClient {
+    fn fetch_usage<'a>(
+        &'a self,
+        access_token: String,
+        account_id: Option<String>,
+        refresh_token: Option<String>,
+        local_refresh: Option<&'a AuthProfile>,
+        overrides: RequestOverrides,
+    ) -> BoxFuture<'a, Result<(UsageDisplay, Option<TokenResponse>), String>> {
+        Box::pin(UsageFetcher::fetch_usage_direct(
+            access_token,
+            account_id,
+            refresh_token,
+            local_refresh,
+            overrides,
+        ))
+    }
+
+    fn refresh_token<'a>(
+        &'a self,
+        profile: &'a AuthProfile,
+        refresh_token: &'a str,
+    ) -> BoxFuture<'a, Result<TokenResponse, String>> {
+        Box::pin(oauth::refresh_access_token(profile, refresh_token))
+    }
+}
+
+/// 事件出口
+pub trait EventSink: Send + Sync {
+    /// 直接发给前端的事件（Tauri `emit`）
+    fn emit(&self, event: &str, payload: serde_json::Value);
+    /// 类型化事件总线（前端 + Remote Server `/events`）
+    fn publish(&self, event: AppEvent);
+}
+
+/// "token-rotated" 事件负载（低优先级提示，替代冲突弹窗）
+#[derive(Serialize, Clone)]
+pub struct TokenRotatedPayload {
+    pub account_id: String,
+    pub account_name: String,
+}
+
+pub(crate) fn detect_sync_conflict_for_current(
+    account: &Account,
+    disk_auth: &serde_json::Value,
+) -> Option<String> {
+    // API key 账号没有 refresh_token，谈不上轮换冲突
+    if account.is_api_key() {
+        return None;
+    }
+    // 身份不一致时不应提示“Token 冲突”，避免误判
+    if !AccountStore::auth_identity_matches(&account.auth_json, disk_auth) {
+        return None;
+    }
+
+    let official_rt = AccountStore::extract_refresh_token(disk_auth);
+    let local_rt = AccountStore::extract_refresh_token(&account.auth_json);
+
+    // 如果官方 Token 存在且与本地不同（通常是更新了），则视为冲突
+    if official_rt.is_some() && official_rt != local_rt {
+        let disk_email =
+            AccountStore::extract_email(disk_auth).unwrap_or_else(|| "未知账号".to_string());
+        if disk_email == account.name {
+            return Some(account.name.clone());
+        } else {
+            return Some(format!("{} ({})", account.name, disk_email));
+        }
+    }
+
+    None
+}
+
+/// `auto_adopt_current_rotation` 的判定：身份一致，且磁盘 last_refresh 严格新于本地。
+/// 任一侧缺 last_refresh 或磁盘不更新时返回 false，走原来的手动冲突流程。
+fn should_auto_adopt_rotation(
+    enabled: bool,
+    local_auth: &serde_json::Value,
+    disk_auth: &serde_json::Value,
+) -> bool {
+    if !enabled || !AccountStore::auth_identity_matches(local_auth, disk_auth) {
+        return false;
+    }
+    match (
+        AccountStore::extract_last_refresh(disk_auth),
+        AccountStore::extract_last_refresh(local_auth),
+    ) {
+        (Some(disk), Some(local)) => disk > local,
+        _ => false,
+    }
+}
+
+/// 当前账号在磁盘上发生 rt 轮换且满足自动采纳条件时，直接吸收磁盘 auth.json 并落盘。
+/// 返回 Some 表示已采纳，调用方负责 emit "token-rotated"。
+pub(crate) fn auto_adopt_current_rotation(
+    store: &mut AccountStore,
+    disk_auth: &serde_json::Value,
+) -> Option<TokenRotatedPayload> {
+    let current_id = store.current.clone()?;
+    let account = store.accounts.get(&current_id)?;
+    detect_sync_conflict_for_current(account, disk_auth)?;
+    if !should_auto_adopt_rotation(
+        store.settings.auto_adopt_current_rotation,
+        &account.auth_json,
+        disk_auth,
+    ) {
+        return None;
+    }
+    let account_name = account.name.clone();
+    if !store.sync_account_from_auth_json(&current_id, disk_auth.clone()) {
+        return None;
+    }
+    if let Err(e) = store.save() {
+        eprintln!("[Sync] 自动采纳轮换 token 后保存失败: {}", e);
+    }
+    println!(
+        "[Sync] 当前账号 {} 的 refresh_token 已在磁盘轮换，自动采纳",
+        log_identity::id(&account_name)
+    );
+    Some(TokenRotatedPayload {
+        account_id: current_id,
+        account_name,
+    })
+}
+
+/// access_token 是否需要在切号前刷新：解析不了 claims 时按需要处理（盲刷）；
+/// 系统时间异常时不按 exp 判断
+fn needs_refresh_before_switch(access_token: &str) -> bool {
+    match AccountStore::extract_jwt_claims_from_token(access_token) {
+        Ok(_) if crate::clock::is_skewed() => {
+            println!("[Switch] 系统时间异常，不按 JWT 过期时间刷新");
+            false
+        }
+        Ok(claims) => match claims.get("exp").and_then(|v| v.as_i64()) {
+            Some(exp) if exp - crate::clock::now().timestamp() < SWITCH_REFRESH_MARGIN_SECS => {
+                println!("[Switch] JWT 已过期或即将过期 ({}), 触发自动刷新", exp);
+                true
+            }
+            _ => false,
+        },
+        Err(_) => {
+            println!("[Switch] 无法解析 JWT Claims，尝试盲刷");
+            true
+        }
+    }
+}
+
+/// 预检拿到的套餐和缓存里的不一样（升级 / 降级）。此时新数据必须落盘，
+/// 否则预检之后一次失败的刷新就会让旧套餐和旧窗口显示一整个会话
+fn precheck_plan_changed(cached: Option<&CachedQuota>, fresh_plan: &str) -> bool {
+    cached.is_some_and(|q| !q.plan_type.eq_ignore_ascii_case(fresh_plan))
+}
+
+/// 5H 快重置的后备候选的评分；还有额度的号评分都大于 0，后备总排在它们后面
+const FIVE_HOUR_FALLBACK_SCORE: f64 = 0.0;
+
+/// 智能切号候选：排除当前 / 封号 / 失效 / 登出 / 无 Codex 权限 / 额度耗尽（见 `quota_window`）的账号，
+/// 按额度 + Plan 评分从高到低。返回 (id, name, score)
+pub fn score_candidate_accounts(store: &AccountStore) -> Vec<(String, String, f64)> {
+    let current_id = store.current.as_deref().unwrap_or("");
+    let allow_free = store.settings.allow_auto_switch_to_free;
+    let allow_switch_in_relay = store.settings.relay_auto_switch_in;
+    let lookahead_secs = i64::from(store.settings.five_hour_reset_lookahead_minutes) * 60;
+    let now = Utc::now().timestamp();
+
+    let mut scored: Vec<(String, String, f64)> = Vec::new();
+
+    for account in store.accounts.values() {
+        if account.id == current_id
+            || account.archived
+            || account.is_banned
+            || account.is_token_invalid
+            || account.is_logged_out
+            || account.lacks_codex_access()
+        {
+            continue;
+        }
+        // 默认不"切到 Relay"：自动选号跳过 Relay 候选
+        if !allow_switch_in_relay && account.is_relay() {
+            continue;
+        }
+
+        let score = match &account.cached_quota {
+            None => 50.0,
+            Some(q) => {
+                let plan = q.plan_type.to_lowercase();
+                let is_free = plan == "free" || plan == "unknown";
+
+                if is_free && !allow_free {
+                    continue;
+                }
+
+                // Plan 优先级加分：pro > plus/team > free
+                let plan_bonus = match plan.as_str() {
+                    "pro" => 30.0,
+                    "plus" | "team" | "enterprise" => 20.0,
+                    "edu" | "business" => 15.0,
+                    "free" | "unknown" => 0.0,
+                    _ => 10.0,
+                };
+
+                // 周额度耗尽的号要躺好几天，直接排除；5H 耗尽但马上重置的留作后备
+                let windows = quota_window::classify(q, now);
+                match quota_window::eligibility(&windows, is_free, now, lookahead_secs) {
+                    Eligibility::Excluded(_) => continue,
+                    Eligibility::AfterFiveHourReset { .. } => FIVE_HOUR_FALLBACK_SCORE,
+                    Eligibility::Eligible => {
+                        // 缓存是 0 但已过重置时间的窗口按 50 算
+                        let left = |v: f64| if v > 0.0 { v } else { 50.0 };
+                        let effective = if is_free {
+                            left(q.five_hour_left)
+                        } else {
+                            left(q.five_hour_left).min(left(q.weekly_left))
+                        };
+                        // 最终评分 = 额度分 + Plan 加分
+                        effective + plan_bonus
+                    }
+                }
+            }
+        };
+
+        scored.push((account.id.clone(), account.name.clone(), score));
+    }
+
+    // 按得分从高到低排序
+    scored.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(std::cmp::Ordering::Equal));
+    scored
+}
+
+/// 智能切号的"还有额度"：free 只看 5h，其它 Plan 5h 和周都要有
+fn has_quota_left(quota: &UsageDisplay) -> bool {
+    let plan = quota.plan_type.to_lowercase();
+    if plan == "free" || plan == "unknown" {
+        quota.five_hour_left > 0
+    } else {
+        quota.five_hour_left > 0 && quota.weekly_left > 0
+    }
+}
+
+/// 切号写完 auth.json 后按目标账号改 config.toml 的 profile。
+/// 写失败只记日志不阻断切号；返回的修改记录留给保存失败时回滚
+fn apply_codex_profile(store: &AccountStore, id: &str) -> Option<ProfileChange> {
+    let account = store.accounts.get(id)?;
+    codex_config::apply_for_switch(&store.settings, account).unwrap_or_else(|e| {
+        eprintln!("[Switch] 写入 Codex profile 失败（不影响切号）: {}", e);
+        None
+    })
+}
+
+pub struct AccountService {
+    store: Arc<Mutex<AccountStore>>,
+    locks: RefreshLockManager,
+    usage: Arc<dyn UsageClient>,
+    events: Arc<dyn EventSink>,
+    precheck_timeout: Duration,
+}
+
+impl AccountService {
+    pub fn new(
+        store: Arc<Mutex<AccountStore>>,
+        locks: RefreshLockManager,
+        usage: Arc<dyn UsageClient>,
+        events: Arc<dyn EventSink>,
+    ) -> Self {
+        Self {
+            store,
+            locks,
+            usage,
+            events,
+            precheck_timeout: SWITCH_PRECHECK_TIMEOUT,
+        }
+    }
+
+    /// 改切号预检的超时（测试用短超时）
+    pub fn with_precheck_timeout(mut self, timeout: Duration) -> Self {
+        self.precheck_timeout = timeout;
+        self
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, AccountStore>, String> {
+        self.store.lock().map_err(|e| e.to_string())
+    }
+
+    /// 过刷新禁令的 token 刷新：挂着禁令时不发请求直接报错
+    async fn refresh_tokens(
+        &self,
+        id: &str,
+        profile: &AuthProfile,
+        refresh_token: &str,
+    ) -> Result<TokenResponse, String> {
+        refresh_ban::guard(&*self.lock()?, id)?;
+        let result = self.usage.refresh_token(profile, refresh_token).await;
+        if let Err(e) = &result {
+            self.record_refresh_failure(id, e)?;
+        }
+        result
+    }
+
+    /// 刷新失败（含 usage 请求里的本地刷新）被判定重复使用时挂禁令并通知前端
+    fn record_refresh_failure(&self, id: &str, error: &str) -> Result<(), String> {
+        let notice = {
+            let mut store = self.lock()?;
+            let notice = refresh_ban::record_failure(&mut store, id, error);
+            if notice.is_some() {
+                if let Err(e) = store.save() {
+                    eprintln!("[Store] 保存失败: {}", e);
+                }
+            }
+            notice
+        };
+        if let Some(notice) = notice {
+            self.events.emit(
+                refresh_ban::EVENT,
+                serde_json::to_value(&notice).unwrap_or_default(),
+            );
+            self.events
+                .emit("accounts-updated", serde_json::Value::Null);
+        }
+        Ok(())
+    }
+
+    /// 把刷新得到的 token 写回账号并落盘；账号已不存在时返回 false
+    fn apply_tokens(&self, id: &str, tokens: TokenResponse) -> Result<bool, String> {
+        let mut store = self.lock()?;
+        let Some(account) = store.accounts.get_mut(id) else {
+            return Ok(false);
+        };
+        AccountStore::apply_refreshed_tokens(
+            account,
+            tokens.access_token,
+            tokens.refresh_token,
+            tokens.id_token,
+            tokens.expires_in,
+        );
+        if let Err(e) = store.save() {
+            eprintln!("[Store] 保存失败: {}", e);
+        }
+        Ok(true)
+    }
+
+    /// 写配额缓存，标记待落盘；`persist_now`（套餐变更这类不能丢的）时立即保存
+    fn cache_quota(
+        &self,
+        id: &str,
+        usage: &UsageDisplay,
+        source: QuotaSource,
+        persist_now: bool,
+    ) -> Result<(), String> {
+        let mut store = self.lock()?;
+        if let Some(account) = store.accounts.get_mut(id) {
+            account.set_cached_quota((usage, source).into());
+            if !persist_now {
+                store.mark_dirty();
+            } else if let Err(e) = store.save() {
+                eprintln!("[Store] 保存失败: {}", e);
+                store.mark_dirty();
+            }
+        }
+        Ok(())
+    }
+
+    /// 预检没拿到新数据：缓存超过 `quota_suspect_after_hours` 就标为可能过期
+    fn mark_quota_suspect(&self, id: &str) -> Result<(), String> {
+        let mut store = self.lock()?;
+        let stale_after =
+            chrono::Duration::hours(i64::from(store.settings.quota_suspect_after_hours));
+        let marked = store
+            .accounts
+            .get_mut(id)
+            .and_then(|a| a.cached_quota.as_mut())
+            .is_some_and(|q| q.mark_suspect_if_stale(Utc::now(), stale_after));
+        if marked {
+            println!("[Switch] {} 的额度缓存已超过阈值，标为可能过期", id);
+            store.mark_dirty();
+        }
+        Ok(())
+    }
+
+    /// 从当前 Codex 登录状态导入账号
+    pub fn import_current(
+        &self,
+        name: String,
+        notes: Option<String>,
+        force: bool,
+    ) -> Result<ImportedAccount, String> {
+        let auth_json = AccountStore::read_codex_auth()?;
+        // API key 形态的 auth.json 本来就没有 token，不用续期
+        if AccountStore::extract_refresh_token(&auth_json).is_none()
+            && AccountStore::extract_api_key(&auth_json).is_none()
+        {
+            return Err("当前 auth.json 缺少 refresh_token，无法自动续期，请重新登录".to_string());
+        }
+        self.import_auth_value(name, auth_json, notes, force, "当前登录")
+    }
+
+    /// 从粘贴的 auth.json 文本导入账号（同事经密码管理器发来的那种），全程不读写磁盘上的
+    /// auth.json。兼容的历史格式和逐字段报错同 `auth_identify::parse_identity`；
+    /// 名称留空时用 token 里的邮箱。API key 形态和 `import_current` 一样直接收（按 key 去重），
+    /// 它没有邮箱，名称必须填
+    pub fn import_pasted(
+        &self,
+        name: String,
+        json_text: &str,
+        notes: Option<String>,
+        force: bool,
+    ) -> Result<ImportedAccount, String> {
+        let api_key_auth = serde_json::from_str::<serde_json::Value>(json_text.trim())
+            .ok()
+            .filter(|auth| AccountStore::extract_api_key(auth).is_some());
+        if let Some(auth_json) = api_key_auth {
+            let name = match name.trim() {
+                "" => return Err("name: API Key 登录没有邮箱，请填写名称".to_string()),
+                name => name.to_string(),
+            };
+            return self.import_auth_value(name, auth_json, notes, force, "粘贴的 API Key");
+        }
+        let (auth_json, identity) =
+            auth_identify::parse_identity(json_text).map_err(|e| e.to_string())?;
+        if !identity.has_refresh_token {
+            return Err("tokens.refresh_token: 缺少 refresh_token，无法自动续期".to_string());
+        }
+        let name = match name.trim() {
+            "" => identity
+                .email
+                .ok_or("name: 名称为空，且 token 里没有邮箱可用")?,
+            name => name.to_string(),
+        };
+        self.import_auth_value(name, auth_json, notes, force, "粘贴的账号")
+    }
+
+    /// 导入一份已校验过的 auth：同一身份已存在时只更新 token（见 `AccountStore::import_auth`），
+    /// 备注没填时按模板预填。`source` 只用于日志
+    fn import_auth_value(
+        &self,
+        name: String,
+        auth_json: serde_json::Value,
+        notes: Option<String>,
+        force: bool,
+        source: &str,
+    ) -> Result<ImportedAccount, String> {
+        let mut store = self.lock()?;
+        // 用户自己填了备注就用用户的，否则按模板预填
+        let notes = match notes.filter(|n| !n.trim().is_empty()) {
+            Some(notes) => Some(notes),
+            None => notes_template::render(
+                &store.settings.new_account_notes_template,
+                &notes_template::TemplateValues::from_auth(
+                    &auth_json,
+                    chrono::Local::now().date_naive(),
+                ),
+                None,
+            )?,
+        };
+        let imported = store.import_auth(name, auth_json, notes, force);
+        if let Some(reason) = &imported.skipped {
+            println!("[Import] {}未导入: {}", source, reason);
+            return Ok(imported);
+        }
+        store.save()?;
+        if imported.updated {
+            println!(
+                "[Import] {}已存在（{}），只更新了 token",
+                source,
+                log_identity::id(&imported.account.name)
+            );
+        }
+        Ok(imported)
+    }
+
+    /// 检查当前账号在磁盘上是否有未同步的 token 更新。
+    /// 开了自动采纳且磁盘版本更新时直接吸收（发 "token-rotated"），否则返回冲突账号名并发 SyncConflict
+    pub fn check_conflict(&self) -> Result<Option<String>, String> {
+        let auth_json = match AccountStore::read_codex_auth() {
+            Ok(a) => a,
+            Err(_) => return Ok(None), // 如果由于文件不存在等原因读取失败，视为无冲突
+        };
+
+        let mut store = self.lock()?;
+
+        // 归档的号完全不参与冲突检测，也不自动采纳轮换
+        let current = store.current.as_ref().and_then(|id| store.accounts.get(id));
+        if current.is_some_and(|account| account.archived) {
+            return Ok(None);
+        }
+
+        if let Some(rotated) = auto_adopt_current_rotation(&mut store, &auth_json) {
+            self.events.emit(
+                "token-rotated",
+                serde_json::to_value(&rotated).unwrap_or_default(),
+            );
+            self.events
+                .emit("accounts-updated", serde_json::Value::Null);
+            return Ok(None);
+        }
+
+        let conflict = store
+            .current
+            .as_ref()
+            .and_then(|id| store.accounts.get(id))
+            .and_then(|account| detect_sync_conflict_for_current(account, &auth_json));
+        if let Some(name) = &conflict {
+            self.events.publish(AppEvent::SyncConflict {
+                account_name: name.clone(),
+            });
+        }
+        Ok(conflict)
+    }
+
+    /// 解决冲突：把当前 Codex auth.json 强制同步到指定账号
+    pub fn resolve_conflict(&self, id: &str) -> Result<(), String> {
+        let auth_json = AccountStore::read_codex_auth()?;
+        let mut store = self.lock()?;
+        store.ensure_unprotected(id, "用当前 auth.json 覆盖")?;
+        match store.try_sync_account_from_auth_json(id, auth_json) {
+            Ok(true) => store.save(),
+            Ok(false) => Err(format!("同步失败：账号 {} 不存在或没有可同步的 token", id)),
+            Err(warning) => Err(format!("同步失败：{}", warning)),
+        }
+    }
+
+    /// 同 [`Self::check_conflict`]，查登记的其它 Codex 安装（见 `codex_installs`）；不自动采纳
+    pub fn check_install_conflict(&self, install: &str) -> Result<Option<String>, String> {
+        let conflict = codex_installs::conflict(&*self.lock()?, install);
+        if let Some(name) = &conflict {
+            self.events.publish(AppEvent::SyncConflict {
+                account_name: name.clone(),
+            });
+        }
+        Ok(conflict)
+    }
+
+    /// 同 [`Self::resolve_conflict`]，用登记安装的 auth.json
+    pub fn resolve_install_conflict(&self, install: &str, id: &str) -> Result<(), String> {
+        let mut store = self.lock()?;
+        store.ensure_unprotected(id, &format!("用 {} 的 auth.json 覆盖", install))?;
+        let auth_json = codex_installs::read_auth(&store, install)?;
+        match store.try_sync_account_from_auth_json(id, auth_json) {
+            Ok(true) => store.save(),
+            Ok(false) => Err(format!("同步失败：账号 {} 不存在或没有可同步的 token", id)),
+            Err(warning) => Err(format!("同步失败：{}", warning)),
+        }
+    }
+
+    /// 切换到指定账号：回流当前账号 → 必要时刷新目标 token → 非阻断预检配额 → 持锁切换落盘。
+    /// `proxy_running` 参与热/冷切判定（见 `account::should_hot_switch`）。
+    /// 返回沿途没挡住切号的警告（预检失败、刷新失败……）
+    pub async fn switch(&self, id: &str, proxy_running: bool) -> Result<Vec<Warning>, String> {
+        let Warned { value, warnings } = self.switch_tracked(id, proxy_running).await?;
+        value.done_with(&warnings);
+        Ok(warnings)
+    }
+
+    /// 同 [`Self::switch`]，沿途发 `switch-progress`。成功时把进度守卫交给调用方，
+    /// 收尾做完再 `done_with()`；失败时这里已经发过 `failed`
+    pub async fn switch_tracked(
+        &self,
+        id: &str,
+        proxy_running: bool,
+    ) -> Result<Warned<SwitchProgress>, String> {
+        let mut progress = SwitchProgress::begin(self.events.clone(), id);
+        match self.run_switch(id, proxy_running, &mut progress).await {
+            Ok(warnings) => Ok(Warned::new(progress, warnings)),
+            Err(e) => {
+                progress.fail(&e);
+                Err(e)
+            }
+        }
+    }
+
+    async fn run_switch(
+        &self,
+        id: &str,
+        proxy_running: bool,
+        progress: &mut SwitchProgress,
+    ) -> Result<Vec<Warning>, String> {
+        let mut warnings = Vec::new();
+        // 0. 切换前仅同步“当前激活账号”与官方 auth.json，避免全表匹配导致串号
+        if let Ok(current_auth) = AccountStore::read_codex_auth() {
+            if let Ok(mut store) = self.store.lock() {
+                if let Some(current_id) = store.current.clone() {
+                    match store.try_sync_account_from_auth_json(&current_id, current_auth) {
+                        Ok(true) => {
+                            if let Err(e) = store.save() {
+                                eprintln!("[Sync] 保存当前账号失败: {}", e);
+                            }
+                        }
+                        Ok(false) => {}
+                        Err(warning) => warnings.push(warning),
+                    }
+                }
+            }
+        }
+
+        progress.stage(SwitchStage::Prechecking);
+
+        // 1. 获取目标账号的校验凭据；Relay / API key 类型跳过 OpenAI usage 预检
+        let (
+            name,
+            is_relay,
+            is_api_key,
+            access_token,
+            expires_at,
+            refresh_token,
+            account_id,
+            profile,
+            overrides,
+        ) = {
+            let store = self.lock()?;
+            let account = store
+                .accounts
+                .get(id)
+                .ok_or_else(|| format!("账号 {} 不存在", id))?;
+            let access_token = account
+                .auth_json
+                .get("tokens")
+                .and_then(|t| t.get("access_token"))
+                .and_then(|v| v.as_str())
+                .map(|s| s.to_string());
+            // API key 账号的 auth.json 原样写盘，不需要 access_token
+            let access_token = match access_token {
+                Some(at) => at,
+                None if account.is_api_key() => String::new(),
+                None => return Err("账号缺少 access_token".to_string()),
+            };
+            (
+                account.name.clone(),
+                account.is_relay(),
+                account.is_api_key(),
+                access_token,
+                AccountStore::extract_access_token_expiry(&account.auth_json),
+                account.refresh_token.clone(),
+                AccountStore::extract_account_id(&account.auth_json),
+                store.auth_profile_of(account),
+                RequestOverrides::for_account(&store.settings, account),
+            )
+        };
+
+        // 1.5. JWT 过期或即将过期时先刷新
+        let (access_token, refresh_token, refreshed) = match refresh_token.as_deref() {
+            Some(rt) if needs_refresh_before_switch(&access_token) => {
+                match self.refresh_tokens(id, &profile, rt).await {
+                    Ok(tokens) => {
+                        println!("[Switch] 自动刷新 Token 成功");
+                        let (at, rt) = (tokens.access_token.clone(), tokens.refresh_token.clone());
+                        if self.apply_tokens(id, tokens)? {
+                            (at, rt, true)
+                        } else {
+                            (access_token, refresh_token, false)
+                        }
+                    }
+                    Err(e) => {
+                        println!("[Switch] 自动刷新 Token 失败: {}", e);
+                        warnings.push(
+                            Warning::new(WarningCode::TokenRefreshFailed)
+                                .with("account", &name)
+                                .with("error", e),
+                        );
+                        (access_token, refresh_token, false)
+                    }
+                }
+            }
+            _ => (access_token, refresh_token, false),
+        };
+
+        // 2. 预检（非阻断）：仅尝试读取配额缓存，不触发本地 refresh_token 刷新。
+        // 失败不阻断切换，交由 Codex 在实际请求中按需维护 token 生命周期。
+        // token 已过期又没刷新成功时这次请求注定 401，直接跳过
+        let expired =
+            !refreshed && usage::token_preflight_now(expires_at, false) == TokenPreflight::Fail;
+        if is_relay {
+            println!("[Switch] Relay 类型，跳过 OpenAI usage 预检: {}", id);
+        } else if is_api_key {
+            println!("[Switch] API key 账号，跳过 ChatGPT 额度预检: {}", id);
+        } else if expired {
+            println!(
+                "[Switch] access_token 已过期且未能刷新，跳过配额预检: {}",
+                id
+            );
+            warnings.push(Warning::new(WarningCode::PrecheckSkipped).with("account", &name));
+            self.mark_quota_suspect(id)?;
+        } else {
+            println!("[Switch] 预检目标账号配额（不触发本地 refresh）: {}", id);
+            let precheck =
+                self.usage
+                    .fetch_usage(access_token, account_id, refresh_token, None, overrides);
+            match tokio::time::timeout(self.precheck_timeout, precheck)
+                .await
+                .unwrap_or_else(|_| Err("预检超时".to_string()))
+            {
+                Ok((usage, _)) => {
+                    let (email, plan_changed) = {
+                        let store = self.lock()?;
+                        let account = store.accounts.get(id);
+                        (
+                            account
+                                .and_then(|a| AccountStore::extract_email(&a.auth_json))
+                                .unwrap_or_default(),
+                            precheck_plan_changed(
+                                account.and_then(|a| a.cached_quota.as_ref()),
+                                &usage.plan_type,
+                            ),
+                        )
+                    };
+                    quota_snapshot::append_from_usage(id, &email, &usage, "switch_precheck");
+                    if plan_changed {
+                        println!(
+                            "[Switch] 预检发现套餐变更（{}），强制写入新额度",
+                            usage.plan_type
+                        );
+                    }
+                    // 无论套餐变没变都整份覆盖：set_cached_quota 负责记录套餐变更、清掉 suspect
+                    self.cache_quota(id, &usage, QuotaSource::SwitchPrecheck, plan_changed)?;
+                }
+                Err(e) => {
+                    println!("[Switch] 预检配额失败（忽略，不阻断切换）: {}", e);
+                    self.mark_quota_suspect(id)?;
+                    warnings.push(
+                        Warning::new(WarningCode::PrecheckFailed)
+                            .with("account", &name)
+                            .with("error", e),
+                    );
+                }
+            }
+        }
+
+        // 3. 执行切换：根据 switch_mode + 代理运行状态决定热/冷切
+        let hot = account::should_hot_switch(&self.lock()?.settings, proxy_running);
+        println!(
+            "[Switch] 执行切换...（模式={}）",
+            if hot { "热切" } else { "冷切" }
+        );
+        progress.stage(SwitchStage::AcquiringLock);
+        if !self
+            .locks
+            .acquire(id, tokio::time::Duration::from_secs(SWITCH_LOCK_WAIT_SECS))
+            .await
+        {
+            return Err("该账号正在被其他流程刷新，请稍后重试".to_string());
+        }
+        progress.stage(SwitchStage::WritingAuth);
+        let intent_path = switch_intent::default_path();
+        let result = self.lock().and_then(|mut store| {
+            // 手机锚生效时 switch_to 不写 auth.json（见 `AccountStore::switch_to`）
+            let mut intent = None;
+            if let Some(anchor) = store.session_anchor_id().filter(|anchor| anchor != id) {
+                let anchor = store
+                    .accounts
+                    .get(&anchor)
+                    .map_or(anchor, |a| a.name.clone());
+                warnings.push(
+                    Warning::new(WarningCode::AuthWriteSkipped)
+                        .with("account", &name)
+                        .with("anchor", anchor),
+                );
+            } else {
+                // 写 auth.json 前先落意图，崩在中间时启动能收拾（见 `switch_intent`）
+                let pending = SwitchIntent::new(id, store.current.clone(), Utc::now());
+                switch_intent::save_to(&intent_path, &pending)
+                    .map_err(|e| format!("记录切号意图失败: {}", e))?;
+                intent = Some(pending);
+            }
+            if let Err(e) = store.switch_to(id, hot) {
+                if let Err(e) = switch_intent::clear_at(&intent_path) {
+                    eprintln!("[Switch] 清除切号意图失败: {}", e);
+                }
+                return Err(e);
+            }
+            if let Some(mut pending) = intent {
+                pending.stage = IntentStage::AuthWritten;
+                if let Err(e) = switch_intent::save_to(&intent_path, &pending) {
+                    eprintln!("[Switch] 更新切号意图失败: {}", e);
+                }
+            }
+            let profile_change = apply_codex_profile(&store, id);
+            let saved = store.save();
+            if saved.is_ok() {
+                if let Err(e) = switch_intent::clear_at(&intent_path) {
+                    eprintln!("[Switch] 清除切号意图失败: {}", e);
+                }
+            } else {
+                if let Some(change) = profile_change {
+                    if let Err(e) = change.rollback() {
+                        eprintln!("[Switch] {}", e);
+                    }
+                }
+            }
+            saved
+        });
+        self.locks.release(id).await;
+        result.map(|()| warnings)
+    }
+
+    /// 刷新单个账号的额度（不切换账号）并写缓存。
+    /// 当前账号先按 `~/.codex/auth.json` 校验身份并回流；封号 / 失效 / 登出分别打标记，
+    /// 上游故障（5xx / HTML 错误页）不是账号问题，保留旧缓存
+    pub async fn refresh_quota(
+        &self,
+        id: &str,
+        source: QuotaSource,
+    ) -> Result<UsageDisplay, String> {
+        let (is_relay, is_api_key, is_archived, is_current, anchor_owns_disk) = {
+            let store = self.lock()?;
+            (
+                store.accounts.get(id).is_some_and(|a| a.is_relay()),
+                store.accounts.get(id).is_some_and(|a| a.is_api_key()),
+                store.accounts.get(id).is_some_and(|a| a.archived),
+                store.current.as_deref() == Some(id),
+                // 手机锚模式（v0.7+）：disk 故意锁在 anchor 上，跟 current 身份不匹配是 BY DESIGN
+                store.session_anchor_id().is_some_and(|aid| aid != id),
+            )
+        };
+        if is_relay {
+            return Err(
+                "RELAY_ACCOUNT:中转站账号请用「中转站余额刷新」，不是 OpenAI usage".to_string(),
+            );
+        }
+        if is_api_key {
+            return Err("API_KEY_ACCOUNT:API key 账号没有 ChatGPT 额度可查".to_string());
+        }
+        if is_archived && source.is_batch() {
+            return Err(format!("ARCHIVED:账号已归档，{}跳过", source.label()));
+        }
+
+        if is_current && anchor_owns_disk {
+            println!(
+                "[Quota] 手机锚生效，disk 归 anchor，跳过 current({}) 的 disk 一致性校验",
+                id
+            );
+        } else if is_current {
+            self.sync_current_from_disk(id)?;
+        }
+
+        // 1. 从 Store 获取该账号的 Token
+        let (access_token, expires_at, account_id, refresh_token, profile, overrides) = {
+            let store = self.lock()?;
+            let account = store
+                .accounts
+                .get(id)
+                .ok_or_else(|| format!("账号 {} 不存在", id))?;
+            let rt = account
+                .refresh_token
+                .clone()
+                .or_else(|| AccountStore::extract_refresh_token(&account.auth_json));
+            (
+                AccountStore::extract_access_token(&account.auth_json),
+                AccountStore::extract_access_token_expiry(&account.auth_json),
+                AccountStore::extract_account_id(&account.auth_json),
+                rt,
+                store.auth_profile_of(account),
+                RequestOverrides::for_account(&store.settings, account),
+            )
+        };
+
+        // 没有 access_token、或者它已经过期时，先用 refresh_token 换一个
+        let preflight = usage::token_preflight_now(expires_at, refresh_token.is_some());
+        let (access_token, refresh_token) = match (access_token, refresh_token.as_deref()) {
+            (Some(at), _) if preflight == TokenPreflight::UseAsIs => (at, refresh_token),
+            (_, Some(rt)) => match self.refresh_tokens(id, &profile, rt).await {
+                Ok(tokens) => {
+                    let at = tokens.access_token.clone();
+                    let rt = tokens.refresh_token.clone().or(refresh_token);
+                    self.apply_tokens(id, tokens)?;
+                    (at, rt)
+                }
+                Err(e) => return Err(format!("TOKEN_INVALID:刷新 token 失败: {}", e)),
+            },
+            (Some(_), None) => {
+                return Err(usage::token_expired_error(
+                    expires_at.unwrap_or_else(Utc::now),
+                ))
+            }
+            (None, None) => {
+                return Err("TOKEN_INVALID:无 access_token 且无 refresh_token".to_string())
+            }
+        };
+
+        // 2. 使用 Token 获取用量（允许自动刷新，解决 token 过期问题；挂着刷新禁令时不刷）
+        let local_refresh = refresh_ban::guard(&*self.lock()?, id)
+            .is_ok()
+            .then_some(&profile);
+        let result = self
+            .usage
+            .fetch_usage(
+                access_token,
+                account_id,
+                refresh_token,
+                local_refresh,
+                overrides,
+            )
+            .await;
+        let (usage, new_tokens) = match result {
+            Ok(ok) => ok,
+            Err(e) => {
+                self.record_refresh_failure(id, &e)?;
+                self.flag_usage_error(id, &e)?;
+                return Err(e);
+            }
+        };
+
+        // 3. 有新 Token 时写回，再更新配额缓存
+        if let Some(tokens) = new_tokens {
+            self.apply_tokens(id, tokens)?;
+        }
+        let mut store = self.lock()?;
+        if let Some(account) = store.accounts.get_mut(id) {
+            account.set_cached_quota((&usage, source).into());
+        }
+        store.mark_dirty();
+        Ok(usage)
+    }
+
+    /// 智能切号：按 [`score_candidate_accounts`] 从高到低逐个查实时额度，切到第一个还有额度的账号
+    /// （Relay 不走 OpenAI usage，直接接受）。返回切到的账号 id
+    pub async fn switch_to_next(&self, proxy_running: bool) -> Result<String, String> {
+        self.switch_to_next_warned(proxy_running)
+            .await
+            .map(|warned| warned.value)
+    }
+
+    /// 同 [`Self::switch_to_next`]，另带跳过的候选和切号本身的警告
+    pub async fn switch_to_next_warned(
+        &self,
+        proxy_running: bool,
+    ) -> Result<Warned<String>, String> {
+        let candidates = score_candidate_accounts(&*self.lock()?);
+        let mut warnings = Vec::new();
+        if candidates.is_empty() {
+            return Err("没有可用账号".to_string());
+        }
+
+        for (id, name, score) in candidates {
+            println!(
+                "[SmartSwitch] 候选: {} (评分 {:.0})",
+                log_identity::id(&name),
+                score
+            );
+            let is_relay = self.lock()?.accounts.get(&id).is_some_and(|a| a.is_relay());
+            if is_relay {
+                println!(
+                    "[SmartSwitch] Relay 类型，跳过 quota 检查直接切换: {}",
+                    log_identity::id(&name)
+                );
+            } else {
+                match self.refresh_quota(&id, QuotaSource::SwitchPrecheck).await {
+                    Ok(quota) if has_quota_left(&quota) => println!(
+                        "[SmartSwitch] 选中最优账号: {} ({}, 5h={}%, 周={}%)",
+                        log_identity::id(&name),
+                        quota.plan_type,
+                        quota.five_hour_left,
+                        quota.weekly_left
+                    ),
+                    Ok(_) => {
+                        println!(
+                            "[SmartSwitch] 账号 {} 额度已耗尽，继续找",
+                            log_identity::id(&name)
+                        );
+                        warnings.push(
+                            Warning::new(WarningCode::CandidateSkipped).with("account", &name),
+                        );
+                        continue;
+                    }
+                    Err(e) => {
+                        println!(
+                            "[SmartSwitch] 账号 {} 额度查询失败: {}，跳过",
+                            log_identity::id(&name),
+                            e
+                        );
+                        warnings.push(
+                            Warning::new(WarningCode::CandidateSkipped)
+                                .with("account", &name)
+                                .with("error", e),
+                        );
+                        continue;
+                    }
+                }
+            }
+            warnings.extend(self.switch(&id, proxy_running).await?);
+            return Ok(Warned::new(id, warnings));
+        }
+
+        Err("遍历完所有账号，未发现可用配额的账号".to_string())
+    }
+
+    /// 刷新当前激活账号的额度（托盘用，不经前端）；Relay 账号报 RELAY_ACCOUNT，由调用方改走中转站刷新
+    pub async fn refresh_current_quota(&self) -> Result<UsageDisplay, String> {
+        let id = self.lock()?.current.clone().ok_or("当前没有激活账号")?;
+        self.refresh_quota(&id, QuotaSource::ManualRefresh).await
+    }
+
+    /// 开关后台刷新并落盘，返回原来的值。client 模式不能开启（保活由 Server 负责）；
+    /// 调度器启停只在 Tauri 里存在，由调用方按前后值处理
+    pub fn set_background_refresh(&self, enabled: bool) -> Result<bool, String> {
+        let prev = {
+            let mut store = self.lock()?;
+            if enabled && store.settings.remote_mode == "client" {
+                return Err("client 模式下保活由 Server 负责，不能开启后台刷新".to_string());
+            }
+            let prev = store.settings.background_refresh;
+            store.settings.background_refresh = enabled;
+            store.save()?;
+            prev
+        };
+        self.events
+            .emit("settings-updated", serde_json::Value::Null);
+        Ok(prev)
+    }
+
+    /// 当前激活账号：按 `~/.codex/auth.json` 做身份校验，磁盘有变化时按权威源回流
+    fn sync_current_from_disk(&self, id: &str) -> Result<(), String> {
+        let official_auth = AccountStore::read_codex_auth()?;
+        let mut store = self.lock()?;
+        let local_auth = store
+            .accounts
+            .get(id)
+            .ok_or_else(|| format!("账号 {} 不存在", id))?
+            .auth_json
+            .clone();
+
+        if !AccountStore::auth_identity_matches(&local_auth, &official_auth) {
+            return Err(
+                "当前激活账号与 ~/.codex/auth.json 身份不匹配，已拒绝覆盖，请先在 Codex 中切回同一账号".to_string(),
+            );
+        }
+
+        if local_auth != official_auth {
+            println!(
+                "[Quota] 当前激活账号 {}：检测到官方 auth.json 变更，按权威源同步。",
+                id
+            );
+            if store.sync_account_from_auth_json(id, official_auth) {
+                store.save()?;
+            }
+        } else {
+            println!("[Quota] 当前激活账号 {}：已与官方 auth.json 保持一致。", id);
+        }
+        Ok(())
+    }
+
+    /// usage 失败时按错误类型给账号打标记（三种标记互斥）；上游故障不动账号
+    fn flag_usage_error(&self, id: &str, error: &str) -> Result<(), String> {
+        if usage::is_upstream_unavailable(error) {
+            println!("[Quota] {} 上游暂时不可用，保留旧缓存: {}", id, error);
+            return Ok(());
+        }
+        let (banned, invalid, logged_out) = if error.contains("ACCOUNT_BANNED") {
+            (true, false, false)
+        } else if error.contains("TOKEN_INVALID") {
+            (false, true, false)
+        } else if error.contains("ACCOUNT_LOGGED_OUT") {
+            (false, false, true)
+        } else {
+            return Ok(());
+        };
+        let mut store = self.lock()?;
+        if let Some(account) = store.accounts.get_mut(id) {
+            account.is_banned = banned;
+            account.is_token_invalid = invalid;
+            account.is_logged_out = logged_out;
+            if let Err(e) = store.save() {
+                eprintln!("[Store] 保存失败: {}", e);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::{AccountKind, KeepaliveState};
+
+    fn test_auth(account_id: &str, refresh_token: &str) -> serde_json::Value {
+        serde_json::json!({
+            "tokens": {
+                "account_id": account_id,
+                "refresh_token": refresh_token
+            }
+        })
+    }
+
+    fn test_account(name: &str, account_id: &str, refresh_token: &str) -> Account {
+        let auth_json = test_auth(account_id, refresh_token);
+        Account {
+            id: "acc-1".to_string(),
+            name: name.to_string(),
+            email: None,
+            auth_json: auth_json.clone(),
+            refresh_token: AccountStore::extract_refresh_token(&auth_json),
+            created_at: Utc::now(),
+            last_used: None,
+            switch_count: 0,
+            switch_history: Vec::new(),
+            notes: None,
+            cached_quota: None,
+            keepalive: KeepaliveState::default(),
+            is_banned: false,
+            is_token_invalid: false,
+            is_logged_out: false,
+            kind: AccountKind::Legacy,
+            relay_base_url: None,
+            relay_homepage: None,
+            relay_usage_preset: None,
+            relay_usage_cookie: None,
+            relay_usage_cache: None,
+            relay_model_map: None,
+            relay_model_fallback: None,
+            relay_protocol: None,
+            relay_category: None,
+            is_session_anchor: false,
+            color: None,
+            avatar_emoji: None,
+            last_plan_change: None,
+            token_ref: None,
+            auth_profile_id: None,
+            refresh_token_used_at: None,
+            codex_profile: None,
+            request_headers: Default::default(),
+            tags: Vec::new(),
+            metadata: Default::default(),
+            protected: false,
+            pinned: false,
+            archived: false,
+            sort_index: None,
+            updated_at: None,
+        }
+    }
+
+    #[test]
+    fn precheck_forces_write_when_plan_differs_from_cache() {
+        let cached: CachedQuota = serde_json::from_value(serde_json::json!({
+            "five_hour_left": 80.0,
+            "five_hour_reset": "",
+            "five_hour_reset_at": null,
+            "weekly_left": 60.0,
+            "weekly_reset": "",
+            "weekly_reset_at": null,
+            "plan_type": "plus",
+            "updated_at": "2026-03-01T08:00:00Z",
+        }))
+        .unwrap();
+        assert!(precheck_plan_changed(Some(&cached), "pro"));
+        assert!(!precheck_plan_changed(Some(&cached), "Plus"));
+        // 没有缓存时不算套餐变更（照常写入）
+        assert!(!precheck_plan_changed(None, "pro"));
+    }
+
+    #[test]
+    fn sync_conflict_is_ignored_when_identity_mismatch() {
+        let current = test_account("current", "acct-local", "rt-local");
+        let disk_auth = test_auth("acct-disk", "rt-new");
+
+        assert_eq!(detect_sync_conflict_for_current(&current, &disk_auth), None);
+    }
+
+    #[test]
+    fn sync_conflict_is_reported_when_identity_matches_and_refresh_token_changed() {
+        let current = test_account("current", "acct-1", "rt-local");
+        let disk_auth = test_auth("acct-1", "rt-new");
+
+        assert_eq!(
+            detect_sync_conflict_for_current(&current, &disk_auth),
+            Some("current".to_string())
+        );
+    }
+
+    fn with_last_refresh(mut auth: serde_json::Value, ts: Option<&str>) -> serde_json::Value {
+        if let Some(ts) = ts {
+            auth["last_refresh"] = serde_json::json!(ts);
+        }
+        auth
+    }
+
+    #[test]
+    fn auto_adopt_only_when_disk_last_refresh_is_strictly_newer() {
+        let older = Some("2026-01-01T00:00:00Z");
+        let newer = Some("2026-01-02T00:00:00Z");
+        let cases = [
+            (newer, older, true),
+            (older, newer, false),
+            (newer, newer, false),
+            (None, older, false),
+            (newer, None, false),
+            (None, None, false),
+        ];
+        for (disk_ts, local_ts, expected) in cases {
+            let local = with_last_refresh(test_auth("acct-1", "rt-local"), local_ts);
+            let disk = with_last_refresh(test_auth("acct-1", "rt-new"), disk_ts);
+            assert_eq!(
+                should_auto_adopt_rotation(true, &local, &disk),
+                expected,
+                "disk={:?} local={:?}",
+                disk_ts,
+                local_ts
+            );
+        }
+    }
+
+    #[test]
+    fn auto_adopt_requires_setting_and_identity() {
+        let local = with_last_refresh(
+            test_auth("acct-1", "rt-local"),
+            Some("2026-01-01T00:00:00Z"),
+        );
+        let disk = with_last_refresh(test_auth("acct-1", "rt-new"), Some("2026-01-02T00:00:00Z"));
+        assert!(!should_auto_adopt_rotation(false, &local, &disk));
+
+        let other = with_last_refresh(test_auth("acct-2", "rt-new"), Some("2026-01-02T00:00:00Z"));
+        assert!(!should_auto_adopt_rotation(true, &local, &other));
+    }
+
+    fn quota_with(
+        five: f64,
+        five_reset: Option<i64>,
+        weekly: f64,
+        weekly_reset: Option<i64>,
+    ) -> CachedQuota {
+        serde_json::from_value(serde_json::json!({
+            "five_hour_left": five,
+            "five_hour_reset": "",
+            "five_hour_reset_at": five_reset,
+            "weekly_left": weekly,
+            "weekly_reset": "",
+            "weekly_reset_at": weekly_reset,
+            "plan_type": "plus",
+            "updated_at": "2026-03-01T08:00:00Z",
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn selection_excludes_weekly_exhaustion_but_keeps_imminent_five_hour_reset() {
+        let now = Utc::now().timestamp();
+        let mut store = AccountStore::default();
+        for (id, quota) in [
+            ("healthy", quota_with(20.0, None, 20.0, None)),
+            // 5H 用完、10 分钟后重置：后备
+            ("five-soon", quota_with(0.0, Some(now + 600), 90.0, None)),
+            // 5H 用完、3 小时后才重置：排除
+            (
+                "five-later",
+                quota_with(0.0, Some(now + 3 * 3600), 90.0, None),
+            ),
+            // 周额度用完，哪怕 5H 满格、周重置只差 10 分钟：排除
+            ("weekly-out", quota_with(100.0, None, 0.0, Some(now + 600))),
+            // 归档的号额度再多也不选
+            ("archived", quota_with(100.0, None, 100.0, None)),
+        ] {
+            let mut account = test_account(id, id, "rt");
+            account.id = id.to_string();
+            account.cached_quota = Some(quota);
+            account.archived = id == "archived";
+            store.accounts.insert(id.to_string(), account);
+        }
+
+        let ids: Vec<String> = score_candidate_accounts(&store)
+            .into_iter()
+            .map(|(id, _, _)| id)
+            .collect();
+        assert_eq!(ids, vec!["healthy", "five-soon"]);
+
+        // 前瞻窗口关掉后，5H 用完的也不再入选
+        store.settings.five_hour_reset_lookahead_minutes = 0;
+        let ids: Vec<String> = score_candidate_accounts(&store)
+            .into_iter()
+            .map(|(id, _, _)| id)
+            .collect();
+        assert_eq!(ids, vec!["healthy"]);
+    }
+}
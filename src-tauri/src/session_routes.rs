@@ -55,10 +55,7 @@ pub struct SessionRoutesStore {
 impl SessionRoutesStore {
     /// 磁盘路径：`~/.codex-switcher/session_routes.json`
     pub fn config_path() -> PathBuf {
-        dirs::home_dir()
-            .expect("无法获取用户目录")
-            .join(".codex-switcher")
-            .join("session_routes.json")
+        crate::paths::data_dir().join("session_routes.json")
     }
 
     /// 从磁盘读；任何错误都降级成空 store
@@ -0,0 +1,503 @@
+//! 额度快照分享：把账号池的剩余额度拼成一段能直接贴进群聊的文本
+//!
+//! 只带名字、套餐、5H / 周剩余、缓存多久了、状态，外加一个截至时间；
+//! 账号 id、token、备注和其它元数据一概不带（备注要显式打开 `include_notes`）。
+//! 邮箱默认不出现，可选打码或原样。
+//!
+//! [`build`] 从账号收集行，[`render`] 按格式出文本，两者都是纯函数：
+//! - Markdown：表格
+//! - PlainText：按显示宽度对齐的列（中文算两格）
+//! - Json：固定结构，带 `schema_version`
+//!
+//! 行按剩余额度降序排（5H 和周取较小值，那才是真正还能用的），没缓存的排最后，同额度按名字。
+//! 未指定账号时取所有订阅账号：中转站和 API key 没有 5H / 周额度窗口。
+
+use std::cmp::Ordering;
+
+use chrono::{DateTime, FixedOffset, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::account::{Account, AccountKind};
+use crate::presentation;
+
+/// 名字最多显示的字符数，超出截断加 `…`
+pub const MAX_NAME_CHARS: usize = 24;
+/// 备注最多显示的字符数
+pub const MAX_NOTES_CHARS: usize = 40;
+/// JSON 结构版本，改字段时递增
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// 缺数据时的占位
+const MISSING: &str = "?";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShareFormat {
+    Markdown,
+    PlainText,
+    Json,
+}
+
+/// 邮箱怎么出现
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShareEmails {
+    #[default]
+    Hidden,
+    Masked,
+    Full,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ShareOptions {
+    pub emails: ShareEmails,
+    pub include_notes: bool,
+    /// 演示模式：名字也打码，邮箱最多打码，不带备注
+    pub presentation: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShareStatus {
+    Ok,
+    Banned,
+    TokenInvalid,
+    LoggedOut,
+}
+
+impl ShareStatus {
+    fn label(self) -> &'static str {
+        match self {
+            ShareStatus::Ok => "正常",
+            ShareStatus::Banned => "封号",
+            ShareStatus::TokenInvalid => "授权失效",
+            ShareStatus::LoggedOut => "已登出",
+        }
+    }
+}
+
+/// 快照里的一行
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ShareRow {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    pub plan: Option<String>,
+    pub five_hour_left: Option<f64>,
+    pub weekly_left: Option<f64>,
+    /// 额度缓存距截至时间多少分钟
+    pub cache_age_minutes: Option<i64>,
+    pub status: ShareStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notes: Option<String>,
+}
+
+impl ShareRow {
+    fn remaining(&self) -> Option<f64> {
+        match (self.five_hour_left, self.weekly_left) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ShareSnapshot {
+    pub schema_version: u32,
+    pub as_of: DateTime<FixedOffset>,
+    pub accounts: Vec<ShareRow>,
+}
+
+/// 未指定账号时是否默认收进快照
+pub fn included_by_default(account: &Account) -> bool {
+    account.effective_kind() == AccountKind::ChatgptOauth
+}
+
+fn row(account: &Account, options: &ShareOptions, now: DateTime<Utc>) -> ShareRow {
+    let quota = account.cached_quota.as_ref();
+    let email = match (options.emails, options.presentation) {
+        (ShareEmails::Hidden, _) => None,
+        (ShareEmails::Full, false) => {
+            crate::account::AccountStore::extract_email(&account.auth_json)
+        }
+        _ => crate::account::AccountStore::extract_email(&account.auth_json)
+            .map(|e| presentation::mask_email(&e)),
+    };
+    let name = if options.presentation {
+        presentation::mask_name(&account.name)
+    } else {
+        account.name.clone()
+    };
+    let status = if account.is_banned {
+        ShareStatus::Banned
+    } else if account.is_token_invalid {
+        ShareStatus::TokenInvalid
+    } else if account.is_logged_out {
+        ShareStatus::LoggedOut
+    } else {
+        ShareStatus::Ok
+    };
+    ShareRow {
+        name,
+        email,
+        plan: quota
+            .map(|q| {
+                if q.display_plan.is_empty() {
+                    q.plan_type.clone()
+                } else {
+                    q.display_plan.clone()
+                }
+            })
+            .filter(|p| !p.is_empty()),
+        five_hour_left: quota.map(|q| q.five_hour_left),
+        weekly_left: quota.map(|q| q.weekly_left),
+        cache_age_minutes: quota.map(|q| (now - q.updated_at).num_minutes().max(0)),
+        status,
+        notes: account
+            .notes
+            .as_deref()
+            .map(str::trim)
+            .filter(|n| options.include_notes && !options.presentation && !n.is_empty())
+            .map(str::to_string),
+    }
+}
+
+/// 收集快照：行按剩余额度降序（见模块文档）
+pub fn build<'a>(
+    accounts: impl IntoIterator<Item = &'a Account>,
+    options: &ShareOptions,
+    as_of: DateTime<FixedOffset>,
+) -> ShareSnapshot {
+    let now = as_of.with_timezone(&Utc);
+    let mut rows: Vec<ShareRow> = accounts.into_iter().map(|a| row(a, options, now)).collect();
+    rows.sort_by(|a, b| {
+        match (a.remaining(), b.remaining()) {
+            (Some(x), Some(y)) => y.partial_cmp(&x).unwrap_or(Ordering::Equal),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+        .then_with(|| a.name.cmp(&b.name))
+    });
+    ShareSnapshot {
+        schema_version: SCHEMA_VERSION,
+        as_of,
+        accounts: rows,
+    }
+}
+
+pub fn render(snapshot: &ShareSnapshot, format: ShareFormat) -> String {
+    match format {
+        ShareFormat::Markdown => render_markdown(snapshot),
+        ShareFormat::PlainText => render_plain(snapshot),
+        ShareFormat::Json => render_json(snapshot),
+    }
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut cut: String = text.chars().take(max - 1).collect();
+    cut.push('…');
+    cut
+}
+
+fn percent(value: Option<f64>) -> String {
+    value.map_or_else(|| MISSING.to_string(), |v| format!("{:.0}%", v))
+}
+
+fn age(minutes: Option<i64>) -> String {
+    match minutes {
+        None => MISSING.to_string(),
+        Some(m) if m < 60 => format!("{}分钟", m),
+        Some(m) if m < 24 * 60 => format!("{}小时", m / 60),
+        Some(m) => format!("{}天", m / (24 * 60)),
+    }
+}
+
+fn as_of_label(snapshot: &ShareSnapshot) -> String {
+    snapshot.as_of.format("%Y-%m-%d %H:%M (UTC%:z)").to_string()
+}
+
+/// 表头和每行的单元格；邮箱 / 备注列只在至少一行有值时出现
+fn table(snapshot: &ShareSnapshot) -> (Vec<&'static str>, Vec<Vec<String>>) {
+    let with_email = snapshot.accounts.iter().any(|r| r.email.is_some());
+    let with_notes = snapshot.accounts.iter().any(|r| r.notes.is_some());
+    let mut header = vec!["账号"];
+    if with_email {
+        header.push("邮箱");
+    }
+    header.extend(["套餐", "5H", "周", "缓存", "状态"]);
+    if with_notes {
+        header.push("备注");
+    }
+    let rows = snapshot
+        .accounts
+        .iter()
+        .map(|r| {
+            let mut cells = vec![truncate(&r.name, MAX_NAME_CHARS)];
+            if with_email {
+                cells.push(r.email.clone().unwrap_or_else(|| MISSING.to_string()));
+            }
+            cells.extend([
+                r.plan.clone().unwrap_or_else(|| MISSING.to_string()),
+                percent(r.five_hour_left),
+                percent(r.weekly_left),
+                age(r.cache_age_minutes),
+                r.status.label().to_string(),
+            ]);
+            if with_notes {
+                cells.push(truncate(r.notes.as_deref().unwrap_or(""), MAX_NOTES_CHARS));
+            }
+            cells
+        })
+        .collect();
+    (header, rows)
+}
+
+pub fn render_markdown(snapshot: &ShareSnapshot) -> String {
+    let (header, rows) = table(snapshot);
+    let line = |cells: Vec<String>| format!("| {} |", cells.join(" | "));
+    let mut out = vec![
+        format!("**Codex 额度快照** · 截至 {}", as_of_label(snapshot)),
+        String::new(),
+        line(header.iter().map(|h| h.to_string()).collect()),
+        line(header.iter().map(|_| "---".to_string()).collect()),
+    ];
+    out.extend(
+        rows.into_iter()
+            .map(|cells| line(cells.into_iter().map(|c| c.replace('|', "\\|")).collect())),
+    );
+    out.join("\n")
+}
+
+/// 终端 / 等宽字体下的显示宽度：中日韩、全角字符和 emoji 占两格
+fn display_width(text: &str) -> usize {
+    text.chars()
+        .map(|c| match u32::from(c) {
+            0x1100..=0x115F
+            | 0x2E80..=0xA4CF
+            | 0xAC00..=0xD7A3
+            | 0xF900..=0xFAFF
+            | 0xFE30..=0xFE4F
+            | 0xFF00..=0xFF60
+            | 0xFFE0..=0xFFE6
+            | 0x1F300..=0x1FAFF => 2,
+            _ => 1,
+        })
+        .sum()
+}
+
+pub fn render_plain(snapshot: &ShareSnapshot) -> String {
+    let (header, rows) = table(snapshot);
+    let header: Vec<String> = header.iter().map(|h| h.to_string()).collect();
+    let widths: Vec<usize> = (0..header.len())
+        .map(|i| {
+            std::iter::once(&header)
+                .chain(&rows)
+                .map(|cells| display_width(&cells[i]))
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    let line = |cells: &Vec<String>| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(c, w)| format!("{}{}", c, " ".repeat(w - display_width(c))))
+            .collect();
+        padded.join("  ").trim_end().to_string()
+    };
+    let mut out = vec![format!("Codex 额度快照 · 截至 {}", as_of_label(snapshot))];
+    out.push(line(&header));
+    out.extend(rows.iter().map(line));
+    out.join("\n")
+}
+
+pub fn render_json(snapshot: &ShareSnapshot) -> String {
+    serde_json::to_string_pretty(snapshot).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::{AccountStore, CachedQuota};
+    use base64::Engine;
+    use chrono::{Duration, TimeZone};
+    use serde_json::json;
+
+    fn as_of() -> DateTime<FixedOffset> {
+        FixedOffset::east_opt(8 * 3600)
+            .unwrap()
+            .with_ymd_and_hms(2026, 3, 1, 9, 0, 0)
+            .unwrap()
+    }
+
+    fn account(
+        store: &mut AccountStore,
+        name: &str,
+        email: &str,
+        quota: Option<(f64, f64, i64)>,
+    ) -> Account {
+        let encode = |s: &str| base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(s);
+        let id_token = format!(
+            "{}.{}.sig",
+            encode(r#"{"alg":"none"}"#),
+            encode(&format!(r#"{{"email":"{}"}}"#, email))
+        );
+        let auth = json!({ "tokens": { "id_token": id_token, "access_token": "eyJ.at.sig" } });
+        let mut account = store.add_account(name.to_string(), auth, None);
+        account.notes = Some("内部备注 token=secret".to_string());
+        account.cached_quota = quota.map(|(five_hour, weekly, age_minutes)| {
+            serde_json::from_value::<CachedQuota>(json!({
+                "five_hour_left": five_hour,
+                "five_hour_reset": "",
+                "five_hour_reset_at": null,
+                "weekly_left": weekly,
+                "weekly_reset": "",
+                "weekly_reset_at": null,
+                "plan_type": "team",
+                "display_plan": "Team",
+                "updated_at": as_of().with_timezone(&Utc) - Duration::minutes(age_minutes),
+            }))
+            .unwrap()
+        });
+        account
+    }
+
+    fn pool() -> Vec<Account> {
+        let mut store = AccountStore::default();
+        let mut banned = account(
+            &mut store,
+            "backup",
+            "backup@corp.com",
+            Some((90.0, 5.0, 130)),
+        );
+        banned.is_banned = true;
+        vec![
+            account(
+                &mut store,
+                "a-very-long-teammate-pool-account-name",
+                "pool@corp.com",
+                Some((40.0, 60.0, 12)),
+            ),
+            account(&mut store, "新号", "new@corp.com", None),
+            banned,
+            account(&mut store, "work", "work@corp.com", Some((100.0, 80.0, 3))),
+        ]
+    }
+
+    #[test]
+    fn markdown_snapshot() {
+        let accounts = pool();
+        let snapshot = build(&accounts, &ShareOptions::default(), as_of());
+        assert_eq!(
+            render(&snapshot, ShareFormat::Markdown),
+            "\
+**Codex 额度快照** · 截至 2026-03-01 09:00 (UTC+08:00)
+
+| 账号 | 套餐 | 5H | 周 | 缓存 | 状态 |
+| --- | --- | --- | --- | --- | --- |
+| work | Team | 100% | 80% | 3分钟 | 正常 |
+| a-very-long-teammate-po… | Team | 40% | 60% | 12分钟 | 正常 |
+| backup | Team | 90% | 5% | 2小时 | 封号 |
+| 新号 | ? | ? | ? | ? | 正常 |"
+        );
+    }
+
+    #[test]
+    fn plain_text_snapshot_aligns_wide_characters() {
+        let accounts = pool();
+        let snapshot = build(&accounts, &ShareOptions::default(), as_of());
+        assert_eq!(
+            render(&snapshot, ShareFormat::PlainText),
+            "\
+Codex 额度快照 · 截至 2026-03-01 09:00 (UTC+08:00)
+账号                      套餐  5H    周   缓存    状态
+work                      Team  100%  80%  3分钟   正常
+a-very-long-teammate-po…  Team  40%   60%  12分钟  正常
+backup                    Team  90%   5%   2小时   封号
+新号                      ?     ?     ?    ?       正常"
+        );
+    }
+
+    #[test]
+    fn json_snapshot_has_stable_schema_and_masked_emails() {
+        let accounts = pool();
+        let options = ShareOptions {
+            emails: ShareEmails::Masked,
+            ..ShareOptions::default()
+        };
+        let snapshot = build(&accounts[..2], &options, as_of());
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&render(&snapshot, ShareFormat::Json))
+                .unwrap(),
+            json!({
+                "schema_version": 1,
+                "as_of": "2026-03-01T09:00:00+08:00",
+                "accounts": [
+                    {
+                        "name": "a-very-long-teammate-pool-account-name",
+                        "email": "po…@co….com",
+                        "plan": "Team",
+                        "five_hour_left": 40.0,
+                        "weekly_left": 60.0,
+                        "cache_age_minutes": 12,
+                        "status": "ok"
+                    },
+                    {
+                        "name": "新号",
+                        "email": "ne…@co….com",
+                        "plan": null,
+                        "five_hour_left": null,
+                        "weekly_left": null,
+                        "cache_age_minutes": null,
+                        "status": "ok"
+                    }
+                ]
+            })
+        );
+        let markdown = render(&snapshot, ShareFormat::Markdown);
+        assert!(markdown.contains("| 账号 | 邮箱 | 套餐 |"));
+        assert!(markdown.contains("| po…@co….com |"));
+    }
+
+    #[test]
+    fn never_leaks_ids_tokens_or_notes_unless_asked() {
+        let accounts = pool();
+        for format in [
+            ShareFormat::Markdown,
+            ShareFormat::PlainText,
+            ShareFormat::Json,
+        ] {
+            let text = render(&build(&accounts, &ShareOptions::default(), as_of()), format);
+            assert!(accounts.iter().all(|a| !text.contains(&a.id)));
+            assert!(!text.contains("eyJ") && !text.contains("内部备注"));
+            assert!(!text.contains("@corp.com"));
+        }
+
+        let with_notes = ShareOptions {
+            include_notes: true,
+            ..ShareOptions::default()
+        };
+        let text = render(
+            &build(&accounts, &with_notes, as_of()),
+            ShareFormat::Markdown,
+        );
+        assert!(text.contains("| 备注 |") && text.contains("内部备注"));
+
+        // 演示模式压过其它选项
+        let presenting = ShareOptions {
+            emails: ShareEmails::Full,
+            include_notes: true,
+            presentation: true,
+        };
+        let text = render(
+            &build(&accounts, &presenting, as_of()),
+            ShareFormat::PlainText,
+        );
+        assert!(!text.contains("work@corp.com") && !text.contains("内部备注"));
+        assert!(text.contains("wo…"));
+    }
+}
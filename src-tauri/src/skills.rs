@@ -105,15 +105,12 @@ impl Default for SkillData {
 
 /// SSOT 目录：~/.codex-switcher/skills/
 fn ssot_dir() -> PathBuf {
-    dirs::home_dir()
-        .unwrap()
-        .join(".codex-switcher")
-        .join("skills")
+    crate::paths::data_dir().join("skills")
 }
 
 /// 各 CLI 的 skills 目录（跨平台）
 fn app_skills_dir(app: &str) -> Option<PathBuf> {
-    let home = dirs::home_dir()?;
+    let home = crate::paths::home_dir();
     match app {
         "codex" => Some(home.join(".codex").join("skills")),
         "claude" => Some(home.join(".claude").join("skills")),
@@ -136,7 +133,7 @@ fn app_skills_dir(app: &str) -> Option<PathBuf> {
 /// 初始化 SSOT：如果 ~/.codex/skills/ 是真实目录（非 symlink），迁移到 SSOT
 pub fn init_ssot() -> Result<(), String> {
     let ssot = ssot_dir();
-    let codex_skills = dirs::home_dir().unwrap().join(".codex").join("skills");
+    let codex_skills = crate::paths::codex_dir().join("skills");
 
     // SSOT 已存在且 codex 已经是 symlink → 不需要迁移
     if ssot.exists() && codex_skills.is_symlink() {
@@ -260,10 +257,7 @@ fn link_app_to_ssot(app: &str) -> Result<(), String> {
 }
 
 fn data_path() -> PathBuf {
-    dirs::home_dir()
-        .unwrap()
-        .join(".codex-switcher")
-        .join("skills.json")
+    crate::paths::data_dir().join("skills.json")
 }
 
 // ────────────────────────────────────────────────────────────────
@@ -0,0 +1,291 @@
+//! 试运行暂存：把账号的 auth.json 写到另一个 CODEX_HOME
+//!
+//! 新导入的账号想先跑通再用，又不想打断当前的 Codex 会话：`stage_account` 把账号的
+//! 规范 auth.json 写到暂存目录（默认 `~/.codex-switcher/staging/<id>/`，目录 0700、文件 0600），
+//! 返回 `CODEX_HOME=<dir> codex`，在终端里起一个一次性会话即可。
+//! - 暂存副本记在 `~/.codex-switcher/staging.json`，`cleanup_staging` / 删号时据此清理
+//! - 同步 / 冲突检测只认 `~/.codex/auth.json`，从不读暂存副本；暂存目录也不允许指向它
+//! - 数据只从 Store 流向暂存副本：重复暂存直接用 Store 覆盖，一次性会话里轮换出的新 token
+//!   不会回流
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::account::{Account, AccountStore};
+
+/// 一份暂存副本
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct StagedAuth {
+    pub account_id: String,
+    /// 作为 CODEX_HOME 的目录
+    pub codex_home: PathBuf,
+    pub auth_path: PathBuf,
+    pub staged_at: DateTime<Utc>,
+    /// 默认暂存目录归我们管，清理时整个删掉（含一次性会话留下的 sessions 等）；
+    /// 用户指定的目录只删 auth.json
+    #[serde(default)]
+    pub owned: bool,
+}
+
+/// `stage_account` 的返回
+#[derive(Serialize, Clone, Debug)]
+pub struct StageResult {
+    pub auth_path: PathBuf,
+    pub codex_home: PathBuf,
+    pub shell_snippet: String,
+}
+
+pub struct Staging {
+    /// `~/.codex-switcher`
+    root: PathBuf,
+    /// `~/.codex`：真实登录所在，不允许暂存到这里
+    codex_dir: PathBuf,
+}
+
+impl Staging {
+    pub fn new(root: PathBuf, codex_dir: PathBuf) -> Self {
+        Self { root, codex_dir }
+    }
+
+    pub fn system() -> Self {
+        Self::new(crate::paths::data_dir(), crate::paths::codex_dir())
+    }
+
+    fn index_path(&self) -> PathBuf {
+        self.root.join("staging.json")
+    }
+
+    pub fn default_dir(&self, account_id: &str) -> PathBuf {
+        self.root.join("staging").join(account_id)
+    }
+
+    /// 已登记且文件还在的暂存副本（手动删掉的自动剔除）
+    pub fn list(&self) -> Vec<StagedAuth> {
+        let Ok(content) = fs::read_to_string(self.index_path()) else {
+            return Vec::new();
+        };
+        let entries: Vec<StagedAuth> = serde_json::from_str(&content).unwrap_or_default();
+        entries
+            .into_iter()
+            .filter(|e| e.auth_path.exists())
+            .collect()
+    }
+
+    fn save_index(&self, entries: &[StagedAuth]) -> Result<(), String> {
+        let content =
+            serde_json::to_string_pretty(entries).map_err(|e| format!("序列化失败: {}", e))?;
+        fs::create_dir_all(&self.root).map_err(|e| format!("创建目录失败: {}", e))?;
+        crate::atomic_write::write_atomic(&self.index_path(), content.as_bytes())
+    }
+
+    /// 把账号的规范 auth.json 写进暂存目录；同一路径再次暂存时用 Store 覆盖
+    pub fn stage(&self, account: &Account, dir: Option<PathBuf>) -> Result<StageResult, String> {
+        if account.id.is_empty() || account.id.contains(['/', '\\']) || account.id.contains("..") {
+            return Err(format!("账号 id 不能用作目录名: {}", account.id));
+        }
+        let owned = dir.is_none();
+        let codex_home = dir.unwrap_or_else(|| self.default_dir(&account.id));
+        if same_dir(&codex_home, &self.codex_dir) {
+            return Err(format!(
+                "暂存目录不能是 Codex 的正式目录: {}",
+                codex_home.display()
+            ));
+        }
+
+        let auth_path = codex_home.join("auth.json");
+        AccountStore::write_auth_file(&auth_path, &account.to_codex_auth_value())?;
+
+        let mut entries = self.list();
+        entries.retain(|e| e.auth_path != auth_path);
+        entries.push(StagedAuth {
+            account_id: account.id.clone(),
+            codex_home: codex_home.clone(),
+            auth_path: auth_path.clone(),
+            staged_at: Utc::now(),
+            owned,
+        });
+        self.save_index(&entries)?;
+
+        Ok(StageResult {
+            shell_snippet: shell_snippet(&codex_home),
+            auth_path,
+            codex_home,
+        })
+    }
+
+    /// 删除某个账号（None = 全部）的暂存副本，返回删掉的份数
+    pub fn cleanup(&self, account_id: Option<&str>) -> Result<usize, String> {
+        let (remove, keep): (Vec<_>, Vec<_>) = self
+            .list()
+            .into_iter()
+            .partition(|e| account_id.is_none_or(|id| e.account_id == id));
+
+        for entry in &remove {
+            if entry.owned {
+                fs::remove_dir_all(&entry.codex_home)
+                    .map_err(|e| format!("删除暂存目录失败: {}", e))?;
+            } else {
+                fs::remove_file(&entry.auth_path)
+                    .map_err(|e| format!("删除暂存 auth.json 失败: {}", e))?;
+                // 用户目录里还有别的东西就留着
+                let _ = fs::remove_dir(&entry.codex_home);
+            }
+        }
+        self.save_index(&keep)?;
+        Ok(remove.len())
+    }
+}
+
+fn same_dir(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+/// `CODEX_HOME=<dir> codex`，路径里有空格等特殊字符时加单引号
+pub fn shell_snippet(codex_home: &Path) -> String {
+    let dir = codex_home.to_string_lossy();
+    let plain = dir
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "/._-~:\\".contains(c));
+    if plain {
+        format!("CODEX_HOME={} codex", dir)
+    } else {
+        format!("CODEX_HOME='{}' codex", dir.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_root() -> PathBuf {
+        std::env::temp_dir().join(format!("cs-staging-{}", uuid::Uuid::new_v4()))
+    }
+
+    fn account(id: &str, access: &str) -> Account {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "name": id,
+            "email": format!("{}@example.com", id),
+            "plan_type": "plus",
+            "auth_json": {
+                "tokens": {
+                    "access_token": access,
+                    "refresh_token": "rt",
+                    "id_token": "it",
+                    "account_id": "acct"
+                }
+            },
+            "created_at": "2026-01-01T00:00:00Z",
+            "last_used": null
+        }))
+        .unwrap()
+    }
+
+    fn staged_access_token(path: &Path) -> String {
+        let auth: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(path).unwrap()).unwrap();
+        auth.pointer("/tokens/access_token")
+            .and_then(|v| v.as_str())
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn stages_into_default_dir_with_private_permissions() {
+        let root = temp_root();
+        let staging = Staging::new(root.clone(), root.join("codex"));
+        let result = staging.stage(&account("a", "at-1"), None).unwrap();
+
+        assert_eq!(result.codex_home, root.join("staging").join("a"));
+        assert_eq!(result.auth_path, result.codex_home.join("auth.json"));
+        assert_eq!(
+            result.shell_snippet,
+            format!("CODEX_HOME={} codex", result.codex_home.display())
+        );
+        assert_eq!(staged_access_token(&result.auth_path), "at-1");
+        assert!(!root.join("codex").exists(), "不碰正式目录");
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |p: &Path| fs::metadata(p).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode(&result.codex_home), 0o700);
+            assert_eq!(mode(&result.auth_path), 0o600);
+        }
+
+        let listed = staging.list();
+        assert_eq!(listed.len(), 1);
+        assert!(listed[0].owned);
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn restaging_overwrites_from_store() {
+        let root = temp_root();
+        let staging = Staging::new(root.clone(), root.join("codex"));
+        let first = staging.stage(&account("a", "at-1"), None).unwrap();
+        // 一次性会话里 Codex 轮换了 token
+        fs::write(&first.auth_path, r#"{"tokens":{"access_token":"rotated"}}"#).unwrap();
+
+        let second = staging.stage(&account("a", "at-2"), None).unwrap();
+        assert_eq!(second.auth_path, first.auth_path);
+        assert_eq!(staged_access_token(&second.auth_path), "at-2");
+        assert_eq!(staging.list().len(), 1, "同一路径只登记一次");
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn refuses_real_codex_dir() {
+        let root = temp_root();
+        let codex = root.join("codex");
+        fs::create_dir_all(&codex).unwrap();
+        let staging = Staging::new(root.clone(), codex.clone());
+
+        let err = staging
+            .stage(&account("a", "at-1"), Some(codex.join(".")))
+            .unwrap_err();
+        assert!(err.contains("正式目录"));
+        assert!(!codex.join("auth.json").exists());
+        assert!(staging.stage(&account("../x", "at-1"), None).is_err());
+        let _ = fs::remove_dir_all(root);
+    }
+
+    #[test]
+    fn cleanup_by_account_and_all() {
+        let root = temp_root();
+        let staging = Staging::new(root.clone(), root.join("codex"));
+        let a = staging.stage(&account("a", "at-a"), None).unwrap();
+        let custom = root.join("my dir");
+        fs::create_dir_all(&custom).unwrap();
+        fs::write(custom.join("config.toml"), "").unwrap();
+        let b = staging
+            .stage(&account("b", "at-b"), Some(custom.clone()))
+            .unwrap();
+        assert_eq!(
+            b.shell_snippet,
+            format!("CODEX_HOME='{}' codex", custom.display())
+        );
+        // 一次性会话留下的文件
+        fs::create_dir_all(a.codex_home.join("sessions")).unwrap();
+
+        assert_eq!(staging.cleanup(Some("a")).unwrap(), 1);
+        assert!(!a.codex_home.exists(), "默认目录整个删掉");
+        assert!(b.auth_path.exists());
+
+        assert_eq!(staging.cleanup(None).unwrap(), 1);
+        assert!(!b.auth_path.exists());
+        assert!(
+            custom.join("config.toml").exists(),
+            "用户目录里别的文件保留"
+        );
+        assert!(staging.list().is_empty());
+        assert_eq!(staging.cleanup(None).unwrap(), 0);
+        let _ = fs::remove_dir_all(root);
+    }
+}
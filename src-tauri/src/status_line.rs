@@ -0,0 +1,195 @@
+//! Shell 提示符用的单行状态（starship / powerlevel10k 等）
+//!
+//! 只读 `accounts.json` 里的当前账号和 cached_quota，不联网、不跑迁移、不写盘，
+//! 保证提示符每次渲染都在几十毫秒内返回。入口：
+//! - Tauri command `get_status_line`（走内存里的 store）
+//! - `codex-switcher-status [--format <模板>]` 小工具（直接读磁盘）
+//!
+//! 模板占位符：`{name}` `{email}` `{plan}` `{5h_left}` `{weekly_left}` `{reset_in}`；
+//! `{plan}` 是套餐展示名（见 `plan::display_plan`）；
+//! `{{` / `}}` 输出字面花括号；未知占位符原样保留；缺数据渲染为 `-`。
+
+use crate::account::{Account, AccountStore};
+
+/// 默认模板
+pub const DEFAULT_FORMAT: &str = "{name} {5h_left}% {weekly_left}%";
+
+/// 缺数据时的占位
+const MISSING: &str = "-";
+
+/// 渲染用的字段（全部可缺）
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StatusFields {
+    pub name: Option<String>,
+    pub email: Option<String>,
+    pub plan: Option<String>,
+    pub five_hour_left: Option<f64>,
+    pub weekly_left: Option<f64>,
+    /// 距 5h 窗口重置的秒数
+    pub reset_in_secs: Option<i64>,
+}
+
+impl StatusFields {
+    /// 从账号提取；`now` 为 unix 秒
+    pub fn from_account(account: &Account, now: i64) -> Self {
+        let quota = account.cached_quota.as_ref();
+        Self {
+            name: Some(account.name.clone()),
+            email: AccountStore::extract_email(&account.auth_json),
+            plan: quota.map(|q| crate::plan::display_plan(&q.plan_type)),
+            five_hour_left: quota.map(|q| q.five_hour_left),
+            weekly_left: quota.map(|q| q.weekly_left),
+            reset_in_secs: quota
+                .and_then(|q| q.five_hour_reset_at)
+                .map(|at| at - now)
+                .filter(|secs| *secs > 0),
+        }
+    }
+
+    fn lookup(&self, key: &str) -> Option<String> {
+        let value = match key {
+            "name" => self.name.clone(),
+            "email" => self.email.clone(),
+            "plan" => self.plan.clone(),
+            "5h_left" => self.five_hour_left.map(|v| format!("{:.0}", v)),
+            "weekly_left" => self.weekly_left.map(|v| format!("{:.0}", v)),
+            "reset_in" => self.reset_in_secs.map(format_reset_in),
+            _ => return None,
+        };
+        Some(
+            value
+                .filter(|v| !v.is_empty())
+                .unwrap_or_else(|| MISSING.to_string()),
+        )
+    }
+}
+
+/// `2h13m` / `45m`
+fn format_reset_in(secs: i64) -> String {
+    let mins = (secs + 59) / 60;
+    if mins >= 60 {
+        format!("{}h{}m", mins / 60, mins % 60)
+    } else {
+        format!("{}m", mins)
+    }
+}
+
+/// 按模板渲染（纯函数）
+pub fn render(template: &str, fields: &StatusFields) -> String {
+    let mut out = String::with_capacity(template.len() + 16);
+    let mut chars = template.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let mut key = String::new();
+                let mut closed = false;
+                for k in chars.by_ref() {
+                    if k == '}' {
+                        closed = true;
+                        break;
+                    }
+                    key.push(k);
+                }
+                match fields.lookup(&key).filter(|_| closed) {
+                    Some(v) => out.push_str(&v),
+                    None => {
+                        out.push('{');
+                        out.push_str(&key);
+                        if closed {
+                            out.push('}');
+                        }
+                    }
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// 基于已加载的 store 生成状态行；没有当前账号时返回空串
+pub fn from_store(store: &AccountStore, format: Option<&str>) -> String {
+    let Some(account) = store.current.as_ref().and_then(|id| store.accounts.get(id)) else {
+        return String::new();
+    };
+    let fields = StatusFields::from_account(account, chrono::Utc::now().timestamp());
+    render(format.unwrap_or(DEFAULT_FORMAT), &fields)
+}
+
+/// 直接读磁盘上的 accounts.json（不走 `AccountStore::load` 的迁移 / 回写）。
+/// 文件不存在或解析失败都返回空串，调用方照常 exit 0。
+pub fn from_disk(format: Option<&str>) -> String {
+    let Ok(content) = std::fs::read_to_string(AccountStore::config_path()) else {
+        return String::new();
+    };
+    match serde_json::from_str::<AccountStore>(&content) {
+        Ok(store) => from_store(&store, format),
+        Err(_) => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn full() -> StatusFields {
+        StatusFields {
+            name: Some("work".to_string()),
+            email: Some("a@b.com".to_string()),
+            plan: Some("plus".to_string()),
+            five_hour_left: Some(72.4),
+            weekly_left: Some(40.0),
+            reset_in_secs: Some(2 * 3600 + 13 * 60),
+        }
+    }
+
+    #[test]
+    fn default_format_renders_name_and_quotas() {
+        assert_eq!(render(DEFAULT_FORMAT, &full()), "work 72% 40%");
+        assert_eq!(
+            render("{email} {plan} {reset_in}", &full()),
+            "a@b.com plus 2h13m"
+        );
+    }
+
+    #[test]
+    fn missing_fields_render_as_dash() {
+        let fields = StatusFields {
+            name: Some("work".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(render(DEFAULT_FORMAT, &fields), "work -% -%");
+        assert_eq!(render("{reset_in}", &fields), "-");
+    }
+
+    #[test]
+    fn unknown_placeholders_are_kept_verbatim() {
+        assert_eq!(render("{nope} {name}", &full()), "{nope} work");
+        assert_eq!(render("tail {name", &full()), "tail {name");
+    }
+
+    #[test]
+    fn doubled_braces_are_literal() {
+        assert_eq!(render("{{{name}}}", &full()), "{work}");
+        assert_eq!(render("{{name}}", &full()), "{name}");
+    }
+
+    #[test]
+    fn reset_in_rounds_up_to_minutes() {
+        assert_eq!(format_reset_in(30), "1m");
+        assert_eq!(format_reset_in(3600), "1h0m");
+    }
+
+    #[test]
+    fn empty_store_renders_nothing() {
+        assert_eq!(from_store(&AccountStore::default(), None), "");
+    }
+}
@@ -0,0 +1,390 @@
+//! 账号库备份
+//!
+//! 两种备份都放在 `<数据目录>/backups/`，内容和落盘的 accounts.json 一样走 `persisted_view`（0600）：
+//! 文件存储模式下带明文 token；Keychain 模式下 token 留在凭据库，备份里只有 `token_ref`，
+//! 恢复时由 [`read`] 从凭据库补回（凭据库里已经没有的账号要重新登录）。
+//!
+//! - 替换式导入前（[`BackupKind::Import`]，`accounts-<时间>.json`）：粘错一段 JSON 也能找回来，
+//!   写备份失败就不导入；超出设置 `store_backup_keep` 份时从最老的删起（0 = 不自动删）
+//! - 定时（[`BackupKind::Scheduled`]，`auto-accounts-<时间>.json`）：设置 `auto_backup` 打开时
+//!   调度器每天最多写一份（[`scheduled_due`]），超过 `backup_retention_days` 天的删掉（0 = 不删）
+//!
+//! 两种各删各的。写备份要在持有账号库锁时调（和 `AccountStore::save` 一样），
+//! 拿到的一定是一份完整的库，不会和保存交错；文件本身走 `atomic_write`。
+//! [`list`] 给 `list_store_backups` 用，按时间从新到旧；`restore_store_backup` 按文件名取备份时
+//! 走 [`resolve`]，只认备份目录里的备份文件。
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use serde::Serialize;
+
+use crate::account::AccountStore;
+use crate::token_store::{self, TokenBackend};
+
+const SUFFIX: &str = ".json";
+const STAMP_FORMAT: &str = "%Y%m%dT%H%M%S%.3fZ";
+/// 定时备份的最小间隔
+const SCHEDULED_INTERVAL_HOURS: i64 = 24;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupKind {
+    /// 替换式导入前
+    Import,
+    /// 调度器每天一份
+    Scheduled,
+}
+
+impl BackupKind {
+    const ALL: [BackupKind; 2] = [BackupKind::Import, BackupKind::Scheduled];
+
+    fn prefix(self) -> &'static str {
+        match self {
+            BackupKind::Import => "accounts-",
+            BackupKind::Scheduled => "auto-accounts-",
+        }
+    }
+}
+
+/// 一份备份
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StoreBackup {
+    pub path: String,
+    pub file_name: String,
+    pub kind: BackupKind,
+    /// 从文件名里的时间戳解析
+    pub created_at: DateTime<Utc>,
+    pub size_bytes: u64,
+}
+
+/// 备份目录
+pub fn backups_dir() -> PathBuf {
+    crate::paths::data_dir().join("backups")
+}
+
+/// 文件名里的种类、时间和序号；同一毫秒里的第二份起带 `-2` 之类的后缀
+fn parse_name(file_name: &str) -> Option<(BackupKind, DateTime<Utc>, u32)> {
+    let (kind, stem) = BackupKind::ALL.into_iter().find_map(|kind| {
+        let stem = file_name
+            .strip_prefix(kind.prefix())?
+            .strip_suffix(SUFFIX)?;
+        Some((kind, stem))
+    })?;
+    let (stamp, seq) = match stem.split_once('-') {
+        Some((stamp, seq)) => (stamp, seq.parse().ok()?),
+        None => (stem, 1),
+    };
+    let created_at = NaiveDateTime::parse_from_str(stamp, STAMP_FORMAT)
+        .ok()?
+        .and_utc();
+    Some((kind, created_at, seq))
+}
+
+/// `dir` 里的备份，从新到旧；目录不存在时为空
+pub fn list(dir: &Path) -> Vec<StoreBackup> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut backups: Vec<(u32, StoreBackup)> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            let (kind, created_at, seq) = parse_name(&file_name)?;
+            let backup = StoreBackup {
+                kind,
+                created_at,
+                size_bytes: entry.metadata().ok()?.len(),
+                path: entry.path().display().to_string(),
+                file_name,
+            };
+            Some((seq, backup))
+        })
+        .collect();
+    backups.sort_by(|(seq_a, a), (seq_b, b)| {
+        b.created_at
+            .cmp(&a.created_at)
+            .then_with(|| seq_b.cmp(seq_a))
+    });
+    backups.into_iter().map(|(_, backup)| backup).collect()
+}
+
+/// `list_store_backups` 给出的文件名 → 备份目录里的路径。只接受本目录下、名字符合备份格式的文件，
+/// 带目录分隔符、`..`、绝对路径的一律拒绝，命令没法借它读任意文件
+pub fn resolve(dir: &Path, file_name: &str) -> Result<PathBuf, String> {
+    let plain = !file_name.contains(['/', '\\'])
+        && !file_name.contains("..")
+        && !Path::new(file_name).is_absolute();
+    if !plain || parse_name(file_name).is_none() {
+        return Err(format!("不是备份目录里的备份文件: {}", file_name));
+    }
+    let path = dir.join(file_name);
+    if !path.is_file() {
+        return Err(format!("备份不存在: {}", file_name));
+    }
+    Ok(path)
+}
+
+/// 读一份备份，返回可以直接交给导入的账号库 JSON；Keychain 模式写的备份按 `token_ref` 补回 token
+pub fn read(path: &Path) -> Result<String, String> {
+    read_with(path, token_store::keychain())
+}
+
+/// 同 [`read`]，从 `backend` 补 token
+pub fn read_with(path: &Path, backend: &dyn TokenBackend) -> Result<String, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("读取备份失败: {}", e))?;
+    let mut store = AccountStore::import(&text)?;
+    // 补不回来的账号保留 token_ref、没有 refresh_token，替换式导入会拒绝、合并式会跳过
+    let failures = store.hydrate_tokens_with(backend);
+    if !failures.is_empty() {
+        eprintln!(
+            "[StoreBackups] {} 个账号没能从凭据库补回 token",
+            failures.len()
+        );
+    }
+    serde_json::to_string(&store).map_err(|e| format!("序列化账号库失败: {}", e))
+}
+
+fn list_kind(dir: &Path, kind: BackupKind) -> impl Iterator<Item = StoreBackup> {
+    list(dir).into_iter().filter(move |b| b.kind == kind)
+}
+
+/// 把 `store` 整份写成一份新备份，返回它；调用方应持有账号库锁
+pub fn write(
+    dir: &Path,
+    kind: BackupKind,
+    store: &AccountStore,
+    now: DateTime<Utc>,
+) -> Result<StoreBackup, String> {
+    write_with(dir, kind, store, token_store::keychain(), now)
+}
+
+/// 同 [`write`]；Keychain 模式下 token 写进 `backend`，和 `AccountStore::save_with` 一样
+pub fn write_with(
+    dir: &Path,
+    kind: BackupKind,
+    store: &AccountStore,
+    backend: &dyn TokenBackend,
+    now: DateTime<Utc>,
+) -> Result<StoreBackup, String> {
+    let (view, failures) = store.persisted_view(backend);
+    for (_, err) in &failures {
+        token_store::record_error(err);
+    }
+    let content =
+        serde_json::to_string_pretty(&view).map_err(|e| format!("序列化账号库失败: {}", e))?;
+    fs::create_dir_all(dir).map_err(|e| format!("创建备份目录失败: {}", e))?;
+    crate::account::ensure_private_dir_permissions(dir)?;
+
+    let stamp = now.format(STAMP_FORMAT).to_string();
+    let path = (1..)
+        .map(|n| match n {
+            1 => dir.join(format!("{}{}{}", kind.prefix(), stamp, SUFFIX)),
+            n => dir.join(format!("{}{}-{}{}", kind.prefix(), stamp, n, SUFFIX)),
+        })
+        .find(|candidate| !candidate.exists())
+        .unwrap_or_default();
+    crate::atomic_write::write_atomic(&path, content.as_bytes())?;
+    crate::account::ensure_private_file_permissions(&path)?;
+    Ok(StoreBackup {
+        path: path.display().to_string(),
+        file_name: path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned(),
+        kind,
+        created_at: now,
+        size_bytes: content.len() as u64,
+    })
+}
+
+fn remove(backups: impl Iterator<Item = StoreBackup>) -> usize {
+    let mut removed = 0;
+    for old in backups {
+        match fs::remove_file(&old.path) {
+            Ok(()) => removed += 1,
+            Err(e) => eprintln!("[StoreBackups] 删除旧备份 {} 失败: {}", old.file_name, e),
+        }
+    }
+    removed
+}
+
+/// `kind` 的备份只留最新的 `keep` 份（0 = 不删），返回删了几份
+pub fn prune_keep_latest(dir: &Path, kind: BackupKind, keep: u32) -> usize {
+    if keep == 0 {
+        return 0;
+    }
+    remove(list_kind(dir, kind).skip(keep as usize))
+}
+
+/// 删掉 `kind` 里超过 `days` 天的备份（0 = 不删），返回删了几份
+pub fn prune_older_than(dir: &Path, kind: BackupKind, days: u32, now: DateTime<Utc>) -> usize {
+    if days == 0 {
+        return 0;
+    }
+    let cutoff = now - Duration::days(i64::from(days));
+    remove(list_kind(dir, kind).filter(|b| b.created_at < cutoff))
+}
+
+/// 距上一份定时备份满一天了（或者还没有）
+pub fn scheduled_due(dir: &Path, now: DateTime<Utc>) -> bool {
+    list_kind(dir, BackupKind::Scheduled)
+        .next()
+        .is_none_or(|last| now - last.created_at >= Duration::hours(SCHEDULED_INTERVAL_HOURS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("store-backups-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn store_with_token() -> (AccountStore, String) {
+        let mut store = AccountStore::default();
+        let id = store
+            .add_account(
+                "a".to_string(),
+                serde_json::json!({ "tokens": { "refresh_token": "rt-a" } }),
+                None,
+            )
+            .id;
+        (store, id)
+    }
+
+    #[test]
+    fn import_backups_hold_the_whole_store_and_keep_the_latest() {
+        let dir = temp_dir("import");
+        assert!(list(&dir).is_empty());
+        let (store, id) = store_with_token();
+
+        let start = Utc::now();
+        let first = write(&dir, BackupKind::Import, &store, start).unwrap();
+        let restored = AccountStore::import(&fs::read_to_string(&first.path).unwrap()).unwrap();
+        assert_eq!(
+            restored.accounts[&id].refresh_token.as_deref(),
+            Some("rt-a")
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&first.path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // 同一毫秒再写一份不会盖掉上一份
+        let second = write(&dir, BackupKind::Import, &store, start).unwrap();
+        assert_ne!(first.path, second.path);
+        let third = write(
+            &dir,
+            BackupKind::Import,
+            &store,
+            start + Duration::seconds(1),
+        )
+        .unwrap();
+        let scheduled = write(&dir, BackupKind::Scheduled, &store, start).unwrap();
+
+        assert_eq!(prune_keep_latest(&dir, BackupKind::Import, 2), 1);
+        let paths: Vec<String> = list(&dir).into_iter().map(|b| b.path).collect();
+        assert_eq!(paths, vec![third.path, second.path, scheduled.path]);
+        assert_eq!(prune_keep_latest(&dir, BackupKind::Import, 0), 0);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn only_backup_files_inside_the_directory_resolve() {
+        let dir = temp_dir("resolve");
+        let (store, _) = store_with_token();
+        let backup = write(&dir, BackupKind::Scheduled, &store, Utc::now()).unwrap();
+        assert_eq!(
+            resolve(&dir, &backup.file_name).unwrap(),
+            Path::new(&backup.path)
+        );
+
+        fs::write(dir.join("notes.json"), "{}").unwrap();
+        for bad in [
+            "notes.json",
+            "../accounts.json",
+            "/etc/passwd",
+            "sub/accounts-20260101T000000.000Z.json",
+            "..\\accounts-20260101T000000.000Z.json",
+            "accounts-20260101T000000.000Z.json",
+            "",
+        ] {
+            assert!(resolve(&dir, bad).is_err(), "{}", bad);
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn keychain_mode_backups_hold_no_tokens_and_restore_from_the_keychain() {
+        let dir = temp_dir("keychain");
+        let (mut store, id) = store_with_token();
+        store.accounts.get_mut(&id).unwrap().auth_json["tokens"]["access_token"] =
+            serde_json::json!("at-a");
+        store.settings.token_storage = token_store::TokenStorage::Keychain;
+        store.settings.keychain_include_access_tokens = true;
+        let backend = token_store::MemoryBackend::default();
+
+        // 导入前的和每天定时的走同一条写入路径
+        for kind in BackupKind::ALL {
+            let backup = write_with(&dir, kind, &store, &backend, Utc::now()).unwrap();
+            let content = fs::read_to_string(&backup.path).unwrap();
+            assert!(!content.contains("rt-a"), "{}", content);
+            assert!(!content.contains("at-a"), "{}", content);
+            assert!(content.contains("token_ref"));
+
+            let restored =
+                AccountStore::import(&read_with(Path::new(&backup.path), &backend).unwrap())
+                    .unwrap();
+            assert_eq!(
+                restored.accounts[&id].refresh_token.as_deref(),
+                Some("rt-a")
+            );
+            assert_eq!(restored.accounts[&id].token_ref, None);
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn scheduled_backups_run_daily_and_expire_by_age() {
+        let dir = temp_dir("scheduled");
+        let (store, _) = store_with_token();
+        let now = Utc::now();
+        assert!(scheduled_due(&dir, now));
+
+        let old = write(
+            &dir,
+            BackupKind::Scheduled,
+            &store,
+            now - Duration::days(10),
+        )
+        .unwrap();
+        assert!(scheduled_due(&dir, now));
+        let recent = write(
+            &dir,
+            BackupKind::Scheduled,
+            &store,
+            now - Duration::hours(2),
+        )
+        .unwrap();
+        assert!(!scheduled_due(&dir, now));
+        assert!(scheduled_due(&dir, now + Duration::hours(22)));
+        // 导入前备份不算定时备份，也不按天数删
+        write(&dir, BackupKind::Import, &store, now - Duration::days(30)).unwrap();
+        assert!(!scheduled_due(&dir, now));
+
+        assert_eq!(prune_older_than(&dir, BackupKind::Scheduled, 0, now), 0);
+        assert_eq!(prune_older_than(&dir, BackupKind::Scheduled, 7, now), 1);
+        assert!(!Path::new(&old.path).exists());
+        assert!(Path::new(&recent.path).exists());
+        assert_eq!(list(&dir).len(), 2);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
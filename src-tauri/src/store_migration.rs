@@ -0,0 +1,204 @@
+//! accounts.json 的版本迁移
+//!
+//! 以前 `AccountStore::load` 直接反序列化，解析失败就退回空库，接着任何一次保存都会拿空库
+//! 覆盖原文件。现在先读成 `Value`，按 `version` 依次跑 [`MIGRATIONS`] 升到
+//! [`STORE_VERSION`]，再反序列化成当前结构：
+//! - 比 [`STORE_VERSION`] 新的文件（新版本写的）直接报错，不猜
+//! - 迁移或反序列化失败算文件损坏（[`ParseError::Corrupt`]），怎么恢复见 `store_recovery`
+//!
+//! 改 accounts.json 的结构（改名、改类型、挪位置）时：[`STORE_VERSION`] +1，
+//! 在 [`MIGRATIONS`] 末尾加一个 `migrate_vN_to_vN+1`，再配一个旧格式的测试样例。
+//! 只新增带默认值的字段不用升版本。
+
+use serde_json::Value;
+
+use crate::account::AccountStore;
+
+/// 当前代码写出的 accounts.json 版本
+pub const STORE_VERSION: u32 = 1;
+
+type Migration = fn(&mut Value) -> Result<(), String>;
+
+/// accounts.json 读不进来的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// 更新版本的程序写的：文件没坏，原样留着等升级
+    Newer(String),
+    /// 不是合法 JSON、迁移失败或结构对不上
+    Corrupt(String),
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::Newer(message) | ParseError::Corrupt(message) => f.write_str(message),
+        }
+    }
+}
+
+/// 第 n 项把版本 n 的文档升到 n+1
+const MIGRATIONS: [Migration; STORE_VERSION as usize] = [migrate_v0_to_v1];
+
+/// 没有版本号的老文件（`version` 缺失或为 0）：账号条目里缺 `id` 的用所在的键补上，
+/// 缺 `created_at` 的补成迁移时间——以前这两样缺一个整份文件都解析不了
+fn migrate_v0_to_v1(doc: &mut Value) -> Result<(), String> {
+    let now = Value::String(chrono::Utc::now().to_rfc3339());
+    let fill = |key: &str, account: &mut Value| -> Result<(), String> {
+        let account = account
+            .as_object_mut()
+            .ok_or_else(|| format!("账号 {} 不是对象", key))?;
+        account
+            .entry("id")
+            .or_insert_with(|| Value::String(key.to_string()));
+        account.entry("created_at").or_insert_with(|| now.clone());
+        Ok(())
+    };
+    if let Some(accounts) = doc.get_mut("accounts").and_then(Value::as_object_mut) {
+        for (key, account) in accounts.iter_mut() {
+            fill(key, account)?;
+        }
+    }
+    if let Some(trash) = doc.get_mut("trash").and_then(Value::as_object_mut) {
+        for (key, entry) in trash.iter_mut() {
+            if let Some(account) = entry.get_mut("account") {
+                fill(key, account)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 文档里记的版本；没有记为 0
+fn version_of(doc: &Value) -> Result<u32, String> {
+    match doc.get("version") {
+        None | Some(Value::Null) => Ok(0),
+        Some(v) => v
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .ok_or_else(|| format!("accounts.json 的 version 不是非负整数: {}", v)),
+    }
+}
+
+/// 把文档原地升到 [`STORE_VERSION`]，返回原来的版本
+pub fn migrate(doc: &mut Value) -> Result<u32, ParseError> {
+    if !doc.is_object() {
+        return Err(ParseError::Corrupt(
+            "accounts.json 不是 JSON 对象".to_string(),
+        ));
+    }
+    let from = version_of(doc).map_err(ParseError::Corrupt)?;
+    if from > STORE_VERSION {
+        return Err(ParseError::Newer(format!(
+            "accounts.json 是更新版本的 Codex Switcher 写的（版本 {}，本程序只认到 {}），\
+             请升级后再打开；原文件未改动",
+            from, STORE_VERSION
+        )));
+    }
+    for (version, step) in MIGRATIONS.iter().enumerate().skip(from as usize) {
+        step(doc).map_err(|e| {
+            ParseError::Corrupt(format!("accounts.json 从版本 {} 迁移失败: {}", version, e))
+        })?;
+        doc["version"] = Value::from(version as u32 + 1);
+    }
+    Ok(from)
+}
+
+/// 解析 accounts.json 的内容：迁移到当前版本后反序列化；返回账号库和原来的版本
+pub fn parse(content: &str) -> Result<(AccountStore, u32), ParseError> {
+    let mut doc: Value = serde_json::from_str(content)
+        .map_err(|e| ParseError::Corrupt(format!("accounts.json 不是合法 JSON: {}", e)))?;
+    let from = migrate(&mut doc)?;
+    let store = serde_json::from_value(doc).map_err(|e| {
+        ParseError::Corrupt(format!("accounts.json 解析失败（版本 {}）: {}", from, e))
+    })?;
+    Ok((store, from))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 没有版本号时代的文件：没有 version，账号缺 id / created_at
+    const V0_FIXTURE: &str = r#"{
+        "accounts": {
+            "a1": {
+                "name": "work@example.com",
+                "auth_json": {"tokens": {"access_token": "at", "refresh_token": "rt"}},
+                "refresh_token": "rt",
+                "last_used": null,
+                "notes": "老账号"
+            },
+            "a2": {
+                "id": "a2",
+                "name": "home",
+                "auth_json": {},
+                "refresh_token": null,
+                "created_at": "2025-01-02T03:04:05Z",
+                "last_used": null,
+                "notes": null
+            }
+        },
+        "current": "a1",
+        "settings": {"refresh_interval_minutes": 30},
+        "trash": {
+            "t1": {
+                "account": {"name": "gone", "auth_json": {}, "refresh_token": null, "last_used": null, "notes": null},
+                "deleted_at": "2025-02-01T00:00:00Z"
+            }
+        }
+    }"#;
+
+    #[test]
+    fn pre_versioned_files_migrate_to_the_current_version() {
+        let (store, from) = parse(V0_FIXTURE).unwrap();
+        assert_eq!(from, 0);
+        assert_eq!(store.version, STORE_VERSION);
+        assert_eq!(store.current.as_deref(), Some("a1"));
+        let a1 = &store.accounts["a1"];
+        assert_eq!(a1.id, "a1");
+        assert_eq!(a1.notes.as_deref(), Some("老账号"));
+        assert_eq!(
+            store.accounts["a2"].created_at.to_rfc3339(),
+            "2025-01-02T03:04:05+00:00"
+        );
+        assert_eq!(store.trash["t1"].account.id, "t1");
+
+        // version 写成 0 的同样从头迁移
+        let mut doc: Value = serde_json::from_str(V0_FIXTURE).unwrap();
+        doc["version"] = Value::from(0);
+        assert_eq!(parse(&doc.to_string()).unwrap().1, 0);
+    }
+
+    #[test]
+    fn current_files_pass_through_untouched() {
+        let (store, _) = parse(V0_FIXTURE).unwrap();
+        let saved = serde_json::to_string(&store).unwrap();
+        let mut doc: Value = serde_json::from_str(&saved).unwrap();
+        let before = doc.clone();
+        assert_eq!(migrate(&mut doc).unwrap(), STORE_VERSION);
+        assert_eq!(doc, before);
+    }
+
+    #[test]
+    fn newer_or_broken_files_are_errors_not_empty_stores() {
+        let newer = format!(r#"{{"version": {}, "accounts": {{}}}}"#, STORE_VERSION + 1);
+        let err = parse(&newer).unwrap_err();
+        assert!(
+            matches!(&err, ParseError::Newer(m) if m.contains("更新版本")),
+            "{}",
+            err
+        );
+
+        let corrupt = |content: &str| match parse(content) {
+            Err(ParseError::Corrupt(message)) => message,
+            other => panic!("expected corrupt, got {:?}", other.map(|(_, v)| v)),
+        };
+        corrupt(r#"{"version": "two", "accounts": {}}"#);
+        corrupt("[]");
+        corrupt("{");
+        // 迁移走完但结构对不上（账号名是数字）
+        let err = corrupt(r#"{"accounts": {"a": {"name": 1, "auth_json": {}}}}"#);
+        assert!(err.contains("版本 0"), "{}", err);
+        assert!(corrupt(r#"{"accounts": {"a": 1}}"#).contains("迁移失败"));
+    }
+}
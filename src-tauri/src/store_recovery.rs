@@ -0,0 +1,362 @@
+//! accounts.json 损坏时的恢复
+//!
+//! 文件不存在是首次启动，用空库；文件在但读不出来就要分情况，不能悄悄从空库开始：
+//! - 更新版本写的、读文件本身失败：原文件不动，本次运行不写回（[`StoreHealth::Refused`]）
+//! - 内容坏了（不是 JSON、结构对不上）：把它改名成 `accounts.json.corrupt-<时间>` 留着，
+//!   再从新到旧试 `accounts.json.bak*` 恢复（[`StoreHealth::RestoredFromBackup`]），
+//!   没有可用备份才从空库开始（[`StoreHealth::StartedEmpty`]）。改名失败按 Refused 处理
+//!
+//! 备份由保存时轮转（[`rotate_backups`]）：写新内容前把旧文件复制成 `accounts.json.bak`，
+//! 更早的依次挪到 `.bak.1`、`.bak.2`，共留 [`BACKUP_GENERATIONS`] 份。额度刷新、切号时
+//! 几秒就存一次，每次都轮转的话几份备份转眼全是刚才的内容；所以最新一份备份不满
+//! [`BACKUP_MIN_INTERVAL`] 时不轮转，几份备份之间至少隔这么久。
+//! 结果经 `get_store_health` 给前端，非 Ok 时同时记进错误中心。
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::account::AccountStore;
+use crate::store_migration::{self, ParseError};
+
+/// 启动时账号库的加载情况
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum StoreHealth {
+    /// 正常加载，或首次启动还没有文件
+    #[default]
+    Ok,
+    /// 原文件损坏，已改名留存，账号从备份恢复
+    RestoredFromBackup {
+        corrupt_path: String,
+        backup_path: String,
+        error: String,
+    },
+    /// 原文件损坏，已改名留存，没有可用备份，从空库开始
+    StartedEmpty { corrupt_path: String, error: String },
+    /// 原文件没动，本次运行不写回
+    Refused { error: String },
+}
+
+impl StoreHealth {
+    /// 给错误中心的说明；正常时为 None
+    pub fn message(&self) -> Option<String> {
+        match self {
+            StoreHealth::Ok => None,
+            StoreHealth::RestoredFromBackup {
+                corrupt_path,
+                backup_path,
+                error,
+            } => Some(format!(
+                "accounts.json 已损坏（{}），原文件已改名为 {}，账号已从备份 {} 恢复",
+                error, corrupt_path, backup_path
+            )),
+            StoreHealth::StartedEmpty {
+                corrupt_path,
+                error,
+            } => Some(format!(
+                "accounts.json 已损坏（{}），原文件已改名为 {}，没有可用备份，账号库从空开始",
+                error, corrupt_path
+            )),
+            StoreHealth::Refused { error } => Some(format!(
+                "{}。本次运行不会写回 accounts.json，原文件保持不变",
+                error
+            )),
+        }
+    }
+}
+
+/// 加载结果：账号库和它原来的版本，或者不能写回的原因
+pub type Loaded = Result<(AccountStore, u32), String>;
+
+/// 保留几份备份
+pub const BACKUP_GENERATIONS: usize = 3;
+/// 两次轮转之间的最短间隔
+pub const BACKUP_MIN_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// 最新一份备份
+pub fn backup_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".bak")
+}
+
+/// 第 `generation` 份备份，0 是最新的
+fn backup_generation(path: &Path, generation: usize) -> PathBuf {
+    match generation {
+        0 => backup_path(path),
+        n => with_suffix(path, &format!(".bak.{}", n)),
+    }
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+/// 同目录下的 `<文件名>.bak*`，按修改时间从新到旧
+fn backups_newest_first(path: &Path) -> Vec<PathBuf> {
+    let prefix = backup_path(path)
+        .file_name()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .into_owned();
+    let Some(Ok(entries)) = path.parent().map(fs::read_dir) else {
+        return Vec::new();
+    };
+    let mut backups: Vec<_> = entries
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with(&prefix))
+        .filter_map(|entry| Some((entry.metadata().ok()?.modified().ok()?, entry.path())))
+        .collect();
+    backups.sort_by_key(|(modified, _)| std::cmp::Reverse(*modified));
+    backups.into_iter().map(|(_, path)| path).collect()
+}
+
+/// 保存前调用：现有的 `path` 复制成最新一份备份，更早的往后挪一格，最老的丢掉。
+/// 最新一份备份还不满 [`BACKUP_MIN_INTERVAL`] 时什么都不做。备份和原文件一样是 0600
+pub fn rotate_backups(path: &Path) -> Result<(), String> {
+    rotate_backups_at(path, SystemTime::now())
+}
+
+fn rotate_backups_at(path: &Path, now: SystemTime) -> Result<(), String> {
+    let latest = fs::metadata(backup_path(path)).and_then(|m| m.modified());
+    if let Ok(latest) = latest {
+        // 时钟往回拨（now 早于备份时间）时照常轮转，免得一直不备份
+        if now
+            .duration_since(latest)
+            .is_ok_and(|age| age < BACKUP_MIN_INTERVAL)
+        {
+            return Ok(());
+        }
+    }
+    let content = match fs::read(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(format!("读取 {} 失败: {}", path.display(), e)),
+    };
+    for generation in (1..BACKUP_GENERATIONS).rev() {
+        let from = backup_generation(path, generation - 1);
+        if from.exists() {
+            fs::rename(&from, backup_generation(path, generation))
+                .map_err(|e| format!("轮转备份 {} 失败: {}", from.display(), e))?;
+        }
+    }
+    let backup = backup_path(path);
+    crate::atomic_write::write_atomic(&backup, &content)?;
+    crate::account::ensure_private_file_permissions(&backup)
+}
+
+/// 读 `path` 处的账号库，损坏时按模块说明恢复
+pub fn load(path: &Path, now: DateTime<Utc>) -> (Loaded, StoreHealth) {
+    if !path.exists() {
+        return (
+            Ok((AccountStore::default(), store_migration::STORE_VERSION)),
+            StoreHealth::Ok,
+        );
+    }
+    let refused = |error: String| (Err(error.clone()), StoreHealth::Refused { error });
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) => return refused(format!("读取 accounts.json 失败: {}", e)),
+    };
+    let error = match store_migration::parse(&content) {
+        Ok(loaded) => return (Ok(loaded), StoreHealth::Ok),
+        Err(ParseError::Newer(error)) => return refused(error),
+        Err(ParseError::Corrupt(error)) => error,
+    };
+
+    // 同一毫秒里坏两次也不能盖掉上一份
+    let stamp = now.format("%Y%m%dT%H%M%S%.3fZ").to_string();
+    let corrupt = (1..)
+        .map(|n| match n {
+            1 => with_suffix(path, &format!(".corrupt-{}", stamp)),
+            n => with_suffix(path, &format!(".corrupt-{}-{}", stamp, n)),
+        })
+        .find(|candidate| !candidate.exists())
+        .unwrap_or_default();
+    if let Err(e) = fs::rename(path, &corrupt) {
+        return refused(format!(
+            "{}；改名留存到 {} 也失败了: {}",
+            error,
+            corrupt.display(),
+            e
+        ));
+    }
+    eprintln!(
+        "[StoreRecovery] accounts.json 已损坏（{}），已改名为 {}",
+        error,
+        corrupt.display()
+    );
+    let corrupt_path = corrupt.display().to_string();
+
+    for backup in backups_newest_first(path) {
+        let restored = fs::read_to_string(&backup)
+            .map_err(|e| e.to_string())
+            .and_then(|content| store_migration::parse(&content).map_err(|e| e.to_string()));
+        match restored {
+            Ok(loaded) => {
+                println!("[StoreRecovery] 已从备份 {} 恢复账号库", backup.display());
+                return (
+                    Ok(loaded),
+                    StoreHealth::RestoredFromBackup {
+                        corrupt_path,
+                        backup_path: backup.display().to_string(),
+                        error,
+                    },
+                );
+            }
+            Err(e) => eprintln!("[StoreRecovery] 备份 {} 也不可用: {}", backup.display(), e),
+        }
+    }
+    (
+        Ok((AccountStore::default(), store_migration::STORE_VERSION)),
+        StoreHealth::StartedEmpty {
+            corrupt_path,
+            error,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("store-recovery-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    const GOOD: &str = r#"{"version": 1, "accounts": {}, "current": "a1"}"#;
+
+    #[test]
+    fn missing_and_good_files_load() {
+        let dir = temp_dir("good");
+        let path = dir.join("accounts.json");
+
+        let (loaded, health) = load(&path, Utc::now());
+        assert!(loaded.unwrap().0.current.is_none());
+        assert_eq!(health, StoreHealth::Ok);
+
+        fs::write(&path, GOOD).unwrap();
+        let (loaded, health) = load(&path, Utc::now());
+        assert_eq!(loaded.unwrap().0.current.as_deref(), Some("a1"));
+        assert_eq!(health, StoreHealth::Ok);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn backups_rotate_and_keep_a_fixed_number_of_generations() {
+        let dir = temp_dir("rotate");
+        let path = dir.join("accounts.json");
+        rotate_backups(&path).unwrap();
+        assert!(!backup_path(&path).exists());
+
+        let start = SystemTime::now();
+        for n in 0..5 {
+            fs::write(&path, format!("v{}", n)).unwrap();
+            let at = start + BACKUP_MIN_INTERVAL * n;
+            rotate_backups_at(&path, at).unwrap();
+            // 备份的修改时间按模拟的时间算
+            fs::File::options()
+                .write(true)
+                .open(backup_path(&path))
+                .and_then(|f| f.set_modified(at))
+                .unwrap();
+            // 间隔内的频繁保存不轮转，备份还是上一份
+            fs::write(&path, format!("v{}-soon", n)).unwrap();
+            rotate_backups_at(&path, at + Duration::from_secs(5)).unwrap();
+        }
+        let generations: Vec<String> = (0..BACKUP_GENERATIONS)
+            .map(|g| fs::read_to_string(backup_generation(&path, g)).unwrap())
+            .collect();
+        assert_eq!(generations, vec!["v4", "v3", "v2"]);
+        assert!(!backup_generation(&path, BACKUP_GENERATIONS).exists());
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(backup_path(&path))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn corrupt_files_are_set_aside_and_restored_from_backup() {
+        let dir = temp_dir("corrupt");
+        let path = dir.join("accounts.json");
+        fs::write(backup_path(&path), GOOD).unwrap();
+        fs::write(&path, "{ not json").unwrap();
+
+        let now = Utc::now();
+        let (loaded, health) = load(&path, now);
+        assert_eq!(loaded.unwrap().0.current.as_deref(), Some("a1"));
+        let StoreHealth::RestoredFromBackup { corrupt_path, .. } = &health else {
+            panic!("{:?}", health);
+        };
+        assert!(!path.exists());
+        assert!(corrupt_path.contains("accounts.json.corrupt-"));
+        assert_eq!(fs::read_to_string(corrupt_path).unwrap(), "{ not json");
+        assert!(health.message().unwrap().contains("从备份"));
+
+        // 最新的备份坏了就试更早的
+        fs::write(backup_generation(&path, 1), GOOD).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        fs::write(backup_path(&path), "[]").unwrap();
+        fs::write(&path, "{ broken again").unwrap();
+        let (loaded, health) = load(&path, now);
+        assert_eq!(loaded.unwrap().0.current.as_deref(), Some("a1"));
+        let StoreHealth::RestoredFromBackup {
+            backup_path: used, ..
+        } = &health
+        else {
+            panic!("{:?}", health);
+        };
+        assert!(used.ends_with(".bak.1"), "{}", used);
+
+        // 备份全坏了：从空库开始，坏文件同样留着
+        fs::write(backup_generation(&path, 1), "[]").unwrap();
+        fs::write(&path, "{ still not json").unwrap();
+        let (loaded, health) = load(&path, now);
+        assert!(loaded.unwrap().0.accounts.is_empty());
+        assert!(
+            matches!(health, StoreHealth::StartedEmpty { .. }),
+            "{:?}",
+            health
+        );
+        let corrupt_files = fs::read_dir(&dir)
+            .unwrap()
+            .filter(|e| {
+                let name = e.as_ref().unwrap().file_name();
+                name.to_string_lossy().contains(".corrupt-")
+            })
+            .count();
+        assert_eq!(corrupt_files, 3);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn newer_files_are_refused_and_left_in_place() {
+        let dir = temp_dir("newer");
+        let path = dir.join("accounts.json");
+        let newer = format!(
+            r#"{{"version": {}, "accounts": {{}}}}"#,
+            store_migration::STORE_VERSION + 1
+        );
+        fs::write(&path, &newer).unwrap();
+
+        let (loaded, health) = load(&path, Utc::now());
+        assert!(loaded.is_err());
+        assert!(matches!(health, StoreHealth::Refused { .. }));
+        assert_eq!(fs::read_to_string(&path).unwrap(), newer);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
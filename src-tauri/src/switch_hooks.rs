@@ -0,0 +1,420 @@
+//! 切号后钩子：切号成功后按顺序执行用户配置的命令
+//!
+//! 典型用法是重启本地代理、`touch` 一个编辑器监听的文件。钩子在切号收尾
+//! （代理重连、托盘、solo 推送）之后执行：
+//! - 不经 shell，`command` + `args` 原样交给系统；要管道 / 通配就自己写 `sh -c`
+//! - 环境变量带上切到的账号的 id / 名字 / 邮箱（[`ENV_ACCOUNT_ID`] 等），不带任何 token
+//! - 每个钩子有自己的超时，超时即杀掉；stdout / stderr 各只留前 [`MAX_OUTPUT_BYTES`] 字节
+//! - 失败只进报告，不回滚切号，也不影响后面的钩子
+//!
+//! 执行期间不持有 store 锁：调用方先在锁内取好钩子列表和 [`HookContext`] 再调 [`run_hooks`]。
+
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::account::{Account, AccountStore};
+
+/// 最多配置的钩子数
+pub const MAX_HOOKS: usize = 10;
+/// 单个钩子允许的超时范围（秒）
+pub const HOOK_TIMEOUT_RANGE: std::ops::RangeInclusive<u32> = 1..=120;
+/// stdout / stderr 各自保留的字节数
+pub const MAX_OUTPUT_BYTES: usize = 4096;
+
+pub const ENV_ACCOUNT_ID: &str = "CODEX_SWITCHER_ACCOUNT_ID";
+pub const ENV_ACCOUNT_NAME: &str = "CODEX_SWITCHER_ACCOUNT_NAME";
+pub const ENV_ACCOUNT_EMAIL: &str = "CODEX_SWITCHER_ACCOUNT_EMAIL";
+
+/// 一条钩子配置
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct SwitchHook {
+    pub name: String,
+    /// 可执行文件（PATH 里的名字或绝对路径）
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default = "default_timeout_secs")]
+    pub timeout_secs: u32,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_timeout_secs() -> u32 {
+    30
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// 保存设置前校验，顺带去掉首尾空白
+pub fn validate(hooks: &mut [SwitchHook]) -> Result<(), String> {
+    if hooks.len() > MAX_HOOKS {
+        return Err(format!("切号钩子最多 {} 个", MAX_HOOKS));
+    }
+    for (i, hook) in hooks.iter_mut().enumerate() {
+        hook.name = hook.name.trim().to_string();
+        hook.command = hook.command.trim().to_string();
+        let label = if hook.name.is_empty() {
+            format!("第 {} 个钩子", i + 1)
+        } else {
+            format!("钩子「{}」", hook.name)
+        };
+        if hook.command.is_empty() {
+            return Err(format!("{}的命令不能为空", label));
+        }
+        if !HOOK_TIMEOUT_RANGE.contains(&hook.timeout_secs) {
+            return Err(format!(
+                "{}的超时必须在 {}-{} 秒之间",
+                label,
+                HOOK_TIMEOUT_RANGE.start(),
+                HOOK_TIMEOUT_RANGE.end()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// 注入钩子环境变量的账号信息
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct HookContext {
+    pub account_id: String,
+    pub account_name: String,
+    pub email: Option<String>,
+}
+
+impl HookContext {
+    pub fn for_account(account: &Account) -> Self {
+        Self {
+            account_id: account.id.clone(),
+            account_name: account.name.clone(),
+            email: AccountStore::extract_email(&account.auth_json),
+        }
+    }
+
+    pub fn env(&self) -> Vec<(&'static str, String)> {
+        vec![
+            (ENV_ACCOUNT_ID, self.account_id.clone()),
+            (ENV_ACCOUNT_NAME, self.account_name.clone()),
+            (ENV_ACCOUNT_EMAIL, self.email.clone().unwrap_or_default()),
+        ]
+    }
+}
+
+/// `run_hooks_dry` 的一项：会执行什么
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct HookPlan {
+    pub name: String,
+    pub command: String,
+    pub args: Vec<String>,
+    pub timeout_secs: u32,
+    /// 钩子本身启用且总开关打开
+    pub will_run: bool,
+    pub env: Vec<(&'static str, String)>,
+}
+
+pub fn plan(hooks: &[SwitchHook], globally_enabled: bool, ctx: &HookContext) -> Vec<HookPlan> {
+    hooks
+        .iter()
+        .map(|hook| HookPlan {
+            name: hook.name.clone(),
+            command: hook.command.clone(),
+            args: hook.args.clone(),
+            timeout_secs: hook.timeout_secs,
+            will_run: globally_enabled && hook.enabled,
+            env: ctx.env(),
+        })
+        .collect()
+}
+
+/// 单个钩子的执行结果
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct HookReport {
+    pub name: String,
+    /// 退出码为 0
+    pub success: bool,
+    pub exit_code: Option<i32>,
+    pub timed_out: bool,
+    /// 启动失败等没拿到退出码的原因
+    pub error: Option<String>,
+    pub stdout: String,
+    pub stderr: String,
+    /// stdout / stderr 被截断过
+    pub output_truncated: bool,
+    pub duration_ms: u64,
+}
+
+/// 按顺序执行启用的钩子；总开关关着时什么都不做
+pub async fn run_hooks(
+    hooks: &[SwitchHook],
+    globally_enabled: bool,
+    ctx: &HookContext,
+) -> Vec<HookReport> {
+    if !globally_enabled {
+        return Vec::new();
+    }
+    let mut reports = Vec::new();
+    for hook in hooks.iter().filter(|h| h.enabled) {
+        let report = run_hook(hook, ctx).await;
+        if report.success {
+            println!("[Hooks] {} 完成（{} ms）", hook.name, report.duration_ms);
+        } else {
+            eprintln!(
+                "[Hooks] {} 失败: {}",
+                hook.name,
+                report
+                    .error
+                    .clone()
+                    .unwrap_or_else(|| format!("退出码 {:?}", report.exit_code))
+            );
+        }
+        reports.push(report);
+    }
+    reports
+}
+
+async fn run_hook(hook: &SwitchHook, ctx: &HookContext) -> HookReport {
+    let started = Instant::now();
+    let mut report = HookReport {
+        name: hook.name.clone(),
+        success: false,
+        exit_code: None,
+        timed_out: false,
+        error: None,
+        stdout: String::new(),
+        stderr: String::new(),
+        output_truncated: false,
+        duration_ms: 0,
+    };
+
+    let mut command = tokio::process::Command::new(&hook.command);
+    command
+        .args(&hook.args)
+        .envs(ctx.env())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            report.error = Some(format!("启动 {} 失败: {}", hook.command, e));
+            report.duration_ms = started.elapsed().as_millis() as u64;
+            return report;
+        }
+    };
+
+    let stdout = child.stdout.take();
+    let stderr = child.stderr.take();
+    let run = async {
+        tokio::join!(
+            read_capped(stdout, MAX_OUTPUT_BYTES),
+            read_capped(stderr, MAX_OUTPUT_BYTES),
+            child.wait()
+        )
+    };
+    let timeout = Duration::from_secs(u64::from(hook.timeout_secs));
+    let result = tokio::time::timeout(timeout, run).await;
+    match result {
+        Ok(((stdout, out_cut), (stderr, err_cut), status)) => {
+            report.stdout = stdout;
+            report.stderr = stderr;
+            report.output_truncated = out_cut || err_cut;
+            match status {
+                Ok(status) => {
+                    report.exit_code = status.code();
+                    report.success = status.success();
+                }
+                Err(e) => report.error = Some(format!("等待进程失败: {}", e)),
+            }
+        }
+        Err(_) => {
+            let _ = child.kill().await;
+            report.timed_out = true;
+            report.error = Some(format!("{} 秒内未结束，已终止", hook.timeout_secs));
+        }
+    }
+    report.duration_ms = started.elapsed().as_millis() as u64;
+    report
+}
+
+/// 读到 EOF，只保留前 `cap` 字节（其余读掉丢弃，免得子进程写满管道卡住）
+async fn read_capped<R: AsyncRead + Unpin>(reader: Option<R>, cap: usize) -> (String, bool) {
+    let Some(mut reader) = reader else {
+        return (String::new(), false);
+    };
+    let mut kept = Vec::new();
+    let mut truncated = false;
+    let mut buf = [0u8; 4096];
+    loop {
+        match reader.read(&mut buf).await {
+            Ok(0) | Err(_) => break,
+            Ok(n) => {
+                let room = cap.saturating_sub(kept.len());
+                kept.extend_from_slice(&buf[..n.min(room)]);
+                truncated |= n > room;
+            }
+        }
+    }
+    (String::from_utf8_lossy(&kept).into_owned(), truncated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hook(name: &str, script: &str, timeout_secs: u32) -> SwitchHook {
+        SwitchHook {
+            name: name.to_string(),
+            command: "sh".to_string(),
+            args: vec!["-c".to_string(), script.to_string()],
+            timeout_secs,
+            enabled: true,
+        }
+    }
+
+    fn ctx() -> HookContext {
+        HookContext {
+            account_id: "acc-1".to_string(),
+            account_name: "工作号".to_string(),
+            email: Some("work@example.com".to_string()),
+        }
+    }
+
+    #[test]
+    fn validation_rejects_bad_entries() {
+        let mut ok = vec![SwitchHook {
+            command: "  touch ".to_string(),
+            ..hook(" reload ", "", 120)
+        }];
+        assert!(validate(&mut ok).is_ok());
+        assert_eq!(
+            (ok[0].name.as_str(), ok[0].command.as_str()),
+            ("reload", "touch")
+        );
+
+        let mut empty = vec![SwitchHook {
+            command: " ".to_string(),
+            ..hook("a", "", 5)
+        }];
+        assert!(validate(&mut empty).unwrap_err().contains("命令不能为空"));
+        assert!(validate(&mut [hook("a", "", 0)]).is_err());
+        assert!(validate(&mut [hook("a", "", 121)]).is_err());
+        let mut many: Vec<_> = (0..=MAX_HOOKS)
+            .map(|i| hook(&i.to_string(), "", 5))
+            .collect();
+        assert!(validate(&mut many).unwrap_err().contains("最多"));
+    }
+
+    #[test]
+    fn dry_run_lists_env_without_tokens() {
+        let mut disabled = hook("off", "true", 5);
+        disabled.enabled = false;
+        let hooks = vec![hook("on", "true", 5), disabled];
+        let plans = plan(&hooks, true, &ctx());
+        assert_eq!(
+            plans.iter().map(|p| p.will_run).collect::<Vec<_>>(),
+            vec![true, false]
+        );
+        assert!(plans[0]
+            .env
+            .contains(&(ENV_ACCOUNT_EMAIL, "work@example.com".to_string())));
+        assert!(plan(&hooks, false, &ctx()).iter().all(|p| !p.will_run));
+
+        let account: Account = serde_json::from_value(serde_json::json!({
+            "id": "acc-9",
+            "name": "n",
+            "auth_json": { "tokens": { "access_token": "secret-at", "refresh_token": "secret-rt" } },
+            "refresh_token": "secret-rt",
+            "created_at": "2026-01-01T00:00:00Z",
+            "last_used": null,
+            "notes": null,
+        }))
+        .unwrap();
+        let env = HookContext::for_account(&account).env();
+        assert!(env.iter().all(|(_, v)| !v.contains("secret")));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn hooks_run_in_order_with_account_env() {
+        let dir = std::env::temp_dir().join(format!("cs-hooks-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let marker = dir.join("order.txt");
+        let hooks = vec![
+            hook(
+                "first",
+                &format!(
+                    "echo \"1 ${} ${}\" >> {}",
+                    ENV_ACCOUNT_ID,
+                    ENV_ACCOUNT_EMAIL,
+                    marker.display()
+                ),
+                5,
+            ),
+            hook(
+                "second",
+                &format!(
+                    "echo \"2 ${}\" >> {}; echo done",
+                    ENV_ACCOUNT_NAME,
+                    marker.display()
+                ),
+                5,
+            ),
+        ];
+        let reports = run_hooks(&hooks, true, &ctx()).await;
+        assert!(reports.iter().all(|r| r.success && r.exit_code == Some(0)));
+        assert_eq!(reports[1].stdout, "done\n");
+        assert_eq!(
+            std::fs::read_to_string(&marker).unwrap(),
+            "1 acc-1 work@example.com\n2 工作号\n"
+        );
+
+        // 总开关关着：不执行
+        assert!(run_hooks(&hooks, false, &ctx()).await.is_empty());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn failures_and_timeouts_are_reported_and_later_hooks_still_run() {
+        let hooks = vec![
+            hook("fails", "echo boom >&2; exit 3", 5),
+            hook("hangs", "sleep 30", 1),
+            SwitchHook {
+                command: "/nonexistent/cs-hook".to_string(),
+                args: Vec::new(),
+                ..hook("missing", "", 5)
+            },
+            hook("ok", "true", 5),
+        ];
+        let started = Instant::now();
+        let reports = run_hooks(&hooks, true, &ctx()).await;
+        assert!(
+            started.elapsed() < Duration::from_secs(10),
+            "超时的钩子要被杀掉"
+        );
+
+        assert_eq!(reports.len(), 4);
+        assert_eq!((reports[0].success, reports[0].exit_code), (false, Some(3)));
+        assert_eq!(reports[0].stderr, "boom\n");
+        assert!(reports[1].timed_out && !reports[1].success);
+        assert!(reports[2].error.as_deref().unwrap().contains("启动"));
+        assert!(reports[3].success);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn output_is_capped() {
+        let hooks = vec![hook("noisy", "yes x | head -c 100000; echo tail >&2", 5)];
+        let report = &run_hooks(&hooks, true, &ctx()).await[0];
+        assert!(report.success);
+        assert_eq!(report.stdout.len(), MAX_OUTPUT_BYTES);
+        assert_eq!(report.stderr, "tail\n");
+        assert!(report.output_truncated);
+    }
+}
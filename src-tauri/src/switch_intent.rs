@@ -0,0 +1,257 @@
+//! 切号的预写意图：崩溃后把半截切号收拾干净
+//!
+//! 切号要先写 `~/.codex/auth.json`，再改 `current` / `last_used` 并保存 `accounts.json`。
+//! 中间崩溃（或被杀、断电）就会出现磁盘 auth.json 已是新号、账号表还指着旧号的分裂状态，
+//! 之后的同步会把新号的 token 回流到旧号名下。
+//!
+//! 所以写 auth.json 之前先落一条 [`SwitchIntent`]（`pending-switch.json`），账号表保存成功后删掉。
+//! 启动时若还留着，按磁盘 auth.json 的实际身份调 [`decide`]（纯函数）定夺：
+//! - 磁盘已是目标、账号表也已切过去 → [`Resolution::AlreadyCompleted`]，只删记录
+//! - 磁盘已是目标、账号表没跟上 → [`Resolution::Finish`]，补完 `current` / `last_used`
+//! - 磁盘不是目标、账号表却指着目标 → [`Resolution::RollBack`]，`current` 退回切号前的账号
+//! - 磁盘不是目标、账号表也没动 → [`Resolution::NotStarted`]，只删记录
+//! - 记录超过 [`STALE_AFTER_HOURS`] 小时 → [`Resolution::Stale`]，不再猜，报告后删掉
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+use crate::account::AccountStore;
+
+/// 超过这么久的意图记录不再自动收拾
+pub const STALE_AFTER_HOURS: i64 = 24;
+
+/// 切号走到哪一步了
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IntentStage {
+    /// 即将写 auth.json
+    WritingAuth,
+    /// auth.json 已写，账号表还没保存
+    AuthWritten,
+}
+
+/// 一次进行中的切号
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SwitchIntent {
+    pub target_id: String,
+    /// 切号前的 current
+    #[serde(default)]
+    pub previous_id: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub stage: IntentStage,
+}
+
+impl SwitchIntent {
+    pub fn new(target_id: &str, previous_id: Option<String>, now: DateTime<Utc>) -> Self {
+        Self {
+            target_id: target_id.to_string(),
+            previous_id,
+            started_at: now,
+            stage: IntentStage::WritingAuth,
+        }
+    }
+}
+
+/// 启动时对残留意图的处理结论
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Resolution {
+    /// 切号其实已完成
+    AlreadyCompleted,
+    /// auth.json 已写、账号表没跟上：补完
+    Finish,
+    /// 账号表指着目标但磁盘不是：退回切号前的账号
+    RollBack,
+    /// auth.json 没写成，账号表也没动
+    NotStarted,
+    /// 目标账号已不存在
+    TargetMissing,
+    /// 记录太旧
+    Stale,
+}
+
+impl Resolution {
+    /// 是否需要改账号表（调用方据此决定要不要保存）
+    pub fn changes_store(&self) -> bool {
+        matches!(self, Resolution::Finish | Resolution::RollBack)
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Resolution::AlreadyCompleted => "已完成",
+            Resolution::Finish => "补完",
+            Resolution::RollBack => "回滚",
+            Resolution::NotStarted => "未开始",
+            Resolution::TargetMissing => "目标账号已删除",
+            Resolution::Stale => "记录过期",
+        }
+    }
+}
+
+/// 纯决策：`disk_is_target` 为磁盘 auth.json 是否是目标账号的身份
+pub fn decide(
+    intent: &SwitchIntent,
+    target_exists: bool,
+    disk_is_target: bool,
+    current: Option<&str>,
+    now: DateTime<Utc>,
+) -> Resolution {
+    if now - intent.started_at > Duration::hours(STALE_AFTER_HOURS) {
+        return Resolution::Stale;
+    }
+    if !target_exists {
+        return Resolution::TargetMissing;
+    }
+    let current_is_target = current == Some(intent.target_id.as_str());
+    match (disk_is_target, current_is_target) {
+        (true, true) => Resolution::AlreadyCompleted,
+        (true, false) => Resolution::Finish,
+        (false, true) => Resolution::RollBack,
+        (false, false) => Resolution::NotStarted,
+    }
+}
+
+/// 按磁盘 auth.json 判定并就地改账号表；需要保存时 [`Resolution::changes_store`] 为真
+pub fn reconcile(
+    store: &mut AccountStore,
+    intent: &SwitchIntent,
+    disk_auth: Option<&Value>,
+    now: DateTime<Utc>,
+) -> Resolution {
+    let target = store.accounts.get(&intent.target_id);
+    let disk_is_target = match (target, disk_auth) {
+        (Some(account), Some(disk)) => {
+            AccountStore::auth_identity_matches(&account.auth_json, disk)
+        }
+        _ => false,
+    };
+    let resolution = decide(
+        intent,
+        target.is_some(),
+        disk_is_target,
+        store.current.as_deref(),
+        now,
+    );
+    match resolution {
+        Resolution::Finish => {
+            if let Some(account) = store.accounts.get_mut(&intent.target_id) {
+                account.last_used = Some(now);
+                account.touch();
+            }
+            store.current = Some(intent.target_id.clone());
+        }
+        Resolution::RollBack => {
+            store.current = intent
+                .previous_id
+                .clone()
+                .filter(|id| store.accounts.contains_key(id));
+        }
+        _ => {}
+    }
+    resolution
+}
+
+pub fn default_path() -> PathBuf {
+    crate::paths::data_dir().join("pending-switch.json")
+}
+
+/// 读残留意图；文件不存在或损坏时视为没有
+pub fn load_from(path: &Path) -> Option<SwitchIntent> {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|text| serde_json::from_str(&text).ok())
+}
+
+pub fn save_to(path: &Path, intent: &SwitchIntent) -> Result<(), String> {
+    let json = serde_json::to_vec(intent).map_err(|e| e.to_string())?;
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    crate::atomic_write::write_atomic(path, &json)
+}
+
+pub fn clear_at(path: &Path) -> Result<(), String> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.to_string()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn t0() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, 1, 8, 0, 0).unwrap()
+    }
+
+    fn intent() -> SwitchIntent {
+        SwitchIntent::new("work", Some("home".to_string()), t0())
+    }
+
+    #[test]
+    fn completed_switch_only_clears_the_record() {
+        let soon = t0() + Duration::seconds(5);
+        assert_eq!(
+            decide(&intent(), true, true, Some("work"), soon),
+            Resolution::AlreadyCompleted
+        );
+        assert!(!Resolution::AlreadyCompleted.changes_store());
+    }
+
+    #[test]
+    fn switch_that_never_wrote_auth_is_left_alone() {
+        let soon = t0() + Duration::seconds(5);
+        assert_eq!(
+            decide(&intent(), true, false, Some("home"), soon),
+            Resolution::NotStarted
+        );
+    }
+
+    #[test]
+    fn half_done_switch_is_finished_or_rolled_back_by_disk_identity() {
+        let soon = t0() + Duration::seconds(5);
+        // auth.json 已写、账号表没保存
+        assert_eq!(
+            decide(&intent(), true, true, Some("home"), soon),
+            Resolution::Finish
+        );
+        // 账号表指着目标，但磁盘不是目标
+        assert_eq!(
+            decide(&intent(), true, false, Some("work"), soon),
+            Resolution::RollBack
+        );
+        assert_eq!(
+            decide(&intent(), false, false, Some("home"), soon),
+            Resolution::TargetMissing
+        );
+    }
+
+    #[test]
+    fn records_older_than_a_day_are_stale() {
+        let later = t0() + Duration::hours(STALE_AFTER_HOURS) + Duration::minutes(1);
+        assert_eq!(
+            decide(&intent(), true, true, Some("home"), later),
+            Resolution::Stale
+        );
+    }
+
+    #[test]
+    fn intent_file_round_trips_and_clears() {
+        let path = std::env::temp_dir()
+            .join(format!("cs-switch-intent-{}", uuid::Uuid::new_v4()))
+            .join("pending-switch.json");
+        assert_eq!(load_from(&path), None);
+
+        let mut written = intent();
+        written.stage = IntentStage::AuthWritten;
+        save_to(&path, &written).unwrap();
+        assert_eq!(load_from(&path), Some(written));
+
+        clear_at(&path).unwrap();
+        assert!(!path.exists());
+        clear_at(&path).unwrap();
+    }
+}
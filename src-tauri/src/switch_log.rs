@@ -149,10 +149,7 @@ impl SwitchLogger {
     }
 
     fn log_path() -> PathBuf {
-        dirs::home_dir()
-            .expect("home dir")
-            .join(".codex-switcher")
-            .join("switch-history.jsonl")
+        crate::paths::data_dir().join("switch-history.jsonl")
     }
 
     fn load_from_disk() -> Vec<SwitchEvent> {
@@ -0,0 +1,211 @@
+//! 切号进度事件 `switch-progress`
+//!
+//! 一次切号要回流当前账号、预检配额、等刷新锁、写 auth.json、做收尾（代理重连、托盘、
+//! solo 推送），前端只有一个转圈，卡住时看不出卡在哪。[`SwitchProgress`] 在每个阶段开始时
+//! 经 `EventSink::emit` 发一条事件，结束时发 `done` 或 `failed`。
+//!
+//! 守卫保证每次切号一定有终态事件：没调 [`SwitchProgress::done`] / [`SwitchProgress::fail`]
+//! 就被 drop（提前返回、future 被取消）时，按当时所处阶段补发 `failed`。
+//! 没挡住切号的警告（预检失败等，见 `warnings`）挂在 `done` 那条上，托盘 / 定时切号也能看到。
+//! `switch_seq` 进程内单调递增，前端据此丢掉已被新一次切号取代的事件。
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+use crate::service::EventSink;
+use crate::warnings::Warning;
+
+/// 事件名
+pub const SWITCH_PROGRESS_EVENT: &str = "switch-progress";
+
+static SWITCH_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// 切号阶段
+#[derive(Serialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SwitchStage {
+    /// 把当前账号和 ~/.codex/auth.json 对齐
+    SyncingCurrent,
+    /// 必要时刷新目标 token，并预检配额
+    Prechecking,
+    /// 等目标账号的刷新锁
+    AcquiringLock,
+    /// 写 auth.json / config.toml、保存账号库
+    WritingAuth,
+    /// 落盘后的收尾：代理重连、托盘、solo 推送
+    Reloading,
+    Done,
+    /// `stage` 是出错时所处的阶段
+    Failed {
+        stage: Box<SwitchStage>,
+        error: String,
+    },
+}
+
+/// `switch-progress` 事件负载
+#[derive(Serialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct SwitchProgressEvent {
+    pub switch_seq: u64,
+    pub target_id: String,
+    pub stage: SwitchStage,
+    pub elapsed_ms: u64,
+    /// 只在 `done` 上可能非空
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<Warning>,
+}
+
+/// 一次切号的进度守卫
+pub struct SwitchProgress {
+    events: Arc<dyn EventSink>,
+    seq: u64,
+    target_id: String,
+    started: Instant,
+    stage: SwitchStage,
+    finished: bool,
+}
+
+impl SwitchProgress {
+    /// 分配新的 `switch_seq` 并发出第一个阶段 `syncing_current`
+    pub fn begin(events: Arc<dyn EventSink>, target_id: &str) -> Self {
+        let progress = Self {
+            events,
+            seq: SWITCH_SEQ.fetch_add(1, Ordering::Relaxed) + 1,
+            target_id: target_id.to_string(),
+            started: Instant::now(),
+            stage: SwitchStage::SyncingCurrent,
+            finished: false,
+        };
+        progress.emit(SwitchStage::SyncingCurrent);
+        progress
+    }
+
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    /// 进入下一阶段
+    pub fn stage(&mut self, stage: SwitchStage) {
+        self.stage = stage.clone();
+        self.emit(stage);
+    }
+
+    pub fn done(self) {
+        self.done_with(&[]);
+    }
+
+    /// 成功收尾，带上沿途的警告
+    pub fn done_with(mut self, warnings: &[Warning]) {
+        self.finished = true;
+        self.emit_with(SwitchStage::Done, warnings.to_vec());
+    }
+
+    /// 以当前阶段报失败
+    pub fn fail(mut self, error: &str) {
+        self.finished = true;
+        self.emit_failed(error);
+    }
+
+    fn emit_failed(&self, error: &str) {
+        self.emit(SwitchStage::Failed {
+            stage: Box::new(self.stage.clone()),
+            error: error.to_string(),
+        });
+    }
+
+    fn emit(&self, stage: SwitchStage) {
+        self.emit_with(stage, Vec::new());
+    }
+
+    fn emit_with(&self, stage: SwitchStage, warnings: Vec<Warning>) {
+        let event = SwitchProgressEvent {
+            switch_seq: self.seq,
+            target_id: self.target_id.clone(),
+            stage,
+            elapsed_ms: self.started.elapsed().as_millis() as u64,
+            warnings,
+        };
+        match serde_json::to_value(&event) {
+            Ok(payload) => self.events.emit(SWITCH_PROGRESS_EVENT, payload),
+            Err(e) => eprintln!("[Switch] 序列化切号进度失败: {}", e),
+        }
+    }
+}
+
+impl Drop for SwitchProgress {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.emit_failed("切换中断");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::AppEvent;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<(String, serde_json::Value)>>);
+
+    impl EventSink for Recorder {
+        fn emit(&self, event: &str, payload: serde_json::Value) {
+            self.0.lock().unwrap().push((event.to_string(), payload));
+        }
+        fn publish(&self, _event: AppEvent) {}
+    }
+
+    fn stages(recorder: &Recorder) -> Vec<serde_json::Value> {
+        recorder
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(name, payload)| {
+                assert_eq!(name, SWITCH_PROGRESS_EVENT);
+                payload["stage"].clone()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn dropped_guard_reports_failure_at_current_stage() {
+        let recorder = Arc::new(Recorder::default());
+        {
+            let mut progress = SwitchProgress::begin(recorder.clone(), "acc-1");
+            progress.stage(SwitchStage::AcquiringLock);
+        }
+        assert_eq!(
+            stages(&recorder),
+            vec![
+                serde_json::json!("syncing_current"),
+                serde_json::json!("acquiring_lock"),
+                serde_json::json!({"failed": {"stage": "acquiring_lock", "error": "切换中断"}}),
+            ]
+        );
+    }
+
+    #[test]
+    fn finished_guard_emits_single_terminal_event() {
+        let recorder = Arc::new(Recorder::default());
+        let first = SwitchProgress::begin(recorder.clone(), "acc-1");
+        let second = SwitchProgress::begin(recorder.clone(), "acc-2");
+        assert!(second.seq() > first.seq(), "后开始的切号序号更大");
+        first.fail("HTTP 500");
+        second.done();
+
+        let events = recorder.0.lock().unwrap();
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[2].1["target_id"], "acc-1");
+        assert_eq!(
+            events[2].1["stage"],
+            serde_json::json!({"failed": {"stage": "syncing_current", "error": "HTTP 500"}})
+        );
+        assert_eq!(events[3].1["stage"], "done");
+        assert_eq!(events[3].1["target_id"], "acc-2");
+    }
+}
@@ -0,0 +1,253 @@
+//! 限时临时切号：到点自动切回原账号
+//!
+//! 借队友池子里的号干半小时活、忘了切回来，一夜就能把人家的周额度烧光。
+//! `switch_account_temporarily` 照常切号，另外记一条 [`PendingRevert`]（切回谁、几点切），
+//! 落盘到 `pending-revert.json`，重启后接着算。
+//!
+//! 后台定时器每隔一会儿问一次 [`step`]（纯函数）：
+//! - 当前账号已经不是临时账号（用户手动切走了、或自动切号换了人）→ [`Step::Superseded`]，作废
+//! - 到点 → [`Step::Revert`]，走正常切号流程切回去
+//! - 离到点不到 [`WARN_BEFORE_MINUTES`] 分钟且还没提醒过 → [`Step::Warn`]，
+//!   用户可调 `cancel_pending_revert` 留着临时账号
+//!
+//! 手动切号（界面 / 托盘）直接清掉待切回，不等定时器发现。
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// 到点前多久提醒一次
+pub const WARN_BEFORE_MINUTES: i64 = 5;
+
+/// 临时切号允许的时长（分钟）
+pub const MINUTES_RANGE: std::ops::RangeInclusive<u32> = 1..=24 * 60;
+
+/// 一次待执行的切回
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingRevert {
+    /// 临时切过去的账号
+    pub temporary_id: String,
+    /// 到点切回的账号（临时切号前的 current）
+    pub revert_to: String,
+    pub started_at: DateTime<Utc>,
+    pub revert_at: DateTime<Utc>,
+    /// 到点前的提醒是否已发过
+    #[serde(default)]
+    pub warned: bool,
+}
+
+/// 定时器这一拍该做什么
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    Wait,
+    Warn,
+    Revert,
+    Superseded,
+}
+
+/// 校验并生成一条待切回；`current` 为临时切号前的当前账号
+pub fn schedule(
+    current: Option<&str>,
+    temporary_id: &str,
+    minutes: u32,
+    now: DateTime<Utc>,
+) -> Result<PendingRevert, String> {
+    if !MINUTES_RANGE.contains(&minutes) {
+        return Err(format!(
+            "临时切换时长需在 {}-{} 分钟之间",
+            MINUTES_RANGE.start(),
+            MINUTES_RANGE.end()
+        ));
+    }
+    let Some(current) = current else {
+        return Err("当前没有使用中的账号，无处切回，请直接切换".to_string());
+    };
+    if current == temporary_id {
+        return Err("该账号已经是当前账号".to_string());
+    }
+    Ok(PendingRevert {
+        temporary_id: temporary_id.to_string(),
+        revert_to: current.to_string(),
+        started_at: now,
+        revert_at: now + Duration::minutes(i64::from(minutes)),
+        warned: false,
+    })
+}
+
+/// 提醒时间点
+pub fn warn_at(pending: &PendingRevert) -> DateTime<Utc> {
+    pending.revert_at - Duration::minutes(WARN_BEFORE_MINUTES)
+}
+
+/// 看当前账号和时间决定这一拍的动作
+pub fn step(pending: &PendingRevert, current: Option<&str>, now: DateTime<Utc>) -> Step {
+    if current != Some(pending.temporary_id.as_str()) {
+        Step::Superseded
+    } else if now >= pending.revert_at {
+        Step::Revert
+    } else if !pending.warned && now >= warn_at(pending) {
+        Step::Warn
+    } else {
+        Step::Wait
+    }
+}
+
+/// 待切回的持久化槽位（同一时间最多一条，新的覆盖旧的）
+pub struct RevertSlot {
+    path: PathBuf,
+    pending: Mutex<Option<PendingRevert>>,
+}
+
+impl RevertSlot {
+    /// 从默认位置（数据目录下的 `pending-revert.json`）加载
+    pub fn load() -> Self {
+        Self::load_from(crate::paths::data_dir().join("pending-revert.json"))
+    }
+
+    /// 从指定文件加载；文件不存在或损坏时视为没有待切回
+    pub fn load_from(path: PathBuf) -> Self {
+        let pending = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str(&text).ok());
+        Self {
+            path,
+            pending: Mutex::new(pending),
+        }
+    }
+
+    pub fn get(&self) -> Option<PendingRevert> {
+        self.pending.lock().ok().and_then(|p| p.clone())
+    }
+
+    pub fn set(&self, pending: PendingRevert) {
+        if let Ok(mut slot) = self.pending.lock() {
+            *slot = Some(pending);
+            self.save(&slot);
+        }
+    }
+
+    /// 清掉并返回原来那条
+    pub fn clear(&self) -> Option<PendingRevert> {
+        let mut slot = self.pending.lock().ok()?;
+        let taken = slot.take();
+        if taken.is_some() {
+            self.save(&slot);
+        }
+        taken
+    }
+
+    /// 记下已提醒；期间被换成了别的待切回则不动
+    pub fn mark_warned(&self, pending: &PendingRevert) {
+        if let Ok(mut slot) = self.pending.lock() {
+            if let Some(current) = slot.as_mut().filter(|p| *p == pending) {
+                current.warned = true;
+                self.save(&slot);
+            }
+        }
+    }
+
+    fn save(&self, pending: &Option<PendingRevert>) {
+        let result = match pending {
+            None => match std::fs::remove_file(&self.path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.to_string()),
+                _ => Ok(()),
+            },
+            Some(pending) => serde_json::to_vec(pending)
+                .map_err(|e| e.to_string())
+                .and_then(|json| {
+                    if let Some(parent) = self.path.parent() {
+                        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                    }
+                    crate::atomic_write::write_atomic(&self.path, &json)
+                }),
+        };
+        if let Err(e) = result {
+            eprintln!("[TempSwitch] 落盘失败: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn t0() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 3, 1, 9, 0, 0).unwrap()
+    }
+
+    fn pending(minutes: u32) -> PendingRevert {
+        schedule(Some("home"), "borrowed", minutes, t0()).unwrap()
+    }
+
+    #[test]
+    fn schedule_validates_inputs() {
+        let p = pending(30);
+        assert_eq!(p.revert_to, "home");
+        assert_eq!(p.revert_at, t0() + Duration::minutes(30));
+        assert!(schedule(None, "borrowed", 30, t0()).is_err());
+        assert!(schedule(Some("borrowed"), "borrowed", 30, t0()).is_err());
+        assert!(schedule(Some("home"), "borrowed", 0, t0()).is_err());
+        assert!(schedule(Some("home"), "borrowed", 24 * 60 + 1, t0()).is_err());
+    }
+
+    #[test]
+    fn warns_five_minutes_before_then_reverts_at_expiry() {
+        let mut p = pending(30);
+        let at = |m: i64| t0() + Duration::minutes(m);
+        let current = Some("borrowed");
+
+        assert_eq!(step(&p, current, at(0)), Step::Wait);
+        assert_eq!(step(&p, current, at(24)), Step::Wait);
+        assert_eq!(step(&p, current, at(25)), Step::Warn);
+        p.warned = true;
+        assert_eq!(step(&p, current, at(29)), Step::Wait, "只提醒一次");
+        assert_eq!(step(&p, current, at(30)), Step::Revert);
+        assert_eq!(step(&p, current, at(600)), Step::Revert, "错过的也照切");
+
+        // 时长不足提醒提前量：第一拍就提醒
+        let short = pending(3);
+        assert_eq!(step(&short, current, t0()), Step::Warn);
+    }
+
+    #[test]
+    fn switching_away_supersedes_the_revert() {
+        let p = pending(30);
+        let late = t0() + Duration::minutes(45);
+        assert_eq!(step(&p, Some("home"), late), Step::Superseded);
+        assert_eq!(step(&p, Some("other"), t0()), Step::Superseded);
+        assert_eq!(step(&p, None, t0()), Step::Superseded);
+    }
+
+    #[test]
+    fn pending_revert_survives_restart() {
+        let path = std::env::temp_dir()
+            .join(format!("cs-temp-switch-{}", uuid::Uuid::new_v4()))
+            .join("pending-revert.json");
+        let slot = RevertSlot::load_from(path.clone());
+        assert_eq!(slot.get(), None);
+
+        let p = pending(30);
+        slot.set(p.clone());
+        slot.mark_warned(&p);
+        let reloaded = RevertSlot::load_from(path.clone());
+        assert_eq!(
+            reloaded.get(),
+            Some(PendingRevert {
+                warned: true,
+                ..p.clone()
+            })
+        );
+
+        // 被新的覆盖后，旧那条的提醒标记不会误落到新的上
+        let newer = schedule(Some("home"), "other", 60, t0()).unwrap();
+        reloaded.set(newer.clone());
+        reloaded.mark_warned(&p);
+        assert_eq!(reloaded.get(), Some(newer));
+
+        assert!(reloaded.clear().is_some());
+        assert!(!path.exists());
+        assert_eq!(RevertSlot::load_from(path).get(), None);
+    }
+}
@@ -0,0 +1,276 @@
+//! refresh_token 闲置预警
+//!
+//! OpenAI 会让长期没用过的 refresh_token 在服务端失效，很少用的备用号往往到想切过去时
+//! 才发现早就登录失效了。这里按「refresh_token 最近一次成功换到新 token 的时间」算闲置天数：
+//! - 账号上的 `refresh_token_used_at` 在任何刷新成功路径都会更新（`apply_refreshed_tokens`、
+//!   从 auth.json 同步到轮换后的 rt）；老账号没有这个字段时退回 auth.json 的 `last_refresh`
+//! - 闲置超过 `refresh_token_aging_days` 即算老化：scheduler 发 `refresh-token-aging` 事件和
+//!   系统通知，`get_aging_tokens` 给界面列出来
+//! - 能本地刷新的账号可以手动保活一次（`keepalive_refresh_account`）；只有打开
+//!   `aging_auto_keepalive` 时 scheduler 才替用户自动刷
+
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::account::{Account, AccountStore};
+use crate::oauth;
+use crate::refresh_lock::RefreshLockManager;
+
+/// 等别的流程释放同一账号刷新锁的最长时间
+pub(crate) const LOCK_WAIT_SECS: u64 = 5;
+
+/// refresh_token 最近一次被成功使用的时间：两者取较新的，没有记录时退回 `last_refresh`
+pub fn last_exercised(
+    used_at: Option<DateTime<Utc>>,
+    last_refresh: Option<DateTime<Utc>>,
+) -> Option<DateTime<Utc>> {
+    used_at.max(last_refresh)
+}
+
+/// 闲置天数达到阈值即返回天数；阈值 0 表示关闭。
+/// 完全没有时间戳（从没刷新过、也不知道何时登录）的账号无从判断，不报
+pub fn aging_days(
+    last_exercised: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+    threshold_days: u32,
+) -> Option<i64> {
+    if threshold_days == 0 {
+        return None;
+    }
+    let idle = (now - last_exercised?).num_days();
+    (idle >= i64::from(threshold_days)).then_some(idle)
+}
+
+/// 对一个老化账号能做的保活
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum KeepaliveOffer {
+    /// 本机不能刷新（client 模式、当前账号、Relay、已失效等）
+    Unavailable,
+    /// 界面上提供「手动刷新一次」
+    Manual,
+    /// 用户打开了 `aging_auto_keepalive`，scheduler 自动刷
+    Auto,
+}
+
+pub fn keepalive_offer(local_refresh_allowed: bool, auto_opt_in: bool) -> KeepaliveOffer {
+    match (local_refresh_allowed, auto_opt_in) {
+        (false, _) => KeepaliveOffer::Unavailable,
+        (true, false) => KeepaliveOffer::Manual,
+        (true, true) => KeepaliveOffer::Auto,
+    }
+}
+
+/// 本机能否替这个账号刷 token。当前账号的 rt 归 Codex 轮换，手机锚账号有自己的循环，
+/// client 模式下 token 由 Server 维护，这些都不碰
+pub fn local_refresh_allowed(store: &AccountStore, account: &Account) -> bool {
+    store.settings.remote_mode != "client"
+        && store.current.as_deref() != Some(account.id.as_str())
+        && !account.is_session_anchor
+        && !account.is_relay()
+        && !account.is_token_invalid
+        && !account.is_logged_out
+        && account.keepalive.inactive_refresh_enabled
+        && refresh_token_of(account).is_some()
+}
+
+fn refresh_token_of(account: &Account) -> Option<String> {
+    account
+        .refresh_token
+        .clone()
+        .or_else(|| AccountStore::extract_refresh_token(&account.auth_json))
+}
+
+/// `get_aging_tokens` / `refresh-token-aging` 的条目
+#[derive(Serialize, Clone, Debug)]
+pub struct AgingToken {
+    pub id: String,
+    pub name: String,
+    pub idle_days: i64,
+    pub last_exercised_at: DateTime<Utc>,
+    pub keepalive: KeepaliveOffer,
+}
+
+/// 所有已老化的账号，闲置最久的在前
+pub fn collect(store: &AccountStore, now: DateTime<Utc>) -> Vec<AgingToken> {
+    let threshold = store.settings.refresh_token_aging_days;
+    let auto = store.settings.aging_auto_keepalive;
+    let mut aging: Vec<AgingToken> = store
+        .accounts
+        .values()
+        .filter(|a| !a.is_relay())
+        .filter_map(|account| {
+            let last = last_exercised(
+                account.refresh_token_used_at,
+                AccountStore::extract_last_refresh(&account.auth_json),
+            )?;
+            let idle_days = aging_days(Some(last), now, threshold)?;
+            Some(AgingToken {
+                id: account.id.clone(),
+                name: account.name.clone(),
+                idle_days,
+                last_exercised_at: last,
+                keepalive: keepalive_offer(local_refresh_allowed(store, account), auto),
+            })
+        })
+        .collect();
+    aging.sort_by(|a, b| b.idle_days.cmp(&a.idle_days).then(a.name.cmp(&b.name)));
+    aging
+}
+
+/// 挑出这次新跨过阈值的账号（每次跨过只提醒一次）；不再老化的从 `notified` 里移除，
+/// 之后再次跨过会重新提醒
+pub fn newly_aging(notified: &mut HashSet<String>, aging: &[AgingToken]) -> Vec<AgingToken> {
+    notified.retain(|id| aging.iter().any(|t| &t.id == id));
+    aging
+        .iter()
+        .filter(|t| notified.insert(t.id.clone()))
+        .cloned()
+        .collect()
+}
+
+/// 通知正文：最多列三个，其余合计
+pub fn notification_body(aging: &[AgingToken]) -> String {
+    const SHOWN: usize = 3;
+    let mut lines: Vec<String> = aging
+        .iter()
+        .take(SHOWN)
+        .map(|t| format!("{} {} 天未活动，建议手动刷新一次", t.name, t.idle_days))
+        .collect();
+    if aging.len() > SHOWN {
+        lines.push(format!("等共 {} 个账号", aging.len()));
+    }
+    lines.join("\n")
+}
+
+/// 保活刷新一次：持账号刷新锁，用账号自己的 OAuth profile 换新 token 并落盘
+pub async fn keepalive(
+    store: &Arc<Mutex<AccountStore>>,
+    locks: &RefreshLockManager,
+    id: &str,
+) -> Result<(), String> {
+    if !locks
+        .acquire(id, tokio::time::Duration::from_secs(LOCK_WAIT_SECS))
+        .await
+    {
+        return Err("该账号正在被其他流程刷新，请稍后重试".to_string());
+    }
+    let result = keepalive_locked(store, id).await;
+    locks.release(id).await;
+    result
+}
+
+async fn keepalive_locked(store: &Arc<Mutex<AccountStore>>, id: &str) -> Result<(), String> {
+    let (rt, profile) = {
+        let s = store.lock().map_err(|e| e.to_string())?;
+        let account = s
+            .accounts
+            .get(id)
+            .ok_or_else(|| format!("账号 {} 不存在", id))?;
+        if !local_refresh_allowed(&s, account) {
+            return Err(format!("账号 {} 不能在本机刷新", account.name));
+        }
+        crate::refresh_ban::guard(&s, id)?;
+        let rt = refresh_token_of(account).ok_or("账号缺少 refresh_token")?;
+        (rt, s.auth_profile_of(account))
+    };
+
+    let result = oauth::refresh_access_token(&profile, &rt).await;
+    let mut s = store.lock().map_err(|e| e.to_string())?;
+    match result {
+        Ok(tokens) => {
+            if let Some(account) = s.accounts.get_mut(id) {
+                AccountStore::apply_refreshed_tokens(
+                    account,
+                    tokens.access_token,
+                    tokens.refresh_token,
+                    tokens.id_token,
+                    tokens.expires_in,
+                );
+            }
+            s.mark_keepalive_attempt_success(id);
+            s.save()
+        }
+        Err(e) => {
+            s.mark_keepalive_attempt_failed(id, e.clone());
+            crate::refresh_ban::record_failure(&mut s, id, &e);
+            let _ = s.save();
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(day: i64) -> DateTime<Utc> {
+        DateTime::from_timestamp(1_700_000_000 + day * 86_400, 0).unwrap()
+    }
+
+    fn token(id: &str) -> AgingToken {
+        AgingToken {
+            id: id.to_string(),
+            name: id.to_string(),
+            idle_days: 40,
+            last_exercised_at: at(0),
+            keepalive: KeepaliveOffer::Manual,
+        }
+    }
+
+    #[test]
+    fn falls_back_to_last_refresh_when_never_tracked() {
+        assert_eq!(last_exercised(None, Some(at(3))), Some(at(3)));
+        assert_eq!(last_exercised(Some(at(5)), Some(at(3))), Some(at(5)));
+        // 记录早于 auth.json 的 last_refresh（比如被 Server 同步覆盖过）时取较新的
+        assert_eq!(last_exercised(Some(at(1)), Some(at(3))), Some(at(3)));
+        assert_eq!(last_exercised(None, None), None);
+    }
+
+    #[test]
+    fn crosses_threshold_at_exact_day() {
+        let now = at(30);
+        assert_eq!(aging_days(Some(at(1)), now, 30), None);
+        assert_eq!(aging_days(Some(at(0)), now, 30), Some(30));
+        assert_eq!(aging_days(Some(at(-15)), now, 30), Some(45));
+        assert_eq!(aging_days(Some(at(-15)), now, 0), None, "阈值 0 = 关闭");
+        assert_eq!(aging_days(None, now, 30), None);
+    }
+
+    #[test]
+    fn auto_keepalive_requires_opt_in_and_local_refresh() {
+        assert_eq!(keepalive_offer(false, false), KeepaliveOffer::Unavailable);
+        assert_eq!(keepalive_offer(false, true), KeepaliveOffer::Unavailable);
+        assert_eq!(keepalive_offer(true, false), KeepaliveOffer::Manual);
+        assert_eq!(keepalive_offer(true, true), KeepaliveOffer::Auto);
+    }
+
+    #[test]
+    fn notifies_once_per_crossing() {
+        let mut notified = HashSet::new();
+        let first = newly_aging(&mut notified, &[token("a"), token("b")]);
+        assert_eq!(first.len(), 2);
+        assert!(newly_aging(&mut notified, &[token("a"), token("b")]).is_empty());
+
+        // b 被刷新过、不再老化，之后再次跨过要重新提醒
+        assert!(newly_aging(&mut notified, &[token("a")]).is_empty());
+        let again = newly_aging(&mut notified, &[token("a"), token("b")]);
+        assert_eq!(again.len(), 1);
+        assert_eq!(again[0].id, "b");
+    }
+
+    #[test]
+    fn notification_lists_at_most_three() {
+        let one = notification_body(&[token("a")]);
+        assert_eq!(one, "a 40 天未活动，建议手动刷新一次");
+
+        let many: Vec<AgingToken> = ["a", "b", "c", "d"].iter().map(|id| token(id)).collect();
+        let body = notification_body(&many);
+        assert_eq!(body.lines().count(), 4);
+        assert!(body.ends_with("等共 4 个账号"));
+        assert!(!body.contains("d 40"));
+    }
+}
@@ -0,0 +1,270 @@
+//! 重复 token 去重
+//!
+//! 同一个 workspace 建出来的多个账号经常带着字节级相同、好几 KB 的 id_token。
+//! `accounts.json` 本身保持原样（老版本还要能读），只在两个地方去重：
+//! - 导出：出现不止一次的 token 挪进顶层 `token_table`（sha256 → 原文），账号里换成
+//!   `{"$token": "<sha256>"}`，导入时先 [`expand`] 还原。没有重复 token 的导出和以前
+//!   逐字节相同；带 `token_table` 的导出需要本版本之后才能导入
+//! - JWT claims 解码：按 token 的 sha256 缓存，相同的 token 只解一次（见 [`ClaimsCache`]）
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+
+/// 参与去重的 auth_json 字段（`tokens.<key>`，也兼容根级）
+pub const INTERNED_KEYS: &[&str] = &["id_token", "access_token", "refresh_token"];
+/// 短于这个长度的不值得换成引用（引用本身约 80 字节）
+pub const MIN_INTERN_LEN: usize = 256;
+/// 导出顶层的 token 表
+pub const TABLE_KEY: &str = "token_table";
+/// 账号里引用 token 表的键
+pub const REF_KEY: &str = "$token";
+/// claims 缓存最多这么多条，超出按插入顺序淘汰
+pub const CLAIMS_CACHE_CAP: usize = 512;
+
+pub fn token_hash(token: &str) -> String {
+    Sha256::digest(token.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+/// 一次去重的效果
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct InternStats {
+    /// 进了 token 表的不同 token 数
+    pub tokens: usize,
+    /// 换成引用的位置数
+    pub references: usize,
+}
+
+/// 导出文档里每个账号 auth_json 中可去重的字符串位置
+fn token_slots(doc: &mut Value) -> Vec<&mut Value> {
+    let Some(accounts) = doc.get_mut("accounts").and_then(Value::as_object_mut) else {
+        return Vec::new();
+    };
+    let mut slots = Vec::new();
+    for account in accounts.values_mut() {
+        let Some(auth) = account.get_mut("auth_json").and_then(Value::as_object_mut) else {
+            continue;
+        };
+        for (key, value) in auth.iter_mut() {
+            if key == "tokens" {
+                if let Some(tokens) = value.as_object_mut() {
+                    slots.extend(
+                        tokens
+                            .iter_mut()
+                            .filter(|(k, _)| INTERNED_KEYS.contains(&k.as_str()))
+                            .map(|(_, v)| v),
+                    );
+                }
+            } else if INTERNED_KEYS.contains(&key.as_str()) {
+                slots.push(value);
+            }
+        }
+    }
+    slots
+}
+
+/// 把导出文档里重复出现的长 token 挪进 `token_table`；没有重复时文档不变
+pub fn intern(doc: &mut Value) -> InternStats {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for slot in token_slots(doc) {
+        if let Some(s) = slot.as_str().filter(|s| s.len() >= MIN_INTERN_LEN) {
+            *counts.entry(s.to_string()).or_default() += 1;
+        }
+    }
+    counts.retain(|_, n| *n > 1);
+    if counts.is_empty() {
+        return InternStats::default();
+    }
+
+    let hashes: HashMap<String, String> = counts
+        .into_keys()
+        .map(|token| (token_hash(&token), token))
+        .collect();
+    let by_token: HashMap<&str, &str> = hashes
+        .iter()
+        .map(|(hash, token)| (token.as_str(), hash.as_str()))
+        .collect();
+    let mut stats = InternStats {
+        tokens: hashes.len(),
+        references: 0,
+    };
+    for slot in token_slots(doc) {
+        if let Some(hash) = slot.as_str().and_then(|s| by_token.get(s)) {
+            *slot = serde_json::json!({ REF_KEY: hash });
+            stats.references += 1;
+        }
+    }
+
+    if let Some(obj) = doc.as_object_mut() {
+        let table: Map<String, Value> = hashes
+            .into_iter()
+            .map(|(hash, token)| (hash, Value::String(token)))
+            .collect();
+        obj.insert(TABLE_KEY.to_string(), Value::Object(table));
+    }
+    stats
+}
+
+/// 还原 [`intern`] 过的文档；没有 `token_table` 时原样返回。
+/// 引用了表里没有的 hash 视为文件损坏，整体报错
+pub fn expand(doc: &mut Value) -> Result<(), String> {
+    let Some(table) = doc.as_object_mut().and_then(|o| o.remove(TABLE_KEY)) else {
+        return Ok(());
+    };
+    let Value::Object(table) = table else {
+        return Err(format!("导入失败: {} 不是对象", TABLE_KEY));
+    };
+    for slot in token_slots(doc) {
+        let Some(hash) = slot.get(REF_KEY).and_then(Value::as_str) else {
+            continue;
+        };
+        let token = table
+            .get(hash)
+            .and_then(Value::as_str)
+            .ok_or_else(|| format!("导入失败: token 表里缺少 {}", hash))?;
+        *slot = Value::String(token.to_string());
+    }
+    Ok(())
+}
+
+/// JWT claims 缓存，按 token 的 sha256 作键，不存 token 原文
+pub struct ClaimsCache {
+    inner: Mutex<CacheInner>,
+}
+
+#[derive(Default)]
+struct CacheInner {
+    claims: HashMap<[u8; 32], Option<Arc<Value>>>,
+    order: VecDeque<[u8; 32]>,
+    decodes: usize,
+}
+
+impl Default for ClaimsCache {
+    fn default() -> Self {
+        Self {
+            inner: Mutex::new(CacheInner::default()),
+        }
+    }
+}
+
+impl ClaimsCache {
+    /// 取 claims；缓存里没有时调 `decode` 并记下结果（解不出来的也记，免得反复解）
+    pub fn get_or_decode(
+        &self,
+        token: &str,
+        decode: impl FnOnce(&str) -> Option<Value>,
+    ) -> Option<Arc<Value>> {
+        let key: [u8; 32] = Sha256::digest(token.as_bytes()).into();
+        if let Ok(inner) = self.inner.lock() {
+            if let Some(hit) = inner.claims.get(&key) {
+                return hit.clone();
+            }
+        }
+        let claims = decode(token).map(Arc::new);
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.decodes += 1;
+            if inner.claims.insert(key, claims.clone()).is_none() {
+                inner.order.push_back(key);
+            }
+            while inner.order.len() > CLAIMS_CACHE_CAP {
+                if let Some(old) = inner.order.pop_front() {
+                    inner.claims.remove(&old);
+                }
+            }
+        }
+        claims
+    }
+
+    /// 实际解码过多少次（测试 / 诊断用）
+    pub fn decodes(&self) -> usize {
+        self.inner.lock().map(|i| i.decodes).unwrap_or(0)
+    }
+}
+
+pub fn claims_cache() -> &'static ClaimsCache {
+    static CACHE: OnceLock<ClaimsCache> = OnceLock::new();
+    CACHE.get_or_init(ClaimsCache::default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn doc_with(tokens: &[&str]) -> Value {
+        let accounts: Map<String, Value> = tokens
+            .iter()
+            .enumerate()
+            .map(|(i, t)| {
+                (
+                    format!("acc-{i}"),
+                    json!({ "auth_json": { "tokens": { "id_token": t, "access_token": format!("at-{i}") } } }),
+                )
+            })
+            .collect();
+        json!({ "accounts": accounts, "version": 0 })
+    }
+
+    #[test]
+    fn only_repeated_long_tokens_are_interned() {
+        let long = "x".repeat(MIN_INTERN_LEN);
+        let other = "y".repeat(MIN_INTERN_LEN);
+        let mut doc = doc_with(&[&long, &long, &other, "short", "short"]);
+        let original = doc.clone();
+        let stats = intern(&mut doc);
+        assert_eq!(
+            stats,
+            InternStats {
+                tokens: 1,
+                references: 2
+            }
+        );
+        assert_eq!(doc[TABLE_KEY][token_hash(&long)], json!(long));
+        assert_eq!(
+            doc["accounts"]["acc-0"]["auth_json"]["tokens"]["id_token"],
+            json!({ REF_KEY: token_hash(&long) })
+        );
+        assert_eq!(
+            doc["accounts"]["acc-2"]["auth_json"]["tokens"]["id_token"],
+            json!(other)
+        );
+        expand(&mut doc).unwrap();
+        assert_eq!(doc, original);
+    }
+
+    #[test]
+    fn nothing_to_intern_leaves_doc_untouched() {
+        let mut doc = doc_with(&["a", "b"]);
+        let original = doc.clone();
+        assert_eq!(intern(&mut doc), InternStats::default());
+        assert_eq!(doc, original);
+        expand(&mut doc).unwrap();
+        assert_eq!(doc, original);
+    }
+
+    #[test]
+    fn dangling_reference_is_an_error() {
+        let mut doc = json!({
+            "accounts": { "a": { "auth_json": { "tokens": { "id_token": { REF_KEY: "beef" } } } } },
+            TABLE_KEY: {},
+        });
+        assert!(expand(&mut doc).unwrap_err().contains("beef"));
+    }
+
+    #[test]
+    fn identical_tokens_decode_once() {
+        let cache = ClaimsCache::default();
+        let decode = |_: &str| Some(json!({ "email": "a@b.c" }));
+        let first = cache.get_or_decode("tok", decode).unwrap();
+        let second = cache.get_or_decode("tok", decode).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(cache.get_or_decode("bad", |_| None), None);
+        assert_eq!(cache.get_or_decode("bad", |_| None), None);
+        assert_eq!(cache.decodes(), 2);
+    }
+}
@@ -0,0 +1,134 @@
+//! 账号 access_token 的新鲜度
+//!
+//! 列表上想一眼看出哪些号的 token 已经过期、快过期，又不能为了看一眼去刷新。
+//! 这里只读 auth_json 算一个 [`TokenStatus`]：
+//! - `expires_at` 用 `AccountStore::extract_access_token_expiry`（JWT 的 `exp` 优先，
+//!   不是 JWT 时取 `tokens.expires_at`）
+//! - `last_refresh` 用 `AccountStore::extract_last_refresh`
+//! - 剩余时间不超过设置 `token_expiring_soon_minutes` 算快过期（0 = 不标快过期）
+//!
+//! 中转站账号没有 token，一律 `unknown`。
+
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+
+use crate::account::{Account, AccountStore, AppSettings};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenState {
+    Fresh,
+    ExpiringSoon,
+    Expired,
+    /// 没有 token 或读不出过期时间
+    Unknown,
+}
+
+/// `get_account_statuses` 里每个账号的一项
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TokenStatus {
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_refresh: Option<DateTime<Utc>>,
+    pub token_state: TokenState,
+}
+
+/// 按过期时间分档
+pub fn classify(
+    expires_at: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+    soon_minutes: u32,
+) -> TokenState {
+    let Some(expires_at) = expires_at else {
+        return TokenState::Unknown;
+    };
+    if expires_at <= now {
+        TokenState::Expired
+    } else if expires_at - now <= Duration::minutes(i64::from(soon_minutes)) {
+        TokenState::ExpiringSoon
+    } else {
+        TokenState::Fresh
+    }
+}
+
+pub fn status(account: &Account, now: DateTime<Utc>, settings: &AppSettings) -> TokenStatus {
+    if account.is_relay() {
+        return TokenStatus {
+            expires_at: None,
+            last_refresh: None,
+            token_state: TokenState::Unknown,
+        };
+    }
+    let expires_at = AccountStore::extract_access_token_expiry(&account.auth_json);
+    TokenStatus {
+        expires_at,
+        last_refresh: AccountStore::extract_last_refresh(&account.auth_json),
+        token_state: classify(expires_at, now, settings.token_expiring_soon_minutes),
+    }
+}
+
+/// 列表里所有账号的状态，键为账号 id
+pub fn statuses(store: &AccountStore, now: DateTime<Utc>) -> HashMap<String, TokenStatus> {
+    store
+        .accounts
+        .values()
+        .map(|a| (a.id.clone(), status(a, now, &store.settings)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn expiry_is_bucketed_by_the_configured_threshold() {
+        let now = Utc::now();
+        let at = |minutes: i64| Some(now + Duration::minutes(minutes));
+        assert_eq!(classify(None, now, 30), TokenState::Unknown);
+        assert_eq!(classify(at(-1), now, 30), TokenState::Expired);
+        assert_eq!(classify(at(0), now, 30), TokenState::Expired);
+        assert_eq!(classify(at(30), now, 30), TokenState::ExpiringSoon);
+        assert_eq!(classify(at(31), now, 30), TokenState::Fresh);
+        // 0 = 不标快过期
+        assert_eq!(classify(at(1), now, 0), TokenState::Fresh);
+    }
+
+    #[test]
+    fn status_reads_tokens_expires_at_and_last_refresh_without_touching_the_account() {
+        let now = DateTime::parse_from_rfc3339("2026-01-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let mut store = AccountStore::default();
+        store.settings.token_expiring_soon_minutes = 30;
+        let auth = json!({
+            "tokens": {
+                "access_token": "opaque",
+                "refresh_token": "rt",
+                "expires_at": "2026-01-01T12:10:00Z",
+            },
+            "last_refresh": "2026-01-01T11:10:00Z",
+        });
+        let id = store.add_account("a".to_string(), auth.clone(), None).id;
+
+        let all = statuses(&store, now);
+        let s = &all[&id];
+        assert_eq!(s.token_state, TokenState::ExpiringSoon);
+        assert_eq!(
+            s.expires_at.unwrap().to_rfc3339(),
+            "2026-01-01T12:10:00+00:00"
+        );
+        assert_eq!(
+            s.last_refresh.unwrap().to_rfc3339(),
+            "2026-01-01T11:10:00+00:00"
+        );
+        assert_eq!(store.accounts[&id].auth_json, auth);
+
+        let json = serde_json::to_value(s).unwrap();
+        assert_eq!(json["token_state"], "expiring_soon");
+
+        store.accounts.get_mut(&id).unwrap().auth_json = json!({});
+        assert_eq!(statuses(&store, now)[&id].token_state, TokenState::Unknown);
+    }
+}
@@ -0,0 +1,377 @@
+//! 账号 token 的持久化后端（`AppSettings.token_storage`）
+//!
+//! - `File`（默认）：token 跟着 accounts.json 一起落盘（0600）
+//! - `Keychain`：refresh_token（可选连同 access / id token）存进系统凭据库
+//!   （macOS 钥匙串 / Windows 凭据管理器 / Linux Secret Service），
+//!   accounts.json 里只留 `token_ref` 引用键
+//!
+//! 内存里的 `AccountStore` 永远是完整的：只在 `save` / `load` 边界拆装 token，
+//! 其余代码不感知后端。凭据库读写失败时不丢 token：写失败回落为明文写进文件，
+//! 读失败保留 `token_ref`、不覆盖凭据库里的旧值，错误通过 [`last_error`] 暴露给前端。
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Mutex, OnceLock};
+
+/// 凭据库里的 service 名
+pub const KEYCHAIN_SERVICE: &str = "codex-switcher";
+
+/// token 存放位置
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenStorage {
+    #[default]
+    File,
+    Keychain,
+}
+
+impl TokenStorage {
+    pub fn label(&self) -> &'static str {
+        match self {
+            TokenStorage::File => "accounts.json",
+            TokenStorage::Keychain => "系统凭据库",
+        }
+    }
+}
+
+/// 凭据库错误；`code` 给前端按类型提示，Display 以 code 开头（同 `usage::UPSTREAM_UNAVAILABLE` 的约定）
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "code", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TokenBackendError {
+    /// 系统没有可用的凭据服务（如 Linux 没跑 Secret Service）
+    KeychainUnavailable { detail: String },
+    /// 钥匙串已锁定 / 用户拒绝访问
+    KeychainLocked { detail: String },
+    /// 其它读写失败
+    KeychainFailed { detail: String },
+}
+
+impl TokenBackendError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            TokenBackendError::KeychainUnavailable { .. } => "KEYCHAIN_UNAVAILABLE",
+            TokenBackendError::KeychainLocked { .. } => "KEYCHAIN_LOCKED",
+            TokenBackendError::KeychainFailed { .. } => "KEYCHAIN_FAILED",
+        }
+    }
+}
+
+impl fmt::Display for TokenBackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (hint, detail) = match self {
+            TokenBackendError::KeychainUnavailable { detail } => (
+                "系统凭据服务不可用（Linux 需要运行 Secret Service）",
+                detail,
+            ),
+            TokenBackendError::KeychainLocked { detail } => {
+                ("钥匙串已锁定或拒绝访问，请解锁后重试", detail)
+            }
+            TokenBackendError::KeychainFailed { detail } => ("读写系统凭据库失败", detail),
+        };
+        write!(f, "{}: {}: {}", self.code(), hint, detail)
+    }
+}
+
+/// token 存取后端；key 为 [`token_ref_key`]，值为 [`TokenSecrets`] 的 JSON
+pub trait TokenBackend: Send + Sync {
+    /// 条目不存在时返回 `Ok(None)`
+    fn get(&self, key: &str) -> Result<Option<String>, TokenBackendError>;
+    fn set(&self, key: &str, secret: &str) -> Result<(), TokenBackendError>;
+    /// 条目不存在也算成功
+    fn delete(&self, key: &str) -> Result<(), TokenBackendError>;
+}
+
+/// 账号在凭据库里的条目名
+pub fn token_ref_key(account_id: &str) -> String {
+    format!("account:{}", account_id)
+}
+
+/// 系统凭据库（keyring crate）。记住本进程写过 / 读到的值，
+/// 内容没变就不重复写——`save` 很频繁，每次都写会反复触发系统授权弹窗。
+#[derive(Default)]
+pub struct KeychainBackend {
+    known: Mutex<HashMap<String, String>>,
+}
+
+impl KeychainBackend {
+    fn entry(key: &str) -> Result<keyring::Entry, TokenBackendError> {
+        keyring::Entry::new(KEYCHAIN_SERVICE, key).map_err(map_keyring_error)
+    }
+
+    fn remember(&self, key: &str, secret: Option<&str>) {
+        if let Ok(mut known) = self.known.lock() {
+            match secret {
+                Some(s) => known.insert(key.to_string(), s.to_string()),
+                None => known.remove(key),
+            };
+        }
+    }
+}
+
+impl TokenBackend for KeychainBackend {
+    fn get(&self, key: &str) -> Result<Option<String>, TokenBackendError> {
+        match Self::entry(key)?.get_password() {
+            Ok(secret) => {
+                self.remember(key, Some(&secret));
+                Ok(Some(secret))
+            }
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(map_keyring_error(e)),
+        }
+    }
+
+    fn set(&self, key: &str, secret: &str) -> Result<(), TokenBackendError> {
+        let unchanged = self
+            .known
+            .lock()
+            .map(|known| known.get(key).is_some_and(|s| s == secret))
+            .unwrap_or(false);
+        if unchanged {
+            return Ok(());
+        }
+        Self::entry(key)?
+            .set_password(secret)
+            .map_err(map_keyring_error)?;
+        self.remember(key, Some(secret));
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> Result<(), TokenBackendError> {
+        match Self::entry(key)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => {
+                self.remember(key, None);
+                Ok(())
+            }
+            Err(e) => Err(map_keyring_error(e)),
+        }
+    }
+}
+
+fn map_keyring_error(e: keyring::Error) -> TokenBackendError {
+    let detail = e.to_string();
+    match e {
+        keyring::Error::NoStorageAccess(_) => TokenBackendError::KeychainLocked { detail },
+        keyring::Error::PlatformFailure(_) => TokenBackendError::KeychainUnavailable { detail },
+        _ => TokenBackendError::KeychainFailed { detail },
+    }
+}
+
+/// 进程内共享的系统凭据库后端
+pub fn keychain() -> &'static KeychainBackend {
+    static KEYCHAIN: OnceLock<KeychainBackend> = OnceLock::new();
+    KEYCHAIN.get_or_init(KeychainBackend::default)
+}
+
+/// 内存后端：测试用；`fail_with` 非空时所有操作都返回该错误，模拟钥匙串锁定等
+#[derive(Default)]
+pub struct MemoryBackend {
+    pub entries: Mutex<HashMap<String, String>>,
+    pub fail_with: Mutex<Option<TokenBackendError>>,
+}
+
+impl MemoryBackend {
+    fn check(&self) -> Result<(), TokenBackendError> {
+        match self.fail_with.lock().unwrap().clone() {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+impl TokenBackend for MemoryBackend {
+    fn get(&self, key: &str) -> Result<Option<String>, TokenBackendError> {
+        self.check()?;
+        Ok(self.entries.lock().unwrap().get(key).cloned())
+    }
+
+    fn set(&self, key: &str, secret: &str) -> Result<(), TokenBackendError> {
+        self.check()?;
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key.to_string(), secret.to_string());
+        Ok(())
+    }
+
+    fn delete(&self, key: &str) -> Result<(), TokenBackendError> {
+        self.check()?;
+        self.entries.lock().unwrap().remove(key);
+        Ok(())
+    }
+}
+
+/// 最近一次凭据库错误（save / load 里吞掉的那些），供设置页展示
+static LAST_ERROR: Mutex<Option<TokenBackendError>> = Mutex::new(None);
+
+pub fn record_error(err: &TokenBackendError) {
+    eprintln!("[TokenStore] {}", err);
+    if let Ok(mut last) = LAST_ERROR.lock() {
+        *last = Some(err.clone());
+    }
+}
+
+pub fn clear_error() {
+    if let Ok(mut last) = LAST_ERROR.lock() {
+        *last = None;
+    }
+}
+
+pub fn last_error() -> Option<TokenBackendError> {
+    LAST_ERROR.lock().ok().and_then(|last| last.clone())
+}
+
+/// 设置页展示用的存储状态
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenStorageStatus {
+    pub storage: TokenStorage,
+    pub include_access_tokens: bool,
+    /// token 还没从凭据库读出来的账号名
+    pub unresolved_accounts: Vec<String>,
+    pub last_error: Option<TokenBackendError>,
+}
+
+/// 一个账号的 token 材料（凭据库条目的内容）
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TokenSecrets {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access_token: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id_token: Option<String>,
+}
+
+impl TokenSecrets {
+    pub fn is_empty(&self) -> bool {
+        self.refresh_token.is_none() && self.access_token.is_none() && self.id_token.is_none()
+    }
+}
+
+fn take_str(obj: Option<&mut serde_json::Map<String, Value>>, key: &str) -> Option<String> {
+    match obj?.remove(key)? {
+        Value::String(s) => Some(s),
+        other => Some(other.to_string()),
+    }
+}
+
+/// 从账号上拆出 token：`Account.refresh_token` + `auth_json` 里的 `tokens.refresh_token`
+/// / 根级 `refresh_token`；`include_access` 时连同 `tokens.access_token` / `tokens.id_token`
+pub fn take_secrets(
+    auth_json: &mut Value,
+    refresh_token: &mut Option<String>,
+    include_access: bool,
+) -> TokenSecrets {
+    let nested_rt = take_str(
+        auth_json.get_mut("tokens").and_then(Value::as_object_mut),
+        "refresh_token",
+    );
+    let root_rt = take_str(auth_json.as_object_mut(), "refresh_token");
+    let mut secrets = TokenSecrets {
+        refresh_token: refresh_token.take().or(nested_rt).or(root_rt),
+        ..Default::default()
+    };
+    if include_access {
+        let tokens = auth_json.get_mut("tokens").and_then(Value::as_object_mut);
+        if let Some(tokens) = tokens {
+            secrets.access_token = take_str(Some(&mut *tokens), "access_token");
+            secrets.id_token = take_str(Some(tokens), "id_token");
+        }
+    }
+    secrets
+}
+
+/// [`take_secrets`] 的逆操作；只补缺失的字段（文件里已有的值更新，优先保留）
+pub fn restore_secrets(
+    auth_json: &mut Value,
+    refresh_token: &mut Option<String>,
+    secrets: TokenSecrets,
+) {
+    let Some(root) = auth_json.as_object_mut() else {
+        return;
+    };
+    let target = match root.get_mut("tokens").and_then(Value::as_object_mut) {
+        Some(tokens) => tokens,
+        None => root,
+    };
+    let fields = [
+        ("refresh_token", &secrets.refresh_token),
+        ("access_token", &secrets.access_token),
+        ("id_token", &secrets.id_token),
+    ];
+    for (key, value) in fields {
+        if let Some(v) = value {
+            target
+                .entry(key.to_string())
+                .or_insert_with(|| Value::String(v.clone()));
+        }
+    }
+    if refresh_token.is_none() {
+        *refresh_token = secrets.refresh_token;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn take_and_restore_round_trip() {
+        let original = json!({
+            "tokens": { "account_id": "acct", "refresh_token": "rt", "access_token": "at", "id_token": "idt" },
+            "last_refresh": "2026-01-01T00:00:00Z",
+        });
+        let mut auth = original.clone();
+        let mut rt = Some("rt".to_string());
+
+        let secrets = take_secrets(&mut auth, &mut rt, false);
+        assert_eq!(secrets.refresh_token.as_deref(), Some("rt"));
+        assert_eq!(secrets.access_token, None);
+        assert_eq!(rt, None);
+        assert!(auth.pointer("/tokens/refresh_token").is_none());
+        assert_eq!(auth["tokens"]["access_token"], "at");
+
+        restore_secrets(&mut auth, &mut rt, secrets);
+        assert_eq!(auth, original);
+        assert_eq!(rt.as_deref(), Some("rt"));
+
+        let secrets = take_secrets(&mut auth, &mut rt, true);
+        assert_eq!(secrets.id_token.as_deref(), Some("idt"));
+        assert_eq!(auth["tokens"], json!({ "account_id": "acct" }));
+        restore_secrets(&mut auth, &mut rt, secrets);
+        assert_eq!(auth, original);
+    }
+
+    #[test]
+    fn restore_keeps_newer_inline_values() {
+        let mut auth = json!({ "tokens": { "refresh_token": "rt-new" } });
+        let mut rt = Some("rt-new".to_string());
+        restore_secrets(
+            &mut auth,
+            &mut rt,
+            TokenSecrets {
+                refresh_token: Some("rt-old".to_string()),
+                access_token: Some("at".to_string()),
+                id_token: None,
+            },
+        );
+        assert_eq!(auth["tokens"]["refresh_token"], "rt-new");
+        assert_eq!(auth["tokens"]["access_token"], "at");
+        assert_eq!(rt.as_deref(), Some("rt-new"));
+    }
+
+    #[test]
+    fn errors_carry_code_prefix() {
+        let err = TokenBackendError::KeychainLocked {
+            detail: "user canceled".to_string(),
+        };
+        assert!(err.to_string().starts_with("KEYCHAIN_LOCKED: "));
+        assert_eq!(
+            serde_json::to_value(&err).unwrap(),
+            json!({ "code": "KEYCHAIN_LOCKED", "detail": "user canceled" })
+        );
+    }
+}
@@ -249,10 +249,7 @@ impl TokenTracker {
     }
 
     fn history_path() -> PathBuf {
-        dirs::home_dir()
-            .expect("home dir")
-            .join(".codex-switcher")
-            .join("token-history.jsonl")
+        crate::paths::data_dir().join("token-history.jsonl")
     }
 
     fn append_history(entry: &TokenHistoryEntry) {
@@ -270,10 +267,7 @@ impl TokenTracker {
     }
 
     fn stats_path() -> PathBuf {
-        dirs::home_dir()
-            .expect("home dir")
-            .join(".codex-switcher")
-            .join("proxy-usage.json")
+        crate::paths::data_dir().join("proxy-usage.json")
     }
 
     fn load_from_disk() -> Option<UsageStats> {
@@ -1,10 +1,23 @@
 use tauri::{
     image::Image,
+    menu::{CheckMenuItem, Menu, MenuItem, PredefinedMenuItem},
     tray::{TrayIcon, TrayIconBuilder, TrayIconEvent},
     webview::WebviewWindowBuilder,
-    AppHandle, Manager,
+    AppHandle, Emitter, Manager, Wry,
 };
 
+use crate::navigation::{self, NavIntent, WindowHost};
+
+// 右键菜单项 id。切号 / 刷新 / 后台刷新直接调后端，不依赖主窗口 webview 是否加载过
+const MENU_SWITCH_NEXT: &str = "switch_next";
+const MENU_REFRESH: &str = "refresh_current";
+const MENU_BACKGROUND_REFRESH: &str = "background_refresh";
+const MENU_DASHBOARD: &str = "dashboard";
+const MENU_SETTINGS: &str = "settings";
+const MENU_ADD_ACCOUNT: &str = "add_account";
+const MENU_QUIT: &str = "quit";
+const MENU_CANCEL_REVERT: &str = "cancel_revert";
+
 /// 初始化系统托盘
 pub fn init(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
     // 加载并缩放图标
@@ -36,15 +49,18 @@ pub fn init(app: &AppHandle) -> Result<(), Box<dyn std::error::Error>> {
     let _tray = TrayIconBuilder::with_id("main")
         .icon(icon)
         .icon_as_template(false)
+        .menu(&build_menu(app, background_refresh_enabled(app))?)
         .show_menu_on_left_click(false)
+        .on_menu_event(|app, event| handle_menu_event(app, event.id().as_ref()))
         .on_tray_icon_event(|tray: &TrayIcon, event: TrayIconEvent| {
             if let TrayIconEvent::Click {
+                button: tauri::tray::MouseButton::Left,
                 button_state: tauri::tray::MouseButtonState::Up,
                 position,
                 ..
             } = event
             {
-                // 任意点击 → 弹出 popup
+                // 左键 → 弹出 popup；右键走原生菜单
                 toggle_popup(tray.app_handle(), position);
             }
         })
@@ -124,15 +140,246 @@ fn position_popup(
     Ok(())
 }
 
+/// 右键菜单；后台刷新的勾选状态跟设置走，`update_tray_menu` 时重建
+fn build_menu(app: &AppHandle, background_refresh: bool) -> tauri::Result<Menu<Wry>> {
+    let menu = Menu::with_items(
+        app,
+        &[
+            &MenuItem::with_id(app, MENU_SWITCH_NEXT, "切到下一个账号", true, None::<&str>)?,
+            &MenuItem::with_id(app, MENU_REFRESH, "刷新当前额度", true, None::<&str>)?,
+            &CheckMenuItem::with_id(
+                app,
+                MENU_BACKGROUND_REFRESH,
+                "后台刷新",
+                true,
+                background_refresh,
+                None::<&str>,
+            )?,
+            &PredefinedMenuItem::separator(app)?,
+            &MenuItem::with_id(app, MENU_DASHBOARD, "打开主窗口", true, None::<&str>)?,
+            &MenuItem::with_id(app, MENU_SETTINGS, "设置…", true, None::<&str>)?,
+            &MenuItem::with_id(app, MENU_ADD_ACCOUNT, "添加账号…", true, None::<&str>)?,
+            &PredefinedMenuItem::separator(app)?,
+            &MenuItem::with_id(app, MENU_QUIT, "退出", true, None::<&str>)?,
+        ],
+    )?;
+    // 临时切号：顶部提示几点切回，附一个取消入口
+    if let Some(label) = pending_revert_label(app) {
+        menu.prepend_items(&[
+            &MenuItem::new(app, label, false, None::<&str>)?,
+            &MenuItem::with_id(
+                app,
+                MENU_CANCEL_REVERT,
+                "保留当前账号（取消自动切回）",
+                true,
+                None::<&str>,
+            )?,
+            &PredefinedMenuItem::separator(app)?,
+        ])?;
+    }
+    // 系统时间异常：顶部放一条不可点的提示
+    if let Some(label) = crate::clock::global().health().label() {
+        menu.prepend_items(&[
+            &MenuItem::new(app, format!("⚠️ {}", label), false, None::<&str>)?,
+            &PredefinedMenuItem::separator(app)?,
+        ])?;
+    }
+    Ok(menu)
+}
+
+fn pending_revert_label(app: &AppHandle) -> Option<String> {
+    let state = app.state::<crate::AppState>();
+    let pending = state.pending_revert.get()?;
+    let store = state.store.lock().ok()?;
+    let name = store
+        .accounts
+        .get(&pending.revert_to)
+        .map_or_else(|| pending.revert_to.clone(), |a| a.name.clone());
+    let name = if state.presentation.is_enabled() {
+        crate::presentation::mask_name(&name)
+    } else {
+        name
+    };
+    Some(format!(
+        "⏱ {} 自动切回 {}",
+        pending
+            .revert_at
+            .with_timezone(&chrono::Local)
+            .format("%H:%M"),
+        name
+    ))
+}
+
+fn background_refresh_enabled(app: &AppHandle) -> bool {
+    app.state::<crate::AppState>()
+        .store
+        .lock()
+        .map(|s| s.settings.background_refresh)
+        .unwrap_or(false)
+}
+
+fn handle_menu_event(app: &AppHandle, id: &str) {
+    match id {
+        MENU_SWITCH_NEXT => {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move { switch_next(app).await });
+        }
+        MENU_REFRESH => {
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move { refresh_current(app).await });
+        }
+        MENU_BACKGROUND_REFRESH => toggle_background_refresh(app),
+        MENU_DASHBOARD => show_main_window_from_cmd(app),
+        MENU_SETTINGS => open_main_window_with(app, NavIntent::Settings),
+        MENU_ADD_ACCOUNT => open_main_window_with(app, NavIntent::AddAccount),
+        MENU_CANCEL_REVERT => {
+            if !blocked_by_presentation(app, "取消自动切回") {
+                crate::cancel_revert(app, "托盘取消");
+            }
+        }
+        MENU_QUIT => app.exit(0),
+        _ => {}
+    }
+}
+
+/// 演示模式下拦住会改状态的托盘动作（托盘直接调后端，绕过了命令层的拦截）
+fn blocked_by_presentation(app: &AppHandle, action: &str) -> bool {
+    let blocked = app.state::<crate::AppState>().presentation.is_enabled();
+    if blocked {
+        crate::notify::send(action, "演示模式下不允许此操作");
+    }
+    blocked
+}
+
+async fn switch_next(app: AppHandle) {
+    if blocked_by_presentation(&app, "切号") {
+        return;
+    }
+    let state = app.state::<crate::AppState>();
+    match crate::switch_to_next_account_internal(state, app.clone()).await {
+        Ok(_) => {
+            let _ = app.emit("accounts-updated", ());
+        }
+        Err(e) => {
+            eprintln!("[Tray] 切号失败: {}", e);
+            crate::notify::send("切号失败", &e);
+        }
+    }
+}
+
+async fn refresh_current(app: AppHandle) {
+    if blocked_by_presentation(&app, "刷新额度") {
+        return;
+    }
+    let state = app.state::<crate::AppState>();
+    let current_relay = state.store.lock().ok().and_then(|s| {
+        let id = s.current.clone()?;
+        s.accounts.get(&id).filter(|a| a.is_relay()).map(|_| id)
+    });
+    // Relay 走中转站余额刷新，订阅号走 OpenAI usage
+    let result = match current_relay {
+        Some(id) => crate::refresh_relay_usage(state, id).await.map(|_| ()),
+        None => state
+            .account_service(&app)
+            .refresh_current_quota()
+            .await
+            .map(|_| ()),
+    };
+    match result {
+        Ok(()) => {
+            let _ = app.emit("accounts-updated", ());
+            update_tray_menu(&app);
+        }
+        Err(e) => {
+            eprintln!("[Tray] 刷新额度失败: {}", e);
+            crate::notify::send("刷新额度失败", &e);
+        }
+    }
+}
+
+fn toggle_background_refresh(app: &AppHandle) {
+    if blocked_by_presentation(app, "后台刷新") {
+        update_tray_menu(app);
+        return;
+    }
+    let state = app.state::<crate::AppState>();
+    let enabled = !background_refresh_enabled(app);
+    let result = state
+        .account_service(app)
+        .set_background_refresh(enabled)
+        .and_then(|prev| crate::apply_background_refresh(&state, app, prev, enabled));
+    if let Err(e) = result {
+        eprintln!("[Tray] 切换后台刷新失败: {}", e);
+        crate::notify::send("后台刷新", &e);
+    } else {
+        println!("[Tray] 后台刷新已{}", if enabled { "开启" } else { "关闭" });
+    }
+    // 失败时把勾选状态拨回去
+    update_tray_menu(app);
+}
+
+/// 主窗口的 [`WindowHost`] 实现
+struct MainWindowHost<'a>(&'a AppHandle);
+
+impl WindowHost for MainWindowHost<'_> {
+    fn has_main_window(&self) -> bool {
+        self.0.get_webview_window("main").is_some()
+    }
+
+    fn create_main_window(&self) -> Result<(), String> {
+        let config = self
+            .0
+            .config()
+            .app
+            .windows
+            .iter()
+            .find(|w| w.label == "main")
+            .cloned()
+            .ok_or("配置里没有主窗口")?;
+        WebviewWindowBuilder::from_config(self.0, &config)
+            .and_then(|builder| builder.build())
+            .map(|_| ())
+            .map_err(|e| format!("创建主窗口失败: {}", e))
+    }
+
+    fn show_main_window(&self) {
+        if let Some(window) = self.0.get_webview_window("main") {
+            let _ = window.show();
+            let _ = window.unminimize();
+            let _ = window.set_focus();
+            #[cfg(target_os = "macos")]
+            self.0
+                .set_activation_policy(tauri::ActivationPolicy::Regular)
+                .unwrap_or(());
+        }
+    }
+
+    fn notify_pending(&self) {
+        let _ = self.0.emit(navigation::PENDING_EVENT, ());
+    }
+}
+
+/// 需要界面的托盘动作：确保主窗口在并前置，再把跳转意图排队给前端
+fn open_main_window_with(app: &AppHandle, intent: NavIntent) {
+    let state = app.state::<crate::AppState>();
+    if let Err(e) = navigation::open_with_intent(&MainWindowHost(app), &state.navigation, intent) {
+        eprintln!("[Tray] 打开主窗口失败: {}", e);
+    }
+    if let Some(popup) = app.get_webview_window("tray-popup") {
+        let _ = popup.hide();
+    }
+}
+
+/// 显示主窗口；主窗口不存在时按配置新建
 pub fn show_main_window(app: &AppHandle) {
-    if let Some(window) = app.get_webview_window("main") {
-        let _ = window.show();
-        let _ = window.unminimize();
-        let _ = window.set_focus();
-        #[cfg(target_os = "macos")]
-        app.set_activation_policy(tauri::ActivationPolicy::Regular)
-            .unwrap_or(());
+    let host = MainWindowHost(app);
+    if !host.has_main_window() {
+        if let Err(e) = host.create_main_window() {
+            eprintln!("[Tray] {}", e);
+            return;
+        }
     }
+    host.show_main_window();
 }
 
 /// 供 Tauri command 调用的入口
@@ -144,7 +391,7 @@ pub fn show_main_window_from_cmd(app: &AppHandle) {
     }
 }
 
-/// 更新托盘 tooltip（不再需要完整菜单）
+/// 更新托盘 tooltip，并按当前设置重建右键菜单
 pub fn update_tray_menu(app: &AppHandle) {
     let state = app.state::<crate::AppState>();
     let store = match state.store.lock() {
@@ -157,9 +404,30 @@ pub fn update_tray_menu(app: &AppHandle) {
             let quota = acc
                 .cached_quota
                 .as_ref()
-                .map(|q| format!(" | 5H: {:.0}%  周: {:.0}%", q.five_hour_left, q.weekly_left))
+                .map(|q| {
+                    // 额度用完时标出卡住的是哪个窗口：5H 几小时就回来，周额度要等几天
+                    let blocker = crate::quota_window::classify(q, chrono::Utc::now().timestamp())
+                        .blocker()
+                        .map(|b| format!(" ({})", b.label()))
+                        .unwrap_or_default();
+                    format!(
+                        " | 5H: {}  周: {}{}",
+                        crate::usage::format_left(q.five_hour_left, &q.five_hour_counts),
+                        crate::usage::format_left(q.weekly_left, &q.weekly_counts),
+                        blocker
+                    )
+                })
                 .unwrap_or_default();
-            format!("Codex Switcher - {}{}", acc.name, quota)
+            let name = if state.presentation.is_enabled() {
+                crate::presentation::mask_name(&acc.name)
+            } else {
+                acc.name.clone()
+            };
+            format!(
+                "Codex Switcher - {}{}",
+                tray_entry_label(&name, acc.avatar_emoji.as_deref(), acc.color.as_deref()),
+                quota
+            )
         } else {
             "Codex Switcher".to_string()
         }
@@ -167,7 +435,95 @@ pub fn update_tray_menu(app: &AppHandle) {
         "Codex Switcher - 未登录".to_string()
     };
 
+    let tooltip = match crate::clock::global().health().label() {
+        Some(label) => format!("⚠️ {}\n{}", label, tooltip),
+        None => tooltip,
+    };
+
+    let background_refresh = store.settings.background_refresh;
+    drop(store);
+
     if let Some(tray) = app.tray_by_id("main") {
         let _ = tray.set_tooltip(Some(&tooltip));
+        match build_menu(app, background_refresh) {
+            Ok(menu) => {
+                let _ = tray.set_menu(Some(menu));
+            }
+            Err(e) => eprintln!("[Tray] 重建菜单失败: {}", e),
+        }
+    }
+}
+
+/// 原生菜单 / tooltip 画不了任意颜色，只能用彩色圆点 emoji 近似。
+/// RGB 取自常见 emoji 字体里圆点的主色。
+const COLOR_PALETTE: [((u8, u8, u8), &str); 9] = [
+    ((221, 46, 68), "🔴"),
+    ((244, 144, 12), "🟠"),
+    ((253, 203, 88), "🟡"),
+    ((120, 177, 89), "🟢"),
+    ((85, 172, 238), "🔵"),
+    ((170, 142, 214), "🟣"),
+    ((193, 105, 79), "🟤"),
+    ((49, 55, 61), "⚫"),
+    ((230, 231, 232), "⚪"),
+];
+
+/// 把 `#RRGGBB` 映射到色板里欧氏距离最近的圆点 emoji；非法颜色返回 None
+pub fn nearest_color_emoji(color: &str) -> Option<&'static str> {
+    let (r, g, b) = crate::account::parse_hex_color(color)?;
+    let dist = |(pr, pg, pb): (u8, u8, u8)| {
+        let d = |a: u8, b: u8| (a as i32 - b as i32).pow(2);
+        d(r, pr) + d(g, pg) + d(b, pb)
+    };
+    COLOR_PALETTE
+        .iter()
+        .min_by_key(|(rgb, _)| dist(*rgb))
+        .map(|(_, emoji)| *emoji)
+}
+
+/// 托盘条目文案：有头像 emoji 时加 emoji 前缀，否则有颜色标签时加圆点前缀
+fn tray_entry_label(name: &str, avatar: Option<&str>, color: Option<&str>) -> String {
+    match avatar.or_else(|| color.and_then(nearest_color_emoji)) {
+        Some(prefix) => format!("{} {}", prefix, name),
+        None => name.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn palette_colors_map_to_themselves() {
+        assert_eq!(nearest_color_emoji("#dd2e44"), Some("🔴"));
+        assert_eq!(nearest_color_emoji("#55ACEE"), Some("🔵"));
+    }
+
+    #[test]
+    fn arbitrary_colors_map_to_nearest_entry() {
+        assert_eq!(nearest_color_emoji("#ff0000"), Some("🔴"));
+        assert_eq!(nearest_color_emoji("#00ff00"), Some("🟢"));
+        assert_eq!(nearest_color_emoji("#000000"), Some("⚫"));
+        assert_eq!(nearest_color_emoji("#ffffff"), Some("⚪"));
+        assert_eq!(nearest_color_emoji("#8000ff"), Some("🟣"));
+    }
+
+    #[test]
+    fn invalid_color_gets_no_prefix() {
+        assert_eq!(nearest_color_emoji("#f00"), None);
+        assert_eq!(tray_entry_label("a@b.com", None, Some("red")), "a@b.com");
+        assert_eq!(tray_entry_label("a@b.com", None, None), "a@b.com");
+        assert_eq!(
+            tray_entry_label("a@b.com", None, Some("#ff0000")),
+            "🔴 a@b.com"
+        );
+    }
+
+    #[test]
+    fn avatar_emoji_takes_precedence_over_color_dot() {
+        assert_eq!(
+            tray_entry_label("a@b.com", Some("🦊"), Some("#ff0000")),
+            "🦊 a@b.com"
+        );
     }
 }
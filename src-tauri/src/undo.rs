@@ -0,0 +1,507 @@
+//! 破坏性操作的撤销窗口
+//!
+//! 删号（单个 / 批量）和替换式导入在动手前把受影响账号的完整 `Account` 存进内存里的撤销缓冲，
+//! 返回一个 `undo_token`；[`UNDO_TTL`] 内拿它调 `undo` 就原样放回（同 id、同数据）。
+//! - 缓冲只在内存里，和磁盘无关：之后的 save 不影响撤销，重启就没了
+//! - 最多留最近 [`MAX_UNDO_ENTRIES`] 次操作，更早的被挤掉
+//! - 一次操作一个 token，撤销是全有或全无：批量删除后只要有一个 id 又被占用
+//!   （比如重新导入了同一个账号），整次撤销都拒绝，什么也不改
+//! - `current`：被删的账号里有当时的 current、且之后 current 没被切到别处，撤销时指回去
+//!
+//! 时间从 [`Clock`] 取，测试里换成假时钟。
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+use crate::account::{Account, AccountStore, AppSettings};
+use crate::activity::{Clock, SystemClock};
+
+/// 撤销窗口
+pub const UNDO_TTL: Duration = Duration::from_secs(60);
+/// 最多保留的操作数
+pub const MAX_UNDO_ENTRIES: usize = 5;
+/// 记住多少个已失效的 token，用来给出"已撤销过 / 已过期"而不是笼统的"不存在"
+const MAX_RETIRED: usize = 32;
+
+/// 撤销失败的错误前缀，前端据此识别
+pub const ERROR_PREFIX: &str = "UNDO_UNAVAILABLE";
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum UndoKind {
+    Delete,
+    ImportReplace,
+}
+
+/// 操作前的现场
+#[derive(Debug, Clone)]
+pub struct UndoStash {
+    pub kind: UndoKind,
+    /// 操作前受影响账号的完整数据
+    pub accounts: Vec<Account>,
+    /// 操作新引入、撤销时要拿掉的账号（替换式导入带进来的新 id）
+    pub introduced: Vec<String>,
+    /// 操作前的 current
+    pub current_before: Option<String>,
+    /// 操作刚结束时的 current；撤销时还是它才把 current 指回去
+    pub current_after: Option<String>,
+    /// 替换式导入连设置一起换了，撤销时一并还原
+    pub settings: Option<AppSettings>,
+}
+
+impl UndoStash {
+    /// 删号前调用：`ids` 里不存在的账号忽略
+    pub fn before_delete(store: &AccountStore, ids: &[String]) -> Self {
+        Self {
+            kind: UndoKind::Delete,
+            accounts: ids
+                .iter()
+                .filter_map(|id| store.accounts.get(id).cloned())
+                .collect(),
+            introduced: Vec::new(),
+            current_before: store.current.clone(),
+            current_after: None,
+            settings: None,
+        }
+    }
+
+    /// 替换式导入前调用：旧库整份存下，新库里多出来的 id 撤销时拿掉
+    pub fn before_replace(store: &AccountStore, incoming: &AccountStore) -> Self {
+        Self {
+            kind: UndoKind::ImportReplace,
+            accounts: store.accounts.values().cloned().collect(),
+            introduced: incoming
+                .accounts
+                .keys()
+                .filter(|id| !store.accounts.contains_key(*id))
+                .cloned()
+                .collect(),
+            current_before: store.current.clone(),
+            current_after: None,
+            settings: Some(store.settings.clone()),
+        }
+    }
+
+    /// 操作完成后记下当时的 current
+    pub fn finish(mut self, store: &AccountStore) -> Self {
+        self.current_after = store.current.clone();
+        self
+    }
+
+    pub fn account_ids(&self) -> Vec<String> {
+        self.accounts.iter().map(|a| a.id.clone()).collect()
+    }
+}
+
+/// 破坏性操作返回给前端的撤销凭据
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct UndoReceipt {
+    pub undo_token: String,
+    pub kind: UndoKind,
+    /// 受影响（可恢复）的账号
+    pub account_ids: Vec<String>,
+    pub expires_in_secs: u64,
+}
+
+/// 撤销结果
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct UndoOutcome {
+    pub kind: UndoKind,
+    pub restored: Vec<String>,
+    pub removed: Vec<String>,
+    /// current 被指回了原来的账号
+    pub current_restored: bool,
+}
+
+/// 撤销前检查：删号撤销要求所有 id 都还空着（全有或全无）
+pub fn check(store: &AccountStore, stash: &UndoStash) -> Result<(), String> {
+    if stash.kind != UndoKind::Delete {
+        return Ok(());
+    }
+    let taken: Vec<&str> = stash
+        .accounts
+        .iter()
+        .filter(|a| store.accounts.contains_key(&a.id))
+        .map(|a| a.name.as_str())
+        .collect();
+    if taken.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "{}:以下账号已重新存在，撤销会覆盖它们，已取消: {}",
+            ERROR_PREFIX,
+            taken.join(", ")
+        ))
+    }
+}
+
+/// 把现场放回 store（先 [`check`]）
+pub fn apply(store: &mut AccountStore, stash: UndoStash) -> UndoOutcome {
+    let restored = stash.account_ids();
+    let mut removed = Vec::new();
+    for id in &stash.introduced {
+        if store.accounts.remove(id).is_some() {
+            removed.push(id.clone());
+        }
+    }
+    if let Some(settings) = stash.settings {
+        // token 存储方式是本机属性，导入时没换，撤销也不动
+        let (storage, include_access) = (
+            store.settings.token_storage,
+            store.settings.keychain_include_access_tokens,
+        );
+        store.settings = settings;
+        store.settings.token_storage = storage;
+        store.settings.keychain_include_access_tokens = include_access;
+    }
+    for account in stash.accounts {
+        store.restore_account(account);
+    }
+    let current_restored = stash.current_before.is_some()
+        && store.current == stash.current_after
+        && stash
+            .current_before
+            .as_ref()
+            .is_some_and(|id| store.accounts.contains_key(id))
+        && store.current != stash.current_before;
+    if current_restored {
+        store.current = stash.current_before;
+    } else if store
+        .current
+        .as_ref()
+        .is_some_and(|id| !store.accounts.contains_key(id))
+    {
+        store.current = None;
+    }
+    UndoOutcome {
+        kind: stash.kind,
+        restored,
+        removed,
+        current_restored,
+    }
+}
+
+struct Entry {
+    token: String,
+    created: Instant,
+    stash: UndoStash,
+}
+
+#[derive(Clone, Copy)]
+enum Retired {
+    Used,
+    Expired,
+    Evicted,
+}
+
+#[derive(Default)]
+struct Inner {
+    entries: VecDeque<Entry>,
+    retired: VecDeque<(String, Retired)>,
+}
+
+impl Inner {
+    fn retire(&mut self, token: String, why: Retired) {
+        self.retired.push_back((token, why));
+        while self.retired.len() > MAX_RETIRED {
+            self.retired.pop_front();
+        }
+    }
+
+    /// 过期的条目连同账号数据一起丢掉，只记 token
+    fn prune(&mut self, now: Instant, ttl: Duration) {
+        while let Some(pos) = self
+            .entries
+            .iter()
+            .position(|e| now.saturating_duration_since(e.created) >= ttl)
+        {
+            if let Some(entry) = self.entries.remove(pos) {
+                self.retire(entry.token, Retired::Expired);
+            }
+        }
+    }
+}
+
+pub struct UndoBuffer<C: Clock = SystemClock> {
+    clock: C,
+    ttl: Duration,
+    inner: Mutex<Inner>,
+}
+
+impl Default for UndoBuffer {
+    fn default() -> Self {
+        Self::with_clock(SystemClock, UNDO_TTL)
+    }
+}
+
+impl<C: Clock> UndoBuffer<C> {
+    pub fn with_clock(clock: C, ttl: Duration) -> Self {
+        Self {
+            clock,
+            ttl,
+            inner: Mutex::new(Inner::default()),
+        }
+    }
+
+    /// 存一次操作的现场，返回撤销凭据
+    pub fn push(&self, stash: UndoStash) -> UndoReceipt {
+        let token = uuid::Uuid::new_v4().to_string();
+        let receipt = UndoReceipt {
+            undo_token: token.clone(),
+            kind: stash.kind,
+            account_ids: stash.account_ids(),
+            expires_in_secs: self.ttl.as_secs(),
+        };
+        let now = self.clock.now();
+        if let Ok(mut inner) = self.inner.lock() {
+            inner.prune(now, self.ttl);
+            inner.entries.push_back(Entry {
+                token,
+                created: now,
+                stash,
+            });
+            while inner.entries.len() > MAX_UNDO_ENTRIES {
+                if let Some(old) = inner.entries.pop_front() {
+                    inner.retire(old.token, Retired::Evicted);
+                }
+            }
+        }
+        receipt
+    }
+
+    /// 兑现撤销：`precheck` 通过才取出现场并消耗 token，不通过时条目原样留着（还能再试）
+    pub fn redeem(
+        &self,
+        token: &str,
+        precheck: impl FnOnce(&UndoStash) -> Result<(), String>,
+    ) -> Result<UndoStash, String> {
+        let now = self.clock.now();
+        let mut inner = self.inner.lock().map_err(|e| e.to_string())?;
+        inner.prune(now, self.ttl);
+        let Some(pos) = inner.entries.iter().position(|e| e.token == token) else {
+            let why = inner
+                .retired
+                .iter()
+                .rev()
+                .find(|(t, _)| t == token)
+                .map(|(_, why)| *why);
+            return Err(match why {
+                Some(Retired::Used) => format!("{}:该操作已经撤销过了", ERROR_PREFIX),
+                Some(Retired::Expired) => format!(
+                    "{}:撤销已过期（只保留 {} 秒）",
+                    ERROR_PREFIX,
+                    self.ttl.as_secs()
+                ),
+                Some(Retired::Evicted) => format!(
+                    "{}:之后又有 {} 次可撤销操作，这次已无法撤销",
+                    ERROR_PREFIX, MAX_UNDO_ENTRIES
+                ),
+                None => format!("{}:撤销凭据无效或已过期", ERROR_PREFIX),
+            });
+        };
+        precheck(&inner.entries[pos].stash)?;
+        let Some(entry) = inner.entries.remove(pos) else {
+            return Err(format!("{}:撤销凭据无效或已过期", ERROR_PREFIX));
+        };
+        inner.retire(entry.token, Retired::Used);
+        Ok(entry.stash)
+    }
+
+    /// 撤销 = 检查 + 放回 store，供命令层在持有 store 锁时调用
+    pub fn undo(&self, store: &mut AccountStore, token: &str) -> Result<UndoOutcome, String> {
+        let stash = self.redeem(token, |stash| check(store, stash))?;
+        Ok(apply(store, stash))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[derive(Clone)]
+    struct FakeClock(Arc<Mutex<Instant>>);
+
+    impl FakeClock {
+        fn new() -> Self {
+            Self(Arc::new(Mutex::new(Instant::now())))
+        }
+
+        fn advance(&self, d: Duration) {
+            *self.0.lock().unwrap() += d;
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> Instant {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    fn account(id: &str) -> Account {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "name": format!("name-{}", id),
+            "auth_json": { "tokens": { "refresh_token": format!("rt-{}", id) } },
+            "refresh_token": format!("rt-{}", id),
+            "created_at": "2026-01-01T00:00:00Z",
+            "last_used": null,
+            "notes": format!("notes-{}", id),
+        }))
+        .unwrap()
+    }
+
+    fn store_with(ids: &[&str], current: Option<&str>) -> AccountStore {
+        let mut store = AccountStore::default();
+        for id in ids {
+            store.accounts.insert(id.to_string(), account(id));
+        }
+        store.current = current.map(String::from);
+        store
+    }
+
+    fn buffer() -> (UndoBuffer<FakeClock>, FakeClock) {
+        let clock = FakeClock::new();
+        (UndoBuffer::with_clock(clock.clone(), UNDO_TTL), clock)
+    }
+
+    /// 模拟 delete 命令：跳过不存在的 id，删除前存现场，删除后记 current
+    fn delete(buf: &UndoBuffer<FakeClock>, store: &mut AccountStore, ids: &[&str]) -> UndoReceipt {
+        let ids: Vec<String> = ids
+            .iter()
+            .filter(|id| store.accounts.contains_key(**id))
+            .map(|s| s.to_string())
+            .collect();
+        let stash = UndoStash::before_delete(store, &ids);
+        for id in &ids {
+            if store.current.as_deref() == Some(id.as_str()) {
+                store.current = None;
+            }
+            store.delete_account(id).unwrap();
+        }
+        buf.push(stash.finish(store))
+    }
+
+    #[test]
+    fn delete_then_undo_restores_same_account_and_current() {
+        let (buf, _) = buffer();
+        let mut store = store_with(&["a", "b"], Some("a"));
+        let before = store.accounts["a"].clone();
+        let receipt = delete(&buf, &mut store, &["a"]);
+        assert_eq!(receipt.account_ids, vec!["a"]);
+        assert!(!store.accounts.contains_key("a"));
+        assert_eq!(store.current, None);
+
+        let outcome = buf.undo(&mut store, &receipt.undo_token).unwrap();
+        assert_eq!(outcome.restored, vec!["a"]);
+        assert!(outcome.current_restored);
+        assert_eq!(store.current.as_deref(), Some("a"));
+        let restored = &store.accounts["a"];
+        assert_eq!(restored.name, before.name);
+        assert_eq!(restored.notes, before.notes);
+        assert_eq!(restored.auth_json, before.auth_json);
+        assert!(store.deleted_accounts.iter().all(|t| t.id != "a"));
+
+        let again = buf.undo(&mut store, &receipt.undo_token).unwrap_err();
+        assert!(again.starts_with(ERROR_PREFIX) && again.contains("已经撤销过"));
+    }
+
+    #[test]
+    fn current_is_not_relinked_after_switching_elsewhere() {
+        let (buf, _) = buffer();
+        let mut store = store_with(&["a", "b"], Some("a"));
+        let receipt = delete(&buf, &mut store, &["a"]);
+        store.current = Some("b".into());
+
+        let outcome = buf.undo(&mut store, &receipt.undo_token).unwrap();
+        assert!(!outcome.current_restored);
+        assert_eq!(store.current.as_deref(), Some("b"));
+        assert!(store.accounts.contains_key("a"));
+
+        // 删的不是 current：current 不受影响
+        let receipt = delete(&buf, &mut store, &["a"]);
+        let outcome = buf.undo(&mut store, &receipt.undo_token).unwrap();
+        assert!(!outcome.current_restored);
+        assert_eq!(store.current.as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn bulk_delete_undo_is_all_or_nothing() {
+        let (buf, _) = buffer();
+        let mut store = store_with(&["a", "b", "c"], Some("c"));
+        let receipt = delete(&buf, &mut store, &["a", "b", "missing"]);
+        assert_eq!(receipt.account_ids, vec!["a", "b"]);
+
+        // b 在撤销前又被加回来了：整次撤销拒绝，a 也不恢复
+        store.accounts.insert("b".into(), account("b"));
+        let err = buf.undo(&mut store, &receipt.undo_token).unwrap_err();
+        assert!(err.contains("name-b"));
+        assert!(!store.accounts.contains_key("a"));
+
+        // 冲突解除后同一个 token 仍可用
+        store.delete_account("b").unwrap();
+        let outcome = buf.undo(&mut store, &receipt.undo_token).unwrap();
+        assert_eq!(outcome.restored, vec!["a", "b"]);
+        assert!(store.accounts.contains_key("a") && store.accounts.contains_key("b"));
+        assert_eq!(store.current.as_deref(), Some("c"));
+    }
+
+    #[test]
+    fn tokens_expire_after_ttl() {
+        let (buf, clock) = buffer();
+        let mut store = store_with(&["a", "b"], None);
+        let receipt = delete(&buf, &mut store, &["a"]);
+        clock.advance(UNDO_TTL);
+
+        let err = buf.undo(&mut store, &receipt.undo_token).unwrap_err();
+        assert!(err.contains("已过期"), "{}", err);
+        assert!(!store.accounts.contains_key("a"));
+        let unknown = buf.undo(&mut store, "nope").unwrap_err();
+        assert!(unknown.starts_with(ERROR_PREFIX));
+    }
+
+    #[test]
+    fn buffer_keeps_only_the_last_few_operations() {
+        let (buf, _) = buffer();
+        let ids: Vec<String> = (0..=MAX_UNDO_ENTRIES).map(|i| i.to_string()).collect();
+        let refs: Vec<&str> = ids.iter().map(String::as_str).collect();
+        let mut store = store_with(&refs, None);
+        let receipts: Vec<_> = refs
+            .iter()
+            .map(|id| delete(&buf, &mut store, &[id]))
+            .collect();
+
+        let err = buf.undo(&mut store, &receipts[0].undo_token).unwrap_err();
+        assert!(err.contains("无法撤销"), "{}", err);
+        assert!(buf
+            .undo(&mut store, &receipts[MAX_UNDO_ENTRIES].undo_token)
+            .is_ok());
+    }
+
+    #[test]
+    fn replace_import_undo_restores_old_library_and_drops_new_ids() {
+        let (buf, _) = buffer();
+        let mut store = store_with(&["a", "b"], Some("a"));
+        store.settings.proxy_port = 1111;
+        let mut incoming = store_with(&["b", "x"], Some("x"));
+        incoming.accounts.get_mut("b").unwrap().name = "imported".into();
+        incoming.settings.proxy_port = 2222;
+
+        let stash = UndoStash::before_replace(&store, &incoming);
+        store = incoming;
+        let receipt = buf.push(stash.finish(&store));
+        assert_eq!(receipt.kind, UndoKind::ImportReplace);
+
+        let outcome = buf.undo(&mut store, &receipt.undo_token).unwrap();
+        assert_eq!(outcome.removed, vec!["x"]);
+        let mut ids: Vec<_> = store.accounts.keys().cloned().collect();
+        ids.sort();
+        assert_eq!(ids, vec!["a", "b"]);
+        assert_eq!(store.accounts["b"].name, "name-b");
+        assert_eq!(store.settings.proxy_port, 1111);
+        assert_eq!(store.current.as_deref(), Some("a"));
+    }
+}
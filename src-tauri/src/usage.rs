@@ -2,11 +2,14 @@
 //!
 //! 从 OpenAI API 获取 Codex 使用量信息
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::sync::OnceLock;
 use std::time::Duration;
 
+use crate::request_headers::RequestOverrides;
+
 /// 进程级共享 reqwest::Client — 整个 quota 刷新链路共用一个连接池，
 /// 不再每个账号都跑一次 TLS 握手。30 秒空闲回收，最多 8 个 keep-alive。
 fn usage_client() -> &'static reqwest::Client {
@@ -22,10 +25,13 @@ fn usage_client() -> &'static reqwest::Client {
 }
 
 /// 前端展示的用量数据
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct UsageDisplay {
-    /// 套餐类型
+    /// 套餐类型（原始值）
     pub plan_type: String,
+    /// 套餐展示名，见 `plan::display_plan`
+    #[serde(default)]
+    pub display_plan: String,
     /// 5小时窗口使用百分比
     pub five_hour_used: i32,
     /// 5小时窗口剩余百分比
@@ -52,6 +58,258 @@ pub struct UsageDisplay {
     pub has_credits: bool,
     /// Token 是否对 CLI 有效 (api.openai.com)
     pub is_valid_for_cli: bool,
+    /// 套餐是否包含 Codex（false 时上面的窗口字段无意义）
+    #[serde(default = "default_true")]
+    pub codex_available: bool,
+    /// 5小时窗口的绝对次数（接口没给时全为 None）
+    #[serde(default)]
+    pub five_hour_counts: WindowCounts,
+    /// 周窗口的绝对次数
+    #[serde(default)]
+    pub weekly_counts: WindowCounts,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// 窗口的绝对请求次数。部分套餐的 wham/usage 在 `used_percent` 旁边还给
+/// `used` / `limit` / `remaining`（数字或字符串），任意一个都可能缺
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct WindowCounts {
+    pub used: Option<u64>,
+    pub limit: Option<u64>,
+    pub remaining: Option<u64>,
+}
+
+impl WindowCounts {
+    fn from_window(window: &Value) -> Self {
+        let count = |key: &str| {
+            window
+                .get(key)
+                .and_then(UsageFetcher::parse_number)
+                .filter(|n| n.is_finite() && *n >= 0.0)
+                .map(|n| n.round() as u64)
+        };
+        Self {
+            used: count("used"),
+            limit: count("limit"),
+            remaining: count("remaining"),
+        }
+    }
+
+    /// 剩余次数：优先用接口给的 `remaining`，否则 `limit - used`
+    pub fn remaining(&self) -> Option<u64> {
+        self.remaining
+            .or_else(|| Some(self.limit?.saturating_sub(self.used?)))
+    }
+}
+
+/// 剩余额度的展示文本：有绝对次数时「剩余 37 次（21%）」，否则「剩余 21%」
+pub fn format_left(left_percent: f64, counts: &WindowCounts) -> String {
+    match counts.remaining() {
+        Some(n) => format!("剩余 {} 次（{:.0}%）", n, left_percent),
+        None => format!("剩余 {:.0}%", left_percent),
+    }
+}
+
+/// 不含 Codex 的套餐（usage 接口对这些 plan 不返回 rate_limit 窗口）。
+/// 新发现的档位直接往这里加。
+const PLANS_WITHOUT_CODEX: &[&str] = &["free", "guest"];
+
+/// 按 plan_type 判断套餐是否不含 Codex
+pub fn plan_lacks_codex(plan_type: &str) -> bool {
+    let plan = plan_type.trim().to_lowercase();
+    PLANS_WITHOUT_CODEX.contains(&plan.as_str())
+}
+
+const USAGE_URL: &str = "https://chatgpt.com/backend-api/wham/usage";
+
+/// 上游故障（5xx / Cloudflare HTML 错误页）的错误前缀：不是账号问题，调用方应保留旧缓存稍后重试
+pub const UPSTREAM_UNAVAILABLE: &str = "UPSTREAM_UNAVAILABLE";
+
+/// 错误信息里附带的响应体片段上限（字符）
+const BODY_SNIPPET_CHARS: usize = 120;
+
+/// 是否是上游暂时不可用（见 [`UPSTREAM_UNAVAILABLE`]）
+pub fn is_upstream_unavailable(err: &str) -> bool {
+    err.starts_with(UPSTREAM_UNAVAILABLE)
+}
+
+/// 本地已判定 access_token 过期、又不允许在本机刷新时的错误前缀：跳过了注定 401 的请求，
+/// 不代表授权失效，调用方不要据此打 TOKEN_INVALID 标记
+pub const TOKEN_EXPIRED: &str = "TOKEN_EXPIRED";
+
+/// 判断过期时预留的时钟偏差
+pub const EXPIRY_SKEW_SECS: i64 = 60;
+
+/// 调 usage 接口前对缓存 access_token 的预检结论
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenPreflight {
+    /// 没过期，或者过期时间不明（照旧先试）
+    UseAsIs,
+    /// 已过期且允许刷新：先换 token，省掉一次注定 401 的请求
+    RefreshFirst,
+    /// 已过期且不允许刷新：直接返回 [`TOKEN_EXPIRED`]
+    Fail,
+}
+
+/// `expires_at` 为 None（解析不出 / 没有）时不做判断
+pub fn token_preflight(
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    now: chrono::DateTime<chrono::Utc>,
+    allow_refresh: bool,
+) -> TokenPreflight {
+    match expires_at {
+        Some(exp) if exp <= now + chrono::Duration::seconds(EXPIRY_SKEW_SECS) => {
+            if allow_refresh {
+                TokenPreflight::RefreshFirst
+            } else {
+                TokenPreflight::Fail
+            }
+        }
+        _ => TokenPreflight::UseAsIs,
+    }
+}
+
+/// 用 [`crate::clock`] 的"现在"预检；系统时间异常期间不按过期时间下结论，照常使用
+pub fn token_preflight_now(
+    expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    allow_refresh: bool,
+) -> TokenPreflight {
+    if crate::clock::is_skewed() {
+        return TokenPreflight::UseAsIs;
+    }
+    token_preflight(expires_at, crate::clock::now(), allow_refresh)
+}
+
+pub fn token_expired_error(expires_at: chrono::DateTime<chrono::Utc>) -> String {
+    format!(
+        "{}:access_token 已于 {} 过期，且不允许在本机刷新",
+        TOKEN_EXPIRED,
+        expires_at
+            .with_timezone(&chrono::Local)
+            .format("%Y-%m-%d %H:%M")
+    )
+}
+
+/// 响应体是不是 HTML 页面；没有 Content-Type 时看正文是否以 `<` 开头
+fn looks_like_html(content_type: Option<&str>, body: &str) -> bool {
+    content_type.is_some_and(|ct| ct.to_ascii_lowercase().contains("text/html"))
+        || body.trim_start().starts_with('<')
+}
+
+/// 压成单行并截断，放进错误信息
+fn body_snippet(body: &str) -> String {
+    let flat = body.split_whitespace().collect::<Vec<_>>().join(" ");
+    if flat.chars().count() <= BODY_SNIPPET_CHARS {
+        return flat;
+    }
+    let head: String = flat.chars().take(BODY_SNIPPET_CHARS).collect();
+    format!("{}…", head)
+}
+
+/// 解析前先看状态码和内容类型：5xx / HTML 错误页归为上游故障，其它非 2xx 带状态码报错，
+/// 只有正常响应才按 JSON 解析（401/403 已在调用前处理）
+fn parse_usage_body(status: u16, content_type: Option<&str>, body: &str) -> Result<Value, String> {
+    if status >= 500 || looks_like_html(content_type, body) {
+        return Err(format!(
+            "{}:ChatGPT 服务暂时不可用 (HTTP {})，稍后自动重试: {}",
+            UPSTREAM_UNAVAILABLE,
+            status,
+            body_snippet(body)
+        ));
+    }
+    if !(200..300).contains(&status) {
+        return Err(format!(
+            "配额接口返回 HTTP {}: {}",
+            status,
+            body_snippet(body)
+        ));
+    }
+    serde_json::from_str(body).map_err(|e| format!("解析 JSON 失败: {}", e))
+}
+
+/// usage 响应里的工作区 id 和请求头里的 `ChatGPT-Account-Id` 对不上时的错误前缀：
+/// 这份额度属于别的工作区，调用方不要缓存
+pub const WORKSPACE_MISMATCH: &str = "WORKSPACE_MISMATCH";
+
+/// usage 响应里可能带工作区 id 的字段（JSON pointer），按顺序取第一个非空的
+const WORKSPACE_ID_FIELDS: &[&str] = &[
+    "/account_id",
+    "/chatgpt_account_id",
+    "/workspace_id",
+    "/account/id",
+    "/account/account_id",
+    "/workspace/id",
+];
+
+/// 响应报告的工作区和请求的不一致
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorkspaceMismatch {
+    /// 请求头里带的 account_id
+    pub sent: String,
+    /// 响应里报告的 id
+    pub reported: String,
+    /// 取自哪个字段
+    pub field: &'static str,
+}
+
+impl std::fmt::Display for WorkspaceMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:配额接口返回的是工作区 {}（{}），与请求的 {} 不一致，未缓存这份额度",
+            WORKSPACE_MISMATCH, self.reported, self.field, self.sent
+        )
+    }
+}
+
+/// 从 usage 响应里找工作区 id，返回（字段, 值）；没有任何已知字段时 None
+pub fn reported_workspace_id(json: &Value) -> Option<(&'static str, &str)> {
+    WORKSPACE_ID_FIELDS.iter().find_map(|field| {
+        json.pointer(field)
+            .and_then(Value::as_str)
+            .map(str::trim)
+            .filter(|id| !id.is_empty())
+            .map(|id| (*field, id))
+    })
+}
+
+/// 响应里的工作区 id 是否和请求的一致。请求没带 account_id、或响应里没有可比的字段时不判断
+pub fn check_workspace(sent: Option<&str>, json: &Value) -> Result<(), WorkspaceMismatch> {
+    let Some(sent) = sent.map(str::trim).filter(|id| !id.is_empty()) else {
+        return Ok(());
+    };
+    match reported_workspace_id(json) {
+        Some((field, reported)) if !reported.eq_ignore_ascii_case(sent) => Err(WorkspaceMismatch {
+            sent: sent.to_string(),
+            reported: reported.to_string(),
+            field,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// OAuth token（JWT）却没有 account_id：请求不带 `ChatGPT-Account-Id`，后端会按 token 的
+/// 默认工作区返回，多工作区账号拿到的就是别处的额度
+fn missing_account_id_warning(access_token: &str, account_id: Option<&str>) -> Option<String> {
+    if account_id.is_some_and(|id| !id.trim().is_empty()) || !access_token.starts_with("eyJ") {
+        return None;
+    }
+    let claim = crate::account::AccountStore::extract_jwt_claims_from_token(access_token)
+        .ok()
+        .and_then(|claims| crate::oauth::chatgpt_account_id_from_claims(&claims));
+    Some(match claim {
+        Some(claim) => format!(
+            "[Usage] ⚠️ OAuth 账号缺少 account_id，请求不带 ChatGPT-Account-Id，\
+             额度可能来自 token 默认工作区（token 声明的工作区 {}）",
+            claim
+        ),
+        None => "[Usage] ⚠️ OAuth 账号缺少 account_id，请求不带 ChatGPT-Account-Id，\
+                 额度可能来自 token 默认工作区"
+            .to_string(),
+    })
 }
 
 /// 用量获取器
@@ -59,36 +317,74 @@ pub struct UsageFetcher;
 
 impl UsageFetcher {
     /// 从 API 获取用量 (直接使用提供的 Token，不读取 auth.json)
+    ///
+    /// `local_refresh` 为 None 时禁用本地 refresh；Some 时遇到 401/403 用该账号的
+    /// OAuth profile 刷新一次再重试
+    ///
+    /// `overrides` 是该账号叠加在默认请求头上的部分（见 `request_headers`）
     pub async fn fetch_usage_direct(
         access_token: String,
         account_id: Option<String>,
         refresh_token: Option<String>,
-        allow_local_refresh: bool,
+        local_refresh: Option<&crate::oauth::AuthProfile>,
+        overrides: RequestOverrides,
+    ) -> Result<(UsageDisplay, Option<crate::oauth::TokenResponse>), String> {
+        Self::fetch_usage_from(
+            USAGE_URL,
+            access_token,
+            account_id,
+            refresh_token,
+            local_refresh,
+            overrides,
+        )
+        .await
+    }
+
+    /// `fetch_usage_direct` 的实现，地址可注入（测试用 mock server）
+    async fn fetch_usage_from(
+        url: &str,
+        access_token: String,
+        account_id: Option<String>,
+        refresh_token: Option<String>,
+        local_refresh: Option<&crate::oauth::AuthProfile>,
+        overrides: RequestOverrides,
     ) -> Result<(UsageDisplay, Option<crate::oauth::TokenResponse>), String> {
         let mut current_token = access_token;
         let mut new_tokens: Option<crate::oauth::TokenResponse> = None;
 
         let client = usage_client();
-        let user_agent = format!(
-            "codex_cli_rs/{} (Mac OS; x86_64) codex-cli",
-            env!("CARGO_PKG_VERSION")
-        );
+        let defaults = [
+            (
+                "User-Agent",
+                format!(
+                    "codex_cli_rs/{} (Mac OS; x86_64) codex-cli",
+                    env!("CARGO_PKG_VERSION")
+                ),
+            ),
+            ("originator", "codex_cli_rs".to_string()),
+            ("Accept", "application/json".to_string()),
+        ];
+        let headers = crate::request_headers::merge(&defaults, &overrides);
         let build_request = |at: &str, aid: &Option<String>| {
             // 12s 是经验值：正常 < 2s，5s+ 已经是慢路径，>12s 基本可以判定为节流/超时。
             // 之前 30s 让 "刷新全部" 的尾延迟被个别慢账号拖很久。
             let mut req = client
-                .get("https://chatgpt.com/backend-api/wham/usage")
+                .get(url)
                 .header("Authorization", format!("Bearer {}", at))
-                .header("User-Agent", &user_agent)
-                .header("originator", "codex_cli_rs")
-                .header("Accept", "application/json")
                 .timeout(Duration::from_secs(12));
+            for (name, value) in &headers {
+                req = req.header(name.as_str(), value.as_str());
+            }
             if let Some(id) = aid {
                 req = req.header("ChatGPT-Account-Id", id);
             }
             req
         };
 
+        if let Some(warning) = missing_account_id_warning(&current_token, account_id.as_deref()) {
+            eprintln!("{}", warning);
+        }
+
         let mut response = build_request(&current_token, &account_id)
             .send()
             .await
@@ -97,37 +393,53 @@ impl UsageFetcher {
         let mut status = response.status();
 
         // 如果允许本地刷新，且 401/403 且有 refresh_token，尝试刷新
-        if allow_local_refresh && (status == 401 || status == 403) && refresh_token.is_some() {
-            if let Some(ref rt) = refresh_token {
-                match crate::oauth::refresh_access_token(rt).await {
-                    Ok(token_res) => {
-                        current_token = token_res.access_token.clone();
-                        new_tokens = Some(token_res);
-
-                        // 重试请求
-                        response = build_request(&current_token, &account_id)
-                            .send()
-                            .await
-                            .map_err(|e| format!("刷新后重试失败: {}", e))?;
-                        status = response.status();
+        let refresh = local_refresh
+            .zip(refresh_token.as_deref())
+            .filter(|_| status == 401 || status == 403);
+        if let Some((profile, rt)) = refresh {
+            match crate::oauth::refresh_access_token(profile, rt).await {
+                Ok(token_res) => {
+                    current_token = token_res.access_token.clone();
+                    new_tokens = Some(token_res);
+
+                    // 重试请求
+                    response = build_request(&current_token, &account_id)
+                        .send()
+                        .await
+                        .map_err(|e| format!("刷新后重试失败: {}", e))?;
+                    status = response.status();
+                }
+                Err(e) => {
+                    // 重复使用一类原样上抛，调用方据此挂刷新禁令（见 `refresh_ban`）
+                    if crate::oauth::is_refresh_reused(&e) {
+                        return Err(format!("TOKEN_INVALID:{}", e));
                     }
-                    Err(e) => {
-                        let lower = e.to_lowercase();
-                        if lower.contains("logged out")
-                            || lower.contains("signed in to another account")
-                            || lower.contains("invalid_grant")
-                        {
-                            return Err("ACCOUNT_LOGGED_OUT:您已登出或登录了其他账号，请重新登录"
-                                .to_string());
-                        }
+                    let lower = e.to_lowercase();
+                    if lower.contains("logged out")
+                        || lower.contains("signed in to another account")
+                        || lower.contains("invalid_grant")
+                    {
+                        return Err(
+                            "ACCOUNT_LOGGED_OUT:您已登出或登录了其他账号，请重新登录".to_string()
+                        );
                     }
                 }
             }
         }
 
+        let capture = crate::usage_debug::global();
+
         if status == 401 || status == 403 {
             // 读取响应体以检测是否为封号
-            let body = response.text().await.unwrap_or_default().to_lowercase();
+            let raw_body = response.text().await.unwrap_or_default();
+            if capture.is_enabled() {
+                capture.record(
+                    &Self::capture_key(&current_token, account_id.as_deref()),
+                    status.as_u16(),
+                    &raw_body,
+                );
+            }
+            let body = raw_body.to_lowercase();
             let is_banned = body.contains("deactivated")
                 || body.contains("banned")
                 || body.contains("suspended")
@@ -137,7 +449,7 @@ impl UsageFetcher {
                 return Err("ACCOUNT_BANNED:该账号已被封禁".to_string());
             }
 
-            if !allow_local_refresh {
+            if local_refresh.is_none() {
                 return Err(
                     "当前激活账号访问配额接口返回 401/403；已禁用本地 refresh_token 刷新，请稍后重试或在 Codex 中触发一次请求".to_string(),
                 );
@@ -146,13 +458,25 @@ impl UsageFetcher {
             return Err("TOKEN_INVALID:授权已失效，请删除该账号后重新登录".to_string());
         }
 
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.to_string());
         let text = response
             .text()
             .await
             .map_err(|e| format!("读取响应失败: {}", e))?;
 
-        let json: Value =
-            serde_json::from_str(&text).map_err(|e| format!("解析 JSON 失败: {}", e))?;
+        if capture.is_enabled() {
+            capture.record(
+                &Self::capture_key(&current_token, account_id.as_deref()),
+                status.as_u16(),
+                &text,
+            );
+        }
+
+        let json = parse_usage_body(status.as_u16(), content_type.as_deref(), &text)?;
 
         // 检测 200 状态码下的软封号/停用响应，如 {"detail":{"code":"deactivated_workspace"}}
         if let Some(detail_code) = json
@@ -170,13 +494,29 @@ impl UsageFetcher {
             }
         }
 
+        if let Err(mismatch) = check_workspace(account_id.as_deref(), &json) {
+            eprintln!(
+                "[Usage] {}；原始响应: {}",
+                mismatch,
+                crate::redact::redact_text(&text)
+            );
+            return Err(mismatch.to_string());
+        }
+
         let display = Self::parse_usage_response(&json)?;
 
         Ok((display, new_tokens))
     }
 
+    /// 抓包分桶 key：跟 `usage_debug::identity_key` 用同一套身份（workspace id + user id）
+    fn capture_key(access_token: &str, account_id: Option<&str>) -> String {
+        let auth = serde_json::json!({ "tokens": { "access_token": access_token } });
+        let user_id = crate::account::AccountStore::extract_openai_user_id(&auth);
+        crate::usage_debug::identity_key(account_id, user_id.as_deref())
+    }
+
     /// 从 Value 解析用量数据
-    fn parse_usage_response(json: &Value) -> Result<UsageDisplay, String> {
+    pub(crate) fn parse_usage_response(json: &Value) -> Result<UsageDisplay, String> {
         let plan_type = json
             .get("plan_type")
             .and_then(|v| v.as_str())
@@ -185,12 +525,41 @@ impl UsageFetcher {
 
         let rate_limit = json.get("rate_limit");
 
+        let primary_val = rate_limit
+            .and_then(|r| r.get("primary_window"))
+            .filter(|v| !v.is_null());
+        let secondary_val = rate_limit
+            .and_then(|r| r.get("secondary_window"))
+            .filter(|v| !v.is_null());
+
+        // 两个窗口都没有 + 套餐本身不含 Codex：如实标记，不编造 0% / 未知
+        if primary_val.is_none() && secondary_val.is_none() && plan_lacks_codex(&plan_type) {
+            return Ok(UsageDisplay {
+                display_plan: crate::plan::display_plan(&plan_type),
+                plan_type,
+                five_hour_used: 0,
+                five_hour_left: 0,
+                five_hour_label: "5H 限额".to_string(),
+                five_hour_reset: "无 Codex 权限".to_string(),
+                five_hour_reset_at: None,
+                weekly_used: 0,
+                weekly_left: 0,
+                weekly_label: "周限额".to_string(),
+                weekly_reset: "无 Codex 权限".to_string(),
+                weekly_reset_at: None,
+                credits_balance: None,
+                has_credits: false,
+                is_valid_for_cli: true,
+                codex_available: false,
+                five_hour_counts: WindowCounts::default(),
+                weekly_counts: WindowCounts::default(),
+            });
+        }
+
         // 解析 5 小时窗口 (Primary)
-        let primary_val = rate_limit.and_then(|r| r.get("primary_window"));
         let (p_used, p_reset, p_label, p_reset_at) = Self::parse_window(primary_val, "5H 限额");
 
         // 解析周窗口 (Secondary)
-        let secondary_val = rate_limit.and_then(|r| r.get("secondary_window"));
         let (s_used, s_reset, s_label, s_reset_at) = Self::parse_window(secondary_val, "周限额");
 
         // 解析额度
@@ -208,6 +577,7 @@ impl UsageFetcher {
             .and_then(Self::parse_number);
 
         Ok(UsageDisplay {
+            display_plan: crate::plan::display_plan(&plan_type),
             plan_type,
             five_hour_used: p_used,
             five_hour_left: 100 - p_used,
@@ -222,6 +592,13 @@ impl UsageFetcher {
             credits_balance,
             has_credits: has_credits || unlimited,
             is_valid_for_cli: true,
+            codex_available: true,
+            five_hour_counts: primary_val
+                .map(WindowCounts::from_window)
+                .unwrap_or_default(),
+            weekly_counts: secondary_val
+                .map(WindowCounts::from_window)
+                .unwrap_or_default(),
         })
     }
 
@@ -879,6 +1256,35 @@ mod tests {
     use super::*;
     use serde_json::json;
 
+    #[test]
+    fn token_preflight_table() {
+        let now = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let secs = |s: i64| Some(now + chrono::Duration::seconds(s));
+        let cases = [
+            // (expires_at, allow_refresh, expected)
+            (None, true, TokenPreflight::UseAsIs),
+            (None, false, TokenPreflight::UseAsIs),
+            (secs(3600), true, TokenPreflight::UseAsIs),
+            (secs(3600), false, TokenPreflight::UseAsIs),
+            (secs(61), false, TokenPreflight::UseAsIs),
+            // 60s 时钟偏差内按已过期处理
+            (secs(60), true, TokenPreflight::RefreshFirst),
+            (secs(30), false, TokenPreflight::Fail),
+            (secs(-7200), true, TokenPreflight::RefreshFirst),
+            (secs(-7200), false, TokenPreflight::Fail),
+        ];
+        for (exp, allow, expected) in cases {
+            assert_eq!(
+                token_preflight(exp, now, allow),
+                expected,
+                "exp={:?} allow_refresh={}",
+                exp,
+                allow
+            );
+        }
+        assert!(token_expired_error(now).starts_with("TOKEN_EXPIRED:"));
+    }
+
     fn parse_relay_response(body: Value) -> Result<(f64, String, bool), String> {
         // 单元测试只验证字段优先级，不实际打网络
         let remaining = body
@@ -1002,6 +1408,123 @@ mod tests {
         assert!(UsageFetcher::normalize_mimo_cookie_header("Cookie: userId=123").is_none());
     }
 
+    #[test]
+    fn windowless_free_plan_is_marked_without_codex() {
+        let json = json!({ "plan_type": "free", "rate_limit": null });
+        let display = UsageFetcher::parse_usage_response(&json).unwrap();
+        assert!(!display.codex_available);
+        assert_eq!(display.five_hour_left, 0);
+        assert!(plan_lacks_codex("FREE"));
+    }
+
+    #[test]
+    fn windowless_paid_plan_keeps_codex_available() {
+        // 缺窗口但套餐本身含 Codex：不乱贴标记（可能只是接口抖动）
+        let json = json!({ "plan_type": "plus" });
+        let display = UsageFetcher::parse_usage_response(&json).unwrap();
+        assert!(display.codex_available);
+    }
+
+    #[test]
+    fn free_plan_with_windows_recovers_codex_available() {
+        let before = json!({ "plan_type": "free", "rate_limit": {} });
+        let display = UsageFetcher::parse_usage_response(&before).unwrap();
+        assert!(!display.codex_available);
+
+        let after = json!({
+            "plan_type": "free",
+            "rate_limit": { "primary_window": { "used_percent": 25 } }
+        });
+        let display = UsageFetcher::parse_usage_response(&after).unwrap();
+        assert!(display.codex_available);
+        assert_eq!(display.five_hour_left, 75);
+    }
+
+    #[test]
+    fn window_counts_parse_full_absolutes() {
+        let json = json!({
+            "plan_type": "plus",
+            "rate_limit": {
+                "primary_window": { "used_percent": 79, "used": 140, "limit": 177, "remaining": 37 },
+                "secondary_window": { "used_percent": 40, "used": "600", "limit": "1500" }
+            }
+        });
+        let display = UsageFetcher::parse_usage_response(&json).unwrap();
+        assert_eq!(
+            display.five_hour_counts,
+            WindowCounts {
+                used: Some(140),
+                limit: Some(177),
+                remaining: Some(37),
+            }
+        );
+        assert_eq!(
+            format_left(display.five_hour_left as f64, &display.five_hour_counts),
+            "剩余 37 次（21%）"
+        );
+        // 没给 remaining 时用 limit - used 推出来
+        assert_eq!(display.weekly_counts.remaining, None);
+        assert_eq!(display.weekly_counts.remaining(), Some(900));
+        assert_eq!(
+            format_left(display.weekly_left as f64, &display.weekly_counts),
+            "剩余 900 次（60%）"
+        );
+    }
+
+    #[test]
+    fn window_counts_parse_partial_absolutes() {
+        let json = json!({
+            "plan_type": "plus",
+            "rate_limit": {
+                "primary_window": { "used_percent": 50, "remaining": "12" },
+                "secondary_window": { "used_percent": 10, "limit": 300, "used": null }
+            }
+        });
+        let display = UsageFetcher::parse_usage_response(&json).unwrap();
+        assert_eq!(display.five_hour_counts.remaining(), Some(12));
+        assert_eq!(
+            format_left(display.five_hour_left as f64, &display.five_hour_counts),
+            "剩余 12 次（50%）"
+        );
+        // 只有 limit 算不出剩余，退回百分比
+        assert_eq!(display.weekly_counts.limit, Some(300));
+        assert_eq!(display.weekly_counts.remaining(), None);
+        assert_eq!(
+            format_left(display.weekly_left as f64, &display.weekly_counts),
+            "剩余 90%"
+        );
+    }
+
+    #[test]
+    fn window_counts_absent_for_percent_only_response() {
+        let json = json!({
+            "plan_type": "pro",
+            "rate_limit": {
+                "primary_window": { "used_percent": 21.4 },
+                "secondary_window": { "used_percent": 3 }
+            }
+        });
+        let display = UsageFetcher::parse_usage_response(&json).unwrap();
+        assert_eq!(display.five_hour_counts, WindowCounts::default());
+        assert_eq!(display.weekly_counts, WindowCounts::default());
+        assert_eq!(
+            format_left(display.five_hour_left as f64, &display.five_hour_counts),
+            "剩余 79%"
+        );
+
+        // 旧缓存 / 旧 Server 下发的 UsageDisplay 没有这两个字段
+        let legacy: UsageDisplay = serde_json::from_value(json!({
+            "plan_type": "pro",
+            "five_hour_used": 10, "five_hour_left": 90, "five_hour_label": "5H 限额",
+            "five_hour_reset": "", "five_hour_reset_at": null,
+            "weekly_used": 0, "weekly_left": 100, "weekly_label": "周限额",
+            "weekly_reset": "", "weekly_reset_at": null,
+            "credits_balance": null, "has_credits": false, "is_valid_for_cli": true
+        }))
+        .unwrap();
+        assert_eq!(legacy.five_hour_counts, WindowCounts::default());
+    }
+
     #[test]
     fn mimo_period_end_parser_reads_console_timestamp() {
         assert_eq!(
@@ -1009,4 +1532,306 @@ mod tests {
             Some(1_778_025_599)
         );
     }
+
+    /// 起一个只应答一次的 HTTP server，返回 usage 地址
+    async fn serve_once(status: &str, content_type: Option<&str>, body: &str) -> String {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let content_type = content_type
+            .map(|ct| format!("Content-Type: {}\r\n", ct))
+            .unwrap_or_default();
+        let response = format!(
+            "HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status,
+            content_type,
+            body.len(),
+            body
+        );
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 4096];
+            let _ = socket.read(&mut buf).await;
+            let _ = socket.write_all(response.as_bytes()).await;
+            let _ = socket.shutdown().await;
+        });
+        format!("http://{}/backend-api/wham/usage", addr)
+    }
+
+    async fn fetch_from(url: &str) -> Result<UsageDisplay, String> {
+        UsageFetcher::fetch_usage_from(
+            url,
+            "at".to_string(),
+            None,
+            None,
+            None,
+            RequestOverrides::default(),
+        )
+        .await
+        .map(|(usage, _)| usage)
+    }
+
+    #[tokio::test]
+    async fn html_502_is_upstream_unavailable() {
+        let html = "<!DOCTYPE html>\n<html><head><title>502 Bad Gateway</title></head>\n<body>cloudflare</body></html>";
+        let url = serve_once("502 Bad Gateway", Some("text/html; charset=UTF-8"), html).await;
+        let err = fetch_from(&url).await.unwrap_err();
+        assert!(is_upstream_unavailable(&err), "{}", err);
+        assert!(err.contains("HTTP 502"), "{}", err);
+        assert!(err.contains("502 Bad Gateway"), "{}", err);
+        assert!(!err.contains("解析 JSON 失败"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn json_503_is_upstream_unavailable() {
+        let body = r#"{"detail":"Service temporarily unavailable"}"#;
+        let url = serve_once("503 Service Unavailable", Some("application/json"), body).await;
+        let err = fetch_from(&url).await.unwrap_err();
+        assert!(is_upstream_unavailable(&err), "{}", err);
+        assert!(err.contains("HTTP 503"), "{}", err);
+        assert!(err.contains("temporarily unavailable"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn truncated_json_200_is_a_parse_error() {
+        let url = serve_once(
+            "200 OK",
+            Some("application/json"),
+            r#"{"plan_type":"plus","rate_"#,
+        )
+        .await;
+        let err = fetch_from(&url).await.unwrap_err();
+        assert!(!is_upstream_unavailable(&err), "{}", err);
+        assert!(err.starts_with("解析 JSON 失败"), "{}", err);
+    }
+
+    #[test]
+    fn html_is_sniffed_without_content_type() {
+        assert!(looks_like_html(None, "  <html>oops</html>"));
+        assert!(!looks_like_html(None, r#"{"ok":true}"#));
+        let err = parse_usage_body(200, None, "<html>maintenance</html>").unwrap_err();
+        assert!(is_upstream_unavailable(&err), "{}", err);
+        let err = parse_usage_body(429, Some("application/json"), "{}").unwrap_err();
+        assert!(
+            err.contains("HTTP 429") && !is_upstream_unavailable(&err),
+            "{}",
+            err
+        );
+    }
+
+    const USAGE_FIXTURE: &str = r#"{
+        "plan_type": "team",
+        "rate_limit": {
+            "primary_window": {"used_percent": 20, "limit_window_seconds": 18000, "reset_after_seconds": 600},
+            "secondary_window": {"used_percent": 40, "limit_window_seconds": 604800, "reset_after_seconds": 6000}
+        }
+    }"#;
+
+    fn usage_fixture(extra: Value) -> Value {
+        let mut json: Value = serde_json::from_str(USAGE_FIXTURE).unwrap();
+        json.as_object_mut()
+            .unwrap()
+            .extend(extra.as_object().unwrap().clone());
+        json
+    }
+
+    #[test]
+    fn workspace_id_is_probed_from_known_fields() {
+        let top = usage_fixture(json!({ "account_id": "ws-a" }));
+        assert_eq!(reported_workspace_id(&top), Some(("/account_id", "ws-a")));
+        let nested = usage_fixture(json!({ "account": { "id": " ws-b " } }));
+        assert_eq!(
+            reported_workspace_id(&nested),
+            Some(("/account/id", "ws-b"))
+        );
+        let blank = usage_fixture(json!({ "workspace_id": "", "workspace": { "id": "ws-c" } }));
+        assert_eq!(
+            reported_workspace_id(&blank),
+            Some(("/workspace/id", "ws-c"))
+        );
+        assert_eq!(reported_workspace_id(&usage_fixture(json!({}))), None);
+    }
+
+    #[test]
+    fn workspace_check_matches_mismatches_and_skips() {
+        let matching = usage_fixture(json!({ "chatgpt_account_id": "WS-A" }));
+        assert_eq!(check_workspace(Some("ws-a"), &matching), Ok(()));
+
+        let other = usage_fixture(json!({ "account_id": "ws-default" }));
+        let err = check_workspace(Some("ws-a"), &other).unwrap_err();
+        assert_eq!(
+            err,
+            WorkspaceMismatch {
+                sent: "ws-a".into(),
+                reported: "ws-default".into(),
+                field: "/account_id",
+            }
+        );
+        let msg = err.to_string();
+        assert!(msg.starts_with(WORKSPACE_MISMATCH), "{}", msg);
+        assert!(
+            msg.contains("ws-a") && msg.contains("ws-default"),
+            "{}",
+            msg
+        );
+
+        // 响应里没有可比的字段 / 请求本来就没带 id：不判断
+        assert_eq!(
+            check_workspace(Some("ws-a"), &usage_fixture(json!({}))),
+            Ok(())
+        );
+        assert_eq!(check_workspace(None, &other), Ok(()));
+        assert_eq!(check_workspace(Some("  "), &other), Ok(()));
+    }
+
+    #[test]
+    fn oauth_token_without_account_id_warns() {
+        let engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+        let payload = json!({ "https://api.openai.com/auth": { "chatgpt_account_id": "ws-a" } });
+        let jwt = format!(
+            "eyJhbGciOiJub25lIn0.{}.sig",
+            base64::Engine::encode(&engine, payload.to_string())
+        );
+        let warning = missing_account_id_warning(&jwt, None).unwrap();
+        assert!(warning.contains("ws-a"), "{}", warning);
+        assert!(missing_account_id_warning(&jwt, Some("")).is_some());
+        assert_eq!(missing_account_id_warning(&jwt, Some("ws-a")), None);
+        assert_eq!(missing_account_id_warning("sk-relay", None), None);
+    }
+
+    #[tokio::test]
+    async fn mismatched_workspace_is_not_returned_as_usage() {
+        let body = usage_fixture(json!({ "account_id": "ws-default" })).to_string();
+        let url = serve_once("200 OK", Some("application/json"), &body).await;
+        let err = UsageFetcher::fetch_usage_from(
+            &url,
+            "at".into(),
+            Some("ws-a".into()),
+            None,
+            None,
+            RequestOverrides::default(),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.starts_with(WORKSPACE_MISMATCH), "{}", err);
+        assert!(!is_upstream_unavailable(&err));
+
+        let url = serve_once("200 OK", Some("application/json"), &body).await;
+        let (usage, _) = UsageFetcher::fetch_usage_from(
+            &url,
+            "at".into(),
+            Some("ws-default".into()),
+            None,
+            None,
+            RequestOverrides::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(usage.plan_type, "team");
+    }
+
+    /// 应答一次并把收到的请求头（名称小写）交回来
+    async fn serve_once_capturing(
+        body: &str,
+    ) -> (
+        String,
+        tokio::sync::oneshot::Receiver<Vec<(String, String)>>,
+    ) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = vec![0u8; 8192];
+            let n = socket.read(&mut buf).await.unwrap();
+            let request = String::from_utf8_lossy(&buf[..n]).to_string();
+            let headers = request
+                .lines()
+                .skip(1)
+                .take_while(|l| !l.is_empty())
+                .filter_map(|l| l.split_once(':'))
+                .map(|(k, v)| (k.trim().to_ascii_lowercase(), v.trim().to_string()))
+                .collect();
+            let _ = tx.send(headers);
+            let _ = socket.write_all(response.as_bytes()).await;
+            let _ = socket.shutdown().await;
+        });
+        (format!("http://{}/backend-api/wham/usage", addr), rx)
+    }
+
+    #[tokio::test]
+    async fn account_header_overrides_reach_the_wire() {
+        let body = usage_fixture(json!({})).to_string();
+        let header = |headers: &[(String, String)], name: &str| {
+            headers
+                .iter()
+                .find(|(k, _)| k == name)
+                .map(|(_, v)| v.clone())
+        };
+
+        // 没有覆盖：默认 UA
+        let (url, rx) = serve_once_capturing(&body).await;
+        UsageFetcher::fetch_usage_from(
+            &url,
+            "at".into(),
+            Some("ws-a".into()),
+            None,
+            None,
+            RequestOverrides::default(),
+        )
+        .await
+        .unwrap();
+        let seen = rx.await.unwrap();
+        assert!(header(&seen, "user-agent")
+            .unwrap()
+            .starts_with("codex_cli_rs/"));
+        assert_eq!(header(&seen, "x-team"), None);
+
+        // 全局 UA 被账号自己的 UA 覆盖；鉴权头不受账号配置影响
+        let (url, rx) = serve_once_capturing(&body).await;
+        let overrides = RequestOverrides {
+            user_agent: Some("global-ua".into()),
+            headers: [
+                ("User-Agent", "team-allowed/1.0"),
+                ("X-Team", "blue"),
+                ("Authorization", "Bearer evil"),
+            ]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect(),
+        };
+        UsageFetcher::fetch_usage_from(
+            &url,
+            "at".into(),
+            Some("ws-a".into()),
+            None,
+            None,
+            overrides,
+        )
+        .await
+        .unwrap();
+        let seen = rx.await.unwrap();
+        assert_eq!(
+            header(&seen, "user-agent").as_deref(),
+            Some("team-allowed/1.0")
+        );
+        assert_eq!(header(&seen, "x-team").as_deref(), Some("blue"));
+        assert_eq!(header(&seen, "authorization").as_deref(), Some("Bearer at"));
+        assert_eq!(header(&seen, "chatgpt-account-id").as_deref(), Some("ws-a"));
+        assert_eq!(header(&seen, "originator").as_deref(), Some("codex_cli_rs"));
+    }
+
+    #[test]
+    fn body_snippet_is_single_line_and_truncated() {
+        let long = format!("<html>\n{}</html>", "x".repeat(500));
+        let snippet = body_snippet(&long);
+        assert!(!snippet.contains('\n'));
+        assert_eq!(snippet.chars().count(), BODY_SNIPPET_CHARS + 1);
+    }
 }
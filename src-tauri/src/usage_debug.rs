@@ -0,0 +1,248 @@
+//! usage 接口原始响应抓取（开发者选项 `debug_capture_responses`）
+//!
+//! `parse_usage_response` 读错新字段时，用户没法看到服务端到底回了什么。
+//! 开关打开后，`fetch_usage_direct` 每次读到 body 都在这里留一份：
+//! - 先过 `redact::redact_jwt_like` 脱敏，再截断到 `MAX_BODY_BYTES`
+//! - 每个账号身份只保留最近 `MAX_PER_ACCOUNT` 条
+//! - 可选落盘到 `~/.codex-switcher/debug/usage-<key>.json`（同样受上面两个上限约束）
+//!
+//! 开关关闭时热路径上只有一次 `AtomicBool` 读 + 分支。
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+
+use crate::usage::UsageDisplay;
+
+/// 单条 body 最多保留的字节数（超出截断）
+pub const MAX_BODY_BYTES: usize = 64 * 1024;
+/// 每个账号身份最多保留的条数
+pub const MAX_PER_ACCOUNT: usize = 5;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CapturedUsageResponse {
+    pub captured_at: DateTime<Utc>,
+    pub status: u16,
+    /// 脱敏 + 截断后的原始 body
+    pub body: String,
+    /// body 是否被截断
+    pub truncated: bool,
+    /// 按当前解析逻辑得到的结果（body 不是合法 JSON 或解析失败时为 None）
+    pub parsed: Option<UsageDisplay>,
+    pub parse_error: Option<String>,
+}
+
+/// `get_last_usage_response` 的返回值
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageDebugReport {
+    pub account_id: String,
+    pub capture_enabled: bool,
+    /// 从旧到新
+    pub captures: Vec<CapturedUsageResponse>,
+}
+
+pub struct UsageCaptureStore {
+    enabled: AtomicBool,
+    dump_to_disk: AtomicBool,
+    entries: Mutex<HashMap<String, VecDeque<CapturedUsageResponse>>>,
+}
+
+impl UsageCaptureStore {
+    pub fn new() -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            dump_to_disk: AtomicBool::new(false),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 按设置开关抓取；关闭时顺手清空内存里的历史，避免残留 body。
+    pub fn configure(&self, enabled: bool, dump_to_disk: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+        self.dump_to_disk.store(dump_to_disk, Ordering::Relaxed);
+        if !enabled {
+            if let Ok(mut entries) = self.entries.lock() {
+                entries.clear();
+            }
+        }
+    }
+
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// 记录一次响应。`key` 由 `identity_key` 生成。
+    pub fn record(&self, key: &str, status: u16, raw_body: &str) {
+        if !self.is_enabled() {
+            return;
+        }
+        let (parsed, parse_error) = match serde_json::from_str::<serde_json::Value>(raw_body) {
+            Ok(json) => match crate::usage::UsageFetcher::parse_usage_response(&json) {
+                Ok(d) => (Some(d), None),
+                Err(e) => (None, Some(e)),
+            },
+            Err(e) => (None, Some(format!("解析 JSON 失败: {}", e))),
+        };
+        let (body, truncated) = truncate_utf8(&crate::redact::redact_jwt_like(raw_body));
+        let entry = CapturedUsageResponse {
+            captured_at: Utc::now(),
+            status,
+            body,
+            truncated,
+            parsed,
+            parse_error,
+        };
+
+        let snapshot = {
+            let Ok(mut entries) = self.entries.lock() else {
+                return;
+            };
+            let ring = entries.entry(key.to_string()).or_default();
+            ring.push_back(entry);
+            while ring.len() > MAX_PER_ACCOUNT {
+                ring.pop_front();
+            }
+            if self.dump_to_disk.load(Ordering::Relaxed) {
+                Some(ring.iter().cloned().collect::<Vec<_>>())
+            } else {
+                None
+            }
+        };
+        if let Some(captures) = snapshot {
+            dump(key, &captures);
+        }
+    }
+
+    /// 取某身份最近的抓包（从旧到新）
+    pub fn recent(&self, key: &str) -> Vec<CapturedUsageResponse> {
+        self.entries
+            .lock()
+            .ok()
+            .and_then(|entries| entries.get(key).map(|r| r.iter().cloned().collect()))
+            .unwrap_or_default()
+    }
+}
+
+impl Default for UsageCaptureStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 进程级抓包缓冲（fetch_usage_direct 拿不到 Tauri State，只能走全局）
+pub fn global() -> &'static UsageCaptureStore {
+    static STORE: OnceLock<UsageCaptureStore> = OnceLock::new();
+    STORE.get_or_init(UsageCaptureStore::new)
+}
+
+/// 抓包按"账号身份"分桶：ChatGPT workspace id + OpenAI user id。
+/// fetch 侧只有 token，store 侧只有 auth_json，两边都能算出同一个 key。
+pub fn identity_key(account_id: Option<&str>, user_id: Option<&str>) -> String {
+    format!("{}|{}", account_id.unwrap_or("-"), user_id.unwrap_or("-"))
+}
+
+fn truncate_utf8(s: &str) -> (String, bool) {
+    if s.len() <= MAX_BODY_BYTES {
+        return (s.to_string(), false);
+    }
+    let mut end = MAX_BODY_BYTES;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+    (s[..end].to_string(), true)
+}
+
+fn dump(key: &str, captures: &[CapturedUsageResponse]) {
+    let dir = crate::paths::data_dir().join("debug");
+    if std::fs::create_dir_all(&dir).is_err() {
+        return;
+    }
+    let safe_key: String = key
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if let Ok(content) = serde_json::to_string_pretty(captures) {
+        let _ = std::fs::write(dir.join(format!("usage-{}.json", safe_key)), content);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USAGE_BODY: &str =
+        r#"{"plan_type":"plus","rate_limit":{"primary_window":{"used_percent":40}}}"#;
+
+    #[test]
+    fn nothing_is_captured_when_disabled() {
+        let store = UsageCaptureStore::new();
+        store.record("k", 200, USAGE_BODY);
+        assert!(store.recent("k").is_empty());
+    }
+
+    #[test]
+    fn captures_are_redacted_and_parsed() {
+        let store = UsageCaptureStore::new();
+        store.configure(true, false);
+        store.record(
+            "k",
+            200,
+            r#"{"plan_type":"pro","echo":"eyJhbGc.eyJ4Ijox.c2ln"}"#,
+        );
+        let got = store.recent("k");
+        assert_eq!(got.len(), 1);
+        assert!(!got[0].body.contains("eyJhbGc"));
+        assert_eq!(got[0].parsed.as_ref().unwrap().plan_type, "pro");
+
+        store.record("k", 502, "<html>bad gateway</html>");
+        let got = store.recent("k");
+        assert!(got[1].parsed.is_none());
+        assert!(got[1].parse_error.is_some());
+    }
+
+    #[test]
+    fn count_cap_keeps_only_latest_per_account() {
+        let store = UsageCaptureStore::new();
+        store.configure(true, false);
+        for i in 0..(MAX_PER_ACCOUNT + 3) {
+            store.record("a", 200 + i as u16, USAGE_BODY);
+        }
+        store.record("b", 200, USAGE_BODY);
+        let got = store.recent("a");
+        assert_eq!(got.len(), MAX_PER_ACCOUNT);
+        assert_eq!(
+            got.last().unwrap().status,
+            200 + (MAX_PER_ACCOUNT + 2) as u16
+        );
+        assert_eq!(store.recent("b").len(), 1);
+    }
+
+    #[test]
+    fn size_cap_truncates_on_char_boundary() {
+        let store = UsageCaptureStore::new();
+        store.configure(true, false);
+        let huge = "额".repeat(MAX_BODY_BYTES);
+        store.record("k", 200, &huge);
+        let got = store.recent("k");
+        assert!(got[0].truncated);
+        assert!(got[0].body.len() <= MAX_BODY_BYTES);
+    }
+
+    #[test]
+    fn disabling_clears_history() {
+        let store = UsageCaptureStore::new();
+        store.configure(true, false);
+        store.record("k", 200, USAGE_BODY);
+        store.configure(false, false);
+        assert!(store.recent("k").is_empty());
+    }
+}
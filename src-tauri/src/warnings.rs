@@ -0,0 +1,175 @@
+//! 结构化警告
+//!
+//! 切号、回流同步这些流程里有不少"没做成但不算失败"的判断（预检配额失败、身份不匹配拒绝同步……），
+//! 原先只打在 stdout 上，界面用户看不到某件事为什么悄悄没发生。
+//! 现在流程沿途把这些判断攒成 [`Warning`]（`code` + `params`），随结果一起返回：
+//! - 命令结果用 [`Warned`] 信封（`{ value, warnings }`）；老命令签名不变，另加 `_v2` 命令返回信封
+//! - 后台流程把警告挂在它发出的事件上（切号进度 `done`、`sync-warning`）
+//!
+//! 日志照打，文案见 [`Warning`] 的 `Display`；前端按 `code` 自己出文案，`params` 只放填空用的值。
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use schemars::JsonSchema;
+use serde::Serialize;
+
+/// 警告种类（序列化为 snake_case，前端按它出文案）
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum WarningCode {
+    /// 回流同步被拒：auth.json 的 account_id / uid 和本地账号对不上（`account`）
+    SyncIdentityMismatch,
+    /// 回流同步被拒：账号名是邮箱，但和 token 里的邮箱不一致（`account`、`token_email`）
+    SyncEmailMismatch,
+    /// 切号前刷新目标 token 失败，沿用旧 token（`account`、`error`）
+    TokenRefreshFailed,
+    /// 切号预检配额失败，不阻断切号（`account`、`error`）
+    PrecheckFailed,
+    /// access_token 已过期且没刷新成功，跳过预检（`account`）
+    PrecheckSkipped,
+    /// 手机锚生效，切到非锚账号时没写 auth.json（`account`、`anchor`）
+    AuthWriteSkipped,
+    /// 智能切号跳过的候选（`account`，额度查询失败时带 `error`）
+    CandidateSkipped,
+    /// 切号已生效，但推给 Server 失败（`error`）
+    SoloPushFailed,
+}
+
+/// 一条警告
+#[derive(Serialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct Warning {
+    pub code: WarningCode,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub params: BTreeMap<String, String>,
+}
+
+impl Warning {
+    pub fn new(code: WarningCode) -> Self {
+        Self {
+            code,
+            params: BTreeMap::new(),
+        }
+    }
+
+    pub fn with(mut self, key: &str, value: impl ToString) -> Self {
+        self.params.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    fn param(&self, key: &str) -> &str {
+        self.params.get(key).map_or("?", String::as_str)
+    }
+
+    /// 演示模式下遮掉账号名和邮箱
+    pub fn masked(mut self) -> Self {
+        for (key, value) in self.params.iter_mut() {
+            match key.as_str() {
+                "account" | "anchor" => *value = crate::presentation::mask_name(value),
+                "token_email" => *value = crate::presentation::mask_email(value),
+                _ => {}
+            }
+        }
+        self
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let account = self.param("account");
+        match self.code {
+            WarningCode::SyncIdentityMismatch => {
+                write!(f, "拒绝同步 {}：auth.json 身份不匹配", account)
+            }
+            WarningCode::SyncEmailMismatch => write!(
+                f,
+                "拒绝同步 {}：账号名与 token 邮箱 {} 不一致",
+                account,
+                self.param("token_email")
+            ),
+            WarningCode::TokenRefreshFailed => write!(
+                f,
+                "{} 切号前刷新 Token 失败: {}",
+                account,
+                self.param("error")
+            ),
+            WarningCode::PrecheckFailed => {
+                write!(f, "{} 预检配额失败: {}", account, self.param("error"))
+            }
+            WarningCode::PrecheckSkipped => {
+                write!(
+                    f,
+                    "{} 的 access_token 已过期且未能刷新，跳过配额预检",
+                    account
+                )
+            }
+            WarningCode::AuthWriteSkipped => write!(
+                f,
+                "手机锚 {} 生效，切到 {} 未写 auth.json",
+                self.param("anchor"),
+                account
+            ),
+            WarningCode::CandidateSkipped => match self.params.get("error") {
+                Some(error) => write!(f, "跳过候选 {}：额度查询失败: {}", account, error),
+                None => write!(f, "跳过候选 {}：额度已耗尽", account),
+            },
+            WarningCode::SoloPushFailed => {
+                write!(f, "切号未同步到 Server: {}", self.param("error"))
+            }
+        }
+    }
+}
+
+/// 命令结果信封：值 + 沿途攒下的警告
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Warned<T> {
+    pub value: T,
+    pub warnings: Vec<Warning>,
+}
+
+impl<T> Warned<T> {
+    pub fn new(value: T, warnings: Vec<Warning>) -> Self {
+        Self { value, warnings }
+    }
+
+    /// 演示模式时遮掉所有警告里的账号名 / 邮箱
+    pub fn masked_if(mut self, presentation: bool) -> Self {
+        if presentation {
+            self.warnings = self.warnings.into_iter().map(Warning::masked).collect();
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn serializes_code_and_params_and_masks_identities() {
+        let warning = Warning::new(WarningCode::SyncEmailMismatch)
+            .with("account", "work@example.com")
+            .with("token_email", "other@example.com");
+        assert_eq!(
+            serde_json::to_value(&warning).unwrap(),
+            serde_json::json!({
+                "code": "sync_email_mismatch",
+                "params": {"account": "work@example.com", "token_email": "other@example.com"},
+            })
+        );
+        assert_eq!(
+            serde_json::to_value(Warning::new(WarningCode::PrecheckSkipped)).unwrap(),
+            serde_json::json!({"code": "precheck_skipped"})
+        );
+
+        let masked = Warned::new((), vec![warning.clone()]).masked_if(true);
+        let text = masked.warnings[0].to_string();
+        assert!(!text.contains("work@example.com") && !text.contains("other@example.com"));
+        assert_eq!(
+            Warned::new((), vec![warning.clone()])
+                .masked_if(false)
+                .warnings,
+            vec![warning]
+        );
+    }
+}
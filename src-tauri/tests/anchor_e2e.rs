@@ -11,6 +11,7 @@
 
 use base64::Engine;
 use codex_switcher_lib::account::AccountStore;
+use codex_switcher_lib::auth_journal::AuthWriteOp;
 use serde_json::{json, Value};
 use std::fs;
 use std::path::PathBuf;
@@ -25,6 +26,8 @@ impl HomeGuard {
     fn redirect_to(tmp: PathBuf) -> Self {
         let original = std::env::var("HOME").ok();
         std::env::set_var("HOME", &tmp);
+        // 外面设了 CODEX_HOME 时 auth.json 会写到真实目录去
+        std::env::remove_var("CODEX_HOME");
         Self {
             original,
             _tmp: tmp,
@@ -146,6 +149,8 @@ fn anchor_e2e_disk_routing() {
             .get(&pro_id)
             .unwrap()
             .to_codex_auth_value(),
+        AuthWriteOp::Anchor,
+        Some(&pro_id),
     )
     .expect("seed disk with anchor");
     let disk_before = read_disk_auth(&tmp).expect("disk has anchor seed");
@@ -163,6 +168,11 @@ fn anchor_e2e_disk_routing() {
         Some(other_id.as_str()),
         "store.current 仍要切到 other"
     );
+    // 不写盘的切换也照样计数
+    let other = &store.accounts[&other_id];
+    assert_eq!(other.switch_count, 2);
+    assert_eq!(other.switch_history.len(), 2);
+    assert_eq!(other.switch_history.last(), other.last_used.as_ref());
     assert!(
         !store.should_write_disk_for(&other_id),
         "non-anchor target 不允许写盘"
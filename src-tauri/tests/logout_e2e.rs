@@ -0,0 +1,140 @@
+//! `logout_codex` 端到端测试
+//!
+//! 临时改 HOME 把 `~/.codex/auth.json` 与 `~/.codex-switcher/accounts.json`
+//! 重定向到 tempdir。setenv 不是线程安全的，所以所有场景放在同一个 #[test] 里串行跑。
+//!
+//! 跑法：`cargo test --test logout_e2e -- --nocapture`
+
+use base64::Engine;
+use codex_switcher_lib::account::AccountStore;
+use codex_switcher_lib::auth_journal::AuthWriteOp;
+use serde_json::{json, Value};
+use std::fs;
+use std::path::PathBuf;
+
+/// HOME 重定向 RAII 守卫：drop 时还原原值。
+struct HomeGuard {
+    original: Option<String>,
+}
+
+impl HomeGuard {
+    fn redirect_to(tmp: &PathBuf) -> Self {
+        let original = std::env::var("HOME").ok();
+        std::env::set_var("HOME", tmp);
+        // 外面设了 CODEX_HOME 时 auth.json 会写到真实目录去
+        std::env::remove_var("CODEX_HOME");
+        Self { original }
+    }
+}
+
+impl Drop for HomeGuard {
+    fn drop(&mut self) {
+        match self.original.take() {
+            Some(v) => std::env::set_var("HOME", v),
+            None => std::env::remove_var("HOME"),
+        }
+    }
+}
+
+fn make_tmpdir() -> PathBuf {
+    let stamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let dir = std::env::temp_dir().join(format!("codex-switcher-logout-e2e-{}", stamp));
+    fs::create_dir_all(dir.join(".codex")).unwrap();
+    dir
+}
+
+fn jwt(payload: Value) -> String {
+    let engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    format!(
+        "{}.{}.sig",
+        engine.encode(r#"{"alg":"none"}"#),
+        engine.encode(serde_json::to_vec(&payload).unwrap())
+    )
+}
+
+fn make_oauth_auth(email: &str, account_id: &str, refresh_token: &str) -> Value {
+    let auth_claim = json!({ "chatgpt_account_id": account_id });
+    json!({
+        "tokens": {
+            "account_id": account_id,
+            "refresh_token": refresh_token,
+            "id_token": jwt(json!({ "email": email, "https://api.openai.com/auth": auth_claim })),
+            "access_token": jwt(json!({ "https://api.openai.com/auth": auth_claim })),
+        },
+        "last_refresh": chrono::Utc::now().to_rfc3339(),
+    })
+}
+
+fn saved_current() -> Option<String> {
+    let content = fs::read_to_string(AccountStore::config_path()).unwrap();
+    let saved: Value = serde_json::from_str(&content).unwrap();
+    saved["current"].as_str().map(str::to_string)
+}
+
+/// 串行跑完 3 个子场景：
+///   1. 磁盘上有轮换过的 token → 先回流再删文件、清 current
+///   2. 文件已不存在 → 照常成功
+///   3. 文件损坏 → 默认报错且什么都不动；skip_final_sync 后正常退出
+#[test]
+fn logout_codex_clears_disk_and_pointer() {
+    let tmp = make_tmpdir();
+    let _guard = HomeGuard::redirect_to(&tmp);
+    let auth_path = AccountStore::codex_auth_path();
+    assert!(auth_path.starts_with(&tmp));
+
+    let mut store = AccountStore::default();
+    let id = store
+        .add_account(
+            "work@example.com".to_string(),
+            make_oauth_auth("work@example.com", "acct-work", "rt-old"),
+            None,
+        )
+        .id;
+    assert_eq!(store.current.as_deref(), Some(id.as_str()));
+
+    // 1. Codex 刚轮换过 refresh_token
+    AccountStore::write_codex_auth(
+        &make_oauth_auth("work@example.com", "acct-work", "rt-rotated"),
+        AuthWriteOp::Other,
+        None,
+    )
+    .unwrap();
+    let outcome = store.logout_codex(false).unwrap();
+    assert!(outcome.synced);
+    assert!(outcome.removed_auth_file);
+    assert_eq!(outcome.previous_account_id.as_deref(), Some(id.as_str()));
+    assert_eq!(
+        store.accounts[&id].refresh_token.as_deref(),
+        Some("rt-rotated")
+    );
+    assert!(!auth_path.exists());
+    assert_eq!(store.current, None);
+    assert_eq!(saved_current(), None);
+    assert_eq!(store.accounts.len(), 1, "退出登录不删账号");
+
+    // 2. 已经是登出状态
+    let outcome = store.logout_codex(false).unwrap();
+    assert!(!outcome.synced);
+    assert!(!outcome.removed_auth_file);
+    assert_eq!(outcome.previous_account_id, None);
+
+    // 3. 磁盘文件损坏
+    store.current = Some(id.clone());
+    fs::write(&auth_path, "{ not json").unwrap();
+    let err = store.logout_codex(false).unwrap_err();
+    assert!(err.contains("解析 auth.json 失败"), "{}", err);
+    assert!(auth_path.exists());
+    assert_eq!(store.current.as_deref(), Some(id.as_str()));
+
+    let outcome = store.logout_codex(true).unwrap();
+    assert!(!outcome.synced);
+    assert!(outcome.removed_auth_file);
+    assert!(!auth_path.exists());
+    assert_eq!(store.current, None);
+    assert_eq!(saved_current(), None);
+
+    let _ = fs::remove_dir_all(&tmp);
+}
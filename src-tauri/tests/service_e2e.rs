@@ -0,0 +1,1058 @@
+//! `AccountService` 特征测试：切号（含写 config.toml 的 profile）、单账号刷新额度、冲突检测 / 解决、导入当前账号
+//!
+//! usage / OAuth 接口换成脚本化的假 `UsageClient`，事件换成记录用的 `EventSink`；
+//! 临时改 HOME 把 `~/.codex/auth.json` 与 `~/.codex-switcher/accounts.json` 重定向到 tempdir。
+//! setenv 不是线程安全的，所以所有场景放在同一个 #[test] 里串行跑。
+//!
+//! 跑法：`cargo test --test service_e2e -- --nocapture`
+
+use base64::Engine;
+use codex_switcher_lib::account::{AccountStore, QuotaSource};
+use codex_switcher_lib::auth_journal::{AuthJournal, AuthWriteOp, Fingerprint};
+use codex_switcher_lib::events::AppEvent;
+use codex_switcher_lib::oauth::{AuthProfile, TokenResponse};
+use codex_switcher_lib::refresh_lock::RefreshLockManager;
+use codex_switcher_lib::request_headers::RequestOverrides;
+use codex_switcher_lib::service::{AccountService, EventSink, UsageClient};
+use codex_switcher_lib::usage::UsageDisplay;
+use codex_switcher_lib::warnings::{Warning, WarningCode};
+use futures_util::future::BoxFuture;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// HOME 重定向 RAII 守卫：drop 时还原原值。
+struct HomeGuard {
+    original: Option<String>,
+}
+
+impl HomeGuard {
+    fn redirect_to(tmp: &PathBuf) -> Self {
+        let original = std::env::var("HOME").ok();
+        std::env::set_var("HOME", tmp);
+        // 外面设了 CODEX_HOME 时 auth.json 会写到真实目录去
+        std::env::remove_var("CODEX_HOME");
+        Self { original }
+    }
+}
+
+impl Drop for HomeGuard {
+    fn drop(&mut self) {
+        match self.original.take() {
+            Some(v) => std::env::set_var("HOME", v),
+            None => std::env::remove_var("HOME"),
+        }
+    }
+}
+
+fn make_tmpdir() -> PathBuf {
+    let stamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let dir = std::env::temp_dir().join(format!("codex-switcher-service-e2e-{}", stamp));
+    fs::create_dir_all(dir.join(".codex")).unwrap();
+    dir
+}
+
+fn jwt(payload: Value) -> String {
+    let engine = base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    format!(
+        "{}.{}.sig",
+        engine.encode(r#"{"alg":"none"}"#),
+        engine.encode(serde_json::to_vec(&payload).unwrap())
+    )
+}
+
+/// access_token 的 exp = 现在 + `expires_in_secs`
+fn access_token(account_id: &str, expires_in_secs: i64) -> String {
+    jwt(json!({
+        "exp": chrono::Utc::now().timestamp() + expires_in_secs,
+        "https://api.openai.com/auth": { "chatgpt_account_id": account_id },
+    }))
+}
+
+fn make_oauth_auth(email: &str, account_id: &str, refresh_token: &str, expires_in: i64) -> Value {
+    let auth_claim = json!({ "chatgpt_account_id": account_id });
+    json!({
+        "tokens": {
+            "account_id": account_id,
+            "refresh_token": refresh_token,
+            "id_token": jwt(json!({ "email": email, "https://api.openai.com/auth": auth_claim })),
+            "access_token": access_token(account_id, expires_in),
+        },
+        "last_refresh": "2026-01-01T00:00:00Z",
+    })
+}
+
+fn usage(five_hour_left: i32) -> UsageDisplay {
+    UsageDisplay {
+        plan_type: "plus".to_string(),
+        display_plan: "Plus".to_string(),
+        five_hour_used: 100 - five_hour_left,
+        five_hour_left,
+        five_hour_label: "5H 限额".to_string(),
+        five_hour_reset: String::new(),
+        five_hour_reset_at: None,
+        weekly_used: 10,
+        weekly_left: 90,
+        weekly_label: "周限额".to_string(),
+        weekly_reset: String::new(),
+        weekly_reset_at: None,
+        credits_balance: None,
+        has_credits: false,
+        is_valid_for_cli: true,
+        codex_available: true,
+        five_hour_counts: Default::default(),
+        weekly_counts: Default::default(),
+    }
+}
+
+fn tokens(access_token: String, refresh_token: &str) -> TokenResponse {
+    TokenResponse {
+        access_token,
+        refresh_token: Some(refresh_token.to_string()),
+        id_token: None,
+        expires_in: Some(3600),
+    }
+}
+
+/// 一次 usage 请求：带的 access_token，以及是否允许本地刷新（允许时记 profile id）
+#[derive(Debug, Clone, PartialEq)]
+struct UsageCall {
+    access_token: String,
+    local_refresh: Option<String>,
+}
+
+type UsageReply = Result<(UsageDisplay, Option<TokenResponse>), String>;
+
+/// 按脚本依次回放响应的假 usage / OAuth 接口
+#[derive(Default)]
+struct FakeUsage {
+    usage_replies: Mutex<VecDeque<UsageReply>>,
+    refresh_replies: Mutex<VecDeque<Result<TokenResponse, String>>>,
+    usage_calls: Mutex<Vec<UsageCall>>,
+    refresh_calls: Mutex<Vec<String>>,
+    /// usage 响应前先等这么久（模拟卡住的上游）
+    usage_delay: Mutex<Option<Duration>>,
+}
+
+impl FakeUsage {
+    fn reply_usage(&self, reply: UsageReply) {
+        self.usage_replies.lock().unwrap().push_back(reply);
+    }
+
+    fn reply_refresh(&self, reply: Result<TokenResponse, String>) {
+        self.refresh_replies.lock().unwrap().push_back(reply);
+    }
+
+    fn delay_usage(&self, delay: Option<Duration>) {
+        *self.usage_delay.lock().unwrap() = delay;
+    }
+
+    fn take_usage_calls(&self) -> Vec<UsageCall> {
+        std::mem::take(&mut *self.usage_calls.lock().unwrap())
+    }
+
+    fn take_refresh_calls(&self) -> Vec<String> {
+        std::mem::take(&mut *self.refresh_calls.lock().unwrap())
+    }
+}
+
+impl UsageClient for FakeUsage {
+    fn fetch_usage<'a>(
+        &'a self,
+        access_token: String,
+        _account_id: Option<String>,
+        _refresh_token: Option<String>,
+        local_refresh: Option<&'a AuthProfile>,
+        _overrides: RequestOverrides,
+    ) -> BoxFuture<'a, UsageReply> {
+        self.usage_calls.lock().unwrap().push(UsageCall {
+            access_token,
+            local_refresh: local_refresh.map(|p| p.id.clone()),
+        });
+        let reply = self
+            .usage_replies
+            .lock()
+            .unwrap()
+            .pop_front()
+            .expect("没有预设的 usage 响应");
+        let delay = *self.usage_delay.lock().unwrap();
+        Box::pin(async move {
+            if let Some(delay) = delay {
+                tokio::time::sleep(delay).await;
+            }
+            reply
+        })
+    }
+
+    fn refresh_token<'a>(
+        &'a self,
+        _profile: &'a AuthProfile,
+        refresh_token: &'a str,
+    ) -> BoxFuture<'a, Result<TokenResponse, String>> {
+        self.refresh_calls
+            .lock()
+            .unwrap()
+            .push(refresh_token.to_string());
+        let reply = self
+            .refresh_replies
+            .lock()
+            .unwrap()
+            .pop_front()
+            .expect("没有预设的刷新响应");
+        Box::pin(async move { reply })
+    }
+}
+
+#[derive(Default)]
+struct RecordingSink {
+    emitted: Mutex<Vec<(String, Value)>>,
+    published: Mutex<Vec<AppEvent>>,
+}
+
+impl RecordingSink {
+    fn take_emitted(&self) -> Vec<String> {
+        std::mem::take(&mut *self.emitted.lock().unwrap())
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect()
+    }
+
+    /// 取走所有 `switch-progress` 负载，其它事件留着
+    fn take_progress(&self) -> Vec<Value> {
+        let mut emitted = self.emitted.lock().unwrap();
+        let (progress, rest) = std::mem::take(&mut *emitted)
+            .into_iter()
+            .partition::<Vec<_>, _>(|(name, _)| name == "switch-progress");
+        *emitted = rest;
+        progress.into_iter().map(|(_, payload)| payload).collect()
+    }
+
+    fn take_published(&self) -> Vec<AppEvent> {
+        std::mem::take(&mut *self.published.lock().unwrap())
+    }
+}
+
+impl EventSink for RecordingSink {
+    fn emit(&self, event: &str, payload: Value) {
+        self.emitted
+            .lock()
+            .unwrap()
+            .push((event.to_string(), payload));
+    }
+
+    fn publish(&self, event: AppEvent) {
+        self.published.lock().unwrap().push(event);
+    }
+}
+
+fn saved() -> AccountStore {
+    AccountStore::load()
+}
+
+/// 先把合并写的改动（额度缓存等，见 `AccountStore::mark_dirty`）落盘再读
+fn flushed(store: &Mutex<AccountStore>) -> AccountStore {
+    store.lock().unwrap().flush().unwrap();
+    saved()
+}
+
+/// 串行跑完所有子场景：
+///   1. 刷新额度：usage 顺带换回新 token → 写回 rt / 记录使用时间 / 写缓存并落盘
+///   2. 刷新额度：没有 access_token 或已过期时先用 rt 换，再按新 token 查 usage；没有 rt 直接 TOKEN_EXPIRED
+///   3. 刷新额度：封号 / 失效打标记且互斥；上游故障保留旧缓存
+///   4. 刷新当前账号：磁盘身份不一致拒绝，一致时先回流
+///   5. 切号：目标 token 快过期先刷新；预检不允许本地刷新；写 auth.json、切 current
+///   6. 切号：预检失败不阻断；刷新锁被占用时报错且不切；token 过期又刷新失败时跳过预检
+///   7. 冲突：rt 轮换 → SyncConflict 并记一条外部写入；开了自动采纳 → token-rotated；手动解决同步磁盘
+///   8. 导入当前账号：缺 rt 拒绝
+///   9. 托盘直调：智能切号跳过额度耗尽的候选；刷新当前账号额度；开关后台刷新
+///  10. 切号写 config.toml 的 profile：开关 / 未绑定 / 文件不存在时的新建开关
+///  11. 切号进度事件：成功、预检超时、等锁超时、写 auth.json 失败各自的阶段序列
+///  12. 刷新被判定 refresh_token 重复使用：挂刷新禁令并通知，之后不再发刷新请求；重新登录解除
+///  13. 加载：没有版本号的 accounts.json 迁移后保存带上版本；更新版本写的文件加载失败且不会被覆盖
+#[test]
+fn account_service_characterization() {
+    let tmp = make_tmpdir();
+    let _guard = HomeGuard::redirect_to(&tmp);
+    assert!(AccountStore::codex_auth_path().starts_with(&tmp));
+    let rt = tokio::runtime::Runtime::new().unwrap();
+
+    let mut initial = AccountStore::default();
+    let work = initial
+        .add_account(
+            "work@example.com".to_string(),
+            make_oauth_auth("work@example.com", "acct-work", "rt-work", 3600),
+            None,
+        )
+        .id;
+    let spare = initial
+        .add_account(
+            "spare@example.com".to_string(),
+            make_oauth_auth("spare@example.com", "acct-spare", "rt-spare", 3600),
+            None,
+        )
+        .id;
+    initial.save().unwrap();
+    AccountStore::write_codex_auth(&initial.accounts[&work].auth_json, AuthWriteOp::Other, None)
+        .unwrap();
+    assert_eq!(initial.current.as_deref(), Some(work.as_str()));
+
+    let store = Arc::new(Mutex::new(initial));
+    let locks = RefreshLockManager::default();
+    let fake = Arc::new(FakeUsage::default());
+    let sink = Arc::new(RecordingSink::default());
+    let service = AccountService::new(store.clone(), locks.clone(), fake.clone(), sink.clone());
+
+    // 1. usage 顺带换回了新 token
+    fake.reply_usage(Ok((
+        usage(80),
+        Some(tokens(access_token("acct-spare", 3600), "rt-spare-2")),
+    )));
+    let display = rt
+        .block_on(service.refresh_quota(&spare, QuotaSource::ManualRefresh))
+        .unwrap();
+    assert_eq!(display.five_hour_left, 80);
+    let calls = fake.take_usage_calls();
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].local_refresh.as_deref(), Some("openai-default"));
+    // 换回的 token 立即落盘；额度缓存只标记待写，合并写时才落盘
+    let persisted = saved();
+    assert_eq!(
+        persisted.accounts[&spare].refresh_token.as_deref(),
+        Some("rt-spare-2")
+    );
+    assert!(persisted.accounts[&spare].cached_quota.is_none());
+    assert!(store.lock().unwrap().flush().unwrap());
+    assert!(
+        !store.lock().unwrap().flush().unwrap(),
+        "没有新改动不重复写"
+    );
+    let persisted = saved();
+    let account = &persisted.accounts[&spare];
+    assert_eq!(account.refresh_token.as_deref(), Some("rt-spare-2"));
+    assert_eq!(
+        AccountStore::extract_refresh_token(&account.auth_json).as_deref(),
+        Some("rt-spare-2")
+    );
+    assert!(account.refresh_token_used_at.is_some());
+    let quota = account.cached_quota.as_ref().unwrap();
+    assert_eq!(quota.five_hour_left, 80.0);
+    assert_eq!(quota.source, QuotaSource::ManualRefresh);
+
+    // 2. 没有 access_token：先用 rt 换
+    {
+        let mut s = store.lock().unwrap();
+        let tokens = s.accounts.get_mut(&spare).unwrap().auth_json["tokens"]
+            .as_object_mut()
+            .unwrap();
+        tokens.remove("access_token");
+    }
+    let fresh = access_token("acct-spare", 3600);
+    fake.reply_refresh(Ok(tokens(fresh.clone(), "rt-spare-3")));
+    fake.reply_usage(Ok((usage(70), None)));
+    rt.block_on(service.refresh_quota(&spare, QuotaSource::BulkRefresh))
+        .unwrap();
+    assert_eq!(fake.take_refresh_calls(), vec!["rt-spare-2".to_string()]);
+    assert_eq!(fake.take_usage_calls()[0].access_token, fresh);
+    let persisted = flushed(&store);
+    assert_eq!(
+        persisted.accounts[&spare].refresh_token.as_deref(),
+        Some("rt-spare-3")
+    );
+    assert_eq!(
+        persisted.accounts[&spare]
+            .cached_quota
+            .as_ref()
+            .unwrap()
+            .source,
+        QuotaSource::BulkRefresh
+    );
+
+    // 2b. access_token 已过期：有 rt 先刷新，不发注定 401 的请求；没有 rt 直接 TOKEN_EXPIRED
+    {
+        let mut s = store.lock().unwrap();
+        let account = s.accounts.get_mut(&spare).unwrap();
+        account.auth_json["tokens"]["access_token"] = json!(access_token("acct-spare", -7200));
+    }
+    let renewed = access_token("acct-spare", 3600);
+    fake.reply_refresh(Ok(tokens(renewed.clone(), "rt-spare-3")));
+    fake.reply_usage(Ok((usage(70), None)));
+    rt.block_on(service.refresh_quota(&spare, QuotaSource::ManualRefresh))
+        .unwrap();
+    assert_eq!(fake.take_refresh_calls(), vec!["rt-spare-3".to_string()]);
+    let calls = fake.take_usage_calls();
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].access_token, renewed);
+
+    let stashed = {
+        let mut s = store.lock().unwrap();
+        let account = s.accounts.get_mut(&spare).unwrap();
+        let stashed = account.auth_json.clone();
+        account.refresh_token = None;
+        let tokens = account.auth_json["tokens"].as_object_mut().unwrap();
+        tokens.remove("refresh_token");
+        tokens.insert(
+            "access_token".to_string(),
+            json!(access_token("acct-spare", -7200)),
+        );
+        stashed
+    };
+    let err = rt
+        .block_on(service.refresh_quota(&spare, QuotaSource::ManualRefresh))
+        .unwrap_err();
+    assert!(err.starts_with("TOKEN_EXPIRED:"), "{}", err);
+    assert!(fake.take_usage_calls().is_empty());
+    assert!(fake.take_refresh_calls().is_empty());
+    {
+        let mut s = store.lock().unwrap();
+        let account = s.accounts.get_mut(&spare).unwrap();
+        assert!(!account.is_token_invalid, "本地判定过期不算失效");
+        account.refresh_token = Some("rt-spare-3".to_string());
+        account.auth_json = stashed;
+    }
+
+    // 3. 上游故障保留旧缓存、不打标记；封号打标记
+    fake.reply_usage(Err(format!("{}: HTTP 502", "UPSTREAM_UNAVAILABLE")));
+    let err = rt
+        .block_on(service.refresh_quota(&spare, QuotaSource::ManualRefresh))
+        .unwrap_err();
+    assert!(err.starts_with("UPSTREAM_UNAVAILABLE"), "{}", err);
+    {
+        let s = store.lock().unwrap();
+        let account = &s.accounts[&spare];
+        assert!(!account.is_banned && !account.is_token_invalid);
+        assert_eq!(account.cached_quota.as_ref().unwrap().five_hour_left, 70.0);
+    }
+    fake.reply_usage(Err("ACCOUNT_BANNED:账号已被封禁".to_string()));
+    let err = rt
+        .block_on(service.refresh_quota(&spare, QuotaSource::ManualRefresh))
+        .unwrap_err();
+    assert!(err.contains("ACCOUNT_BANNED"));
+    assert!(saved().accounts[&spare].is_banned);
+    fake.reply_usage(Err("TOKEN_INVALID:401".to_string()));
+    let _ = rt.block_on(service.refresh_quota(&spare, QuotaSource::ManualRefresh));
+    let persisted = saved();
+    assert!(persisted.accounts[&spare].is_token_invalid);
+    assert!(!persisted.accounts[&spare].is_banned, "标记互斥");
+    {
+        let mut s = store.lock().unwrap();
+        s.accounts.get_mut(&spare).unwrap().is_token_invalid = false;
+    }
+    fake.take_usage_calls();
+
+    // 4. 当前账号：磁盘是别的账号时拒绝；同一账号轮换过 rt 时先回流再查
+    AccountStore::write_codex_auth(
+        &make_oauth_auth("spare@example.com", "acct-spare", "rt-x", 3600),
+        AuthWriteOp::Other,
+        None,
+    )
+    .unwrap();
+    let err = rt
+        .block_on(service.refresh_quota(&work, QuotaSource::ManualRefresh))
+        .unwrap_err();
+    assert!(err.contains("身份不匹配"), "{}", err);
+    assert!(fake.take_usage_calls().is_empty());
+
+    AccountStore::write_codex_auth(
+        &make_oauth_auth("work@example.com", "acct-work", "rt-work-rotated", 3600),
+        AuthWriteOp::Other,
+        None,
+    )
+    .unwrap();
+    fake.reply_usage(Ok((usage(60), None)));
+    rt.block_on(service.refresh_quota(&work, QuotaSource::ManualRefresh))
+        .unwrap();
+    assert_eq!(
+        saved().accounts[&work].refresh_token.as_deref(),
+        Some("rt-work-rotated")
+    );
+    fake.take_usage_calls();
+
+    // 5. 切到 spare：access_token 只剩 1 分钟，先刷新；预检不允许本地刷新
+    {
+        let mut s = store.lock().unwrap();
+        let account = s.accounts.get_mut(&spare).unwrap();
+        account.auth_json["tokens"]["access_token"] = json!(access_token("acct-spare", 60));
+    }
+    let switched = access_token("acct-spare", 3600);
+    fake.reply_refresh(Ok(tokens(switched.clone(), "rt-spare-4")));
+    fake.reply_usage(Ok((usage(50), None)));
+    let warnings = rt.block_on(service.switch(&spare, false)).unwrap();
+    assert!(warnings.is_empty(), "{:?}", warnings);
+    assert_eq!(fake.take_refresh_calls(), vec!["rt-spare-3".to_string()]);
+    assert_eq!(
+        fake.take_usage_calls(),
+        vec![UsageCall {
+            access_token: switched.clone(),
+            local_refresh: None,
+        }]
+    );
+    let persisted = saved();
+    assert_eq!(persisted.current.as_deref(), Some(spare.as_str()));
+    let quota = persisted.accounts[&spare].cached_quota.clone().unwrap();
+    assert_eq!(quota.source, QuotaSource::SwitchPrecheck);
+    assert_eq!(quota.five_hour_left, 50.0);
+    let disk = AccountStore::read_codex_auth().unwrap();
+    assert_eq!(
+        AccountStore::extract_refresh_token(&disk).as_deref(),
+        Some("rt-spare-4")
+    );
+    assert_eq!(disk["tokens"]["access_token"], json!(switched));
+    let last = AuthJournal::system().read(1).remove(0);
+    assert_eq!(last.op, AuthWriteOp::Switch);
+    assert_eq!(last.account_id.as_deref(), Some(spare.as_str()));
+    assert_eq!(last.after, Some(Fingerprint::of(&disk)));
+
+    // 6. 切回 work：token 还新，不刷新；预检失败不阻断，作为警告返回
+    fake.reply_usage(Err("HTTP 500".to_string()));
+    let warnings = rt.block_on(service.switch(&work, false)).unwrap();
+    assert_eq!(
+        warnings,
+        vec![Warning::new(WarningCode::PrecheckFailed)
+            .with("account", "work@example.com")
+            .with("error", "HTTP 500")]
+    );
+    assert!(fake.take_refresh_calls().is_empty());
+    assert_eq!(fake.take_usage_calls().len(), 1);
+    assert_eq!(saved().current.as_deref(), Some(work.as_str()));
+
+    // 刷新锁被别的流程占着：等满超时后报错，不切
+    assert!(rt.block_on(locks.acquire(&spare, std::time::Duration::from_secs(1))));
+    fake.reply_usage(Ok((usage(50), None)));
+    let err = rt.block_on(service.switch(&spare, false)).unwrap_err();
+    assert!(err.contains("正在被其他流程刷新"), "{}", err);
+    assert_eq!(saved().current.as_deref(), Some(work.as_str()));
+    rt.block_on(locks.release(&spare));
+    fake.take_usage_calls();
+
+    // 6b. 目标 token 已过期且刷新失败：跳过注定 401 的预检，照常切换
+    {
+        let mut s = store.lock().unwrap();
+        let account = s.accounts.get_mut(&spare).unwrap();
+        account.auth_json["tokens"]["access_token"] = json!(access_token("acct-spare", -7200));
+    }
+    fake.reply_refresh(Err("网络请求失败".to_string()));
+    let warnings = rt.block_on(service.switch(&spare, false)).unwrap();
+    assert_eq!(
+        warnings.iter().map(|w| w.code).collect::<Vec<_>>(),
+        vec![
+            WarningCode::TokenRefreshFailed,
+            WarningCode::PrecheckSkipped
+        ]
+    );
+    assert_eq!(fake.take_refresh_calls().len(), 1);
+    assert!(fake.take_usage_calls().is_empty());
+    assert_eq!(saved().current.as_deref(), Some(spare.as_str()));
+    fake.reply_usage(Ok((usage(50), None)));
+    rt.block_on(service.switch(&work, false)).unwrap();
+    fake.take_usage_calls();
+
+    // 7. 冲突：Codex 在磁盘上轮换了 work 的 rt
+    sink.take_progress();
+    let mut rotated = make_oauth_auth("work@example.com", "acct-work", "rt-work-disk", 3600);
+    fs::write(AccountStore::codex_auth_path(), rotated.to_string()).unwrap();
+    assert_eq!(
+        service.check_conflict().unwrap().as_deref(),
+        Some("work@example.com")
+    );
+    let last = AuthJournal::system().read(1).remove(0);
+    assert_eq!(last.op, AuthWriteOp::External, "同步路径读到别人写的内容");
+    assert_eq!(last.after, Some(Fingerprint::of(&rotated)));
+    assert_eq!(
+        sink.take_published(),
+        vec![AppEvent::SyncConflict {
+            account_name: "work@example.com".to_string(),
+        }]
+    );
+    assert!(sink.take_emitted().is_empty());
+
+    store.lock().unwrap().settings.auto_adopt_current_rotation = true;
+    rotated["last_refresh"] = json!(chrono::Utc::now().to_rfc3339());
+    AccountStore::write_codex_auth(&rotated, AuthWriteOp::Other, None).unwrap();
+    assert_eq!(service.check_conflict().unwrap(), None);
+    assert_eq!(
+        sink.take_emitted(),
+        vec!["token-rotated", "accounts-updated"]
+    );
+    assert!(sink.take_published().is_empty());
+    assert_eq!(
+        saved().accounts[&work].refresh_token.as_deref(),
+        Some("rt-work-disk")
+    );
+
+    AccountStore::write_codex_auth(
+        &make_oauth_auth("work@example.com", "acct-work", "rt-work-manual", 3600),
+        AuthWriteOp::Other,
+        None,
+    )
+    .unwrap();
+    service.resolve_conflict(&work).unwrap();
+    assert_eq!(
+        saved().accounts[&work].refresh_token.as_deref(),
+        Some("rt-work-manual")
+    );
+    let err = service.resolve_conflict(&spare).unwrap_err();
+    assert!(err.contains("身份不匹配"), "身份不一致拒绝同步: {}", err);
+
+    // 8. 导入当前账号
+    let mut no_rt = make_oauth_auth("new@example.com", "acct-new", "rt-new", 3600);
+    no_rt["tokens"]
+        .as_object_mut()
+        .unwrap()
+        .remove("refresh_token");
+    AccountStore::write_codex_auth(&no_rt, AuthWriteOp::Other, None).unwrap();
+    let err = service
+        .import_current("new@example.com".to_string(), None, false)
+        .unwrap_err();
+    assert!(err.contains("缺少 refresh_token"), "{}", err);
+
+    AccountStore::write_codex_auth(
+        &make_oauth_auth("new@example.com", "acct-new", "rt-new", 3600),
+        AuthWriteOp::Other,
+        None,
+    )
+    .unwrap();
+    let imported = service
+        .import_current(
+            "new@example.com".to_string(),
+            Some("备用".to_string()),
+            false,
+        )
+        .unwrap();
+    assert!(!imported.updated);
+    let persisted = saved();
+    assert_eq!(persisted.accounts.len(), 3);
+    assert_eq!(
+        persisted.accounts[&imported.account.id]
+            .refresh_token
+            .as_deref(),
+        Some("rt-new")
+    );
+    // 再导入一次同一登录：更新原账号，不新增
+    let again = service
+        .import_current("new@example.com".to_string(), None, false)
+        .unwrap();
+    assert!(again.updated);
+    assert_eq!(again.account.id, imported.account.id);
+    assert_eq!(saved().accounts.len(), 3);
+
+    // 9. 托盘直调（主窗口 webview 可能从没加载过，不经前端）
+    {
+        let mut s = store.lock().unwrap();
+        s.current = Some(work.clone());
+        let account = s.accounts.get_mut(&spare).unwrap();
+        account.auth_json["tokens"]["access_token"] = json!(access_token("acct-spare", 3600));
+        account.cached_quota = None;
+        s.accounts
+            .get_mut(&imported.account.id)
+            .unwrap()
+            .set_cached_quota((&usage(10), QuotaSource::ManualRefresh).into());
+    }
+    // 评分：spare 没缓存（50）排在 new（10 + Plus 加分）前面；spare 实查 5h 已用完，跳过
+    fake.reply_usage(Ok((usage(0), None)));
+    fake.reply_usage(Ok((usage(60), None)));
+    fake.reply_usage(Ok((usage(60), None)));
+    let picked = rt.block_on(service.switch_to_next_warned(false)).unwrap();
+    assert_eq!(picked.value, imported.account.id);
+    // 磁盘上还是刚导入的 new，和 current（work）对不上：切号前的回流被拒，也作为警告带回
+    assert_eq!(
+        picked.warnings,
+        vec![
+            Warning::new(WarningCode::CandidateSkipped).with("account", "spare@example.com"),
+            Warning::new(WarningCode::SyncIdentityMismatch).with("account", "work@example.com"),
+        ]
+    );
+    assert_eq!(
+        fake.take_usage_calls().len(),
+        3,
+        "两次候选实查 + 一次切号预检"
+    );
+    let persisted = saved();
+    assert_eq!(
+        persisted.current.as_deref(),
+        Some(imported.account.id.as_str())
+    );
+    let spare_quota = persisted.accounts[&spare].cached_quota.clone().unwrap();
+    assert_eq!(spare_quota.five_hour_left, 0.0);
+    assert_eq!(spare_quota.source, QuotaSource::SwitchPrecheck);
+    let disk = AccountStore::read_codex_auth().unwrap();
+    assert_eq!(
+        AccountStore::extract_refresh_token(&disk).as_deref(),
+        Some("rt-new")
+    );
+
+    fake.reply_usage(Ok((usage(40), None)));
+    let refreshed = rt.block_on(service.refresh_current_quota()).unwrap();
+    assert_eq!(refreshed.five_hour_left, 40);
+    let quota = flushed(&store).accounts[&imported.account.id]
+        .cached_quota
+        .clone()
+        .unwrap();
+    assert_eq!(quota.five_hour_left, 40.0);
+    assert_eq!(quota.source, QuotaSource::ManualRefresh);
+    fake.take_usage_calls();
+
+    sink.take_emitted();
+    assert!(!service.set_background_refresh(true).unwrap());
+    assert!(saved().settings.background_refresh);
+    assert_eq!(sink.take_emitted(), vec!["settings-updated"]);
+    assert!(service.set_background_refresh(false).unwrap());
+    assert!(!saved().settings.background_refresh);
+    store.lock().unwrap().settings.remote_mode = "client".to_string();
+    let err = service.set_background_refresh(true).unwrap_err();
+    assert!(err.contains("client"), "{}", err);
+    assert!(!saved().settings.background_refresh);
+
+    store.lock().unwrap().current = None;
+    let err = rt.block_on(service.refresh_current_quota()).unwrap_err();
+    assert!(err.contains("没有激活账号"), "{}", err);
+    assert!(fake.take_usage_calls().is_empty(), "没有当前账号时不发请求");
+
+    // 10. 切号时按账号写 config.toml 的 profile：只改这一个键，注释原样保留
+    let config_path = tmp.join(".codex").join("config.toml");
+    let config =
+        "# 个人配置\nprofile = \"personal\" # 切号时改写\n\n[profiles.work]\nmodel = \"o3\"\n";
+    fs::write(&config_path, config).unwrap();
+    store
+        .lock()
+        .unwrap()
+        .set_codex_profile(&work, Some(" work "))
+        .unwrap();
+    assert_eq!(saved().accounts[&work].codex_profile, None, "setter 不落盘");
+    fake.reply_usage(Ok((usage(50), None)));
+    rt.block_on(service.switch(&work, false)).unwrap();
+    assert_eq!(
+        fs::read_to_string(&config_path).unwrap(),
+        config,
+        "开关没开不动 config.toml"
+    );
+    assert_eq!(
+        saved().accounts[&work].codex_profile.as_deref(),
+        Some("work")
+    );
+
+    store.lock().unwrap().settings.apply_profile_on_switch = true;
+    fake.reply_usage(Ok((usage(50), None)));
+    rt.block_on(service.switch(&spare, false)).unwrap();
+    assert_eq!(
+        fs::read_to_string(&config_path).unwrap(),
+        config,
+        "目标账号没绑 profile"
+    );
+    fake.reply_usage(Ok((usage(50), None)));
+    rt.block_on(service.switch(&work, false)).unwrap();
+    assert_eq!(
+        fs::read_to_string(&config_path).unwrap(),
+        config.replace("\"personal\"", "\"work\"")
+    );
+
+    // config.toml 不存在：默认不新建，开了第二个开关才建最小文件
+    fs::remove_file(&config_path).unwrap();
+    fake.reply_usage(Ok((usage(50), None)));
+    rt.block_on(service.switch(&work, false)).unwrap();
+    assert!(!config_path.exists());
+    store
+        .lock()
+        .unwrap()
+        .settings
+        .create_codex_config_if_missing = true;
+    fake.reply_usage(Ok((usage(50), None)));
+    rt.block_on(service.switch(&work, false)).unwrap();
+    assert_eq!(
+        fs::read_to_string(&config_path).unwrap(),
+        "profile = \"work\"\n"
+    );
+    fake.take_usage_calls();
+
+    // 11. 切号进度事件：阶段按顺序发，最后一定是 done / failed，switch_seq 递增
+    sink.take_progress();
+    let stages = |events: &[Value]| {
+        events
+            .iter()
+            .map(|e| e["stage"].clone())
+            .collect::<Vec<_>>()
+    };
+    let seq_of = |events: &[Value]| {
+        let seq = events[0]["switch_seq"].as_u64().unwrap();
+        assert!(events.iter().all(|e| e["switch_seq"] == json!(seq)));
+        seq
+    };
+    let success = vec![
+        json!("syncing_current"),
+        json!("prechecking"),
+        json!("acquiring_lock"),
+        json!("writing_auth"),
+        json!("done"),
+    ];
+
+    fake.reply_usage(Ok((usage(50), None)));
+    rt.block_on(service.switch(&spare, false)).unwrap();
+    let events = sink.take_progress();
+    assert_eq!(stages(&events), success);
+    assert!(events.iter().all(|e| e["target_id"] == json!(spare)));
+    let mut last_seq = seq_of(&events);
+    fake.take_usage_calls();
+
+    // 上游卡住：预检超时按失败处理，不阻断
+    fake.delay_usage(Some(Duration::from_secs(30)));
+    let impatient = AccountService::new(store.clone(), locks.clone(), fake.clone(), sink.clone())
+        .with_precheck_timeout(Duration::from_millis(50));
+    fake.reply_usage(Ok((usage(50), None)));
+    rt.block_on(impatient.switch(&work, false)).unwrap();
+    fake.delay_usage(None);
+    assert_eq!(fake.take_usage_calls().len(), 1);
+    let events = sink.take_progress();
+    assert_eq!(stages(&events), success);
+    assert!(seq_of(&events) > last_seq);
+    last_seq = seq_of(&events);
+    assert_eq!(saved().current.as_deref(), Some(work.as_str()));
+
+    // 刷新锁被占用：停在 acquiring_lock
+    assert!(rt.block_on(locks.acquire(&spare, Duration::from_secs(1))));
+    fake.reply_usage(Ok((usage(50), None)));
+    assert!(rt.block_on(service.switch(&spare, false)).is_err());
+    rt.block_on(locks.release(&spare));
+    let events = sink.take_progress();
+    assert_eq!(
+        stages(&events),
+        vec![
+            json!("syncing_current"),
+            json!("prechecking"),
+            json!("acquiring_lock"),
+            json!({"failed": {
+                "stage": "acquiring_lock",
+                "error": "该账号正在被其他流程刷新，请稍后重试",
+            }}),
+        ]
+    );
+    assert!(seq_of(&events) > last_seq);
+    last_seq = seq_of(&events);
+
+    // 写 auth.json 失败（目标路径被目录占住）：停在 writing_auth，current 不变
+    let auth_path = AccountStore::codex_auth_path();
+    let disk_before = fs::read_to_string(&auth_path).unwrap();
+    fs::remove_file(&auth_path).unwrap();
+    fs::create_dir(&auth_path).unwrap();
+    fake.reply_usage(Ok((usage(50), None)));
+    assert!(rt.block_on(service.switch(&spare, false)).is_err());
+    fs::remove_dir(&auth_path).unwrap();
+    fs::write(&auth_path, disk_before).unwrap();
+    let events = sink.take_progress();
+    assert_eq!(events.len(), 5);
+    assert_eq!(stages(&events[..4]), success[..4].to_vec());
+    assert_eq!(events[4]["stage"]["failed"]["stage"], json!("writing_auth"));
+    assert!(seq_of(&events) > last_seq);
+    assert_eq!(saved().current.as_deref(), Some(work.as_str()));
+
+    // 12. token 端点判定 rt 重复使用：挂禁令、标记失效、通知一次
+    let burned = store
+        .lock()
+        .unwrap()
+        .add_account(
+            "burned@example.com".to_string(),
+            make_oauth_auth("burned@example.com", "acct-burned", "rt-burned", -7200),
+            None,
+        )
+        .id;
+    sink.take_emitted();
+    fake.take_usage_calls();
+    fake.reply_refresh(Err(
+        r#"REFRESH_REUSED:刷新令牌被拒绝: {"error":{"code":"refresh_token_reused"}}"#.to_string(),
+    ));
+    let err = rt
+        .block_on(service.refresh_quota(&burned, QuotaSource::ManualRefresh))
+        .unwrap_err();
+    assert!(err.starts_with("TOKEN_INVALID:"), "{}", err);
+    assert_eq!(fake.take_refresh_calls(), vec!["rt-burned".to_string()]);
+    assert!(fake.take_usage_calls().is_empty());
+    let persisted = saved();
+    let account = &persisted.accounts[&burned];
+    assert!(account.is_token_invalid);
+    let ban = account.keepalive.refresh_ban.as_ref().unwrap();
+    assert_eq!(ban.reason, "refresh token 被判定重复使用");
+    assert!(sink.take_emitted().contains(&"refresh-banned".to_string()));
+
+    // 之后的刷新直接短路，不再打 token 端点，也不重复通知
+    let err = rt
+        .block_on(service.refresh_quota(&burned, QuotaSource::ManualRefresh))
+        .unwrap_err();
+    assert!(err.contains("REFRESH_BANNED:"), "{}", err);
+    assert!(fake.take_refresh_calls().is_empty());
+    assert!(fake.take_usage_calls().is_empty());
+    assert!(!sink.take_emitted().contains(&"refresh-banned".to_string()));
+
+    // 重新登录（同一邮箱、同一身份）解除禁令，刷新恢复
+    let relogin_auth = make_oauth_auth("burned@example.com", "acct-burned", "rt-burned-2", 3600);
+    {
+        let mut s = store.lock().unwrap();
+        let other = make_oauth_auth("spare@example.com", "acct-burned", "rt-x", 3600);
+        assert_eq!(s.find_refresh_banned_account(&other), None);
+        assert_eq!(
+            s.find_refresh_banned_account(&relogin_auth).as_deref(),
+            Some(burned.as_str())
+        );
+        s.complete_relogin(&burned, relogin_auth, Some("rt-burned-2".to_string()))
+            .unwrap();
+        assert!(s.accounts[&burned].keepalive.refresh_ban.is_none());
+        assert!(!s.accounts[&burned].is_token_invalid);
+    }
+    fake.reply_usage(Ok((usage(80), None)));
+    rt.block_on(service.refresh_quota(&burned, QuotaSource::ManualRefresh))
+        .unwrap();
+    let calls = fake.take_usage_calls();
+    assert_eq!(calls.len(), 1);
+    assert!(calls[0].local_refresh.is_some(), "禁令解除后允许本地刷新");
+
+    // 13. 版本迁移
+    let path = AccountStore::config_path();
+    let mut doc: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    doc.as_object_mut().unwrap().remove("version");
+    fs::write(&path, doc.to_string()).unwrap();
+    let migrated = saved();
+    assert!(migrated.load_error().is_none());
+    assert_eq!(
+        migrated.accounts.len(),
+        store.lock().unwrap().accounts.len()
+    );
+    migrated.save().unwrap();
+    let doc: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+    assert!(doc["version"].as_u64().unwrap() >= 1);
+
+    let newer = r#"{"version": 999, "accounts": {}, "current": null}"#;
+    fs::write(&path, newer).unwrap();
+    let refused = saved();
+    assert!(refused.load_error().unwrap().contains("999"));
+    assert!(refused.accounts.is_empty());
+    assert!(refused.save().is_err());
+    assert_eq!(fs::read_to_string(&path).unwrap(), newer);
+
+    // 14. 文件损坏：改名留存，从最新一份备份恢复（备份一小时最多轮转一次，不一定是最后一次保存的内容）
+    let backup: Value = serde_json::from_str(
+        &fs::read_to_string(path.with_file_name("accounts.json.bak")).unwrap(),
+    )
+    .unwrap();
+    fs::write(&path, "{\"accounts\": {").unwrap();
+    let recovered = saved();
+    let health = serde_json::to_value(recovered.health()).unwrap();
+    assert_eq!(health["status"], "restored_from_backup");
+    assert!(recovered.load_error().is_none());
+    assert_eq!(
+        recovered.accounts.len(),
+        backup["accounts"].as_object().unwrap().len()
+    );
+    let corrupt_path = health["corrupt_path"].as_str().unwrap();
+    assert_eq!(
+        fs::read_to_string(corrupt_path).unwrap(),
+        "{\"accounts\": {"
+    );
+    recovered.save().unwrap();
+    assert!(saved().health().message().is_none());
+
+    // 15. API key 形态的 auth.json：能导入、不算冲突、切号原样写盘且不查额度
+    let key_auth = json!({ "OPENAI_API_KEY": "sk-team-123" });
+    AccountStore::write_codex_auth(&key_auth, AuthWriteOp::Other, None).unwrap();
+    let key_account = service
+        .import_current("team-key".to_string(), None, false)
+        .unwrap();
+    assert!(!key_account.updated);
+    assert!(key_account.account.is_api_key());
+    let again = service
+        .import_current("team-key".to_string(), None, false)
+        .unwrap();
+    assert!(again.updated);
+    assert_eq!(again.account.id, key_account.account.id);
+
+    store.lock().unwrap().current = Some(key_account.account.id.clone());
+    assert_eq!(service.check_conflict().unwrap(), None);
+
+    AccountStore::write_codex_auth(
+        &make_oauth_auth("work@example.com", "acct-work", "rt-disk", 3600),
+        AuthWriteOp::Other,
+        None,
+    )
+    .unwrap();
+    store.lock().unwrap().current = Some(work.clone());
+    fake.take_usage_calls();
+    rt.block_on(service.switch(&key_account.account.id, false))
+        .unwrap();
+    assert!(
+        fake.take_usage_calls().is_empty(),
+        "API key 账号不做额度预检"
+    );
+    assert_eq!(AccountStore::read_codex_auth().unwrap(), key_auth);
+    let err = rt
+        .block_on(service.refresh_quota(&key_account.account.id, QuotaSource::ManualRefresh))
+        .unwrap_err();
+    assert!(err.starts_with("API_KEY_ACCOUNT:"), "{}", err);
+    assert!(fake.take_usage_calls().is_empty());
+
+    // 16. 粘贴 auth.json 导入：不碰磁盘上的 auth.json，同一身份只更新
+    let disk_before = fs::read_to_string(AccountStore::codex_auth_path()).unwrap();
+    let err = service
+        .import_pasted(String::new(), "{\"tokens\": ", None, false)
+        .unwrap_err();
+    assert!(err.starts_with("json: 第 1 行"), "{}", err);
+    let err = service
+        .import_pasted(String::new(), r#"{"last_refresh": null}"#, None, false)
+        .unwrap_err();
+    assert!(err.contains("tokens: 缺少 tokens"), "{}", err);
+    let pasted = make_oauth_auth("colleague@example.com", "acct-colleague", "rt-c", 3600);
+    let mut no_rt = pasted.clone();
+    no_rt["tokens"]
+        .as_object_mut()
+        .unwrap()
+        .remove("refresh_token");
+    let err = service
+        .import_pasted(String::new(), &no_rt.to_string(), None, false)
+        .unwrap_err();
+    assert!(err.starts_with("tokens.refresh_token:"), "{}", err);
+
+    let colleague = service
+        .import_pasted(String::new(), &pasted.to_string(), None, false)
+        .unwrap();
+    assert!(!colleague.updated);
+    assert_eq!(colleague.account.name, "colleague@example.com");
+    let again = service
+        .import_pasted("同事".to_string(), &pasted.to_string(), None, false)
+        .unwrap();
+    assert!(again.updated);
+    assert_eq!(again.account.id, colleague.account.id);
+    assert!(saved().accounts.contains_key(&colleague.account.id));
+
+    // API key 形态同样能粘贴导入：没有邮箱要填名称，按 key 去重
+    let pasted_key = serde_json::json!({ "OPENAI_API_KEY": "sk-pasted" });
+    let err = service
+        .import_pasted(String::new(), &pasted_key.to_string(), None, false)
+        .unwrap_err();
+    assert!(err.starts_with("name:"), "{}", err);
+    let key_import = service
+        .import_pasted(
+            "同事的 key".to_string(),
+            &pasted_key.to_string(),
+            None,
+            false,
+        )
+        .unwrap();
+    assert!(!key_import.updated);
+    assert!(key_import.account.is_api_key());
+    assert_eq!(
+        saved().accounts[&key_import.account.id].to_codex_auth_value(),
+        pasted_key
+    );
+    let key_again = service
+        .import_pasted("team".to_string(), &key_auth.to_string(), None, false)
+        .unwrap();
+    assert!(key_again.updated);
+    assert_eq!(key_again.account.id, key_account.account.id);
+    assert_eq!(
+        fs::read_to_string(AccountStore::codex_auth_path()).unwrap(),
+        disk_before
+    );
+
+    let _ = fs::remove_dir_all(&tmp);
+}
@@ -0,0 +1,161 @@
+//! token 存储迁移（accounts.json ⇄ 凭据库）端到端测试
+//!
+//! 临时改 HOME 把 `~/.codex-switcher/accounts.json` 重定向到 tempdir，凭据库用
+//! `MemoryBackend` 代替。setenv 不是线程安全的，所以所有场景放在同一个 #[test] 里串行跑。
+//!
+//! 跑法：`cargo test --test token_storage_e2e -- --nocapture`
+
+use codex_switcher_lib::account::AccountStore;
+use codex_switcher_lib::token_store::{self, MemoryBackend, TokenBackendError, TokenStorage};
+use serde_json::{json, Value};
+use std::fs;
+use std::path::PathBuf;
+
+/// HOME 重定向 RAII 守卫：drop 时还原原值。
+struct HomeGuard {
+    original: Option<String>,
+}
+
+impl HomeGuard {
+    fn redirect_to(tmp: &PathBuf) -> Self {
+        let original = std::env::var("HOME").ok();
+        std::env::set_var("HOME", tmp);
+        // 外面设了 CODEX_HOME 时 auth.json 会写到真实目录去
+        std::env::remove_var("CODEX_HOME");
+        Self { original }
+    }
+}
+
+impl Drop for HomeGuard {
+    fn drop(&mut self) {
+        match self.original.take() {
+            Some(v) => std::env::set_var("HOME", v),
+            None => std::env::remove_var("HOME"),
+        }
+    }
+}
+
+fn make_tmpdir() -> PathBuf {
+    let stamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_nanos();
+    let dir = std::env::temp_dir().join(format!("codex-switcher-token-e2e-{}", stamp));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+fn auth(account_id: &str, refresh_token: &str) -> Value {
+    json!({
+        "tokens": {
+            "account_id": account_id,
+            "refresh_token": refresh_token,
+            "access_token": format!("eyJ.at-{}", account_id),
+            "id_token": format!("eyJ.id-{}", account_id),
+        },
+        "last_refresh": "2026-01-01T00:00:00Z",
+    })
+}
+
+fn saved_file() -> String {
+    fs::read_to_string(AccountStore::config_path()).unwrap()
+}
+
+fn reload(backend: &MemoryBackend) -> AccountStore {
+    let mut store: AccountStore = serde_json::from_str(&saved_file()).unwrap();
+    assert!(store.hydrate_tokens_with(backend).is_empty());
+    store
+}
+
+/// 串行跑完 4 个子场景：
+///   1. File → Keychain：文件里不再有 refresh_token，重新加载后 token 完整
+///   2. 只放 refresh_token ↔ 连同 access / id token 一起放
+///   3. 凭据库写入失败 → 迁移报错、设置不变、文件里 token 仍完整
+///   4. Keychain → File：token 写回文件，凭据库条目清空
+#[test]
+fn token_storage_migrates_both_ways() {
+    let tmp = make_tmpdir();
+    let _guard = HomeGuard::redirect_to(&tmp);
+    assert!(AccountStore::config_path().starts_with(&tmp));
+    let backend = MemoryBackend::default();
+
+    let mut store = AccountStore::default();
+    let a = store
+        .add_account("a@example.com".to_string(), auth("acct-a", "rt-a"), None)
+        .id;
+    let b = store
+        .add_account("b@example.com".to_string(), auth("acct-b", "rt-b"), None)
+        .id;
+    store.save_with(&backend).unwrap();
+    assert!(saved_file().contains("rt-a"));
+    let original = store.clone();
+
+    // 1. File → Keychain
+    let report = store
+        .migrate_token_storage_with(TokenStorage::Keychain, false, &backend)
+        .unwrap();
+    assert_eq!(report.to, TokenStorage::Keychain);
+    assert_eq!(report.accounts, 2);
+    let content = saved_file();
+    assert!(!content.contains("rt-a") && !content.contains("rt-b"));
+    assert!(content.contains(&token_store::token_ref_key(&a)));
+    assert!(content.contains("eyJ.at-acct-a"), "默认只迁 refresh_token");
+    assert_eq!(backend.entries.lock().unwrap().len(), 2);
+    let loaded = reload(&backend);
+    assert_eq!(loaded.settings.token_storage, TokenStorage::Keychain);
+    assert_eq!(
+        loaded.accounts[&a].auth_json,
+        original.accounts[&a].auth_json
+    );
+    assert_eq!(loaded.accounts[&b].refresh_token.as_deref(), Some("rt-b"));
+
+    // 2. 连同 access / id token 一起放，再放回来
+    store
+        .migrate_token_storage_with(TokenStorage::Keychain, true, &backend)
+        .unwrap();
+    assert!(!saved_file().contains("eyJ.at-acct-a"));
+    assert_eq!(
+        reload(&backend).accounts[&a].auth_json,
+        original.accounts[&a].auth_json
+    );
+    store
+        .migrate_token_storage_with(TokenStorage::Keychain, false, &backend)
+        .unwrap();
+    assert!(saved_file().contains("eyJ.at-acct-a"));
+
+    // 3. 钥匙串锁定：File → Keychain 失败不改设置
+    let locked = MemoryBackend::default();
+    *locked.fail_with.lock().unwrap() = Some(TokenBackendError::KeychainLocked {
+        detail: "locked".to_string(),
+    });
+    let mut file_store = original.clone();
+    let err = file_store
+        .migrate_token_storage_with(TokenStorage::Keychain, false, &locked)
+        .unwrap_err();
+    assert!(err.contains("KEYCHAIN_LOCKED"), "{}", err);
+    assert_eq!(file_store.settings.token_storage, TokenStorage::File);
+
+    // 4. Keychain → File
+    let report = store
+        .migrate_token_storage_with(TokenStorage::File, false, &backend)
+        .unwrap();
+    assert_eq!(report.from, TokenStorage::Keychain);
+    assert_eq!(report.accounts, 2);
+    assert!(report.warnings.is_empty());
+    assert!(backend.entries.lock().unwrap().is_empty());
+    let content = saved_file();
+    assert!(content.contains("rt-a") && content.contains("rt-b"));
+    assert!(!content.contains("token_ref"));
+    let loaded = reload(&backend);
+    assert_eq!(loaded.settings.token_storage, TokenStorage::File);
+    assert_eq!(
+        loaded.accounts[&a].auth_json,
+        original.accounts[&a].auth_json
+    );
+    assert_eq!(
+        loaded.accounts[&b].auth_json,
+        original.accounts[&b].auth_json
+    );
+
+    let _ = fs::remove_dir_all(&tmp);
+}
@@ -1,10 +1,10 @@
 import { useState, useEffect } from 'react';
 import { Zap } from 'lucide-react';
-import { invoke } from '@tauri-apps/api/core';
+import { invoke } from './ipc';
 import { listen } from '@tauri-apps/api/event';
 import { save } from '@tauri-apps/plugin-dialog';
 import { writeTextFile } from '@tauri-apps/plugin-fs';
-import { useAccounts } from './hooks/useAccounts';
+import { useAccounts, PlanChangedEvent, SwitchProgress, SWITCH_STAGE_LABELS, OpWarning, describeWarning, StoreBackup } from './hooks/useAccounts';
 import { useUsage } from './hooks/useUsage';
 import { AddAccountModal } from './components/AddAccountModal';
 import { AddRelayModal } from './components/AddRelayModal';
@@ -18,13 +18,28 @@ import { SessionRoutes } from './components/SessionRoutes';
 import CachePanel from './components/CachePanel';
 import { ConfirmModal } from './components/ConfirmModal';
 import { RelayImportConfirm } from './components/RelayImportConfirm';
+import { ErrorCenter } from './components/ErrorCenter';
 import './App.css';
 
+/** `get_pending_revert`：临时切号到点自动切回 */
+interface PendingRevert {
+  temporary_id: string;
+  temporary_name: string;
+  revert_to: string;
+  revert_to_name: string;
+  revert_at: string;
+  warn_at: string;
+  warned: boolean;
+}
+
 type PageType = 'dashboard' | 'accounts' | 'proxy' | 'routes' | 'stats' | 'cache' | 'skills' | 'settings';
 
 function App() {
   const {
     accounts,
+    compatibility,
+    tokenStatuses,
+    integrityIssues,
     currentId,
     settings,
     loading,
@@ -33,7 +48,12 @@ function App() {
     importCurrent,
     switchTo,
     deleteAccount,
+    deleteAccounts,
+    pendingUndo,
+    undoLast,
+    dismissUndo,
     exportAccounts,
+    exportAccountsRedacted,
     reloadIdeWindows,
     updateSettings,
     checkSyncConflict,
@@ -64,11 +84,21 @@ function App() {
 
   // 冲突确认弹窗状态
   const [showConflictModal, setShowConflictModal] = useState(false);
+  const [showExportModal, setShowExportModal] = useState(false);
+  const [exportPassphrase, setExportPassphrase] = useState('');
+  const [exportRedacted, setExportRedacted] = useState(false);
   const [conflictAccountName, setConflictAccountName] = useState('');
   const [pendingSwitchId, setPendingSwitchId] = useState<string | null>(null);
   const [isSwitching, setIsSwitching] = useState(false);
   const [syncStatus, setSyncStatus] = useState<any>(null);
   const [proxyRunning, setProxyRunning] = useState(false);
+  const [presentationMode, setPresentationMode] = useState(false);
+
+  const checkPresentationMode = async () => {
+    try {
+      setPresentationMode(await invoke<boolean>('get_presentation_mode'));
+    } catch { setPresentationMode(false); }
+  };
 
   const checkProxyStatus = async () => {
     try {
@@ -89,6 +119,7 @@ function App() {
   useEffect(() => {
     checkSyncStatus();
     checkProxyStatus();
+    checkPresentationMode();
   }, []);
 
   const currentAccount = accounts.find(a => a.id === currentId) || null;
@@ -96,6 +127,7 @@ function App() {
   const classifyRefreshFailure = (reason: string): 'permanent' | 'transient' => {
     const lower = reason.toLowerCase();
     if (
+      reason.includes('REFRESH_REUSED:') ||
       lower.includes('refresh_token_reused') ||
       lower.includes('refresh_token_invalidated') ||
       lower.includes('refresh_token_expired')
@@ -110,6 +142,7 @@ function App() {
     const unlisten = listen('accounts-updated', () => {
       console.log('[Frontend] 收到后台刷新通知，重新加载账号列表');
       refresh();
+      checkPresentationMode();
     });
 
     return () => {
@@ -129,7 +162,26 @@ function App() {
         setSchedulerError(`后台保活临时失败（${account_name}）：${reason} @ ${timestamp}`);
       }
     });
+    // refresh_token 被判定重复使用：该账号已停止一切自动刷新，必须重新登录
+    const unlistenBan = listen<{ account_id: string; account_name: string; message: string }>('refresh-banned', (event) => {
+      setSchedulerError(`${event.payload.message} @ ${new Date().toLocaleTimeString()}`);
+    });
 
+    return () => {
+      unlisten.then(f => f());
+      unlistenBan.then(f => f());
+    };
+  }, []);
+
+  const [pendingRevert, setPendingRevert] = useState<PendingRevert | null>(null);
+
+  // 临时切号横幅：启动时拉一次，之后跟着后端事件刷新
+  useEffect(() => {
+    const load = () => invoke<PendingRevert | null>('get_pending_revert')
+      .then(setPendingRevert)
+      .catch(err => console.error('获取待切回失败:', err));
+    load();
+    const unlisten = listen('pending-revert-changed', load);
     return () => {
       unlisten.then(f => f());
     };
@@ -137,6 +189,7 @@ function App() {
 
   // 监听代理切号/封号事件
   const [proxyNotice, setProxyNotice] = useState<string | null>(null);
+  const [switchProgress, setSwitchProgress] = useState<SwitchProgress | null>(null);
   useEffect(() => {
     const unsub1 = listen<string>('proxy-account-switched', (e) => {
       const msg = `代理已自动切号 → ${e.payload}`;
@@ -162,6 +215,100 @@ function App() {
     };
   }, [refresh]);
 
+  // 套餐变更（尤其降级）：额度突然变少前先提醒一句
+  useEffect(() => {
+    const unlisten = listen<PlanChangedEvent>('plan-changed', (e) => {
+      const { account_name, old_display, new_display, downgrade } = e.payload;
+      setProxyNotice(`${account_name} 套餐${downgrade ? '降级' : '变更'}：${old_display} → ${new_display}`);
+      setTimeout(() => setProxyNotice(null), 15000);
+    });
+    return () => {
+      unlisten.then(f => f());
+    };
+  }, []);
+
+  // 当前账号 token 在磁盘轮换、已被自动采纳（auto_adopt_current_rotation）：只做轻提示
+  useEffect(() => {
+    const unlisten = listen<{ account_id: string; account_name: string }>('token-rotated', (e) => {
+      console.log('[Frontend] 已自动采纳磁盘轮换的 token:', e.payload.account_name);
+      setProxyNotice(`已同步 ${e.payload.account_name} 的新 Token`);
+      setTimeout(() => setProxyNotice(null), 5000);
+      refresh();
+    });
+    return () => {
+      unlisten.then(f => f());
+    };
+  }, [refresh]);
+
+  // 后台回流当前账号被拒（身份 / 邮箱对不上）：结论变化时才会收到
+  useEffect(() => {
+    const unlisten = listen<{ account_id: string; warning: OpWarning | null }>('sync-warning', (e) => {
+      if (!e.payload.warning) return;
+      setProxyNotice(describeWarning(e.payload.warning));
+      setTimeout(() => setProxyNotice(null), 10000);
+    });
+    return () => {
+      unlisten.then(f => f());
+    };
+  }, []);
+
+  // 当前账号按近期速率预计 30 分钟内耗尽 5h 额度
+  useEffect(() => {
+    const unlisten = listen<{ account_name: string; minutes_remaining: number }>('quota-exhaustion-warning', (e) => {
+      setProxyNotice(`${e.payload.account_name} 预计 30 分钟内耗尽（约 ${e.payload.minutes_remaining} 分钟）`);
+      setTimeout(() => setProxyNotice(null), 10000);
+    });
+    return () => {
+      unlisten.then(f => f());
+    };
+  }, []);
+
+  // 每日自动备份写好了
+  useEffect(() => {
+    const unlisten = listen<StoreBackup>('backup-created', (e) => {
+      setProxyNotice(`已自动备份账号库：${e.payload.file_name}`);
+      setTimeout(() => setProxyNotice(null), 5000);
+    });
+    return () => {
+      unlisten.then(f => f());
+    };
+  }, []);
+
+  // 托盘"设置…"/"添加账号…"：主窗口可能刚被创建，加载时和收到通知时都取一次排队的跳转
+  useEffect(() => {
+    const applyPendingNavigation = async () => {
+      try {
+        const intents = await invoke<Array<'dashboard' | 'settings' | 'add_account'>>('get_pending_navigation');
+        for (const intent of intents) {
+          if (intent === 'add_account') {
+            setShowAddModal(true);
+          } else {
+            setCurrentPage(intent);
+          }
+        }
+      } catch (err) {
+        console.error('读取托盘跳转失败:', err);
+      }
+    };
+    applyPendingNavigation();
+    const unlisten = listen('navigation-pending', applyPendingNavigation);
+
+    return () => {
+      unlisten.then(f => f());
+    };
+  }, []);
+
+  // 切号进度：只认最新一次切号的事件，晚到的旧事件丢掉
+  useEffect(() => {
+    const unlisten = listen<SwitchProgress>('switch-progress', (event) => {
+      setSwitchProgress(prev => (prev && prev.switch_seq > event.payload.switch_seq ? prev : event.payload));
+    });
+
+    return () => {
+      unlisten.then(f => f());
+    };
+  }, []);
+
   // 监听设置更新事件
   useEffect(() => {
     const unlisten = listen('settings-updated', () => {
@@ -260,15 +407,29 @@ function App() {
     setPendingSwitchId(null);
   };
 
-  const handleExport = async () => {
+  const handleExport = () => {
+    setExportPassphrase('');
+    setExportRedacted(false);
+    setShowExportModal(true);
+  };
+
+  // 口令留空导出明文；填了导出加密信封；勾了脱敏则不带 token、不能再导入
+  const runExport = async () => {
+    const passphrase = exportPassphrase;
+    const redacted = exportRedacted;
+    setShowExportModal(false);
+    setExportPassphrase('');
     try {
-      const json = await exportAccounts();
+      const json = redacted
+        ? await exportAccountsRedacted()
+        : await exportAccounts(passphrase || undefined);
+      const suffix = redacted ? '-redacted' : passphrase ? '-encrypted' : '';
       const path = await save({
         filters: [{
           name: 'JSON',
           extensions: ['json']
         }],
-        defaultPath: `codex-accounts-${new Date().toISOString().slice(0, 10)}.json`
+        defaultPath: `codex-accounts-${new Date().toISOString().slice(0, 10)}${suffix}.json`
       });
 
       if (path) {
@@ -360,6 +521,7 @@ function App() {
         </nav>
 
         <div className="header-actions">
+          <ErrorCenter />
           <button className="btn btn-primary" onClick={() => setShowAddModal(true)}>
             + 登录账号
           </button>
@@ -376,7 +538,40 @@ function App() {
         </div>
       )}
 
-      {proxyNotice && (
+      {presentationMode && (
+        <div className="presentation-banner">
+          演示模式：账号信息已打码，修改操作已禁用
+          <button onClick={() => invoke('set_presentation_mode', { enabled: false })}>退出演示模式</button>
+        </div>
+      )}
+
+      {pendingUndo && (
+        <div className="presentation-banner">
+          {pendingUndo.kind === 'delete'
+            ? `已删除 ${pendingUndo.account_ids.length} 个账号`
+            : '已用导入文件替换账号库'}
+          <button onClick={undoLast}>撤销</button>
+          <button onClick={dismissUndo}>关闭</button>
+        </div>
+      )}
+
+      {pendingRevert && (
+        <div className="presentation-banner">
+          临时使用 {pendingRevert.temporary_name}，
+          {new Date(pendingRevert.revert_at).toLocaleTimeString('zh-CN', { hour: '2-digit', minute: '2-digit' })} 自动切回 {pendingRevert.revert_to_name}
+          <button onClick={() => invoke('cancel_pending_revert')}>保留当前账号</button>
+        </div>
+      )}
+
+      {/* 失败原因走 error banner，这里只显示进行中的阶段 */}
+      {switchProgress && typeof switchProgress.stage === 'string' && switchProgress.stage !== 'done' && (
+        <div className="proxy-notice-banner switch-progress">
+          切换中：{SWITCH_STAGE_LABELS[switchProgress.stage]}（{(switchProgress.elapsed_ms / 1000).toFixed(1)}s）
+        </div>
+      )}
+
+      {/* 演示模式下通知里带真实账号名，直接不显示 */}
+      {proxyNotice && !presentationMode && (
         <div className="proxy-notice-banner" onClick={() => setProxyNotice(null)}>
           {proxyNotice}
         </div>
@@ -429,10 +624,14 @@ function App() {
         ) : currentPage === 'accounts' ? (
           <AccountList
             accounts={accounts}
+            compatibility={compatibility}
+            tokenStatuses={tokenStatuses}
+            integrityIssues={integrityIssues}
             currentId={currentId}
             settings={settings}
             onSwitch={handleSwitch}
             onDelete={deleteAccount}
+            onDeleteMany={deleteAccounts}
             onUpdateSettings={updateSettings}
             onRefreshComplete={refresh}
             onAddAccount={() => setShowAddModal(true)}
@@ -468,6 +667,37 @@ function App() {
         onSuccess={refresh}
       />
 
+      <ConfirmModal
+        isOpen={showExportModal}
+        title="导出账号数据"
+        message={
+          <>
+            <p>导出文件包含所有账号的 refresh_token。设置口令后导出为加密文件，导入时需要同一口令。</p>
+            <input
+              type="password"
+              className="text-input"
+              style={{ width: '100%', marginTop: '8px' }}
+              value={exportPassphrase}
+              onChange={e => setExportPassphrase(e.target.value)}
+              placeholder="口令（至少 8 位；留空则导出明文）"
+              disabled={exportRedacted}
+              autoFocus
+            />
+            <label style={{ display: 'flex', gap: '6px', alignItems: 'center', marginTop: '8px' }}>
+              <input
+                type="checkbox"
+                checked={exportRedacted}
+                onChange={e => setExportRedacted(e.target.checked)}
+              />
+              脱敏导出：token 全部替换为 &lt;redacted&gt;，可贴进求助帖，但不能再导入
+            </label>
+          </>
+        }
+        confirmText={exportRedacted ? '脱敏导出' : exportPassphrase ? '加密导出' : '明文导出'}
+        onConfirm={runExport}
+        onCancel={() => { setShowExportModal(false); setExportPassphrase(''); }}
+      />
+
       <ConfirmModal
         isOpen={showConflictModal}
         title="⚠️ 登录状态冲突警告"
@@ -1,8 +1,11 @@
-import { useState, useEffect, useMemo, useRef } from 'react';
-import { Zap, RefreshCw, ArrowLeftRight, Trash2, Clock, UploadCloud, Plus, Gauge } from 'lucide-react';
-import { Account, AppSettings, RelayUsageCache, effectiveKind } from '../hooks/useAccounts';
-import { invoke } from '@tauri-apps/api/core';
+import { Fragment, useState, useEffect, useMemo, useRef } from 'react';
+import { Zap, RefreshCw, ArrowLeftRight, Trash2, Clock, UploadCloud, Plus, Gauge, Settings2, Tags, Tag, Pin, Shield, ArrowUp, Archive, Timer, Share2, Copy } from 'lucide-react';
+import { Account, AppSettings, BulkDeleteOutcome, TrashedAccount, CachedQuota, CompatibilityVerdict, IntegrityIssue, TokenStatus, QUOTA_SOURCE_LABELS, QuotaSource, RelayUsageCache, effectiveKind, quotaExhaustions } from '../hooks/useAccounts';
+import { WindowCounts, formatLeft, remainingCount } from '../hooks/useUsage';
+import { invoke } from '../ipc';
 import { openUrl } from '@tauri-apps/plugin-opener';
+import { save } from '@tauri-apps/plugin-dialog';
+import { writeTextFile } from '@tauri-apps/plugin-fs';
 
 const KIND_BADGE: Record<ReturnType<typeof effectiveKind>, { label: string; className: string }> = {
     chatgpt_oauth: { label: '订阅', className: 'badge kind-chatgpt' },
@@ -23,6 +26,26 @@ function relayCategoryBadge(account: Account): { label: string; className: strin
             return { label: '中转', className: 'badge kind-relay' };
     }
 }
+/** 自洽性问题的一行说明（badge 的 title） */
+function describeIntegrityIssue(issue: IntegrityIssue): string {
+    switch (issue.code) {
+        case 'missing_refresh_token':
+            return '缺少 refresh_token，无法续期';
+        case 'missing_id_token':
+            return '缺少 id_token，无法核对身份';
+        case 'undecodable_token':
+            return `${issue.token} 解析失败：${issue.error}`;
+        case 'account_id_mismatch':
+            return `account_id 为 ${issue.stored ?? '空'}，${issue.token} 里是 ${issue.claimed}`;
+        case 'subject_mismatch':
+            return `id_token 与 access_token 的 sub 不一致（${issue.id_token} / ${issue.access_token}）`;
+        case 'email_mismatch':
+            return `记录的邮箱 ${issue.stored} 与 token 里的 ${issue.claimed} 不一致`;
+        case 'name_mismatch':
+            return `名称 ${issue.name} 与 token 里的邮箱 ${issue.claimed} 不一致`;
+    }
+}
+
 import { useShortCountdown } from '../hooks/useCountdown';
 import './AccountList.css';
 import { ConfirmModal } from './ConfirmModal';
@@ -37,17 +60,90 @@ interface UsageData {
     weekly_reset_at?: number;
     weekly_label: string;
     plan_type: string;
+    display_plan?: string;
     is_valid_for_cli: boolean;
+    codex_available?: boolean;
+    five_hour_counts?: WindowCounts;
+    weekly_counts?: WindowCounts;
+}
+
+/** 套餐变更徽标显示多久（7 天） */
+const PLAN_CHANGE_BADGE_MS = 7 * 24 * 60 * 60 * 1000;
+
+interface Compared<T> {
+    a: T;
+    b: T;
+    winner: 'a' | 'b' | 'equal' | 'incomparable';
+}
+
+interface QuotaWindow {
+    left: number;
+    reset_at: number | null;
+    cache_age_secs: number;
+}
+
+interface AccountComparison {
+    id_a: string;
+    id_b: string;
+    name: Compared<string>;
+    same_identity: boolean;
+    email: Compared<string | null>;
+    account_id: Compared<string | null>;
+    plan: Compared<string | null>;
+    five_hour: Compared<QuotaWindow | null>;
+    weekly: Compared<QuotaWindow | null>;
+    quota_updated_at: Compared<string | null>;
+    last_refresh: Compared<string | null>;
+    access_token_expires_at: Compared<string | null>;
+    has_refresh_token: Compared<boolean>;
+    created_at: Compared<string>;
+    last_used: Compared<string | null>;
+    status: Compared<{ banned: boolean; token_invalid: boolean; logged_out: boolean }>;
+    refresh_errors?: string[];
+}
+
+type ComparisonRow = [string, Compared<any>, (value: any) => string];
+
+function comparisonRows(c: AccountComparison): ComparisonRow[] {
+    const text = (v: string | null) => v ?? '-';
+    const time = (v: string | null) => (v ? new Date(v).toLocaleString('zh-CN') : '-');
+    const quota = (w: QuotaWindow | null) =>
+        w ? `${Math.round(w.left)}%（${Math.round(w.cache_age_secs / 60)} 分钟前）` : '-';
+    const status = (s: AccountComparison['status']['a']) =>
+        [s.banned && '已封号', s.token_invalid && '授权失效', s.logged_out && '已登出'].filter(Boolean).join('、') || '正常';
+    return [
+        ['名称', c.name, text],
+        ['邮箱', c.email, text],
+        ['Account ID', c.account_id, text],
+        ['套餐', c.plan, text],
+        ['5H 额度', c.five_hour, quota],
+        ['周额度', c.weekly, quota],
+        ['额度缓存时间', c.quota_updated_at, time],
+        ['上次刷新 token', c.last_refresh, time],
+        ['access_token 过期', c.access_token_expires_at, time],
+        ['refresh_token', c.has_refresh_token, (v: boolean) => (v ? '有' : '无')],
+        ['创建时间', c.created_at, time],
+        ['上次使用', c.last_used, time],
+        ['状态', c.status, status],
+    ];
 }
 
 type FilterType = 'all' | 'sub' | 'plus' | 'pro' | 'team' | 'free' | 'relay' | 'coding_plan' | 'third_party';
 
 interface AccountListProps {
     accounts: Account[];
+    /** 各账号与本机 Codex 版本的兼容性，按 id */
+    compatibility?: Record<string, CompatibilityVerdict>;
+    /** 各账号 access_token 的过期状态，按 id */
+    tokenStatuses?: Record<string, TokenStatus>;
+    /** 自洽性检查有问题的账号，按 id */
+    integrityIssues?: Record<string, IntegrityIssue[]>;
     currentId: string | null;
     settings: AppSettings;
     onSwitch: (id: string) => void | Promise<void>;
     onDelete: (id: string) => void;
+    /** 批量删除；当前账号默认跳过，`allowCurrent` 时一并删 */
+    onDeleteMany?: (ids: string[], allowCurrent: boolean) => Promise<BulkDeleteOutcome>;
     onUpdateSettings: (settings: AppSettings) => void;
     onRefreshComplete?: () => void;
     onAddAccount?: () => void;
@@ -58,6 +154,9 @@ interface AccountListProps {
 
 export function AccountList({
     accounts,
+    compatibility,
+    tokenStatuses,
+    integrityIssues,
     currentId,
     settings,
     onSwitch,
@@ -66,6 +165,7 @@ export function AccountList({
     onRefreshUsage,
     usageLoading,
     onDelete,
+    onDeleteMany,
     onUpdateSettings,
     onRefreshComplete,
 }: AccountListProps) {
@@ -77,15 +177,44 @@ export function AccountList({
     const [isRefreshingAll, setIsRefreshingAll] = useState(false);
     const [searchQuery, setSearchQuery] = useState('');
     const [filter, setFilter] = useState<FilterType>('all');
+    // 标签过滤，空串为不过滤
+    const [tagFilter, setTagFilter] = useState('');
+    // 键值备注过滤：点列表里的小标签设置
+    const [metaFilter, setMetaFilter] = useState<{ key: string; value: string } | null>(null);
     const [invalidIds, setInvalidIds] = useState<Set<string>>(new Set());
     const [bannedIds, setBannedIds] = useState<Set<string>>(new Set());
     const [accountToDelete, setAccountToDelete] = useState<{ id: string, name: string } | null>(null);
+    // 批量删除确认框；选中里有当前账号时可勾选一并删除
+    const [bulkDelete, setBulkDelete] = useState<{ ids: string[]; allowCurrent: boolean } | null>(null);
+    const [subsetExport, setSubsetExport] = useState<{ ids: string[]; includeSettings: boolean } | null>(null);
     const [pushingIds, setPushingIds] = useState<Set<string>>(new Set());
     const [pushToast, setPushToast] = useState<{ type: 'success' | 'error'; text: string } | null>(null);
     // Relay 类型账号的余额缓存（与 ChatGPT usage 独立）
     const [relayUsageMap, setRelayUsageMap] = useState<Record<string, RelayUsageCache>>({});
     const [cookieEditor, setCookieEditor] = useState<{ id: string; name: string; value: string } | null>(null);
     const [savingCookie, setSavingCookie] = useState(false);
+    const [profileEditor, setProfileEditor] = useState<{ id: string; name: string; value: string } | null>(null);
+    // 标签编辑：逗号分隔；键值备注逐条增删，即时保存
+    const [tagsEditor, setTagsEditor] = useState<{ id: string; name: string; value: string; metadata: Record<string, string>; metaKey: string; metaValue: string } | null>(null);
+    // 复制备份：填副本名称
+    const [cloneEditor, setCloneEditor] = useState<{ id: string; name: string; value: string } | null>(null);
+    // 外观编辑：颜色 + 头像 emoji
+    const [appearanceEditor, setAppearanceEditor] = useState<{ id: string; name: string; color: string; emoji: string } | null>(null);
+    // 回收站，打开时才加载
+    const [trash, setTrash] = useState<TrashedAccount[] | null>(null);
+    // 同一登录的重复账号分组（老版本能重复导入），列表上方提示合并
+    const [duplicates, setDuplicates] = useState<string[][]>([]);
+    // 请求头编辑：每行一个 "Name: value"
+    const [headersEditor, setHeadersEditor] = useState<{ id: string; name: string; value: string } | null>(null);
+    const [tempSwitch, setTempSwitch] = useState<{ id: string; name: string; minutes: number } | null>(null);
+    const [share, setShare] = useState<{
+        format: 'markdown' | 'plain_text' | 'json';
+        emails: 'hidden' | 'masked' | 'full';
+        includeNotes: boolean;
+        text: string;
+    } | null>(null);
+    const [comparison, setComparison] = useState<AccountComparison | null>(null);
+    const [comparing, setComparing] = useState(false);
 
     const autoReload = settings.auto_reload_ide;
     const setAutoReload = (val: boolean) => onUpdateSettings({ ...settings, auto_reload_ide: val });
@@ -126,7 +255,11 @@ export function AccountList({
                     weekly_reset_at: acc.cached_quota.weekly_reset_at,
                     weekly_label: acc.cached_quota.weekly_label || '周限额',
                     plan_type: acc.cached_quota.plan_type,
+                    display_plan: acc.cached_quota.display_plan,
                     is_valid_for_cli: isValid,
+                    codex_available: acc.cached_quota.codex_available,
+                    five_hour_counts: acc.cached_quota.five_hour_counts,
+                    weekly_counts: acc.cached_quota.weekly_counts,
                 };
                 if (!isValid) initialInvalids.add(acc.id);
             }
@@ -143,7 +276,7 @@ export function AccountList({
     //   失败的话 90s 后自动重试，最多 90s/次的开销可以接受
     // - 跳过 refreshingIds 里在飞的，避免叠加
     // - is_token_invalid/banned/logged_out 由 backend 持久化，前端尊重
-    const handleRefreshOneRef = useRef<(id: string) => Promise<void>>(async () => {});
+    const handleRefreshOneRef = useRef<(id: string, source?: QuotaSource) => Promise<void>>(async () => {});
     const autoRefreshTsRef = useRef<Map<string, number>>(new Map());
     const refreshingIdsRef = useRef<Set<string>>(new Set());
     refreshingIdsRef.current = refreshingIds;
@@ -180,7 +313,7 @@ export function AccountList({
             const worker = async () => {
                 while (cursor < stale.length) {
                     const i = cursor++;
-                    await handleRefreshOneRef.current(stale[i]).catch((e) => {
+                    await handleRefreshOneRef.current(stale[i], 'bulk_refresh').catch((e) => {
                         console.warn(`[AutoRefresh] ${stale[i]} 刷新失败:`, e);
                     });
                 }
@@ -193,12 +326,38 @@ export function AccountList({
         return () => clearInterval(t);
     }, [accounts]);
 
+    // 搜索走后端（能搜邮箱和备注，结果按匹配程度排好）；输入停顿后再查
+    const [searchHits, setSearchHits] = useState<string[] | null>(null);
+    useEffect(() => {
+        if (!searchQuery.trim()) {
+            setSearchHits(null);
+            return;
+        }
+        const t = setTimeout(() => {
+            invoke<Account[]>('search_accounts', { query: searchQuery })
+                .then(hits => setSearchHits(hits.map(a => a.id)))
+                .catch(() => setSearchHits(null));
+        }, 150);
+        return () => clearTimeout(t);
+    }, [searchQuery, accounts]);
+
     // 搜索与过滤逻辑
     const filteredAccounts = useMemo(() => {
-        let result = searchQuery
-            ? accounts.filter(a => a.name.toLowerCase().includes(searchQuery.toLowerCase()))
+        const byId = new Map(accounts.map(a => [a.id, a]));
+        let result = searchHits
+            ? searchHits.map(id => byId.get(id)).filter((a): a is Account => !!a)
             : accounts;
 
+        if (tagFilter) {
+            // 与后端 list_accounts_filtered 一致：不区分大小写
+            const wanted = tagFilter.toLowerCase();
+            result = result.filter(a => (a.tags ?? []).some(t => t.toLowerCase() === wanted));
+        }
+
+        if (metaFilter) {
+            result = result.filter(a => a.metadata?.[metaFilter.key] === metaFilter.value);
+        }
+
         if (filter !== 'all') {
             result = result.filter(a => {
                 // Relay 类账号现在按 relay_category 分流
@@ -217,8 +376,18 @@ export function AccountList({
                 return true;
             });
         }
-        return result;
-    }, [accounts, searchQuery, filter, usageMap]);
+        // 归档的放到最后单独一组（filter 保持原有顺序）
+        return [...result.filter(a => !a.archived), ...result.filter(a => a.archived)];
+    }, [accounts, searchHits, tagFilter, metaFilter, filter, usageMap]);
+
+    // 所有账号出现过的标签（不区分大小写去重，保留先出现的写法）
+    const allTags = useMemo(() => {
+        const seen = new Map<string, string>();
+        accounts.forEach(a => (a.tags ?? []).forEach(t => {
+            if (!seen.has(t.toLowerCase())) seen.set(t.toLowerCase(), t);
+        }));
+        return Array.from(seen.values()).sort((a, b) => a.localeCompare(b));
+    }, [accounts]);
 
     const filterCounts = useMemo(() => {
         const counts = { all: accounts.length, sub: 0, pro: 0, plus: 0, team: 0, free: 0, relay: 0, coding_plan: 0, third_party: 0 };
@@ -249,6 +418,14 @@ export function AccountList({
         return isNaN(d.getTime()) ? '-' : d.toLocaleDateString('zh-CN', { month: '2-digit', day: '2-digit', hour: '2-digit', minute: '2-digit' });
     };
 
+    // 例："来源：后台刷新 · 12 分钟前"
+    const quotaSourceTitle = (q?: CachedQuota | null) => {
+        if (!q?.updated_at) return undefined;
+        const mins = Math.max(0, Math.floor((Date.now() - new Date(q.updated_at).getTime()) / 60000));
+        const ago = mins < 1 ? '刚刚' : mins < 60 ? `${mins} 分钟前` : mins < 1440 ? `${Math.floor(mins / 60)} 小时前` : `${Math.floor(mins / 1440)} 天前`;
+        return `来源：${QUOTA_SOURCE_LABELS[q.source ?? 'manual_refresh']} · ${ago}`;
+    };
+
     const parseDuration = (str?: string) => {
         if (!str || str === '未知' || str === 'N/A') return { text: 'N/A', hours: 999 };
         if (str === '即将重置') return { text: '重置中', hours: 0 };
@@ -293,7 +470,11 @@ export function AccountList({
     // 把 Tauri/后端原始报错翻译成人能看懂的一句话。
     const humanizeRefreshError = (raw: string): string => {
         const s = raw.toLowerCase();
+        if (s.includes('upstream_unavailable')) return 'ChatGPT 服务暂时不可用，已保留旧额度，稍后自动重试';
         if (s.includes('account_banned')) return '账号已被封禁';
+        if (s.includes('token_expired')) return 'access_token 已过期，且无法在本机刷新';
+        if (s.includes('refresh_banned') || s.includes('refresh_reused')) return 'refresh token 被判定重复使用，已停止自动刷新，需要重新登录';
+        if (s.includes('workspace_mismatch')) return '配额接口返回了别的工作区的额度，已丢弃（检查该账号的 account_id）';
         if (s.includes('token_invalid')) return 'Token 已失效，需要重新登录';
         if (s.includes('account_logged_out')) return '账号已登出，需要重新登录';
         if (s.includes('timeout') || s.includes('timed out')) return '请求超时（OpenAI 端慢/被节流）';
@@ -305,7 +486,7 @@ export function AccountList({
     };
 
     // 交互处理
-    const handleRefreshOne = async (id: string) => {
+    const handleRefreshOne = async (id: string, source: QuotaSource = 'manual_refresh') => {
         setRefreshingIds(prev => new Set(prev).add(id));
         const acc = accounts.find(a => a.id === id);
         const accName = acc?.name ?? id;
@@ -320,7 +501,7 @@ export function AccountList({
             const cmd = settings.remote_mode === 'client'
                 ? 'remote_refresh_account_quota'
                 : 'get_quota_by_id';
-            const usage = await invoke<UsageData>(cmd, { id });
+            const usage = await invoke<UsageData>(cmd, { id, source });
             setUsageMap(prev => ({ ...prev, [id]: usage }));
             setInvalidIds(prev => {
                 const next = new Set(prev);
@@ -352,6 +533,321 @@ export function AccountList({
     // 不必把它放进依赖里反复重建。
     handleRefreshOneRef.current = handleRefreshOne;
 
+    const handleSetColor = async (id: string, color: string | null) => {
+        try {
+            await invoke('set_account_color', { id, color });
+            onRefreshComplete?.();
+        } catch (e) {
+            setPushToast({ type: 'error', text: `设置颜色失败: ${e}` });
+            setTimeout(() => setPushToast(null), 4000);
+        }
+    };
+
+    const handleSaveCodexProfile = async () => {
+        if (!profileEditor) return;
+        try {
+            await invoke('set_account_codex_profile', {
+                id: profileEditor.id,
+                profile: profileEditor.value.trim() || null,
+            });
+            setProfileEditor(null);
+            onRefreshComplete?.();
+        } catch (e) {
+            setPushToast({ type: 'error', text: `设置 Codex profile 失败: ${e}` });
+            setTimeout(() => setPushToast(null), 4000);
+        }
+    };
+
+    const handleTogglePin = async (acc: Account) => {
+        try {
+            await invoke('pin_account', { id: acc.id, pinned: !acc.pinned });
+            onRefreshComplete?.();
+        } catch (e) {
+            setPushToast({ type: 'error', text: `置顶失败: ${e}` });
+            setTimeout(() => setPushToast(null), 4000);
+        }
+    };
+
+    const handleBulkDelete = async () => {
+        if (!bulkDelete || !onDeleteMany) return;
+        const { ids, allowCurrent } = bulkDelete;
+        setBulkDelete(null);
+        try {
+            const outcome = await onDeleteMany(ids, allowCurrent);
+            setSelectedIds(new Set());
+            const names = new Map(accounts.map(a => [a.id, a.name]));
+            const skipped = outcome.skipped.map(s => `${names.get(s.id) ?? s.id}（${s.reason}）`).join('，');
+            setPushToast({
+                type: outcome.skipped.length > 0 ? 'error' : 'success',
+                text: `已删除 ${outcome.removed.length} 个${skipped ? `；跳过：${skipped}` : ''}`,
+            });
+        } catch (e) {
+            setPushToast({ type: 'error', text: `批量删除失败: ${e}` });
+        }
+        setTimeout(() => setPushToast(null), 6000);
+    };
+
+    // 只导出选中的账号（交给队友）；默认不带设置，对方导入时保留自己的设置
+    const handleExportSelected = async () => {
+        if (!subsetExport) return;
+        const { ids, includeSettings } = subsetExport;
+        setSubsetExport(null);
+        try {
+            const json = await invoke<string>('export_accounts_subset', { ids, includeSettings });
+            const path = await save({
+                filters: [{ name: 'JSON', extensions: ['json'] }],
+                defaultPath: `codex-accounts-${ids.length}-${new Date().toISOString().slice(0, 10)}.json`,
+            });
+            if (!path) return;
+            await writeTextFile(path, json);
+            setPushToast({ type: 'success', text: `已导出 ${ids.length} 个账号` });
+        } catch (e) {
+            setPushToast({ type: 'error', text: `导出失败: ${e}` });
+        }
+        setTimeout(() => setPushToast(null), 4000);
+    };
+
+    // 取消保护要先向后端要一张一次性确认
+    const handleToggleProtected = async (acc: Account) => {
+        try {
+            if (acc.protected) {
+                if (!confirm(`取消保护后「${acc.name}」可以被删除，也可能被手动同步覆盖 token。是否继续？`)) return;
+                const ticket = await invoke<string>('request_unprotect_ticket', { id: acc.id });
+                await invoke('set_account_protected', { id: acc.id, protected: false, ticket });
+            } else {
+                await invoke('set_account_protected', { id: acc.id, protected: true });
+            }
+            onRefreshComplete?.();
+        } catch (e) {
+            setPushToast({ type: 'error', text: `设置保护失败: ${e}` });
+            setTimeout(() => setPushToast(null), 4000);
+        }
+    };
+
+    // 和上一个同为置顶 / 非置顶的账号对调位置；accounts 已是后端排好的顺序
+    const handleMoveUp = async (acc: Account) => {
+        const ids = accounts.map(a => a.id);
+        const index = ids.indexOf(acc.id);
+        if (index <= 0 || !!accounts[index - 1].pinned !== !!acc.pinned) return;
+        [ids[index - 1], ids[index]] = [ids[index], ids[index - 1]];
+        try {
+            await invoke('reorder_accounts', { ids });
+            onRefreshComplete?.();
+        } catch (e) {
+            setPushToast({ type: 'error', text: `调整顺序失败: ${e}` });
+            setTimeout(() => setPushToast(null), 4000);
+        }
+    };
+
+    const handleArchive = async (acc: Account) => {
+        try {
+            await invoke('archive_account', { id: acc.id, archived: !acc.archived });
+            onRefreshComplete?.();
+        } catch (e) {
+            setPushToast({ type: 'error', text: `归档失败: ${e}` });
+            setTimeout(() => setPushToast(null), 4000);
+        }
+    };
+
+    useEffect(() => {
+        invoke<string[][]>('find_duplicate_accounts')
+            .then(setDuplicates)
+            .catch(() => setDuplicates([]));
+    }, [accounts]);
+
+    // 组内第一个（列表顺序）保留，其余依次合并进去
+    const handleMergeGroup = async (group: string[]) => {
+        const [keepId, ...rest] = group;
+        try {
+            for (const removeId of rest) {
+                await invoke('merge_accounts', { keepId, removeId });
+            }
+            onRefreshComplete?.();
+        } catch (e) {
+            setPushToast({ type: 'error', text: `合并失败: ${e}` });
+            setTimeout(() => setPushToast(null), 4000);
+        }
+    };
+
+    const openTrash = async () => {
+        try {
+            setTrash(await invoke<TrashedAccount[]>('get_trash'));
+        } catch (e) {
+            setPushToast({ type: 'error', text: `读取回收站失败: ${e}` });
+            setTimeout(() => setPushToast(null), 4000);
+        }
+    };
+
+    const handleRestore = async (id: string) => {
+        try {
+            await invoke('restore_account', { id });
+            setTrash(prev => prev ? prev.filter(t => t.account.id !== id) : prev);
+            onRefreshComplete?.();
+        } catch (e) {
+            setPushToast({ type: 'error', text: `恢复失败: ${e}` });
+            setTimeout(() => setPushToast(null), 4000);
+        }
+    };
+
+    const handleEmptyTrash = async () => {
+        try {
+            await invoke<number>('purge_trash', { olderThanDays: 0 });
+            setTrash([]);
+        } catch (e) {
+            setPushToast({ type: 'error', text: `清空回收站失败: ${e}` });
+            setTimeout(() => setPushToast(null), 4000);
+        }
+    };
+
+    const handleClone = async () => {
+        if (!cloneEditor) return;
+        try {
+            await invoke<Account>('clone_account', { id: cloneEditor.id, newName: cloneEditor.value });
+            setCloneEditor(null);
+            onRefreshComplete?.();
+        } catch (e) {
+            setPushToast({ type: 'error', text: `复制失败: ${e}` });
+            setTimeout(() => setPushToast(null), 4000);
+        }
+    };
+
+    const handleSaveAppearance = async () => {
+        if (!appearanceEditor) return;
+        try {
+            await invoke('update_account_appearance', {
+                id: appearanceEditor.id,
+                color: appearanceEditor.color.trim() || null,
+                emoji: appearanceEditor.emoji.trim() || null,
+            });
+            setAppearanceEditor(null);
+            onRefreshComplete?.();
+        } catch (e) {
+            setPushToast({ type: 'error', text: `设置外观失败: ${e}` });
+            setTimeout(() => setPushToast(null), 4000);
+        }
+    };
+
+    const handleSaveTags = async () => {
+        if (!tagsEditor) return;
+        try {
+            await invoke('set_account_tags', {
+                id: tagsEditor.id,
+                tags: tagsEditor.value.split(/[,，]/).map(t => t.trim()).filter(Boolean),
+            });
+            setTagsEditor(null);
+            onRefreshComplete?.();
+        } catch (e) {
+            setPushToast({ type: 'error', text: `设置标签失败: ${e}` });
+            setTimeout(() => setPushToast(null), 4000);
+        }
+    };
+
+    const handleSetMetadata = async () => {
+        if (!tagsEditor) return;
+        const key = tagsEditor.metaKey.trim().toLowerCase();
+        const value = tagsEditor.metaValue.trim();
+        try {
+            await invoke('set_account_metadata', { id: tagsEditor.id, key, value });
+            setTagsEditor(prev => prev ? { ...prev, metadata: { ...prev.metadata, [key]: value }, metaKey: '', metaValue: '' } : prev);
+            onRefreshComplete?.();
+        } catch (e) {
+            setPushToast({ type: 'error', text: `设置键值备注失败: ${e}` });
+            setTimeout(() => setPushToast(null), 4000);
+        }
+    };
+
+    const handleDeleteMetadata = async (key: string) => {
+        if (!tagsEditor) return;
+        try {
+            await invoke('delete_account_metadata', { id: tagsEditor.id, key });
+            setTagsEditor(prev => {
+                if (!prev) return prev;
+                const metadata = { ...prev.metadata };
+                delete metadata[key];
+                return { ...prev, metadata };
+            });
+            onRefreshComplete?.();
+        } catch (e) {
+            setPushToast({ type: 'error', text: `删除键值备注失败: ${e}` });
+            setTimeout(() => setPushToast(null), 4000);
+        }
+    };
+
+    const handleCompare = async (idA: string, idB: string, refreshFirst: boolean) => {
+        setComparing(true);
+        try {
+            setComparison(await invoke<AccountComparison>('compare_accounts', { idA, idB, refreshFirst }));
+        } catch (e) {
+            setPushToast({ type: 'error', text: `对比失败: ${e}` });
+            setTimeout(() => setPushToast(null), 4000);
+        } finally {
+            setComparing(false);
+        }
+    };
+
+    // 分享快照：选中了账号就只分享选中的，否则后端默认取全部订阅账号
+    const shareOptions = share && `${share.format}|${share.emails}|${share.includeNotes}`;
+    useEffect(() => {
+        if (!share) return;
+        invoke<string>('generate_share_snapshot', {
+            format: share.format,
+            includeAccounts: selectedIds.size > 0 ? Array.from(selectedIds) : null,
+            emails: share.emails,
+            includeNotes: share.includeNotes,
+        })
+            .then(text => setShare(prev => prev ? { ...prev, text } : prev))
+            .catch(e => setShare(prev => prev ? { ...prev, text: `生成失败: ${e}` } : prev));
+    }, [shareOptions]);
+
+    const handleCopyShare = async () => {
+        if (!share) return;
+        try {
+            await invoke('copy_to_clipboard', { text: share.text });
+            setPushToast({ type: 'success', text: '额度快照已复制' });
+        } catch (e) {
+            setPushToast({ type: 'error', text: `复制失败: ${e}` });
+        } finally {
+            setTimeout(() => setPushToast(null), 3000);
+        }
+    };
+
+    const handleTemporarySwitch = async () => {
+        if (!tempSwitch) return;
+        const { id, name, minutes } = tempSwitch;
+        setTempSwitch(null);
+        try {
+            await invoke('switch_account_temporarily', { id, minutes });
+            setPushToast({ type: 'success', text: `已临时切到 ${name}，${minutes} 分钟后自动切回` });
+        } catch (e) {
+            setPushToast({ type: 'error', text: `临时切换失败: ${e}` });
+        } finally {
+            setTimeout(() => setPushToast(null), 4000);
+        }
+    };
+
+    const handleSaveRequestHeaders = async () => {
+        if (!headersEditor) return;
+        const headers: Record<string, string> = {};
+        for (const line of headersEditor.value.split('\n')) {
+            if (!line.trim()) continue;
+            const idx = line.indexOf(':');
+            if (idx <= 0) {
+                setPushToast({ type: 'error', text: `请求头格式应为 "Name: value"：${line.trim()}` });
+                setTimeout(() => setPushToast(null), 4000);
+                return;
+            }
+            headers[line.slice(0, idx).trim()] = line.slice(idx + 1).trim();
+        }
+        try {
+            await invoke('set_account_request_headers', { id: headersEditor.id, headers });
+            setHeadersEditor(null);
+            onRefreshComplete?.();
+        } catch (e) {
+            setPushToast({ type: 'error', text: `设置请求头失败: ${e}` });
+            setTimeout(() => setPushToast(null), 4000);
+        }
+    };
+
     const handleSaveUsageCookie = async () => {
         if (!cookieEditor) return;
         setSavingCookie(true);
@@ -447,7 +943,7 @@ export function AccountList({
         );
     };
 
-    const QuotaItem = ({ label, percentage, reset, resetAt }: { label: string, percentage: number | undefined, reset: string | undefined, resetAt?: number }) => {
+    const QuotaItem = ({ label, percentage, reset, resetAt, counts }: { label: string, percentage: number | undefined, reset: string | undefined, resetAt?: number, counts?: WindowCounts }) => {
         const countdown = useShortCountdown(resetAt);
         if (percentage === undefined) return (
             <div className="quota-mini-card empty">
@@ -459,6 +955,7 @@ export function AccountList({
         const displayTime = countdown || text;
         const color = percentage > 50 ? 'green' : percentage > 20 ? 'orange' : 'red';
         const timeColor = hours < 1 ? 'success' : hours < 6 ? 'warning' : 'neutral';
+        const left = remainingCount(counts);
 
         return (
             <div className="quota-mini-card">
@@ -469,7 +966,9 @@ export function AccountList({
                         <Clock className="icon-tiny" />
                         <span>{displayTime}</span>
                     </div>
-                    <span className={`quota-percent ${color}`}>{Math.round(percentage)}%</span>
+                    <span className={`quota-percent ${color}`} title={formatLeft(percentage, counts)}>
+                        {left == null ? `${Math.round(percentage)}%` : `${left} 次 · ${Math.round(percentage)}%`}
+                    </span>
                 </div>
             </div>
         );
@@ -480,7 +979,7 @@ export function AccountList({
             <div className="account-list-toolbar">
                 <div className="search-box">
                     <span className="search-icon">🔍</span>
-                    <input type="text" placeholder="搜索邮箱..." value={searchQuery} onChange={e => setSearchQuery(e.target.value)} />
+                    <input type="text" placeholder="搜索名称 / 邮箱 / 备注..." value={searchQuery} onChange={e => setSearchQuery(e.target.value)} />
                 </div>
                 <div className="filter-group">
                     {(['all', 'sub', 'pro', 'plus', 'team', 'free', 'relay', 'coding_plan', 'third_party'] as const).map(t => {
@@ -503,6 +1002,22 @@ export function AccountList({
                         );
                     })}
                 </div>
+                {allTags.length > 0 && (
+                    <select
+                        className="tag-filter"
+                        value={tagFilter}
+                        onChange={e => setTagFilter(e.target.value)}
+                        title="按标签过滤"
+                    >
+                        <option value="">全部标签</option>
+                        {allTags.map(t => <option key={t} value={t}>{t}</option>)}
+                    </select>
+                )}
+                {metaFilter && (
+                    <span className="badge account-meta" onClick={() => setMetaFilter(null)} title="点击取消过滤">
+                        {metaFilter.key}: {metaFilter.value} ×
+                    </span>
+                )}
                 <div className="toolbar-spacer" />
                 <button
                     className={`toolbar-icon-btn ${autoReload ? 'active-reload' : ''}`}
@@ -529,6 +1044,16 @@ export function AccountList({
                         <Plus size={16} />
                     </button>
                 )}
+                <button className="toolbar-icon-btn" onClick={openTrash} title="回收站">
+                    <Trash2 size={16} />
+                </button>
+                <button
+                    className="toolbar-icon-btn"
+                    onClick={() => setShare({ format: 'markdown', emails: 'hidden', includeNotes: false, text: '' })}
+                    title="分享额度快照（选中账号时只分享选中的）"
+                >
+                    <Share2 size={16} />
+                </button>
                 {onRefreshUsage && (
                     <button
                         className="toolbar-icon-btn toolbar-icon-btn-accent"
@@ -545,13 +1070,14 @@ export function AccountList({
                     // 改成并发上限 6 的滑动窗口：快账号先回，慢账号自然排队，
                     // 既不雷霆万钧也不串行。
                     const CONCURRENCY = 6;
-                    const ids = filteredAccounts.map(a => a.id);
+                    // 归档的号不参与批量刷新（后端也会拒绝）
+                    const ids = filteredAccounts.filter(a => !a.archived).map(a => a.id);
                     setIsRefreshingAll(true);
                     let cursor = 0;
                     const worker = async () => {
                         while (cursor < ids.length) {
                             const i = cursor++;
-                            await handleRefreshOne(ids[i]);
+                            await handleRefreshOne(ids[i], 'bulk_refresh');
                         }
                     };
                     const workers = Array.from({ length: Math.min(CONCURRENCY, ids.length) }, worker);
@@ -561,6 +1087,17 @@ export function AccountList({
                 </button>
             </div>
 
+            {duplicates.map(group => (
+                <div key={group.join(',')} className="duplicate-banner">
+                    <span>
+                        重复的登录：{group.map(id => accounts.find(a => a.id === id)?.name ?? id).join('、')}
+                    </span>
+                    <button className="btn btn-ghost" onClick={() => handleMergeGroup(group)} title="保留第一个，其余合并进去（备注、标签合并，被合并的进回收站）">
+                        合并
+                    </button>
+                </div>
+            ))}
+
             <div className="account-table-scroll">
                 <div className="account-table-header">
                     <div className="col-checkbox">
@@ -574,7 +1111,7 @@ export function AccountList({
                 </div>
 
                 <div className="account-table-body">
-                    {filteredAccounts.map(acc => {
+                    {filteredAccounts.map((acc, index) => {
                         const usage = usageMap[acc.id];
                         const status = getStatusInfo(acc);
                         const err = acc.keepalive?.last_error;
@@ -585,13 +1122,44 @@ export function AccountList({
                         const isCurrent = acc.id === currentId;
                         const isRefreshing = refreshingIds.has(acc.id);
 
+                        const firstArchived = !!acc.archived && !filteredAccounts[index - 1]?.archived;
+
                         return (
-                            <div key={acc.id} className={`account-row ${isCurrent ? 'current' : ''} ${selectedIds.has(acc.id) ? 'selected' : ''} ${isBanned ? 'banned' : isLoggedOut ? 'logged-out' : isInvalid ? 'expired' : ''}`}>
+                            <Fragment key={acc.id}>
+                            {firstArchived && <div className="account-section-header">已归档</div>}
+                            <div className={`account-row ${isCurrent ? 'current' : ''} ${selectedIds.has(acc.id) ? 'selected' : ''} ${acc.archived ? 'archived' : ''} ${isBanned ? 'banned' : isLoggedOut ? 'logged-out' : isInvalid ? 'expired' : ''}`}>
                                 <div className="col-checkbox">
                                     <input type="checkbox" className="custom-checkbox" checked={selectedIds.has(acc.id)} onChange={() => { const s = new Set(selectedIds); s.has(acc.id) ? s.delete(acc.id) : s.add(acc.id); setSelectedIds(s); }} />
                                 </div>
                                 <div className="col-drag"><span className="drag-handle">⋮⋮</span></div>
                                 <div className="col-email" title="点击复制账号">
+                                    <label
+                                        className={`color-dot ${acc.color ? '' : 'empty'}`}
+                                        style={acc.color ? { background: acc.color } : undefined}
+                                        title={acc.color ? `颜色标签 ${acc.color}（右键清除）` : '设置颜色标签'}
+                                        onClick={e => e.stopPropagation()}
+                                        onContextMenu={e => {
+                                            if (!acc.color) return;
+                                            e.preventDefault();
+                                            handleSetColor(acc.id, null);
+                                        }}
+                                    >
+                                        <input
+                                            type="color"
+                                            value={acc.color ?? '#55acee'}
+                                            onChange={e => handleSetColor(acc.id, e.target.value)}
+                                        />
+                                    </label>
+                                    <span
+                                        className={`avatar-emoji ${acc.avatar_emoji ? '' : 'empty'}`}
+                                        title="设置头像和颜色"
+                                        onClick={e => {
+                                            e.stopPropagation();
+                                            setAppearanceEditor({ id: acc.id, name: acc.name, color: acc.color ?? '', emoji: acc.avatar_emoji ?? '' });
+                                        }}
+                                    >
+                                        {acc.avatar_emoji ?? '☺'}
+                                    </span>
                                     {(() => {
                                         const isRelay = effectiveKind(acc) === 'relay';
                                         const isMiMoRelay = [
@@ -620,7 +1188,9 @@ export function AccountList({
                                             <span
                                                 className={isRelay ? 'email-text relay-name-link' : 'email-text'}
                                                 onClick={onNameClick}
-                                                title={isRelay && link ? `点击打开 ${link}` : undefined}
+                                                title={isRelay && link
+                                                    ? `点击打开 ${link}`
+                                                    : acc.email && acc.email !== acc.name ? `登录邮箱：${acc.email}` : undefined}
                                             >
                                                 {acc.name}
                                             </span>
@@ -641,16 +1211,81 @@ export function AccountList({
                                             >📱 手机锚</span>
                                         )}
                                         {isBanned ? <span className="badge banned" title="该账号已被 OpenAI 封禁">封号</span> : isLoggedOut ? <span className="badge logged-out" title="您已登出或登录了其他账号，请重新登录">已登出</span> : isInvalid && <span className="badge expired" title="该账号 Token 已过期或失效">过期</span>}
-                                        {usage?.plan_type && <span className="badge plan">{usage.plan_type.toUpperCase()}</span>}
+                                        {usage?.plan_type && <span className="badge plan">{usage.display_plan || usage.plan_type.toUpperCase()}</span>}
+                                        {acc.last_plan_change && Date.now() - new Date(acc.last_plan_change.changed_at).getTime() < PLAN_CHANGE_BADGE_MS && (
+                                            <span
+                                                className="badge plan-changed"
+                                                title={`${formatDate(acc.last_plan_change.changed_at)} 套餐由 ${acc.last_plan_change.old_plan} 变为 ${acc.last_plan_change.new_plan}`}
+                                            >套餐变更</span>
+                                        )}
+                                        {(acc.tags ?? []).map(t => (
+                                            <span key={t} className="badge account-tag" onClick={() => setTagFilter(t)} title="按此标签过滤">
+                                                {t}
+                                            </span>
+                                        ))}
+                                        {Object.entries(acc.metadata ?? {}).map(([k, v]) => (
+                                            <span key={`meta-${k}`} className="badge account-meta" onClick={() => setMetaFilter({ key: k, value: v })} title="按此键值过滤">
+                                                {k}: {v}
+                                            </span>
+                                        ))}
+                                        {acc.codex_profile && (
+                                            <span className="badge codex-profile" title="切到此号时写入 ~/.codex/config.toml 的 profile（需在设置里开启）">
+                                                profile: {acc.codex_profile}
+                                            </span>
+                                        )}
+                                        {(() => {
+                                            const verdict = compatibility?.[acc.id];
+                                            if (verdict?.status !== 'needs_field' && verdict?.status !== 'unsupported_field') return null;
+                                            return (
+                                                <span className="badge codex-incompatible" title={`${verdict.status === 'needs_field' ? '本机 Codex 版本要求' : '本机 Codex 版本不支持'} ${verdict.field}：${verdict.note}`}>
+                                                    Codex 不兼容
+                                                </span>
+                                            );
+                                        })()}
+                                        {(() => {
+                                            const token = tokenStatuses?.[acc.id];
+                                            if (isBanned || isLoggedOut || isInvalid) return null;
+                                            if (token?.token_state !== 'expired' && token?.token_state !== 'expiring_soon') return null;
+                                            const detail = [
+                                                token.expires_at && `access_token ${formatDate(token.expires_at)} 过期`,
+                                                token.last_refresh && `上次刷新 ${formatDate(token.last_refresh)}`,
+                                            ].filter(Boolean).join('；');
+                                            return token.token_state === 'expired' ? (
+                                                <span className="badge token-expired" title={`${detail}；Codex 下次请求时会自动刷新`}>AT 已过期</span>
+                                            ) : (
+                                                <span className="badge token-expiring" title={detail}>AT 快过期</span>
+                                            );
+                                        })()}
+                                        {integrityIssues?.[acc.id]?.length ? (
+                                            <span className="badge integrity-broken" title={integrityIssues[acc.id].map(describeIntegrityIssue).join('\n')}>
+                                                身份不符
+                                            </span>
+                                        ) : null}
+                                        {usage?.codex_available === false && (
+                                            <span className="badge no-codex" title="该套餐不含 Codex：不参与后台轮询、自动选号和故障切换；手动刷新查到额度后自动恢复">无 Codex</span>
+                                        )}
+                                        {effectiveKind(acc) !== 'relay' && acc.cached_quota && quotaExhaustions(acc.cached_quota).map(e => (
+                                            e.window === 'weekly' ? (
+                                                <span key={e.window} className="badge weekly-exhausted" title={`周额度已用完，不参与自动选号${e.reset_at ? `；${new Date(e.reset_at * 1000).toLocaleString()} 重置` : ''}`}>
+                                                    周额度用完
+                                                </span>
+                                            ) : (
+                                                <span key={e.window} className="badge five-hour-exhausted" title={`5H 额度已用完${e.reset_at ? `，${new Date(e.reset_at * 1000).toLocaleTimeString()} 重置；快重置时仍可作为后备被自动选中` : ''}`}>
+                                                    5H 用完
+                                                </span>
+                                            )
+                                        ))}
                                     </div>
                                 </div>
                                 <div className="col-quota-merged">
                                     {effectiveKind(acc) === 'relay' ? (
                                         <RelayQuotaItem account={acc} cache={relayUsageMap[acc.id]} />
+                                    ) : usage?.codex_available === false ? (
+                                        <span className="quota-empty">套餐不含 Codex</span>
                                     ) : usage ? (
                                         <div className="quota-grid">
-                                            <QuotaItem label={usage.five_hour_label} percentage={usage.five_hour_left} reset={usage.five_hour_reset} resetAt={usage.five_hour_reset_at} />
-                                            <QuotaItem label={usage.weekly_label} percentage={usage.weekly_left} reset={usage.weekly_reset} resetAt={usage.weekly_reset_at} />
+                                            <QuotaItem label={usage.five_hour_label} percentage={usage.five_hour_left} reset={usage.five_hour_reset} resetAt={usage.five_hour_reset_at} counts={usage.five_hour_counts} />
+                                            <QuotaItem label={usage.weekly_label} percentage={usage.weekly_left} reset={usage.weekly_reset} resetAt={usage.weekly_reset_at} counts={usage.weekly_counts} />
                                         </div>
                                     ) : <span className="quota-empty">未获取数据</span>}
                                 </div>
@@ -661,7 +1296,12 @@ export function AccountList({
                                     </div>
                                     <div className="time-item refresh">
                                         <span className="time-label">刷新:</span>
-                                        <span className="time-val">{formatDate(acc.cached_quota?.updated_at)}</span>
+                                        <span className={`time-val ${acc.cached_quota?.suspect ? 'warn' : ''}`} title={quotaSourceTitle(acc.cached_quota)}>{formatDate(acc.cached_quota?.updated_at)}</span>
+                                        {acc.cached_quota?.suspect && <span className="time-val warn">数据可能过期</span>}
+                                    </div>
+                                    <div className="time-item">
+                                        <span className="time-label">切换:</span>
+                                        <span className="time-val" title={acc.last_used ? `上次切到此号 ${formatDate(acc.last_used)}` : undefined}>{acc.switch_count ?? 0} 次</span>
                                     </div>
                                 </div>
                                 <div className="col-actions">
@@ -676,12 +1316,76 @@ export function AccountList({
                                             <UploadCloud size={14} className={pushingIds.has(acc.id) ? 'spinning' : ''} />
                                         </button>
                                     )}
-                                    {!isCurrent && (
+                                    <button
+                                        className={`action-btn pin ${acc.pinned ? 'active' : ''}`}
+                                        onClick={() => handleTogglePin(acc)}
+                                        title={acc.pinned ? '取消置顶' : '置顶'}
+                                    >
+                                        <Pin size={14} fill={acc.pinned ? 'currentColor' : 'none'} />
+                                    </button>
+                                    <button
+                                        className={`action-btn pin ${acc.protected ? 'active' : ''}`}
+                                        onClick={() => handleToggleProtected(acc)}
+                                        title={acc.protected ? '已保护：不能删除、不会被手动同步覆盖。点击取消保护' : '保护：不能删除、不会被手动同步覆盖'}
+                                    >
+                                        <Shield size={14} fill={acc.protected ? 'currentColor' : 'none'} />
+                                    </button>
+                                    <button className="action-btn" onClick={() => handleMoveUp(acc)} title="上移">
+                                        <ArrowUp size={14} />
+                                    </button>
+                                    <button
+                                        className="action-btn profile"
+                                        onClick={() => setTagsEditor({ id: acc.id, name: acc.name, value: (acc.tags ?? []).join(', '), metadata: acc.metadata ?? {}, metaKey: '', metaValue: '' })}
+                                        title="标签与键值备注"
+                                    >
+                                        <Tag size={14} />
+                                    </button>
+                                    {effectiveKind(acc) !== 'relay' && (
+                                        <button
+                                            className="action-btn profile"
+                                            onClick={() => setProfileEditor({ id: acc.id, name: acc.name, value: acc.codex_profile ?? '' })}
+                                            title="Codex profile"
+                                        >
+                                            <Settings2 size={14} />
+                                        </button>
+                                    )}
+                                    {effectiveKind(acc) !== 'relay' && (
+                                        <button
+                                            className="action-btn profile"
+                                            onClick={() => setHeadersEditor({
+                                                id: acc.id,
+                                                name: acc.name,
+                                                value: Object.entries(acc.request_headers ?? {}).map(([k, v]) => `${k}: ${v}`).join('\n'),
+                                            })}
+                                            title="配额请求头"
+                                        >
+                                            <Tags size={14} />
+                                        </button>
+                                    )}
+                                    <button
+                                        className={`action-btn ${acc.archived ? 'active' : ''}`}
+                                        onClick={() => handleArchive(acc)}
+                                        title={acc.archived ? '取消归档' : '归档（不再出现在切换和批量刷新里）'}
+                                    >
+                                        <Archive size={14} />
+                                    </button>
+                                    <button
+                                        className="action-btn"
+                                        onClick={() => setCloneEditor({ id: acc.id, name: acc.name, value: `${acc.name} 备份` })}
+                                        title="复制一份备份（不会自动切过去，副本不做保活）"
+                                    >
+                                        <Copy size={14} />
+                                    </button>
+                                    {!isCurrent && !acc.archived && (
                                         <button className="action-btn switch" onClick={() => onSwitch(acc.id)} disabled={switchingIds.has(acc.id)} title="切换"><ArrowLeftRight size={14} /></button>
                                     )}
-                                    <button className="action-btn delete" onClick={() => setAccountToDelete({ id: acc.id, name: acc.name })} title="删除"><Trash2 size={14} /></button>
+                                    {!isCurrent && !acc.archived && currentId && (
+                                        <button className="action-btn switch" onClick={() => setTempSwitch({ id: acc.id, name: acc.name, minutes: 30 })} title="临时切换（到时自动切回）"><Timer size={14} /></button>
+                                    )}
+                                    <button className="action-btn delete" disabled={acc.protected} onClick={() => setAccountToDelete({ id: acc.id, name: acc.name })} title={acc.protected ? '受保护的账号不能删除' : '删除'}><Trash2 size={14} /></button>
                                 </div>
                             </div>
+                            </Fragment>
                         );
                     })}
                 </div>
@@ -690,6 +1394,21 @@ export function AccountList({
             <div className="account-list-footer">
                 <span>共 {filteredAccounts.length} 个账号</span>
                 {selectedIds.size > 0 && <span className="selected-info">已选 {selectedIds.size} 个</span>}
+                {selectedIds.size === 2 && (
+                    <button className="btn btn-ghost" onClick={() => { const [a, b] = Array.from(selectedIds); handleCompare(a, b, false); }} disabled={comparing}>
+                        对比
+                    </button>
+                )}
+                {selectedIds.size > 0 && onDeleteMany && (
+                    <button className="btn btn-ghost" onClick={() => setBulkDelete({ ids: Array.from(selectedIds), allowCurrent: false })}>
+                        删除所选
+                    </button>
+                )}
+                {selectedIds.size > 0 && (
+                    <button className="btn btn-ghost" onClick={() => setSubsetExport({ ids: Array.from(selectedIds), includeSettings: false })}>
+                        导出所选
+                    </button>
+                )}
                 {pushToast && (
                     <span className={`push-toast ${pushToast.type}`} style={{ marginLeft: 'auto' }}>
                         {pushToast.text}
@@ -700,8 +1419,8 @@ export function AccountList({
             <ConfirmModal
                 isOpen={!!accountToDelete}
                 title="确认删除账号"
-                message={<p>确定要永久删除账号 <strong>{accountToDelete?.name}</strong> 吗？<br /><br />此操作不可恢复，删除后有关该账号的本地授权信息将被清除。</p>}
-                confirmText="彻底删除"
+                message={<p>确定要删除账号 <strong>{accountToDelete?.name}</strong> 吗？<br /><br />账号会移到回收站，可从工具栏的回收站恢复；超过设置里的保留天数后自动彻底清除。</p>}
+                confirmText="删除"
                 onConfirm={() => {
                     if (accountToDelete) {
                         onDelete(accountToDelete.id);
@@ -710,6 +1429,427 @@ export function AccountList({
                 }}
                 onCancel={() => setAccountToDelete(null)}
             />
+            <ConfirmModal
+                isOpen={!!bulkDelete}
+                title="确认批量删除"
+                message={
+                    <div>
+                        <p>确定要删除选中的 <strong>{bulkDelete?.ids.length}</strong> 个账号吗？账号会移到回收站，可整批撤销。受保护的账号会被跳过。</p>
+                        {bulkDelete && currentId && bulkDelete.ids.includes(currentId) && (
+                            <label style={{ display: 'flex', alignItems: 'center', gap: 6, marginTop: 8 }}>
+                                <input
+                                    type="checkbox"
+                                    checked={bulkDelete.allowCurrent}
+                                    onChange={e => setBulkDelete(prev => prev ? { ...prev, allowCurrent: e.target.checked } : prev)}
+                                />
+                                同时删除当前账号（不勾选则跳过）
+                            </label>
+                        )}
+                    </div>
+                }
+                confirmText="删除"
+                onConfirm={handleBulkDelete}
+                onCancel={() => setBulkDelete(null)}
+            />
+            <ConfirmModal
+                isOpen={!!subsetExport}
+                title="导出选中的账号"
+                message={
+                    <div>
+                        <p>将导出选中的 <strong>{subsetExport?.ids.length}</strong> 个账号，文件包含它们的 refresh_token，请通过安全渠道发送。</p>
+                        <label style={{ display: 'flex', alignItems: 'center', gap: 6, marginTop: 8 }}>
+                            <input
+                                type="checkbox"
+                                checked={subsetExport?.includeSettings ?? false}
+                                onChange={e => setSubsetExport(prev => prev ? { ...prev, includeSettings: e.target.checked } : prev)}
+                            />
+                            同时导出本机设置（不勾选时对方导入后保留自己的设置）
+                        </label>
+                    </div>
+                }
+                confirmText="导出"
+                onConfirm={handleExportSelected}
+                onCancel={() => setSubsetExport(null)}
+            />
+            {profileEditor && (
+                <div className="modal-overlay" onClick={() => setProfileEditor(null)}>
+                    <div className="modal-content" onClick={e => e.stopPropagation()}>
+                        <div className="modal-header">
+                            <div className="header-top">
+                                <h2>Codex profile</h2>
+                                <button className="close-btn" onClick={() => setProfileEditor(null)}>
+                                    ×
+                                </button>
+                            </div>
+                        </div>
+                        <div className="modal-body">
+                            <p className="modal-tip" style={{ marginBottom: 12 }}>
+                                账号：{profileEditor.name}。填 <code>~/.codex/config.toml</code> 里 <code>[profiles.名字]</code> 的名字，切到此号时写进顶层 <code>profile</code>；留空则不改。需在设置里开启"切号时应用账号的 Codex profile"。
+                            </p>
+                            <input
+                                type="text"
+                                value={profileEditor.value}
+                                onChange={e => setProfileEditor(prev => prev ? { ...prev, value: e.target.value } : prev)}
+                                placeholder="work"
+                                style={{ fontFamily: 'ui-monospace, Menlo, monospace', fontSize: 12, width: '100%' }}
+                            />
+                        </div>
+                        <div className="modal-footer">
+                            <button type="button" className="btn btn-ghost" onClick={() => setProfileEditor(null)}>
+                                取消
+                            </button>
+                            <button type="button" className="btn btn-primary" onClick={handleSaveCodexProfile}>
+                                保存
+                            </button>
+                        </div>
+                    </div>
+                </div>
+            )}
+            {trash && (
+                <div className="modal-overlay" onClick={() => setTrash(null)}>
+                    <div className="modal-content" onClick={e => e.stopPropagation()}>
+                        <div className="modal-header">
+                            <div className="header-top">
+                                <h2>回收站</h2>
+                                <button className="close-btn" onClick={() => setTrash(null)}>
+                                    ×
+                                </button>
+                            </div>
+                        </div>
+                        <div className="modal-body">
+                            {trash.length === 0 ? (
+                                <p className="modal-tip">回收站是空的</p>
+                            ) : (
+                                <div className="trash-list">
+                                    {trash.map(t => (
+                                        <div key={t.account.id} className="trash-item">
+                                            <span className="trash-name">{t.account.name}</span>
+                                            <span className="trash-date">删除于 {formatDate(t.deleted_at)}</span>
+                                            <button type="button" className="btn btn-ghost" onClick={() => handleRestore(t.account.id)}>
+                                                恢复
+                                            </button>
+                                        </div>
+                                    ))}
+                                </div>
+                            )}
+                        </div>
+                        <div className="modal-footer">
+                            <button type="button" className="btn btn-ghost" onClick={() => setTrash(null)}>
+                                关闭
+                            </button>
+                            <button type="button" className="btn btn-danger" onClick={handleEmptyTrash} disabled={trash.length === 0}>
+                                清空回收站
+                            </button>
+                        </div>
+                    </div>
+                </div>
+            )}
+            {cloneEditor && (
+                <div className="modal-overlay" onClick={() => setCloneEditor(null)}>
+                    <div className="modal-content" onClick={e => e.stopPropagation()}>
+                        <div className="modal-header">
+                            <div className="header-top">
+                                <h2>复制账号</h2>
+                                <button className="close-btn" onClick={() => setCloneEditor(null)}>
+                                    ×
+                                </button>
+                            </div>
+                        </div>
+                        <div className="modal-body">
+                            <p className="modal-tip" style={{ marginBottom: 12 }}>
+                                复制 {cloneEditor.name} 的登录凭证和备注，留作回滚用的备份。副本不会自动成为当前账号，也不做非活跃保活。
+                            </p>
+                            <input
+                                type="text"
+                                value={cloneEditor.value}
+                                onChange={e => setCloneEditor(prev => prev ? { ...prev, value: e.target.value } : prev)}
+                                placeholder="副本名称"
+                                style={{ fontSize: 12, width: '100%' }}
+                            />
+                        </div>
+                        <div className="modal-footer">
+                            <button type="button" className="btn btn-ghost" onClick={() => setCloneEditor(null)}>
+                                取消
+                            </button>
+                            <button type="button" className="btn btn-primary" onClick={handleClone} disabled={!cloneEditor.value.trim()}>
+                                复制
+                            </button>
+                        </div>
+                    </div>
+                </div>
+            )}
+            {appearanceEditor && (
+                <div className="modal-overlay" onClick={() => setAppearanceEditor(null)}>
+                    <div className="modal-content" onClick={e => e.stopPropagation()}>
+                        <div className="modal-header">
+                            <div className="header-top">
+                                <h2>外观</h2>
+                                <button className="close-btn" onClick={() => setAppearanceEditor(null)}>
+                                    ×
+                                </button>
+                            </div>
+                        </div>
+                        <div className="modal-body">
+                            <p className="modal-tip" style={{ marginBottom: 12 }}>
+                                账号：{appearanceEditor.name}。头像 emoji 显示在列表和托盘里的名字前；颜色为 #RRGGBB。留空清除。
+                            </p>
+                            <input
+                                type="text"
+                                value={appearanceEditor.emoji}
+                                onChange={e => setAppearanceEditor(prev => prev ? { ...prev, emoji: e.target.value } : prev)}
+                                placeholder="🦊"
+                                style={{ fontSize: 12, width: '100%', marginBottom: 8 }}
+                            />
+                            <input
+                                type="text"
+                                value={appearanceEditor.color}
+                                onChange={e => setAppearanceEditor(prev => prev ? { ...prev, color: e.target.value } : prev)}
+                                placeholder="#55acee"
+                                style={{ fontSize: 12, width: '100%' }}
+                            />
+                        </div>
+                        <div className="modal-footer">
+                            <button type="button" className="btn btn-ghost" onClick={() => setAppearanceEditor(null)}>
+                                取消
+                            </button>
+                            <button type="button" className="btn btn-primary" onClick={handleSaveAppearance}>
+                                保存
+                            </button>
+                        </div>
+                    </div>
+                </div>
+            )}
+            {tagsEditor && (
+                <div className="modal-overlay" onClick={() => setTagsEditor(null)}>
+                    <div className="modal-content" onClick={e => e.stopPropagation()}>
+                        <div className="modal-header">
+                            <div className="header-top">
+                                <h2>标签与键值备注</h2>
+                                <button className="close-btn" onClick={() => setTagsEditor(null)}>
+                                    ×
+                                </button>
+                            </div>
+                        </div>
+                        <div className="modal-body">
+                            <p className="modal-tip" style={{ marginBottom: 12 }}>
+                                账号：{tagsEditor.name}。多个标签用逗号分隔，不区分大小写；留空清除。
+                            </p>
+                            <input
+                                type="text"
+                                value={tagsEditor.value}
+                                onChange={e => setTagsEditor(prev => prev ? { ...prev, value: e.target.value } : prev)}
+                                placeholder="工作, 团队共用"
+                                style={{ fontSize: 12, width: '100%' }}
+                            />
+                            <p className="modal-tip" style={{ margin: '16px 0 8px' }}>
+                                键值备注（如 team: infra），改动即时保存；键不区分大小写，最多 32 条。
+                            </p>
+                            {Object.entries(tagsEditor.metadata).map(([k, v]) => (
+                                <div key={k} style={{ display: 'flex', alignItems: 'center', gap: 8, marginBottom: 6, fontSize: 12 }}>
+                                    <span className="badge account-meta">{k}: {v}</span>
+                                    <button type="button" className="btn btn-ghost" onClick={() => handleDeleteMetadata(k)}>删除</button>
+                                </div>
+                            ))}
+                            <div style={{ display: 'flex', gap: 8 }}>
+                                <input
+                                    type="text"
+                                    value={tagsEditor.metaKey}
+                                    onChange={e => setTagsEditor(prev => prev ? { ...prev, metaKey: e.target.value } : prev)}
+                                    placeholder="键，如 owner"
+                                    style={{ fontSize: 12, flex: 1 }}
+                                />
+                                <input
+                                    type="text"
+                                    value={tagsEditor.metaValue}
+                                    onChange={e => setTagsEditor(prev => prev ? { ...prev, metaValue: e.target.value } : prev)}
+                                    placeholder="值，如 alice"
+                                    style={{ fontSize: 12, flex: 2 }}
+                                />
+                                <button
+                                    type="button"
+                                    className="btn btn-ghost"
+                                    disabled={!tagsEditor.metaKey.trim() || !tagsEditor.metaValue.trim()}
+                                    onClick={handleSetMetadata}
+                                >
+                                    添加
+                                </button>
+                            </div>
+                        </div>
+                        <div className="modal-footer">
+                            <button type="button" className="btn btn-ghost" onClick={() => setTagsEditor(null)}>
+                                取消
+                            </button>
+                            <button type="button" className="btn btn-primary" onClick={handleSaveTags}>
+                                保存
+                            </button>
+                        </div>
+                    </div>
+                </div>
+            )}
+            {comparison && (
+                <div className="modal-overlay" onClick={() => setComparison(null)}>
+                    <div className="modal-content" onClick={e => e.stopPropagation()}>
+                        <div className="modal-header">
+                            <div className="header-top">
+                                <h2>账号对比</h2>
+                                <button className="close-btn" onClick={() => setComparison(null)}>
+                                    ×
+                                </button>
+                            </div>
+                        </div>
+                        <div className="modal-body">
+                            <p className="modal-tip" style={{ marginBottom: 12 }}>
+                                {comparison.same_identity ? '两个账号是同一个身份（account_id / 用户一致）。' : '两个账号是不同的身份。'}
+                                高亮的一侧在该项更好。
+                            </p>
+                            {comparison.refresh_errors && comparison.refresh_errors.length > 0 && (
+                                <p className="modal-tip" style={{ marginBottom: 12, color: 'var(--danger-color)' }}>
+                                    刷新失败，以下按旧缓存对比：{comparison.refresh_errors.join('；')}
+                                </p>
+                            )}
+                            <table className="compare-table">
+                                <tbody>
+                                    {comparisonRows(comparison).map(([label, field, render]) => (
+                                        <tr key={label}>
+                                            <th>{label}</th>
+                                            <td className={field.winner === 'a' ? 'compare-win' : ''}>{render(field.a)}</td>
+                                            <td className={field.winner === 'b' ? 'compare-win' : ''}>{render(field.b)}</td>
+                                        </tr>
+                                    ))}
+                                </tbody>
+                            </table>
+                        </div>
+                        <div className="modal-footer">
+                            <button type="button" className="btn btn-ghost" onClick={() => setComparison(null)}>
+                                关闭
+                            </button>
+                            <button type="button" className="btn btn-primary" onClick={() => handleCompare(comparison.id_a, comparison.id_b, true)} disabled={comparing}>
+                                {comparing ? '刷新中…' : '刷新额度后对比'}
+                            </button>
+                        </div>
+                    </div>
+                </div>
+            )}
+            {share && (
+                <div className="modal-overlay" onClick={() => setShare(null)}>
+                    <div className="modal-content" onClick={e => e.stopPropagation()}>
+                        <div className="modal-header">
+                            <div className="header-top">
+                                <h2>分享额度快照</h2>
+                                <button className="close-btn" onClick={() => setShare(null)}>
+                                    ×
+                                </button>
+                            </div>
+                        </div>
+                        <div className="modal-body">
+                            <p className="modal-tip" style={{ marginBottom: 12 }}>
+                                {selectedIds.size > 0 ? `已选 ${selectedIds.size} 个账号` : '全部订阅账号'}，按剩余额度排序。不含账号 id、token 和其它元数据。
+                            </p>
+                            <div style={{ display: 'flex', gap: 12, marginBottom: 12, alignItems: 'center' }}>
+                                <select value={share.format} onChange={e => setShare({ ...share, format: e.target.value as typeof share.format })}>
+                                    <option value="markdown">Markdown 表格</option>
+                                    <option value="plain_text">纯文本</option>
+                                    <option value="json">JSON</option>
+                                </select>
+                                <select value={share.emails} onChange={e => setShare({ ...share, emails: e.target.value as typeof share.emails })}>
+                                    <option value="hidden">不含邮箱</option>
+                                    <option value="masked">邮箱打码</option>
+                                    <option value="full">完整邮箱</option>
+                                </select>
+                                <label>
+                                    <input type="checkbox" checked={share.includeNotes} onChange={e => setShare({ ...share, includeNotes: e.target.checked })} />
+                                    含备注
+                                </label>
+                            </div>
+                            <textarea
+                                readOnly
+                                value={share.text}
+                                rows={10}
+                                style={{ fontFamily: 'ui-monospace, Menlo, monospace', fontSize: 12, width: '100%' }}
+                            />
+                        </div>
+                        <div className="modal-footer">
+                            <button type="button" className="btn btn-ghost" onClick={() => setShare(null)}>
+                                关闭
+                            </button>
+                            <button type="button" className="btn btn-primary" onClick={handleCopyShare} disabled={!share.text}>
+                                复制
+                            </button>
+                        </div>
+                    </div>
+                </div>
+            )}
+            {tempSwitch && (
+                <div className="modal-overlay" onClick={() => setTempSwitch(null)}>
+                    <div className="modal-content" onClick={e => e.stopPropagation()}>
+                        <div className="modal-header">
+                            <div className="header-top">
+                                <h2>临时切换</h2>
+                                <button className="close-btn" onClick={() => setTempSwitch(null)}>
+                                    ×
+                                </button>
+                            </div>
+                        </div>
+                        <div className="modal-body">
+                            <p className="modal-tip" style={{ marginBottom: 12 }}>
+                                切到 {tempSwitch.name}，到时自动切回当前账号；到点前 5 分钟提醒，期间手动切到别的账号则不再切回。
+                            </p>
+                            <label>
+                                使用时长（分钟）
+                                <input
+                                    type="number"
+                                    min={1}
+                                    max={1440}
+                                    value={tempSwitch.minutes}
+                                    onChange={e => setTempSwitch(prev => prev ? { ...prev, minutes: Number(e.target.value) } : prev)}
+                                />
+                            </label>
+                        </div>
+                        <div className="modal-footer">
+                            <button type="button" className="btn btn-ghost" onClick={() => setTempSwitch(null)}>
+                                取消
+                            </button>
+                            <button type="button" className="btn btn-primary" onClick={handleTemporarySwitch}>
+                                切换
+                            </button>
+                        </div>
+                    </div>
+                </div>
+            )}
+            {headersEditor && (
+                <div className="modal-overlay" onClick={() => setHeadersEditor(null)}>
+                    <div className="modal-content" onClick={e => e.stopPropagation()}>
+                        <div className="modal-header">
+                            <div className="header-top">
+                                <h2>配额请求头</h2>
+                                <button className="close-btn" onClick={() => setHeadersEditor(null)}>
+                                    ×
+                                </button>
+                            </div>
+                        </div>
+                        <div className="modal-body">
+                            <p className="modal-tip" style={{ marginBottom: 12 }}>
+                                账号：{headersEditor.name}。查询配额时附加的请求头，每行一个 <code>Name: value</code>，覆盖默认值和设置里的全局 User-Agent。<code>Authorization</code>、<code>ChatGPT-Account-Id</code> 不能自定义。留空则清除。
+                            </p>
+                            <textarea
+                                value={headersEditor.value}
+                                onChange={e => setHeadersEditor(prev => prev ? { ...prev, value: e.target.value } : prev)}
+                                placeholder="User-Agent: my-allowed-client/1.0"
+                                rows={6}
+                                style={{ fontFamily: 'ui-monospace, Menlo, monospace', fontSize: 12, width: '100%' }}
+                            />
+                        </div>
+                        <div className="modal-footer">
+                            <button type="button" className="btn btn-ghost" onClick={() => setHeadersEditor(null)}>
+                                取消
+                            </button>
+                            <button type="button" className="btn btn-primary" onClick={handleSaveRequestHeaders}>
+                                保存
+                            </button>
+                        </div>
+                    </div>
+                </div>
+            )}
             {cookieEditor && (
                 <div className="modal-overlay" onClick={() => !savingCookie && setCookieEditor(null)}>
                     <div className="modal-content" onClick={e => e.stopPropagation()}>
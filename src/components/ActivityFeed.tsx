@@ -0,0 +1,125 @@
+import { useState, useEffect } from 'react';
+import { invoke } from '../ipc';
+
+type ActivityCategory = 'switch' | 'quota' | 'conflict' | 'import' | 'error';
+
+interface ActivityEntry {
+    timestamp: string;
+    category: ActivityCategory;
+    account_id: string | null;
+    summary_key: string;
+    params: Record<string, string>;
+}
+
+const PAGE_SIZE = 50;
+
+const CATEGORY_LABELS: Record<ActivityCategory, string> = {
+    switch: '切号',
+    quota: '额度',
+    conflict: '冲突',
+    import: '导入',
+    error: '错误',
+};
+
+// summary_key → 文案；后端只给 key + params，新增 key 时这里补一条
+const SUMMARY_TEXT: Record<string, (p: Record<string, string>) => string> = {
+    'switch.changed': p => `当前账号切换为 ${p.account_name}`,
+    'switch.cleared': () => '当前没有使用中的账号',
+    'switch.recovery_finished': p => `上次切号到 ${p.account_name} 时中断，已补完`,
+    'switch.recovery_rolled_back': p => `上次切号到 ${p.account_name} 时中断，已退回原账号`,
+    'switch.recovery_stale': p => `发现一条过期的切号记录（${p.account_name}），已清除`,
+    'quota.round_finished': p =>
+        Number(p.failed) > 0
+            ? `后台刷新额度：成功 ${p.refreshed} 个，失败 ${p.failed} 个`
+            : `后台刷新额度：成功 ${p.refreshed} 个`,
+    'quota.plan_changed': p => `${p.account_name} 套餐变更：${p.old_plan} → ${p.new_plan}`,
+    'quota.plan_downgraded': p => `${p.account_name} 套餐降级：${p.old_plan} → ${p.new_plan}`,
+    'conflict.sync': p => `${p.account_name} 在 IDE 里有未同步的 token 更新`,
+    'import.finished': p =>
+        `导入完成：新增 ${p.added}，覆盖 ${p.updated}，跳过 ${p.skipped}，失败 ${p.failed}`,
+    'import.cancelled': p =>
+        `导入已取消：新增 ${p.added}，覆盖 ${p.updated}，跳过 ${p.skipped}，失败 ${p.failed}`,
+    'error.account_banned': p => `${p.account_name} 被判定封号`,
+    'error.refresh_banned': p => `${p.account_name} 的 refresh token 被判定重复使用，需要重新登录`,
+    'error.all_exhausted': p => p.message,
+};
+
+function summarize(entry: ActivityEntry): string {
+    const render = SUMMARY_TEXT[entry.summary_key];
+    return render ? render(entry.params) : entry.summary_key;
+}
+
+function formatTime(ts: string): string {
+    const d = new Date(ts);
+    return d.toLocaleString('zh-CN', { month: 'numeric', day: 'numeric', hour: '2-digit', minute: '2-digit' });
+}
+
+export function ActivityFeed() {
+    const [category, setCategory] = useState<ActivityCategory | null>(null);
+    const [entries, setEntries] = useState<ActivityEntry[]>([]);
+    const [hasMore, setHasMore] = useState(false);
+
+    const fetchPage = async (before: string | null) => {
+        try {
+            const page = await invoke<ActivityEntry[]>('get_activity_feed', {
+                category,
+                limit: PAGE_SIZE,
+                before,
+            });
+            setEntries(prev => (before ? [...prev, ...page] : page));
+            setHasMore(page.length === PAGE_SIZE);
+        } catch (e) {
+            console.error('加载活动记录失败:', e);
+        }
+    };
+
+    useEffect(() => { fetchPage(null); }, [category]);
+
+    return (
+        <div className="stats-section">
+            <div className="stats-header">
+                <h3>活动记录</h3>
+                <div className="time-range-btns">
+                    <button
+                        className={`range-btn ${category === null ? 'active' : ''}`}
+                        onClick={() => setCategory(null)}
+                    >
+                        全部
+                    </button>
+                    {(Object.keys(CATEGORY_LABELS) as ActivityCategory[]).map(c => (
+                        <button
+                            key={c}
+                            className={`range-btn ${category === c ? 'active' : ''}`}
+                            onClick={() => setCategory(c)}
+                        >
+                            {CATEGORY_LABELS[c]}
+                        </button>
+                    ))}
+                </div>
+            </div>
+            <div className="switch-log-table">
+                {entries.length === 0 ? (
+                    <div className="log-empty">暂无活动记录</div>
+                ) : (
+                    entries.map((e, i) => (
+                        <div key={`${e.timestamp}-${i}`} className="activity-row">
+                            <span className="log-time">{formatTime(e.timestamp)}</span>
+                            <span className={`log-reason activity-category ${e.category}`}>
+                                {CATEGORY_LABELS[e.category]}
+                            </span>
+                            <span className="activity-summary">{summarize(e)}</span>
+                        </div>
+                    ))
+                )}
+                {hasMore && (
+                    <button
+                        className="range-btn activity-more"
+                        onClick={() => fetchPage(entries[entries.length - 1].timestamp)}
+                    >
+                        加载更多
+                    </button>
+                )}
+            </div>
+        </div>
+    );
+}
@@ -1,9 +1,9 @@
 import { useState, useEffect } from 'react';
 import { listen, emit } from '@tauri-apps/api/event';
-import { invoke } from '@tauri-apps/api/core';
+import { invoke } from '../ipc';
 import { open as openDialog } from '@tauri-apps/plugin-dialog';
 import { readFile } from '@tauri-apps/plugin-fs';
-import { useAccounts } from '../hooks/useAccounts';
+import { useAccounts, AuthProfile, DEFAULT_AUTH_PROFILE_ID } from '../hooks/useAccounts';
 import { RELAY_PRESETS } from '../data/relay_presets';
 import './AddAccountModal.css';
 
@@ -20,11 +20,12 @@ function statusIcon(status: string): string {
 interface AddAccountModalProps {
     isOpen: boolean;
     onClose: () => void;
-    onAdd: (name: string, notes?: string) => Promise<void>;
+    /** 返回 true 表示更新了已有的同一登录，没有新增 */
+    onAdd: (name: string, notes?: string) => Promise<boolean>;
     onSuccess?: () => void;  // 添加成功后的回调，用于刷新父组件列表
 }
 
-type TabType = 'official' | 'openai' | 'otp_batch' | 'bulk' | 'relay' | 'session';
+type TabType = 'official' | 'openai' | 'oauth_batch' | 'otp_batch' | 'bulk' | 'relay' | 'session';
 
 interface ImportedSessionInfo {
     email: string | null;
@@ -62,6 +63,26 @@ interface BulkImportResult {
     fatal: string[];
 }
 
+type DirImportOutcome =
+    | { status: 'imported' | 'updated'; account_id: string; name: string }
+    | { status: 'skipped'; reason: string }
+    | { status: 'failed'; error: string };
+
+interface DirImportReport {
+    directory: string;
+    files: ({ file: string } & DirImportOutcome)[];
+    exports: string[];
+    imported: number;
+    updated: number;
+}
+
+const DIR_IMPORT_STATUS_LABEL: Record<DirImportOutcome['status'], string> = {
+    imported: '新增',
+    updated: '已更新',
+    skipped: '跳过',
+    failed: '失败',
+};
+
 const BULK_FORMAT_LABEL: Record<string, string> = {
     cpa: 'cpa（codex_credentials）',
     sub2api: 'sub2api',
@@ -109,6 +130,63 @@ interface OtpBatchProgress {
 }
 
 /** 邮箱域名 → 默认 provider 的启发规则（没显式 token 时用） */
+/** 后端 external_logins::DiscoveredLogin（只取界面用到的字段） */
+interface DiscoveredLogin {
+    source_tool: string;
+    path: string;
+    identity: { email: string | null; plan: string | null };
+    matched_account: { id: string; name: string } | null;
+    token_fresher_than_stored: boolean | null;
+}
+
+interface DiscoveryReport {
+    logins: DiscoveredLogin[];
+    errors: { source_tool: string; path: string; error: string }[];
+}
+
+/** 后端 bulk_login::BulkLoginProgress */
+interface BulkLoginProgress {
+    completed: number;
+    failed: number;
+    total: number;
+    last_account: string | null;
+    next_index: number | null;
+    next_hint: string | null;
+    paused: boolean;
+}
+
+interface BulkLoginStep {
+    index: number;
+    hint: string | null;
+    status: 'succeeded' | 'failed' | 'timed_out' | 'cancelled';
+    account?: string;
+    error?: string;
+}
+
+interface BulkLoginSummary {
+    total: number;
+    succeeded: number;
+    failed: number;
+    not_attempted: number;
+    cancelled: boolean;
+    steps: BulkLoginStep[];
+}
+
+/** 每行一个邮箱（作为 login_hint 预填），或一个纯数字 N 表示再排 N 个不带提示的登录 */
+function parseBulkLoginEntries(raw: string): (string | null)[] {
+    const entries: (string | null)[] = [];
+    for (const line of raw.split('\n')) {
+        const t = line.trim();
+        if (!t) continue;
+        if (/^\d+$/.test(t)) {
+            entries.push(...Array<null>(Math.min(Number(t), 50)).fill(null));
+        } else {
+            entries.push(t);
+        }
+    }
+    return entries;
+}
+
 function pickProviderByDomain(email: string): OtpProvider {
     const domain = email.split('@')[1]?.toLowerCase() || '';
     // usmail.my.id 服务的域名
@@ -179,21 +257,38 @@ export function AddAccountModal({ isOpen, onClose, onAdd, onSuccess }: AddAccoun
     const [activeTab, setActiveTab] = useState<TabType>('openai');
     const [name, setName] = useState('');
     const [notes, setNotes] = useState('');
+    // 同事发来的 auth.json 原文；填了就从这里导入，不读本机登录
+    const [pastedAuth, setPastedAuth] = useState('');
     const [loading, setLoading] = useState(false);
     const [error, setError] = useState<string | null>(null);
     const [oauthStatus, setOauthStatus] = useState<string>('');
+    const [authProfiles, setAuthProfiles] = useState<AuthProfile[]>([]);
+    const [authProfileId, setAuthProfileId] = useState(DEFAULT_AUTH_PROFILE_ID);
     const [showPasteInput, setShowPasteInput] = useState(false);
     const [callbackInput, setCallbackInput] = useState('');
     const [submittingCallback, setSubmittingCallback] = useState(false);
+    // 只剩 refresh_token 的老登录：换一套新 token 后建账号
+    const [showRtInput, setShowRtInput] = useState(false);
+    const [rtInput, setRtInput] = useState('');
+    const [submittingRt, setSubmittingRt] = useState(false);
     // OTP 批量授权
     const [otpInput, setOtpInput] = useState('');
     const [otpTimeout, setOtpTimeout] = useState(180);
     const [otpRows, setOtpRows] = useState<OtpRow[]>([]);
     const [otpRunning, setOtpRunning] = useState(false);
     // 批量导入
+    // OAuth 批量登录
+    const [oauthBatchInput, setOauthBatchInput] = useState('');
+    const [oauthBatchTimeout, setOauthBatchTimeout] = useState(180);
+    const [oauthBatchProgress, setOauthBatchProgress] = useState<BulkLoginProgress | null>(null);
+    const [oauthBatchSummary, setOauthBatchSummary] = useState<BulkLoginSummary | null>(null);
+    const [oauthBatchRunning, setOauthBatchRunning] = useState(false);
     const [bulkBusy, setBulkBusy] = useState(false);
     const [bulkResult, setBulkResult] = useState<BulkImportResult | null>(null);
     const [bulkError, setBulkError] = useState<string | null>(null);
+    const [dirResult, setDirResult] = useState<DirImportReport | null>(null);
+    const [discovery, setDiscovery] = useState<DiscoveryReport | null>(null);
+    const [adopted, setAdopted] = useState<Record<string, DirImportOutcome>>({});
     // ChatGPT Web session 导入（无 refresh_token，access_token 过期前可用）
     const [sessionInput, setSessionInput] = useState('');
     const [sessionBusy, setSessionBusy] = useState(false);
@@ -305,6 +400,14 @@ export function AddAccountModal({ isOpen, onClose, onAdd, onSuccess }: AddAccoun
         }
     };
 
+    // 只有配置了自定义 OAuth 配置时才显示选择器
+    useEffect(() => {
+        if (!isOpen) return;
+        invoke<AuthProfile[]>('list_auth_profiles')
+            .then(setAuthProfiles)
+            .catch(() => setAuthProfiles([]));
+    }, [isOpen]);
+
     // 监听后端发来的授权码
     useEffect(() => {
         if (!isOpen) return;
@@ -333,6 +436,26 @@ export function AddAccountModal({ isOpen, onClose, onAdd, onSuccess }: AddAccoun
         };
     }, [isOpen, finalizeOAuthLogin]);
 
+    // OAuth 批量登录进度；重新打开弹窗时先拉一次，接上还在跑的会话
+    useEffect(() => {
+        if (!isOpen) return;
+        invoke<BulkLoginProgress | null>('get_bulk_login_status')
+            .then(p => {
+                if (p) {
+                    setOauthBatchProgress(p);
+                    setOauthBatchRunning(true);
+                }
+            })
+            .catch(() => {});
+        const unlisten = listen<BulkLoginProgress>('bulk-login-progress', (event) => {
+            setOauthBatchProgress(event.payload);
+            if (event.payload.next_index === null) setOauthBatchRunning(false);
+        });
+        return () => {
+            unlisten.then(f => f());
+        };
+    }, [isOpen]);
+
     // 监听 OTP 批量授权进度（重试时 backend 的 index 是子集索引，要翻译回原 rows index）
     useEffect(() => {
         if (!isOpen) return;
@@ -362,7 +485,9 @@ export function AddAccountModal({ isOpen, onClose, onAdd, onSuccess }: AddAccoun
     // 处理官方导入
     const handleSubmitOfficial = async (e: React.FormEvent) => {
         e.preventDefault();
-        if (!name.trim()) {
+        const pasted = pastedAuth.trim();
+        // 粘贴导入时名称可留空，后端用 token 里的邮箱
+        if (!name.trim() && !pasted) {
             setError('请输入账号名称');
             return;
         }
@@ -371,7 +496,22 @@ export function AddAccountModal({ isOpen, onClose, onAdd, onSuccess }: AddAccoun
         setError(null);
 
         try {
-            await onAdd(name.trim(), notes.trim() || undefined);
+            let updated: boolean;
+            if (pasted) {
+                const imported = await invoke<{ updated: boolean; skipped?: string | null }>('import_auth_json', {
+                    name: name.trim(),
+                    jsonText: pasted,
+                    notes: notes.trim() || undefined,
+                });
+                if (imported.skipped) throw imported.skipped;
+                updated = imported.updated;
+                onSuccess?.();
+            } else {
+                updated = await onAdd(name.trim(), notes.trim() || undefined);
+            }
+            if (updated) {
+                alert('该登录已在账号列表中，已更新原账号的凭证');
+            }
             handleClose();
         } catch (err) {
             setError(String(err));
@@ -388,7 +528,7 @@ export function AddAccountModal({ isOpen, onClose, onAdd, onSuccess }: AddAccoun
 
         try {
             // 启动 OAuth 后端任务，后端会处理打开浏览器和启动监听
-            await startOAuthLogin();
+            await startOAuthLogin(true, authProfileId);
             setOauthStatus('请在打开的浏览器窗口中完成 OpenAI 授权...');
         } catch (err) {
             setError(String(err));
@@ -404,7 +544,7 @@ export function AddAccountModal({ isOpen, onClose, onAdd, onSuccess }: AddAccoun
         setOauthStatus('正在生成授权链接...');
 
         try {
-            const url = await startOAuthLogin(false);
+            const url = await startOAuthLogin(false, authProfileId);
             // 走后端 pbcopy 而不是 navigator.clipboard：webview 跨 await 后 user-gesture 失效会触发 NotAllowedError
             try {
                 await invoke('copy_to_clipboard', { text: url });
@@ -426,6 +566,7 @@ export function AddAccountModal({ isOpen, onClose, onAdd, onSuccess }: AddAccoun
         // OAuth 进行中也允许关闭：后端 oauth_server 下次 start 时会 abort 旧任务，无需显式取消
         setName('');
         setNotes('');
+        setPastedAuth('');
         setError(null);
         setOauthStatus('');
         setLoading(false);
@@ -447,6 +588,7 @@ export function AddAccountModal({ isOpen, onClose, onAdd, onSuccess }: AddAccoun
     const handleBulkPickAndImport = async () => {
         setBulkError(null);
         setBulkResult(null);
+        setDirResult(null);
         const selection = await openDialog({
             multiple: true,
             filters: [
@@ -473,9 +615,60 @@ export function AddAccountModal({ isOpen, onClose, onAdd, onSuccess }: AddAccoun
         }
     };
 
+    // 文件夹导入：目录里的每个 auth.json 备份按身份合并（已存账号用更新的 token 覆盖）
+    const handleDirImport = async () => {
+        setBulkError(null);
+        setBulkResult(null);
+        setDirResult(null);
+        const selection = await openDialog({ directory: true, multiple: false });
+        const path = Array.isArray(selection) ? selection[0] : selection;
+        if (!path) return;
+        setBulkBusy(true);
+        try {
+            const r = await invoke<DirImportReport>('import_auth_directory', { path });
+            setDirResult(r);
+            if (r.imported + r.updated > 0) onSuccess?.();
+        } catch (e: any) {
+            setBulkError(`${e}`);
+        } finally {
+            setBulkBusy(false);
+        }
+    };
+
     // ChatGPT Web session 导入：粘贴 chatgpt.com 的 session JSON（带 accessToken）
     // → 转成我们的 auth.json 并落库。源逻辑参考 gtxx3600/GPTSession2CPAandSub2API。
     // 没有 refresh_token，约 30 天后 access_token 过期需要重新导入。
+    // 扫描其它工具目录里的登录凭据（只读）
+    const handleDiscover = async () => {
+        setBulkBusy(true);
+        setBulkError(null);
+        try {
+            setDiscovery(await invoke<DiscoveryReport>('discover_external_logins'));
+            setAdopted({});
+        } catch (err) {
+            setBulkError(String(err));
+        } finally {
+            setBulkBusy(false);
+        }
+    };
+
+    const handleAdopt = async (path: string) => {
+        setBulkBusy(true);
+        setBulkError(null);
+        try {
+            const outcome = await invoke<DirImportOutcome>('adopt_discovered_login', { path });
+            setAdopted(prev => ({ ...prev, [path]: outcome }));
+            if (outcome.status === 'imported' || outcome.status === 'updated') {
+                await emit('accounts-updated');
+                onSuccess?.();
+            }
+        } catch (err) {
+            setBulkError(String(err));
+        } finally {
+            setBulkBusy(false);
+        }
+    };
+
     const handleSessionImport = async () => {
         setSessionError(null);
         setSessionResult(null);
@@ -528,6 +721,39 @@ export function AddAccountModal({ isOpen, onClose, onAdd, onSuccess }: AddAccoun
         }
     };
 
+    const handleOAuthBatch = async () => {
+        const entries = parseBulkLoginEntries(oauthBatchInput);
+        if (entries.length === 0) {
+            setError('请输入至少一个邮箱或数量');
+            return;
+        }
+        setError(null);
+        setOauthBatchSummary(null);
+        setOauthBatchProgress(null);
+        setOauthBatchRunning(true);
+        try {
+            const summary = await invoke<BulkLoginSummary>('start_bulk_login', {
+                entries,
+                stepTimeoutSecs: oauthBatchTimeout,
+                profileId: authProfileId,
+            });
+            setOauthBatchSummary(summary);
+            onSuccess?.();
+        } catch (err) {
+            setError(String(err));
+        } finally {
+            setOauthBatchRunning(false);
+        }
+    };
+
+    const controlOAuthBatch = async (command: 'pause_bulk_login' | 'resume_bulk_login' | 'cancel_bulk_login') => {
+        try {
+            await invoke(command);
+        } catch (err) {
+            setError(String(err));
+        }
+    };
+
     // 仅重跑失败的几条
     const handleRetryFailed = async () => {
         if (!otpSubmission) return;
@@ -597,10 +823,29 @@ export function AddAccountModal({ isOpen, onClose, onAdd, onSuccess }: AddAccoun
         }
     };
 
+    // 用 refresh_token 换 token 建账号；换不到时后端不建账号
+    const handleSubmitRefreshToken = async () => {
+        const refreshToken = rtInput.trim();
+        if (!refreshToken) return;
+        setSubmittingRt(true);
+        setError(null);
+        try {
+            await invoke('add_account_from_refresh_token', { name: '', refreshToken, profileId: authProfileId });
+            setRtInput('');
+            setShowRtInput(false);
+            onSuccess?.();
+            handleClose();
+        } catch (err) {
+            setError(String(err));
+        } finally {
+            setSubmittingRt(false);
+        }
+    };
+
     return (
         <div className="modal-overlay" onClick={handleClose}>
             <div
-                className={`modal-content${activeTab === 'otp_batch' || activeTab === 'relay' ? ' modal-wide' : ''}`}
+                className={`modal-content${activeTab === 'otp_batch' || activeTab === 'oauth_batch' || activeTab === 'relay' ? ' modal-wide' : ''}`}
                 onClick={e => e.stopPropagation()}
             >
                 <div className="modal-header">
@@ -617,6 +862,12 @@ export function AddAccountModal({ isOpen, onClose, onAdd, onSuccess }: AddAccoun
                         >
                             OpenAI 登录 (推荐)
                         </button>
+                        <button
+                            className={`tab-item ${activeTab === 'oauth_batch' ? 'active' : ''}`}
+                            onClick={() => !loading && !otpRunning && setActiveTab('oauth_batch')}
+                        >
+                            OAuth 批量登录
+                        </button>
                         <button
                             className={`tab-item ${activeTab === 'official' ? 'active' : ''}`}
                             onClick={() => !loading && setActiveTab('official')}
@@ -663,7 +914,114 @@ export function AddAccountModal({ isOpen, onClose, onAdd, onSuccess }: AddAccoun
                             >
                                 {bulkBusy ? '导入中…' : '选择文件并导入'}
                             </button>
+                            <button
+                                className="btn btn-secondary btn-full"
+                                style={{ marginTop: 8 }}
+                                onClick={handleDirImport}
+                                disabled={bulkBusy}
+                                title="扫描文件夹里的 *.json（auth-work.json、auth-personal.json…），同一账号用更新的 token 覆盖"
+                            >
+                                选择文件夹导入 auth.json 备份
+                            </button>
+                            <button
+                                className="btn btn-secondary btn-full"
+                                style={{ marginTop: 8 }}
+                                onClick={handleDiscover}
+                                disabled={bulkBusy}
+                                title="只读扫描其它工具（every-code、CLIProxyAPI…）目录里的 Codex 登录，逐条确认后再导入"
+                            >
+                                扫描其它工具里的登录
+                            </button>
                             {bulkError && <div className="error-msg" style={{ marginTop: 12 }}>{bulkError}</div>}
+                            {discovery && (
+                                <div className="bulk-result" style={{ marginTop: 16 }}>
+                                    {discovery.logins.length === 0 && discovery.errors.length === 0 && (
+                                        <div className="bulk-summary-item">没有在已知位置发现登录</div>
+                                    )}
+                                    {discovery.logins.length > 0 && (
+                                        <table className="bulk-table">
+                                            <thead>
+                                                <tr><th>来源</th><th>身份</th><th>账号库</th><th></th></tr>
+                                            </thead>
+                                            <tbody>
+                                                {discovery.logins.map((l) => {
+                                                    const outcome = adopted[l.path];
+                                                    return (
+                                                        <tr key={l.path} title={l.path}>
+                                                            <td>{l.source_tool}</td>
+                                                            <td>{l.identity.email ?? '—'}{l.identity.plan ? ` (${l.identity.plan})` : ''}</td>
+                                                            <td>
+                                                                {l.matched_account
+                                                                    ? `${l.matched_account.name}${l.token_fresher_than_stored ? '（这份更新）' : ''}`
+                                                                    : '未导入'}
+                                                            </td>
+                                                            <td>
+                                                                {outcome ? (
+                                                                    DIR_IMPORT_STATUS_LABEL[outcome.status]
+                                                                ) : (
+                                                                    <button
+                                                                        className="btn btn-ghost"
+                                                                        onClick={() => handleAdopt(l.path)}
+                                                                        disabled={bulkBusy}
+                                                                    >
+                                                                        {l.matched_account ? '同步' : '导入'}
+                                                                    </button>
+                                                                )}
+                                                            </td>
+                                                        </tr>
+                                                    );
+                                                })}
+                                            </tbody>
+                                        </table>
+                                    )}
+                                    {discovery.errors.map((e) => (
+                                        <div key={e.path} className="bulk-fatal" title={e.path}>
+                                            ⚠️ {e.source_tool}：{e.error}
+                                        </div>
+                                    ))}
+                                </div>
+                            )}
+                            {dirResult && (
+                                <div className="bulk-result" style={{ marginTop: 16 }}>
+                                    <div style={{ display: 'flex', gap: 10, flexWrap: 'wrap', marginBottom: 12 }}>
+                                        <span className="bulk-stat">文件 {dirResult.files.length}</span>
+                                        <span className="bulk-stat ok">新增 {dirResult.imported}</span>
+                                        <span className="bulk-stat ok">更新 {dirResult.updated}</span>
+                                        {dirResult.files.length - dirResult.imported - dirResult.updated > 0 && (
+                                            <span className="bulk-stat skip">
+                                                跳过/失败 {dirResult.files.length - dirResult.imported - dirResult.updated}
+                                            </span>
+                                        )}
+                                    </div>
+                                    {dirResult.exports.length > 0 && (
+                                        <div className="bulk-fatal">
+                                            ⚠️ {dirResult.exports.join('、')} 是账号库导出文件，请用上面的“选择文件并导入”
+                                        </div>
+                                    )}
+                                    {dirResult.files.length > 0 && (
+                                        <table className="bulk-table">
+                                            <thead>
+                                                <tr><th>文件</th><th>结果</th><th>说明</th></tr>
+                                            </thead>
+                                            <tbody>
+                                                {dirResult.files.map((f) => (
+                                                    <tr key={f.file}>
+                                                        <td>{f.file}</td>
+                                                        <td>{DIR_IMPORT_STATUS_LABEL[f.status]}</td>
+                                                        <td>
+                                                            {f.status === 'imported' || f.status === 'updated'
+                                                                ? f.name
+                                                                : f.status === 'skipped'
+                                                                    ? f.reason
+                                                                    : <span className="needs-refresh">{f.error}</span>}
+                                                        </td>
+                                                    </tr>
+                                                ))}
+                                            </tbody>
+                                        </table>
+                                    )}
+                                </div>
+                            )}
                             {bulkResult && (
                                 <div className="bulk-result" style={{ marginTop: 16 }}>
                                     <div style={{ display: 'flex', gap: 10, flexWrap: 'wrap', marginBottom: 12 }}>
@@ -794,6 +1152,118 @@ export function AddAccountModal({ isOpen, onClose, onAdd, onSuccess }: AddAccoun
                                 </div>
                             )}
                         </div>
+                    ) : activeTab === 'oauth_batch' ? (
+                        <div className="otp-panel">
+                            <h3>OAuth 批量登录</h3>
+                            <p className="otp-desc">
+                                按顺序逐个打开浏览器授权，一个账号落库后自动发起下一个。
+                                每行一个邮箱（授权页会预填），或写一个数字 N 表示再登录 N 个不指定邮箱的账号。
+                                <br />
+                                进行中不能再单独点"立即登录"；浏览器跳不回来时仍可在"OpenAI 登录"页粘贴回调链接。
+                            </p>
+
+                            <div className="form-group">
+                                <label htmlFor="oauth-batch-entries">邮箱 / 数量（每行一个）</label>
+                                <textarea
+                                    id="oauth-batch-entries"
+                                    className="otp-emails"
+                                    value={oauthBatchInput}
+                                    onChange={e => setOauthBatchInput(e.target.value)}
+                                    placeholder={'work@example.com\npersonal@example.com\n3'}
+                                    disabled={oauthBatchRunning}
+                                    rows={6}
+                                    spellCheck={false}
+                                />
+                            </div>
+
+                            <div className="otp-row-inline">
+                                <label htmlFor="oauth-batch-timeout">每个账号授权超时(秒)</label>
+                                <input
+                                    id="oauth-batch-timeout"
+                                    type="number"
+                                    min={30}
+                                    max={600}
+                                    value={oauthBatchTimeout}
+                                    onChange={e => setOauthBatchTimeout(Math.max(30, Math.min(600, Number(e.target.value) || 180)))}
+                                    disabled={oauthBatchRunning}
+                                />
+                            </div>
+
+                            <div className="otp-actions">
+                                <button
+                                    className="btn btn-primary"
+                                    onClick={handleOAuthBatch}
+                                    disabled={oauthBatchRunning || !oauthBatchInput.trim()}
+                                    type="button"
+                                >
+                                    {oauthBatchRunning ? '登录中…' : '开始批量登录'}
+                                </button>
+                                {oauthBatchRunning && (
+                                    <>
+                                        <button
+                                            className="btn btn-secondary"
+                                            onClick={() => controlOAuthBatch(oauthBatchProgress?.paused ? 'resume_bulk_login' : 'pause_bulk_login')}
+                                            type="button"
+                                        >
+                                            {oauthBatchProgress?.paused ? '继续' : '暂停'}
+                                        </button>
+                                        <button
+                                            className="btn btn-ghost"
+                                            onClick={() => controlOAuthBatch('cancel_bulk_login')}
+                                            type="button"
+                                        >
+                                            取消
+                                        </button>
+                                    </>
+                                )}
+                            </div>
+
+                            {error && <div className="error-message" style={{ marginTop: 12 }}>{error}</div>}
+
+                            {oauthBatchProgress && (
+                                <div className="otp-progress-summary">
+                                    <span className="ok">成功 {oauthBatchProgress.completed}</span>
+                                    {' / '}
+                                    <span className="fail">失败 {oauthBatchProgress.failed}</span>
+                                    {' / '}
+                                    共 {oauthBatchProgress.total}
+                                    {oauthBatchProgress.last_account && <> · 最近：{oauthBatchProgress.last_account}</>}
+                                    {oauthBatchProgress.next_index !== null && (
+                                        <>
+                                            {' · '}
+                                            {oauthBatchProgress.paused ? '已暂停，下一个' : '正在登录'}
+                                            第 {oauthBatchProgress.next_index + 1} 个
+                                            {oauthBatchProgress.next_hint && `（${oauthBatchProgress.next_hint}）`}
+                                        </>
+                                    )}
+                                </div>
+                            )}
+
+                            {oauthBatchSummary && (
+                                <div className="otp-progress">
+                                    {oauthBatchSummary.steps.map(step => (
+                                        <div
+                                            key={step.index}
+                                            className={`otp-progress-row ${step.status === 'succeeded' ? 'ok' : 'fail'}`}
+                                        >
+                                            <span className="icon">{statusIcon(step.status === 'succeeded' ? 'ok' : 'fail')}</span>
+                                            <span className="email">{step.account ?? step.hint ?? `第 ${step.index + 1} 个`}</span>
+                                            <span className="stage">
+                                                {step.status === 'succeeded' && '已添加'}
+                                                {step.status === 'failed' && (step.error ? step.error.slice(0, 80) : '失败')}
+                                                {step.status === 'timed_out' && '超时，已跳过'}
+                                                {step.status === 'cancelled' && '已取消'}
+                                            </span>
+                                        </div>
+                                    ))}
+                                    {oauthBatchSummary.not_attempted > 0 && (
+                                        <div className="otp-progress-summary">
+                                            未尝试 {oauthBatchSummary.not_attempted} 个
+                                        </div>
+                                    )}
+                                </div>
+                            )}
+                        </div>
                     ) : activeTab === 'otp_batch' ? (
                         <div className="otp-panel">
                             <h3>邮箱 OTP 批量自动授权</h3>
@@ -1063,7 +1533,8 @@ export function AddAccountModal({ isOpen, onClose, onAdd, onSuccess }: AddAccoun
                     ) : activeTab === 'official' ? (
                         <form onSubmit={handleSubmitOfficial}>
                             <p className="modal-tip">
-                                将从本地官方 Codex 的登录状态 (`auth.json`) 中提取认证信息。
+                                将从本地官方 Codex 的登录状态 (`auth.json`) 中提取认证信息；
+                                也可以直接粘贴别人发来的 `auth.json`，不会改动本机的登录。
                             </p>
 
                             <div className="form-group">
@@ -1079,6 +1550,19 @@ export function AddAccountModal({ isOpen, onClose, onAdd, onSuccess }: AddAccoun
                                 />
                             </div>
 
+                            <div className="form-group">
+                                <label htmlFor="pasted-auth">粘贴 auth.json（可选）</label>
+                                <textarea
+                                    id="pasted-auth"
+                                    value={pastedAuth}
+                                    onChange={e => setPastedAuth(e.target.value)}
+                                    placeholder='{"tokens": {"id_token": "...", "access_token": "...", "refresh_token": "..."}}'
+                                    disabled={loading}
+                                    rows={4}
+                                    spellCheck={false}
+                                />
+                            </div>
+
                             <div className="form-group">
                                 <label htmlFor="notes">备注</label>
                                 <textarea
@@ -1098,7 +1582,7 @@ export function AddAccountModal({ isOpen, onClose, onAdd, onSuccess }: AddAccoun
                                     取消
                                 </button>
                                 <button type="submit" className="btn btn-primary" disabled={loading}>
-                                    {loading ? '导入中...' : '导入当前账号'}
+                                    {loading ? '导入中...' : pastedAuth.trim() ? '导入粘贴的账号' : '导入当前账号'}
                                 </button>
                             </div>
                         </form>
@@ -1110,6 +1594,24 @@ export function AddAccountModal({ isOpen, onClose, onAdd, onSuccess }: AddAccoun
                                 直接通过 OpenAI 官方渠道登录。支持令牌自动续期，多账号切换更稳定，无需再手动更新 `auth.json`。
                             </p>
 
+                            {authProfiles.length > 1 && (
+                                <div className="form-group">
+                                    <label htmlFor="auth-profile">OAuth 配置</label>
+                                    <select
+                                        id="auth-profile"
+                                        value={authProfileId}
+                                        onChange={e => setAuthProfileId(e.target.value)}
+                                        disabled={loading}
+                                    >
+                                        {authProfiles.map(p => (
+                                            <option key={p.id} value={p.id}>
+                                                {p.name || p.id} — {p.issuer}
+                                            </option>
+                                        ))}
+                                    </select>
+                                </div>
+                            )}
+
                             <button
                                 className="btn btn-primary btn-full"
                                 style={{ padding: '14px' }}
@@ -1190,6 +1692,51 @@ export function AddAccountModal({ isOpen, onClose, onAdd, onSuccess }: AddAccoun
                                     </div>
                                 </div>
                             )}
+
+                            {!showRtInput ? (
+                                <button
+                                    className="btn btn-ghost btn-full"
+                                    style={{ marginTop: '8px', fontSize: '12px' }}
+                                    onClick={() => setShowRtInput(true)}
+                                    type="button"
+                                >
+                                    只有 refresh_token？用它恢复账号
+                                </button>
+                            ) : (
+                                <div style={{ marginTop: '12px', textAlign: 'left' }}>
+                                    <div style={{ fontSize: '12px', color: 'var(--text-secondary)', marginBottom: '6px' }}>
+                                        粘贴 refresh_token，会先换一套新 token 再建账号（原 token 可能随之失效）：
+                                    </div>
+                                    <textarea
+                                        className="text-input"
+                                        style={{ width: '100%', minHeight: '48px', fontFamily: 'monospace', fontSize: '12px' }}
+                                        value={rtInput}
+                                        onChange={e => setRtInput(e.target.value)}
+                                        placeholder="rt_..."
+                                        disabled={submittingRt}
+                                        spellCheck={false}
+                                    />
+                                    <div style={{ display: 'flex', gap: '8px', marginTop: '8px' }}>
+                                        <button
+                                            className="btn btn-primary"
+                                            style={{ flex: 1 }}
+                                            onClick={handleSubmitRefreshToken}
+                                            disabled={submittingRt || !rtInput.trim()}
+                                            type="button"
+                                        >
+                                            {submittingRt ? '换取中...' : '添加账号'}
+                                        </button>
+                                        <button
+                                            className="btn btn-ghost"
+                                            onClick={() => { setShowRtInput(false); setRtInput(''); }}
+                                            disabled={submittingRt}
+                                            type="button"
+                                        >
+                                            取消
+                                        </button>
+                                    </div>
+                                </div>
+                            )}
                         </div>
                     )}
                 </div>
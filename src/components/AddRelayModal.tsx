@@ -1,5 +1,5 @@
 import { useEffect, useMemo, useState } from 'react';
-import { invoke } from '@tauri-apps/api/core';
+import { invoke } from '../ipc';
 import { emit } from '@tauri-apps/api/event';
 import { ChevronRight } from 'lucide-react';
 import { RELAY_PRESETS, RelayPreset } from '../data/relay_presets';
@@ -1,5 +1,5 @@
 import { useEffect, useMemo, useState } from 'react';
-import { invoke } from '@tauri-apps/api/core';
+import { invoke } from '../ipc';
 import { Account, effectiveKind } from '../hooks/useAccounts';
 import {
     CodexSession,
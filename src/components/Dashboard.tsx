@@ -143,7 +143,7 @@ export function Dashboard({
                                 <span className="email-icon">✉</span>
                                 <span className="email">{currentAccount.name}</span>
                                 {usage?.plan_type && (
-                                    <span className="plan-badge">{usage.plan_type.toUpperCase()}</span>
+                                    <span className="plan-badge">{usage.display_plan || usage.plan_type.toUpperCase()}</span>
                                 )}
                             </div>
 
@@ -0,0 +1,125 @@
+import { useState, useEffect } from 'react';
+import { Bell, X } from 'lucide-react';
+import { invoke, ERROR_RECORDED_EVENT } from '../ipc';
+import './ErrorCenter.css';
+
+interface ErrorRecord {
+    id: string;
+    timestamp: string;
+    origin: { kind: 'command' | 'background'; name: string };
+    account_id: string | null;
+    code: string;
+    message: string;
+    count: number;
+    read: boolean;
+}
+
+interface BadgeState {
+    unread_errors: number;
+}
+
+const BADGE_POLL_MS = 15000;
+
+const BACKGROUND_TASK_LABELS: Record<string, string> = {
+    keepalive: '后台保活',
+    refresh: 'Token 刷新',
+    proxy: '代理',
+    quota_refresh: '额度刷新',
+};
+
+function originLabel(origin: ErrorRecord['origin']): string {
+    return origin.kind === 'command' ? origin.name : BACKGROUND_TASK_LABELS[origin.name] ?? origin.name;
+}
+
+function formatTime(ts: string): string {
+    return new Date(ts).toLocaleString('zh-CN', { month: 'numeric', day: 'numeric', hour: '2-digit', minute: '2-digit' });
+}
+
+/** 顶栏的错误中心：角标显示未读数，展开后列出最近的错误（展开即已读） */
+export function ErrorCenter() {
+    const [unread, setUnread] = useState(0);
+    const [open, setOpen] = useState(false);
+    const [records, setRecords] = useState<ErrorRecord[]>([]);
+
+    const refreshBadge = async () => {
+        try {
+            const badge = await invoke<BadgeState>('get_badge_state');
+            setUnread(badge.unread_errors);
+        } catch (e) {
+            console.error('读取错误角标失败:', e);
+        }
+    };
+
+    const loadRecords = async () => {
+        try {
+            setRecords(await invoke<ErrorRecord[]>('get_recent_errors', { limit: 50 }));
+            setUnread(0);
+        } catch (e) {
+            console.error('读取错误记录失败:', e);
+        }
+    };
+
+    useEffect(() => {
+        refreshBadge();
+        const timer = setInterval(refreshBadge, BADGE_POLL_MS);
+        const onRecorded = () => refreshBadge();
+        window.addEventListener(ERROR_RECORDED_EVENT, onRecorded);
+        return () => {
+            clearInterval(timer);
+            window.removeEventListener(ERROR_RECORDED_EVENT, onRecorded);
+        };
+    }, []);
+
+    const toggle = () => {
+        if (!open) loadRecords();
+        setOpen(!open);
+    };
+
+    const dismiss = async (id: string) => {
+        await invoke('dismiss_error', { id }).catch(() => {});
+        setRecords(prev => prev.filter(r => r.id !== id));
+    };
+
+    const clearAll = async () => {
+        await invoke('clear_errors').catch(() => {});
+        setRecords([]);
+        setUnread(0);
+    };
+
+    return (
+        <div className="error-center">
+            <button className="error-center-btn" onClick={toggle} title="错误记录">
+                <Bell size={16} />
+                {unread > 0 && <span className="error-center-badge">{unread > 99 ? '99+' : unread}</span>}
+            </button>
+            {open && (
+                <div className="error-center-panel">
+                    <div className="error-center-header">
+                        <span>最近的错误</span>
+                        {records.length > 0 && (
+                            <button className="error-center-clear" onClick={clearAll}>清空</button>
+                        )}
+                    </div>
+                    {records.length === 0 ? (
+                        <div className="error-center-empty">暂无错误记录</div>
+                    ) : (
+                        records.map(r => (
+                            <div key={r.id} className={`error-center-item ${r.read ? '' : 'unread'}`}>
+                                <div className="error-center-meta">
+                                    <span>{formatTime(r.timestamp)}</span>
+                                    <span>{originLabel(r.origin)}</span>
+                                    {r.code !== 'UNKNOWN' && <span className="error-center-code">{r.code}</span>}
+                                    {r.count > 1 && <span>×{r.count}</span>}
+                                    <button className="error-center-dismiss" onClick={() => dismiss(r.id)} title="移除">
+                                        <X size={12} />
+                                    </button>
+                                </div>
+                                <div className="error-center-message">{r.message}</div>
+                            </div>
+                        ))
+                    )}
+                </div>
+            )}
+        </div>
+    );
+}
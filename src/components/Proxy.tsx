@@ -1,5 +1,5 @@
 import { useState, useEffect } from 'react';
-import { invoke } from '@tauri-apps/api/core';
+import { invoke } from '../ipc';
 import { listen } from '@tauri-apps/api/event';
 import { Copy, Check, Save } from 'lucide-react';
 import './Proxy.css';
@@ -29,6 +29,7 @@ interface AppSettings {
     proxy_allow_lan: boolean;
     proxy_threshold_5h: number;
     proxy_threshold_weekly: number;
+    proxy_threshold_unit?: 'percent' | 'count';
     proxy_free_guard: number;
     notify_on_switch: boolean;
     inject_switch_message: boolean;
@@ -152,6 +153,7 @@ export function Proxy() {
 
     const isRunning = status?.is_running ?? false;
     const isEnabled = settings?.proxy_enabled ?? false;
+    const thresholdByCount = settings?.proxy_threshold_unit === 'count';
 
     return (
         <div className="proxy-page">
@@ -519,17 +521,37 @@ export function Proxy() {
             {/* 智能切号策略 */}
             <div className="settings-section">
                 <h3>智能切号策略</h3>
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">阈值单位</span>
+                        <span className="setting-desc">按剩余百分比，或按剩余请求次数（仅对接口返回绝对次数的账号生效，其余账号只由 429 触发）</span>
+                    </div>
+                    <select
+                        className="select-input"
+                        value={settings?.proxy_threshold_unit ?? 'percent'}
+                        onChange={async e => {
+                            if (!settings) return;
+                            const unit = e.target.value as 'percent' | 'count';
+                            const updated = { ...settings, proxy_threshold_unit: unit };
+                            setSettings(updated);
+                            await invoke('update_settings', { settings: updated });
+                        }}
+                    >
+                        <option value="percent">百分比</option>
+                        <option value="count">剩余次数</option>
+                    </select>
+                </div>
                 <div className="setting-item">
                     <div className="setting-info">
                         <span className="setting-label">5h 配额预防性切号阈值</span>
-                        <span className="setting-desc">剩余配额低于此百分比时提前切号（0 = 仅 429 触发，推荐 10）</span>
+                        <span className="setting-desc">剩余配额低于此值时提前切号（0 = 仅 429 触发，按百分比推荐 10）</span>
                     </div>
                     <div className="threshold-input-group">
                         <input
                             type="number"
                             className="number-input"
                             min={0}
-                            max={50}
+                            max={thresholdByCount ? 255 : 50}
                             value={settings?.proxy_threshold_5h ?? 0}
                             onChange={e => {
                                 if (!settings) return;
@@ -539,20 +561,20 @@ export function Proxy() {
                                 invoke('update_settings', { settings: updated });
                             }}
                         />
-                        <span className="threshold-unit">%</span>
+                        <span className="threshold-unit">{thresholdByCount ? '次' : '%'}</span>
                     </div>
                 </div>
                 <div className="setting-item">
                     <div className="setting-info">
                         <span className="setting-label">周配额预防性切号阈值</span>
-                        <span className="setting-desc">剩余周配额低于此百分比时提前切号（0 = 仅 429 触发，推荐 5）</span>
+                        <span className="setting-desc">剩余周配额低于此值时提前切号（0 = 仅 429 触发，按百分比推荐 5）</span>
                     </div>
                     <div className="threshold-input-group">
                         <input
                             type="number"
                             className="number-input"
                             min={0}
-                            max={50}
+                            max={thresholdByCount ? 255 : 50}
                             value={settings?.proxy_threshold_weekly ?? 0}
                             onChange={e => {
                                 if (!settings) return;
@@ -562,7 +584,7 @@ export function Proxy() {
                                 invoke('update_settings', { settings: updated });
                             }}
                         />
-                        <span className="threshold-unit">%</span>
+                        <span className="threshold-unit">{thresholdByCount ? '次' : '%'}</span>
                     </div>
                 </div>
                 <div className="setting-item">
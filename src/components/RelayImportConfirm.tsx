@@ -1,5 +1,5 @@
 import { useEffect, useState } from 'react';
-import { invoke } from '@tauri-apps/api/core';
+import { invoke } from '../ipc';
 import { listen } from '@tauri-apps/api/event';
 import { emit } from '@tauri-apps/api/event';
 import './ConfirmModal.css';
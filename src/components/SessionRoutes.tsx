@@ -1,5 +1,5 @@
 import { useCallback, useEffect, useMemo, useState } from 'react';
-import { invoke } from '@tauri-apps/api/core';
+import { invoke } from '../ipc';
 import { Pencil, RefreshCw, Trash2 } from 'lucide-react';
 import { useAccounts, Account, effectiveKind } from '../hooks/useAccounts';
 import {
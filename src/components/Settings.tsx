@@ -1,7 +1,9 @@
 import { useState, useEffect, useMemo } from 'react';
-import { invoke } from '@tauri-apps/api/core';
-import { Palette, Server, Monitor, Wrench, Save, Github, Radio, Smartphone, Search, X } from 'lucide-react';
-import { Account, effectiveKind } from '../hooks/useAccounts';
+import { invoke } from '../ipc';
+import { Palette, Server, Monitor, Wrench, Save, Github, Radio, Smartphone, Search, X, KeyRound } from 'lucide-react';
+import { Account, CodexCompatibilityReport, effectiveKind } from '../hooks/useAccounts';
+import { save, open } from '@tauri-apps/plugin-dialog';
+import { readTextFile, writeTextFile } from '@tauri-apps/plugin-fs';
 import './Settings.css';
 
 interface AppSettings {
@@ -11,6 +13,7 @@ interface AppSettings {
     background_refresh: boolean;
     refresh_interval_minutes: number;
     inactive_refresh_days: number;
+    scheduler_quiet_secs: number;
     theme_palette: string;
     allow_auto_switch_to_free: boolean;
     proxy_enabled: boolean;
@@ -28,8 +31,140 @@ interface AppSettings {
     proxy_bootstrap_time_cap_ms: number;
     relay_auto_switch_out: boolean;
     relay_auto_switch_in: boolean;
+    auto_adopt_current_rotation: boolean;
+    apply_profile_on_switch: boolean;
+    create_codex_config_if_missing: boolean;
+    post_switch_hooks: SwitchHook[];
+    post_switch_hooks_enabled: boolean;
+    codex_version_override: string;
+    usage_user_agent: string;
+    leave_auth_permissions: boolean;
+    log_identifier_style: 'full' | 'masked' | 'hashed';
+    codex_home: string | null;
+    codex_installs: CodexInstall[];
+    daily_report_enabled: boolean;
+    daily_report_time: string;
+    refresh_token_aging_days: number;
+    aging_auto_keepalive: boolean;
+    quota_suspect_after_hours: number;
+    token_expiring_soon_minutes: number;
+    new_account_notes_template: string;
+    five_hour_reset_lookahead_minutes: number;
+    trash_retention_days: number;
+    store_backup_keep: number;
+    auto_backup: boolean;
+    backup_retention_days: number;
 }
 
+interface CodexInstall {
+    name: string;
+    auth_path: string;
+}
+
+interface SwitchHook {
+    name: string;
+    command: string;
+    args: string[];
+    timeout_secs: number;
+    enabled: boolean;
+}
+
+/** `repair_setup` 每一步的结果 */
+interface RepairReport {
+    step: string;
+    status:
+        | { kind: 'skipped' }
+        | { kind: 'fixed' }
+        | { kind: 'needs_manual_action'; instruction: string }
+        | { kind: 'failed'; error: string };
+    detail: string | null;
+    would_fix: boolean;
+}
+
+interface HookPlan {
+    name: string;
+    command: string;
+    args: string[];
+    timeout_secs: number;
+    will_run: boolean;
+    env: [string, string][];
+}
+
+const MAX_SWITCH_HOOKS = 10;
+
+interface AgingToken {
+    id: string;
+    name: string;
+    idle_days: number;
+    last_exercised_at: string;
+    keepalive: 'unavailable' | 'manual' | 'auto';
+}
+
+type TokenStorage = 'file' | 'keychain';
+
+interface TokenStorageStatus {
+    storage: TokenStorage;
+    include_access_tokens: boolean;
+    unresolved_accounts: string[];
+    last_error: { code: string; detail: string } | null;
+}
+
+interface TokenMigration {
+    from: TokenStorage;
+    to: TokenStorage;
+    accounts: number;
+    warnings: string[];
+}
+
+interface FieldError {
+    field: string;
+    message: string;
+}
+
+interface IdentifyResult {
+    parsed_identity: {
+        shape: 'standard' | 'root_account_id' | 'root_tokens' | 'python_repr_tokens';
+        email: string | null;
+        account_id: string | null;
+        user_id: string | null;
+        plan: string | null;
+        access_token_expires_at: string | null;
+        last_refresh: string | null;
+        has_refresh_token: boolean;
+        warnings: FieldError[];
+    };
+    matched_account: { id: string; name: string; kind: string; email: string | null; is_current: boolean } | null;
+    refresh_token_matches_stored: boolean | null;
+    token_fresher_than_stored: boolean | null;
+}
+
+const AUTH_SHAPE_LABELS: Record<IdentifyResult['parsed_identity']['shape'], string> = {
+    standard: '标准格式',
+    root_account_id: '旧格式（根级 account_id）',
+    root_tokens: '旧格式（token 在根上）',
+    python_repr_tokens: '旧格式（tokens 为 Python repr 字符串）',
+};
+
+interface AuthJournalEntry {
+    at: string;
+    op: string;
+    account_id: string | null;
+    before: { access: string | null; refresh: string | null } | null;
+    after: { access: string | null; refresh: string | null } | null;
+}
+
+const AUTH_WRITE_OP_LABELS: Record<string, string> = {
+    switch: '切换',
+    resolve: '冲突解决',
+    refresh: '刷新',
+    remote_sync: '远程同步',
+    anchor: '锚定',
+    logout: '退出登录',
+    repair: '修复',
+    external: '外部修改',
+    other: '其他',
+};
+
 interface RemoteHealth {
     mode: string;
     version: string;
@@ -57,6 +192,7 @@ export function Settings({ accounts = [], onSetSessionAnchor }: SettingsProps =
         background_refresh: false,
         refresh_interval_minutes: 30,
         inactive_refresh_days: 7,
+        scheduler_quiet_secs: 30,
         theme_palette: 'midnight',
         allow_auto_switch_to_free: false,
         proxy_enabled: false,
@@ -74,6 +210,29 @@ export function Settings({ accounts = [], onSetSessionAnchor }: SettingsProps =
         proxy_bootstrap_time_cap_ms: 8000,
         relay_auto_switch_out: true,
         relay_auto_switch_in: false,
+        auto_adopt_current_rotation: false,
+        apply_profile_on_switch: false,
+        create_codex_config_if_missing: false,
+        post_switch_hooks: [],
+        post_switch_hooks_enabled: true,
+        codex_version_override: '',
+        usage_user_agent: '',
+        leave_auth_permissions: false,
+        log_identifier_style: 'full',
+        codex_home: null,
+        codex_installs: [],
+        daily_report_enabled: false,
+        daily_report_time: '09:00',
+        refresh_token_aging_days: 30,
+        aging_auto_keepalive: false,
+        quota_suspect_after_hours: 24,
+        token_expiring_soon_minutes: 30,
+        new_account_notes_template: '',
+        five_hour_reset_lookahead_minutes: 15,
+        trash_retention_days: 30,
+        store_backup_keep: 10,
+        auto_backup: false,
+        backup_retention_days: 14,
     });
     const [saving, setSaving] = useState(false);
     const [repairing, setRepairing] = useState(false);
@@ -83,6 +242,25 @@ export function Settings({ accounts = [], onSetSessionAnchor }: SettingsProps =
     const [showAnchorPicker, setShowAnchorPicker] = useState(false);
     const [anchorSearch, setAnchorSearch] = useState('');
     const [anchorBusy, setAnchorBusy] = useState(false);
+    const [exportingDiagnostics, setExportingDiagnostics] = useState(false);
+    const [reconciling, setReconciling] = useState(false);
+    const [loggingOut, setLoggingOut] = useState(false);
+    const [tokenStorage, setTokenStorage] = useState<TokenStorageStatus | null>(null);
+    const [migratingTokens, setMigratingTokens] = useState(false);
+    const [showIdentify, setShowIdentify] = useState(false);
+    const [identifyText, setIdentifyText] = useState('');
+    const [identifyResult, setIdentifyResult] = useState<IdentifyResult | null>(null);
+    const [identifyErrors, setIdentifyErrors] = useState<FieldError[]>([]);
+    const [presentationMode, setPresentationMode] = useState(false);
+    const [agingTokens, setAgingTokens] = useState<AgingToken[]>([]);
+    const [keepaliveBusy, setKeepaliveBusy] = useState<string | null>(null);
+    const [authJournal, setAuthJournal] = useState<AuthJournalEntry[] | null>(null);
+    const [hookPlan, setHookPlan] = useState<HookPlan[] | null>(null);
+    const [repairReports, setRepairReports] = useState<RepairReport[] | null>(null);
+    const [repairRunning, setRepairRunning] = useState(false);
+    const [codexCompat, setCodexCompat] = useState<CodexCompatibilityReport | null>(null);
+    const [detectingCodex, setDetectingCodex] = useState(false);
+    const [notesPreview, setNotesPreview] = useState<string | null>(null);
 
     // 手机锚只对 ChatGPT 订阅号有效：Codex.app `/codex/remote/control/*`
     // 必须用 chatgpt_account_id 鉴权；Relay / OpenAI API key 没有这个 claim。
@@ -105,8 +283,20 @@ export function Settings({ accounts = [], onSetSessionAnchor }: SettingsProps =
 
     useEffect(() => {
         loadSettings();
+        loadTokenStorage();
+        loadAgingTokens();
+        loadCodexCompat(false);
+        invoke<boolean>('get_presentation_mode').then(setPresentationMode).catch(() => {});
     }, []);
 
+    const togglePresentationMode = async (enabled: boolean) => {
+        try {
+            setPresentationMode(await invoke<boolean>('set_presentation_mode', { enabled }));
+        } catch (e) {
+            setMessage({ type: 'error', text: `❌ 切换演示模式失败: ${e}` });
+        }
+    };
+
     const loadSettings = async () => {
         try {
             const data = await invoke<AppSettings>('get_settings');
@@ -116,12 +306,73 @@ export function Settings({ accounts = [], onSetSessionAnchor }: SettingsProps =
         }
     };
 
+    const loadCodexCompat = async (refresh: boolean) => {
+        setDetectingCodex(true);
+        try {
+            setCodexCompat(await invoke<CodexCompatibilityReport>('get_codex_compatibility', { refresh }));
+        } catch (e) {
+            console.error('检测 Codex 版本失败:', e);
+        } finally {
+            setDetectingCodex(false);
+        }
+    };
+
+    const loadAgingTokens = async () => {
+        try {
+            setAgingTokens(await invoke<AgingToken[]>('get_aging_tokens'));
+        } catch (e) {
+            console.error('读取 refresh_token 闲置状态失败:', e);
+        }
+    };
+
+    const keepaliveAccount = async (token: AgingToken) => {
+        setKeepaliveBusy(token.id);
+        try {
+            await invoke('keepalive_refresh_account', { id: token.id });
+            setMessage({ type: 'success', text: `✅ ${token.name} 已刷新` });
+            await loadAgingTokens();
+        } catch (e) {
+            setMessage({ type: 'error', text: `❌ ${token.name} 刷新失败: ${e}` });
+        } finally {
+            setKeepaliveBusy(null);
+        }
+    };
+
+    const loadTokenStorage = async () => {
+        try {
+            setTokenStorage(await invoke<TokenStorageStatus>('get_token_storage_status'));
+        } catch (e) {
+            console.error('读取 token 存储状态失败:', e);
+        }
+    };
+
+    const migrateTokenStorage = async (target: TokenStorage, includeAccessTokens: boolean) => {
+        setMigratingTokens(true);
+        setMessage(null);
+        try {
+            const report = await invoke<TokenMigration>('migrate_token_storage', { target, includeAccessTokens });
+            const where = target === 'keychain' ? '系统凭据库' : 'accounts.json';
+            const warnings = report.warnings.length ? `（${report.warnings.join('；')}）` : '';
+            setMessage({ type: 'success', text: `✅ 已把 ${report.accounts} 个账号的 token 迁移到${where}${warnings}` });
+        } catch (e) {
+            setMessage({ type: 'error', text: `❌ 迁移 token 存储失败: ${e}` });
+        } finally {
+            setMigratingTokens(false);
+            loadTokenStorage();
+        }
+    };
+
     const saveSettings = async () => {
         setSaving(true);
         setMessage(null);
         try {
-            await invoke('update_settings', { settings });
-            setMessage({ type: 'success', text: '✅ 设置已保存' });
+            const saved = await invoke<AppSettings>('update_settings', { settings });
+            setSettings(saved);
+            if (saved.refresh_interval_minutes !== settings.refresh_interval_minutes) {
+                setMessage({ type: 'success', text: `✅ 设置已保存（调度间隔已调整为 ${saved.refresh_interval_minutes} 分钟）` });
+            } else {
+                setMessage({ type: 'success', text: '✅ 设置已保存' });
+            }
             setTimeout(() => setMessage(null), 3000);
         } catch (e) {
             setMessage({ type: 'error', text: `❌ 保存失败: ${e}` });
@@ -130,10 +381,81 @@ export function Settings({ accounts = [], onSetSessionAnchor }: SettingsProps =
         }
     };
 
+    // 只导出 / 导入设置，不含账号和 token；导入后立即生效（同保存设置）
+    const exportSettings = async () => {
+        try {
+            const json = await invoke<string>('export_settings');
+            const path = await save({
+                filters: [{ name: 'JSON', extensions: ['json'] }],
+                defaultPath: `codex-settings-${new Date().toISOString().slice(0, 10)}.json`,
+            });
+            if (!path) return;
+            await writeTextFile(path, json);
+            setMessage({ type: 'success', text: '✅ 设置已导出' });
+            setTimeout(() => setMessage(null), 3000);
+        } catch (e) {
+            setMessage({ type: 'error', text: `❌ 导出设置失败: ${e}` });
+        }
+    };
+
+    const importSettings = async () => {
+        try {
+            const path = await open({ multiple: false, filters: [{ name: 'JSON', extensions: ['json'] }] });
+            if (!path || Array.isArray(path)) return;
+            const json = await readTextFile(path);
+            const saved = await invoke<AppSettings>('import_settings', { json });
+            setSettings(saved);
+            setMessage({ type: 'success', text: '✅ 设置已导入并生效' });
+            setTimeout(() => setMessage(null), 3000);
+        } catch (e) {
+            setMessage({ type: 'error', text: `❌ 导入设置失败: ${e}` });
+        }
+    };
+
     const updateField = <K extends keyof AppSettings>(key: K, value: AppSettings[K]) => {
         setSettings(prev => ({ ...prev, [key]: value }));
     };
 
+    const installs = settings.codex_installs ?? [];
+
+    const updateInstall = (index: number, patch: Partial<CodexInstall>) => {
+        updateField('codex_installs', installs.map((c, i) => (i === index ? { ...c, ...patch } : c)));
+    };
+
+    const hooks = settings.post_switch_hooks ?? [];
+
+    const updateHook = (index: number, patch: Partial<SwitchHook>) => {
+        updateField('post_switch_hooks', hooks.map((h, i) => (i === index ? { ...h, ...patch } : h)));
+        setHookPlan(null);
+    };
+
+    const addHook = () => {
+        updateField('post_switch_hooks', [
+            ...hooks,
+            { name: `钩子 ${hooks.length + 1}`, command: '', args: [], timeout_secs: 30, enabled: true },
+        ]);
+        setHookPlan(null);
+    };
+
+    const removeHook = (index: number) => {
+        updateField('post_switch_hooks', hooks.filter((_, i) => i !== index));
+        setHookPlan(null);
+    };
+
+    // 预览按已保存的设置算，未保存的修改不会体现
+    const previewHooks = async () => {
+        const target = accounts.find(a => a.is_active) || accounts[0];
+        if (!target) {
+            setMessage({ type: 'error', text: '❌ 没有账号可用于预览' });
+            return;
+        }
+        try {
+            setHookPlan(await invoke<HookPlan[]>('run_hooks_dry', { id: target.id }));
+        } catch (e) {
+            setMessage({ type: 'error', text: `❌ 预览切号钩子失败: ${e}` });
+        }
+    };
+
     const withRemote = async (label: string, fn: () => Promise<string>) => {
         setRemoteBusy(true);
         setRemoteStatus('');
@@ -242,6 +564,130 @@ export function Settings({ accounts = [], onSetSessionAnchor }: SettingsProps =
         }
     };
 
+    const handleExportDiagnostics = async () => {
+        setExportingDiagnostics(true);
+        setMessage(null);
+        try {
+            const manifest = await invoke<{ path: string; files: { name: string }[] }>('create_diagnostics_bundle', { path: null });
+            setMessage({ type: 'success', text: `诊断包已导出（${manifest.files.length} 个文件）：${manifest.path}` });
+        } catch (e) {
+            setMessage({ type: 'error', text: `导出诊断包失败: ${e}` });
+        } finally {
+            setExportingDiagnostics(false);
+        }
+    };
+
+    const handleToggleAuthJournal = async () => {
+        if (authJournal) {
+            setAuthJournal(null);
+            return;
+        }
+        try {
+            setAuthJournal(await invoke<AuthJournalEntry[]>('get_auth_journal', { limit: 50 }));
+        } catch (e) {
+            setMessage({ type: 'error', text: `读取写入记录失败: ${e}` });
+        }
+    };
+
+    const fingerprint = (fp: AuthJournalEntry['before']) =>
+        fp ? `${fp.access?.slice(0, 8) ?? '-'}/${fp.refresh?.slice(0, 8) ?? '-'}` : '无';
+
+    const handleReconcileAccountIds = async () => {
+        type Change = { name: string; old_account_id: string | null; new_account_id: string };
+        setReconciling(true);
+        setMessage(null);
+        try {
+            const changes = await invoke<Change[]>('reconcile_account_ids', { apply: false });
+            if (changes.length === 0) {
+                setMessage({ type: 'success', text: '所有账号的工作区 ID 都正确' });
+                return;
+            }
+            const lines = changes.map(c => `${c.name}: ${c.old_account_id ?? '（空）'} → ${c.new_account_id}`).join('\n');
+            if (!confirm(`以下 ${changes.length} 个账号的工作区 ID 与 id_token 不一致：\n\n${lines}\n\n是否修正？`)) {
+                return;
+            }
+            await invoke('reconcile_account_ids', { apply: true });
+            setMessage({ type: 'success', text: `已修正 ${changes.length} 个账号的工作区 ID` });
+        } catch (e) {
+            setMessage({ type: 'error', text: `校正工作区 ID 失败: ${e}` });
+        } finally {
+            setReconciling(false);
+        }
+    };
+
+    const previewNotesTemplate = async () => {
+        try {
+            const rendered = await invoke<string | null>('render_notes_template', {
+                idOrPreviewIdentity: null,
+                template: settings.new_account_notes_template ?? '',
+            });
+            setNotesPreview(rendered ?? '（模板为空，沿用默认备注）');
+        } catch (e) {
+            setNotesPreview(null);
+            setMessage({ type: 'error', text: `${e}` });
+        }
+    };
+
+    const handleIdentify = async () => {
+        setIdentifyResult(null);
+        setIdentifyErrors([]);
+        try {
+            setIdentifyResult(await invoke<IdentifyResult>('identify_auth_json', { jsonText: identifyText }));
+        } catch (e) {
+            const errors = (e as { errors?: FieldError[] })?.errors;
+            setIdentifyErrors(errors ?? [{ field: 'input', message: String(e) }]);
+        }
+    };
+
+    const handleLogoutCodex = async () => {
+        if (!confirm('将退出 Codex 登录：删除 ~/.codex/auth.json 并取消当前账号，已保存的账号不受影响。\n\n是否继续？')) {
+            return;
+        }
+        setLoggingOut(true);
+        setMessage(null);
+        try {
+            try {
+                await invoke('logout_codex', { skipFinalSync: false });
+            } catch (e) {
+                // auth.json 损坏读不出来时，允许跳过最后一次同步直接退出
+                if (!confirm(`最后一次同步失败：${e}\n\n是否跳过同步直接退出？`)) {
+                    return;
+                }
+                await invoke('logout_codex', { skipFinalSync: true });
+            }
+            setMessage({ type: 'success', text: '已退出 Codex 登录' });
+        } catch (e) {
+            setMessage({ type: 'error', text: `退出登录失败: ${e}` });
+        } finally {
+            setLoggingOut(false);
+        }
+    };
+
+    const runRepairSetup = async (dryRun: boolean) => {
+        setRepairRunning(true);
+        setMessage(null);
+        try {
+            setRepairReports(await invoke<RepairReport[]>('repair_setup', { dryRun }));
+        } catch (e) {
+            setMessage({ type: 'error', text: `❌ 一键修复失败: ${e}` });
+        } finally {
+            setRepairRunning(false);
+        }
+    };
+
+    const repairStatusText = (r: RepairReport) => {
+        switch (r.status.kind) {
+            case 'skipped':
+                return r.would_fix ? `🔧 将会修复：${r.detail}` : `✅ ${r.detail ?? '正常'}`;
+            case 'fixed':
+                return `✅ 已修复：${r.detail}`;
+            case 'needs_manual_action':
+                return `👉 需要手动处理：${r.status.instruction}`;
+            case 'failed':
+                return `❌ 失败：${r.status.error}`;
+        }
+    };
+
     const handleRepair = async () => {
         if (!confirm('这将尝试移除 Codex App 的安全隔离属性。\n\n系统可能会弹窗要求输入密码以获得权限。是否继续？')) {
             return;
@@ -298,6 +744,20 @@ export function Settings({ accounts = [], onSetSessionAnchor }: SettingsProps =
                         <option value="agate">玛瑙绿</option>
                     </select>
                 </div>
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">演示模式</span>
+                        <span className="setting-desc">录屏 / 共享屏幕时开启：账号名和邮箱打码，切号等修改操作全部禁用，不弹系统通知。不保存，重启后自动关闭</span>
+                    </div>
+                    <label className="toggle">
+                        <input
+                            type="checkbox"
+                            checked={presentationMode}
+                            onChange={e => togglePresentationMode(e.target.checked)}
+                        />
+                        <span className="toggle-slider"></span>
+                    </label>
+                </div>
             </div>
 
             <div className="settings-section">
@@ -343,6 +803,94 @@ export function Settings({ accounts = [], onSetSessionAnchor }: SettingsProps =
                     </label>
                 </div>
 
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">5H 即将重置的账号仍可选（分钟）</span>
+                        <span className="setting-desc">5H 额度用完的账号，重置时间在这么多分钟内时仍可被自动选中（排在有额度的账号之后）；周额度用完的账号一律不选。0 = 不选</span>
+                    </div>
+                    <input
+                        type="number"
+                        className="number-input"
+                        min={0}
+                        max={300}
+                        value={settings.five_hour_reset_lookahead_minutes}
+                        onChange={e => updateField('five_hour_reset_lookahead_minutes', Math.max(0, parseInt(e.target.value) || 0))}
+                    />
+                </div>
+
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">回收站保留天数</span>
+                        <span className="setting-desc">删除的账号先进回收站，可以恢复；放满这么多天后自动彻底清除（连同钥匙串里的 token）。0 = 不自动清除</span>
+                    </div>
+                    <input
+                        type="number"
+                        className="number-input"
+                        min={0}
+                        max={3650}
+                        value={settings.trash_retention_days}
+                        onChange={e => updateField('trash_retention_days', Math.max(0, parseInt(e.target.value) || 0))}
+                    />
+                </div>
+
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">导入前备份保留份数</span>
+                        <span className="setting-desc">替换式导入前会把当前账号库备份到数据目录下的 backups/（和 accounts.json 一样：文件存储时含 token，钥匙串存储时 token 留在钥匙串），超出份数时删最老的。0 = 不自动删除</span>
+                    </div>
+                    <input
+                        type="number"
+                        className="number-input"
+                        min={0}
+                        max={1000}
+                        value={settings.store_backup_keep}
+                        onChange={e => updateField('store_backup_keep', Math.max(0, parseInt(e.target.value) || 0))}
+                    />
+                </div>
+
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">每日自动备份</span>
+                        <span className="setting-desc">每天把账号库备份一份到数据目录下的 backups/（权限 0600；文件存储时含 token，钥匙串存储时 token 留在钥匙串）</span>
+                    </div>
+                    <label className="toggle">
+                        <input
+                            type="checkbox"
+                            checked={settings.auto_backup}
+                            onChange={e => updateField('auto_backup', e.target.checked)}
+                        />
+                        <span className="toggle-slider"></span>
+                    </label>
+                </div>
+
+                {settings.auto_backup && (
+                    <div className="setting-item">
+                        <div className="setting-info">
+                            <span className="setting-label">自动备份保留天数</span>
+                            <span className="setting-desc">超过这么多天的自动备份会被删掉。0 = 不自动删除</span>
+                        </div>
+                        <input
+                            type="number"
+                            className="number-input"
+                            min={0}
+                            max={3650}
+                            value={settings.backup_retention_days}
+                            onChange={e => updateField('backup_retention_days', Math.max(0, parseInt(e.target.value) || 0))}
+                        />
+                    </div>
+                )}
+
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">导出 / 导入设置</span>
+                        <span className="setting-desc">只含设置，不含账号、token、远程共享密钥和切号钩子，可以放心在机器之间拷贝。导入时保留本机的 Codex 目录、共享密钥和切号钩子</span>
+                    </div>
+                    <div style={{ display: 'flex', gap: 8 }}>
+                        <button className="action-button" onClick={exportSettings}>导出设置</button>
+                        <button className="action-button" onClick={importSettings}>导入设置</button>
+                    </div>
+                </div>
+
                 <div className="setting-item">
                     <div className="setting-info">
                         <span className="setting-label">中转 / Plan / 三方 出问题时切回订阅号</span>
@@ -377,18 +925,334 @@ export function Settings({ accounts = [], onSetSessionAnchor }: SettingsProps =
                     </label>
                 </div>
 
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">自动采纳 Codex 轮换的 Token</span>
+                        <span className="setting-desc">
+                            当前账号的 refresh_token 被 Codex 在 auth.json 里轮换、且磁盘版本更新时，直接同步进账号库，不再弹"Token 冲突"。磁盘版本更旧或缺 last_refresh 时仍走手动确认。
+                        </span>
+                    </div>
+                    <label className="toggle">
+                        <input
+                            type="checkbox"
+                            checked={settings.auto_adopt_current_rotation ?? false}
+                            onChange={e => updateField('auto_adopt_current_rotation', e.target.checked)}
+                        />
+                        <span className="toggle-slider"></span>
+                    </label>
+                </div>
+
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">Codex 目录</span>
+                        <span className="setting-desc">
+                            切号读写 auth.json、config.toml 的目录，对应 Codex 的 CODEX_HOME。留空时使用 CODEX_HOME 环境变量，未设置则为 ~/.codex；目录不存在会自动创建，保存后立即生效
+                        </span>
+                    </div>
+                    <input
+                        type="text"
+                        className="text-input"
+                        value={settings.codex_home ?? ''}
+                        onChange={e => updateField('codex_home', e.target.value || null)}
+                        placeholder="~/.codex"
+                    />
+                </div>
+
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">其它 Codex 安装</span>
+                        <span className="setting-desc">
+                            同时用着多个 Codex（如正式版和 fork）时，在这里登记其它安装的 auth.json，切号时可以指定切哪一个，各自记当前账号、各自回流。热切、手机锚和切号钩子只作用于默认安装
+                        </span>
+                    </div>
+                    <button
+                        className="action-button"
+                        onClick={() => updateField('codex_installs', [...installs, { name: `安装 ${installs.length + 1}`, auth_path: '' }])}
+                    >
+                        添加
+                    </button>
+                </div>
+                {installs.map((install, i) => (
+                    <div className="setting-item sub-item" key={i}>
+                        <div className="setting-info" style={{ gap: 6 }}>
+                            <input
+                                type="text"
+                                className="text-input"
+                                value={install.name}
+                                onChange={e => updateInstall(i, { name: e.target.value })}
+                                placeholder="名称"
+                            />
+                            <input
+                                type="text"
+                                className="text-input"
+                                style={{ fontFamily: 'monospace', fontSize: 12 }}
+                                value={install.auth_path}
+                                onChange={e => updateInstall(i, { auth_path: e.target.value })}
+                                placeholder="auth.json 绝对路径，例 /Users/me/.codex-fork/auth.json"
+                            />
+                        </div>
+                        <button
+                            className="action-button"
+                            onClick={() => updateField('codex_installs', installs.filter((_, j) => j !== i))}
+                        >
+                            删除
+                        </button>
+                    </div>
+                ))}
+
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">不自动收紧 auth.json 权限</span>
+                        <span className="setting-desc">
+                            默认每轮后台同步发现 ~/.codex/auth.json 比 0600（或 ~/.codex 比 0700）宽时会自动收紧；同一天反复被放宽会提醒一次。打开后完全不碰权限。仅 macOS / Linux。
+                        </span>
+                    </div>
+                    <label className="toggle">
+                        <input
+                            type="checkbox"
+                            checked={settings.leave_auth_permissions ?? false}
+                            onChange={e => updateField('leave_auth_permissions', e.target.checked)}
+                        />
+                        <span className="toggle-slider"></span>
+                    </label>
+                </div>
+
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">日志中的账号标识</span>
+                        <span className="setting-desc">
+                            多人共用一台机器时使用：日志、活动流、auth.json 写入记录和诊断包里的账号名 / 邮箱按此处理。打码形如 wo…@co….com；哈希为固定的短哈希，同一账号在各行里一致，能对照但看不出原文。
+                        </span>
+                    </div>
+                    <select
+                        className="select-input"
+                        value={settings.log_identifier_style ?? 'full'}
+                        onChange={e => updateField('log_identifier_style', e.target.value as AppSettings['log_identifier_style'])}
+                    >
+                        <option value="full">完整</option>
+                        <option value="masked">打码</option>
+                        <option value="hashed">哈希</option>
+                    </select>
+                </div>
+
+                <div className="setting-item" style={{ alignItems: 'flex-start' }}>
+                    <div className="setting-info">
+                        <span className="setting-label">新账号备注模板</span>
+                        <span className="setting-desc">
+                            OAuth 登录、批量登录、导入当前登录新建账号时预填的备注。可用占位符：{'{email}'}、{'{plan}'}、{'{date}'}、{'{account_id}'}；留空则沿用默认备注。
+                        </span>
+                        {notesPreview !== null && (
+                            <pre className="setting-desc" style={{ whiteSpace: 'pre-wrap', margin: 0 }}>{notesPreview}</pre>
+                        )}
+                    </div>
+                    <div style={{ display: 'flex', flexDirection: 'column', gap: 6 }}>
+                        <textarea
+                            className="text-input"
+                            rows={3}
+                            value={settings.new_account_notes_template ?? ''}
+                            onChange={e => {
+                                updateField('new_account_notes_template', e.target.value);
+                                setNotesPreview(null);
+                            }}
+                            placeholder={'负责人：\n购买：{date}\n续费：'}
+                        />
+                        <button className="action-button" onClick={previewNotesTemplate}>
+                            预览
+                        </button>
+                    </div>
+                </div>
+
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">切号时应用账号的 Codex profile</span>
+                        <span className="setting-desc">
+                            切到绑定了 profile 的账号后，把 ~/.codex/config.toml 顶层的 profile 改成该名字（只改这一行，注释和其它配置保留）。在账号列表里点齿轮按钮绑定。
+                        </span>
+                    </div>
+                    <label className="toggle">
+                        <input
+                            type="checkbox"
+                            checked={settings.apply_profile_on_switch ?? false}
+                            onChange={e => updateField('apply_profile_on_switch', e.target.checked)}
+                        />
+                        <span className="toggle-slider"></span>
+                    </label>
+                </div>
+                {settings.apply_profile_on_switch && (
+                    <div className="setting-item sub-item">
+                        <div className="setting-info">
+                            <span className="setting-label">config.toml 不存在时新建</span>
+                            <span className="setting-desc">关闭（默认）时没有 config.toml 就跳过；开启后新建一个只含 profile 的最小文件</span>
+                        </div>
+                        <label className="toggle">
+                            <input
+                                type="checkbox"
+                                checked={settings.create_codex_config_if_missing ?? false}
+                                onChange={e => updateField('create_codex_config_if_missing', e.target.checked)}
+                            />
+                            <span className="toggle-slider"></span>
+                        </label>
+                    </div>
+                )}
+
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">Codex CLI 版本</span>
+                        <span className="setting-desc">
+                            用于判断各账号的 auth.json 能否被本机 Codex 正常读取。
+                            {codexCompat?.detected ? `检测到 ${codexCompat.detected}` : '未检测到 codex 命令'}
+                            {codexCompat?.source === 'override' && `，当前按手填的 ${codexCompat.version} 判断`}
+                            {settings.codex_version_override.trim() !== '' && codexCompat?.source === 'unknown' && '，手填的版本号无法解析'}
+                            ；留空则自动检测
+                        </span>
+                    </div>
+                    <div style={{ display: 'flex', gap: 6, alignItems: 'center' }}>
+                        <input
+                            type="text"
+                            className="text-input"
+                            value={settings.codex_version_override ?? ''}
+                            onChange={e => updateField('codex_version_override', e.target.value)}
+                            placeholder="自动检测"
+                        />
+                        <button className="action-button" onClick={() => loadCodexCompat(true)} disabled={detectingCodex}>
+                            {detectingCodex ? '检测中…' : '重新检测'}
+                        </button>
+                    </div>
+                </div>
+
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">配额请求 User-Agent</span>
+                        <span className="setting-desc">
+                            查询配额时使用的 User-Agent，适用于只放行白名单 UA 的工作区；账号自己配置的请求头优先。留空用默认值
+                        </span>
+                    </div>
+                    <input
+                        type="text"
+                        className="text-input"
+                        value={settings.usage_user_agent ?? ''}
+                        onChange={e => updateField('usage_user_agent', e.target.value)}
+                        placeholder="默认"
+                    />
+                </div>
+
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">切号后执行钩子</span>
+                        <span className="setting-desc">
+                            切号成功后按顺序执行下面的命令（不经 shell），环境变量 CODEX_SWITCHER_ACCOUNT_ID / _NAME / _EMAIL 为切到的账号，不含 token。钩子失败只提示，不回滚切号。
+                        </span>
+                    </div>
+                    <label className="toggle">
+                        <input
+                            type="checkbox"
+                            checked={settings.post_switch_hooks_enabled ?? true}
+                            onChange={e => updateField('post_switch_hooks_enabled', e.target.checked)}
+                        />
+                        <span className="toggle-slider"></span>
+                    </label>
+                </div>
+                {settings.post_switch_hooks_enabled && (
+                    <>
+                        {hooks.map((hook, i) => (
+                            <div className="setting-item sub-item" key={i}>
+                                <div className="setting-info" style={{ gap: 6 }}>
+                                    <input
+                                        type="text"
+                                        className="text-input"
+                                        value={hook.name}
+                                        onChange={e => updateHook(i, { name: e.target.value })}
+                                        placeholder="名称"
+                                    />
+                                    <input
+                                        type="text"
+                                        className="text-input"
+                                        style={{ fontFamily: 'monospace', fontSize: 12 }}
+                                        value={hook.command}
+                                        onChange={e => updateHook(i, { command: e.target.value })}
+                                        placeholder="命令，例 /usr/bin/touch"
+                                    />
+                                    <textarea
+                                        className="text-input"
+                                        style={{ fontFamily: 'monospace', fontSize: 12 }}
+                                        rows={2}
+                                        value={hook.args.join('\n')}
+                                        onChange={e => updateHook(i, { args: e.target.value.split('\n').filter(a => a !== '') })}
+                                        placeholder="参数，每行一个"
+                                    />
+                                </div>
+                                <div style={{ display: 'flex', gap: 6, alignItems: 'center' }}>
+                                    <input
+                                        type="number"
+                                        className="number-input"
+                                        min={1}
+                                        max={120}
+                                        title="超时（秒），1 ~ 120"
+                                        value={hook.timeout_secs}
+                                        onChange={e => updateHook(i, { timeout_secs: parseInt(e.target.value) || 30 })}
+                                    />
+                                    <label className="toggle">
+                                        <input
+                                            type="checkbox"
+                                            checked={hook.enabled}
+                                            onChange={e => updateHook(i, { enabled: e.target.checked })}
+                                        />
+                                        <span className="toggle-slider"></span>
+                                    </label>
+                                    <button className="action-button" onClick={() => removeHook(i)}>
+                                        删除
+                                    </button>
+                                </div>
+                            </div>
+                        ))}
+                        <div className="setting-item sub-item">
+                            <div className="setting-info">
+                                <span className="setting-desc">
+                                    最多 {MAX_SWITCH_HOOKS} 个，超时 1 ~ 120 秒；输出各保留前 4 KB。预览按已保存的设置和当前账号计算。
+                                </span>
+                            </div>
+                            <div style={{ display: 'flex', gap: 6 }}>
+                                <button
+                                    className="action-button"
+                                    onClick={addHook}
+                                    disabled={hooks.length >= MAX_SWITCH_HOOKS}
+                                >
+                                    添加钩子
+                                </button>
+                                <button className="action-button" onClick={previewHooks} disabled={hooks.length === 0}>
+                                    预览
+                                </button>
+                            </div>
+                        </div>
+                        {hookPlan && (
+                            <div className="setting-item sub-item">
+                                <div className="setting-info">
+                                    {hookPlan.length === 0 && <span className="setting-desc">没有配置钩子</span>}
+                                    {hookPlan.map((p, i) => (
+                                        <span className="setting-desc" key={i} style={{ fontFamily: 'monospace', fontSize: 12 }}>
+                                            {p.will_run ? '▶' : '⏸'} {p.name}: {[p.command, ...p.args].join(' ')}（{p.timeout_secs}s）
+                                            {i === 0 && ` · ${p.env.map(([k, v]) => `${k}=${v}`).join(' ')}`}
+                                        </span>
+                                    ))}
+                                </div>
+                            </div>
+                        )}
+                    </>
+                )}
+
                 {
                     settings.background_refresh && settings.remote_mode !== 'client' && (
                         <>
                             <div className="setting-item sub-item">
                                 <div className="setting-info">
                                     <span className="setting-label">调度间隔（分钟）</span>
+                                    <span className="setting-desc">5 ~ 1440，超出范围保存时自动调整</span>
                                 </div>
                                 <input
                                     type="number"
                                     className="number-input"
                                     min={5}
-                                    max={120}
+                                    max={1440}
                                     value={settings.refresh_interval_minutes}
                                     onChange={e => updateField('refresh_interval_minutes', parseInt(e.target.value) || 30)}
                                 />
@@ -407,9 +1271,131 @@ export function Settings({ accounts = [], onSetSessionAnchor }: SettingsProps =
                                     onChange={e => updateField('inactive_refresh_days', parseInt(e.target.value) || 7)}
                                 />
                             </div>
+                            <div className="setting-item sub-item">
+                                <div className="setting-info">
+                                    <span className="setting-label">操作时让路（秒）</span>
+                                    <span className="setting-desc">主窗口打开且这段时间内有手动操作时，调度器推迟本轮同步；刚操作过的账号下一轮不保活。0 = 不让路</span>
+                                </div>
+                                <input
+                                    type="number"
+                                    className="number-input"
+                                    min={0}
+                                    max={600}
+                                    value={settings.scheduler_quiet_secs}
+                                    onChange={e => updateField('scheduler_quiet_secs', Math.max(0, parseInt(e.target.value) || 0))}
+                                />
+                            </div>
                         </>
                     )
                 }
+
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">每日额度报告</span>
+                        <span className="setting-desc">每天定时发一条系统通知汇总所有账号剩余额度；错过时间 2 小时内启动会补发（保存后生效）</span>
+                    </div>
+                    <label className="toggle">
+                        <input
+                            type="checkbox"
+                            checked={settings.daily_report_enabled}
+                            onChange={e => updateField('daily_report_enabled', e.target.checked)}
+                        />
+                        <span className="toggle-slider"></span>
+                    </label>
+                </div>
+                {settings.daily_report_enabled && (
+                    <div className="setting-item sub-item">
+                        <div className="setting-info">
+                            <span className="setting-label">报告时间</span>
+                        </div>
+                        <input
+                            type="time"
+                            className="number-input"
+                            value={settings.daily_report_time}
+                            onChange={e => updateField('daily_report_time', e.target.value || '09:00')}
+                        />
+                    </div>
+                )}
+
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">额度缓存过期阈值（小时）</span>
+                        <span className="setting-desc">切号预检失败时，缓存超过这么多小时的额度标为"数据可能过期"，下次刷新成功后恢复</span>
+                    </div>
+                    <input
+                        type="number"
+                        className="number-input"
+                        min={1}
+                        max={720}
+                        value={settings.quota_suspect_after_hours}
+                        onChange={e => updateField('quota_suspect_after_hours', Math.max(1, parseInt(e.target.value) || 24))}
+                    />
+                </div>
+
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">access_token 快过期提示（分钟）</span>
+                        <span className="setting-desc">账号的 access_token 剩余不到这么多分钟时在列表上标"AT 快过期"；只是提示，不会因此刷新。0 = 不标</span>
+                    </div>
+                    <input
+                        type="number"
+                        className="number-input"
+                        min={0}
+                        max={1440}
+                        value={settings.token_expiring_soon_minutes}
+                        onChange={e => updateField('token_expiring_soon_minutes', Math.max(0, parseInt(e.target.value) || 0))}
+                    />
+                </div>
+
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">refresh_token 闲置预警（天）</span>
+                        <span className="setting-desc">账号的 refresh_token 超过这么多天没用过时提醒一次，避免被服务端判定闲置失效。0 = 关闭</span>
+                    </div>
+                    <input
+                        type="number"
+                        className="number-input"
+                        min={0}
+                        max={365}
+                        value={settings.refresh_token_aging_days}
+                        onChange={e => updateField('refresh_token_aging_days', Math.max(0, parseInt(e.target.value) || 0))}
+                    />
+                </div>
+                {settings.refresh_token_aging_days > 0 && (
+                    <div className="setting-item sub-item">
+                        <div className="setting-info">
+                            <span className="setting-label">自动保活</span>
+                            <span className="setting-desc">闲置超过阈值且能在本机刷新的账号直接刷新一次；关闭时只提醒</span>
+                        </div>
+                        <label className="toggle">
+                            <input
+                                type="checkbox"
+                                checked={settings.aging_auto_keepalive}
+                                onChange={e => updateField('aging_auto_keepalive', e.target.checked)}
+                            />
+                            <span className="toggle-slider"></span>
+                        </label>
+                    </div>
+                )}
+                {agingTokens.map(token => (
+                    <div className="setting-item sub-item" key={token.id}>
+                        <div className="setting-info">
+                            <span className="setting-label">{token.name}</span>
+                            <span className="setting-desc">
+                                {token.idle_days} 天未活动{token.keepalive === 'unavailable' ? '，本机无法刷新该账号' : '，建议手动刷新一次'}
+                            </span>
+                        </div>
+                        {token.keepalive !== 'unavailable' && (
+                            <button
+                                className="action-button"
+                                onClick={() => keepaliveAccount(token)}
+                                disabled={keepaliveBusy !== null}
+                            >
+                                {keepaliveBusy === token.id ? '刷新中...' : '刷新一次'}
+                            </button>
+                        )}
+                    </div>
+                ))}
             </div >
 
             <div className="settings-section">
@@ -694,7 +1680,7 @@ export function Settings({ accounts = [], onSetSessionAnchor }: SettingsProps =
                                         📱 {anchorAccount.name}
                                         <span className={`anchor-current-plan plan-${(anchorAccount.cached_quota?.plan_type || 'unknown').toLowerCase()}`}>
                                             {anchorAccount.cached_quota?.plan_type
-                                                ? anchorAccount.cached_quota.plan_type.toUpperCase()
+                                                ? anchorAccount.cached_quota.display_plan || anchorAccount.cached_quota.plan_type.toUpperCase()
                                                 : '未知'}
                                         </span>
                                     </>
@@ -721,8 +1707,87 @@ export function Settings({ accounts = [], onSetSessionAnchor }: SettingsProps =
                 </div>
             )}
 
+            <div className="settings-section">
+                <h3><KeyRound size={16} /> Token 存储</h3>
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">存入系统凭据库</span>
+                        <span className="setting-desc">refresh_token 改存 macOS 钥匙串 / Windows 凭据管理器 / Secret Service，accounts.json 里只留引用；切换时立即迁移</span>
+                    </div>
+                    <label className="toggle">
+                        <input
+                            type="checkbox"
+                            checked={tokenStorage?.storage === 'keychain'}
+                            disabled={!tokenStorage || migratingTokens}
+                            onChange={e => migrateTokenStorage(e.target.checked ? 'keychain' : 'file', tokenStorage?.include_access_tokens ?? false)}
+                        />
+                        <span className="toggle-slider"></span>
+                    </label>
+                </div>
+                {tokenStorage?.storage === 'keychain' && (
+                    <div className="setting-item sub-item">
+                        <div className="setting-info">
+                            <span className="setting-label">连同 access / id token 一起存</span>
+                            <span className="setting-desc">默认只存 refresh_token</span>
+                        </div>
+                        <label className="toggle">
+                            <input
+                                type="checkbox"
+                                checked={tokenStorage.include_access_tokens}
+                                disabled={migratingTokens}
+                                onChange={e => migrateTokenStorage('keychain', e.target.checked)}
+                            />
+                            <span className="toggle-slider"></span>
+                        </label>
+                    </div>
+                )}
+                {tokenStorage && (tokenStorage.unresolved_accounts.length > 0 || tokenStorage.last_error) && (
+                    <div className="setting-item sub-item">
+                        <div className="setting-info">
+                            <span className="setting-label">凭据库异常</span>
+                            <span className="setting-desc">
+                                {tokenStorage.last_error && `${tokenStorage.last_error.code}: ${tokenStorage.last_error.detail}`}
+                                {tokenStorage.unresolved_accounts.length > 0 && ` 以下账号的 token 暂时读不出来: ${tokenStorage.unresolved_accounts.join(', ')}`}
+                            </span>
+                        </div>
+                        <button className="action-button" onClick={loadTokenStorage}>
+                            重试
+                        </button>
+                    </div>
+                )}
+            </div>
+
             <div className="settings-section danger">
                 <h3><Wrench size={16} /> 故障修复</h3>
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">一键体检修复</span>
+                        <span className="setting-desc">依次检查文件权限、当前账号指针、auth.json 的 refresh_token、后台调度器和残留文件；先检查看看会做什么，再决定是否修复。需要管理员权限或涉及手机锚的只给出操作说明</span>
+                    </div>
+                    <div style={{ display: 'flex', gap: 6 }}>
+                        <button className="action-button" onClick={() => runRepairSetup(true)} disabled={repairRunning}>
+                            检查
+                        </button>
+                        <button
+                            className="action-button warning"
+                            onClick={() => runRepairSetup(false)}
+                            disabled={repairRunning || !repairReports?.some(r => r.would_fix)}
+                        >
+                            {repairRunning ? '处理中...' : '修复'}
+                        </button>
+                    </div>
+                </div>
+                {repairReports && (
+                    <div className="setting-item sub-item">
+                        <div className="setting-info">
+                            {repairReports.map(r => (
+                                <span className="setting-desc" key={r.step}>
+                                    <strong>{r.step}</strong> {repairStatusText(r)}
+                                </span>
+                            ))}
+                        </div>
+                    </div>
+                )}
                 <div className="setting-item">
                     <div className="setting-info">
                         <span className="setting-label">修复 Codex App 闪退</span>
@@ -736,8 +1801,141 @@ export function Settings({ accounts = [], onSetSessionAnchor }: SettingsProps =
                         {repairing ? '修复中...' : '立即修复'}
                     </button>
                 </div>
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">导出诊断包</span>
+                        <span className="setting-desc">打包脱敏后的账号摘要、设置、运行状态和最近日志（不含任何 token），提 issue 时附上</span>
+                    </div>
+                    <button
+                        className="action-button"
+                        onClick={handleExportDiagnostics}
+                        disabled={exportingDiagnostics}
+                    >
+                        {exportingDiagnostics ? '导出中...' : '导出'}
+                    </button>
+                </div>
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">auth.json 写入记录</span>
+                        <span className="setting-desc">每次写 ~/.codex/auth.json 的时间、来源和 token 指纹（access/refresh 的哈希前缀）；"外部修改" 表示是 Codex CLI 等其他程序改的</span>
+                    </div>
+                    <button className="action-button" onClick={handleToggleAuthJournal}>
+                        {authJournal ? '收起' : '查看'}
+                    </button>
+                </div>
+                {authJournal && authJournal.length === 0 && (
+                    <div className="setting-item sub-item">
+                        <div className="setting-info">
+                            <span className="setting-desc">暂无记录</span>
+                        </div>
+                    </div>
+                )}
+                {authJournal?.map((entry, i) => (
+                    <div className="setting-item sub-item" key={`${entry.at}-${i}`}>
+                        <div className="setting-info">
+                            <span className="setting-label">
+                                {AUTH_WRITE_OP_LABELS[entry.op] ?? entry.op}
+                                {entry.account_id && ` · ${accounts.find(a => a.id === entry.account_id)?.name ?? entry.account_id}`}
+                            </span>
+                            <span className="setting-desc">
+                                {new Date(entry.at).toLocaleString()} · {fingerprint(entry.before)} → {fingerprint(entry.after)}
+                            </span>
+                        </div>
+                    </div>
+                ))}
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">校正工作区 ID</span>
+                        <span className="setting-desc">Team 账号显示成个人额度时使用：按 id_token 里的 chatgpt_account_id 重新推导每个账号的工作区 ID，先列出差异再确认修正</span>
+                    </div>
+                    <button
+                        className="action-button"
+                        onClick={handleReconcileAccountIds}
+                        disabled={reconciling}
+                    >
+                        {reconciling ? '检查中...' : '检查'}
+                    </button>
+                </div>
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">识别 auth.json</span>
+                        <span className="setting-desc">粘贴别人发来的 auth.json，看它是哪个已存账号、token 是否比本地新（只读，不会保存或写盘）</span>
+                    </div>
+                    <button className="action-button" onClick={() => setShowIdentify(true)}>
+                        识别
+                    </button>
+                </div>
+                <div className="setting-item">
+                    <div className="setting-info">
+                        <span className="setting-label">退出 Codex 登录</span>
+                        <span className="setting-desc">把电脑交给别人前使用：先同步当前账号的最新 Token，再删除 ~/.codex/auth.json，不删除任何已保存账号</span>
+                    </div>
+                    <button
+                        className="action-button"
+                        onClick={handleLogoutCodex}
+                        disabled={loggingOut}
+                    >
+                        {loggingOut ? '退出中...' : '退出'}
+                    </button>
+                </div>
             </div>
 
+            {showIdentify && (
+                <div className="modal-overlay" onClick={() => setShowIdentify(false)}>
+                    <div className="anchor-picker" onClick={e => e.stopPropagation()}>
+                        <div className="anchor-picker-header">
+                            <div>
+                                <h3>识别 auth.json</h3>
+                                <p className="anchor-picker-hint">兼容旧版格式；内容只在本机比对，不会保存</p>
+                            </div>
+                            <button className="anchor-picker-close" onClick={() => setShowIdentify(false)} title="关闭">
+                                <X size={16} />
+                            </button>
+                        </div>
+                        <div className="anchor-picker-list">
+                            <textarea
+                                value={identifyText}
+                                onChange={e => setIdentifyText(e.target.value)}
+                                rows={8}
+                                placeholder='{"tokens": {"id_token": "eyJ...", "access_token": "eyJ...", ...}}'
+                                style={{ fontFamily: 'ui-monospace, Menlo, monospace', fontSize: 12, width: '100%' }}
+                                autoFocus
+                            />
+                            <button className="action-button" onClick={handleIdentify} disabled={!identifyText.trim()}>
+                                识别
+                            </button>
+                            {identifyErrors.map(err => (
+                                <p key={err.field} className="setting-desc">❌ {err.field}: {err.message}</p>
+                            ))}
+                            {identifyResult && (
+                                <div className="setting-desc">
+                                    <p>
+                                        {identifyResult.matched_account
+                                            ? `✅ 匹配账号：${identifyResult.matched_account.name}${identifyResult.matched_account.is_current ? '（当前账号）' : ''}`
+                                            : '⚠️ 没有匹配的已存账号'}
+                                    </p>
+                                    <p>邮箱：{identifyResult.parsed_identity.email ?? '—'} · 套餐：{identifyResult.parsed_identity.plan ?? '—'}</p>
+                                    <p>account_id：{identifyResult.parsed_identity.account_id ?? '—'}</p>
+                                    <p>格式：{AUTH_SHAPE_LABELS[identifyResult.parsed_identity.shape]}</p>
+                                    {identifyResult.parsed_identity.access_token_expires_at && (
+                                        <p>access_token 过期：{new Date(identifyResult.parsed_identity.access_token_expires_at).toLocaleString()}</p>
+                                    )}
+                                    {identifyResult.refresh_token_matches_stored !== null && (
+                                        <p>refresh_token：{identifyResult.refresh_token_matches_stored ? '与本地一致' : '与本地不同'}</p>
+                                    )}
+                                    {identifyResult.token_fresher_than_stored !== null && (
+                                        <p>token：{identifyResult.token_fresher_than_stored ? '比本地新' : '不比本地新'}</p>
+                                    )}
+                                    {identifyResult.parsed_identity.warnings.map(w => (
+                                        <p key={w.field}>⚠️ {w.field}: {w.message}</p>
+                                    ))}
+                                </div>
+                            )}
+                        </div>
+                    </div>
+                </div>
+            )}
+
             {showAnchorPicker && (
                 <div className="modal-overlay" onClick={() => !anchorBusy && setShowAnchorPicker(false)}>
                     <div className="anchor-picker" onClick={e => e.stopPropagation()}>
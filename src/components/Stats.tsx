@@ -1,9 +1,10 @@
 import { useState, useEffect } from 'react';
-import { invoke } from '@tauri-apps/api/core';
+import { invoke } from '../ipc';
 import {
     AreaChart, Area, BarChart, Bar, PieChart, Pie, Cell,
     XAxis, YAxis, CartesianGrid, Tooltip, ResponsiveContainer, Legend
 } from 'recharts';
+import { ActivityFeed } from './ActivityFeed';
 import './Stats.css';
 
 interface TokenHistoryEntry {
@@ -86,6 +87,7 @@ interface AccountTokenHistory {
     last_week: CycleDetail | null;
     cycles_5h: CycleDetail[];
     cycles_week: CycleDetail[];
+    exhaustion_estimate: { minutes_remaining: number; confidence: number } | null;
 }
 
 const COLORS = ['#8b5cf6', '#10b981', '#f59e0b', '#ef4444', '#3b82f6', '#ec4899', '#14b8a6', '#f97316'];
@@ -303,6 +305,14 @@ export function Stats() {
                                             {acc.is_token_invalid && <span className="quota-badge invalid">失效</span>}
                                             <span className={`quota-plan plan-${(acc.plan_type || 'unknown').toLowerCase()}`}>{acc.plan_type || '—'}</span>
                                             <span className="acct-email-text">{acc.email}</span>
+                                            {acc.exhaustion_estimate && (
+                                                <span
+                                                    className="quota-badge exhausting"
+                                                    title={`按最近 60 分钟的用量速率估算，置信度 ${Math.round(acc.exhaustion_estimate.confidence * 100)}%`}
+                                                >
+                                                    约 {acc.exhaustion_estimate.minutes_remaining} 分钟耗尽
+                                                </span>
+                                            )}
                                         </span>
                                         <CellPair cycle={acc.current_5h} />
                                         <CellPair cycle={acc.last_5h} />
@@ -473,6 +483,9 @@ export function Stats() {
                     </div>
                 </div>
             )}
+
+            {/* 活动记录 */}
+            <ActivityFeed />
         </div>
     );
 }
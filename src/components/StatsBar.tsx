@@ -1,5 +1,5 @@
 import { useState, useEffect } from 'react';
-import { invoke } from '@tauri-apps/api/core';
+import { invoke } from '../ipc';
 import { listen } from '@tauri-apps/api/event';
 import { UsageDisplay } from '../hooks/useUsage';
 import './StatsBar.css';
@@ -1,7 +1,8 @@
 import { useState, useEffect } from 'react';
-import { invoke } from '@tauri-apps/api/core';
+import { invoke } from '../ipc';
 import { listen } from '@tauri-apps/api/event';
 import { getCurrentWebviewWindow } from '@tauri-apps/api/webviewWindow';
+import { fetchAllAccounts } from '../hooks/useAccounts';
 // Rust 端 on_window_event(Focused(false)) 负责隐藏弹窗
 import './TrayPopup.css';
 
@@ -11,6 +12,8 @@ interface QuotaInfo {
     weekly_left: number;
     weekly_reset_at: number | null;
     plan_type: string;
+    display_plan?: string;
+    codex_available?: boolean;
 }
 
 interface AccountInfo {
@@ -78,16 +81,19 @@ function statusLabel(pct: number): string {
 export function TrayPopup() {
     const [data, setData] = useState<TrayData | null>(null);
     const [switching, setSwitching] = useState(false);
+    const [presentationMode, setPresentationMode] = useState(false);
 
     const fetchData = async () => {
         try {
-            const [proxy, tokens] = await Promise.all([
+            const [proxy, tokens, presentation] = await Promise.all([
                 invoke<ProxyStatus>('get_proxy_status'),
                 invoke<TokenStats>('get_token_stats'),
+                invoke<boolean>('get_presentation_mode'),
             ]);
+            setPresentationMode(presentation);
 
             // Get current account info from accounts list
-            const accounts = await invoke<any[]>('get_accounts');
+            const accounts: any[] = (await fetchAllAccounts()).accounts;
             const currentId = await invoke<string | null>('get_current_account_id');
             const account = currentId ? accounts.find((a: any) => a.id === currentId) : null;
             const anchorAcc = accounts.find((a: any) => a.is_session_anchor);
@@ -132,9 +138,9 @@ export function TrayPopup() {
     const handleSwitch = async () => {
         setSwitching(true);
         try {
-            await invoke('switch_to_next_account_internal_cmd');
-        } catch {
-            // fallback: try tray's method
+            await invoke('switch_to_next_account');
+        } catch (e) {
+            console.error('Switch failed:', e);
         }
         await fetchData();
         setSwitching(false);
@@ -145,7 +151,7 @@ export function TrayPopup() {
             const currentId = await invoke<string | null>('get_current_account_id');
             if (!currentId) return;
             // Relay 账号走 refresh_relay_usage（GLM 等），订阅号走 OpenAI usage 路径。
-            const accounts = await invoke<Array<{ id: string; kind?: string }>>('get_accounts');
+            const { accounts } = await fetchAllAccounts();
             const acc = accounts.find(a => a.id === currentId);
             const isRelay = (acc?.kind ?? '').toLowerCase() === 'relay';
             if (isRelay) {
@@ -188,10 +194,11 @@ export function TrayPopup() {
             {data?.account && (
                 <div className="tp-account">
                     {data.account.name}
-                    <span className="tp-plan">{q?.plan_type || '-'}</span>
+                    <span className="tp-plan">{q?.display_plan || q?.plan_type || '-'}</span>
                     {data.account.is_banned && <span className="tp-banned">封号</span>}
                     {data.account.is_logged_out && !data.account.is_banned && <span className="tp-logged-out">登出</span>}
                     {data.account.is_token_invalid && !data.account.is_banned && !data.account.is_logged_out && <span className="tp-invalid">失效</span>}
+                    {q?.codex_available === false && <span className="tp-no-codex">无 Codex</span>}
                 </div>
             )}
 
@@ -294,7 +301,8 @@ export function TrayPopup() {
                 <button
                     className="tp-btn accent"
                     onClick={handleSwitch}
-                    disabled={switching}
+                    disabled={switching || presentationMode}
+                    title={presentationMode ? '演示模式下不能切号' : undefined}
                 >
                     {switching ? '...' : '→ Switch'}
                 </button>
@@ -1,4 +1,4 @@
-import { UsageDisplay } from '../hooks/useUsage';
+import { UsageDisplay, formatLeft } from '../hooks/useUsage';
 import { useCountdown } from '../hooks/useCountdown';
 import './UsageCard.css';
 
@@ -45,7 +45,7 @@ export function UsageCard({ usage, loading, error, onRefresh }: UsageCardProps)
             <div className="usage-row">
                 <span className="usage-label">{isFree ? '限额' : '5h 配额'}</span>
                 <span className="usage-reset">{fiveHourTimeLeft || usage.five_hour_reset}</span>
-                <span className="usage-percent">{usage.five_hour_left}%</span>
+                <span className="usage-percent">{formatLeft(usage.five_hour_left, usage.five_hour_counts)}</span>
             </div>
             <div className="meter-bar">
                 <div
@@ -60,7 +60,7 @@ export function UsageCard({ usage, loading, error, onRefresh }: UsageCardProps)
                     <div className="usage-row">
                         <span className="usage-label">周配额</span>
                         <span className="usage-reset">{weeklyTimeLeft || usage.weekly_reset}</span>
-                        <span className="usage-percent">{usage.weekly_left}%</span>
+                        <span className="usage-percent">{formatLeft(usage.weekly_left, usage.weekly_counts)}</span>
                     </div>
                     <div className="meter-bar">
                         <div
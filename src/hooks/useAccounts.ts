@@ -1,5 +1,6 @@
-import { useState, useEffect, useCallback } from 'react';
-import { invoke } from '@tauri-apps/api/core';
+import { useState, useEffect, useCallback, useRef } from 'react';
+import { invoke } from '../ipc';
+import type { WindowCounts } from './useUsage';
 
 export interface CachedQuota {
     five_hour_left: number;
@@ -12,7 +13,251 @@ export interface CachedQuota {
     weekly_label?: string;
     plan_type: string;
     is_valid_for_cli?: boolean;
+    /** 套餐是否包含 Codex；false 时配额字段无意义 */
+    codex_available?: boolean;
     updated_at: string;
+    /** plan_type 的展示名；旧缓存为空时回退到原始值 */
+    display_plan?: string;
+    /** 这份缓存是哪条路径写的 */
+    source?: QuotaSource;
+    /** 5H / 周窗口的绝对次数（接口给了才有） */
+    five_hour_counts?: WindowCounts;
+    weekly_counts?: WindowCounts;
+    /** 切号预检失败且缓存已旧：数据可能过期，下次拉取成功即清除 */
+    suspect?: boolean;
+}
+
+export type QuotaSource = 'manual_refresh' | 'bulk_refresh' | 'switch_precheck' | 'scheduler' | 'offline_cache';
+
+export const QUOTA_SOURCE_LABELS: Record<QuotaSource, string> = {
+    manual_refresh: '手动刷新',
+    bulk_refresh: '批量刷新',
+    switch_precheck: '切号预检',
+    scheduler: '后台刷新',
+    offline_cache: 'Server 缓存',
+};
+
+/** 耗尽的窗口；与后端 `quota_window::classify` 同一规则：额度为 0 且还没到重置时间 */
+export interface QuotaExhaustion {
+    window: 'five_hour' | 'weekly';
+    reset_at?: number;
+}
+
+/** 两个窗口都耗尽时周额度在后（它才是卡住账号的那个） */
+export function quotaExhaustions(q: CachedQuota, nowSecs = Date.now() / 1000): QuotaExhaustion[] {
+    const exhausted = (left: number, resetAt?: number) => left <= 0 && !(resetAt != null && nowSecs >= resetAt);
+    const out: QuotaExhaustion[] = [];
+    if (exhausted(q.five_hour_left, q.five_hour_reset_at)) out.push({ window: 'five_hour', reset_at: q.five_hour_reset_at });
+    if (exhausted(q.weekly_left, q.weekly_reset_at)) out.push({ window: 'weekly', reset_at: q.weekly_reset_at });
+    return out;
+}
+
+export type SwitchStage =
+    | 'syncing_current'
+    | 'prechecking'
+    | 'acquiring_lock'
+    | 'writing_auth'
+    | 'reloading'
+    | 'done'
+    | { failed: { stage: SwitchStage; error: string } };
+
+/** `switch-progress` 事件负载；switch_seq 小于已见过的说明是被取代的旧切号 */
+export interface SwitchProgress {
+    switch_seq: number;
+    target_id: string;
+    stage: SwitchStage;
+    elapsed_ms: number;
+    /** 只在 done 上出现：没挡住切号的警告 */
+    warnings?: OpWarning[];
+}
+
+export type WarningCode =
+    | 'sync_identity_mismatch'
+    | 'sync_email_mismatch'
+    | 'token_refresh_failed'
+    | 'precheck_failed'
+    | 'precheck_skipped'
+    | 'auth_write_skipped'
+    | 'candidate_skipped'
+    | 'solo_push_failed';
+
+/** 流程里没做成但不算失败的判断（后端 `warnings::Warning`） */
+export interface OpWarning {
+    code: WarningCode;
+    params?: Record<string, string>;
+}
+
+/** `_v2` 命令的返回信封 */
+export interface Warned<T> {
+    value: T;
+    warnings: OpWarning[];
+}
+
+export function describeWarning(w: OpWarning): string {
+    const p = w.params ?? {};
+    switch (w.code) {
+        case 'sync_identity_mismatch':
+            return `未同步 ${p.account}：auth.json 身份不匹配`;
+        case 'sync_email_mismatch':
+            return `未同步 ${p.account}：token 邮箱是 ${p.token_email}`;
+        case 'token_refresh_failed':
+            return `${p.account} 刷新 Token 失败（${p.error}）`;
+        case 'precheck_failed':
+            return `${p.account} 预检配额失败（${p.error}）`;
+        case 'precheck_skipped':
+            return `${p.account} 的 Token 已过期，跳过配额预检`;
+        case 'auth_write_skipped':
+            return `手机锚 ${p.anchor} 生效，未写 auth.json`;
+        case 'candidate_skipped':
+            return p.error ? `跳过 ${p.account}（额度查询失败）` : `跳过 ${p.account}（额度已耗尽）`;
+        case 'solo_push_failed':
+            return `未同步到 Server（${p.error}）`;
+    }
+}
+
+export const SWITCH_STAGE_LABELS: Record<Exclude<SwitchStage, object>, string> = {
+    syncing_current: '同步当前账号',
+    prechecking: '预检配额',
+    acquiring_lock: '等待刷新锁',
+    writing_auth: '写入 auth.json',
+    reloading: '通知代理重连',
+    done: '完成',
+};
+
+/** 单个切号钩子的执行结果（输出已截断到 4 KB） */
+export interface HookReport {
+    name: string;
+    success: boolean;
+    exit_code: number | null;
+    timed_out: boolean;
+    error: string | null;
+    stdout: string;
+    stderr: string;
+    output_truncated: boolean;
+    duration_ms: number;
+}
+
+/** `switch_account` 返回值：切号已成功，钩子结果另报 */
+export interface SwitchResult {
+    hooks: HookReport[];
+    /** 目标账号的 auth.json 可能与本机 Codex 版本不兼容（不阻止切号） */
+    compatibility_warning: string | null;
+}
+
+/** 账号 auth.json 与本机 Codex CLI 版本的兼容性 */
+export type CompatibilityVerdict =
+    | { status: 'compatible' }
+    | { status: 'needs_field'; field: string; note: string }
+    | { status: 'unsupported_field'; field: string; note: string }
+    | { status: 'unknown_version' };
+
+/** `get_codex_compatibility` 的返回 */
+export interface CodexCompatibilityReport {
+    version: string | null;
+    source: 'override' | 'detected' | 'unknown';
+    detected: string | null;
+    accounts: Record<string, CompatibilityVerdict>;
+}
+
+/** `get_account_statuses` 的一项：access_token 的过期时间与新鲜度（只读，不触发刷新） */
+export interface TokenStatus {
+    expires_at: string | null;
+    last_refresh: string | null;
+    token_state: 'fresh' | 'expiring_soon' | 'expired' | 'unknown';
+}
+
+/** `detect_export_format`：粘贴的导出内容是明文、加密信封还是都不是 */
+export type ExportFormat = 'plaintext' | 'encrypted' | 'unknown';
+
+/** `verify_all_accounts` 的一项：存档 token 和账号声称的身份对不上的地方（只读检查） */
+export type IntegrityIssue =
+    | { code: 'missing_refresh_token' }
+    | { code: 'missing_id_token' }
+    | { code: 'undecodable_token'; token: string; error: string }
+    | { code: 'account_id_mismatch'; token: string; stored: string | null; claimed: string }
+    | { code: 'subject_mismatch'; id_token: string; access_token: string }
+    | { code: 'email_mismatch'; stored: string; claimed: string }
+    | { code: 'name_mismatch'; name: string; claimed: string };
+
+/** 删号 / 替换式导入返回的撤销凭据，expires_in_secs 内可调 `undo` */
+export interface UndoReceipt {
+    undo_token: string;
+    kind: 'delete' | 'import_replace';
+    account_ids: string[];
+    expires_in_secs: number;
+}
+
+/** `import_accounts` 的导入方式：整库替换，或并入现有账号 */
+export type ImportMode = 'replace' | 'merge';
+
+/** 合并式导入里单个账号的结果 */
+export type MergeEntry = { name: string } & (
+    | { status: 'added'; account_id: string }
+    | { status: 'updated'; account_id: string }
+    | { status: 'skipped'; reason: string }
+);
+
+/** `import_accounts` 的返回 */
+export interface ImportResult {
+    mode: ImportMode;
+    /** 按账号 id 计的前后差异；removed 只有替换式才有，skipped 只有合并式才有 */
+    summary: {
+        added: number;
+        updated: number;
+        unchanged: number;
+        removed: number;
+        skipped: number;
+    };
+    /** 替换式导入才有 */
+    undo: UndoReceipt | null;
+    /** 替换前留的账号库备份 */
+    backup_path: string | null;
+    /** 合并式导入才有 */
+    merge: {
+        added: number;
+        updated: number;
+        skipped: number;
+        accounts: MergeEntry[];
+    } | null;
+}
+
+/** `list_store_backups` 的一项 */
+export interface StoreBackup {
+    path: string;
+    file_name: string;
+    /** import：替换式导入前；scheduled：每日自动备份 */
+    kind: 'import' | 'scheduled';
+    created_at: string;
+    size_bytes: number;
+}
+
+/** `delete_accounts` 的返回 */
+export interface BulkDeleteOutcome {
+    removed: string[];
+    skipped: { id: string; reason: string }[];
+    /** 删掉了账号时才有 */
+    undo: UndoReceipt | null;
+}
+
+/** `import-progress` 事件负载 */
+export interface ImportProgress {
+    job_id: string;
+    done: number;
+    total: number;
+    current_name: string | null;
+    errors_so_far: number;
+}
+
+/** `import-complete` 事件负载；cancelled 时已并入的账号保留 */
+export interface ImportReport {
+    job_id: string;
+    total: number;
+    processed: number;
+    added: number;
+    updated: number;
+    skipped: number;
+    errors: { name: string; reason: string }[];
+    cancelled: boolean;
 }
 
 export interface AppSettings {
@@ -33,6 +278,8 @@ export interface KeepaliveState {
     last_attempt_at: string | null;
     last_success_at: string | null;
     last_error: string | null;
+    /** 刷新禁令：refresh token 被判定重复使用后挂上，重新登录才解除 */
+    refresh_ban?: { banned_at: string; reason: string } | null;
 }
 
 export interface SyncStatus {
@@ -40,6 +287,8 @@ export interface SyncStatus {
     disk_email: string | null;
     matching_id: string | null;
     current_id: string | null;
+    /** 演示模式：账号信息已打码、修改类命令被拒 */
+    presentation_mode: boolean;
 }
 
 export type AccountKind = 'legacy' | 'chatgpt_oauth' | 'openai_key' | 'relay';
@@ -55,9 +304,13 @@ export interface RelayUsageCache {
 export interface Account {
     id: string;
     name: string;
+    /** 登录邮箱（取自 id_token）；中转站账号没有 */
+    email?: string | null;
     auth_json: unknown;
     created_at: string;
     last_used: string | null;
+    /** 累计切到这个号的次数 */
+    switch_count?: number;
     notes: string | null;
     cached_quota: CachedQuota | null;
     keepalive: KeepaliveState;
@@ -77,6 +330,172 @@ export interface Account {
     relay_category?: 'aggregator' | 'coding_plan' | 'third_party' | null;
     /** 手机锚（Codex.app 手机远程连接绑定）。整个 store 强约束最多一个 true。 */
     is_session_anchor?: boolean;
+    /** 颜色标签 #RRGGBB */
+    color?: string | null;
+    /** 头像 emoji，显示在名字前 */
+    avatar_emoji?: string | null;
+    /** 最近一次检测到的套餐变更 */
+    last_plan_change?: PlanChange | null;
+    /** 切到此号时写进 ~/.codex/config.toml 的 profile */
+    codex_profile?: string | null;
+    /** 查询配额时附加的请求头（覆盖默认值和全局 User-Agent） */
+    request_headers?: Record<string, string>;
+    /** 分组标签（个人 / 工作 / 团队共用……） */
+    tags?: string[];
+    /** 键值备注（键已规范成小写），列表里显示成小标签 */
+    metadata?: Record<string, string>;
+    /** 置顶 */
+    pinned?: boolean;
+    /** 受保护：不能删除、不能被手动同步覆盖 token；取消保护需二次确认 */
+    protected?: boolean;
+    /** 已归档：不能切、不参与自动选号和批量刷新，列表里单独分组 */
+    archived?: boolean;
+    /** 手动排序位置，小的在前；没排过的按新建时间排在后面 */
+    sort_index?: number | null;
+    /** 最近修改时间；老账号没有，按 created_at 算 */
+    updated_at?: string | null;
+}
+
+/** `get_accounts` 的返回；超过上限时 truncated，剩下的走 `get_accounts_page` */
+export interface AccountList {
+    accounts: Account[];
+    total: number;
+    truncated: boolean;
+    /** 增量加载游标，传给 `get_accounts_changed_since` */
+    cursor: string | null;
+    /** 名字已按演示模式打码 */
+    masked: boolean;
+    /** 各账号与本机 Codex 版本的兼容性，按 id */
+    compatibility: Record<string, CompatibilityVerdict>;
+}
+
+interface AccountPage {
+    items: Account[];
+    total: number;
+    offset: number;
+    limit: number;
+    cursor: string | null;
+    masked: boolean;
+    compatibility: Record<string, CompatibilityVerdict>;
+}
+
+interface AccountDelta {
+    changed: Account[];
+    deleted: string[];
+    cursor: string | null;
+    /** 游标太旧：changed 是完整列表 */
+    full_reload: boolean;
+    masked: boolean;
+}
+
+/** `get_account`：单个账号加派生字段 */
+export interface AccountDetail extends Account {
+    last_refresh: string | null;
+    /** 额度缓存距今秒数 */
+    quota_age_secs: number | null;
+    masked: boolean;
+}
+
+/** `get_account` 的错误：not_found 表示账号已在别处删除 */
+export type GetAccountError =
+    | { code: 'not_found'; id: string; in_trash: boolean }
+    | { code: 'unavailable'; message: string };
+
+const ACCOUNT_PAGE_SIZE = 200;
+
+/** 与后端 `Account::display_order` 一致：置顶 → 手动顺序 → 新建在前 */
+const byDisplayOrder = (a: Account, b: Account) =>
+    Number(!!b.pinned) - Number(!!a.pinned)
+    || (a.sort_index ?? Infinity) - (b.sort_index ?? Infinity)
+    || b.created_at.localeCompare(a.created_at)
+    || a.id.localeCompare(b.id);
+
+/** 取完整账号列表：get_accounts 被截断时按页补齐 */
+export async function fetchAllAccounts(): Promise<AccountList> {
+    const list = await invoke<AccountList>('get_accounts');
+    const accounts = [...list.accounts];
+    const compatibility = { ...list.compatibility };
+    while (accounts.length < list.total) {
+        const page = await invoke<AccountPage>('get_accounts_page', {
+            offset: accounts.length,
+            limit: ACCOUNT_PAGE_SIZE,
+        });
+        if (page.items.length === 0) break;
+        accounts.push(...page.items);
+        Object.assign(compatibility, page.compatibility);
+    }
+    // 分页期间有增删时可能重复，按 id 去重
+    const unique = [...new Map(accounts.map(a => [a.id, a])).values()];
+    return { ...list, accounts: unique, compatibility, truncated: false };
+}
+
+/** 把增量合并进当前列表 */
+function applyDelta(prev: Account[], delta: AccountDelta): Account[] {
+    const byId = new Map(prev.map(a => [a.id, a]));
+    delta.deleted.forEach(id => byId.delete(id));
+    delta.changed.forEach(a => byId.set(a.id, a));
+    return [...byId.values()].sort(byDisplayOrder);
+}
+
+/** 回收站里的账号（`get_trash`） */
+export interface TrashedAccount {
+    account: Account;
+    deleted_at: string;
+}
+
+export interface PlanChange {
+    old_plan: string;
+    new_plan: string;
+    changed_at: string;
+}
+
+/** `plan-changed` 事件负载 */
+export interface PlanChangedEvent {
+    account_id: string;
+    account_name: string;
+    old_plan: string;
+    new_plan: string;
+    old_display: string;
+    new_display: string;
+    downgrade: boolean;
+    changed_at: string;
+}
+
+export interface OAuthLoginStart {
+    auth_url: string;
+    port: number;
+}
+
+/** OAuth 配置（issuer / client）；内置的 id 为 openai-default */
+export interface AuthProfile {
+    id: string;
+    name: string;
+    issuer: string;
+    client_id: string;
+    authorize_endpoint?: string;
+    token_endpoint?: string;
+    extra_params: Record<string, string>;
+}
+
+export const DEFAULT_AUTH_PROFILE_ID = 'openai-default';
+
+type OAuthStartError =
+    | { kind: 'port_unavailable'; tried: { port: number; error: string; holder: string | null }[] }
+    | { kind: 'other'; message: string };
+
+/** 把 start_oauth_login 的结构化错误转成可读文案 */
+export function formatOAuthStartError(err: unknown): string {
+    const e = err as OAuthStartError | undefined;
+    if (e && typeof e === 'object' && 'kind' in e) {
+        if (e.kind === 'port_unavailable') {
+            const detail = e.tried
+                .map(t => `${t.port}${t.holder ? `（被 ${t.holder} 占用）` : ''}`)
+                .join('、');
+            return `OAuth 回调端口全部被占用：${detail}。请关闭占用进程后重试`;
+        }
+        return e.message;
+    }
+    return String(err);
 }
 
 /** 解析有效 kind：与 Rust 端 `Account::effective_kind()` 行为一致
@@ -103,19 +522,72 @@ export function useAccounts() {
     });
     const [loading, setLoading] = useState(true);
     const [error, setError] = useState<string | null>(null);
+    const [compatibility, setCompatibility] = useState<Record<string, CompatibilityVerdict>>({});
+    const [tokenStatuses, setTokenStatuses] = useState<Record<string, TokenStatus>>({});
+    const [integrityIssues, setIntegrityIssues] = useState<Record<string, IntegrityIssue[]>>({});
+    const [pendingUndo, setPendingUndo] = useState<UndoReceipt | null>(null);
+    const undoTimerRef = useRef<ReturnType<typeof setTimeout> | null>(null);
+    // 上一次加载的游标；有游标时只取增量
+    const syncRef = useRef<{ cursor: string | null; masked: boolean } | null>(null);
+
+    // 账号列表：首次全量，之后按游标增量合并；游标过期或打码状态变了再全量
+    const loadAccounts = useCallback(async () => {
+        const sync = syncRef.current;
+        // 过期状态随时间变化，每次加载都重新取一份
+        invoke<Record<string, TokenStatus>>('get_account_statuses')
+            .then(setTokenStatuses)
+            .catch(() => {});
+        // 导入 / 手改之后随时可能变，同样每次重取
+        invoke<Record<string, IntegrityIssue[]>>('verify_all_accounts')
+            .then(setIntegrityIssues)
+            .catch(() => {});
+        if (sync?.cursor) {
+            const delta = await invoke<AccountDelta>('get_accounts_changed_since', { cursor: sync.cursor });
+            if (!delta.full_reload && delta.masked === sync.masked) {
+                syncRef.current = { cursor: delta.cursor, masked: delta.masked };
+                if (delta.changed.length > 0 || delta.deleted.length > 0) {
+                    setAccounts(prev => applyDelta(prev, delta));
+                    // 增量不带兼容性，改过的账号重新判定一次（版本走缓存，不会重跑 codex）
+                    invoke<CodexCompatibilityReport>('get_codex_compatibility')
+                        .then(report => setCompatibility(report.accounts))
+                        .catch(() => {});
+                }
+                return;
+            }
+        }
+        const list = await fetchAllAccounts();
+        syncRef.current = { cursor: list.cursor, masked: list.masked };
+        setAccounts(list.accounts);
+        setCompatibility(list.compatibility ?? {});
+    }, []);
+
+    // 只刷新一个账号；已在别处删除时从列表里去掉
+    const reloadAccount = useCallback(async (id: string) => {
+        try {
+            const { last_refresh: _lastRefresh, quota_age_secs: _age, masked: _masked, ...account } =
+                await invoke<AccountDetail>('get_account', { id });
+            setAccounts(prev => prev.map(a => (a.id === id ? account : a)));
+        } catch (err) {
+            const e = err as GetAccountError;
+            if (e?.code === 'not_found') {
+                setAccounts(prev => prev.filter(a => a.id !== id));
+            } else {
+                setError(e?.code === 'unavailable' ? e.message : String(err));
+            }
+        }
+    }, []);
 
     // 加载账号和设置
     const loadData = useCallback(async () => {
         try {
             setError(null);
 
-            const [accountList, current, appSettings] = await Promise.all([
-                invoke<Account[]>('get_accounts'),
+            const [, current, appSettings] = await Promise.all([
+                loadAccounts(),
                 invoke<string | null>('get_current_account_id'),
                 invoke<AppSettings>('get_settings'),
             ]);
 
-            setAccounts(accountList);
             setCurrentId(current);
             setSettings(appSettings);
         } catch (err) {
@@ -123,7 +595,7 @@ export function useAccounts() {
         } finally {
             setLoading(false);
         }
-    }, []);
+    }, [loadAccounts]);
 
     // 初始加载
     useEffect(() => {
@@ -157,8 +629,8 @@ export function useAccounts() {
     const updateSettings = useCallback(async (newSettings: AppSettings) => {
         try {
             setError(null);
-            await invoke('update_settings', { settings: newSettings });
-            setSettings(newSettings);
+            const saved = await invoke<AppSettings>('update_settings', { settings: newSettings });
+            setSettings(saved);
         } catch (err) {
             setError(String(err));
             throw err;
@@ -167,12 +639,15 @@ export function useAccounts() {
 
     // ... 其他方法保持不变，但使用 loadData 替换 loadAccounts ...
 
-    // 导入当前账号
-    const importCurrent = useCallback(async (name: string, notes?: string) => {
+    // 导入当前账号；同一登录已在列表里时后端更新原账号，返回 true
+    const importCurrent = useCallback(async (name: string, notes?: string, force?: boolean) => {
         try {
             setError(null);
-            await invoke('import_current_account', { name, notes });
+            const imported = await invoke<{ updated: boolean; skipped?: string | null }>('import_current_account', { name, notes, force });
+            // 同一登录的已存账号受保护：没动它，按失败提示
+            if (imported.skipped) throw imported.skipped;
             await loadData();
+            return imported.updated;
         } catch (err) {
             setError(String(err));
             throw err;
@@ -183,15 +658,62 @@ export function useAccounts() {
     const switchTo = useCallback(async (id: string) => {
         try {
             setError(null);
-            await invoke('switch_account', { id });
+            const { value: result, warnings } = await invoke<Warned<SwitchResult>>('switch_account_v2', { id });
             setCurrentId(id);
             await loadData();
+            // 切号已成功，钩子失败只提示不回滚
+            const failedHooks = result.hooks.filter(h => !h.success);
+            if (failedHooks.length > 0) {
+                setError(`已切换，但切号钩子失败: ${failedHooks
+                    .map(h => `${h.name}（${h.timed_out ? '超时' : h.error || `退出码 ${h.exit_code}`}）`)
+                    .join('、')}`);
+            } else if (result.compatibility_warning) {
+                setError(`已切换，但${result.compatibility_warning}`);
+            } else if (warnings.length > 0) {
+                setError(`已切换，但${warnings.map(describeWarning).join('；')}`);
+            }
         } catch (err) {
             setError(String(err));
             throw err;
         }
     }, [loadData]);
 
+    // 切换设置里登记的其它 Codex 安装；默认安装的当前账号不变
+    const switchInInstall = useCallback(async (id: string, install: string) => {
+        try {
+            setError(null);
+            await invoke<Warned<SwitchResult>>('switch_account_v2', { id, install });
+            await loadData();
+        } catch (err) {
+            setError(String(err));
+            throw err;
+        }
+    }, [loadData]);
+
+    // 记下最近一次可撤销操作，到期自动收起
+    const offerUndo = useCallback((receipt: UndoReceipt) => {
+        if (undoTimerRef.current) clearTimeout(undoTimerRef.current);
+        setPendingUndo(receipt);
+        undoTimerRef.current = setTimeout(() => setPendingUndo(null), receipt.expires_in_secs * 1000);
+    }, []);
+
+    const dismissUndo = useCallback(() => {
+        if (undoTimerRef.current) clearTimeout(undoTimerRef.current);
+        setPendingUndo(null);
+    }, []);
+
+    const undoLast = useCallback(async () => {
+        if (!pendingUndo) return;
+        dismissUndo();
+        try {
+            setError(null);
+            await invoke('undo', { token: pendingUndo.undo_token });
+            await loadData();
+        } catch (err) {
+            setError(String(err));
+        }
+    }, [pendingUndo, dismissUndo, loadData]);
+
     // 删除账号
     // 注意：不在这里做 confirm —— 上层 UI (AccountList ConfirmModal / AccountCard 二次点击)
     // 已经承担确认职责；这里再弹 window.confirm 会变成双弹框。
@@ -201,13 +723,28 @@ export function useAccounts() {
             if (currentId === id) {
                 setCurrentId(null);
             }
-            await invoke('delete_account', { id });
+            offerUndo(await invoke<UndoReceipt>('delete_account', { id }));
+            await loadData();
+        } catch (err) {
+            setError(String(err));
+            throw err;
+        }
+    }, [loadData, currentId, offerUndo]);
+
+    // 批量删除：调用方已弹过确认框，这里先要一次性确认再删
+    const deleteAccounts = useCallback(async (ids: string[], allowCurrent: boolean) => {
+        try {
+            setError(null);
+            const ticket = await invoke<string>('request_delete_accounts_ticket', { ids });
+            const outcome = await invoke<BulkDeleteOutcome>('delete_accounts', { ids, ticket, allowCurrent });
+            if (outcome.undo) offerUndo(outcome.undo);
             await loadData();
+            return outcome;
         } catch (err) {
             setError(String(err));
             throw err;
         }
-    }, [loadData, currentId]);
+    }, [loadData, offerUndo]);
 
     // 更新账号
     const updateAccount = useCallback(async (id: string, name?: string, notes?: string) => {
@@ -221,27 +758,91 @@ export function useAccounts() {
         }
     }, [loadData]);
 
-    // 导出
-    const exportAccounts = useCallback(async () => {
+    // 导出；给了口令就导出加密信封，否则明文
+    const exportAccounts = useCallback(async (passphrase?: string) => {
         try {
-            return await invoke<string>('export_accounts');
+            return passphrase
+                ? await invoke<string>('export_accounts_encrypted', { passphrase })
+                : await invoke<string>('export_accounts');
         } catch (err) {
             setError(String(err));
             throw err;
         }
     }, []);
 
-    // 导入
-    const importAccounts = useCallback(async (json: string) => {
+    // 脱敏导出：不带 token，只用来给人看账号布局和额度，不能再导入
+    const exportAccountsRedacted = useCallback(async () => {
+        try {
+            return await invoke<string>('export_accounts_redacted');
+        } catch (err) {
+            setError(String(err));
+            throw err;
+        }
+    }, []);
+
+    // 账号库备份（导入前的和每日自动的），从新到旧
+    const listStoreBackups = useCallback(() => invoke<StoreBackup[]>('list_store_backups'), []);
+
+    // 从备份恢复：fileName 取自 listStoreBackups；默认整库替换（可撤销），merge 时并入现有账号
+    const restoreStoreBackup = useCallback(async (fileName: string, mode: ImportMode = 'replace') => {
         try {
             setError(null);
-            await invoke('import_accounts', { json });
+            const result = await invoke<ImportResult>('restore_store_backup', { fileName, mode });
+            if (result.undo) offerUndo(result.undo);
             await loadData();
+            return result;
         } catch (err) {
             setError(String(err));
             throw err;
         }
-    }, [loadData]);
+    }, [loadData, offerUndo]);
+
+    const detectExportFormat = useCallback(
+        (text: string) => invoke<ExportFormat>('detect_export_format', { text }),
+        [],
+    );
+
+    // 导入；加密信封需要口令，口令错误时后端报解密失败。默认整库替换，merge 时并入现有账号
+    const importAccounts = useCallback(async (
+        text: string,
+        passphrase?: string,
+        mode: ImportMode = 'replace',
+    ) => {
+        try {
+            setError(null);
+            const format = await invoke<ExportFormat>('detect_export_format', { text });
+            if (format === 'encrypted' && !passphrase) {
+                throw new Error('这是加密导出，请输入导出时设置的口令');
+            }
+            const result = format === 'encrypted'
+                ? await invoke<ImportResult>('import_accounts_encrypted', { payload: text, passphrase, mode })
+                : await invoke<ImportResult>('import_accounts', { json: text, mode });
+            if (result.undo) offerUndo(result.undo);
+            await loadData();
+            return result;
+        } catch (err) {
+            setError(String(err));
+            throw err;
+        }
+    }, [loadData, offerUndo]);
+
+    // 分批导入（合并），返回任务 id；进度 / 结果走 import-progress / import-complete 事件
+    const importAccountsAsync = useCallback(async (
+        json: string,
+        options?: { overwriteExisting?: boolean; batchSize?: number },
+    ) => {
+        try {
+            setError(null);
+            return await invoke<string>('import_accounts_async', { json, options });
+        } catch (err) {
+            setError(String(err));
+            throw err;
+        }
+    }, []);
+
+    const cancelImport = useCallback(async (jobId: string) => {
+        await invoke('cancel_import', { jobId });
+    }, []);
 
     // 检查 Codex 登录状态
     const checkCodexLogin = useCallback(async () => {
@@ -253,13 +854,15 @@ export function useAccounts() {
     }, []);
 
     // 开始 OAuth 登录；openBrowser=false 时只准备 URL + 启动监听，不打开默认浏览器
-    const startOAuthLogin = useCallback(async (openBrowser: boolean = true) => {
+    const startOAuthLogin = useCallback(async (openBrowser: boolean = true, profileId?: string) => {
         try {
             setError(null);
-            return await invoke<string>('start_oauth_login', { openBrowser });
+            const res = await invoke<OAuthLoginStart>('start_oauth_login', { openBrowser, profileId });
+            return res.auth_url;
         } catch (err) {
-            setError(String(err));
-            throw err;
+            const message = formatOAuthStartError(err);
+            setError(message);
+            throw message;
         }
     }, []);
 
@@ -289,17 +892,32 @@ export function useAccounts() {
 
     return {
         accounts,
+        compatibility,
+        tokenStatuses,
+        integrityIssues,
         currentId,
         settings,
         loading,
         error,
         refresh: loadData,
+        reloadAccount,
         importCurrent,
         switchTo,
+        switchInInstall,
         deleteAccount,
+        deleteAccounts,
+        pendingUndo,
+        undoLast,
+        dismissUndo,
         updateAccount,
         exportAccounts,
+        detectExportFormat,
+        exportAccountsRedacted,
         importAccounts,
+        listStoreBackups,
+        restoreStoreBackup,
+        importAccountsAsync,
+        cancelImport,
         checkCodexLogin,
         startOAuthLogin,
         finalizeOAuthLogin,
@@ -1,9 +1,11 @@
 import { useState, useEffect, useCallback } from 'react';
-import { invoke } from '@tauri-apps/api/core';
-import type { Account, RelayUsageCache } from './useAccounts';
+import { invoke } from '../ipc';
+import { fetchAllAccounts, type RelayUsageCache } from './useAccounts';
 
 export interface UsageDisplay {
     plan_type: string;
+    /** 套餐展示名 */
+    display_plan?: string;
     five_hour_used: number;
     five_hour_left: number;
     five_hour_reset: string;
@@ -14,6 +16,32 @@ export interface UsageDisplay {
     weekly_reset_at?: number;
     credits_balance: number | null;
     has_credits: boolean;
+    /** 5 小时窗口的绝对次数（接口给了才有） */
+    five_hour_counts?: WindowCounts;
+    /** 周窗口的绝对次数 */
+    weekly_counts?: WindowCounts;
+}
+
+/** 窗口的绝对请求次数；任意一个都可能缺 */
+export interface WindowCounts {
+    used?: number | null;
+    limit?: number | null;
+    remaining?: number | null;
+}
+
+/** 剩余次数：优先 remaining，否则 limit - used；都没有时返回 null */
+export function remainingCount(counts?: WindowCounts): number | null {
+    if (!counts) return null;
+    if (counts.remaining != null) return counts.remaining;
+    if (counts.limit != null && counts.used != null) return Math.max(0, counts.limit - counts.used);
+    return null;
+}
+
+/** 「剩余 37 次（21%）」，没有绝对次数时「剩余 21%」—— 与后端 usage::format_left 一致 */
+export function formatLeft(percent: number, counts?: WindowCounts): string {
+    const left = remainingCount(counts);
+    const pct = `${Math.round(percent)}%`;
+    return left == null ? `剩余 ${pct}` : `剩余 ${left} 次（${pct}）`;
 }
 
 /// Relay (中转账号) 没有 OpenAI 5h+周窗口模型，把 GLM 这类返回的百分比剩余值
@@ -54,7 +82,7 @@ export function useUsage() {
             }
             // Relay 账号：走专属 fetcher（GLM /api/monitor/usage/quota/limit 等），
             // 不调 OpenAI usage（那条会返回 RELAY_ACCOUNT 错误）。
-            const accounts = await invoke<Account[]>('get_accounts');
+            const { accounts } = await fetchAllAccounts();
             const acc = accounts.find(a => a.id === currentId);
             const isRelay = (acc?.kind ?? '').toLowerCase() === 'relay';
             if (isRelay) {
@@ -0,0 +1,33 @@
+import { invoke as tauriInvoke, InvokeArgs, InvokeOptions } from '@tauri-apps/api/core';
+
+/** 命令失败回报给错误中心之后派发的窗口事件，角标据此立即刷新 */
+export const ERROR_RECORDED_EVENT = 'error-recorded';
+
+// 错误中心自己的命令失败不再回报，避免递归
+const UNRECORDED = new Set(['record_command_error', 'get_recent_errors', 'get_badge_state']);
+
+function accountIdOf(args?: InvokeArgs): string | null {
+    if (!args || args instanceof ArrayBuffer || ArrayBuffer.isView(args)) return null;
+    const record = args as Record<string, unknown>;
+    for (const key of ['id', 'accountId']) {
+        if (typeof record[key] === 'string') return record[key] as string;
+    }
+    return null;
+}
+
+/**
+ * 所有命令都经过这里：失败时回报给后端错误中心（见 error_center.rs），再原样抛给调用方。
+ * 新加的命令只要从这里 import invoke 就自动覆盖。
+ */
+export async function invoke<T>(cmd: string, args?: InvokeArgs, options?: InvokeOptions): Promise<T> {
+    try {
+        return await tauriInvoke<T>(cmd, args, options);
+    } catch (e) {
+        if (!UNRECORDED.has(cmd)) {
+            tauriInvoke('record_command_error', { command: cmd, error: String(e), accountId: accountIdOf(args) })
+                .then(() => window.dispatchEvent(new Event(ERROR_RECORDED_EVENT)))
+                .catch(() => {});
+        }
+        throw e;
+    }
+}